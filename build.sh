@@ -10,16 +10,21 @@ if cargo --version &>/dev/null; then
   mkdir -p bin
   mv ./target/release/oxidate bin/
   mv ./target/release/ignite bin/
+  mv ./target/release/rustscript bin/
+  mv ./target/release/rustscript-lsp bin/
   echo "Build complete. Executables are in the bin directory."
 
   echo "Adding temporary aliases for executables..."
   CWD=$(pwd)
   alias oxidate="$CWD/bin/oxidate"
   alias ignite="$CWD/bin/ignite"
+  alias rustscript="$CWD/bin/rustscript"
+  alias rustscript-lsp="$CWD/bin/rustscript-lsp"
 
   echo "To use the executables, run the following commands:"
   echo "oxidate --help"
   echo "ignite --help"
+  echo "rustscript --help"
 
 else
   echo "Rust is not installed. Please install Rust to proceed."
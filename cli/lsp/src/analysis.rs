@@ -0,0 +1,398 @@
+use std::ops::Range;
+
+use lexer::Token;
+use parser::structs::Type;
+use rustscript::pipeline;
+use types::type_checker::{Env, TypeChecker};
+
+/// What introduces a binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Let,
+    Fn,
+    Param,
+}
+
+/// A binding introduced by a `let`, a function declaration or a function parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Def {
+    pub name: String,
+    pub kind: Kind,
+    /// Index of the token naming the binding.
+    tok: usize,
+    /// Index of the token starting the declaration, which is the name itself for parameters.
+    start: usize,
+    scope: usize,
+    /// Index of the first token the binding is visible from. Functions are visible in the whole of their scope.
+    visible_from: usize,
+    /// Byte range of the signature: up to the body of a function, and up to the value of a `let`.
+    sig: Range<usize>,
+}
+
+/// A token-based analysis of a source, which needs neither the source to parse nor the AST to carry spans,
+/// so it keeps working while the source is being edited.
+///
+/// Scopes follow the braces of the source. A name refers to the last binding of that name before it in the
+/// innermost scope that has one, where functions are hoisted and parameters are bound in the body of their function.
+pub struct Analysis<'src> {
+    src: &'src str,
+    toks: Vec<(Token, Range<usize>)>,
+    /// The scope of each token.
+    tok_scopes: Vec<usize>,
+    /// The parent of each scope, the top-level scope being the first.
+    scopes: Vec<Option<usize>>,
+    defs: Vec<Def>,
+    /// The types of the top-level bindings, if the program type checks.
+    types: Env,
+}
+
+impl<'src> Analysis<'src> {
+    pub fn new(src: &'src str) -> Analysis<'src> {
+        // Invalid tokens are reported as diagnostics, the rest of the source can still be analysed
+        let mut lexer = lexer::lex(src);
+        let mut toks = vec![];
+        while let Some(tok) = lexer.next() {
+            if let Ok(tok) = tok {
+                toks.push((tok, lexer.span()));
+            }
+        }
+
+        let mut analysis = Analysis {
+            src,
+            toks,
+            tok_scopes: vec![],
+            scopes: vec![None],
+            defs: vec![],
+            types: Env::new(),
+        };
+        analysis.collect_defs();
+
+        let mut envs = vec![];
+        if let Ok(program) = pipeline::parse(src) {
+            if TypeChecker::new(&program).type_check_in(&mut envs).is_ok() {
+                analysis.types = envs.pop().unwrap_or_default();
+            }
+        }
+
+        analysis
+    }
+
+    fn collect_defs(&mut self) {
+        let mut stack = vec![0];
+        // Parameters of the function declared last, bound once its body opens
+        let mut params: Vec<Def> = vec![];
+
+        for idx in 0..self.toks.len() {
+            let scope = *stack.last().expect("Top-level scope is never popped");
+
+            match &self.toks[idx].0 {
+                Token::OpenBrace => {
+                    self.scopes.push(Some(scope));
+                    let body = self.scopes.len() - 1;
+                    stack.push(body);
+
+                    for mut param in params.drain(..) {
+                        param.scope = body;
+                        param.visible_from = idx;
+                        self.defs.push(param);
+                    }
+                }
+                Token::CloseBrace if stack.len() > 1 => {
+                    stack.pop();
+                }
+                Token::Let => {
+                    if let Some(name) = self.ident(idx + 1) {
+                        let end = self.find_at_depth(idx + 1, |tok| matches!(tok, Token::Semi));
+                        let sig_end = self
+                            .find_at_depth(idx + 1, |tok| matches!(tok, Token::Eq | Token::Semi));
+
+                        self.defs.push(Def {
+                            name,
+                            kind: Kind::Let,
+                            tok: idx + 1,
+                            start: idx,
+                            scope,
+                            visible_from: end,
+                            sig: self.span_between(idx, sig_end),
+                        });
+                    }
+                }
+                Token::Fn => {
+                    if let Some(name) = self.ident(idx + 1) {
+                        let body =
+                            self.find_at_depth(idx + 1, |tok| matches!(tok, Token::OpenBrace));
+
+                        self.defs.push(Def {
+                            name,
+                            kind: Kind::Fn,
+                            tok: idx + 1,
+                            start: idx,
+                            scope,
+                            visible_from: 0,
+                            sig: self.span_between(idx, body),
+                        });
+
+                        params = self.params(idx + 2);
+                    }
+                }
+                _ => (),
+            }
+
+            self.tok_scopes.push(scope);
+        }
+    }
+
+    /// The name of the identifier at the index, if there is one.
+    fn ident(&self, idx: usize) -> Option<String> {
+        match self.toks.get(idx) {
+            Some((Token::Ident(name), _)) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    /// The index of the first token from `from` matching the predicate outside of any brackets opened
+    /// after `from`, or one past the last token if there is none.
+    fn find_at_depth(&self, from: usize, pred: impl Fn(&Token) -> bool) -> usize {
+        let mut depth = 0;
+
+        for (idx, (tok, _)) in self.toks.iter().enumerate().skip(from) {
+            if depth == 0 && pred(tok) {
+                return idx;
+            }
+
+            match tok {
+                Token::OpenParen | Token::OpenBracket | Token::OpenBrace => depth += 1,
+                Token::CloseParen | Token::CloseBracket | Token::CloseBrace if depth == 0 => {
+                    return idx
+                }
+                Token::CloseParen | Token::CloseBracket | Token::CloseBrace => depth -= 1,
+                _ => (),
+            }
+        }
+
+        self.toks.len()
+    }
+
+    /// The byte range from the start of the token at `start` to the end of the token before `end`.
+    fn span_between(&self, start: usize, end: usize) -> Range<usize> {
+        let last = end.max(start + 1) - 1;
+        self.toks[start].1.start..self.toks[last.min(self.toks.len() - 1)].1.end
+    }
+
+    /// The parameters in the parentheses starting at the index, each a name followed by a type annotation.
+    fn params(&self, open: usize) -> Vec<Def> {
+        if !matches!(self.toks.get(open), Some((Token::OpenParen, _))) {
+            return vec![];
+        }
+
+        let mut params = vec![];
+        let mut idx = open + 1;
+
+        while idx < self.toks.len() && !matches!(self.toks[idx].0, Token::CloseParen) {
+            let end = self.find_at_depth(idx, |tok| matches!(tok, Token::Comma));
+
+            if let (Some(name), Some((Token::Colon, _))) = (self.ident(idx), self.toks.get(idx + 1))
+            {
+                params.push(Def {
+                    name,
+                    kind: Kind::Param,
+                    tok: idx,
+                    start: idx,
+                    scope: 0,
+                    visible_from: 0,
+                    sig: self.span_between(idx, end),
+                });
+            }
+
+            // Anything but a comma ends the parameters
+            match self.toks.get(end) {
+                Some((Token::Comma, _)) => idx = end + 1,
+                _ => break,
+            }
+        }
+
+        params
+    }
+
+    /// The index of the identifier token at the byte offset, including its end as editors place the cursor there.
+    fn ident_at(&self, offset: usize) -> Option<usize> {
+        self.toks.iter().position(|(tok, span)| {
+            matches!(tok, Token::Ident(_)) && span.start <= offset && offset <= span.end
+        })
+    }
+
+    /// The binding the identifier at the byte offset refers to.
+    pub fn definition(&self, offset: usize) -> Option<&Def> {
+        let idx = self.ident_at(offset)?;
+
+        // Fields and methods are not bindings
+        if idx > 0 && matches!(self.toks[idx - 1].0, Token::Dot) {
+            return None;
+        }
+
+        if let Some(def) = self.defs.iter().find(|def| def.tok == idx) {
+            return Some(def);
+        }
+
+        let name = self.ident(idx)?;
+        let mut scope = Some(self.tok_scopes[idx]);
+
+        while let Some(current) = scope {
+            let mut candidates = self
+                .defs
+                .iter()
+                .filter(|def| def.scope == current && def.name == name);
+
+            let before = candidates
+                .clone()
+                .filter(|def| match def.kind {
+                    Kind::Fn => def.tok < idx,
+                    Kind::Let | Kind::Param => def.visible_from <= idx,
+                })
+                .max_by_key(|def| def.tok);
+
+            if let Some(def) = before.or_else(|| candidates.find(|def| def.kind == Kind::Fn)) {
+                return Some(def);
+            }
+
+            scope = self.scopes[current];
+        }
+
+        None
+    }
+
+    /// The byte range of the name of the binding.
+    pub fn span(&self, def: &Def) -> Range<usize> {
+        self.toks[def.tok].1.clone()
+    }
+
+    /// Markdown describing the binding the identifier at the byte offset refers to: its signature, with the
+    /// inferred type of a top-level `let` without an annotation, and the comments right above its declaration.
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let def = self.definition(offset)?;
+        let mut sig = self.src[def.sig.clone()]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let annotated = self.toks[def.tok + 1..]
+            .first()
+            .is_some_and(|(tok, _)| matches!(tok, Token::Colon));
+
+        if def.kind == Kind::Let && def.scope == 0 && !annotated {
+            match self.types.get(&def.name) {
+                Some(Type::Unitialised) | None => (),
+                Some(ty) => sig = format!("{}: {}", sig, ty),
+            }
+        }
+
+        let mut hover = format!("```rustscript\n{}\n```", sig);
+        let docs = self.docs(def);
+        if !docs.is_empty() {
+            hover = format!("{}\n\n{}", hover, docs.join("\n"));
+        }
+
+        Some(hover)
+    }
+
+    /// The lines of the comments on the lines right above the declaration.
+    fn docs(&self, def: &Def) -> Vec<String> {
+        if def.kind == Kind::Param {
+            return vec![];
+        }
+
+        let before = &self.src[..self.toks[def.start].1.start];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        if !before[line_start..].trim().is_empty() {
+            return vec![];
+        }
+
+        let mut docs: Vec<String> = before[..line_start]
+            .lines()
+            .rev()
+            .map(str::trim)
+            .take_while(|line| line.starts_with("//"))
+            .map(|line| line.trim_start_matches('/').trim().to_string())
+            .collect();
+        docs.reverse();
+
+        docs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The byte offset of the nth occurrence of the pattern.
+    fn offset(src: &str, pat: &str, nth: usize) -> usize {
+        src.match_indices(pat)
+            .nth(nth)
+            .expect("Pattern is in source")
+            .0
+    }
+
+    fn def_offset(src: &str, pat: &str, nth: usize) -> Option<usize> {
+        let analysis = Analysis::new(src);
+        analysis
+            .definition(offset(src, pat, nth))
+            .map(|def| analysis.span(def).start)
+    }
+
+    #[test]
+    fn test_definition_scopes() {
+        let src = "let x = 1;\nfn f(x: int) -> int { let y = x; y }\nlet x = x + 1;\nf(x)";
+
+        // The parameter shadows the top-level binding in the body
+        assert_eq!(def_offset(src, "x", 2), Some(offset(src, "x", 1)));
+        assert_eq!(def_offset(src, "y", 1), Some(offset(src, "y", 0)));
+
+        // A binding is not visible in its own value
+        assert_eq!(def_offset(src, "x", 4), Some(offset(src, "x", 0)));
+        assert_eq!(def_offset(src, "x", 5), Some(offset(src, "x", 3)));
+
+        // On the name of a binding
+        assert_eq!(def_offset(src, "x", 3), Some(offset(src, "x", 3)));
+    }
+
+    #[test]
+    fn test_definition_of_hoisted_fn() {
+        let src = "let h = spawn g();\nfn g() { h.x; z }";
+
+        assert_eq!(def_offset(src, "g", 0), Some(offset(src, "g", 1)));
+        // Neither fields nor unbound names have a definition
+        assert_eq!(def_offset(src, "x", 0), None);
+        assert_eq!(def_offset(src, "z", 0), None);
+    }
+
+    #[test]
+    fn test_definition_while_editing() {
+        let src = "let x = 1;\nfn f() { x + ";
+
+        assert_eq!(def_offset(src, "x", 1), Some(offset(src, "x", 0)));
+    }
+
+    #[test]
+    fn test_hover() {
+        let src = "// The answer\n// to everything\nlet x = 42;\nlet y: bool = true;\n\n// Doubles n\nfn double(n: int) -> int { n * 2 }\ndouble(x)";
+        let analysis = Analysis::new(src);
+
+        assert_eq!(
+            analysis.hover(offset(src, "x", 1)).unwrap(),
+            "```rustscript\nlet x: int\n```\n\nThe answer\nto everything"
+        );
+        assert_eq!(
+            analysis.hover(offset(src, "y:", 0)).unwrap(),
+            "```rustscript\nlet y: bool\n```"
+        );
+        assert_eq!(
+            analysis.hover(offset(src, "double", 1)).unwrap(),
+            "```rustscript\nfn double(n: int) -> int\n```\n\nDoubles n"
+        );
+        assert_eq!(
+            analysis.hover(offset(src, "n *", 0)).unwrap(),
+            "```rustscript\nn: int\n```"
+        );
+        assert_eq!(analysis.hover(offset(src, "42", 0)), None);
+    }
+}
@@ -0,0 +1,11 @@
+use anyhow::Result;
+use server::LspSession;
+
+mod analysis;
+mod server;
+
+/// Serve the Language Server Protocol over stdin and stdout, as editors start language servers.
+fn main() -> Result<()> {
+    let stdin = std::io::stdin();
+    LspSession::new(stdin.lock(), std::io::stdout()).run()
+}
@@ -0,0 +1,418 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    ops::Range,
+};
+
+use anyhow::{Error, Result};
+use rustscript::pipeline;
+use serde_json::{json, Value as Json};
+
+use crate::analysis::Analysis;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// An error response to a request.
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn invalid_params(message: impl ToString) -> ResponseError {
+        ResponseError {
+            code: INVALID_PARAMS,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A session of the Language Server Protocol with a single client, over JSON-RPC messages framed by
+/// Content-Length headers.
+///
+/// Documents are synced in full on every change. Each change is checked by the compiler pipeline, short of
+/// running it, and its errors published as diagnostics. Definitions and hovers come from an [`Analysis`]
+/// of the tokens, so they work on documents that do not parse.
+pub struct LspSession<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    /// The text of each open document by URI.
+    docs: HashMap<String, String>,
+    exited: bool,
+}
+
+impl<R: BufRead, W: Write> LspSession<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        LspSession {
+            reader,
+            writer,
+            docs: HashMap::new(),
+            exited: false,
+        }
+    }
+
+    /// Handle messages until the client sends the exit notification or closes the connection.
+    ///
+    /// # Errors
+    ///
+    /// If the connection fails or a message is malformed. Failed requests are reported to the client instead.
+    pub fn run(mut self) -> Result<()> {
+        while let Some(msg) = self.read_message()? {
+            let method = msg["method"].as_str().unwrap_or_default().to_string();
+
+            // Requests have an id to respond to, notifications do not
+            if msg.get("id").is_some() {
+                let res = self.handle_request(&method, &msg["params"]);
+                self.respond(&msg["id"], res)?;
+            } else {
+                self.handle_notification(&method, &msg["params"])?;
+            }
+
+            if self.exited {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Json) -> Result<Json, ResponseError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    // Full text of the document on every change
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                },
+                "serverInfo": { "name": "rustscript-lsp", "version": "0.1.0" },
+            })),
+            "shutdown" => Ok(Json::Null),
+            "textDocument/definition" => {
+                let (uri, src, offset) = self.position(params)?;
+                let analysis = Analysis::new(src);
+
+                Ok(match analysis.definition(offset) {
+                    Some(def) => json!({
+                        "uri": uri,
+                        "range": range(src, analysis.span(def)),
+                    }),
+                    None => Json::Null,
+                })
+            }
+            "textDocument/hover" => {
+                let (_, src, offset) = self.position(params)?;
+
+                Ok(match Analysis::new(src).hover(offset) {
+                    Some(hover) => json!({ "contents": { "kind": "markdown", "value": hover } }),
+                    None => Json::Null,
+                })
+            }
+            _ => Err(ResponseError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unsupported request {}", method),
+            }),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json) -> Result<()> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.docs.insert(uri.clone(), text.to_string());
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                // With full sync, the last change holds the whole text
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()) {
+                    let text = text["text"].as_str().unwrap_or_default();
+                    self.docs.insert(uri.clone(), text.to_string());
+                }
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(&uri);
+                self.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )
+            }
+            "exit" => {
+                self.exited = true;
+                Ok(())
+            }
+            // Including initialized, and notifications the client may send without checking the capabilities
+            _ => Ok(()),
+        }
+    }
+
+    /// The URI, text and byte offset of the position in a document given by the params of a request.
+    fn position<'a>(
+        &'a self,
+        params: &'a Json,
+    ) -> Result<(&'a str, &'a str, usize), ResponseError> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| ResponseError::invalid_params("Missing document"))?;
+        let src = self.docs.get(uri).ok_or_else(|| {
+            ResponseError::invalid_params(format!("Document {} is not open", uri))
+        })?;
+
+        let pos = &params["position"];
+        let (line, character) = match (pos["line"].as_u64(), pos["character"].as_u64()) {
+            (Some(line), Some(character)) => (line as usize, character as usize),
+            _ => return Err(ResponseError::invalid_params("Missing position")),
+        };
+
+        Ok((uri, src, offset(src, line, character)))
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> Result<()> {
+        let src = self.docs.get(uri).map(String::as_str).unwrap_or_default();
+
+        let diagnostics: Vec<Json> = match pipeline::compile(src, true) {
+            Ok(_) => vec![],
+            Err(diagnostic) => {
+                // Errors without a span, such as type errors, are placed at the start of the document
+                let range = range(src, diagnostic.span.clone().unwrap_or(0..0));
+
+                diagnostic
+                    .errors
+                    .iter()
+                    .map(|err| {
+                        json!({
+                            "range": range,
+                            // Error
+                            "severity": 1,
+                            "source": "rustscript",
+                            "code": diagnostic.phase.to_string(),
+                            "message": err,
+                        })
+                    })
+                    .collect()
+            }
+        };
+
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    fn read_message(&mut self) -> Result<Option<Json>> {
+        let mut len = None;
+
+        loop {
+            let mut header = String::new();
+            if self.reader.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(val) = header.strip_prefix("Content-Length:") {
+                len = Some(val.trim().parse::<usize>()?);
+            }
+        }
+
+        let len = len.ok_or_else(|| Error::msg("Message without a Content-Length header"))?;
+        let mut content = vec![0; len];
+        self.reader.read_exact(&mut content)?;
+
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    fn send(&mut self, mut msg: Json) -> Result<()> {
+        msg["jsonrpc"] = json!("2.0");
+
+        let content = msg.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    fn respond(&mut self, id: &Json, res: Result<Json, ResponseError>) -> Result<()> {
+        match res {
+            Ok(result) => self.send(json!({ "id": id, "result": result })),
+            Err(err) => self.send(json!({
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+            })),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> Result<()> {
+        self.send(json!({ "method": method, "params": params }))
+    }
+}
+
+/// The LSP position of a byte offset in the source, where characters are counted in UTF-16 code units.
+fn position(src: &str, offset: usize) -> Json {
+    let before = &src[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+fn range(src: &str, span: Range<usize>) -> Json {
+    json!({ "start": position(src, span.start), "end": position(src, span.end) })
+}
+
+/// The byte offset of an LSP position in the source, clamped to the end of its line.
+fn offset(src: &str, line: usize, character: usize) -> usize {
+    let line_start = match line {
+        0 => 0,
+        _ => match src.match_indices('\n').nth(line - 1) {
+            Some((idx, _)) => idx + 1,
+            None => return src.len(),
+        },
+    };
+
+    let mut units = 0;
+    for (idx, ch) in src[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return line_start + idx;
+        }
+        units += ch.len_utf16();
+    }
+
+    src.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn message(msg: Json) -> String {
+        let content = msg.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    fn request(id: u64, method: &str, params: Json) -> String {
+        message(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+
+    fn notification(method: &str, params: Json) -> String {
+        message(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn messages(out: &[u8]) -> Vec<Json> {
+        let mut reader = Cursor::new(out);
+        let mut session = LspSession::new(&mut reader, vec![]);
+        let mut msgs = vec![];
+        while let Some(msg) = session.read_message().unwrap() {
+            msgs.push(msg);
+        }
+        msgs
+    }
+
+    fn run(input: &[String]) -> Vec<Json> {
+        let input = input.concat();
+        let mut out = vec![];
+        LspSession::new(Cursor::new(input.as_bytes()), &mut out)
+            .run()
+            .unwrap();
+        messages(&out)
+    }
+
+    #[test]
+    fn test_lsp_session() {
+        let uri = "file:///test.rst";
+        let src = "let x = 2;\nlet y = x + 1;";
+
+        let msgs = run(&[
+            request(1, "initialize", json!({})),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": uri, "languageId": "rustscript", "version": 1, "text": src } }),
+            ),
+            request(
+                2,
+                "textDocument/definition",
+                json!({ "textDocument": { "uri": uri }, "position": { "line": 1, "character": 8 } }),
+            ),
+            request(
+                3,
+                "textDocument/hover",
+                json!({ "textDocument": { "uri": uri }, "position": { "line": 1, "character": 5 } }),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({ "textDocument": { "uri": uri, "version": 2 }, "contentChanges": [{ "text": "let x = ;" }] }),
+            ),
+            request(4, "textDocument/rename", json!({})),
+            request(5, "shutdown", json!(null)),
+            notification("exit", json!(null)),
+            // Not handled after exit
+            request(6, "shutdown", json!(null)),
+        ]);
+
+        assert_eq!(msgs.len(), 7);
+        assert_eq!(msgs[0]["id"], 1);
+        assert_eq!(msgs[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        assert_eq!(msgs[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(msgs[1]["params"]["diagnostics"], json!([]));
+
+        assert_eq!(
+            msgs[2]["result"],
+            json!({
+                "uri": uri,
+                "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } },
+            })
+        );
+        assert_eq!(
+            msgs[3]["result"]["contents"]["value"],
+            "```rustscript\nlet y: int\n```"
+        );
+
+        let diagnostics = &msgs[4]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["code"], "parse");
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({ "start": { "line": 0, "character": 8 }, "end": { "line": 0, "character": 9 } })
+        );
+
+        assert_eq!(msgs[5]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(msgs[6]["id"], 5);
+        assert_eq!(msgs[6]["result"], Json::Null);
+    }
+
+    #[test]
+    fn test_positions() {
+        let src = "let s = \"é😀\";\nlet t = s;";
+
+        // The emoji takes two UTF-16 code units and four bytes
+        let semi = src.find(';').unwrap();
+        assert_eq!(position(src, semi), json!({ "line": 0, "character": 13 }));
+        assert_eq!(offset(src, 0, 13), semi);
+        assert_eq!(offset(src, 1, 4), src.find("t =").unwrap());
+
+        // Past the end of a line or of the source
+        assert_eq!(offset(src, 0, 99), semi + 1);
+        assert_eq!(offset(src, 9, 0), src.len());
+    }
+}
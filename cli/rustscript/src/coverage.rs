@@ -0,0 +1,99 @@
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use compiler::debug_info::DebugInfo;
+
+/// The line coverage of a source file: for each line a statement starts on, how many times the statement ran.
+/// Lines without statements, e.g. comments and closing braces, are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineCoverage {
+    /// The number of times each line ran, by line number counted from 1.
+    pub lines: BTreeMap<usize, u64>,
+}
+
+impl LineCoverage {
+    /// The coverage of a run of the program compiled from the source, given the number of times each of its
+    /// instructions ran. A line runs as many times as the first instruction of the statements that start on it.
+    pub fn new(src: &str, debug_info: &DebugInfo, counts: &[u64]) -> LineCoverage {
+        let mut lines = BTreeMap::new();
+
+        for (pc, line) in debug_info.lines(src) {
+            let count = counts.get(pc).copied().unwrap_or_default();
+            let line_count = lines.entry(line).or_insert(0);
+            *line_count = count.max(*line_count);
+        }
+
+        LineCoverage { lines }
+    }
+
+    /// Add the counts of another run of the same source, e.g. of another test.
+    pub fn merge(&mut self, other: &LineCoverage) {
+        for (line, count) in other.lines.iter() {
+            *self.lines.entry(*line).or_insert(0) += count;
+        }
+    }
+
+    /// The number of lines that ran at least once.
+    pub fn covered(&self) -> usize {
+        self.lines.values().filter(|count| **count > 0).count()
+    }
+
+    /// The number of lines that can run.
+    pub fn total(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Write the coverage as a record of an lcov tracefile, as read by genhtml and coverage services.
+    pub fn write_lcov(&self, file: &Path, w: &mut impl Write) -> std::io::Result<()> {
+        writeln!(w, "TN:")?;
+        writeln!(w, "SF:{}", file.display())?;
+        for (line, count) in self.lines.iter() {
+            writeln!(w, "DA:{},{}", line, count)?;
+        }
+        writeln!(w, "LF:{}", self.total())?;
+        writeln!(w, "LH:{}", self.covered())?;
+        writeln!(w, "end_of_record")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compiler::compiler::Compiler;
+    use ignite::{run, Runtime};
+
+    use crate::pipeline;
+
+    use super::*;
+
+    #[test]
+    fn test_line_coverage() {
+        let src = r"let x = 2;
+fn double(n: int) -> int {
+    n * 2
+}
+fn unused() {
+    println(1);
+}
+// Comments are not lines that run
+let y = double(x) + double(x);
+y";
+        let program = pipeline::parse(src).unwrap();
+        let (instrs, debug_info) = Compiler::new(program).compile_with_debug_info().unwrap();
+        let mut rt = Runtime::new(instrs);
+        rt.record_coverage();
+        run(&mut rt).unwrap();
+
+        let coverage = LineCoverage::new(src, &debug_info, rt.coverage().unwrap());
+        let lines: Vec<(usize, u64)> = coverage.lines.clone().into_iter().collect();
+        assert_eq!(
+            lines,
+            vec![(1, 1), (2, 1), (3, 2), (5, 1), (6, 0), (9, 1), (10, 1)]
+        );
+        assert_eq!((coverage.covered(), coverage.total()), (6, 7));
+
+        let mut lcov = vec![];
+        coverage.write_lcov(Path::new("a.rst"), &mut lcov).unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+        assert!(lcov.starts_with("TN:\nSF:a.rst\nDA:1,1\n"));
+        assert!(lcov.ends_with("DA:10,1\nLF:7\nLH:6\nend_of_record\n"));
+    }
+}
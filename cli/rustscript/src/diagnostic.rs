@@ -0,0 +1,132 @@
+use std::{fmt::Display, ops::Range};
+
+use compiler::compiler::CompileError;
+use parser::structs::ParseError;
+use types::type_checker::TypeErrors;
+
+/// The phase of the pipeline an error occurred in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading the source file.
+    Io,
+    Lex,
+    Parse,
+    Type,
+    Compile,
+    Runtime,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            Phase::Io => "io",
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::Type => "type",
+            Phase::Compile => "compile",
+            Phase::Runtime => "runtime",
+        };
+
+        write!(f, "{}", phase)
+    }
+}
+
+/// The errors of a phase of the pipeline, reported the same way whichever phase they come from:
+/// one `error[phase]: message` line per error, followed by an indented note if there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub errors: Vec<String>,
+    /// More information on where the error occurred, e.g. the stack trace of a runtime error.
+    pub note: Option<String>,
+    /// The byte range in the source the error is at, if it is known.
+    pub span: Option<Range<usize>>,
+}
+
+impl Diagnostic {
+    pub fn new(phase: Phase, err: impl ToString) -> Diagnostic {
+        Diagnostic {
+            phase,
+            errors: vec![err.to_string()],
+            note: None,
+            span: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: impl ToString) -> Diagnostic {
+        self.note = Some(note.to_string());
+        self
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines: Vec<String> = self
+            .errors
+            .iter()
+            .map(|err| format!("error[{}]: {}", self.phase, err))
+            .collect();
+
+        if let Some(note) = &self.note {
+            lines.extend(note.lines().map(|line| format!("  {}", line)));
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        Diagnostic::new(Phase::Parse, err.msg())
+    }
+}
+
+impl From<TypeErrors> for Diagnostic {
+    fn from(errs: TypeErrors) -> Self {
+        Diagnostic {
+            phase: Phase::Type,
+            errors: errs.errs().to_vec(),
+            note: None,
+            span: None,
+        }
+    }
+}
+
+impl From<CompileError> for Diagnostic {
+    fn from(err: CompileError) -> Self {
+        Diagnostic::new(Phase::Compile, err.msg())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_display() {
+        let diagnostic = Diagnostic {
+            phase: Phase::Type,
+            errors: vec!["first".to_string(), "second".to_string()],
+            note: None,
+            span: None,
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[type]: first\nerror[type]: second"
+        );
+
+        let diagnostic = Diagnostic::new(Phase::Runtime, "Stack underflow")
+            .with_note("Runtime error in thread 1 at pc=0: POP\n    at <main>");
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[runtime]: Stack underflow\n  Runtime error in thread 1 at pc=0: POP\n      at <main>"
+        );
+    }
+}
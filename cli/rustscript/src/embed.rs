@@ -0,0 +1,609 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    io::{BufRead, Write},
+    rc::Rc,
+    time::Duration,
+};
+
+use ignite::{AsyncHostFn, HostFn, Runtime, RuntimeConfig};
+use parser::structs::FnTypeData;
+use types::type_checker::Env;
+
+pub use bytecode::Value;
+pub use ignite::{Capability, IntOverflow, PanicPolicy, WakeupPolicy};
+pub use parser::structs::Type;
+
+use crate::{
+    diagnostic::{Diagnostic, Phase},
+    repl::Session,
+};
+
+/// Run the source in a new VM with the default configuration, converting its final value to `T`.
+/// A program that ends in a statement has the value unit.
+///
+/// ```
+/// let sum: i64 = rustscript::eval("let x = 1; x + 2").unwrap();
+/// assert_eq!(sum, 3);
+/// ```
+///
+/// # Errors
+///
+/// If the source fails in any phase, or its value does not convert to `T`.
+pub fn eval<T>(src: &str) -> Result<T, Diagnostic>
+where
+    T: TryFrom<Value>,
+    T::Error: Display,
+{
+    Vm::new().eval(src)
+}
+
+/// A function of the host along with its type, for the type checker.
+/// Usually made by [`host_fn!`](crate::host_fn), which derives the type from the signature of a Rust function.
+pub struct HostFunction {
+    pub name: String,
+    pub ty: FnTypeData,
+    pub f: HostFn,
+}
+
+impl HostFunction {
+    pub fn new(
+        name: &str,
+        params: Vec<Type>,
+        ret_type: Type,
+        f: impl Fn(Vec<Value>) -> anyhow::Result<Value> + 'static,
+    ) -> HostFunction {
+        HostFunction {
+            name: name.to_string(),
+            ty: FnTypeData { params, ret_type },
+            f: Rc::new(f),
+        }
+    }
+}
+
+/// Rust types that have a counterpart in the language, which host functions can take and return.
+pub trait ScriptType: TryFrom<Value> + Into<Value> {
+    fn script_type() -> Type;
+}
+
+impl ScriptType for i64 {
+    fn script_type() -> Type {
+        Type::Int
+    }
+}
+
+impl ScriptType for f64 {
+    fn script_type() -> Type {
+        Type::Float
+    }
+}
+
+impl ScriptType for bool {
+    fn script_type() -> Type {
+        Type::Bool
+    }
+}
+
+impl ScriptType for String {
+    fn script_type() -> Type {
+        Type::String
+    }
+}
+
+impl ScriptType for () {
+    fn script_type() -> Type {
+        Type::Unit
+    }
+}
+
+/// Check that a host function was called with as many arguments as it has parameters.
+pub fn check_arity(name: &str, args: &[Value], arity: usize) -> anyhow::Result<()> {
+    if args.len() != arity {
+        anyhow::bail!(
+            "Function '{}' takes {} arguments but {} were supplied",
+            name,
+            arity,
+            args.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Turn a Rust function into a [`HostFunction`] to give to [`VmBuilder::host_fn`], generating the glue that checks
+/// the number of arguments and converts them from values, and the result back to a value.
+/// The parameter and return types must implement [`ScriptType`]: `i64`, `f64`, `bool`, `String` or `()`.
+///
+/// The macro defines a function of the same name returning the [`HostFunction`].
+///
+/// ```
+/// use rustscript::{host_fn, VmBuilder};
+///
+/// host_fn!(fn add(a: i64, b: i64) -> i64 { a + b });
+///
+/// let mut vm = VmBuilder::new().host_fn(add()).build().unwrap();
+/// assert_eq!(vm.eval::<i64>("add(1, 2)").unwrap(), 3);
+/// ```
+#[macro_export]
+macro_rules! host_fn {
+    ($vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $body:block) => {
+        $crate::host_fn!($vis fn $name($($arg: $ty),*) -> () $body);
+    };
+    ($vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty $body:block) => {
+        $vis fn $name() -> $crate::embed::HostFunction {
+            fn body($($arg: $ty),*) -> $ret $body
+
+            $crate::embed::HostFunction::new(
+                stringify!($name),
+                vec![$(<$ty as $crate::embed::ScriptType>::script_type()),*],
+                <$ret as $crate::embed::ScriptType>::script_type(),
+                |args: Vec<$crate::embed::Value>| {
+                    const ARITY: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+                    $crate::embed::check_arity(stringify!($name), &args, ARITY)?;
+
+                    #[allow(unused_mut, unused_variables)]
+                    let mut args = args.into_iter();
+                    $(
+                        let $arg: $ty = args
+                            .next()
+                            .expect("Arity was checked")
+                            .try_into()?;
+                    )*
+
+                    Ok(body($($arg),*).into())
+                },
+            )
+        }
+    };
+}
+
+/// Configures the limits and functions of a [`Vm`].
+///
+/// ```
+/// use rustscript::{embed::Type, Value, VmBuilder};
+///
+/// let mut vm = VmBuilder::new()
+///     .instr_budget(10_000)
+///     .function("double", vec![Type::Int], Type::Int, |args| {
+///         let n: i64 = args[0].clone().try_into()?;
+///         Ok(Value::Int(n * 2))
+///     })
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(vm.eval::<i64>("double(21)").unwrap(), 42);
+/// ```
+pub struct VmBuilder {
+    type_check: bool,
+    config: RuntimeConfig,
+    fns: Vec<HostFunction>,
+    async_fns: Vec<(String, FnTypeData, AsyncHostFn)>,
+    stdout: Option<Box<dyn Write>>,
+    stdin: Option<Box<dyn BufRead>>,
+}
+
+impl VmBuilder {
+    pub fn new() -> VmBuilder {
+        VmBuilder {
+            type_check: true,
+            config: RuntimeConfig::default(),
+            fns: vec![],
+            async_fns: vec![],
+            stdout: None,
+            stdin: None,
+        }
+    }
+
+    /// Whether sources are type checked before they run, which they are by default.
+    pub fn type_check(mut self, type_check: bool) -> VmBuilder {
+        self.type_check = type_check;
+        self
+    }
+
+    /// Limit the number of instructions the VM executes, over all the sources it runs.
+    pub fn instr_budget(mut self, budget: u64) -> VmBuilder {
+        self.config = self.config.instr_budget(budget);
+        self
+    }
+
+    pub fn max_call_depth(mut self, depth: usize) -> VmBuilder {
+        self.config = self.config.max_call_depth(depth);
+        self
+    }
+
+    pub fn max_operand_stack(mut self, size: usize) -> VmBuilder {
+        self.config = self.config.max_operand_stack(size);
+        self
+    }
+
+    pub fn time_quantum(mut self, quantum: Duration) -> VmBuilder {
+        self.config = self.config.time_quantum(quantum);
+        self
+    }
+
+    /// Limit the memory the variables of the sources may hold, in bytes, as estimated by the garbage collector.
+    pub fn max_memory(mut self, bytes: usize) -> VmBuilder {
+        self.config = self.config.max_memory(bytes);
+        self
+    }
+
+    /// What integer arithmetic does when its result does not fit in an i64, trapping by default.
+    pub fn int_overflow(mut self, int_overflow: IntOverflow) -> VmBuilder {
+        self.config = self.config.int_overflow(int_overflow);
+        self
+    }
+
+    /// What happens when a thread does not catch a runtime error, stopping the sources by default.
+    pub fn panic_policy(mut self, panic_policy: PanicPolicy) -> VmBuilder {
+        self.config = self.config.panic_policy(panic_policy);
+        self
+    }
+
+    /// Which thread blocked on a semaphore is woken up when it is posted, the first to block by default.
+    pub fn wakeup_policy(mut self, wakeup_policy: WakeupPolicy) -> VmBuilder {
+        self.config = self.config.wakeup_policy(wakeup_policy);
+        self
+    }
+
+    /// Deny the sources the builtins that need the capability, e.g. exit for [`Capability::Process`].
+    pub fn deny(mut self, capability: Capability) -> VmBuilder {
+        self.config = self.config.deny(capability);
+        self
+    }
+
+    /// Write what the sources print to the stream instead of stdout, e.g. to capture it.
+    pub fn stdout(mut self, stdout: impl Write + 'static) -> VmBuilder {
+        self.stdout = Some(Box::new(stdout));
+        self
+    }
+
+    /// Read the lines the sources read from the stream instead of stdin.
+    pub fn stdin(mut self, stdin: impl BufRead + 'static) -> VmBuilder {
+        self.stdin = Some(Box::new(stdin));
+        self
+    }
+
+    /// Provide a function of the host to the sources, which call it like a builtin.
+    /// The parameter and return types are checked by the type checker, the function receives one value per parameter.
+    pub fn function(
+        self,
+        name: &str,
+        params: Vec<Type>,
+        ret_type: Type,
+        f: impl Fn(Vec<Value>) -> anyhow::Result<Value> + 'static,
+    ) -> VmBuilder {
+        self.host_fn(HostFunction::new(name, params, ret_type, f))
+    }
+
+    /// Provide a function of the host made by [`host_fn!`](crate::host_fn) or [`HostFunction::new`].
+    pub fn host_fn(mut self, f: HostFunction) -> VmBuilder {
+        self.fns.push(f);
+        self
+    }
+
+    /// Provide an asynchronous function of the host, e.g. for IO, typed like [`VmBuilder::function`].
+    /// A thread of the source that calls it waits for the future to be ready, while the other threads keep running.
+    pub fn async_function<F>(
+        mut self,
+        name: &str,
+        params: Vec<Type>,
+        ret_type: Type,
+        f: impl Fn(Vec<Value>) -> F + 'static,
+    ) -> VmBuilder
+    where
+        F: Future<Output = anyhow::Result<Value>> + 'static,
+    {
+        let ty = FnTypeData { params, ret_type };
+        self.async_fns
+            .push((name.to_string(), ty, Rc::new(move |args| Box::pin(f(args)))));
+        self
+    }
+
+    /// # Errors
+    ///
+    /// If a function is named after a builtin or another function.
+    pub fn build(self) -> Result<Vm, Diagnostic> {
+        let mut rt = Runtime::with_config(vec![], self.config);
+        if let Some(stdout) = self.stdout {
+            rt = rt.with_stdout(stdout);
+        }
+        if let Some(stdin) = self.stdin {
+            rt = rt.with_stdin(stdin);
+        }
+
+        let mut types = Env::new();
+        for HostFunction { name, ty, f } in self.fns {
+            let arity = ty.params.len();
+            rt.register_fn(&name, arity, move |args| f(args))
+                .map_err(|err| Diagnostic::new(Phase::Runtime, err))?;
+            types.insert(name, Type::UserFn(Box::new(ty)));
+        }
+
+        for (name, ty, f) in self.async_fns {
+            let arity = ty.params.len();
+            rt.register_async_fn(&name, arity, move |args| f(args))
+                .map_err(|err| Diagnostic::new(Phase::Runtime, err))?;
+            types.insert(name, Type::UserFn(Box::new(ty)));
+        }
+
+        let type_envs = if types.is_empty() {
+            vec![]
+        } else {
+            vec![types]
+        };
+
+        Ok(Vm {
+            session: Session::with_runtime(rt, type_envs, self.type_check),
+        })
+    }
+}
+
+impl Default for VmBuilder {
+    fn default() -> Self {
+        VmBuilder::new()
+    }
+}
+
+/// A VM embedded in a Rust program. Sources run one after another in the same global scope,
+/// so later sources can use what earlier ones declared.
+pub struct Vm {
+    session: Session,
+}
+
+impl Vm {
+    /// A VM with the default configuration, type checking sources.
+    pub fn new() -> Vm {
+        Vm {
+            session: Session::new(true),
+        }
+    }
+
+    pub fn builder() -> VmBuilder {
+        VmBuilder::new()
+    }
+
+    /// Run the source, converting its final value to `T`. A source that ends in a statement has the value unit.
+    ///
+    /// # Errors
+    ///
+    /// If the source fails in any phase, in which case its bindings are dropped, or its value does not convert to `T`.
+    pub fn eval<T>(&mut self, src: &str) -> Result<T, Diagnostic>
+    where
+        T: TryFrom<Value>,
+        T::Error: Display,
+    {
+        let val = self.session.eval(src)?.unwrap_or(Value::Unit);
+
+        T::try_from(val).map_err(|err| Diagnostic::new(Phase::Runtime, err))
+    }
+
+    /// Call a function declared by an earlier source, converting its value to `T`,
+    /// e.g. a hook the source defines for the host to invoke.
+    ///
+    /// # Errors
+    ///
+    /// If no source has run, the call fails, or its value does not convert to `T`.
+    pub fn call<T>(&mut self, name: &str, args: Vec<Value>) -> Result<T, Diagnostic>
+    where
+        T: TryFrom<Value>,
+        T::Error: Display,
+    {
+        let val = self.session.call(name, args)?;
+
+        T::try_from(val).map_err(|err| Diagnostic::new(Phase::Runtime, err))
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval::<i64>("1 + 2"), Ok(3));
+        assert_eq!(eval::<String>("let s = \"hi\"; s"), Ok("hi".to_string()));
+        assert_eq!(eval::<()>("let x = 1;"), Ok(()));
+        assert_eq!(eval::<Value>("2.5"), Ok(Value::Float(2.5)));
+
+        let err = eval::<bool>("1").unwrap_err();
+        assert_eq!(err.phase, Phase::Runtime);
+        assert_eq!(err.errors, vec!["Type mismatch, expected Bool, found 1"]);
+
+        assert_eq!(eval::<i64>("1 + true").unwrap_err().phase, Phase::Type);
+    }
+
+    #[test]
+    fn test_vm_keeps_bindings() {
+        let mut vm = Vm::new();
+        vm.eval::<()>("fn square(n: int) -> int { n * n }").unwrap();
+        assert_eq!(vm.eval::<i64>("square(4)"), Ok(16));
+    }
+
+    #[test]
+    fn test_call() {
+        let mut vm = Vm::new();
+        vm.eval::<()>("fn on_event(n: int) -> int { n + 1 }")
+            .unwrap();
+        assert_eq!(vm.call::<i64>("on_event", vec![Value::Int(1)]), Ok(2));
+
+        let err = vm.call::<i64>("missing", vec![]).unwrap_err();
+        assert_eq!(err.errors, vec!["Unbounded name: missing"]);
+        assert_eq!(vm.eval::<i64>("on_event(2)"), Ok(3));
+    }
+
+    #[test]
+    fn test_io_streams() {
+        #[derive(Clone, Default)]
+        struct Output(Rc<std::cell::RefCell<Vec<u8>>>);
+
+        impl Write for Output {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let mut vm = VmBuilder::new()
+            .stdout(output.clone())
+            .stdin("world\n".as_bytes())
+            .build()
+            .unwrap();
+
+        vm.eval::<()>("print(\"hello \"); print(read_line());")
+            .unwrap();
+        assert_eq!(output.0.borrow().as_slice(), b"hello world\n");
+    }
+
+    #[test]
+    fn test_async_function() {
+        let mut vm = VmBuilder::new()
+            .async_function("fetch", vec![Type::Int], Type::Int, |args| {
+                let mut polled = false;
+                std::future::poll_fn(move |cx| {
+                    if !polled {
+                        polled = true;
+                        cx.waker().wake_by_ref();
+                        return std::task::Poll::Pending;
+                    }
+
+                    let n: i64 = args[0].clone().try_into()?;
+                    std::task::Poll::Ready(Ok(Value::Int(n * 2)))
+                })
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(vm.eval::<i64>("fetch(21)"), Ok(42));
+        assert_eq!(
+            vm.eval::<i64>("fetch(true)").unwrap_err().phase,
+            Phase::Type
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut vm = VmBuilder::new()
+            .instr_budget(1000)
+            .function("greet", vec![Type::String], Type::String, |args| {
+                Ok(format!("hello {}", args[0]).into())
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            vm.eval::<String>("greet(\"world\")"),
+            Ok("hello world".to_string())
+        );
+        assert_eq!(
+            vm.eval::<String>("greet(1)").unwrap_err().phase,
+            Phase::Type
+        );
+
+        let err = vm.eval::<()>("loop {}").unwrap_err();
+        assert!(err.errors[0].starts_with("Instruction budget exceeded"));
+
+        let err = VmBuilder::new()
+            .function("print", vec![], Type::Unit, |_| Ok(Value::Unit))
+            .build()
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(err.errors, vec!["Name already bound: print"]);
+    }
+
+    #[test]
+    fn test_int_overflow() {
+        let src = "let big = 9223372036854775807; big + 1";
+
+        let err = Vm::new().eval::<i64>(src).unwrap_err();
+        assert_eq!(err.phase, Phase::Runtime);
+        assert!(err.errors[0].starts_with("Integer overflow: 9223372036854775807 + 1"));
+
+        let mut vm = VmBuilder::new()
+            .int_overflow(IntOverflow::Wrap)
+            .build()
+            .unwrap();
+        assert_eq!(vm.eval::<i64>(src), Ok(i64::MIN));
+
+        let mut vm = VmBuilder::new()
+            .int_overflow(IntOverflow::Saturate)
+            .build()
+            .unwrap();
+        assert_eq!(vm.eval::<i64>(src), Ok(i64::MAX));
+        assert_eq!(vm.eval::<i64>("let small = -big - 1; -small"), Ok(i64::MAX));
+
+        let err = vm.eval::<i64>("1 / 0").unwrap_err();
+        assert_eq!(err.errors[0], "Division by zero: 1 / 0");
+    }
+
+    #[test]
+    fn test_deny() {
+        let mut vm = VmBuilder::new().deny(Capability::Random).build().unwrap();
+
+        let err = vm.eval::<i64>("random_int(0, 2)").unwrap_err();
+        assert_eq!(err.phase, Phase::Runtime);
+        assert_eq!(
+            err.errors[0],
+            "random_int needs the random capability, which the runtime is denied"
+        );
+        assert_eq!(vm.eval::<i64>("1 + 1"), Ok(2));
+    }
+
+    host_fn!(
+        fn scale(x: f64, by: i64) -> f64 {
+            x * by as f64
+        }
+    );
+    host_fn!(
+        fn shout(s: String) -> String {
+            s.to_uppercase()
+        }
+    );
+    host_fn!(
+        fn answer() -> i64 {
+            42
+        }
+    );
+    host_fn!(
+        fn ignore(_b: bool) {}
+    );
+
+    #[test]
+    fn test_host_fn_macro() {
+        let f = scale();
+        assert_eq!(f.name, "scale");
+        assert_eq!(f.ty.params, vec![Type::Float, Type::Int]);
+        assert_eq!(f.ty.ret_type, Type::Float);
+        assert_eq!(ignore().ty.ret_type, Type::Unit);
+
+        let err = (scale().f)(vec![Value::Float(1.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Function 'scale' takes 2 arguments but 1 were supplied"
+        );
+        let err = (scale().f)(vec![Value::Int(1), Value::Int(2)]).unwrap_err();
+        assert_eq!(err.to_string(), "Type mismatch, expected Float, found 1");
+
+        let mut vm = VmBuilder::new()
+            .host_fn(scale())
+            .host_fn(shout())
+            .host_fn(answer())
+            .host_fn(ignore())
+            .build()
+            .unwrap();
+
+        assert_eq!(vm.eval::<f64>("scale(1.5, 2)"), Ok(3.0));
+        assert_eq!(vm.eval::<String>("shout(\"hi\")"), Ok("HI".to_string()));
+        assert_eq!(vm.eval::<i64>("answer()"), Ok(42));
+        assert_eq!(vm.eval::<()>("ignore(true);"), Ok(()));
+    }
+}
@@ -0,0 +1,115 @@
+use clap::ValueEnum;
+
+use crate::{
+    diagnostic::{Diagnostic, Phase},
+    pipeline,
+};
+
+/// What to dump instead of running the program.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// The token stream of the lexer, one token per line with its position.
+    Tokens,
+    /// The parsed program, pretty-printed or as JSON.
+    Ast,
+    /// The compiled program, one instruction per line with its address.
+    Bytecode,
+}
+
+/// Dump the stage of the source given by `emit`, running the pipeline only as far as needed.
+pub fn emit(src: &str, emit: Emit, json: bool, type_check: bool) -> Result<String, Diagnostic> {
+    match emit {
+        Emit::Tokens => emit_tokens(src),
+        Emit::Ast => emit_ast(src, json),
+        Emit::Bytecode => emit_bytecode(src, type_check),
+    }
+}
+
+fn emit_tokens(src: &str) -> Result<String, Diagnostic> {
+    // Report an invalid token as the lexer phase would, rather than dumping a partial stream
+    pipeline::lex(src)?;
+
+    let mut lexer = lexer::lex(src);
+    let mut lines = vec![];
+
+    while let Some(tok) = lexer.next() {
+        let (line, line_start) = lexer.extras;
+        let tok = tok.expect("Source was lexed above");
+        lines.push(format!(
+            "{}:{} {:?}",
+            line + 1,
+            lexer.span().start - line_start + 1,
+            tok
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn emit_ast(src: &str, json: bool) -> Result<String, Diagnostic> {
+    let program = pipeline::parse(src)?;
+
+    if !json {
+        return Ok(program.to_string());
+    }
+
+    serde_json::to_string_pretty(&program).map_err(|err| Diagnostic::new(Phase::Parse, err))
+}
+
+fn emit_bytecode(src: &str, type_check: bool) -> Result<String, Diagnostic> {
+    let instrs = pipeline::compile(src, type_check)?;
+    let width = instrs.len().saturating_sub(1).to_string().len();
+
+    let lines: Vec<String> = instrs
+        .iter()
+        .enumerate()
+        .map(|(pc, instr)| format!("{:>width$} {:?}", pc, instr))
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_tokens() {
+        let tokens = emit("let x = 2;\nx", Emit::Tokens, false, true).unwrap();
+        assert_eq!(
+            tokens,
+            "1:1 Let\n1:5 Ident(\"x\")\n1:7 Eq\n1:9 Integer(2)\n1:10 Semi\n2:1 Ident(\"x\")"
+        );
+    }
+
+    #[test]
+    fn test_emit_ast() {
+        let json = emit("let x = 2;", Emit::Ast, true, true).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["symbols"], serde_json::json!(["x"]));
+        assert_eq!(json["decls"][0]["LetStmt"]["ident"], "x");
+
+        let err = emit("let x = ;", Emit::Ast, false, true).unwrap_err();
+        assert_eq!(err.phase, Phase::Parse);
+    }
+
+    #[test]
+    fn test_emit_bytecode() {
+        let bytecode = emit(
+            "let x = 2; x + 1; x; x; x; x; x;",
+            Emit::Bytecode,
+            false,
+            true,
+        )
+        .unwrap();
+        let lines: Vec<&str> = bytecode.lines().collect();
+
+        assert_eq!(lines[0], " 0 ENTERSCOPE([\"x\"])");
+        assert_eq!(lines[lines.len() - 1], "20 DONE");
+
+        // Does not type check with the flag off
+        let err = emit("let x: int = true;", Emit::Bytecode, false, true).unwrap_err();
+        assert_eq!(err.phase, Phase::Type);
+        assert!(emit("let x: int = true;", Emit::Bytecode, false, false).is_ok());
+    }
+}
@@ -0,0 +1,448 @@
+use lexer::Token;
+
+use crate::{
+    diagnostic::{Diagnostic, Phase},
+    pipeline,
+};
+
+const INDENT: &str = "    ";
+
+/// A comment in the gap between two tokens.
+struct Comment<'src> {
+    text: &'src str,
+    /// Whether the comment starts its own line, rather than trailing the token before it.
+    own_line: bool,
+    /// Whether there is a blank line before the comment.
+    blank_before: bool,
+}
+
+/// The whitespace and comments between two tokens.
+struct Gap<'src> {
+    comments: Vec<Comment<'src>>,
+    /// Whether there is a blank line between the last comment, or the previous token if there is none, and the next token.
+    blank_after: bool,
+}
+
+impl<'src> Gap<'src> {
+    fn new(text: &'src str) -> Gap<'src> {
+        let mut comments = vec![];
+        let mut newlines = 0;
+        let mut rest = text;
+
+        // The gap only holds whitespace and comments, which run to the end of the line
+        while let Some(start) = rest.find("//") {
+            let end = rest[start..]
+                .find('\n')
+                .map_or(rest.len(), |end| start + end);
+            let before = rest[..start].matches('\n').count();
+
+            comments.push(Comment {
+                text: rest[start..end].trim_end(),
+                own_line: before > 0 || newlines > 0,
+                blank_before: before > 1,
+            });
+
+            // The newline ending the comment is not part of the space between it and the next comment
+            newlines = 0;
+            rest = &rest[end..];
+            if let Some(stripped) = rest.strip_prefix('\n') {
+                newlines = 1;
+                rest = stripped;
+            }
+        }
+
+        Gap {
+            comments,
+            blank_after: newlines + rest.matches('\n').count() > 1,
+        }
+    }
+}
+
+/// How a token is separated from the token before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sep {
+    None,
+    Space,
+    Newline,
+}
+
+/// Lays out tokens and comments, tracking the indentation.
+struct Writer {
+    out: String,
+    depth: usize,
+    line_start: bool,
+}
+
+impl Writer {
+    fn newline(&mut self, blank: bool) {
+        if self.out.is_empty() {
+            return;
+        }
+
+        if !self.line_start {
+            self.out.push('\n');
+        }
+
+        if blank && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+
+        self.line_start = true;
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line_start {
+            self.out.push_str(&INDENT.repeat(self.depth));
+            self.line_start = false;
+        }
+
+        self.out.push_str(text);
+    }
+
+    /// Write the comments of a gap, returning whether the next token has to start a new line.
+    fn comments(&mut self, gap: &Gap, blank_allowed: bool) -> bool {
+        let mut newline = false;
+
+        for comment in gap.comments.iter() {
+            if comment.own_line || self.out.is_empty() {
+                self.newline(comment.blank_before && blank_allowed);
+            } else {
+                self.write(" ");
+            }
+
+            self.write(comment.text);
+            newline = true;
+        }
+
+        newline
+    }
+}
+
+/// Whether the token ends an operand, so that a following minus is binary.
+fn ends_operand(tok: &Token) -> bool {
+    matches!(
+        tok,
+        Token::Ident(_)
+            | Token::Integer(_)
+            | Token::Float(_)
+            | Token::Bool(_)
+            | Token::String(_)
+            | Token::CloseParen
+            | Token::CloseBrace
+            | Token::CloseBracket
+    )
+}
+
+fn is_binop(tok: &Token) -> bool {
+    matches!(
+        tok,
+        Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Percent
+            | Token::Caret
+            | Token::LogEq
+            | Token::Lt
+            | Token::Gt
+            | Token::Le
+            | Token::Ge
+            | Token::LogAnd
+            | Token::LogOr
+            | Token::And
+            | Token::Or
+            | Token::Eq
+    )
+}
+
+/// Whether the token is a prefix operator, given the token before it.
+fn is_unary(tok: &Token, prev: Option<&Token>) -> bool {
+    match tok {
+        Token::Bang => true,
+        Token::Minus => !prev.is_some_and(ends_operand),
+        _ => false,
+    }
+}
+
+/// How to separate `tok` from `prev`, where `prev_prev` is the token before `prev`.
+fn sep(prev: &Token, prev_prev: Option<&Token>, tok: &Token) -> Sep {
+    match (prev, tok) {
+        (Token::OpenBrace, Token::CloseBrace) => Sep::None,
+        (Token::Semi | Token::OpenBrace, _) | (_, Token::CloseBrace) => Sep::Newline,
+        // A block ends a statement, unless the expression it is part of goes on
+        (Token::CloseBrace, Token::Else | Token::Catch) => Sep::Space,
+        (Token::CloseBrace, Token::Semi | Token::CloseParen | Token::Comma | Token::Dot) => {
+            Sep::None
+        }
+        (Token::CloseBrace, next) if is_binop(next) => Sep::Space,
+        (Token::CloseBrace, _) => Sep::Newline,
+        // Attributes, as in #[test]
+        (Token::Pound, _) => Sep::None,
+        (Token::CloseBracket, Token::Fn) => Sep::Newline,
+        // Ranges, as in [x for x in 0..n], and range patterns, as in 1..=9
+        (prev, Token::DotDot | Token::DotDotEq) if ends_operand(prev) => Sep::None,
+        (_, Token::Semi | Token::Comma | Token::CloseParen | Token::CloseBracket)
+        | (_, Token::Dot | Token::Colon | Token::Question)
+        | (Token::OpenParen | Token::OpenBracket | Token::Dot, _)
+        | (Token::DotDot | Token::DotDotEq, _) => Sep::None,
+        // Calls, parameters of function types, join(h), spawn(f, x) and lock(m)
+        (
+            Token::Ident(_)
+            | Token::CloseParen
+            | Token::Fn
+            | Token::Join
+            | Token::Spawn
+            | Token::Lock
+            | Token::ReadLock,
+            Token::OpenParen,
+        ) => Sep::None,
+        // Indexes and slices
+        (Token::Ident(_) | Token::CloseParen | Token::CloseBracket, Token::OpenBracket) => {
+            Sep::None
+        }
+        (prev, _) if is_unary(prev, prev_prev) => Sep::None,
+        _ => Sep::Space,
+    }
+}
+
+/// Format the source in canonical style: one statement per line, blocks indented by four spaces,
+/// single spaces between tokens except around brackets, dots, commas and prefix operators.
+/// Comments are kept where they are, and runs of blank lines are collapsed into one.
+///
+/// # Errors
+///
+/// If the source does not parse, or if formatting would change the parsed program.
+pub fn format(src: &str) -> Result<String, Diagnostic> {
+    let program = pipeline::parse(src)?;
+
+    let mut lexer = lexer::lex(src);
+    let mut toks: Vec<(Token, &str)> = vec![];
+    let mut gaps: Vec<Gap> = vec![];
+    // The lexer skips the shebang line, which is kept as it is
+    let shebang = src
+        .starts_with("#!")
+        .then(|| src.lines().next().unwrap_or_default());
+    let mut end = shebang.map_or(0, str::len);
+    // Number of unclosed angle brackets of Option<T> and Result<T, E>, which are written without spaces
+    let mut generics = 0;
+
+    while let Some(tok) = lexer.next() {
+        let span = lexer.span();
+        gaps.push(Gap::new(&src[end..span.start]));
+        toks.push((tok.expect("Source was lexed when parsing"), lexer.slice()));
+        end = span.end;
+    }
+    let last_gap = Gap::new(&src[end..]);
+
+    let mut w = Writer {
+        out: shebang.map_or(String::new(), |shebang| format!("{}\n", shebang)),
+        depth: 0,
+        line_start: true,
+    };
+
+    for (i, ((tok, text), gap)) in toks.iter().zip(gaps.iter()).enumerate() {
+        let prev = i.checked_sub(1).map(|i| &toks[i].0);
+        let prev_prev = i.checked_sub(2).map(|i| &toks[i].0);
+
+        // Blank lines are not kept at the start or end of a block
+        let blank_allowed =
+            !matches!(prev, Some(Token::OpenBrace)) && !matches!(tok, Token::CloseBrace);
+        // Comments before the end of a block are indented with its body
+        let after_comment = w.comments(gap, blank_allowed);
+
+        if matches!(tok, Token::CloseBrace) {
+            w.depth = w.depth.saturating_sub(1);
+        }
+
+        let generic_open = matches!(tok, Token::Lt)
+            && matches!(prev, Some(Token::Ident(id)) if id == "Option" || id == "Result");
+        let generic_close = matches!(tok, Token::Gt) && generics > 0;
+        let after_generic_open = matches!(prev, Some(Token::Lt))
+            && matches!(prev_prev, Some(Token::Ident(id)) if id == "Option" || id == "Result");
+        if generic_open {
+            generics += 1;
+        } else if generic_close {
+            generics -= 1;
+        }
+
+        match prev.map(|prev| sep(prev, prev_prev, tok)) {
+            _ if after_comment => w.newline(gap.blank_after && blank_allowed),
+            _ if generic_open || generic_close || after_generic_open => (),
+            Some(Sep::Newline) => w.newline(gap.blank_after && blank_allowed),
+            Some(Sep::Space) => w.write(" "),
+            Some(Sep::None) | None => (),
+        }
+
+        w.write(text);
+
+        if matches!(tok, Token::OpenBrace) {
+            w.depth += 1;
+        }
+    }
+
+    w.comments(&last_gap, true);
+    let mut out = w.out;
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+
+    // The pretty-printed programs are equal if and only if formatting only changed the layout
+    let formatted = pipeline::parse(&out)?;
+    if formatted.to_string() != program.to_string() {
+        return Err(Diagnostic::new(
+            Phase::Parse,
+            "Formatting would change the meaning of the program",
+        ));
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_format(src: &str, exp: &str) {
+        let formatted = format(src).expect("Should format");
+        assert_eq!(formatted, exp);
+        assert_eq!(format(&formatted).expect("Should format"), exp);
+    }
+
+    #[test]
+    fn test_format_layout() {
+        test_format(
+            "let x:int=2;fn f(n:int)->int{if(n>1){-n}else{!true;n*x}}let y=f( x )+-1;",
+            r"let x: int = 2;
+fn f(n: int) -> int {
+    if (n > 1) {
+        -n
+    } else {
+        !true;
+        n * x
+    }
+}
+let y = f(x) + -1;
+",
+        );
+
+        test_format(
+            "let h = spawn f();join h;loop x<3 { x = x-1; break; } select { a => { 2 } b => {} }",
+            r"let h = spawn f();
+join h;
+loop x < 3 {
+    x = x - 1;
+    break;
+}
+select {
+    a => {
+        2
+    }
+    b => {}
+}
+",
+        );
+
+        test_format(
+            "let h=spawn { let y = x*2; y };join(h)",
+            "let h = spawn {\n    let y = x * 2;\n    y\n};\njoin(h)\n",
+        );
+        test_format("let h = spawn (f, 1);", "let h = spawn(f, 1);\n");
+
+        test_format(
+            "lock (m) { x = x+1; } read_lock (rw) { x }",
+            "lock(m) {\n    x = x + 1;\n}\nread_lock(rw) {\n    x\n}\n",
+        );
+
+        test_format(
+            "# [ test ]  fn t() { assert(true); }",
+            "#[test]\nfn t() {\n    assert(true);\n}\n",
+        );
+
+        test_format(
+            "let f: fn(int) -> bool = g; h.is_finished()",
+            "let f: fn(int) -> bool = g;\nh.is_finished()\n",
+        );
+
+        test_format(
+            "fn f(r : Result < Option < int >, str >) -> Option<int> { let x = g(r) ?; Some(x < 2) }",
+            "fn f(r: Result<Option<int>, str>) -> Option<int> {\n    let x = g(r)?;\n    Some(x < 2)\n}\n",
+        );
+
+        test_format(
+            "let x = try { f() } catch e { 0 } + 1;",
+            "let x = try {\n    f()\n} catch e {\n    0\n} + 1;\n",
+        );
+
+        test_format(
+            "struct P{x:int,y:int} let q=P{x:1,..p};q.x=2;",
+            "struct P {\n    x: int, y: int\n}\nlet q = P {\n    x: 1, ..p\n};\nq.x = 2;\n",
+        );
+
+        test_format(
+            "let a=[ x*2 for x in 0 .. n+1 if x%2==0 ];[[1,2],[]]",
+            "let a = [x * 2 for x in 0..n + 1 if x % 2 == 0];\n[[1, 2], []]\n",
+        );
+
+        test_format(
+            "let b=a [1 ..n]; s[ ..2 ]+f(x) [0]",
+            "let b = a[1..n];\ns[..2] + f(x)[0]\n",
+        );
+
+        test_format(
+            "match x { n@1 ..= 9|-3..=-1 => { n } _ => { 0 } }",
+            "match x {\n    n @ 1..=9 | -3..=-1 => {\n        n\n    }\n    _ => {\n        0\n    }\n}\n",
+        );
+
+        test_format(
+            "let y='a :loop { for x in xs { break 'a x+1; } break 'a 0; };",
+            "let y = 'a: loop {\n    for x in xs {\n        break 'a x + 1;\n    }\n    break 'a 0;\n};\n",
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        test_format(
+            "// header\n\n\n\nlet x = 2;   // trailing\nfn f() {\n\n  // inside\n  x\n\n}\n\n\nf() // the end",
+            r"// header
+
+let x = 2; // trailing
+fn f() {
+    // inside
+    x
+}
+
+f() // the end
+",
+        );
+    }
+
+    #[test]
+    fn test_format_shebang() {
+        test_format(
+            "#!/usr/bin/env rustscript\n\nlet x=2;",
+            "#!/usr/bin/env rustscript\nlet x = 2;\n",
+        );
+        test_format("#!/usr/bin/env rustscript", "#!/usr/bin/env rustscript\n");
+    }
+
+    #[test]
+    fn test_format_comment_at_end_of_block() {
+        test_format(
+            "fn f() {\n    x\n    // end\n}\n",
+            "fn f() {\n    x\n    // end\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_format_strings_and_floats() {
+        test_format(
+            r#"println("a  \"b\"");let x=.5;"#,
+            "println(\"a  \\\"b\\\"\");\nlet x = .5;\n",
+        );
+    }
+
+    #[test]
+    fn test_format_parse_error() {
+        assert_eq!(format("let x = ;").unwrap_err().phase, Phase::Parse);
+    }
+}
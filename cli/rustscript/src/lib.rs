@@ -0,0 +1,13 @@
+pub mod coverage;
+pub mod diagnostic;
+pub mod embed;
+pub mod emit;
+pub mod fmt;
+pub mod objdump;
+pub mod pipeline;
+pub mod project;
+pub mod repl;
+pub mod test_runner;
+pub mod watch;
+
+pub use embed::{eval, Value, Vm, VmBuilder};
@@ -0,0 +1,597 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bytecode::{
+    builtin, write_bytecode_compressed, write_bytecode_metadata, Compression, O2_VERSION,
+};
+use clap::{Parser, Subcommand};
+use compiler::{optimize::OptArgs, transpile};
+use ignite::{
+    interrupt_on_ctrl_c, step, Runtime, RuntimeArgs, RuntimeConfig, VmError, INTERRUPTED_EXIT_CODE,
+};
+use rustscript::{
+    diagnostic::{Diagnostic, Phase},
+    emit::{self, Emit},
+    fmt, objdump, pipeline, project,
+    repl::rustscript_repl,
+    test_runner,
+    watch::Watcher,
+};
+
+/// How often the file is checked for changes in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Parser, Debug)]
+#[command(name = "RustScript")]
+#[command(version = "0.1.0")]
+#[command(about = "Compile and run RustScript programs", long_about = None)]
+#[command(subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// File containing RustScript code. Must have extension .rst
+    #[arg(required = true)]
+    file: Option<String>,
+
+    /// If present, does not type check
+    #[arg(short)]
+    notype: bool,
+
+    /// Dump the tokens, AST or bytecode of the program instead of running it.
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+
+    /// Dump the AST as JSON rather than pretty-printed. Only used with --emit=ast.
+    #[arg(long)]
+    json: bool,
+
+    /// Run the file again each time it is saved, until interrupted.
+    #[arg(long, conflicts_with = "emit")]
+    watch: bool,
+
+    #[command(flatten)]
+    runtime: RuntimeArgs,
+
+    #[command(flatten)]
+    opt: OptArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start an interactive session, where bindings of earlier inputs can be used in later ones.
+    Repl,
+    /// Link the modules of the project described by script.toml into a single .o2 file.
+    Build {
+        /// Root directory of the project, holding script.toml. Defaults to the current directory.
+        dir: Option<String>,
+    },
+    /// Rewrite .rst files in canonical style. Directories are searched recursively.
+    Fmt {
+        /// Files or directories to format.
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Do not rewrite the files, only list those that are not formatted, failing if there are any.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Print the header, string table, constants, functions and disassembly of a compiled .o2 file.
+    Objdump {
+        /// File containing compiled RustScript bytecode. Must have extension .o2
+        file: String,
+    },
+    /// Rewrite a compiled .o2 file of an older version of the format in the current version.
+    Upgrade {
+        /// File containing compiled RustScript bytecode. Must have extension .o2
+        file: String,
+
+        /// Where to write the upgraded file. Defaults to rewriting the file in place.
+        #[arg(short, long)]
+        out: Option<String>,
+
+        /// How to compress the program: none, deflate or zstd. Defaults to keeping the compression of the file.
+        #[arg(long)]
+        compress: Option<Compression>,
+    },
+    /// Write a Cargo package with a Rust program that runs the file, to be built into a native binary with cargo.
+    /// Only programs of a single thread using ints, floats, bools, strings, arrays and functions can be transpiled.
+    Transpile {
+        /// File containing RustScript code. Must have extension .rst
+        file: String,
+
+        /// Directory of the package, created if it doesn't exist. Defaults to the name of the file without the extension.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Run the functions marked with #[test] in .rst files, each in a fresh runtime. Directories are searched recursively.
+    Test {
+        /// Files or directories to test.
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Report the lines of each file the tests ran, and write them as an lcov tracefile to FILE, lcov.info by default.
+        #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "lcov.info")]
+        coverage: Option<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Command::Repl) => {
+            return match rustscript_repl(!args.notype) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Build { dir }) => {
+            let dir = dir.unwrap_or_else(|| ".".to_string());
+            return match project::build(Path::new(&dir), !args.notype) {
+                Ok(output) => {
+                    println!("Built {}", output.display());
+                    ExitCode::SUCCESS
+                }
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Fmt { paths, check }) => return fmt_paths(&paths, check),
+        Some(Command::Test { paths, coverage }) => {
+            return test_paths(&paths, !args.notype, coverage.as_deref())
+        }
+        Some(Command::Objdump { file }) => {
+            return match objdump_file(&file) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Upgrade {
+            file,
+            out,
+            compress,
+        }) => {
+            return match upgrade_file(&file, out, compress) {
+                Ok(msg) => {
+                    println!("{}", msg);
+                    ExitCode::SUCCESS
+                }
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Transpile { file, out }) => {
+            return match transpile_file(&file, out, !args.notype, &args.opt) {
+                Ok(dir) => {
+                    println!(
+                        "Transpiled {} to {}, build it there with `cargo build --release`",
+                        file,
+                        dir.display()
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        None => (),
+    }
+
+    let file = args.file.expect("File is required without a subcommand");
+
+    if args.watch {
+        watch_file(&file, !args.notype, &args.runtime.config());
+    }
+
+    let res = match args.emit {
+        Some(emit) => emit_file(&file, emit, args.json, !args.notype).map(|()| ExitCode::SUCCESS),
+        None => run_file(&file, !args.notype, &args.runtime, &args.opt),
+    };
+
+    match res {
+        Ok(code) => code,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Compile the file and run it on a new runtime with the configuration, printing the final value of the program if there is one.
+/// The program is optimized and its context switches are recorded or replayed as the flags ask.
+/// Returns the status the program exited with, which is success unless it called exit or was stopped by Ctrl-C.
+fn run_file(
+    file: &str,
+    type_check: bool,
+    args: &RuntimeArgs,
+    opt: &OptArgs,
+) -> Result<ExitCode, Diagnostic> {
+    let src = pipeline::read_source(file)?;
+    let mut instrs = pipeline::compile(&src, type_check)?;
+    opt.optimize(&mut instrs);
+
+    let mut rt = Runtime::with_config(instrs, args.config());
+    rt.set_interrupt_flag(interrupt_on_ctrl_c());
+    let switch_log_error = |err: VmError| pipeline::runtime_error(err.into());
+    args.start_switch_log(&mut rt).map_err(switch_log_error)?;
+
+    let res = pipeline::execute(&mut rt);
+    args.save_switch_log(&rt).map_err(switch_log_error)?;
+
+    let val = match res {
+        // The diagnostic of the interruption gives the stack trace of where the program was stopped
+        Err(diagnostic) if rt.interrupted() => {
+            eprintln!("{}", diagnostic);
+            return Ok(ExitCode::from(INTERRUPTED_EXIT_CODE));
+        }
+        res => res?,
+    };
+
+    if let Some(code) = rt.exit_code {
+        // Statuses are truncated to a byte, as by the exit of a process
+        return Ok(ExitCode::from(code as u8));
+    }
+
+    if let Some(val) = val {
+        builtin::println_impl(&val);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Run the file each time it is saved, with a header before each run and the diagnostics of failed runs.
+/// If the file is saved while it is running, its functions are reloaded into the run, see [`run_watched`].
+fn watch_file(file: &str, type_check: bool, config: &RuntimeConfig) -> ! {
+    let mut watcher = Watcher::new(file);
+    println!("Watching {} for changes. Press Ctrl-C to stop.", file);
+    let interrupt = interrupt_on_ctrl_c();
+
+    loop {
+        // Ctrl-C stops a run in progress, and then watching
+        if interrupt.load(Ordering::Relaxed) {
+            std::process::exit(INTERRUPTED_EXIT_CODE.into());
+        }
+
+        let mut run = watcher.changed();
+        while run {
+            println!();
+            println!("[watch] running {}", file);
+
+            run = match run_watched(file, type_check, config, &mut watcher) {
+                Ok(restart) => {
+                    if !restart {
+                        println!("[watch] finished");
+                    }
+                    restart
+                }
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    false
+                }
+            };
+        }
+
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Compile the file and run it on a new runtime, printing the final value of the program if there is one.
+/// Each time the file is saved during the run, its new version is hot reloaded: the functions it declares
+/// run their new code from their next call on. A version that does not compile is skipped.
+/// Returns whether the run has to be restarted, as a version changed the top-level bindings of the program.
+fn run_watched(
+    file: &str,
+    type_check: bool,
+    config: &RuntimeConfig,
+    watcher: &mut Watcher,
+) -> Result<bool, Diagnostic> {
+    let src = pipeline::read_source(file)?;
+    let (instrs, mut types) = pipeline::compile_with_types(&src, type_check)?;
+
+    let mut rt = Runtime::with_config(instrs, config.clone());
+    rt.set_interrupt_flag(interrupt_on_ctrl_c());
+    let mut checked = Instant::now();
+
+    while !rt.is_done() {
+        step(&mut rt).map_err(pipeline::runtime_error)?;
+
+        if checked.elapsed() < WATCH_INTERVAL {
+            continue;
+        }
+        checked = Instant::now();
+
+        if !watcher.changed() {
+            continue;
+        }
+
+        let compiled = pipeline::read_source(file)
+            .and_then(|src| pipeline::compile_with_types(&src, type_check));
+        let (instrs, new_types) = match compiled {
+            Ok(compiled) => compiled,
+            Err(diagnostic) => {
+                eprintln!("{}", diagnostic);
+                continue;
+            }
+        };
+
+        // Without the types of both versions, the functions can't be checked to be called as before
+        let reloaded = match (&types, &new_types) {
+            (Some(running), Some(new)) => rt.hot_reload_typed(instrs, running, new),
+            _ => {
+                let msg = "the program doesn't type check".to_string();
+                Err(VmError::HotReload(msg).into())
+            }
+        };
+
+        match reloaded {
+            Ok(_) => {
+                types = new_types;
+                println!("[watch] reloaded {}", file);
+            }
+            Err(err) => {
+                println!("[watch] restarting {}: {}", file, err);
+                return Ok(true);
+            }
+        }
+    }
+
+    if let Some(val) = rt.current_thread.operand_stack.last() {
+        builtin::println_impl(val);
+    }
+
+    Ok(false)
+}
+
+/// Dump the stage of the file given by `emit` to stdout, without running it.
+fn emit_file(file: &str, emit: Emit, json: bool, type_check: bool) -> Result<(), Diagnostic> {
+    let src = pipeline::read_source(file)?;
+    println!("{}", emit::emit(&src, emit, json, type_check)?);
+
+    Ok(())
+}
+
+/// Read the compiled .o2 file.
+fn read_object_file(file: &str) -> Result<Vec<u8>, Diagnostic> {
+    let path = Path::new(file);
+    if path.extension().is_none_or(|ext| ext != "o2") {
+        let err = format!("File {} does not have extension .o2", file);
+        return Err(Diagnostic::new(Phase::Io, err));
+    }
+
+    std::fs::read(path).map_err(|err| Diagnostic::new(Phase::Io, format!("{}: {}", file, err)))
+}
+
+/// Print the description of the compiled .o2 file to stdout.
+fn objdump_file(file: &str) -> Result<(), Diagnostic> {
+    let bytes = read_object_file(file)?;
+    println!("{}", objdump::objdump(&bytes)?);
+
+    Ok(())
+}
+
+/// Rewrite the compiled .o2 file in the current version of the format, to `out` or in place,
+/// compressing the program with `compress` if given. Returns what was done.
+fn upgrade_file(
+    file: &str,
+    out: Option<String>,
+    compress: Option<Compression>,
+) -> Result<String, Diagnostic> {
+    let obj = objdump::decode(&read_object_file(file)?)?;
+    let out = out.unwrap_or_else(|| file.to_string());
+    let compression = compress.unwrap_or(obj.compression);
+
+    if obj.version == O2_VERSION && obj.compression == compression && out == file {
+        return Ok(format!("{} is already at version {}", file, O2_VERSION));
+    }
+
+    let io_error = |err: anyhow::Error| Diagnostic::new(Phase::Io, format!("{}: {}", out, err));
+    let mut bytes = vec![];
+    // Keep how the program was built, as upgrading does not rebuild it
+    match &obj.metadata {
+        Some(metadata) => write_bytecode_metadata(&obj.instrs, compression, metadata, &mut bytes),
+        None => write_bytecode_compressed(&obj.instrs, compression, &mut bytes),
+    }
+    .map_err(io_error)?;
+    std::fs::write(&out, bytes).map_err(|err| io_error(err.into()))?;
+
+    let compressed = match compression {
+        Compression::None => String::new(),
+        compression => format!(", {} compressed", compression),
+    };
+    Ok(format!(
+        "Upgraded {} from version {} to version {}{}",
+        out, obj.version, O2_VERSION, compressed
+    ))
+}
+
+/// Transpile the file to Rust, writing a Cargo package building it to the directory. Returns the directory.
+fn transpile_file(
+    file: &str,
+    out: Option<String>,
+    type_check: bool,
+    opt: &OptArgs,
+) -> Result<PathBuf, Diagnostic> {
+    let src = pipeline::read_source(file)?;
+    let mut instrs = pipeline::compile(&src, type_check)?;
+    opt.optimize(&mut instrs);
+    let rust = transpile::transpile(&instrs)?;
+
+    let name = Path::new(file)
+        .file_stem()
+        .expect("File has an extension")
+        .to_string_lossy()
+        .to_string();
+    let dir = PathBuf::from(out.unwrap_or_else(|| name.clone()));
+
+    let io_error =
+        |err: std::io::Error| Diagnostic::new(Phase::Io, format!("{}: {}", dir.display(), err));
+    std::fs::create_dir_all(dir.join("src")).map_err(io_error)?;
+    std::fs::write(dir.join("Cargo.toml"), transpile::manifest(&name)).map_err(io_error)?;
+    std::fs::write(dir.join("src").join("main.rs"), rust).map_err(io_error)?;
+
+    Ok(dir)
+}
+
+/// Format the .rst files at the paths in place, or with `check`, list the files that are not formatted.
+fn fmt_paths(paths: &[String], check: bool) -> ExitCode {
+    let mut ok = true;
+
+    for path in paths {
+        let files = match pipeline::source_files(Path::new(path)) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Diagnostic::new(Phase::Io, format!("{}: {}", path, err))
+                );
+                ok = false;
+                continue;
+            }
+        };
+
+        for file in files {
+            match fmt_file(&file, check) {
+                Ok(true) if check => {
+                    println!("{} is not formatted", file.display());
+                    ok = false;
+                }
+                Ok(_) => (),
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic.with_note(format!("in {}", file.display())));
+                    ok = false;
+                }
+            }
+        }
+    }
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Format the file, rewriting it unless only checking. Returns whether it was not already formatted.
+fn fmt_file(file: &Path, check: bool) -> Result<bool, Diagnostic> {
+    let src = pipeline::read_source(&file.to_string_lossy())?;
+    let formatted = fmt::format(&src)?;
+
+    if formatted == src {
+        return Ok(false);
+    }
+
+    if !check {
+        std::fs::write(file, formatted).map_err(|err| Diagnostic::new(Phase::Io, err))?;
+    }
+
+    Ok(true)
+}
+
+/// Run the tests of the .rst files at the paths, reporting each test and failure, and a summary.
+/// Fails if a test fails or a file cannot be tested.
+fn test_paths(paths: &[String], type_check: bool, coverage: Option<&Path>) -> ExitCode {
+    let mut passed = 0;
+    let mut failures = vec![];
+    let mut ok = true;
+    let mut lcov = vec![];
+
+    for path in paths {
+        let files = match pipeline::source_files(Path::new(path)) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Diagnostic::new(Phase::Io, format!("{}: {}", path, err))
+                );
+                ok = false;
+                continue;
+            }
+        };
+
+        for file in files {
+            let results = pipeline::read_source(&file.to_string_lossy())
+                .and_then(|src| test_runner::run_tests_with_coverage(&src, type_check));
+
+            let (results, lines) = match results {
+                Ok(results) => results,
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic.with_note(format!("in {}", file.display())));
+                    ok = false;
+                    continue;
+                }
+            };
+
+            println!("running {} tests in {}", results.len(), file.display());
+            for res in results {
+                match res.failure {
+                    None => {
+                        println!("test {} ... ok", res.name);
+                        passed += 1;
+                    }
+                    Some(diagnostic) => {
+                        println!("test {} ... FAILED", res.name);
+                        failures.push((res.name, diagnostic));
+                    }
+                }
+            }
+            if coverage.is_some() {
+                println!("coverage: {} of {} lines", lines.covered(), lines.total());
+                lines
+                    .write_lcov(&file, &mut lcov)
+                    .expect("Writing to a Vec can't fail");
+            }
+            println!();
+        }
+    }
+
+    if let Some(path) = coverage {
+        if let Err(err) = std::fs::write(path, lcov) {
+            let err = format!("{}: {}", path.display(), err);
+            eprintln!("{}", Diagnostic::new(Phase::Io, err));
+            ok = false;
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("failures:");
+        for (name, diagnostic) in failures.iter() {
+            println!();
+            println!("---- {} ----", name);
+            println!("{}", diagnostic);
+        }
+        println!();
+        ok = false;
+    }
+
+    let status = if ok { "ok" } else { "FAILED" };
+    println!(
+        "test result: {}. {} passed; {} failed",
+        status,
+        passed,
+        failures.len()
+    );
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
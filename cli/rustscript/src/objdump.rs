@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use bytecode::{
+    read_object, type_of, ByteCode, Compression, Header, ObjectFile, Operand, Value, O2_MAGIC,
+    O2_VERSION,
+};
+
+use crate::diagnostic::{Diagnostic, Phase};
+
+fn invalid(msg: impl ToString) -> Diagnostic {
+    Diagnostic::new(Phase::Io, msg)
+}
+
+/// The time in seconds since the Unix epoch as a UTC date and time, e.g. `2024-03-01 12:00:00 UTC`.
+fn utc(secs: u64) -> String {
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // The proleptic Gregorian calendar from the days since 1970-01-01, by eras of 400 years starting in March
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Decode the contents of a .o2 file of any version that can be read,
+/// checking that the header gives the length of the rest of the file.
+pub fn decode(bytes: &[u8]) -> Result<ObjectFile, Diagnostic> {
+    // Files without the magic number are of the first version, whose header only holds the length.
+    // Otherwise the size of the header depends on the version following the magic number.
+    let header_len = if bytes.starts_with(&O2_MAGIC) {
+        let version = bytes.get(4..8).map_or(O2_VERSION, |v| {
+            u32::from_le_bytes(v.try_into().expect("4 bytes"))
+        });
+        Header::size(version)
+    } else {
+        Header::size(1)
+    };
+    if bytes.len() < header_len {
+        return Err(invalid(format!(
+            "File of {} bytes is too short for the {} byte header",
+            bytes.len(),
+            header_len
+        )));
+    }
+
+    let header = Header::read(&mut &bytes[..]).map_err(invalid)?;
+    let rest = bytes.len() - header.byte_len();
+    if header.len != rest as u64 {
+        return Err(invalid(format!(
+            "Header gives a program of {} bytes, but the file holds {} bytes after it",
+            header.len, rest
+        )));
+    }
+
+    read_object(&mut &bytes[..]).map_err(|err| invalid(format!("Program does not decode: {}", err)))
+}
+
+/// A constant as written in source, with strings quoted.
+fn constant(val: &Value) -> String {
+    match val {
+        Value::String(s) => format!("{:?}", s),
+        val => val.to_string(),
+    }
+}
+
+/// The constants the instruction loads, including the operands of the register backend.
+fn loaded_constants(instr: &ByteCode) -> Vec<&Value> {
+    let operands = match instr {
+        ByteCode::LDC(val) | ByteCode::LDCBINOP(val, _) => return vec![val],
+        ByteCode::BINOPR(_, lhs, rhs, _) => vec![lhs, rhs],
+        ByteCode::MOVR(_, src) => vec![src],
+        _ => vec![],
+    };
+    operands
+        .into_iter()
+        .filter_map(|operand| match operand {
+            Operand::Const(val) => Some(val),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The jumps within a thread, from the address of a JOF, JOFORPOP, GOTO, TRY or NEXT to its target.
+fn jumps(instrs: &[ByteCode]) -> Vec<(usize, usize)> {
+    instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(pc, instr)| match instr {
+            ByteCode::JOF(addr)
+            | ByteCode::JOFORPOP(addr)
+            | ByteCode::GOTO(addr)
+            | ByteCode::TRY(addr)
+            | ByteCode::NEXT(addr)
+                if *addr < instrs.len() =>
+            {
+                Some((pc, *addr))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The gutter of arrows drawn to the left of each instruction, one line per jump from its source (`+-`) to its
+/// target (`+>`). Shorter jumps are drawn closer to the instructions, and jumps only share a lane if they do not overlap.
+fn arrows(instrs: &[ByteCode]) -> Vec<String> {
+    let mut jumps = jumps(instrs);
+    jumps.sort_by_key(|(from, to)| from.abs_diff(*to));
+
+    // The address ranges taken in each lane, the first lane being next to the instructions
+    let mut lanes: Vec<Vec<(usize, usize)>> = vec![];
+    let mut placed = vec![];
+
+    for (from, to) in jumps {
+        let (lo, hi) = (from.min(to), from.max(to));
+        let free = lanes
+            .iter()
+            .position(|lane| lane.iter().all(|&(l, h)| hi < l || h < lo));
+
+        let lane = free.unwrap_or_else(|| {
+            lanes.push(vec![]);
+            lanes.len() - 1
+        });
+        lanes[lane].push((lo, hi));
+        placed.push((from, to, lane));
+    }
+
+    if lanes.is_empty() {
+        return vec![String::new(); instrs.len()];
+    }
+
+    let width = lanes.len() * 2 + 1;
+    let mut rows = vec![vec![' '; width]; instrs.len()];
+
+    for (from, to, lane) in placed {
+        let (lo, hi) = (from.min(to), from.max(to));
+        let col = (lanes.len() - 1 - lane) * 2;
+
+        for (pc, row) in rows.iter_mut().enumerate().take(hi + 1).skip(lo) {
+            if pc != lo && pc != hi {
+                row[col] = '|';
+                continue;
+            }
+
+            row[col] = '+';
+            // Lines of other lanes are kept where this one crosses them
+            for ch in row[col + 1..width - 1].iter_mut() {
+                if *ch == ' ' {
+                    *ch = '-';
+                }
+            }
+            if pc == to || row[width - 1] != '>' {
+                row[width - 1] = if pc == to { '>' } else { '-' };
+            }
+        }
+    }
+
+    rows.into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect()
+}
+
+/// Describe a .o2 file: its header, string table, constants, the functions it defines and its disassembly.
+///
+/// .o2 files carry no source positions, so the only debug information is the name and parameters of each function,
+/// which are mapped to the address the function starts at and marked on the disassembly.
+///
+/// # Errors
+///
+/// If the header does not match the file, or the program does not decode.
+pub fn objdump(bytes: &[u8]) -> Result<String, Diagnostic> {
+    let obj = decode(bytes)?;
+    let compressed = match obj.compression {
+        Compression::None => String::new(),
+        compression => format!(" {} compressed", compression),
+    };
+    let mut lines = vec![format!(
+        "header: version {}, {} bytes, program: {} bytes{}, {} instructions",
+        obj.version,
+        obj.header().byte_len(),
+        obj.len,
+        compressed,
+        obj.instrs.len()
+    )];
+
+    if let Some(metadata) = &obj.metadata {
+        lines.push(format!(
+            "built by: compiler {}, source hash {:016x}, opt-level {}, at {}",
+            metadata.compiler_version,
+            metadata.source_hash,
+            metadata.opt_level,
+            utc(metadata.timestamp)
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("strings:".to_string());
+    for (idx, s) in obj.strings.iter().enumerate() {
+        lines.push(format!("  {:>3} {}", idx, s));
+    }
+
+    // Constants in order of first use, with the instructions loading them
+    let mut constants: Vec<(String, &str, Vec<usize>)> = vec![];
+    for (pc, instr) in obj.instrs.iter().enumerate() {
+        for val in loaded_constants(instr) {
+            let (text, ty) = (constant(val), type_of(val));
+            match constants
+                .iter_mut()
+                .find(|(t, c, _)| *t == text && *c == ty)
+            {
+                Some((_, _, pcs)) => pcs.push(pc),
+                None => constants.push((text, ty, vec![pc])),
+            }
+        }
+    }
+
+    lines.push(String::new());
+    lines.push("constants:".to_string());
+    for (idx, (text, ty, pcs)) in constants.iter().enumerate() {
+        let pcs: Vec<String> = pcs.iter().map(|pc| pc.to_string()).collect();
+        lines.push(format!(
+            "  {:>3} {} {} (at {})",
+            idx,
+            ty,
+            text,
+            pcs.join(", ")
+        ));
+    }
+
+    let mut entries: HashMap<usize, Vec<String>> = HashMap::new();
+    lines.push(String::new());
+    lines.push("functions:".to_string());
+    for instr in obj.instrs.iter() {
+        if let ByteCode::LDF(addr, sym, prms) = instr {
+            let prms: Vec<String> = prms.iter().map(|prm| prm.to_string()).collect();
+            lines.push(format!("  {:>3} {}({})", addr, sym, prms.join(", ")));
+            entries.entry(*addr).or_default().push(sym.to_string());
+        }
+    }
+
+    let width = obj.instrs.len().saturating_sub(1).to_string().len();
+    lines.push(String::new());
+    lines.push("disassembly:".to_string());
+    for (pc, (instr, gutter)) in obj.instrs.iter().zip(arrows(&obj.instrs)).enumerate() {
+        let mut line = format!("  {} {:>width$} {:?}", gutter, pc, instr);
+        if let Some(names) = entries.get(&pc) {
+            line = format!("{}  ; <{}>", line, names.join(", "));
+        }
+        lines.push(line);
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{
+        write_bytecode, write_bytecode_compressed, write_bytecode_metadata, write_bytecode_version,
+        Metadata,
+    };
+
+    use crate::pipeline;
+
+    use super::*;
+
+    fn object(instrs: &[ByteCode]) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_bytecode(instrs, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode_checks_header() {
+        let bytes = object(&[ByteCode::DONE]);
+        assert!(decode(&bytes).is_ok());
+
+        let err = decode(&bytes[..4]).unwrap_err();
+        assert_eq!(
+            err.errors[0],
+            "File of 4 bytes is too short for the 20 byte header"
+        );
+
+        let mut v1 = vec![];
+        write_bytecode_version(&[ByteCode::DONE], 1, &mut v1).unwrap();
+        assert_eq!(decode(&v1).unwrap().version, 1);
+        let err = decode(&v1[..4]).unwrap_err();
+        assert_eq!(
+            err.errors[0],
+            "File of 4 bytes is too short for the 8 byte header"
+        );
+
+        // Version 2 has no flags in its header
+        let mut v2 = vec![];
+        write_bytecode_version(&[ByteCode::DONE], 2, &mut v2).unwrap();
+        assert_eq!(decode(&v2).unwrap().version, 2);
+        let err = decode(&v2[..12]).unwrap_err();
+        assert_eq!(
+            err.errors[0],
+            "File of 12 bytes is too short for the 16 byte header"
+        );
+
+        let mut zstd = vec![];
+        write_bytecode_compressed(&[ByteCode::DONE], Compression::Zstd, &mut zstd).unwrap();
+        assert_eq!(decode(&zstd).unwrap().compression, Compression::Zstd);
+        let dump = objdump(&zstd).unwrap();
+        let header = format!("header: version {}, 20 bytes, program: ", O2_VERSION);
+        assert!(dump.starts_with(&header));
+        assert!(dump.contains(" bytes zstd compressed, 1 instructions\n"));
+
+        let mut newer = bytes.clone();
+        newer[4..8].copy_from_slice(&(O2_VERSION + 1).to_le_bytes());
+        let err = decode(&newer).unwrap_err();
+        assert!(err.errors[0].starts_with("Unsupported .o2 version"));
+
+        let err = decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(err.errors[0].starts_with("Header gives a program of"));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        assert_eq!(utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(utc(1_700_000_000), "2023-11-14 22:13:20 UTC");
+
+        let metadata = Metadata {
+            compiler_version: "0.1.0".to_string(),
+            source_hash: 0xabc,
+            timestamp: 1_700_000_000,
+            opt_level: 1,
+        };
+        let mut bytes = vec![];
+        write_bytecode_metadata(&[ByteCode::DONE], Compression::None, &metadata, &mut bytes)
+            .unwrap();
+
+        let dump = objdump(&bytes).unwrap();
+        let header_len = Header::read(&mut bytes.as_slice()).unwrap().byte_len();
+        let header = format!("header: version {}, {} bytes,", O2_VERSION, header_len);
+        assert!(dump.starts_with(&header), "{}", dump);
+        assert!(dump.contains(
+            "\nbuilt by: compiler 0.1.0, source hash 0000000000000abc, opt-level 1, at 2023-11-14 22:13:20 UTC\n"
+        ));
+
+        let err = decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(err.errors[0].starts_with("Header gives a program of"));
+    }
+
+    #[test]
+    fn test_arrows() {
+        let instrs = vec![
+            ByteCode::ldc(true),
+            ByteCode::JOF(4),
+            ByteCode::ldc(1),
+            ByteCode::GOTO(5),
+            ByteCode::ldc(2),
+            ByteCode::DONE,
+        ];
+
+        assert_eq!(
+            arrows(&instrs),
+            vec!["     ", "+----", "|    ", "| +--", "+-|->", "  +->"]
+        );
+    }
+
+    #[test]
+    fn test_objdump() {
+        let instrs = pipeline::compile(
+            "fn f(n: int) -> int { n + 1 } let s = \"hi\"; f(1) + f(1)",
+            true,
+        )
+        .unwrap();
+        let dump = objdump(&object(&instrs)).unwrap();
+
+        assert!(dump.contains("strings:\n    0 f\n    1 s\n    2 n\n"));
+        assert!(dump.contains("constants:\n    0 Int 1 (at 4, 15, 18)\n"));
+        assert!(dump.contains("    2 String \"hi\" (at 10)\n"));
+        assert!(dump.contains("functions:\n    3 f(n)\n"));
+        assert!(dump.contains("  +--  2 GOTO(7)\n  |    3 LDSLOT(0, 0)  ; <f>\n"));
+    }
+}
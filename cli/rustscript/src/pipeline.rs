@@ -0,0 +1,212 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use bytecode::{ByteCode, Value};
+use compiler::compiler::Compiler;
+use ignite::{run, Runtime, RuntimeErrorContext};
+use lexer::Token;
+use parser::structs::BlockSeq;
+use types::type_checker::{Env, TypeChecker};
+
+use crate::diagnostic::{Diagnostic, Phase};
+
+const RST: &str = "rst";
+
+/// Read the source of a RustScript file, which must have extension .rst
+pub fn read_source(file: &str) -> Result<String, Diagnostic> {
+    let path = Path::new(file);
+
+    if !path.exists() {
+        let err = format!("File '{}' does not exist", file);
+        return Err(Diagnostic::new(Phase::Io, err));
+    }
+
+    if path.extension().is_none_or(|ext| ext != RST) {
+        let err = format!("File {} does not have extension .{RST}", file);
+        return Err(Diagnostic::new(Phase::Io, err));
+    }
+
+    std::fs::read_to_string(path).map_err(|err| Diagnostic::new(Phase::Io, err))
+}
+
+/// The .rst files at the path, searching directories recursively, in a stable order.
+pub fn source_files(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+
+    let mut files = vec![];
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(source_files(&entry)?);
+        } else if entry.extension().is_some_and(|ext| ext == "rst") {
+            files.push(entry);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Check that the whole source is made of valid tokens.
+/// The parser expects the lexer to succeed, so this has to run before parsing.
+pub fn lex(src: &str) -> Result<(), Diagnostic> {
+    let mut lexer = lexer::lex(src);
+
+    while let Some(tok) = lexer.next() {
+        if tok.is_err() {
+            // extras holds the number of newlines seen and the index where the current line starts
+            let (line, line_start) = lexer.extras;
+            let err = format!(
+                "Unrecognized token '{}' at line {}, column {}",
+                lexer.slice(),
+                line + 1,
+                lexer.span().start - line_start + 1
+            );
+            return Err(Diagnostic::new(Phase::Lex, err).with_span(lexer.span()));
+        }
+    }
+
+    Ok(())
+}
+
+/// The tokens of the source with their byte ranges. The source must lex.
+pub fn tokens(src: &str) -> Vec<(Token, Range<usize>)> {
+    let mut lexer = lexer::lex(src);
+    let mut toks = vec![];
+
+    while let Some(tok) = lexer.next() {
+        toks.push((tok.expect("Source was lexed"), lexer.span()));
+    }
+
+    toks
+}
+
+/// The line and column, both counted from 1, of a byte offset in the source.
+pub fn line_col(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    (before.matches('\n').count() + 1, offset - line_start + 1)
+}
+
+/// Parse the source, locating a parse error at the token the parser failed at.
+pub fn parse(src: &str) -> Result<BlockSeq, Diagnostic> {
+    lex(src)?;
+
+    parser::Parser::new_from_string(src).parse().map_err(|err| {
+        let span = err.tok().and_then(|idx| tokens(src).into_iter().nth(idx));
+
+        match span {
+            Some((_, span)) => {
+                let (line, col) = line_col(src, span.start);
+                let msg = format!("{} at line {}, column {}", err.msg(), line, col);
+                Diagnostic::new(Phase::Parse, msg).with_span(span)
+            }
+            None => err.into(),
+        }
+    })
+}
+
+/// Run the source through the lexer, parser, type checker and compiler.
+pub fn compile(src: &str, type_check: bool) -> Result<Vec<ByteCode>, Diagnostic> {
+    let program = parse(src)?;
+
+    if type_check {
+        TypeChecker::new(&program).type_check()?;
+    }
+
+    Ok(Compiler::new(program).compile()?)
+}
+
+/// Compile the source like [`compile`], along with the types of its top-level bindings for
+/// [`Runtime::hot_reload_typed`], which are `None` if it doesn't type check and `type_check` is off.
+pub fn compile_with_types(
+    src: &str,
+    type_check: bool,
+) -> Result<(Vec<ByteCode>, Option<Env>), Diagnostic> {
+    let program = parse(src)?;
+
+    let mut envs = vec![];
+    let types = match TypeChecker::new(&program).type_check_in(&mut envs) {
+        Ok(_) => Some(envs.pop().unwrap_or_default()),
+        Err(err) if type_check => return Err(err.into()),
+        Err(_) => None,
+    };
+
+    Ok((Compiler::new(program).compile()?, types))
+}
+
+/// Run the program to completion, returning its final value if it left one.
+pub fn execute(rt: &mut Runtime) -> Result<Option<Value>, Diagnostic> {
+    run(rt).map_err(runtime_error)?;
+
+    Ok(rt.current_thread.operand_stack.last().cloned())
+}
+
+/// The diagnostic of an error of the runtime, with the context the runtime gave it as the note.
+pub fn runtime_error(err: anyhow::Error) -> Diagnostic {
+    let diagnostic = Diagnostic::new(Phase::Runtime, err.root_cause());
+
+    match err.downcast_ref::<RuntimeErrorContext>() {
+        Some(ctx) => diagnostic.with_note(ctx),
+        None => diagnostic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_source(src: &str) -> Result<Option<Value>, Diagnostic> {
+        let instrs = compile(src, true)?;
+        execute(&mut Runtime::new(instrs))
+    }
+
+    #[test]
+    fn test_run_source() {
+        assert_eq!(
+            run_source("let x = 2; x * 21").unwrap(),
+            Some(Value::Int(42))
+        );
+    }
+
+    #[test]
+    fn test_phase_of_error() {
+        let phase = |src: &str| run_source(src).unwrap_err().phase;
+
+        assert_eq!(phase("let x = 1 ` 2;"), Phase::Lex);
+        assert_eq!(phase("let x = ;"), Phase::Parse);
+        assert_eq!(phase("let x: int = true;"), Phase::Type);
+        assert_eq!(phase("let x = 1; assert(x > 3);"), Phase::Runtime);
+    }
+
+    #[test]
+    fn test_lex_error_location() {
+        let err = lex("let x = 1;\nlet y = `;").unwrap_err();
+        assert_eq!(err.span, Some(19..20));
+        assert_eq!(
+            err.errors,
+            vec!["Unrecognized token '`' at line 2, column 9".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = parse("let x = 1;\nlet y = ;").unwrap_err();
+        assert_eq!(err.span, Some(19..20));
+        assert!(err.errors[0].ends_with("at line 2, column 9"));
+    }
+
+    #[test]
+    fn test_type_errors_are_all_reported() {
+        let err = run_source("let x: int = true; let y: bool = 1;").unwrap_err();
+        assert_eq!(err.errors.len(), 2);
+    }
+}
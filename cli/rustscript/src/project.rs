@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use bytecode::{Compression, Metadata};
+use compiler::{compiler::Compiler, optimize::PassManager};
+use parser::structs::{BlockSeq, Decl};
+use serde::Deserialize;
+use types::type_checker::TypeChecker;
+
+use crate::{
+    diagnostic::{Diagnostic, Phase},
+    pipeline,
+};
+
+/// The name of the manifest file at the root of a project.
+pub const MANIFEST: &str = "script.toml";
+
+/// The highest optimization level the manifest accepts.
+const MAX_OPT_LEVEL: u8 = 3;
+
+/// The manifest of a project, `script.toml`:
+///
+/// ```toml
+/// [project]
+/// name = "hello"
+/// entry = "main.rst"
+/// include = ["lib"]
+/// opt-level = 0
+/// compress = "zstd"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub project: Project,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Project {
+    pub name: String,
+    /// The file the program starts running from, relative to the root of the project.
+    pub entry: PathBuf,
+    /// Files and directories of .rst modules whose declarations are linked into the program, relative to the root.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// How much to optimize the program, from 0 to 3. From 1 on the bytecode is optimized with every pass,
+    /// there being no more costly passes for the higher levels yet.
+    #[serde(default)]
+    pub opt_level: u8,
+    /// Where to write the program, relative to the root. Defaults to the name of the project with extension .o2
+    pub output: Option<PathBuf>,
+    /// How to compress the program in the .o2 file: "none", "deflate" or "zstd".
+    #[serde(default)]
+    pub compress: Compression,
+}
+
+fn manifest_err(err: impl ToString) -> Diagnostic {
+    Diagnostic::new(Phase::Io, err).with_note(format!("in {}", MANIFEST))
+}
+
+impl Manifest {
+    /// Read the manifest at the root of a project.
+    pub fn load(root: &Path) -> Result<Manifest, Diagnostic> {
+        let path = root.join(MANIFEST);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| Diagnostic::new(Phase::Io, format!("{}: {}", path.display(), err)))?;
+
+        Manifest::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Manifest, Diagnostic> {
+        let manifest: Manifest = toml::from_str(text).map_err(|err| manifest_err(err.message()))?;
+
+        if manifest.project.opt_level > MAX_OPT_LEVEL {
+            return Err(manifest_err(format!(
+                "opt-level must be between 0 and {}, got {}",
+                MAX_OPT_LEVEL, manifest.project.opt_level
+            )));
+        }
+
+        Ok(manifest)
+    }
+
+    /// The source files of the program in the order they are linked: the included modules, then the entry.
+    pub fn sources(&self, root: &Path) -> Result<Vec<PathBuf>, Diagnostic> {
+        let entry = root.join(&self.project.entry);
+        let mut files = vec![];
+
+        for include in self.project.include.iter() {
+            let found = pipeline::source_files(&root.join(include)).map_err(|err| {
+                Diagnostic::new(Phase::Io, format!("{}: {}", include.display(), err))
+            })?;
+
+            // The entry may be in an included directory, and a module may be included twice
+            for file in found {
+                if file != entry && !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+
+        files.push(entry);
+        Ok(files)
+    }
+
+    /// Where the program is written.
+    pub fn output(&self, root: &Path) -> PathBuf {
+        match &self.project.output {
+            Some(output) => root.join(output),
+            None => root.join(format!("{}.o2", self.project.name)),
+        }
+    }
+}
+
+/// Link modules into one program, the last being the entry. The top-level declarations of all modules share one scope,
+/// so a module can use what any other module declares. Modules other than the entry may only declare,
+/// since running them is left to the entry.
+///
+/// # Errors
+///
+/// If a module does not parse, an included module has statements other than declarations,
+/// or a name is declared by more than one module.
+pub fn link(modules: &[(PathBuf, String)]) -> Result<BlockSeq, Diagnostic> {
+    let mut program = BlockSeq {
+        decls: vec![],
+        last_expr: None,
+        symbols: vec![],
+        decl_toks: vec![],
+        last_expr_tok: None,
+    };
+    // The module declaring each top-level name
+    let mut declared: HashMap<String, &Path> = HashMap::new();
+
+    for (idx, (path, src)) in modules.iter().enumerate() {
+        let in_module =
+            |diagnostic: Diagnostic| diagnostic.with_note(format!("in {}", path.display()));
+        let module = pipeline::parse(src).map_err(in_module)?;
+        let is_entry = idx == modules.len() - 1;
+
+        if !is_entry {
+            let only_decls = module.last_expr.is_none()
+                && module.decls.iter().all(|decl| {
+                    matches!(
+                        decl,
+                        Decl::LetStmt(_) | Decl::LetDestructureStmt(_) | Decl::FnDeclStmt(_)
+                    )
+                });
+
+            if !only_decls {
+                let err = "Included modules can only declare with let and fn at the top level";
+                return Err(in_module(Diagnostic::new(Phase::Compile, err)));
+            }
+        }
+
+        for sym in module.symbols.iter() {
+            match declared.get(sym) {
+                Some(other) if *other != path.as_path() => {
+                    let err = format!("'{}' is also declared in {}", sym, other.display());
+                    return Err(in_module(Diagnostic::new(Phase::Compile, err)));
+                }
+                Some(_) => (),
+                None => {
+                    declared.insert(sym.clone(), path);
+                    program.symbols.push(sym.clone());
+                }
+            }
+        }
+
+        program.decls.extend(module.decls);
+        if is_entry {
+            program.last_expr = module.last_expr;
+        }
+    }
+
+    Ok(program)
+}
+
+/// Build the project at the root into a single .o2 file, returning where it was written.
+pub fn build(root: &Path, type_check: bool) -> Result<PathBuf, Diagnostic> {
+    let manifest = Manifest::load(root)?;
+
+    let mut modules = vec![];
+    for file in manifest.sources(root)? {
+        let src = pipeline::read_source(&file.to_string_lossy())?;
+        modules.push((file, src));
+    }
+
+    let program = link(&modules)?;
+
+    if type_check {
+        TypeChecker::new(&program).type_check()?;
+    }
+
+    let mut instrs = Compiler::new(program).compile()?;
+    if manifest.project.opt_level > 0 {
+        PassManager::all().run(&mut instrs);
+    }
+
+    let output = manifest.output(root);
+    let io_err =
+        |err: anyhow::Error| Diagnostic::new(Phase::Io, format!("{}: {}", output.display(), err));
+    let mut file = std::fs::File::create(&output).map_err(|err| io_err(err.into()))?;
+    let srcs: Vec<&str> = modules.iter().map(|(_, src)| src.as_str()).collect();
+    let metadata = Metadata::new(compiler::VERSION, &srcs, manifest.project.opt_level);
+    bytecode::write_bytecode_metadata(&instrs, manifest.project.compress, &metadata, &mut file)
+        .map_err(io_err)?;
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules(srcs: &[(&str, &str)]) -> Vec<(PathBuf, String)> {
+        srcs.iter()
+            .map(|(path, src)| (PathBuf::from(path), src.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            "[project]\nname = \"hello\"\nentry = \"main.rst\"\ninclude = [\"lib\"]\nopt-level = 1\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.project.include, vec![PathBuf::from("lib")]);
+        assert_eq!(manifest.project.opt_level, 1);
+        assert_eq!(manifest.project.compress, Compression::None);
+        assert_eq!(
+            manifest.output(Path::new("proj")),
+            PathBuf::from("proj/hello.o2")
+        );
+
+        let err = Manifest::parse("[project]\nname = \"hello\"\n").unwrap_err();
+        assert_eq!(err.errors, vec!["missing field `entry`".to_string()]);
+
+        let err = Manifest::parse("[project]\nname = \"a\"\nentry = \"a.rst\"\nopt-level = 9\n")
+            .unwrap_err();
+        assert_eq!(
+            err.errors,
+            vec!["opt-level must be between 0 and 3, got 9".to_string()]
+        );
+
+        let manifest =
+            Manifest::parse("[project]\nname = \"a\"\nentry = \"a.rst\"\ncompress = \"zstd\"\n")
+                .unwrap();
+        assert_eq!(manifest.project.compress, Compression::Zstd);
+        assert!(Manifest::parse(
+            "[project]\nname = \"a\"\nentry = \"a.rst\"\ncompress = \"lz4\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_link() {
+        let program = link(&modules(&[
+            (
+                "lib/math.rst",
+                "fn double(n: int) -> int { n * 2 } let two = 2;",
+            ),
+            ("main.rst", "let x = double(two); x"),
+        ]))
+        .unwrap();
+
+        assert_eq!(program.symbols, vec!["double", "two", "x"]);
+        let instrs = Compiler::new(program).compile().unwrap();
+        let mut rt = ignite::Runtime::new(instrs);
+        assert_eq!(
+            pipeline::execute(&mut rt).unwrap(),
+            Some(bytecode::Value::Int(4))
+        );
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let err = link(&modules(&[
+            ("a.rst", "let x = 1;"),
+            ("b.rst", "fn x() {}"),
+            ("main.rst", "x"),
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            err.errors,
+            vec!["'x' is also declared in a.rst".to_string()]
+        );
+        assert_eq!(err.note, Some("in b.rst".to_string()));
+
+        let err = link(&modules(&[("a.rst", "println(1);"), ("main.rst", "")])).unwrap_err();
+        assert_eq!(err.phase, Phase::Compile);
+    }
+}
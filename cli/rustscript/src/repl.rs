@@ -0,0 +1,364 @@
+use std::{ops::Range, time::Instant};
+
+use bytecode::{builtin, Symbol, ThreadID, Value};
+use compiler::compiler::Compiler;
+use ignite::{EnvReport, Runtime, ThreadState, MAIN_THREAD_ID};
+use parser::structs::{BlockSeq, Decl, Type};
+use rustyline::{error::ReadlineError, DefaultEditor};
+use types::type_checker::{Env, TypeChecker};
+
+use crate::{
+    diagnostic::{Diagnostic, Phase},
+    pipeline,
+};
+
+/// A REPL session: one runtime kept alive across inputs, with each input compiled and type checked
+/// against the bindings of the inputs before it.
+pub struct Session {
+    rt: Runtime,
+    compiler: Compiler,
+    /// The types of the top-level bindings of the earlier inputs.
+    type_envs: Vec<Env>,
+    type_check: bool,
+    /// The PCs of the instructions the last input was compiled to.
+    last_instrs: Range<usize>,
+}
+
+impl Session {
+    pub fn new(type_check: bool) -> Session {
+        Session::with_runtime(Runtime::new(vec![]), vec![], type_check)
+    }
+
+    /// Start a session on a runtime that has not run anything yet, with the types of the bindings
+    /// it was set up with, e.g. functions of the host.
+    pub fn with_runtime(rt: Runtime, type_envs: Vec<Env>, type_check: bool) -> Session {
+        let program = BlockSeq {
+            decls: vec![],
+            last_expr: None,
+            symbols: vec![],
+            decl_toks: vec![],
+            last_expr_tok: None,
+        };
+
+        Session {
+            rt,
+            compiler: Compiler::new(program),
+            type_envs,
+            type_check,
+            last_instrs: 0..0,
+        }
+    }
+
+    /// The status the program asked to exit with, if an input called exit.
+    pub fn exit_code(&self) -> Option<i64> {
+        self.rt.exit_code
+    }
+
+    /// Run the input in the environment left by the earlier inputs, returning its value if it has one.
+    /// An input that fails leaves no bindings behind, though its side effects up to the error remain.
+    /// A function redefined with the type it had is reloaded: the functions of earlier inputs call the new one.
+    pub fn eval(&mut self, src: &str) -> Result<Option<Value>, Diagnostic> {
+        let program = pipeline::parse(src)?;
+
+        let type_envs = self.type_envs.clone();
+        if self.type_check {
+            TypeChecker::new(&program).type_check_in(&mut self.type_envs)?;
+        }
+
+        let compiler = self.compiler.clone();
+        let start = self.rt.instrs.len();
+        if let Err(err) = self
+            .compiler
+            .compile_incremental(&program, &mut self.rt.instrs)
+        {
+            self.type_envs = type_envs;
+            return Err(err.into());
+        }
+        self.last_instrs = start..self.rt.instrs.len();
+
+        let env = self.rt.current_thread.env.clone();
+        self.rt.resume_at(start);
+
+        let val = pipeline::execute(&mut self.rt).or_else(|diagnostic| {
+            self.type_envs = type_envs.clone();
+            self.compiler = compiler;
+            self.rt
+                .recover(env)
+                .map_err(|err| Diagnostic::new(Phase::Runtime, err))?;
+
+            Err(diagnostic)
+        })?;
+
+        for decl in program.decls.iter() {
+            let Decl::FnDeclStmt(fn_decl) = decl else {
+                continue;
+            };
+
+            // The earlier functions were type checked against the old type
+            let old_ty = type_envs
+                .iter()
+                .rev()
+                .find_map(|env| env.get(&fn_decl.name));
+            let new_ty = self.type_envs.last().and_then(|env| env.get(&fn_decl.name));
+            if !self.type_check || old_ty == new_ty {
+                self.rt.rebind_fn(Symbol::from(&fn_decl.name));
+            }
+        }
+
+        Ok(val)
+    }
+
+    /// The type the input would have, in the scope of the bindings of the earlier inputs, without running it.
+    /// The input is type checked even if the session does not type check.
+    pub fn type_of(&self, src: &str) -> Result<Type, Diagnostic> {
+        let program = pipeline::parse(src)?;
+        let ty = TypeChecker::new(&program).type_check_in(&mut self.type_envs.clone())?;
+
+        Ok(ty)
+    }
+
+    /// The disassembly of the instructions the last input was compiled to, one per line with its PC.
+    pub fn bytecode(&self) -> String {
+        let width = self.last_instrs.end.saturating_sub(1).to_string().len();
+
+        let lines: Vec<String> = self
+            .last_instrs
+            .clone()
+            .map(|pc| format!("{:>width$} {:?}", pc, self.rt.instrs[pc]))
+            .collect();
+
+        lines.join("\n")
+    }
+
+    /// The environment the inputs run in, with the bindings of the earlier inputs.
+    pub fn env(&self) -> Option<EnvReport> {
+        self.rt.inspect_env(MAIN_THREAD_ID)
+    }
+
+    /// The threads the inputs have spawned, and the main thread, with their states.
+    pub fn threads(&self) -> Vec<(ThreadID, ThreadState)> {
+        let mut threads: Vec<_> = self
+            .rt
+            .thread_states
+            .iter()
+            .map(|(tid, state)| (*tid, *state))
+            .collect();
+        threads.sort_by_key(|(tid, _)| *tid);
+
+        threads
+    }
+
+    /// Call a function declared by an earlier input, or a builtin, returning its value.
+    ///
+    /// # Errors
+    ///
+    /// If no input has run, or the call fails, in which case the session is left as it was.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Diagnostic> {
+        self.rt.call(name, args).map_err(pipeline::runtime_error)
+    }
+}
+
+pub fn rustscript_repl(type_check: bool) -> anyhow::Result<()> {
+    let mut rl = DefaultEditor::new()?;
+    let mut session = Session::new(type_check);
+
+    println!("Welcome to the RustScript REPL! Type /exit to exit, or :help for the commands.");
+    println!();
+
+    loop {
+        let inp = match rl.readline(">>> ") {
+            Ok(inp) => inp,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        let inp = inp.trim();
+        if inp.is_empty() {
+            continue;
+        }
+
+        if inp == "/exit" {
+            break;
+        }
+
+        rl.add_history_entry(inp)?;
+
+        if let Some(cmd) = inp.strip_prefix(':') {
+            match meta_command(&mut session, cmd) {
+                Ok(out) => println!("{}", out),
+                Err(diagnostic) => eprintln!("{}", diagnostic),
+            }
+            continue;
+        }
+
+        match session.eval(inp) {
+            // Declarations and statements produce unit, which is not worth echoing
+            Ok(Some(Value::Unit)) | Ok(None) => (),
+            Ok(Some(val)) => builtin::println_impl(&val),
+            Err(diagnostic) => eprintln!("{}", diagnostic),
+        }
+
+        if let Some(code) = session.exit_code() {
+            std::process::exit(code as i32);
+        }
+    }
+
+    println!("See you again!");
+    Ok(())
+}
+
+const META_COMMANDS: &str = "\
+:bytecode    disassemble the instructions the last input was compiled to
+:env         show the bindings of the earlier inputs
+:threads     list the threads and their states
+:type expr   show the type of the expression without running it
+:time expr   run the expression and show how long it took
+:help        show this list";
+
+/// Run a REPL command, given without its leading `:`, returning what it prints.
+fn meta_command(session: &mut Session, cmd: &str) -> Result<String, Diagnostic> {
+    let (name, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (cmd, ""),
+    };
+
+    match (name, arg) {
+        ("help", "") => Ok(META_COMMANDS.to_string()),
+        ("bytecode", "") => Ok(session.bytecode()),
+        ("env", "") => Ok(session
+            .env()
+            .map_or("No environment".to_string(), |report| report.to_string())),
+        ("threads", "") => {
+            let lines: Vec<String> = session
+                .threads()
+                .into_iter()
+                .map(|(tid, state)| format!("thread {}: {}", tid, state))
+                .collect();
+            Ok(lines.join("\n"))
+        }
+        ("type", src) if !src.is_empty() => Ok(session.type_of(src)?.to_string()),
+        ("time", src) if !src.is_empty() => {
+            let start = Instant::now();
+            let val = session.eval(src)?;
+            let elapsed = start.elapsed();
+
+            Ok(match val {
+                Some(Value::Unit) | None => format!("took {:?}", elapsed),
+                Some(val) => format!("{}\ntook {:?}", val, elapsed),
+            })
+        }
+        _ => Err(Diagnostic::new(
+            Phase::Parse,
+            format!(
+                "Unknown command :{}, the commands are:\n{}",
+                cmd, META_COMMANDS
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings_persist() {
+        let mut session = Session::new(true);
+
+        assert_eq!(session.eval("let x = 2;"), Ok(None));
+        assert_eq!(session.eval("fn double(n: int) -> int { n * 2 }"), Ok(None));
+        assert_eq!(session.eval("double(x)"), Ok(Some(Value::Int(4))));
+
+        // Shadowing and assigning to earlier bindings
+        assert_eq!(session.eval("let y = x; x = 10;"), Ok(None));
+        assert_eq!(session.eval("let x = true;"), Ok(None));
+        assert_eq!(
+            session.eval("if x { y + double(3) } else { 0 }"),
+            Ok(Some(Value::Int(8)))
+        );
+    }
+
+    #[test]
+    fn test_failed_input_leaves_no_bindings() {
+        let mut session = Session::new(true);
+        session.eval("let x = 2;").unwrap();
+
+        let err = session.eval("let y = 1; let z: bool = y;").unwrap_err();
+        assert_eq!(err.phase, Phase::Type);
+        assert_eq!(session.eval("y").unwrap_err().phase, Phase::Type);
+
+        // Fails in a function call, after x has been assigned
+        session.eval("fn check(n: int) { assert(n > 5); }").unwrap();
+        let err = session.eval("let w = 1; x = 3; check(x);").unwrap_err();
+        assert_eq!(err.phase, Phase::Runtime);
+        assert_eq!(session.eval("w").unwrap_err().phase, Phase::Type);
+
+        assert_eq!(session.eval("x + 1"), Ok(Some(Value::Int(4))));
+    }
+
+    #[test]
+    fn test_failed_thread() {
+        let mut session = Session::new(true);
+        session.eval("let x = 2;").unwrap();
+
+        let err = session
+            .eval("fn f() { assert(x > 5); } let h = spawn f(); join h;")
+            .unwrap_err();
+        assert_eq!(err.phase, Phase::Runtime);
+
+        assert_eq!(session.eval("x"), Ok(Some(Value::Int(2))));
+    }
+
+    #[test]
+    fn test_meta_commands() {
+        let mut session = Session::new(true);
+        session.eval("let x = 2;").unwrap();
+        session
+            .eval("fn f() {} let h = spawn f(); join h; x + 1")
+            .unwrap();
+
+        let bytecode = meta_command(&mut session, "bytecode").unwrap();
+        assert!(bytecode.contains("SPAWN"));
+        assert!(!bytecode.contains("ASSIGN(\"x\")"));
+
+        let env = meta_command(&mut session, "env").unwrap();
+        assert!(env.contains("x = 2"));
+        assert!(env.contains("h = 2"));
+
+        assert_eq!(
+            meta_command(&mut session, "threads").unwrap(),
+            "thread 1: done\nthread 2: done"
+        );
+
+        assert_eq!(meta_command(&mut session, "type x > 1").unwrap(), "bool");
+        assert_eq!(
+            meta_command(&mut session, "type x + true")
+                .unwrap_err()
+                .phase,
+            Phase::Type
+        );
+
+        let time = meta_command(&mut session, "time x * 21").unwrap();
+        assert!(time.starts_with("42\ntook "));
+
+        assert!(meta_command(&mut session, "nope").is_err());
+        assert!(meta_command(&mut session, "type").is_err());
+    }
+
+    #[test]
+    fn test_redefined_fn_is_reloaded() {
+        let mut session = Session::new(true);
+        session.eval("fn f(n: int) -> int { n + 1 }").unwrap();
+        session.eval("fn g() -> int { f(10) }").unwrap();
+        assert_eq!(session.eval("g()"), Ok(Some(Value::Int(11))));
+
+        session.eval("fn f(n: int) -> int { n * 2 }").unwrap();
+        assert_eq!(session.eval("g()"), Ok(Some(Value::Int(20))));
+
+        // A function of another type only shadows the old one
+        session.eval("fn f(b: bool) -> bool { !b }").unwrap();
+        assert_eq!(session.eval("g()"), Ok(Some(Value::Int(20))));
+        assert_eq!(session.eval("f(true)"), Ok(Some(Value::Bool(false))));
+    }
+}
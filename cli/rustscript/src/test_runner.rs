@@ -0,0 +1,208 @@
+use std::rc::Rc;
+
+use compiler::compiler::Compiler;
+use ignite::Runtime;
+use parser::structs::{BlockSeq, Decl, Expr, FnCallData};
+use types::type_checker::TypeChecker;
+
+use crate::{coverage::LineCoverage, diagnostic::Diagnostic, pipeline};
+
+/// The outcome of a test function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    /// The error the test failed with, e.g. a failed assertion, if it failed.
+    pub failure: Option<Diagnostic>,
+}
+
+/// The names of the test functions of the program, the top-level functions marked with `#[test]`, in order.
+pub fn discover(program: &BlockSeq) -> Vec<String> {
+    program
+        .decls
+        .iter()
+        .filter_map(|decl| match decl {
+            Decl::FnDeclStmt(fn_decl) if fn_decl.is_test => Some(fn_decl.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The program running the test function: the `let` and function declarations of the script, followed by a call
+/// to the function. The other top-level statements are left out, so that the script itself does not run.
+fn test_program(program: &BlockSeq, name: &str) -> BlockSeq {
+    let is_decl = |decl: &&Decl| {
+        matches!(
+            decl,
+            Decl::LetStmt(_) | Decl::LetDestructureStmt(_) | Decl::FnDeclStmt(_)
+        )
+    };
+    let decls: Vec<Decl> = program.decls.iter().filter(is_decl).cloned().collect();
+    // The positions of the declarations kept, for coverage
+    let decl_toks = program
+        .decls
+        .iter()
+        .zip(program.decl_toks.iter())
+        .filter(|(decl, _)| is_decl(decl))
+        .map(|(_, tok)| *tok)
+        .collect();
+
+    let call = Expr::FnCallExpr(FnCallData {
+        name: name.to_string(),
+        args: vec![],
+        names: vec![],
+    });
+
+    BlockSeq {
+        decls,
+        last_expr: Some(Rc::new(call)),
+        symbols: program.symbols.clone(),
+        decl_toks,
+        last_expr_tok: None,
+    }
+}
+
+/// Run the test function, adding the lines of the source it ran to the coverage.
+fn run_test(
+    src: &str,
+    program: &BlockSeq,
+    name: &str,
+    coverage: &mut LineCoverage,
+) -> Result<(), Diagnostic> {
+    let (instrs, debug_info) =
+        Compiler::new(test_program(program, name)).compile_with_debug_info()?;
+    let mut rt = Runtime::new(instrs);
+    rt.record_coverage();
+
+    let res = pipeline::execute(&mut rt);
+    if let Some(counts) = rt.coverage() {
+        coverage.merge(&LineCoverage::new(src, &debug_info, counts));
+    }
+    res?;
+
+    Ok(())
+}
+
+/// Run each test function of the source in a fresh runtime.
+///
+/// # Errors
+///
+/// If the source does not parse or type check, in which case no test is run.
+pub fn run_tests(src: &str, type_check: bool) -> Result<Vec<TestResult>, Diagnostic> {
+    run_tests_with_coverage(src, type_check).map(|(results, _)| results)
+}
+
+/// Run each test function of the source in a fresh runtime, with the lines of the source the tests ran between them.
+///
+/// # Errors
+///
+/// If the source does not parse or type check, in which case no test is run.
+pub fn run_tests_with_coverage(
+    src: &str,
+    type_check: bool,
+) -> Result<(Vec<TestResult>, LineCoverage), Diagnostic> {
+    let program = pipeline::parse(src)?;
+
+    if type_check {
+        TypeChecker::new(&program).type_check()?;
+    }
+
+    let mut coverage = LineCoverage::default();
+    let results = discover(&program)
+        .into_iter()
+        .map(|name| {
+            let failure = run_test(src, &program, &name, &mut coverage).err();
+            TestResult { name, failure }
+        })
+        .collect();
+
+    Ok((results, coverage))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Phase;
+
+    use super::*;
+
+    #[test]
+    fn test_run_tests() {
+        let src = r#"
+        let x = 2;
+        fn double(n: int) -> int { n * 2 }
+
+        // Not run by the tests
+        assert(false);
+
+        #[test]
+        fn doubles() {
+            assert_eq(double(x), 4);
+        }
+
+        #[test]
+        fn fails() {
+            assert_eq(double(x), 5);
+        }
+
+        // Each test has its own runtime
+        #[test]
+        fn assigns() {
+            x = 3;
+            assert_eq(x, 3);
+        }
+
+        #[test]
+        fn sees_initial_value() {
+            assert_eq(x, 2);
+        }
+        "#;
+
+        let results = run_tests(src, true).unwrap();
+        let names: Vec<&str> = results.iter().map(|res| res.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["doubles", "fails", "assigns", "sees_initial_value"]
+        );
+
+        let failed: Vec<&str> = results
+            .iter()
+            .filter(|res| res.failure.is_some())
+            .map(|res| res.name.as_str())
+            .collect();
+        assert_eq!(failed, vec!["fails"]);
+        assert_eq!(results[1].failure.as_ref().unwrap().phase, Phase::Runtime);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let src = r#"fn double(n: int) -> int {
+    n * 2
+}
+fn halve(n: int) -> int {
+    n / 2
+}
+#[test]
+fn doubles() {
+    assert_eq(double(2), 4);
+}
+#[test]
+fn doubles_again() {
+    assert_eq(double(3), 6);
+}
+"#;
+
+        let (results, coverage) = run_tests_with_coverage(src, true).unwrap();
+        assert!(results.iter().all(|res| res.failure.is_none()));
+        // halve is declared by both tests but never called, and each test function only runs in its own test
+        assert_eq!(coverage.lines.get(&2), Some(&2));
+        assert_eq!(coverage.lines.get(&4), Some(&2));
+        assert_eq!(coverage.lines.get(&5), Some(&0));
+        assert_eq!(coverage.lines.get(&9), Some(&1));
+        assert_eq!(coverage.lines.get(&13), Some(&1));
+    }
+
+    #[test]
+    fn test_no_tests_run_on_type_error() {
+        let err = run_tests("#[test] fn t() { let x: int = true; }", true).unwrap_err();
+        assert_eq!(err.phase, Phase::Type);
+    }
+}
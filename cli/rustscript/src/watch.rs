@@ -0,0 +1,66 @@
+use std::{path::PathBuf, time::SystemTime};
+
+/// Tracks when a file was last modified, to notice when it is saved.
+pub struct Watcher {
+    path: PathBuf,
+    /// The modification time when last checked, `None` if the file could not be read then.
+    modified: Option<SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(path: impl Into<PathBuf>) -> Watcher {
+        Watcher {
+            path: path.into(),
+            modified: None,
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+
+    /// Whether the file was modified since the last check, which is the case on the first check if it exists.
+    /// A file that was removed has not changed, so that it is picked up again once it is written back.
+    pub fn changed(&mut self) -> bool {
+        let modified = self.modified();
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_watcher() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join("rustscript_watch.rst");
+        let _ = std::fs::remove_file(&path);
+
+        let mut watcher = Watcher::new(&path);
+        assert!(!watcher.changed());
+
+        std::fs::write(&path, "1")?;
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        // Set the time rather than rewriting, as the resolution of modification times varies
+        let file = std::fs::File::options().write(true).open(&path)?;
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))?;
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        std::fs::remove_file(&path)?;
+        assert!(!watcher.changed());
+
+        Ok(())
+    }
+}
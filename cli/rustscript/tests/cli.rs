@@ -0,0 +1,428 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const RUSTSCRIPT_BINARY: &str = "rustscript";
+
+/// Write the program to a .rst file unique to the test, and run it.
+fn run_program(name: &str, src: &str) -> Result<assert_cmd::assert::Assert> {
+    run_program_with(name, src, &[])
+}
+
+/// Write the program to a .rst file unique to the test, and run rustscript on it with the args.
+fn run_program_with(name: &str, src: &str, args: &[&str]) -> Result<assert_cmd::assert::Assert> {
+    let file = std::env::temp_dir().join(format!("rustscript_cli_{}.rst", name));
+    std::fs::write(&file, src)?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg(&file).args(args);
+    let assert = cmd.assert();
+
+    std::fs::remove_file(&file)?;
+
+    Ok(assert)
+}
+
+#[test]
+fn file_doesnt_exist() -> Result<()> {
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+
+    cmd.arg("test/file/doesnt/exist.rst");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::starts_with("error[io]: File"));
+
+    Ok(())
+}
+
+#[test]
+fn file_not_rst() -> Result<()> {
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+
+    cmd.arg("Cargo.toml");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("does not have extension .rst"));
+
+    Ok(())
+}
+
+#[test]
+fn prints_final_value() -> Result<()> {
+    run_program(
+        "final_value",
+        "fn f(x: int) -> int { x * 2 } println(\"hi\"); f(21)",
+    )?
+    .success()
+    .stdout("hi\n42\n");
+
+    Ok(())
+}
+
+#[test]
+fn reports_errors_of_each_phase() -> Result<()> {
+    run_program("lex_error", "let x = `;")?
+        .failure()
+        .stderr(predicate::str::starts_with(
+            "error[lex]: Unrecognized token '`' at line 1, column 9",
+        ));
+
+    run_program("parse_error", "let x = ;")?
+        .failure()
+        .stderr(predicate::str::starts_with("error[parse]: "));
+
+    run_program("type_error", "let x: int = true;")?
+        .failure()
+        .stderr(predicate::str::starts_with("error[type]: "));
+
+    run_program("runtime_error", "let x = 2;\nassert(x > 3);")?
+        .failure()
+        .stderr(predicate::str::starts_with(
+            "error[runtime]: assertion failed: x > 3",
+        ))
+        .stderr(predicate::str::contains(
+            "  Runtime error in thread 1 at pc=",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn optimizes_with_stats() -> Result<()> {
+    let src = "let x = 1; x = 2; x = 3; x";
+
+    run_program_with("optimize", src, &["-O", "--opt-stats"])?
+        .success()
+        .stdout("3\n")
+        .stderr(predicate::str::contains("redundant-loads: 6 changes"))
+        .stderr(predicate::str::contains("dead-stores: 4 changes"))
+        .stderr(predicate::str::contains("jump-threading: 0 changes"))
+        .stderr(predicate::str::contains("superinstructions: 0 changes"));
+
+    run_program_with(
+        "optimize_pass",
+        src,
+        &["--pass", "dead-stores", "--opt-stats"],
+    )?
+    .success()
+    .stderr("dead-stores: 0 changes, 16 -> 16 instructions\n");
+
+    Ok(())
+}
+
+#[test]
+fn runs_register_backend() -> Result<()> {
+    let src = "let i = 0; let s = 0; loop i < 10 { s = s + i * 2; i = i + 1; } s";
+
+    run_program_with(
+        "register_backend",
+        src,
+        &["-O", "--backend", "register", "--opt-stats"],
+    )?
+    .success()
+    .stdout("90\n")
+    .stderr(predicate::str::contains("register backend: "));
+
+    Ok(())
+}
+
+#[test]
+fn repl_keeps_bindings() -> Result<()> {
+    let mut cmd = assert_cmd::Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+
+    cmd.arg("repl")
+        .write_stdin("let x = 2;\nlet y: bool = x;\nfn f(n: int) -> int { n + x }\nf(40)\n/exit\n");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\n42\n"))
+        .stderr("error[type]: 'y' has declared type bool but assigned type int\n");
+
+    Ok(())
+}
+
+#[test]
+fn emit_does_not_run() -> Result<()> {
+    run_program_with("emit", "println(\"hi\");", &["--emit", "bytecode"])?
+        .success()
+        .stdout(
+            predicate::str::contains("1 LDC(hi)\n").and(predicate::str::contains("\nhi\n").not()),
+        );
+
+    Ok(())
+}
+
+#[test]
+fn fmt_rewrites_files() -> Result<()> {
+    let dir = std::env::temp_dir().join("rustscript_cli_fmt");
+    std::fs::create_dir_all(dir.join("nested"))?;
+    let file = dir.join("nested").join("f.rst");
+    std::fs::write(&file, "let x=2;// two\nx")?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("fmt").arg("--check").arg(&dir);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::ends_with("f.rst is not formatted\n"));
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("fmt").arg(&dir);
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&file)?, "let x = 2; // two\nx\n");
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("fmt").arg("--check").arg(&file);
+    cmd.assert().success();
+
+    std::fs::remove_dir_all(&dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_runs_test_functions() -> Result<()> {
+    let src = r"
+    fn double(n: int) -> int { n * 2 }
+
+    #[test]
+    fn doubles() {
+        assert_eq(double(2), 4);
+    }
+
+    #[test]
+    fn fails() {
+        assert_eq(double(2), 5);
+    }
+    ";
+    let file = std::env::temp_dir().join("rustscript_cli_test.rst");
+    std::fs::write(&file, src)?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("test").arg(&file);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("test doubles ... ok"))
+        .stdout(predicate::str::contains("test fails ... FAILED"))
+        .stdout(predicate::str::contains("---- fails ----\nerror[runtime]:"))
+        .stdout(predicate::str::ends_with(
+            "test result: FAILED. 1 passed; 1 failed\n",
+        ));
+
+    std::fs::remove_file(&file)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_subcommand_reports_coverage() -> Result<()> {
+    let src = "fn double(n: int) -> int {\n    n * 2\n}\nfn unused() {\n    println(1);\n}\n#[test]\nfn doubles() {\n    assert_eq(double(2), 4);\n}\n";
+    let file = std::env::temp_dir().join("rustscript_cli_coverage.rst");
+    let lcov = std::env::temp_dir().join("rustscript_cli_coverage.info");
+    std::fs::write(&file, src)?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("test").arg(&file).arg("--coverage").arg(&lcov);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("coverage: 5 of 6 lines"));
+
+    let report = std::fs::read_to_string(&lcov)?;
+    assert!(report.contains(&format!("SF:{}\n", file.display())));
+    assert!(report.contains("DA:5,0\n"));
+    assert!(report.ends_with("LF:6\nLH:5\nend_of_record\n"));
+
+    std::fs::remove_file(&file)?;
+    std::fs::remove_file(&lcov)?;
+
+    Ok(())
+}
+
+#[test]
+fn objdump_describes_object_file() -> Result<()> {
+    let file = std::env::temp_dir().join("rustscript_cli_objdump.o2");
+    let instrs = vec![
+        bytecode::ByteCode::ldc(2),
+        bytecode::ByteCode::JOF(3),
+        bytecode::ByteCode::GOTO(0),
+        bytecode::ByteCode::DONE,
+    ];
+    bytecode::write_bytecode(&instrs, &mut std::fs::File::create(&file)?)?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("objdump").arg(&file);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "disassembly:\n  +---> 0 LDC(2)\n  | +-- 1 JOF(3)\n  +-|-- 2 GOTO(0)\n    +-> 3 DONE",
+    ));
+
+    // A truncated file does not match its header
+    let bytes = std::fs::read(&file)?;
+    std::fs::write(&file, &bytes[..bytes.len() - 1])?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("objdump").arg(&file);
+    cmd.assert().failure().stderr(predicate::str::starts_with(
+        "error[io]: Header gives a program of",
+    ));
+
+    std::fs::remove_file(&file)?;
+
+    Ok(())
+}
+
+#[test]
+fn upgrade_rewrites_old_object_file() -> Result<()> {
+    let file = std::env::temp_dir().join("rustscript_cli_upgrade.o2");
+    let instrs = vec![bytecode::ByteCode::ldc(42), bytecode::ByteCode::DONE];
+    bytecode::write_bytecode_version(&instrs, 1, &mut std::fs::File::create(&file)?)?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("objdump").arg(&file);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("header: version 1, 8 bytes"));
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("upgrade").arg(&file);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with(format!(
+            "from version 1 to version {}\n",
+            bytecode::O2_VERSION
+        )));
+
+    let obj = bytecode::read_object(&mut std::fs::File::open(&file)?)?;
+    assert_eq!(
+        (obj.version, obj.instrs),
+        (bytecode::O2_VERSION, instrs.clone())
+    );
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("upgrade").arg(&file);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with(format!(
+            "is already at version {}\n",
+            bytecode::O2_VERSION
+        )));
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("upgrade").arg(&file).args(["--compress", "zstd"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with(format!(
+            "to version {}, zstd compressed\n",
+            bytecode::O2_VERSION
+        )));
+
+    let obj = bytecode::read_object(&mut std::fs::File::open(&file)?)?;
+    assert_eq!(obj.compression, bytecode::Compression::Zstd);
+    assert_eq!(obj.instrs, instrs);
+
+    std::fs::remove_file(&file)?;
+
+    Ok(())
+}
+
+#[test]
+fn exit_status_of_script() -> Result<()> {
+    let src = "#!/usr/bin/env rustscript\nprintln(\"a\");\nexit(3);\nprintln(\"b\");";
+    run_program("exit", src)?
+        .code(3)
+        .stdout(predicate::eq("a\n"));
+
+    Ok(())
+}
+
+#[test]
+fn build_links_project() -> Result<()> {
+    let root = std::env::temp_dir().join("rustscript_cli_build");
+    std::fs::create_dir_all(root.join("lib"))?;
+    std::fs::write(
+        root.join("script.toml"),
+        "[project]\nname = \"hello\"\nentry = \"main.rst\"\ninclude = [\"lib\"]\n",
+    )?;
+    std::fs::write(
+        root.join("lib/double.rst"),
+        "fn double(n: int) -> int { n * 2 }",
+    )?;
+    std::fs::write(root.join("main.rst"), "println(double(21));")?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("build").arg(&root);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("Built").and(predicate::str::ends_with("hello.o2\n")));
+
+    let obj = bytecode::read_object(&mut std::fs::File::open(root.join("hello.o2"))?)?;
+    assert!(rustscript::pipeline::execute(&mut ignite::Runtime::new(obj.instrs)).is_ok());
+
+    // The build is recorded with the hash of the modules in the order they are linked
+    let metadata = obj.metadata.expect("build records metadata");
+    let srcs = ["fn double(n: int) -> int { n * 2 }", "println(double(21));"];
+    assert_eq!(
+        metadata.source_hash,
+        bytecode::Metadata::hash_sources(&srcs)
+    );
+    assert_eq!(metadata.compiler_version, compiler::VERSION);
+    assert_eq!(metadata.opt_level, 0);
+
+    // Included modules cannot run statements
+    std::fs::write(root.join("lib/double.rst"), "println(1);")?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("build").arg(&root);
+    cmd.assert().failure().stderr(predicate::str::starts_with(
+        "error[compile]: Included modules can only declare",
+    ));
+
+    std::fs::remove_dir_all(&root)?;
+
+    Ok(())
+}
+
+#[test]
+fn transpiles_to_rust() -> Result<()> {
+    let src = "fn fib(n: int) -> int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }\nlet xs = [x * 2 for x in 0..4];\nprint(xs);\nprintln(sqrt(2.0));\nfib(15)";
+    let file = std::env::temp_dir().join("rustscript_cli_transpile.rst");
+    let dir = std::env::temp_dir().join("rustscript_cli_transpile");
+    std::fs::write(&file, src)?;
+
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.arg("-O")
+        .arg("transpile")
+        .arg(&file)
+        .arg("-o")
+        .arg(&dir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("Transpiled"));
+    assert!(std::fs::read_to_string(dir.join("Cargo.toml"))?
+        .contains("name = \"rustscript_cli_transpile\""));
+
+    // Built with rustc alone, as the program has no dependencies
+    let bin = dir.join("transpiled");
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let status = Command::new(rustc)
+        .arg("--edition=2021")
+        .arg(dir.join("src/main.rs"))
+        .arg("-o")
+        .arg(&bin)
+        .status()?;
+    assert!(status.success());
+
+    let expected = "[0, 2, 4, 6]1.4142135623730951\n610\n";
+    Command::cargo_bin(RUSTSCRIPT_BINARY)?
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::eq(expected));
+    Command::new(&bin)
+        .assert()
+        .success()
+        .stdout(predicate::eq(expected));
+
+    std::fs::remove_file(&file)?;
+    std::fs::remove_dir_all(&dir)?;
+
+    Ok(())
+}
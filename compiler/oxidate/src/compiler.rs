@@ -2,16 +2,39 @@ use anyhow::Result;
 use std::{fmt::Display, rc::Rc, vec};
 use types::type_checker::TypeChecker;
 
-use bytecode::{BinOp, ByteCode, Value};
+use crate::debug_info::DebugInfo;
+
+use bytecode::{builtin, BinOp, ByteCode, Symbol, Value};
+use parser::named_args::resolve_named_args;
 use parser::structs::{
-    BinOpType, BlockSeq, Decl, Expr, FnCallData, FnDeclData, IfElseData, LoopData, UnOpType,
+    BinOpType, BlockSeq, BreakData, ComprehensionData, Decl, DestructurePattern, EnumDeclData,
+    Expr, FieldAssignData, FnCallData, FnDeclData, ForData, IfElseData, ImplData, Iterable,
+    LetDestructureData, LetStmtData, LockData, LoopData, MatchData, MethodCallData, Pattern,
+    SelectData, SelectOp, StructExprData, TryCatchData, UnOpType,
 };
 
+#[derive(Clone)]
 pub struct Compiler {
     program: BlockSeq,
-    // Tracks idx in bytecode for any nested break stmts compiled for that loop. Stack of vecs since we can have nested loops
-    // and break should only break the closest enclosing loop
-    loop_stack: Vec<Vec<usize>>,
+    // Loops enclosing the code being compiled, innermost last. Stack since we can have nested loops
+    // and break leaves the closest enclosing loop, or the closest one with its label
+    loops: Vec<LoopCtx>,
+    // Symbols of each frame the compiled code runs in, innermost last. Mirrors the environment chain at runtime
+    // so symbols can be resolved to (depth, index) slots. Anything not found here lives in the global frame.
+    scopes: Vec<Vec<Symbol>>,
+    // Where the code of each statement compiled so far starts
+    debug_info: DebugInfo,
+}
+
+// A loop being compiled, which break can leave
+#[derive(Clone)]
+struct LoopCtx {
+    label: Option<String>,
+    // Number of scopes entered when the loop started, so break can exit the scopes entered since
+    depth: usize,
+    // Tracks idx in bytecode of the GOTO of each break compiled for the loop, to patch once the end is known.
+    // Breaks with a value jump past the unit the loop produces, since their value is on the stack instead
+    breaks: Vec<(usize, bool)>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,6 +48,10 @@ impl CompileError {
             msg: err.to_owned(),
         }
     }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
 }
 
 impl Display for CompileError {
@@ -35,19 +62,84 @@ impl Display for CompileError {
 
 impl std::error::Error for CompileError {}
 
+// Intern a name of the program, failing the compilation rather than panicking once the interner is full,
+// e.g in a process that keeps compiling sources with new names
+fn intern(name: &str) -> Result<Symbol, CompileError> {
+    Symbol::try_intern(name).map_err(|err| CompileError::new(&err.to_string()))
+}
+
 // Workaround to ensure builtins that dont pop produce Unit when compiling fn call
 // Because user functions even if empty will produce unit (everything is value producing), so
 // this issue only applies to builtins with no value pushed
-const BUILTINS_WITH_NO_VAL: [&str; 3] = ["println", "print", "sem_set"];
+const BUILTINS_WITH_NO_VAL: [&str; 18] = [
+    "println",
+    "print",
+    "sem_set",
+    "kill",
+    "set_thread_local",
+    "set_priority",
+    "cv_wait",
+    "cv_notify_one",
+    "cv_notify_all",
+    "barrier_wait",
+    "wg_add",
+    "wg_done",
+    "wg_wait",
+    "close",
+    "push",
+    "store",
+    "assert",
+    "assert_eq",
+];
+
+// Symbols holding the value being matched, tried or destructured, and the parameter of the constructors of enum variants.
+// They can't be written in source, so they never clash with user symbols
+const MATCH_SYM: &str = "$match";
+const LET_SYM: &str = "$let";
+const TRY_SYM: &str = "$try";
+const VARIANT_VALUE_SYM: &str = "$value";
+// Symbol holding the iterator a comprehension or for loop takes its values from
+const ITER_SYM: &str = "$iter";
 
 impl Compiler {
     pub fn new(program: BlockSeq) -> Compiler {
         Compiler {
             program,
-            loop_stack: vec![],
+            loops: vec![],
+            scopes: vec![],
+            debug_info: DebugInfo::default(),
         }
     }
 
+    /// Resolve a symbol to the (depth, index) of its slot, searching from the innermost scope outward.
+    fn resolve(&self, sym: Symbol) -> Option<(usize, usize)> {
+        self.scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| scope.iter().position(|s| *s == sym).map(|idx| (depth, idx)))
+    }
+
+    /// Load a symbol by slot if it is resolved, otherwise by name from the global frame.
+    fn compile_ld(&self, sym: &str, arr: &mut Vec<ByteCode>) -> Result<(), CompileError> {
+        let sym = intern(sym)?;
+        match self.resolve(sym) {
+            Some((depth, idx)) => arr.push(ByteCode::LDSLOT(depth, idx)),
+            None => arr.push(ByteCode::ld(sym)),
+        }
+        Ok(())
+    }
+
+    /// Assign to a symbol by slot if it is resolved, otherwise by name in the global frame.
+    fn compile_st(&self, sym: &str, arr: &mut Vec<ByteCode>) -> Result<(), CompileError> {
+        let sym = intern(sym)?;
+        match self.resolve(sym) {
+            Some((depth, idx)) => arr.push(ByteCode::ASSIGNSLOT(depth, idx)),
+            None => arr.push(ByteCode::assign(sym)),
+        }
+        Ok(())
+    }
+
     fn compile_unop(
         &mut self,
         op: &UnOpType,
@@ -78,12 +170,16 @@ impl Compiler {
                     decls: vec![],
                     last_expr: Some(Rc::new(rhs.clone())),
                     symbols: vec![],
+                    decl_toks: vec![],
+                    last_expr_tok: None,
                 };
 
                 let else_blk = BlockSeq {
                     decls: vec![],
                     last_expr: Some(Rc::new(Expr::Bool(false))),
                     symbols: vec![],
+                    decl_toks: vec![],
+                    last_expr_tok: None,
                 };
 
                 let stmt = IfElseData {
@@ -101,12 +197,16 @@ impl Compiler {
                     decls: vec![],
                     last_expr: Some(Rc::new(Expr::Bool(true))),
                     symbols: vec![],
+                    decl_toks: vec![],
+                    last_expr_tok: None,
                 };
 
                 let else_blk = BlockSeq {
                     decls: vec![],
                     last_expr: Some(Rc::new(rhs.clone())),
                     symbols: vec![],
+                    decl_toks: vec![],
+                    last_expr_tok: None,
                 };
 
                 let stmt = IfElseData {
@@ -143,9 +243,12 @@ impl Compiler {
             BinOpType::Add => arr.push(ByteCode::BINOP(bytecode::BinOp::Add)),
             BinOpType::Mul => arr.push(ByteCode::BINOP(bytecode::BinOp::Mul)),
             BinOpType::Div => arr.push(ByteCode::BINOP(bytecode::BinOp::Div)),
+            BinOpType::Mod => arr.push(ByteCode::BINOP(BinOp::Mod)),
             BinOpType::Sub => arr.push(ByteCode::BINOP(bytecode::BinOp::Sub)),
             BinOpType::Gt => arr.push(ByteCode::BINOP(BinOp::Gt)),
             BinOpType::Lt => arr.push(ByteCode::BINOP(BinOp::Lt)),
+            BinOpType::Ge => arr.push(ByteCode::BINOP(BinOp::Ge)),
+            BinOpType::Le => arr.push(ByteCode::BINOP(BinOp::Le)),
             BinOpType::LogicalEq => arr.push(ByteCode::BINOP(BinOp::Eq)),
             // Rest are and/or: handled above
             _ => unreachable!(),
@@ -163,7 +266,7 @@ impl Compiler {
             Expr::Integer(val) => arr.push(ByteCode::ldc(*val)),
             Expr::Float(val) => arr.push(ByteCode::ldc(*val)),
             Expr::Bool(val) => arr.push(ByteCode::ldc(*val)),
-            Expr::StringLiteral(str) => arr.push(ByteCode::LDC(Value::String(str.to_owned()))),
+            Expr::StringLiteral(str) => arr.push(ByteCode::ldc(str.as_str())),
             Expr::BinOpExpr(op, lhs, rhs) => {
                 self.compile_binop(op, lhs, rhs, arr)?;
             }
@@ -172,45 +275,98 @@ impl Compiler {
             }
             // Load symbol
             Expr::Symbol(sym) => {
-                arr.push(ByteCode::LD(sym.to_string()));
+                self.compile_ld(sym, arr)?;
             }
             Expr::BlockExpr(blk) => {
                 self.compile_block(blk, arr)?;
             }
             Expr::IfElseExpr(if_else) => self.compile_if_else(if_else, arr)?,
             Expr::FnCallExpr(fn_call) => self.compile_fn_call(fn_call, arr)?,
-            Expr::SpawnExpr(fn_call) => self.compile_spawn(fn_call, arr)?,
+            Expr::MethodCallExpr(method_call) => self.compile_method_call(method_call, arr)?,
+            // The parent evaluates the function and its args, which are moved to the child to call
+            Expr::SpawnExpr(fn_call) => {
+                let arity = self.compile_fn_call_args(fn_call, arr)?;
+                self.compile_spawn(arity + 1, arr, |_, arr| {
+                    Compiler::compile_call(fn_call, arity, arr);
+                    Ok(())
+                })?
+            }
+            Expr::SpawnBlockExpr(blk) => {
+                self.compile_spawn(0, arr, |this, arr| this.compile_block(blk, arr))?
+            }
+            // the value of the block stays on the stack while the threads spawned in it are joined
+            Expr::ScopeExpr(blk) => {
+                arr.push(ByteCode::SPAWNSCOPE);
+                self.compile_block(blk, arr)?;
+                arr.push(ByteCode::JOINSCOPE);
+            }
+            Expr::SelectExpr(select) => self.compile_select(select, arr)?,
+            Expr::MatchExpr(match_data) => self.compile_match(match_data, arr)?,
+            Expr::LoopExpr(lp) => self.compile_loop(lp, arr)?,
+            Expr::TryExpr(expr) => self.compile_try(expr, arr)?,
+            Expr::TryCatchExpr(try_catch) => self.compile_try_catch(try_catch, arr)?,
+            Expr::LockExpr(lock) => self.compile_lock(lock, arr)?,
+            Expr::StructExpr(struct_expr) => self.compile_struct_expr(struct_expr, arr)?,
+            Expr::FieldExpr(expr, field) => {
+                self.compile_expr(expr, arr)?;
+                arr.push(ByteCode::LDFIELD(intern(field)?));
+            }
             Expr::JoinExpr(id) => {
-                arr.push(ByteCode::ld(id));
+                self.compile_ld(id, arr)?;
                 arr.push(ByteCode::JOIN);
             }
+            Expr::ArrayExpr(elems) => {
+                for elem in elems.iter() {
+                    self.compile_expr(elem, arr)?;
+                }
+                arr.push(ByteCode::NEWARRAY(elems.len()));
+            }
+            Expr::ComprehensionExpr(comp) => self.compile_comprehension(comp, arr)?,
+            Expr::IndexExpr(expr, idx) => {
+                self.compile_expr(expr, arr)?;
+                self.compile_expr(idx, arr)?;
+                arr.push(ByteCode::LDELEM);
+            }
+            // a missing bound is unit, so the slice is open on that side
+            Expr::SliceExpr(expr, start, end) => {
+                self.compile_expr(expr, arr)?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.compile_expr(bound, arr)?,
+                        None => arr.push(ByteCode::LDC(Value::Unit)),
+                    }
+                }
+                arr.push(ByteCode::SLICE);
+            }
         }
 
         Ok(())
     }
 
+    // The child thread starts with the top n values of the parent, runs body and finishes with its value,
+    // which join gives back to the parent
     fn compile_spawn(
         &mut self,
-        fn_call: &FnCallData,
+        n: usize,
         arr: &mut Vec<ByteCode>,
+        body: impl FnOnce(&mut Self, &mut Vec<ByteCode>) -> Result<(), CompileError>,
     ) -> Result<(), CompileError> {
-        // dbg!("SPAWN COMPILE:", _fn_call);
         let spawn_idx = arr.len();
-        arr.push(ByteCode::SPAWN(0));
+        arr.push(ByteCode::SPAWN(0, n));
 
         let goto_idx = arr.len();
         arr.push(ByteCode::GOTO(0));
 
         // spawn jumps to POP which is added after this
         let spawn_jmp = arr.len();
-        if let Some(ByteCode::SPAWN(jmp)) = arr.get_mut(spawn_idx) {
+        if let Some(ByteCode::SPAWN(jmp, _)) = arr.get_mut(spawn_idx) {
             *jmp = spawn_jmp;
         }
 
         // child pops value on its stack
         arr.push(ByteCode::POP);
 
-        self.compile_fn_call(fn_call, arr)?;
+        body(self, arr)?;
         arr.push(ByteCode::DONE); // child thread finishes
 
         let goto_jmp = arr.len();
@@ -225,14 +381,13 @@ impl Compiler {
 
     fn compile_assign(
         &mut self,
-        ident: &String,
+        ident: &str,
         expr: &Expr,
         arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
         self.compile_expr(expr, arr)?;
 
-        let assign = ByteCode::ASSIGN(ident.to_owned());
-        arr.push(assign);
+        self.compile_st(ident, arr)?;
 
         // Load unit after stmt to be consistent with popping after every stmt
         arr.push(ByteCode::LDC(Value::Unit));
@@ -246,27 +401,81 @@ impl Compiler {
         &mut self,
         blk: &BlockSeq,
         arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_scope(blk, arr, true)
+    }
+
+    /// Compile the block in a scope of its symbols, exiting the scope at the end if `exit` is set.
+    fn compile_scope(
+        &mut self,
+        blk: &BlockSeq,
+        arr: &mut Vec<ByteCode>,
+        exit: bool,
     ) -> Result<(), CompileError> {
         let decls = &blk.decls;
-        let syms = &blk.symbols;
+        let syms = blk
+            .symbols
+            .iter()
+            .map(|sym| intern(sym))
+            .collect::<Result<Vec<_>, _>>()?;
 
         if !syms.is_empty() {
             arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+            self.scopes.push(syms.clone());
         }
 
-        for decl in decls {
+        // Declare the structs, enums, their methods and the hoisted functions first, so they can be used anywhere in the block
+        let structs = decls
+            .iter()
+            .enumerate()
+            .filter(|(_, decl)| matches!(decl, Decl::StructDeclStmt(_)));
+        let enums = decls
+            .iter()
+            .enumerate()
+            .filter(|(_, decl)| matches!(decl, Decl::EnumDeclStmt(_)));
+        let impls = decls
+            .iter()
+            .enumerate()
+            .filter(|(_, decl)| matches!(decl, Decl::ImplStmt(_)));
+        let mut hoisted: Vec<usize> = structs
+            .chain(enums)
+            .chain(impls)
+            .map(|(idx, _)| idx)
+            .collect();
+        hoisted.extend(blk.hoisted_fns());
+
+        // Blocks made up by the compiler, or that lost their declarations' positions, are not located
+        let tok = |idx: usize| (blk.decl_toks.len() == decls.len()).then(|| blk.decl_toks[idx]);
+
+        for idx in hoisted.iter() {
+            let start = arr.len();
+            self.compile_decl(&decls[*idx], arr)?;
+            self.debug_info.add(start, arr.len(), tok(*idx));
+            arr.push(ByteCode::POP);
+        }
+
+        for (idx, decl) in decls.iter().enumerate() {
+            if hoisted.contains(&idx) {
+                continue;
+            }
+
+            let start = arr.len();
             self.compile_decl(decl, arr)?;
+            self.debug_info.add(start, arr.len(), tok(idx));
             // pop result of statements - need to ensure all stmts produce something (either Unit or something else)
             arr.push(ByteCode::POP);
         }
 
         // Handle expr
         if let Some(expr) = &blk.last_expr {
+            let start = arr.len();
             self.compile_expr(expr.as_ref(), arr)?;
+            self.debug_info.add(start, arr.len(), blk.last_expr_tok);
         }
 
-        if !syms.is_empty() {
+        if exit && !syms.is_empty() {
             arr.push(ByteCode::EXITSCOPE);
+            self.scopes.pop();
         }
 
         Ok(())
@@ -302,20 +511,28 @@ impl Compiler {
             Decl::LetStmt(stmt) => {
                 self.compile_assign(&stmt.ident, &stmt.expr, arr)?;
             }
+            Decl::LetDestructureStmt(stmt) => self.compile_let_destructure(stmt, arr)?,
             Decl::AssignStmt(stmt) => {
                 self.compile_assign(&stmt.ident, &stmt.expr, arr)?;
             }
+            Decl::FieldAssignStmt(stmt) => self.compile_field_assign(stmt, arr)?,
             Decl::IfOnlyStmt(if_else) => self.compile_if_else(if_else, arr)?,
             Decl::LoopStmt(lp) => self.compile_loop(lp, arr)?,
-            // push GOTO, push idx of this break in arr onto loop stack
-            Decl::BreakStmt => {
-                let break_idx = arr.len();
-                arr.push(ByteCode::GOTO(0));
-                if let Some(breaks) = self.loop_stack.last_mut() {
-                    breaks.push(break_idx);
-                }
-            }
+            Decl::ForStmt(lp) => self.compile_for(lp, arr)?,
+            Decl::BreakStmt(brk) => self.compile_break(brk, arr)?,
             Decl::FnDeclStmt(fn_decl) => self.compile_fn_decl(fn_decl, arr)?,
+            Decl::StructDeclStmt(struct_decl) => {
+                let fields = struct_decl
+                    .fields
+                    .iter()
+                    .map(|(field, _)| intern(field))
+                    .collect::<Result<_, _>>()?;
+                arr.push(ByteCode::STRUCT(intern(&struct_decl.name)?, fields));
+                self.compile_st(&struct_decl.name, arr)?;
+                arr.push(ByteCode::ldc(Value::Unit));
+            }
+            Decl::EnumDeclStmt(enum_decl) => self.compile_enum_decl(enum_decl, arr)?,
+            Decl::ImplStmt(impl_data) => self.compile_impl(impl_data, arr)?,
             Decl::ReturnStmt(ret_stmt) => {
                 // compile expr. if not there, push Unit
                 if let Some(expr) = ret_stmt {
@@ -329,12 +546,12 @@ impl Compiler {
             }
             // These don't return anything, so push unit after as well
             Decl::WaitStmt(sem) => {
-                arr.push(ByteCode::ld(sem));
+                self.compile_ld(sem, arr)?;
                 arr.push(ByteCode::WAIT);
                 arr.push(ByteCode::ldc(Value::Unit));
             }
             Decl::PostStmt(sem) => {
-                arr.push(ByteCode::ld(sem));
+                self.compile_ld(sem, arr)?;
                 arr.push(ByteCode::POST);
                 arr.push(ByteCode::ldc(Value::Unit));
             }
@@ -342,6 +559,11 @@ impl Compiler {
                 arr.push(ByteCode::YIELD);
                 arr.push(ByteCode::ldc(Value::Unit));
             }
+            Decl::YieldValueStmt(expr) => {
+                self.compile_expr(expr, arr)?;
+                arr.push(ByteCode::SUSPEND);
+                arr.push(ByteCode::ldc(Value::Unit));
+            }
         };
 
         Ok(())
@@ -351,13 +573,36 @@ impl Compiler {
         &mut self,
         fn_decl: &FnDeclData,
         arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_fn(fn_decl, arr)?;
+
+        // ASSIGN pops closure and then we load Unit so no underflow
+        self.compile_st(&fn_decl.name, arr)?;
+        arr.push(ByteCode::ldc(Value::Unit));
+
+        Ok(())
+    }
+
+    /// Push the closure of the function, skipping over its body
+    fn compile_fn(
+        &mut self,
+        fn_decl: &FnDeclData,
+        arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
         // we are about to push LDF and GOTO before fn compile
         let fn_start_idx = arr.len() + 2;
 
-        let param_strs: Vec<String> = fn_decl.params.iter().map(|x| x.name.to_string()).collect();
+        let param_syms = fn_decl
+            .params
+            .iter()
+            .map(|x| intern(&x.name))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        arr.push(ByteCode::ldf(fn_start_idx, param_strs));
+        arr.push(ByteCode::LDF(
+            fn_start_idx,
+            intern(&fn_decl.name)?,
+            param_syms.clone(),
+        ));
 
         // push GOTO for skipping fn compile
         let goto_idx = arr.len();
@@ -370,16 +615,23 @@ impl Compiler {
 
         // compile the augmented blk
 
-        self.compile_block(&fn_decl.body, arr)?;
+        // A generator returns with its generator straight away, running the rest of the body when resumed
+        if fn_decl.is_generator {
+            arr.push(ByteCode::GENERATOR);
+        }
+
+        // CALL always creates a frame for the params, even if there are none
+        self.scopes.push(param_syms);
+        let compiled = self.compile_block(&fn_decl.body, arr);
+        self.scopes.pop();
+        compiled?;
         // self.compile_block(&fn_blk, arr)?;
 
         // push reset to return last value produced by blk, in case no return was there
         arr.push(ByteCode::RESET(bytecode::FrameType::CallFrame));
 
-        // GOTO will jump to ASSIGN, ASSIGN pops closure and then we load Unit so no underflow
+        // GOTO will jump to after the body, with the closure on the stack
         let goto_addr = arr.len();
-        arr.push(ByteCode::assign(&fn_decl.name));
-        arr.push(ByteCode::ldc(Value::Unit));
 
         // patch GOTO
         if let Some(ByteCode::GOTO(idx)) = arr.get_mut(goto_idx) {
@@ -395,19 +647,226 @@ impl Compiler {
         fn_call: &FnCallData,
         arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
+        let arity = self.compile_fn_call_args(fn_call, arr)?;
+        Compiler::compile_call(fn_call, arity, arr);
+        Ok(())
+    }
+
+    /// Push the function and its args, returning the number of args pushed.
+    fn compile_fn_call_args(
+        &mut self,
+        fn_call: &FnCallData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<usize, CompileError> {
         // TODO: change to accept arbitary expr for fn
         self.compile_expr(&Expr::Symbol(fn_call.name.clone()), arr)?;
 
-        for arg in fn_call.args.iter() {
-            self.compile_expr(arg, arr)?;
-        }
+        let arity = match fn_call.name.as_str() {
+            "assert" | "assert_eq" => self.compile_assert_args(fn_call, arr)?,
+            _ => {
+                for arg in fn_call.args.iter() {
+                    self.compile_expr(arg, arr)?;
+                }
+                fn_call.args.len()
+            }
+        };
+
+        Ok(arity)
+    }
 
-        arr.push(ByteCode::CALL(fn_call.args.len()));
+    /// Call the function pushed along with its args by compile_fn_call_args.
+    fn compile_call(fn_call: &FnCallData, arity: usize, arr: &mut Vec<ByteCode>) {
+        arr.push(ByteCode::CALL(arity));
 
         // push unit for builtin that produces no value
         if BUILTINS_WITH_NO_VAL.contains(&fn_call.name.as_str()) {
             arr.push(ByteCode::ldc(Value::Unit));
         }
+    }
+
+    /// Compile the args of assert or assert_eq along with the source text of the asserted expressions,
+    /// so a failed assertion can report what was asserted. Returns the number of args pushed.
+    ///
+    /// Both always take four args. assert(lhs < rhs) pushes lhs, rhs, the operator and the text so
+    /// the VM can report both sides, any other condition pushes the condition, unit, unit and the text.
+    /// assert_eq(left, right) pushes left, right and the text of each.
+    fn compile_assert_args(
+        &mut self,
+        fn_call: &FnCallData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<usize, CompileError> {
+        match (fn_call.name.as_str(), fn_call.args.as_slice()) {
+            ("assert", [Expr::BinOpExpr(op, lhs, rhs)])
+                if matches!(
+                    op,
+                    BinOpType::Gt
+                        | BinOpType::Lt
+                        | BinOpType::Ge
+                        | BinOpType::Le
+                        | BinOpType::LogicalEq
+                ) =>
+            {
+                self.compile_expr(lhs, arr)?;
+                self.compile_expr(rhs, arr)?;
+                arr.push(ByteCode::ldc(op.to_string()));
+                arr.push(ByteCode::ldc(format!("{} {} {}", lhs, op, rhs)));
+                Ok(4)
+            }
+            ("assert", [cond]) => {
+                self.compile_expr(cond, arr)?;
+                arr.push(ByteCode::ldc(Value::Unit));
+                arr.push(ByteCode::ldc(Value::Unit));
+                arr.push(ByteCode::ldc(cond.to_string()));
+                Ok(4)
+            }
+            ("assert_eq", [left, right]) => {
+                self.compile_expr(left, arr)?;
+                self.compile_expr(right, arr)?;
+                arr.push(ByteCode::ldc(left.to_string()));
+                arr.push(ByteCode::ldc(right.to_string()));
+                Ok(4)
+            }
+            // Wrong number of args is left for the VM to report
+            _ => {
+                for arg in fn_call.args.iter() {
+                    self.compile_expr(arg, arr)?;
+                }
+                Ok(fn_call.args.len())
+            }
+        }
+    }
+
+    /// Method call expression e.g h.is_finished() or p.norm()
+    /// The method is looked up on the receiver at runtime, and called with the receiver as the first argument.
+    fn compile_method_call(
+        &mut self,
+        method_call: &MethodCallData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_expr(&method_call.recv, arr)?;
+        arr.push(ByteCode::LDMETHOD(intern(&method_call.method)?));
+
+        for arg in method_call.args.iter() {
+            self.compile_expr(arg, arr)?;
+        }
+
+        arr.push(ByteCode::CALL(method_call.args.len() + 1));
+
+        Ok(())
+    }
+
+    /// Struct expression e.g Point { x: 1, y: 2 }, or Point { x: 1, ..p } which updates a copy of p
+    fn compile_struct_expr(
+        &mut self,
+        struct_expr: &StructExprData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        if let Some(base) = &struct_expr.base {
+            self.compile_expr(base, arr)?;
+            for (field, expr) in struct_expr.fields.iter() {
+                self.compile_expr(expr, arr)?;
+                arr.push(ByteCode::SETFIELD(intern(field)?));
+            }
+
+            return Ok(());
+        }
+
+        self.compile_ld(&struct_expr.name, arr)?;
+        for (_, expr) in struct_expr.fields.iter() {
+            self.compile_expr(expr, arr)?;
+        }
+
+        let fields = struct_expr
+            .fields
+            .iter()
+            .map(|(field, _)| intern(field))
+            .collect::<Result<_, _>>()?;
+        arr.push(ByteCode::NEWSTRUCT(fields));
+
+        Ok(())
+    }
+
+    /// Assignment to a field e.g p.pos.x = 2
+    /// Structs are immutable, so p.pos is updated with the new x and p with the new pos, which is stored back in p.
+    fn compile_field_assign(
+        &mut self,
+        stmt: &FieldAssignData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        // p, p.pos
+        for depth in 0..stmt.fields.len() {
+            self.compile_ld(&stmt.ident, arr)?;
+            for field in stmt.fields[..depth].iter() {
+                arr.push(ByteCode::LDFIELD(intern(field)?));
+            }
+        }
+
+        self.compile_expr(&stmt.expr, arr)?;
+
+        for field in stmt.fields.iter().rev() {
+            arr.push(ByteCode::SETFIELD(intern(field)?));
+        }
+
+        self.compile_st(&stmt.ident, arr)?;
+        arr.push(ByteCode::ldc(Value::Unit));
+
+        Ok(())
+    }
+
+    /// Bind each variant of the enum to its constructor, a function that pushes the variant holding its argument,
+    /// or to the variant itself if it holds nothing.
+    fn compile_enum_decl(
+        &mut self,
+        enum_decl: &EnumDeclData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let ty = intern(&enum_decl.name)?;
+
+        for (variant, held) in enum_decl.variants.iter() {
+            let sym = intern(variant)?;
+
+            if held.is_none() {
+                arr.push(ByteCode::NEWVARIANT(ty, sym, false));
+                self.compile_st(variant, arr)?;
+                continue;
+            }
+
+            // fn Circle(value) { NEWVARIANT(Shape, Circle) }
+            let prms = vec![intern(VARIANT_VALUE_SYM)?];
+            arr.push(ByteCode::LDF(arr.len() + 2, sym, prms.clone()));
+            let goto_idx = arr.len();
+            arr.push(ByteCode::GOTO(0));
+
+            self.scopes.push(prms);
+            self.compile_ld(VARIANT_VALUE_SYM, arr)?;
+            self.scopes.pop();
+            arr.push(ByteCode::NEWVARIANT(ty, sym, true));
+            arr.push(ByteCode::RESET(bytecode::FrameType::CallFrame));
+
+            let after = arr.len();
+            if let Some(ByteCode::GOTO(idx)) = arr.get_mut(goto_idx) {
+                *idx = after;
+            }
+            self.compile_st(variant, arr)?;
+        }
+
+        arr.push(ByteCode::ldc(Value::Unit));
+        Ok(())
+    }
+
+    /// Add the methods of the impl block to the struct type they are declared for
+    fn compile_impl(
+        &mut self,
+        impl_data: &ImplData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        for method in impl_data.methods.iter() {
+            self.compile_ld(&impl_data.name, arr)?;
+            self.compile_fn(method, arr)?;
+            arr.push(ByteCode::SETMETHOD(intern(&method.name)?));
+        }
+
+        arr.push(ByteCode::ldc(Value::Unit));
 
         Ok(())
     }
@@ -449,11 +908,645 @@ impl Compiler {
         Ok(())
     }
 
+    /// Load the operands of each arm, then SELECT jumps to the arm that is ready, e.g whose semaphore was acquired.
+    /// A recv or send arm starts with its result on the operand stack, which is bound in a scope of its own if named.
+    fn compile_select(
+        &mut self,
+        select: &SelectData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let mut ops: Vec<bytecode::SelectOp> = vec![];
+        for arm in select.arms.iter() {
+            let op = match &arm.op {
+                SelectOp::Wait(sem) => {
+                    self.compile_ld(sem, arr)?;
+                    bytecode::SelectOp::Wait
+                }
+                SelectOp::Recv(ch) => {
+                    self.compile_ld(ch, arr)?;
+                    bytecode::SelectOp::Recv
+                }
+                SelectOp::Send(ch, val) => {
+                    self.compile_ld(ch, arr)?;
+                    self.compile_expr(val, arr)?;
+                    bytecode::SelectOp::Send
+                }
+            };
+            ops.push(op);
+        }
+
+        let select_idx = arr.len();
+        arr.push(ByteCode::SELECT(vec![]));
+
+        let mut arm_addrs: Vec<usize> = vec![];
+        let mut goto_idxs: Vec<usize> = vec![];
+        for arm in select.arms.iter() {
+            arm_addrs.push(arr.len());
+            match &arm.bind {
+                Some(bind) => {
+                    let syms = vec![intern(bind)?];
+                    arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+                    self.scopes.push(syms);
+                    self.compile_st(bind, arr)?;
+
+                    let res = self.compile_block(&arm.blk, arr);
+                    arr.push(ByteCode::EXITSCOPE);
+                    self.scopes.pop();
+                    res?;
+                }
+                None => {
+                    if !matches!(arm.op, SelectOp::Wait(_)) {
+                        arr.push(ByteCode::POP);
+                    }
+                    self.compile_block(&arm.blk, arr)?;
+                }
+            }
+
+            goto_idxs.push(arr.len());
+            arr.push(ByteCode::GOTO(0));
+        }
+
+        // set SELECT args to the start of each arm, a select on semaphores only being SELECT as older versions have it
+        arr[select_idx] = if ops.iter().all(|op| *op == bytecode::SelectOp::Wait) {
+            ByteCode::SELECT(arm_addrs)
+        } else {
+            ByteCode::SELECTARMS(ops.into_iter().zip(arm_addrs).collect())
+        };
+
+        // GOTO after the select once an arm is done executing
+        let len = arr.len();
+        for idx in goto_idxs {
+            if let Some(ByteCode::GOTO(end_idx)) = arr.get_mut(idx) {
+                *end_idx = len;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compile a destructuring let as taking each part of the value, which is bound in a scope of its own,
+    /// and assigning it to its name in the enclosing scope. The length of an array is checked first,
+    /// and a struct without a field raises an error when the field is loaded.
+    ///
+    /// let [a, ..rest] = x;
+    /// => x ENTERSCOPE [$let] ASSIGN $let LD $let CHECKLEN(1, true)
+    ///    LD $let LDC 0 LDELEM ASSIGN a LD $let LDC 1 LDC () SLICE ASSIGN rest EXITSCOPE
+    ///
+    /// let P { x, y: b } = p;
+    /// => p ENTERSCOPE [$let] ASSIGN $let LD $let LDFIELD x ASSIGN x LD $let LDFIELD y ASSIGN b EXITSCOPE
+    fn compile_let_destructure(
+        &mut self,
+        stmt: &LetDestructureData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_expr(&stmt.expr, arr)?;
+
+        let syms = vec![intern(LET_SYM)?];
+        arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+        self.scopes.push(syms);
+        self.compile_st(LET_SYM, arr)?;
+
+        match &stmt.pat {
+            DestructurePattern::Array(elems, rest) => {
+                self.compile_ld(LET_SYM, arr)?;
+                arr.push(ByteCode::CHECKLEN(elems.len(), rest.is_some()));
+
+                for (idx, name) in elems.iter().enumerate() {
+                    self.compile_ld(LET_SYM, arr)?;
+                    arr.push(ByteCode::ldc(idx as i64));
+                    arr.push(ByteCode::LDELEM);
+                    self.compile_st(name, arr)?;
+                }
+
+                if let Some(rest) = rest {
+                    self.compile_ld(LET_SYM, arr)?;
+                    arr.push(ByteCode::ldc(elems.len() as i64));
+                    arr.push(ByteCode::ldc(Value::Unit));
+                    arr.push(ByteCode::SLICE);
+                    self.compile_st(rest, arr)?;
+                }
+            }
+            DestructurePattern::Struct(_, fields, _) => {
+                for (field, name) in fields.iter() {
+                    self.compile_ld(LET_SYM, arr)?;
+                    arr.push(ByteCode::LDFIELD(intern(field)?));
+                    self.compile_st(name, arr)?;
+                }
+            }
+        }
+
+        arr.push(ByteCode::EXITSCOPE);
+        self.scopes.pop();
+
+        arr.push(ByteCode::ldc(Value::Unit));
+        Ok(())
+    }
+
+    /// Compile match as a chain of tests of the matched value, which is bound in a scope of its own.
+    /// Each arm tests its pattern, jumping to the next arm at the first test that fails: the variant of the value,
+    /// or of the value it holds for nested patterns, its equality with a literal, or the bounds of a range.
+    /// The alternatives of a pattern are tested in turn and share the block of the arm.
+    /// The last arm is not tested since the type checker ensures the arms without a guard match every value.
+    /// The names the pattern binds are bound in a scope around the guard and the block of the arm.
+    /// A false guard leaves that scope and falls through to the next arm.
+    ///
+    /// match x { Circle(r) if r > 1 => { .. } Empty => { .. } }
+    /// => ENTERSCOPE [$match] x ASSIGN $match
+    ///    LD $match TESTVARIANT Circle JOF empty ENTERSCOPE [r] LD $match LDPAYLOAD ASSIGN r
+    ///    r > 1 JOF guard_false { .. } EXITSCOPE GOTO end
+    ///    guard_false: EXITSCOPE
+    ///    empty: { .. }
+    ///    end: EXITSCOPE
+    fn compile_match(
+        &mut self,
+        match_data: &MatchData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let match_syms = vec![intern(MATCH_SYM)?];
+        arr.push(ByteCode::ENTERSCOPE(match_syms.clone()));
+        self.scopes.push(match_syms);
+
+        self.compile_expr(&match_data.expr, arr)?;
+        self.compile_st(MATCH_SYM, arr)?;
+
+        let mut end_jumps = vec![];
+        for (idx, arm) in match_data.arms.iter().enumerate() {
+            let last = idx == match_data.arms.len() - 1;
+
+            let mut next_arm_jumps = vec![];
+            if !last {
+                self.compile_pattern_test(&arm.pat, 0, arr, &mut next_arm_jumps)?;
+            }
+
+            let names = arm.pat.bindings();
+            if names.is_empty() {
+                if let Some(guard) = &arm.guard {
+                    self.compile_expr(guard, arr)?;
+                    next_arm_jumps.push(arr.len());
+                    arr.push(ByteCode::JOF(0));
+                }
+
+                self.compile_block(&arm.blk, arr)?;
+                if !last || arm.guard.is_some() {
+                    end_jumps.push(arr.len());
+                    arr.push(ByteCode::GOTO(0));
+                }
+            } else {
+                let syms = names
+                    .iter()
+                    .map(|name| intern(name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+                self.scopes.push(syms);
+
+                self.compile_pattern_binds(&arm.pat, 0, arr)?;
+
+                let guard_jump = match &arm.guard {
+                    Some(guard) => {
+                        self.compile_expr(guard, arr)?;
+                        arr.push(ByteCode::JOF(0));
+                        Some(arr.len() - 1)
+                    }
+                    None => None,
+                };
+
+                self.compile_block(&arm.blk, arr)?;
+                arr.push(ByteCode::EXITSCOPE);
+                self.scopes.pop();
+
+                // a false guard leaves the scope of the binding before going on to the next arm
+                if let Some(idx) = guard_jump {
+                    end_jumps.push(arr.len());
+                    arr.push(ByteCode::GOTO(0));
+
+                    let guard_false = arr.len();
+                    if let Some(ByteCode::JOF(addr)) = arr.get_mut(idx) {
+                        *addr = guard_false;
+                    }
+                    arr.push(ByteCode::EXITSCOPE);
+                } else if !last {
+                    end_jumps.push(arr.len());
+                    arr.push(ByteCode::GOTO(0));
+                }
+            }
+
+            // a value the pattern doesn't match or a false guard jumps to the next arm
+            let next_arm = arr.len();
+            for idx in next_arm_jumps {
+                if let Some(ByteCode::JOF(addr)) = arr.get_mut(idx) {
+                    *addr = next_arm;
+                }
+            }
+        }
+
+        let end = arr.len();
+        for idx in end_jumps {
+            if let Some(ByteCode::GOTO(addr)) = arr.get_mut(idx) {
+                *addr = end;
+            }
+        }
+
+        arr.push(ByteCode::EXITSCOPE);
+        self.scopes.pop();
+
+        Ok(())
+    }
+
+    /// Load the matched value, or the value nested depth variants inside it
+    fn compile_ld_matched(
+        &self,
+        depth: usize,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_ld(MATCH_SYM, arr)?;
+        for _ in 0..depth {
+            arr.push(ByteCode::LDPAYLOAD);
+        }
+        Ok(())
+    }
+
+    /// Test the value depth variants inside the matched value against the pattern,
+    /// adding the JOFs to patch to where a value the pattern doesn't match goes.
+    ///
+    /// Some(1 | 2) => LD $match TESTVARIANT Some JOF fail
+    ///                LD $match LDPAYLOAD LDC 1 BINOP Eq JOF two GOTO matched
+    ///                two: LD $match LDPAYLOAD LDC 2 BINOP Eq JOF fail
+    ///                matched: ..
+    fn compile_pattern_test(
+        &mut self,
+        pat: &Pattern,
+        depth: usize,
+        arr: &mut Vec<ByteCode>,
+        fail_jumps: &mut Vec<usize>,
+    ) -> Result<(), CompileError> {
+        match pat {
+            Pattern::Wildcard | Pattern::Bind(_) => (),
+            Pattern::Literal(lit) => {
+                self.compile_ld_matched(depth, arr)?;
+                self.compile_expr(lit, arr)?;
+                arr.push(ByteCode::BINOP(BinOp::Eq));
+                fail_jumps.push(arr.len());
+                arr.push(ByteCode::JOF(0));
+            }
+            Pattern::Range(start, end) => {
+                for (bound, op) in [(start, BinOp::Ge), (end, BinOp::Le)] {
+                    self.compile_ld_matched(depth, arr)?;
+                    arr.push(ByteCode::ldc(*bound));
+                    arr.push(ByteCode::BINOP(op));
+                    fail_jumps.push(arr.len());
+                    arr.push(ByteCode::JOF(0));
+                }
+            }
+            Pattern::At(_, pat) => self.compile_pattern_test(pat, depth, arr, fail_jumps)?,
+            Pattern::Variant(variant, sub) => {
+                self.compile_ld_matched(depth, arr)?;
+                arr.push(ByteCode::TESTVARIANT(intern(variant.as_str())?));
+                fail_jumps.push(arr.len());
+                arr.push(ByteCode::JOF(0));
+
+                if let Some(sub) = sub {
+                    self.compile_pattern_test(sub, depth + 1, arr, fail_jumps)?;
+                }
+            }
+            Pattern::Or(alts) => {
+                let mut matched_jumps = vec![];
+                for (idx, alt) in alts.iter().enumerate() {
+                    // the last alternative fails the whole pattern, the others go on to the next alternative
+                    if idx == alts.len() - 1 {
+                        self.compile_pattern_test(alt, depth, arr, fail_jumps)?;
+                        break;
+                    }
+
+                    let mut alt_fail_jumps = vec![];
+                    self.compile_pattern_test(alt, depth, arr, &mut alt_fail_jumps)?;
+                    matched_jumps.push(arr.len());
+                    arr.push(ByteCode::GOTO(0));
+
+                    let next_alt = arr.len();
+                    for idx in alt_fail_jumps {
+                        if let Some(ByteCode::JOF(addr)) = arr.get_mut(idx) {
+                            *addr = next_alt;
+                        }
+                    }
+                }
+
+                let matched = arr.len();
+                for idx in matched_jumps {
+                    if let Some(ByteCode::GOTO(addr)) = arr.get_mut(idx) {
+                        *addr = matched;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Assign the names the pattern binds, once the value depth variants inside the matched value matched it.
+    /// Alternatives binding names at different depths are tested again to tell which one matched.
+    fn compile_pattern_binds(
+        &mut self,
+        pat: &Pattern,
+        depth: usize,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        match pat {
+            Pattern::Wildcard
+            | Pattern::Literal(_)
+            | Pattern::Range(..)
+            | Pattern::Variant(_, None) => (),
+            Pattern::Bind(name) => {
+                self.compile_ld_matched(depth, arr)?;
+                self.compile_st(name, arr)?;
+            }
+            Pattern::At(name, pat) => {
+                self.compile_ld_matched(depth, arr)?;
+                self.compile_st(name, arr)?;
+                self.compile_pattern_binds(pat, depth, arr)?;
+            }
+            Pattern::Variant(_, Some(sub)) => self.compile_pattern_binds(sub, depth + 1, arr)?,
+            Pattern::Or(alts) => {
+                let depths: Vec<_> = alts
+                    .iter()
+                    .map(|alt| Self::bind_depths(alt, depth))
+                    .collect();
+                if depths.iter().all(|d| d.is_some() && *d == depths[0]) {
+                    return self.compile_pattern_binds(&alts[0], depth, arr);
+                }
+
+                let mut bound_jumps = vec![];
+                for (idx, alt) in alts.iter().enumerate() {
+                    if idx == alts.len() - 1 {
+                        self.compile_pattern_binds(alt, depth, arr)?;
+                        break;
+                    }
+
+                    let mut alt_fail_jumps = vec![];
+                    self.compile_pattern_test(alt, depth, arr, &mut alt_fail_jumps)?;
+                    self.compile_pattern_binds(alt, depth, arr)?;
+                    bound_jumps.push(arr.len());
+                    arr.push(ByteCode::GOTO(0));
+
+                    let next_alt = arr.len();
+                    for idx in alt_fail_jumps {
+                        if let Some(ByteCode::JOF(addr)) = arr.get_mut(idx) {
+                            *addr = next_alt;
+                        }
+                    }
+                }
+
+                let bound = arr.len();
+                for idx in bound_jumps {
+                    if let Some(ByteCode::GOTO(addr)) = arr.get_mut(idx) {
+                        *addr = bound;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The depths of the values the names of the pattern are bound to, sorted by name.
+    /// None if alternatives of the pattern bind them at different depths.
+    fn bind_depths(pat: &Pattern, depth: usize) -> Option<Vec<(String, usize)>> {
+        match pat {
+            Pattern::Wildcard
+            | Pattern::Literal(_)
+            | Pattern::Range(..)
+            | Pattern::Variant(_, None) => Some(vec![]),
+            Pattern::Bind(name) => Some(vec![(name.to_string(), depth)]),
+            Pattern::At(name, pat) => {
+                let mut depths = Self::bind_depths(pat, depth)?;
+                depths.push((name.to_string(), depth));
+                Some(depths)
+            }
+            Pattern::Variant(_, Some(sub)) => Self::bind_depths(sub, depth + 1),
+            Pattern::Or(alts) => {
+                let mut depths = alts.iter().map(|alt| Self::bind_depths(alt, depth));
+                let first = depths.next()??;
+                if depths.all(|d| d.as_ref() == Some(&first)) {
+                    return Some(first);
+                }
+                None
+            }
+        }
+        .map(|mut depths| {
+            depths.sort();
+            depths
+        })
+    }
+
+    /// Compile a comprehension as a loop appending to the array on the operand stack.
+    /// The iterable is evaluated once, before the loop, into an iterator the loop takes values from.
+    ///
+    /// [e for x in it if c]
+    /// => NEWARRAY(0) ENTERSCOPE [x, $iter] it ASSIGN $iter
+    ///    loop: LD $iter NEXT(end) ASSIGN x c JOF loop e APPEND GOTO loop
+    ///    end: EXITSCOPE
+    fn compile_comprehension(
+        &mut self,
+        comp: &ComprehensionData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        arr.push(ByteCode::NEWARRAY(0));
+
+        let syms = vec![intern(&comp.var)?, intern(ITER_SYM)?];
+        arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+        self.scopes.push(syms);
+
+        let res = self.compile_comprehension_loop(comp, arr);
+
+        arr.push(ByteCode::EXITSCOPE);
+        self.scopes.pop();
+
+        res
+    }
+
+    fn compile_comprehension_loop(
+        &mut self,
+        comp: &ComprehensionData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_iterable(&comp.iter, arr)?;
+
+        let loop_start = arr.len();
+        let next_idx = self.compile_next(&comp.var, arr)?;
+
+        // a false condition skips the element
+        if let Some(cond) = &comp.cond {
+            self.compile_expr(cond, arr)?;
+            arr.push(ByteCode::JOF(loop_start));
+        }
+
+        self.compile_expr(&comp.expr, arr)?;
+        arr.push(ByteCode::APPEND);
+        arr.push(ByteCode::GOTO(loop_start));
+
+        let end = arr.len();
+        if let Some(ByteCode::NEXT(addr)) = arr.get_mut(next_idx) {
+            *addr = end;
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate the iterable into an iterator over its values, and assign it to $iter.
+    ///
+    /// lo..hi => lo hi ITERRANGE ASSIGN $iter
+    /// xs => xs ITER ASSIGN $iter
+    fn compile_iterable(
+        &mut self,
+        iter: &Iterable,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        match iter {
+            Iterable::Range(start, end) => {
+                self.compile_expr(start, arr)?;
+                self.compile_expr(end, arr)?;
+                arr.push(ByteCode::ITERRANGE);
+            }
+            Iterable::Collection(coll) => {
+                self.compile_expr(coll, arr)?;
+                arr.push(ByteCode::ITER);
+            }
+        }
+
+        self.compile_st(ITER_SYM, arr)?;
+        Ok(())
+    }
+
+    /// Assign the next value of $iter to the variable, returning the index of the NEXT to patch with the end of the loop.
+    ///
+    /// => LD $iter NEXT(end) ASSIGN x
+    fn compile_next(&self, var: &str, arr: &mut Vec<ByteCode>) -> Result<usize, CompileError> {
+        self.compile_ld(ITER_SYM, arr)?;
+        let next_idx = arr.len();
+        arr.push(ByteCode::NEXT(0));
+        self.compile_st(var, arr)?;
+        Ok(next_idx)
+    }
+
+    /// Compile expr? as returning the value if it is None or Err, and unwrapping it otherwise.
+    /// The type checker ensures the value is an Option or Result, and is_none is false for a Result and is_err for an Option.
+    ///
+    /// x? => { let $try = x; if is_none($try) || is_err($try) { return $try; } unwrap($try) }
+    fn compile_try(&mut self, expr: &Expr, arr: &mut Vec<ByteCode>) -> Result<(), CompileError> {
+        let tried = Expr::Symbol(TRY_SYM.to_string());
+        let call = |name: &str| {
+            Expr::FnCallExpr(FnCallData {
+                name: name.to_string(),
+                args: vec![tried.clone()],
+                names: vec![],
+            })
+        };
+
+        let ret = IfElseData {
+            cond: Expr::BinOpExpr(
+                BinOpType::LogicalOr,
+                Box::new(call(builtin::IS_NONE_SYM)),
+                Box::new(call(builtin::IS_ERR_SYM)),
+            ),
+            if_blk: BlockSeq {
+                decls: vec![Decl::ReturnStmt(Some(tried.clone()))],
+                last_expr: None,
+                symbols: vec![],
+                decl_toks: vec![],
+                last_expr_tok: None,
+            },
+            else_blk: None,
+        };
+
+        let blk = BlockSeq {
+            decls: vec![
+                Decl::LetStmt(LetStmtData {
+                    ident: TRY_SYM.to_string(),
+                    expr: expr.clone(),
+                    type_ann: None,
+                }),
+                Decl::IfOnlyStmt(ret),
+            ],
+            last_expr: Some(Rc::new(call(builtin::UNWRAP_SYM))),
+            symbols: vec![TRY_SYM.to_string()],
+            decl_toks: vec![],
+            last_expr_tok: None,
+        };
+
+        self.compile_block(&blk, arr)
+    }
+
     /*Assumptions:
     1. Before entering a statement, op_stack length  is 0
     2. Upon jump on false, op stack length is 0
     */
     // Returns index in pc of LDC unit for the loop
+    /// TRY enters an empty scope for the try block, so that break and return leave the try frame like any other scope.
+    /// If an error unwinds to the try frame, the catch block starts with the error message on the operand stack.
+    ///
+    /// TRY(catch), try blk, EXITSCOPE, GOTO(end), catch: ENTERSCOPE([e]), ASSIGNSLOT(0, 0), catch blk, EXITSCOPE, end:
+    fn compile_try_catch(
+        &mut self,
+        try_catch: &TryCatchData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let try_idx = arr.len();
+        arr.push(ByteCode::TRY(0));
+
+        self.scopes.push(vec![]);
+        self.compile_block(&try_catch.try_blk, arr)?;
+        self.scopes.pop();
+        arr.push(ByteCode::EXITSCOPE);
+
+        let goto_idx = arr.len();
+        arr.push(ByteCode::GOTO(0));
+
+        // set TRY arg to the start of the catch block
+        let catch_start = arr.len();
+        if let Some(ByteCode::TRY(addr)) = arr.get_mut(try_idx) {
+            *addr = catch_start;
+        }
+
+        let err = vec![intern(try_catch.err.as_str())?];
+        arr.push(ByteCode::ENTERSCOPE(err.clone()));
+        self.scopes.push(err);
+        arr.push(ByteCode::ASSIGNSLOT(0, 0));
+        self.compile_block(&try_catch.catch_blk, arr)?;
+        self.scopes.pop();
+        arr.push(ByteCode::EXITSCOPE);
+
+        // set GOTO arg to after the catch block
+        let end = arr.len();
+        if let Some(ByteCode::GOTO(addr)) = arr.get_mut(goto_idx) {
+            *addr = end;
+        }
+
+        Ok(())
+    }
+
+    /// LOCK and READLOCK enter an empty scope for the block holding the lock, so that break, return and '?'
+    /// release it when they leave the lock frame like any other scope.
+    ///
+    /// lock, LOCK or READLOCK, blk, EXITSCOPE
+    fn compile_lock(
+        &mut self,
+        lock: &LockData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_expr(&lock.lock, arr)?;
+        if lock.shared {
+            arr.push(ByteCode::READLOCK);
+        } else {
+            arr.push(ByteCode::LOCK);
+        }
+
+        self.scopes.push(vec![]);
+        self.compile_block(&lock.blk, arr)?;
+        self.scopes.pop();
+        arr.push(ByteCode::EXITSCOPE);
+
+        Ok(())
+    }
+
     fn compile_loop_inner(
         &mut self,
         loop_data: &LoopData,
@@ -494,46 +1587,189 @@ impl Compiler {
         loop_data: &LoopData,
         arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
-        self.loop_stack.push(vec![]);
+        self.loops.push(LoopCtx {
+            label: loop_data.label.clone(),
+            depth: self.scopes.len(),
+            breaks: vec![],
+        });
         let end_idx = self.compile_loop_inner(loop_data, arr);
-
-        let end_idx = end_idx?;
-
-        // patch all the break stmts
-        let breaks = self
-            .loop_stack
-            .last()
+        let lp = self
+            .loops
+            .pop()
             .expect("Loop stack should be present since pushed earlier");
 
         // Later: can use this to detect infinite loops
-        // if breaks.len() == 0 && loop_data.cond.is_none() {
+        // if lp.breaks.len() == 0 && loop_data.cond.is_none() {
         //     dbg!("[WARNING] Breaks was empty: loop has no break");
         // }
 
-        for idx in breaks.iter() {
-            let idx = idx.to_owned();
+        Compiler::patch_breaks(&lp, end_idx?, arr);
+        Ok(())
+    }
 
-            if let Some(ByteCode::GOTO(break_idx)) = arr.get_mut(idx) {
-                *break_idx = end_idx;
+    /// Compile a for loop like a comprehension, in a scope binding the variable and the iterator.
+    /// Break exits the scope and jumps past it, to the unit the loop produces.
+    ///
+    /// for x in it { body }
+    /// => ENTERSCOPE [x, $iter] it ASSIGN $iter
+    ///    loop: LD $iter NEXT(exit) ASSIGN x body POP GOTO loop
+    ///    exit: EXITSCOPE
+    ///    end: LDC Unit
+    fn compile_for(
+        &mut self,
+        for_data: &ForData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.loops.push(LoopCtx {
+            label: for_data.label.clone(),
+            depth: self.scopes.len(),
+            breaks: vec![],
+        });
+
+        let syms = vec![intern(&for_data.var)?, intern(ITER_SYM)?];
+        arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+        self.scopes.push(syms);
+
+        let res = self.compile_for_loop(for_data, arr);
+
+        arr.push(ByteCode::EXITSCOPE);
+        self.scopes.pop();
+        let lp = self
+            .loops
+            .pop()
+            .expect("Loop stack should be present since pushed earlier");
+        res?;
+
+        let end_idx = arr.len();
+        arr.push(ByteCode::LDC(Value::Unit));
+        Compiler::patch_breaks(&lp, end_idx, arr);
+
+        Ok(())
+    }
+
+    /// Point the breaks of a loop at its end, the unit it produces, or past it for the breaks that leave it with a value.
+    fn patch_breaks(lp: &LoopCtx, end_idx: usize, arr: &mut [ByteCode]) {
+        for (idx, with_value) in lp.breaks.iter() {
+            if let Some(ByteCode::GOTO(break_idx)) = arr.get_mut(*idx) {
+                *break_idx = if *with_value { end_idx + 1 } else { end_idx };
             }
         }
+    }
+
+    /// Leave the innermost loop, or the innermost one with the label. The value is left on the stack
+    /// as the value of the loop, then the scopes entered in the loop are exited before jumping to its end.
+    ///
+    /// break 'a x
+    /// => x EXITSCOPE.. GOTO(past end of 'a)
+    fn compile_break(
+        &mut self,
+        brk: &BreakData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        if let Some(value) = &brk.value {
+            self.compile_expr(value, arr)?;
+        }
+
+        let target = match &brk.label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|lp| lp.label.as_ref() == Some(label)),
+            None => self.loops.len().checked_sub(1),
+        };
+        let Some(target) = target else {
+            return Err(CompileError::new("break outside of loop"));
+        };
+
+        for _ in self.loops[target].depth..self.scopes.len() {
+            arr.push(ByteCode::EXITSCOPE);
+        }
+
+        let break_idx = arr.len();
+        arr.push(ByteCode::GOTO(0));
+        self.loops[target]
+            .breaks
+            .push((break_idx, brk.value.is_some()));
+
+        Ok(())
+    }
+
+    fn compile_for_loop(
+        &mut self,
+        for_data: &ForData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_iterable(&for_data.iter, arr)?;
+
+        let loop_start = arr.len();
+        let next_idx = self.compile_next(&for_data.var, arr)?;
+
+        self.compile_block(&for_data.body, arr)?;
+        arr.push(ByteCode::POP);
+        arr.push(ByteCode::GOTO(loop_start));
+
+        let exit = arr.len();
+        if let Some(ByteCode::NEXT(addr)) = arr.get_mut(next_idx) {
+            *addr = exit;
+        }
 
-        self.loop_stack.pop();
         Ok(())
     }
 
-    pub fn compile(mut self) -> anyhow::Result<Vec<ByteCode>, CompileError> {
+    pub fn compile(self) -> anyhow::Result<Vec<ByteCode>, CompileError> {
+        let (bytecode, _) = self.compile_with_debug_info()?;
+        Ok(bytecode)
+    }
+
+    /// Compile the program, with where the code of each of its statements starts.
+    pub fn compile_with_debug_info(mut self) -> Result<(Vec<ByteCode>, DebugInfo), CompileError> {
         let mut bytecode: Vec<ByteCode> = vec![];
-        let prog = self.program.clone();
+        let prog = resolve_named_args(&self.program)
+            .map_err(|e| CompileError::new(&e))?
+            .into_owned();
         self.compile_block_body(&prog, &mut bytecode)?;
         bytecode.push(ByteCode::DONE);
 
-        Ok(bytecode)
+        Ok((bytecode, self.debug_info))
+    }
+
+    /// Compile a program that continues from the programs compiled before it, as the inputs of a REPL do.
+    /// The code is appended to `arr`, which holds the code of the earlier programs so that jumps land correctly.
+    /// The top-level scope of the program is never exited, so its bindings stay in scope for the following programs.
+    /// If compilation fails, `arr` and the scopes are left as they were.
+    pub fn compile_incremental(
+        &mut self,
+        program: &BlockSeq,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let program = resolve_named_args(program).map_err(|e| CompileError::new(&e))?;
+        let len = arr.len();
+        let depth = self.scopes.len();
+
+        let res = self.compile_scope(&program, arr, false);
+        if res.is_err() {
+            arr.truncate(len);
+            self.scopes.truncate(depth);
+            self.loops.clear();
+            return res;
+        }
+
+        arr.push(ByteCode::DONE);
+        Ok(())
     }
 }
 
 /// Takes in a string and returns compiled bytecode or errors
 pub fn compile_from_string(inp: &str, type_check: bool) -> Result<Vec<ByteCode>> {
+    let (bytecode, _) = compile_from_string_with_debug_info(inp, type_check)?;
+    Ok(bytecode)
+}
+
+/// Takes in a string and returns compiled bytecode, with where the code of each of its statements starts, or errors
+pub fn compile_from_string_with_debug_info(
+    inp: &str,
+    type_check: bool,
+) -> Result<(Vec<ByteCode>, DebugInfo)> {
     let parser = parser::Parser::new_from_string(inp);
     let program = parser.parse()?;
 
@@ -542,5 +1778,5 @@ pub fn compile_from_string(inp: &str, type_check: bool) -> Result<Vec<ByteCode>>
     }
 
     let compiler = Compiler::new(program);
-    Ok(compiler.compile()?)
+    Ok(compiler.compile_with_debug_info()?)
 }
@@ -0,0 +1,59 @@
+/// Where the code of each statement of a program starts, to map the instructions that ran back to the source,
+/// e.g. for coverage. Statements are located by the index in the token stream of their first token,
+/// as parse errors are, which the caller turns into a line with the source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugInfo {
+    /// The address of the first instruction of each statement and the index of its first token,
+    /// in the order they were compiled. Statements that compile to no instructions are left out.
+    pub stmts: Vec<(usize, usize)>,
+}
+
+impl DebugInfo {
+    /// Add the statement compiled to the instructions from `start` up to `end`, if it was located.
+    pub fn add(&mut self, start: usize, end: usize, tok: Option<usize>) {
+        if let Some(tok) = tok {
+            if end > start {
+                self.stmts.push((start, tok));
+            }
+        }
+    }
+
+    /// The address of the first instruction of each statement and the line, counted from 1, it starts on
+    /// in the source the program was compiled from, in the order they were compiled.
+    pub fn lines(&self, src: &str) -> Vec<(usize, usize)> {
+        let mut lexer = lexer::lex(src);
+        let mut starts = vec![];
+        while lexer.next().is_some() {
+            starts.push(lexer.span().start);
+        }
+
+        self.stmts
+            .iter()
+            .filter_map(|(pc, tok)| {
+                let start = *starts.get(*tok)?;
+                Some((*pc, src[..start].matches('\n').count() + 1))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::Parser;
+
+    use crate::compiler::Compiler;
+
+    #[test]
+    fn test_lines() {
+        let src = "let x = 1;\n\n// y\nlet y = x +\n    2;\ny";
+        let program = Parser::new_from_string(src).parse().unwrap();
+        let (_, debug_info) = Compiler::new(program).compile_with_debug_info().unwrap();
+
+        let lines: Vec<usize> = debug_info
+            .lines(src)
+            .iter()
+            .map(|(_, line)| *line)
+            .collect();
+        assert_eq!(lines, vec![1, 4, 6]);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod compiler;
+pub mod debug_info;
+pub mod optimize;
+pub mod register;
 pub mod tests;
+pub mod transpile;
+
+/// The version of the compiler, recorded in the metadata of the .o2 files it writes.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
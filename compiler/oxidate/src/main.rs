@@ -1,11 +1,15 @@
 pub mod compiler;
+pub mod debug_info;
+pub mod optimize;
+pub mod register;
 
 use anyhow::{Error, Result};
-use bytecode::write_bytecode;
+use bytecode::{write_bytecode_metadata, Compression, Metadata};
 use clap::Parser;
 use std::{io::Read, path::Path};
 
 use crate::compiler::{compile_from_string, CompileError};
+use crate::optimize::OptArgs;
 
 const RST: &str = "rst";
 
@@ -24,6 +28,13 @@ struct Args {
     /// If present, does not type check
     #[arg(short)]
     notype: bool,
+
+    /// How to compress the program in the .o2 file: none, deflate or zstd
+    #[arg(long, default_value_t = Compression::None)]
+    compress: Compression,
+
+    #[command(flatten)]
+    opt: OptArgs,
 }
 
 fn main() -> Result<()> {
@@ -54,7 +65,7 @@ fn main() -> Result<()> {
         .expect("File should exist")
         .read_to_string(&mut code)?;
 
-    let bytecode = match compile_from_string(&code, !args.notype) {
+    let mut bytecode = match compile_from_string(&code, !args.notype) {
         Ok(bc) => bc,
         Err(err) => {
             let e = format!("\n{}", err);
@@ -62,6 +73,8 @@ fn main() -> Result<()> {
         }
     };
 
+    args.opt.optimize(&mut bytecode);
+
     let out_name;
     if let Some(name) = args.out {
         out_name = name;
@@ -77,7 +90,8 @@ fn main() -> Result<()> {
     // Write to .o2 file
     let bc_name = format!("{}.o2", out_name);
     let mut bc_file = std::fs::File::create(&bc_name).unwrap();
-    write_bytecode(&bytecode, &mut bc_file)?;
+    let metadata = Metadata::new(env!("CARGO_PKG_VERSION"), &[&code], args.opt.opt_level());
+    write_bytecode_metadata(&bytecode, args.compress, &metadata, &mut bc_file)?;
 
     println!("Compiled successfully to {}", bc_name);
 
@@ -0,0 +1,537 @@
+use std::{collections::HashSet, fmt::Display};
+
+use bytecode::{Address, ByteCode};
+use clap::ValueEnum;
+
+use crate::register::{self, Backend};
+
+/// An optimization pass over the instructions of a compiled program.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Remove constants that are loaded only to be popped, e.g. the unit value of each statement.
+    RedundantLoads,
+    /// Remove stores to a slot that is stored to again before anything can read it.
+    DeadStores,
+    /// Retarget jumps to a GOTO to where the GOTO goes, and remove GOTOs to the next instruction.
+    JumpThreading,
+    /// Fuse loads and the binary operations or jumps after them into single instructions, dispatched in one step.
+    Superinstructions,
+}
+
+impl Pass {
+    /// Every pass, in the order they run best in: removing the loads of unit values lines up the stores they separate,
+    /// and the instructions are only fused once the other passes no longer look for them.
+    pub const ALL: [Pass; 4] = [
+        Pass::RedundantLoads,
+        Pass::DeadStores,
+        Pass::JumpThreading,
+        Pass::Superinstructions,
+    ];
+
+    /// Run the pass over the instructions, returning the number of changes it made.
+    pub fn run(self, instrs: &mut Vec<ByteCode>) -> usize {
+        match self {
+            Pass::RedundantLoads => redundant_loads(instrs),
+            Pass::DeadStores => dead_stores(instrs),
+            Pass::JumpThreading => jump_threading(instrs),
+            Pass::Superinstructions => superinstructions(instrs),
+        }
+    }
+}
+
+impl Display for Pass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Pass::RedundantLoads => "redundant-loads",
+            Pass::DeadStores => "dead-stores",
+            Pass::JumpThreading => "jump-threading",
+            Pass::Superinstructions => "superinstructions",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// What a pass did to the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassStats {
+    pub pass: Pass,
+    /// The number of instructions removed or rewritten.
+    pub changes: usize,
+    /// The number of instructions before the pass.
+    pub before: usize,
+    /// The number of instructions after the pass.
+    pub after: usize,
+}
+
+impl Display for PassStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} changes, {} -> {} instructions",
+            self.pass, self.changes, self.before, self.after
+        )
+    }
+}
+
+/// Runs optimization passes over a compiled program in order.
+/// The passes keep what the program does, but not the addresses of its instructions, so a program
+/// is optimized as a whole, once compiled, and not e.g. code appended to a running program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassManager {
+    passes: Vec<Pass>,
+}
+
+impl PassManager {
+    pub fn new(passes: impl IntoIterator<Item = Pass>) -> Self {
+        PassManager {
+            passes: passes.into_iter().collect(),
+        }
+    }
+
+    /// Every pass, see [`Pass::ALL`].
+    pub fn all() -> Self {
+        PassManager::new(Pass::ALL)
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Run the passes over the program, returning what each did.
+    pub fn run(&self, instrs: &mut Vec<ByteCode>) -> Vec<PassStats> {
+        self.passes
+            .iter()
+            .map(|pass| {
+                let before = instrs.len();
+                let changes = pass.run(instrs);
+                PassStats {
+                    pass: *pass,
+                    changes,
+                    before,
+                    after: instrs.len(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The command line flags choosing the optimization passes and the backend, shared by the CLIs that compile programs.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct OptArgs {
+    /// Optimize the bytecode with every pass.
+    #[arg(short = 'O', long)]
+    pub optimize: bool,
+
+    /// Optimize the bytecode with the pass, in the order given instead of every pass. Can be given more than once.
+    #[arg(long = "pass", value_enum, value_name = "PASS")]
+    pub passes: Vec<Pass>,
+
+    /// Print what each optimization pass did to stderr.
+    #[arg(long)]
+    pub opt_stats: bool,
+
+    /// The instruction set to generate code for. The register backend is an experiment in the speed of
+    /// reading operands from the slots of the frame rather than the operand stack.
+    #[arg(long, value_enum, default_value_t = Backend::Stack)]
+    pub backend: Backend,
+}
+
+impl OptArgs {
+    /// The passes the flags ask for, none by default.
+    pub fn pass_manager(&self) -> PassManager {
+        if !self.passes.is_empty() {
+            PassManager::new(self.passes.iter().copied())
+        } else if self.optimize {
+            PassManager::all()
+        } else {
+            PassManager::default()
+        }
+    }
+
+    /// The optimization level the flags amount to, as recorded in the metadata of .o2 files:
+    /// 0 if no pass runs, 1 otherwise.
+    pub fn opt_level(&self) -> u8 {
+        u8::from(self.optimize || !self.passes.is_empty())
+    }
+
+    /// Optimize the program as the flags ask, then generate code for the backend,
+    /// printing the stats of the passes if asked to.
+    pub fn optimize(&self, instrs: &mut Vec<ByteCode>) {
+        for stats in self.pass_manager().run(instrs) {
+            if self.opt_stats {
+                eprintln!("{}", stats);
+            }
+        }
+
+        if self.backend == Backend::Register {
+            let before = instrs.len();
+            let changes = register::lower(instrs);
+            if self.opt_stats {
+                eprintln!(
+                    "{} backend: {} changes, {} -> {} instructions",
+                    self.backend,
+                    changes,
+                    before,
+                    instrs.len()
+                );
+            }
+        }
+    }
+}
+
+/// The addresses some instruction jumps to or loads.
+pub(crate) fn targets(instrs: &[ByteCode]) -> HashSet<Address> {
+    instrs.iter().flat_map(ByteCode::addresses).collect()
+}
+
+/// Remove the marked instructions, retargeting the addresses of the others.
+/// An address of a removed instruction becomes that of the next instruction kept, so only instructions
+/// that do nothing together, and are only jumped to at the first of them, can be removed.
+pub(crate) fn remove(instrs: &mut Vec<ByteCode>, removed: &[bool]) {
+    // The new address of each instruction, and of the end of the program
+    let mut new_addrs = Vec::with_capacity(instrs.len() + 1);
+    let mut kept = 0;
+    for is_removed in removed.iter() {
+        new_addrs.push(kept);
+        if !is_removed {
+            kept += 1;
+        }
+    }
+    new_addrs.push(kept);
+
+    let old = std::mem::take(instrs);
+    for (mut instr, is_removed) in old.into_iter().zip(removed.iter()) {
+        if !is_removed {
+            instr.map_addresses(|addr| new_addrs[addr]);
+            instrs.push(instr);
+        }
+    }
+}
+
+/// LDC(v), POP => nothing
+fn redundant_loads(instrs: &mut Vec<ByteCode>) -> usize {
+    let targets = targets(instrs);
+    let mut removed = vec![false; instrs.len()];
+    let mut changes = 0;
+
+    let mut i = 0;
+    while i + 1 < instrs.len() {
+        if matches!(instrs[i], ByteCode::LDC(_))
+            && instrs[i + 1] == ByteCode::POP
+            && !targets.contains(&(i + 1))
+        {
+            removed[i] = true;
+            removed[i + 1] = true;
+            changes += 2;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    remove(instrs, &removed);
+    changes
+}
+
+/// LDC(v), ASSIGNSLOT(d, i), load, ASSIGNSLOT(d, i) => load, ASSIGNSLOT(d, i)
+/// where the load is of a constant or another slot, so it can't read the first value or fail.
+fn dead_stores(instrs: &mut Vec<ByteCode>) -> usize {
+    let targets = targets(instrs);
+    let mut removed = vec![false; instrs.len()];
+    let mut changes = 0;
+
+    let mut i = 0;
+    while i + 3 < instrs.len() {
+        let dead = match &instrs[i..i + 4] {
+            [ByteCode::LDC(_), ByteCode::ASSIGNSLOT(depth, idx), load, ByteCode::ASSIGNSLOT(depth2, idx2)] =>
+            {
+                let pure = match load {
+                    ByteCode::LDC(_) => true,
+                    ByteCode::LDSLOT(d, x) => (d, x) != (depth, idx),
+                    _ => false,
+                };
+                pure && (depth, idx) == (depth2, idx2)
+            }
+            _ => false,
+        };
+
+        if dead && (i + 1..i + 4).all(|addr| !targets.contains(&addr)) {
+            removed[i] = true;
+            removed[i + 1] = true;
+            changes += 2;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    remove(instrs, &removed);
+    changes
+}
+
+/// Where a jump to the address ends up, following GOTOs, or None if they loop forever.
+fn jump_target(instrs: &[ByteCode], mut addr: Address) -> Option<Address> {
+    let mut seen = HashSet::new();
+    while let Some(ByteCode::GOTO(next)) = instrs.get(addr) {
+        if !seen.insert(addr) {
+            return None;
+        }
+        addr = *next;
+    }
+    Some(addr)
+}
+
+/// JOF(a) where a: GOTO(b) => JOF(b), for every jump and GOTOs in a chain, and GOTO(a) at a - 1 => nothing
+fn jump_threading(instrs: &mut Vec<ByteCode>) -> usize {
+    let mut changes = 0;
+
+    let threaded: Vec<ByteCode> = instrs
+        .iter()
+        .map(|instr| {
+            let mut instr = instr.clone();
+            instr.map_addresses(|addr| match jump_target(instrs, addr) {
+                Some(target) if target != addr => {
+                    changes += 1;
+                    target
+                }
+                _ => addr,
+            });
+            instr
+        })
+        .collect();
+    *instrs = threaded;
+
+    // A GOTO to the next instruction does nothing, and jumps to it land on the next instruction once it is removed
+    let removed: Vec<bool> = instrs
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| *instr == ByteCode::GOTO(i + 1))
+        .collect();
+    changes += removed.iter().filter(|is_removed| **is_removed).count();
+    remove(instrs, &removed);
+
+    changes
+}
+
+/// JOF(a), .., GOTO(a + 1), a: LDC(false) => JOFORPOP(a + 1), .. where only the JOF jumps to a, as `&&` compiles to,
+/// then LDSLOT(a), LDSLOT(b), BINOP(op) => LDSLOTSBINOP(a, b, op) and LDC(v), BINOP(op) => LDCBINOP(v, op)
+/// where nothing jumps into the middle of them.
+fn superinstructions(instrs: &mut Vec<ByteCode>) -> usize {
+    let mut changes = 0;
+
+    let mut jumps_to = vec![0; instrs.len() + 1];
+    for addr in instrs.iter().flat_map(ByteCode::addresses) {
+        if let Some(count) = jumps_to.get_mut(addr) {
+            *count += 1;
+        }
+    }
+
+    // A jump to the removed GOTO went on to a + 1 anyway
+    let mut removed = vec![false; instrs.len()];
+    for i in 0..instrs.len() {
+        let ByteCode::JOF(addr) = instrs[i] else {
+            continue;
+        };
+        if addr > i + 1
+            && instrs.get(addr) == Some(&ByteCode::ldc(false))
+            && instrs[addr - 1] == ByteCode::GOTO(addr + 1)
+            && jumps_to[addr] == 1
+        {
+            instrs[i] = ByteCode::JOFORPOP(addr + 1);
+            removed[addr - 1] = true;
+            removed[addr] = true;
+            changes += 3;
+        }
+    }
+    remove(instrs, &removed);
+
+    let targets = targets(instrs);
+    let mut removed = vec![false; instrs.len()];
+    let mut i = 0;
+    while i < instrs.len() {
+        let fused = match &instrs[i..] {
+            [ByteCode::LDSLOT(d1, i1), ByteCode::LDSLOT(d2, i2), ByteCode::BINOP(op), ..]
+                if !targets.contains(&(i + 1)) && !targets.contains(&(i + 2)) =>
+            {
+                Some((
+                    ByteCode::LDSLOTSBINOP((*d1, *i1), (*d2, *i2), op.clone()),
+                    3,
+                ))
+            }
+            [ByteCode::LDC(val), ByteCode::BINOP(op), ..] if !targets.contains(&(i + 1)) => {
+                Some((ByteCode::LDCBINOP(val.clone(), op.clone()), 2))
+            }
+            _ => None,
+        };
+
+        match fused {
+            Some((instr, len)) => {
+                instrs[i] = instr;
+                removed[i + 1..i + len].fill(true);
+                changes += len;
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    remove(instrs, &removed);
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{BinOp, Value};
+
+    use super::*;
+
+    #[test]
+    fn test_redundant_loads() {
+        let mut instrs = vec![
+            ByteCode::ldc(1),
+            ByteCode::ASSIGNSLOT(0, 0),
+            ByteCode::ldc(()),
+            ByteCode::POP,
+            ByteCode::GOTO(6),
+            // Jumped into the middle of, so kept
+            ByteCode::ldc(()),
+            ByteCode::POP,
+            ByteCode::JOF(6),
+            ByteCode::DONE,
+        ];
+
+        assert_eq!(Pass::RedundantLoads.run(&mut instrs), 2);
+        assert_eq!(
+            instrs,
+            vec![
+                ByteCode::ldc(1),
+                ByteCode::ASSIGNSLOT(0, 0),
+                ByteCode::GOTO(4),
+                ByteCode::ldc(()),
+                ByteCode::POP,
+                ByteCode::JOF(4),
+                ByteCode::DONE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dead_stores() {
+        let mut instrs = vec![
+            ByteCode::ldc(1),
+            ByteCode::ASSIGNSLOT(0, 0),
+            ByteCode::ldc(2),
+            ByteCode::ASSIGNSLOT(0, 0),
+            // Reads the slot it is stored to, so not dead
+            ByteCode::ldc(3),
+            ByteCode::ASSIGNSLOT(0, 1),
+            ByteCode::LDSLOT(0, 1),
+            ByteCode::ASSIGNSLOT(0, 1),
+            ByteCode::DONE,
+        ];
+
+        assert_eq!(Pass::DeadStores.run(&mut instrs), 2);
+        assert_eq!(instrs.len(), 7);
+        assert_eq!(instrs[..2], [ByteCode::ldc(2), ByteCode::ASSIGNSLOT(0, 0)]);
+    }
+
+    #[test]
+    fn test_jump_threading() {
+        let mut instrs = vec![
+            ByteCode::ldc(true),
+            ByteCode::JOF(3),
+            ByteCode::GOTO(3),
+            ByteCode::GOTO(5),
+            ByteCode::GOTO(4),
+            ByteCode::DONE,
+        ];
+
+        // Jumps to the GOTO at 3 go straight to 5, and the GOTO looping on itself is left alone
+        assert_eq!(Pass::JumpThreading.run(&mut instrs), 2);
+        assert_eq!(
+            instrs,
+            vec![
+                ByteCode::ldc(true),
+                ByteCode::JOF(5),
+                ByteCode::GOTO(5),
+                ByteCode::GOTO(5),
+                ByteCode::GOTO(4),
+                ByteCode::DONE,
+            ]
+        );
+
+        let mut instrs = vec![
+            ByteCode::ldc(1),
+            ByteCode::GOTO(2),
+            ByteCode::ldc(2),
+            ByteCode::binop(BinOp::Add),
+            ByteCode::DONE,
+        ];
+        assert_eq!(Pass::JumpThreading.run(&mut instrs), 1);
+        assert_eq!(instrs.len(), 4);
+    }
+
+    #[test]
+    fn test_superinstructions() {
+        let mut instrs = vec![
+            ByteCode::LDSLOT(0, 0),
+            ByteCode::ldc(5),
+            ByteCode::binop(BinOp::Lt),
+            ByteCode::JOF(8),
+            ByteCode::LDSLOT(0, 1),
+            ByteCode::LDSLOT(1, 0),
+            ByteCode::binop(BinOp::Ge),
+            ByteCode::GOTO(9),
+            ByteCode::ldc(false),
+            ByteCode::JOF(0),
+            // Jumped into the middle of, so kept
+            ByteCode::ldc(1),
+            ByteCode::binop(BinOp::Add),
+            ByteCode::JOF(11),
+            ByteCode::DONE,
+        ];
+
+        assert_eq!(Pass::Superinstructions.run(&mut instrs), 8);
+        assert_eq!(
+            instrs,
+            vec![
+                ByteCode::LDSLOT(0, 0),
+                ByteCode::LDCBINOP(Value::Int(5), BinOp::Lt),
+                ByteCode::JOFORPOP(4),
+                ByteCode::LDSLOTSBINOP((0, 1), (1, 0), BinOp::Ge),
+                ByteCode::JOF(0),
+                ByteCode::ldc(1),
+                ByteCode::binop(BinOp::Add),
+                ByteCode::JOF(6),
+                ByteCode::DONE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pass_manager() {
+        let src = r"
+        let x = 1;
+        x = 2;
+        let y = if x > 1 { if x > 2 { x } else { 1 } } else { 0 };
+        loop y < 5 { y = y + 1; }
+        y
+        ";
+        let mut instrs = crate::compiler::compile_from_string(src, true).unwrap();
+        let before = instrs.len();
+
+        let stats = PassManager::all().run(&mut instrs);
+        let passes: Vec<Pass> = stats.iter().map(|stats| stats.pass).collect();
+        assert_eq!(passes, Pass::ALL);
+        assert_eq!(stats[0].before, before);
+        assert_eq!(stats[3].after, instrs.len());
+        assert!(stats.iter().all(|stats| stats.changes > 0));
+        assert_eq!(
+            stats[0].to_string(),
+            format!(
+                "redundant-loads: {} changes, {} -> {} instructions",
+                stats[0].changes, stats[0].before, stats[0].after
+            )
+        );
+    }
+}
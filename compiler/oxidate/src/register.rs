@@ -0,0 +1,211 @@
+use std::fmt::Display;
+
+use bytecode::{BinOp, ByteCode, Operand};
+use clap::ValueEnum;
+
+use crate::optimize::{remove, targets};
+
+/// The instruction set the compiler generates code for.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Instructions that pass their operands and results on the operand stack.
+    #[default]
+    Stack,
+    /// Instructions that read their operands from, and assign their results to, the slots of the frame
+    /// as registers where they can, falling back to the operand stack elsewhere.
+    Register,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Backend::Stack => "stack",
+            Backend::Register => "register",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The operand of a load, if it is of a slot or a constant.
+fn load_operand(instr: &ByteCode) -> Option<Operand> {
+    match instr {
+        ByteCode::LDSLOT(depth, idx) => Some(Operand::Slot(*depth, *idx)),
+        ByteCode::LDC(val) => Some(Operand::Const(val.clone())),
+        _ => None,
+    }
+}
+
+/// Whether the operation on the left-hand side may call a method of a struct, which returns to the next instruction.
+fn may_call(lhs: &Operand, op: &BinOp) -> bool {
+    matches!(op, BinOp::Add | BinOp::Eq) && !matches!(lhs, Operand::Const(_))
+}
+
+/// Generate code for the register backend from that of the stack backend:
+/// loads of slots and constants followed by a binary operation, fused or not, become a BINOPR reading them,
+/// a BINOPR followed by an assignment to a slot assigns its result there itself, and a load followed by one
+/// becomes a MOVR. Returns the number of instructions removed or rewritten.
+/// Like the optimization passes, the program is lowered as a whole once compiled.
+pub fn lower(instrs: &mut Vec<ByteCode>) -> usize {
+    lower_binops(instrs) + lower_assigns(instrs)
+}
+
+/// Binary operations read their operands from where they are loaded.
+fn lower_binops(instrs: &mut Vec<ByteCode>) -> usize {
+    let mut changes = 0;
+    let targets = targets(instrs);
+    let mut removed = vec![false; instrs.len()];
+    let mut i = 0;
+    while i < instrs.len() {
+        let not_target = |addr: usize| !targets.contains(&addr);
+        let lowered = match &instrs[i..] {
+            [lhs, ByteCode::LDCBINOP(val, op), ..] if not_target(i + 1) => {
+                load_operand(lhs).map(|lhs| {
+                    let rhs = Operand::Const(val.clone());
+                    (ByteCode::BINOPR(None, lhs, rhs, op.clone()), 2)
+                })
+            }
+            [ByteCode::LDCBINOP(val, op), ..] => Some((
+                ByteCode::BINOPR(
+                    None,
+                    Operand::Stack,
+                    Operand::Const(val.clone()),
+                    op.clone(),
+                ),
+                1,
+            )),
+            [ByteCode::LDSLOTSBINOP(lhs, rhs, op), ..] => Some((
+                ByteCode::BINOPR(
+                    None,
+                    Operand::Slot(lhs.0, lhs.1),
+                    Operand::Slot(rhs.0, rhs.1),
+                    op.clone(),
+                ),
+                1,
+            )),
+            [lhs, rhs, ByteCode::BINOP(op), ..] if not_target(i + 1) && not_target(i + 2) => {
+                load_operand(lhs)
+                    .zip(load_operand(rhs))
+                    .map(|(lhs, rhs)| (ByteCode::BINOPR(None, lhs, rhs, op.clone()), 3))
+            }
+            [rhs, ByteCode::BINOP(op), ..] if not_target(i + 1) => load_operand(rhs)
+                .map(|rhs| (ByteCode::BINOPR(None, Operand::Stack, rhs, op.clone()), 2)),
+            _ => None,
+        };
+
+        match lowered {
+            Some((instr, len)) => {
+                instrs[i] = instr;
+                removed[i + 1..i + len].fill(true);
+                changes += len;
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    remove(instrs, &removed);
+
+    changes
+}
+
+/// Results and loads are assigned straight to the slot.
+fn lower_assigns(instrs: &mut Vec<ByteCode>) -> usize {
+    let mut changes = 0;
+    let targets = targets(instrs);
+    let mut removed = vec![false; instrs.len()];
+    let mut i = 0;
+    while i + 1 < instrs.len() {
+        let lowered = match &instrs[i..i + 2] {
+            _ if targets.contains(&(i + 1)) => None,
+            [ByteCode::BINOPR(None, lhs, rhs, op), ByteCode::ASSIGNSLOT(depth, idx)]
+                if !may_call(lhs, op) =>
+            {
+                Some(ByteCode::BINOPR(
+                    Some((*depth, *idx)),
+                    lhs.clone(),
+                    rhs.clone(),
+                    op.clone(),
+                ))
+            }
+            [src, ByteCode::ASSIGNSLOT(depth, idx)] => {
+                load_operand(src).map(|src| ByteCode::MOVR((*depth, *idx), src))
+            }
+            _ => None,
+        };
+
+        match lowered {
+            Some(instr) => {
+                instrs[i] = instr;
+                removed[i + 1] = true;
+                changes += 2;
+                i += 2;
+            }
+            None => i += 1,
+        }
+    }
+    remove(instrs, &removed);
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_lower() {
+        let mut instrs = vec![
+            // i = i - 1
+            ByteCode::LDSLOT(0, 0),
+            ByteCode::ldc(1),
+            ByteCode::binop(BinOp::Sub),
+            ByteCode::ASSIGNSLOT(0, 0),
+            // s = s + i, which may call the add method of s
+            ByteCode::LDSLOTSBINOP((0, 1), (0, 0), BinOp::Add),
+            ByteCode::ASSIGNSLOT(0, 1),
+            // n = 2
+            ByteCode::ldc(2),
+            ByteCode::ASSIGNSLOT(0, 2),
+            ByteCode::LDSLOT(0, 2),
+            ByteCode::LDCBINOP(Value::Int(3), BinOp::Mul),
+            // Jumped into the middle of, so kept
+            ByteCode::LDSLOT(0, 0),
+            ByteCode::ldc(0),
+            ByteCode::binop(BinOp::Gt),
+            ByteCode::JOF(11),
+            ByteCode::DONE,
+        ];
+
+        assert_eq!(lower(&mut instrs), 12);
+        assert_eq!(
+            instrs,
+            vec![
+                ByteCode::BINOPR(
+                    Some((0, 0)),
+                    Operand::Slot(0, 0),
+                    Operand::Const(Value::Int(1)),
+                    BinOp::Sub
+                ),
+                ByteCode::BINOPR(None, Operand::Slot(0, 1), Operand::Slot(0, 0), BinOp::Add),
+                ByteCode::ASSIGNSLOT(0, 1),
+                ByteCode::MOVR((0, 2), Operand::Const(Value::Int(2))),
+                ByteCode::BINOPR(
+                    None,
+                    Operand::Slot(0, 2),
+                    Operand::Const(Value::Int(3)),
+                    BinOp::Mul
+                ),
+                ByteCode::LDSLOT(0, 0),
+                ByteCode::BINOPR(
+                    None,
+                    Operand::Stack,
+                    Operand::Const(Value::Int(0)),
+                    BinOp::Gt
+                ),
+                ByteCode::JOF(6),
+                ByteCode::DONE,
+            ]
+        );
+    }
+}
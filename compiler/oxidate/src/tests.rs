@@ -1,11 +1,12 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
 
     use std::vec;
 
-    use bytecode::ByteCode;
     use bytecode::ByteCode::*;
     use bytecode::Value::*;
+    use bytecode::{ByteCode, SelectOp};
     use parser::Parser;
 
     use crate::compiler::Compiler;
@@ -25,6 +26,21 @@ mod tests {
         assert_eq!(res, exp);
     }
 
+    #[test]
+    fn test_compile_named_args() {
+        // compiled as the call with the args in order of the params
+        assert_eq!(
+            exp_compile_str("fn f(a: int, b: int) {} f(b: 2, a: 1)"),
+            exp_compile_str("fn f(a: int, b: int) {} f(1, 2)")
+        );
+
+        let parsed = Parser::new_from_string("fn f(a: int) {} f(b: 1)")
+            .parse()
+            .expect("Should parse");
+        let err = Compiler::new(parsed).compile().unwrap_err();
+        assert_eq!(err.msg(), "Function 'f' has no parameter named 'b'");
+    }
+
     #[test]
     fn test_compile_simple() {
         let res = exp_compile_str("42;");
@@ -154,9 +170,9 @@ mod tests {
     fn test_compile_let() {
         let res = exp_compile_str("let x = 2;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPE(vec!["x".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             EXITSCOPE,
@@ -168,13 +184,13 @@ mod tests {
         // stmt last
         let res = exp_compile_str("let x = 2; let y = 3; ");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string(), "y".to_string()]),
+            ENTERSCOPE(vec!["x".into(), "y".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             LDC(Int(3)),
-            ASSIGN("y".to_string()),
+            ASSIGNSLOT(0, 1),
             LDC(Unit),
             POP,
             EXITSCOPE,
@@ -186,13 +202,13 @@ mod tests {
         // many
         let res = exp_compile_str("let x = 2; let y = 3; 40");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string(), "y".to_string()]),
+            ENTERSCOPE(vec!["x".into(), "y".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             LDC(Int(3)),
-            ASSIGN("y".to_string()),
+            ASSIGNSLOT(0, 1),
             LDC(Unit),
             POP,
             LDC(Int(40)),
@@ -207,12 +223,12 @@ mod tests {
     fn test_compile_sym() {
         let res = exp_compile_str("let x = 2; -x+2;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPE(vec!["x".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
-            LD("x".to_string()),
+            LDSLOT(0, 0),
             UNOP(bytecode::UnOp::Neg),
             LDC(Int(2)),
             BINOP(bytecode::BinOp::Add),
@@ -224,16 +240,16 @@ mod tests {
 
         let res = exp_compile_str("let x = 2; let y = x; x*5+2");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string(), "y".to_string()]),
+            ENTERSCOPE(vec!["x".into(), "y".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
-            LD("x".to_string()),
-            ASSIGN("y".to_string()),
+            LDSLOT(0, 0),
+            ASSIGNSLOT(0, 1),
             LDC(Unit),
             POP,
-            LD("x".to_string()),
+            LDSLOT(0, 0),
             LDC(Int(5)),
             BINOP(bytecode::BinOp::Mul),
             LDC(Int(2)),
@@ -276,13 +292,13 @@ mod tests {
     fn test_compile_assign() {
         let res = exp_compile_str("let x = 2; x = 3;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPE(vec!["x".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             LDC(Int(3)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             EXITSCOPE,
@@ -293,13 +309,13 @@ mod tests {
         // diff types
         let res = exp_compile_str("let x = 2; x = true;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPE(vec!["x".into()]),
             LDC(Int(2)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             LDC(Bool(true)),
-            ASSIGN("x".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             EXITSCOPE,
@@ -444,9 +460,9 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 LDC(Unit),
-                ASSIGN("x".to_string()),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
                 EXITSCOPE,
@@ -464,18 +480,18 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 ByteCode::ldc(2),
-                ASSIGN("x".to_string()),
+                ASSIGNSLOT(0, 0),
                 ByteCode::ldc(Unit),
                 POP,
-                ENTERSCOPE(vec!["y".to_string()]),
+                ENTERSCOPE(vec!["y".into()]),
                 LDC(Int(3)),
-                ASSIGN("y".to_string()),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
-                LD("x".to_string()),
-                LD("y".to_string()),
+                LDSLOT(1, 0),
+                LDSLOT(0, 0),
                 ByteCode::binop("+"),
                 EXITSCOPE,
                 EXITSCOPE,
@@ -490,9 +506,9 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 ByteCode::ldc(2),
-                ASSIGN("x".to_string()),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
                 LDC(Int(2)),
@@ -522,9 +538,9 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 ByteCode::ldc(2),
-                ASSIGN("x".to_string()),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
                 LDC(Int(2)),
@@ -629,9 +645,9 @@ mod tests {
         ";
 
         let exp = vec![
-            ENTERSCOPE(vec!["y".to_string()]),
+            ENTERSCOPE(vec!["y".into()]),
             LDC(Bool(true)),
-            ByteCode::ASSIGN("y".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             LDC(Bool(false)),
@@ -642,17 +658,17 @@ mod tests {
             GOTO(12),
             LDC(Unit),
             POP,
-            ByteCode::ld("y"),
+            LDSLOT(0, 0),
             JOF(21),
             LDC(Bool(false)),
-            ByteCode::ASSIGN("y".to_string()),
+            ASSIGNSLOT(0, 0),
             LDC(Unit),
             POP,
             LDC(Unit),
             GOTO(22),
             LDC(Unit),
             POP,
-            ByteCode::ld("y"),
+            LDSLOT(0, 0),
             EXITSCOPE,
             DONE,
         ];
@@ -734,12 +750,12 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["y".to_string(), "x".to_string()]),
+                ENTERSCOPE(vec!["y".into(), "x".into()]),
                 LDC(Bool(true)),
-                ByteCode::ASSIGN("y".to_string()),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
-                ByteCode::ld("y".to_string()),
+                LDSLOT(0, 0),
                 JOF(11),
                 LDC(Int(2)),
                 POP,
@@ -748,10 +764,10 @@ mod tests {
                 LDC(Int(3)),
                 POP,
                 LDC(Bool(false)),
-                ByteCode::ASSIGN("x".to_string()),
+                ASSIGNSLOT(0, 1),
                 LDC(Unit),
                 POP,
-                ByteCode::ld("x".to_string()),
+                LDSLOT(0, 1),
                 EXITSCOPE,
                 DONE,
             ],
@@ -771,7 +787,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 LDC(Bool(true)),
                 JOF(7),
                 LDC(Int(2)),
@@ -781,10 +797,10 @@ mod tests {
                 LDC(Int(3)),
                 POP,
                 LDC(Unit),
-                ByteCode::assign("x".to_string()),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
-                ByteCode::ld("x".to_string()),
+                LDSLOT(0, 0),
                 EXITSCOPE,
                 DONE,
             ],
@@ -949,19 +965,19 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 LDC(Int(0)),
-                ByteCode::assign("x"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
-                ByteCode::ld("x"), // 5 - loop cond (start)
+                LDSLOT(0, 0), // 5 - loop cond (start)
                 LDC(Int(3)),
                 ByteCode::binop("<"),
                 JOF(18),
-                ByteCode::ld("x"),
+                LDSLOT(0, 0),
                 LDC(Int(1)),
                 ByteCode::binop("+"),
-                ByteCode::assign("x"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
                 LDC(Unit),
@@ -969,7 +985,7 @@ mod tests {
                 GOTO(5),
                 LDC(Unit), // 18 - loop end (load unit as value)
                 POP,
-                ByteCode::ld("x"),
+                LDSLOT(0, 0),
                 EXITSCOPE,
                 DONE,
             ],
@@ -991,22 +1007,22 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPE(vec!["x".into()]),
                 LDC(Int(0)),
-                ByteCode::assign("x"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
-                LD("x".to_string()),
+                LDSLOT(0, 0),
                 LDC(Int(3)),
                 ByteCode::binop("<"),
                 JOF(28),
-                LD("x".to_string()),
+                LDSLOT(0, 0),
                 LDC(Int(1)),
                 ByteCode::binop("+"),
-                ByteCode::assign("x"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
-                LD("x".to_string()),
+                LDSLOT(0, 0),
                 LDC(Int(2)),
                 ByteCode::binop("=="),
                 JOF(23),
@@ -1021,7 +1037,89 @@ mod tests {
                 GOTO(5),
                 LDC(Unit),
                 POP,
-                LD("x".to_string()),
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_break_exits_scopes() {
+        let t = r"
+        let x = 0;
+        loop {
+            let y = 1;
+            break;
+        }
+        x
+        ";
+        test_comp(
+            t,
+            vec![
+                ENTERSCOPE(vec!["x".into()]),
+                LDC(Int(0)),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                ENTERSCOPE(vec!["y".into()]),
+                LDC(Int(1)),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                EXITSCOPE,
+                GOTO(17),
+                POP,
+                EXITSCOPE,
+                LDC(Unit),
+                POP,
+                GOTO(5),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_break_with_value() {
+        // the value is left on the stack and the break jumps past the unit of the labelled loop
+        let t = r"
+        let x = 'a: loop {
+            loop {
+                let y = 2;
+                break 'a y;
+            }
+        };
+        ";
+        test_comp(
+            t,
+            vec![
+                ENTERSCOPE(vec!["x".into()]),
+                ENTERSCOPE(vec!["y".into()]),
+                LDC(Int(2)),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                GOTO(20),
+                POP,
+                EXITSCOPE,
+                LDC(Unit),
+                POP,
+                GOTO(1),
+                LDC(Unit),
+                POP,
+                LDC(Unit),
+                POP,
+                GOTO(1),
+                LDC(Unit),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
                 EXITSCOPE,
                 DONE,
             ],
@@ -1058,6 +1156,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compile_assert() {
+        let t = "assert(1 < 2)";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("assert"),
+                LDC(Int(1)),
+                LDC(Int(2)),
+                ByteCode::ldc("<"),
+                ByteCode::ldc("1 < 2"),
+                CALL(4),
+                LDC(Unit),
+                DONE,
+            ],
+        );
+
+        let t = "assert(true)";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("assert"),
+                LDC(Bool(true)),
+                LDC(Unit),
+                LDC(Unit),
+                ByteCode::ldc("true"),
+                CALL(4),
+                LDC(Unit),
+                DONE,
+            ],
+        );
+
+        let t = "assert_eq(1 + 1, 2)";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("assert_eq"),
+                LDC(Int(1)),
+                LDC(Int(1)),
+                BINOP(bytecode::BinOp::Add),
+                LDC(Int(2)),
+                ByteCode::ldc("(1+1)"),
+                ByteCode::ldc("2"),
+                CALL(4),
+                LDC(Unit),
+                DONE,
+            ],
+        );
+    }
+
     #[test]
     fn test_compile_fn_decl() {
         let t = r"
@@ -1069,16 +1217,17 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["f".to_string()]),
-                ByteCode::ldc(300),
-                POP,
-                LDF(5, vec![]),
-                GOTO(7),
+                // fn decls are hoisted to the start of the block
+                ENTERSCOPE(vec!["f".into()]),
+                LDF(3, "f".into(), vec![]),
+                GOTO(5),
                 ByteCode::ldc(2),
                 RESET(bytecode::FrameType::CallFrame),
-                ByteCode::assign("f"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
+                ByteCode::ldc(300),
+                POP,
                 EXITSCOPE,
                 DONE,
             ],
@@ -1093,15 +1242,15 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["f".to_string()]),
-                LDF(3, vec![]),
+                ENTERSCOPE(vec!["f".into()]),
+                LDF(3, "f".into(), vec![]),
                 GOTO(8),
                 ByteCode::ldc(2),
                 RESET(bytecode::FrameType::CallFrame),
                 POP,
                 LDC(Unit),
                 RESET(bytecode::FrameType::CallFrame),
-                ByteCode::assign("f"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
                 EXITSCOPE,
@@ -1121,14 +1270,14 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["fac".to_string()]),
-                LDF(3, vec!["n".to_string()]),
+                ENTERSCOPE(vec!["fac".into()]),
+                LDF(3, "fac".into(), vec!["n".into()]),
                 GOTO(7),
                 ByteCode::ldc(2),
-                ByteCode::ld("n"),
+                LDSLOT(0, 0),
                 ByteCode::binop("+"),
                 RESET(bytecode::FrameType::CallFrame),
-                ByteCode::assign("fac"),
+                ASSIGNSLOT(0, 0),
                 LDC(Unit),
                 POP,
                 EXITSCOPE,
@@ -1149,11 +1298,11 @@ mod tests {
             vec![
                 ByteCode::ldc(2),
                 POP,
-                SPAWN(4),
+                LD("func".into()),
+                ByteCode::ldc(1),
+                SPAWN(6, 2),
                 GOTO(9),
                 POP,
-                LD("func".to_string()),
-                ByteCode::ldc(1),
                 CALL(1),
                 DONE,
                 POP,
@@ -1164,6 +1313,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compile_spawn_blk() {
+        let t = r"
+        let h = spawn { 2 };
+        join h
+        ";
+        test_comp(
+            t,
+            vec![
+                ENTERSCOPE(vec!["h".into()]),
+                SPAWN(3, 0),
+                GOTO(6),
+                POP,
+                ByteCode::ldc(2),
+                DONE,
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                JOIN,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_scope() {
+        let t = r"
+        scope {
+            spawn { 2 };
+            3
+        }
+        ";
+        test_comp(
+            t,
+            vec![
+                SPAWNSCOPE,
+                SPAWN(3, 0),
+                GOTO(6),
+                POP,
+                ByteCode::ldc(2),
+                DONE,
+                POP,
+                ByteCode::ldc(3),
+                JOINSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_lock() {
+        // breaking out of the block exits the scope of the lock frame, releasing the lock
+        let t = r"
+        loop {
+            lock(m) { break; }
+        }
+        read_lock(rw) { 2 }
+        ";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("m"),
+                LOCK,
+                EXITSCOPE,
+                GOTO(9),
+                POP,
+                LDC(Unit),
+                EXITSCOPE,
+                POP,
+                GOTO(0),
+                LDC(Unit),
+                POP,
+                ByteCode::ld("rw"),
+                READLOCK,
+                ByteCode::ldc(2),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
     #[test]
     fn test_compile_wait_post() {
         let t = r"
@@ -1188,4 +1420,531 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_compile_method_call() {
+        let t = "h.is_finished()";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("h"),
+                LDMETHOD("is_finished".into()),
+                CALL(1),
+                DONE,
+            ],
+        );
+
+        let t = "h.id();";
+        test_comp(
+            t,
+            vec![ByteCode::ld("h"), LDMETHOD("id".into()), CALL(1), POP, DONE],
+        );
+    }
+
+    #[test]
+    fn test_compile_structs() {
+        let t = "struct P { x: int, y: int } let p = P { y: 2, x: 1 }; p.x";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["P", "p"]),
+                STRUCT("P".into(), vec!["x".into(), "y".into()]),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                ByteCode::ldc(2),
+                ByteCode::ldc(1),
+                NEWSTRUCT(vec!["y".into(), "x".into()]),
+                ASSIGNSLOT(0, 1),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 1),
+                LDFIELD("x".into()),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        let t = "struct P { x: int } impl P { fn get(self) -> int { self.x } } let p = P { x: 1 }; p.x = 2; p.get()";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["P", "p"]),
+                STRUCT("P".into(), vec!["x".into()]),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                // impl: the methods are set on the struct type
+                LDSLOT(0, 0),
+                ByteCode::ldf(8, "get", vec!["self"]),
+                GOTO(11),
+                LDSLOT(0, 0),
+                LDFIELD("x".into()),
+                RESET(bytecode::FrameType::CallFrame),
+                SETMETHOD("get".into()),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                ByteCode::ldc(1),
+                NEWSTRUCT(vec!["x".into()]),
+                ASSIGNSLOT(0, 1),
+                LDC(Unit),
+                POP,
+                // p.x = 2
+                LDSLOT(0, 1),
+                ByteCode::ldc(2),
+                SETFIELD("x".into()),
+                ASSIGNSLOT(0, 1),
+                LDC(Unit),
+                POP,
+                // p.get()
+                LDSLOT(0, 1),
+                LDMETHOD("get".into()),
+                CALL(1),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_enums() {
+        let t = "enum E { A(int), B } A(1)";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["A", "B"]),
+                // A is a function that wraps its argument in the variant
+                ByteCode::ldf(3, "A", vec!["$value"]),
+                GOTO(6),
+                LDSLOT(0, 0),
+                NEWVARIANT("E".into(), "A".into(), true),
+                RESET(bytecode::FrameType::CallFrame),
+                ASSIGNSLOT(0, 0),
+                NEWVARIANT("E".into(), "B".into(), false),
+                ASSIGNSLOT(0, 1),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                ByteCode::ldc(1),
+                CALL(1),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        let t = "enum E { A(int), B } match B { A(x) => { x } B => { 0 } }";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["A", "B"]),
+                ByteCode::ldf(3, "A", vec!["$value"]),
+                GOTO(6),
+                LDSLOT(0, 0),
+                NEWVARIANT("E".into(), "A".into(), true),
+                RESET(bytecode::FrameType::CallFrame),
+                ASSIGNSLOT(0, 0),
+                NEWVARIANT("E".into(), "B".into(), false),
+                ASSIGNSLOT(0, 1),
+                LDC(Unit),
+                POP,
+                // the matched value is evaluated once
+                ByteCode::enterscope(vec!["$match"]),
+                LDSLOT(1, 1),
+                ASSIGNSLOT(0, 0),
+                // A(x) => { x }
+                LDSLOT(0, 0),
+                TESTVARIANT("A".into()),
+                JOF(24),
+                ByteCode::enterscope(vec!["x"]),
+                LDSLOT(1, 0),
+                LDPAYLOAD,
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                GOTO(25),
+                // the last arm needs no test
+                ByteCode::ldc(0),
+                EXITSCOPE,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        // alternatives of nested patterns are tested in turn and share the block
+        let t = "match x { Some(1 | 2) => { 0 } _ => { 1 } }";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["$match"]),
+                ByteCode::ld("x"),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                TESTVARIANT("Some".into()),
+                JOF(19),
+                // 1 matched goes to the block, otherwise 2 is tried
+                LDSLOT(0, 0),
+                LDPAYLOAD,
+                ByteCode::ldc(1),
+                BINOP(bytecode::BinOp::Eq),
+                JOF(12),
+                GOTO(17),
+                LDSLOT(0, 0),
+                LDPAYLOAD,
+                ByteCode::ldc(2),
+                BINOP(bytecode::BinOp::Eq),
+                JOF(19),
+                ByteCode::ldc(0),
+                GOTO(20),
+                ByteCode::ldc(1),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        // a range tests both bounds, and @ binds the value it matched
+        let t = "match x { n @ 1..=9 => { n } _ => { 0 } }";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["$match"]),
+                ByteCode::ld("x"),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                ByteCode::ldc(1),
+                BINOP(bytecode::BinOp::Ge),
+                JOF(17),
+                LDSLOT(0, 0),
+                ByteCode::ldc(9),
+                BINOP(bytecode::BinOp::Le),
+                JOF(17),
+                ByteCode::enterscope(vec!["n"]),
+                LDSLOT(1, 0),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                GOTO(18),
+                ByteCode::ldc(0),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        // a false guard leaves the scope of the binding and falls through to the next arm
+        let t = "match x { Some(v) if v > 1 => { v } Some(v) => { 0 } None => { 1 } }";
+        test_comp(
+            t,
+            vec![
+                ByteCode::enterscope(vec!["$match"]),
+                ByteCode::ld("x"),
+                ASSIGNSLOT(0, 0),
+                // Some(v) if v > 1 => { v }
+                LDSLOT(0, 0),
+                TESTVARIANT("Some".into()),
+                JOF(18),
+                ByteCode::enterscope(vec!["v"]),
+                LDSLOT(1, 0),
+                LDPAYLOAD,
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                ByteCode::ldc(1),
+                BINOP(bytecode::BinOp::Gt),
+                JOF(17),
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                GOTO(29),
+                EXITSCOPE,
+                // Some(v) => { 0 }
+                LDSLOT(0, 0),
+                TESTVARIANT("Some".into()),
+                JOF(28),
+                ByteCode::enterscope(vec!["v"]),
+                LDSLOT(1, 0),
+                LDPAYLOAD,
+                ASSIGNSLOT(0, 0),
+                ByteCode::ldc(0),
+                EXITSCOPE,
+                GOTO(29),
+                ByteCode::ldc(1),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_arrays() {
+        test_comp(
+            "[1, 2]",
+            vec![ByteCode::ldc(1), ByteCode::ldc(2), NEWARRAY(2), DONE],
+        );
+
+        // the range is evaluated once into an iterator, and a false condition skips the element
+        test_comp(
+            "[x for x in 0..3 if x > 0]",
+            vec![
+                NEWARRAY(0),
+                ByteCode::enterscope(vec!["x", "$iter"]),
+                ByteCode::ldc(0),
+                ByteCode::ldc(3),
+                ITERRANGE,
+                ASSIGNSLOT(0, 1),
+                LDSLOT(0, 1),
+                NEXT(16),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                ByteCode::ldc(0),
+                BINOP(bytecode::BinOp::Gt),
+                JOF(6),
+                LDSLOT(0, 0),
+                APPEND,
+                GOTO(6),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        test_comp(
+            "[x for x in []]",
+            vec![
+                NEWARRAY(0),
+                ByteCode::enterscope(vec!["x", "$iter"]),
+                NEWARRAY(0),
+                ITER,
+                ASSIGNSLOT(0, 1),
+                LDSLOT(0, 1),
+                NEXT(11),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                APPEND,
+                GOTO(5),
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_for() {
+        // break exits the scope of the loop and jumps past it
+        test_comp(
+            r#"for c in "ab" { break; }"#,
+            vec![
+                ByteCode::enterscope(vec!["c", "$iter"]),
+                ByteCode::ldc("ab"),
+                ITER,
+                ASSIGNSLOT(0, 1),
+                LDSLOT(0, 1),
+                NEXT(13),
+                ASSIGNSLOT(0, 0),
+                EXITSCOPE,
+                GOTO(14),
+                POP,
+                LDC(Unit),
+                POP,
+                GOTO(4),
+                EXITSCOPE,
+                LDC(Unit),
+                POP,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_generator() {
+        // the body of a generator starts with GENERATOR, and yield suspends it with the value
+        test_comp(
+            "fn gen() { yield 1; }",
+            vec![
+                ByteCode::enterscope(vec!["gen"]),
+                LDF(3, "gen".into(), vec![]),
+                GOTO(10),
+                GENERATOR,
+                ByteCode::ldc(1),
+                SUSPEND,
+                LDC(Unit),
+                POP,
+                LDC(Unit),
+                RESET(bytecode::FrameType::CallFrame),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_let_destructure() {
+        // the value is bound in a scope of its own, and each part is assigned to its name in the block
+        test_comp(
+            "let [a, ..b] = c;",
+            vec![
+                ByteCode::enterscope(vec!["a", "b"]),
+                ByteCode::ld("c"),
+                ByteCode::enterscope(vec!["$let"]),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                CHECKLEN(1, true),
+                LDSLOT(0, 0),
+                ByteCode::ldc(0),
+                LDELEM,
+                ASSIGNSLOT(1, 0),
+                LDSLOT(0, 0),
+                ByteCode::ldc(1),
+                LDC(Unit),
+                SLICE,
+                ASSIGNSLOT(1, 1),
+                EXITSCOPE,
+                LDC(Unit),
+                POP,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        test_comp(
+            "let P { x: y } = p;",
+            vec![
+                ByteCode::enterscope(vec!["y"]),
+                ByteCode::ld("p"),
+                ByteCode::enterscope(vec!["$let"]),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                LDFIELD("x".into()),
+                ASSIGNSLOT(1, 0),
+                EXITSCOPE,
+                LDC(Unit),
+                POP,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_index_and_slice() {
+        test_comp(
+            "let a = [1]; a[0]",
+            vec![
+                ByteCode::enterscope(vec!["a"]),
+                ByteCode::ldc(1),
+                NEWARRAY(1),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                ByteCode::ldc(0),
+                LDELEM,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+
+        // missing bounds are unit
+        test_comp(
+            "let a = [1]; a[..1]",
+            vec![
+                ByteCode::enterscope(vec!["a"]),
+                ByteCode::ldc(1),
+                NEWARRAY(1),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                LDSLOT(0, 0),
+                LDC(Unit),
+                ByteCode::ldc(1),
+                SLICE,
+                EXITSCOPE,
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_select() {
+        let t = "select { a => { 2 } b => { 3; } }";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("a"),
+                ByteCode::ld("b"),
+                ByteCode::SELECT(vec![3, 5]),
+                ByteCode::ldc(2),
+                GOTO(9),
+                ByteCode::ldc(3),
+                POP,
+                LDC(Unit),
+                GOTO(9),
+                DONE,
+            ],
+        );
+
+        let t = "select { recv(ch) -> x => { x } send(ch, 1) => { 2 } }";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("ch"),
+                ByteCode::ld("ch"),
+                ByteCode::ldc(1),
+                ByteCode::SELECTARMS(vec![(SelectOp::Recv, 4), (SelectOp::Send, 9)]),
+                ByteCode::enterscope(vec!["x"]),
+                ASSIGNSLOT(0, 0),
+                LDSLOT(0, 0),
+                EXITSCOPE,
+                GOTO(12),
+                POP,
+                ByteCode::ldc(2),
+                GOTO(12),
+                DONE,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compile_incremental() {
+        let mut comp = Compiler::new(Parser::new_from_string("").parse().expect("Should parse"));
+        let mut arr = vec![];
+
+        let mut compile = |inp: &str, arr: &mut Vec<ByteCode>| {
+            let parsed = Parser::new_from_string(inp).parse().expect("Should parse");
+            comp.compile_incremental(&parsed, arr)
+        };
+
+        compile("let x = 2;", &mut arr).expect("Should compile");
+        compile("if x > 1 { x } else { 0 }", &mut arr).expect("Should compile");
+        // Fails on the unknown parameter, leaving the code and scopes as they were
+        compile("let y = 3; fn f(a: int) {} f(b: 1);", &mut arr).expect_err("Should fail");
+        compile("let y = x;", &mut arr).expect("Should compile");
+
+        assert_eq!(
+            arr,
+            vec![
+                ByteCode::enterscope(vec!["x"]),
+                ByteCode::ldc(2),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                DONE,
+                // The scope of x is never exited, and jumps land after the earlier code
+                LDSLOT(0, 0),
+                ByteCode::ldc(1),
+                BINOP(bytecode::BinOp::Gt),
+                JOF(12),
+                LDSLOT(0, 0),
+                GOTO(13),
+                ByteCode::ldc(0),
+                DONE,
+                ByteCode::enterscope(vec!["y"]),
+                LDSLOT(1, 0),
+                ASSIGNSLOT(0, 0),
+                LDC(Unit),
+                POP,
+                DONE,
+            ]
+        );
+    }
 }
@@ -0,0 +1,263 @@
+use bytecode::{builtin, BinOp, ByteCode, FrameType, Operand, UnOp, Value};
+
+use crate::compiler::CompileError;
+
+/// The values, frames and instructions the generated code runs on, and its main function.
+/// Copied into every program as is, so it is written as the source of a binary rather than a module.
+const PRELUDE: &str = include_str!("prelude.rs");
+
+/// Why programs using other constants, names or instructions can't be transpiled.
+const UNSUPPORTED_CONST: &str =
+    "only constants of unit, ints, floats, bools and strings are supported";
+const UNSUPPORTED_NAME: &str =
+    "only the names of constants and of builtins on ints, floats, strings and arrays are supported";
+const UNSUPPORTED_INSTR: &str =
+    "only programs of a single thread without structs, enums, variants, generators or try blocks are supported";
+
+/// Generate a standalone Rust program that runs the compiled program as ignite would on a single thread,
+/// printing its final value if there is one. Every instruction becomes an arm of a match on the program counter,
+/// with its operands inlined, and values are those of a `Value` enum like the one of the VM.
+///
+/// # Errors
+///
+/// If the program uses anything but ints, floats, bools, strings, arrays, functions and the builtins on them,
+/// e.g. threads, structs or generators, or names the compiler could not resolve to slots.
+pub fn transpile(instrs: &[ByteCode]) -> Result<String, CompileError> {
+    let mut src = String::new();
+    src.push_str("// Transpiled from RustScript by oxidate.\n");
+    src.push_str("#![allow(dead_code, unreachable_code)]\n\n");
+    src.push_str(PRELUDE);
+
+    src.push_str("\nfn run(m: &mut Machine) -> Result<()> {\n");
+    src.push_str("    let mut pc = 0;\n");
+    src.push_str("    loop {\n");
+    src.push_str("        pc = match pc {\n");
+    for (pc, instr) in instrs.iter().enumerate() {
+        let (stmts, next) = translate(pc, instr)?;
+        src.push_str(&format!("            // {:?}\n", instr));
+        src.push_str(&format!("            {} => {{\n", pc));
+        for stmt in stmts {
+            src.push_str(&format!("                {};\n", stmt));
+        }
+        src.push_str(&format!("                {}\n", next));
+        src.push_str("            }\n");
+    }
+    src.push_str("            _ => unreachable!(\"pc out of bounds\"),\n");
+    src.push_str("        };\n");
+    src.push_str("    }\n");
+    src.push_str("}\n");
+
+    Ok(src)
+}
+
+/// The Cargo.toml of a package building the transpiled program as a binary of the given name.
+/// The package is its own workspace, so it builds wherever it is put.
+pub fn manifest(name: &str) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name = format!("rst_{}", name);
+    }
+
+    let package = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        name
+    );
+    format!(
+        "{}\n[workspace]\n\n[profile.release]\npanic = \"abort\"\n",
+        package
+    )
+}
+
+/// The statements of the arm of the instruction at pc, and the expression giving the pc to continue at.
+fn translate(pc: usize, instr: &ByteCode) -> Result<(Vec<String>, String), CompileError> {
+    let unsupported = |reason: &str| {
+        let msg = format!("Can't transpile {:?} at {} to Rust: {}", instr, pc, reason);
+        CompileError::new(&msg)
+    };
+    let lit = |val: &Value| literal(val).ok_or_else(|| unsupported(UNSUPPORTED_CONST));
+    let operand = |op: &Operand| operand(op).ok_or_else(|| unsupported(UNSUPPORTED_CONST));
+    let next = (pc + 1).to_string();
+    let branch = |cond: &str, stmt: &str, target: usize| {
+        format!("if {} {{ {}{} }} else {{ {} }}", cond, stmt, next, target)
+    };
+
+    let stmts = match instr {
+        ByteCode::DONE => return Ok((vec![], "return Ok(())".to_string())),
+        ByteCode::LDC(val) => vec![format!("m.push({})", lit(val)?)],
+        ByteCode::LD(sym) => {
+            let val = global(sym.as_str()).ok_or_else(|| unsupported(UNSUPPORTED_NAME))?;
+            vec![format!("m.push({})", val)]
+        }
+        ByteCode::LDSLOT(depth, idx) => vec![format!("m.ld_slot({}, {})?", depth, idx)],
+        ByteCode::ASSIGNSLOT(depth, idx) => vec![format!("m.assign_slot({}, {})?", depth, idx)],
+        ByteCode::POP => vec!["m.pop()".to_string()],
+        ByteCode::BINOP(op) => vec![format!("m.binop({})?", binop(op))],
+        ByteCode::UNOP(op) => vec![format!("m.unop({})?", unop(op))],
+        ByteCode::JOF(addr) => return Ok((vec![], branch("m.pop_bool()?", "", *addr))),
+        ByteCode::JOFORPOP(addr) => {
+            return Ok((vec![], branch("m.peek_bool()?", "m.pop(); ", *addr)))
+        }
+        ByteCode::GOTO(addr) => return Ok((vec![], addr.to_string())),
+        ByteCode::NEXT(addr) => return Ok((vec![], branch("m.next()?", "", *addr))),
+        ByteCode::CALL(arity) => return Ok((vec![], format!("m.call({}, {})?", arity, next))),
+        ByteCode::RESET(FrameType::CallFrame) => return Ok((vec![], "m.reset()".to_string())),
+        ByteCode::ENTERSCOPE(syms) => vec![format!("m.enter_scope({})", syms.len())],
+        ByteCode::EXITSCOPE => vec!["m.exit_scope()".to_string()],
+        ByteCode::LDF(addr, _, prms) => vec![format!("m.closure({}, {})", addr, prms.len())],
+        ByteCode::NEWARRAY(len) => vec![format!("m.new_array({})", len)],
+        ByteCode::APPEND => vec!["m.append()?".to_string()],
+        ByteCode::LDELEM => vec!["m.ld_elem()?".to_string()],
+        ByteCode::ITER => vec!["m.iter()?".to_string()],
+        ByteCode::ITERRANGE => vec!["m.iter_range()?".to_string()],
+        ByteCode::LDCBINOP(val, op) => vec![
+            format!("m.push({})", lit(val)?),
+            format!("m.binop({})?", binop(op)),
+        ],
+        ByteCode::LDSLOTSBINOP(lhs, rhs, op) => vec![
+            format!("m.ld_slot({}, {})?", lhs.0, lhs.1),
+            format!("m.ld_slot({}, {})?", rhs.0, rhs.1),
+            format!("m.binop({})?", binop(op)),
+        ],
+        ByteCode::BINOPR(dst, lhs, rhs, op) => {
+            // The right-hand side is read first, as it is the top of the stack if both are popped
+            let mut stmts = vec![
+                format!("let rhs = {}", operand(rhs)?),
+                format!("let lhs = {}", operand(lhs)?),
+                format!("let val = binop({}, lhs, rhs)?", binop(op)),
+            ];
+            stmts.push(match dst {
+                Some((depth, idx)) => format!("m.set_slot({}, {}, val)?", depth, idx),
+                None => "m.push(val)".to_string(),
+            });
+            stmts
+        }
+        ByteCode::MOVR((depth, idx), src) => vec![
+            format!("let val = {}", operand(src)?),
+            format!("m.set_slot({}, {}, val)?", depth, idx),
+        ],
+        _ => return Err(unsupported(UNSUPPORTED_INSTR)),
+    };
+
+    Ok((stmts, next))
+}
+
+/// The expression of a constant, if it is of a type the generated code has.
+fn literal(val: &Value) -> Option<String> {
+    let lit = match val {
+        Value::Unit => "Value::Unit".to_string(),
+        Value::Int(i) => format!("Value::Int({})", i),
+        Value::Float(x) if x.is_finite() => format!("Value::Float({:?})", x),
+        Value::Float(x) => format!("Value::Float(f64::from_bits({:#x}))", x.to_bits()),
+        Value::Bool(b) => format!("Value::Bool({})", b),
+        Value::String(s) => format!("Value::str({:?})", s.as_str()),
+        _ => return None,
+    };
+    Some(lit)
+}
+
+/// The expression of a name bound in the global frame, if it is a constant or a builtin the generated code has.
+fn global(sym: &str) -> Option<String> {
+    let val = match sym {
+        builtin::TRUE_SYM => "Value::Bool(true)",
+        builtin::FALSE_SYM => "Value::Bool(false)",
+        builtin::PI_SYM => "Value::Float(std::f64::consts::PI)",
+        builtin::E_SYM => "Value::Float(std::f64::consts::E)",
+        builtin::MAX_INT_SYM => "Value::Int(i64::MAX)",
+        builtin::MIN_INT_SYM => "Value::Int(i64::MIN)",
+        builtin::MAX_FLOAT_SYM => "Value::Float(f64::MAX)",
+        builtin::MIN_FLOAT_SYM => "Value::Float(f64::MIN)",
+        builtin::EPSILON_SYM => "Value::Float(f64::EPSILON)",
+        builtin::PRINT_SYM => "Value::Builtin(Builtin::Print)",
+        builtin::PRINTLN_SYM => "Value::Builtin(Builtin::Println)",
+        builtin::ABS_SYM => "Value::Builtin(Builtin::Abs)",
+        builtin::SQRT_SYM => "Value::Builtin(Builtin::Sqrt)",
+        builtin::POW_SYM => "Value::Builtin(Builtin::Pow)",
+        builtin::SIN_SYM => "Value::Builtin(Builtin::Sin)",
+        builtin::COS_SYM => "Value::Builtin(Builtin::Cos)",
+        builtin::TAN_SYM => "Value::Builtin(Builtin::Tan)",
+        builtin::LOG_SYM => "Value::Builtin(Builtin::Log)",
+        builtin::MIN_SYM => "Value::Builtin(Builtin::Min)",
+        builtin::MAX_SYM => "Value::Builtin(Builtin::Max)",
+        builtin::INT_TO_FLOAT_SYM => "Value::Builtin(Builtin::IntToFloat)",
+        builtin::FLOAT_TO_INT_SYM => "Value::Builtin(Builtin::FloatToInt)",
+        builtin::ITOA_SYM => "Value::Builtin(Builtin::Itoa)",
+        builtin::ATOI_SYM => "Value::Builtin(Builtin::Atoi)",
+        builtin::LEN_SYM => "Value::Builtin(Builtin::Len)",
+        builtin::EXIT_SYM => "Value::Builtin(Builtin::Exit)",
+        _ => return None,
+    };
+    Some(val.to_string())
+}
+
+/// The expression reading an operand of the register backend.
+fn operand(operand: &Operand) -> Option<String> {
+    match operand {
+        Operand::Stack => Some("m.pop()".to_string()),
+        Operand::Slot(depth, idx) => Some(format!("m.slot({}, {})?", depth, idx)),
+        Operand::Const(val) => literal(val),
+    }
+}
+
+fn binop(op: &BinOp) -> String {
+    format!("BinOp::{:?}", op)
+}
+
+fn unop(op: &UnOp) -> String {
+    format!("UnOp::{:?}", op)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile_from_string;
+
+    use super::*;
+
+    #[test]
+    fn test_transpile() {
+        let instrs = compile_from_string("fn f(x: int) -> int { x * 2 } println(f(21));", true)
+            .expect("Should compile");
+        let src = transpile(&instrs).unwrap();
+
+        assert!(src.starts_with("// Transpiled from RustScript by oxidate.\n"));
+        assert!(src.contains("fn main() {"));
+        assert!(src.contains("m.push(Value::Builtin(Builtin::Println))"));
+        assert!(src.contains("m.binop(BinOp::Mul)?"));
+        assert!(src.contains("m.push(Value::Int(21))"));
+        assert!(src.contains("=> {\n                m.reset()\n            }"));
+    }
+
+    #[test]
+    fn test_transpile_literals() {
+        assert_eq!(literal(&Value::Float(2.0)).unwrap(), "Value::Float(2.0)");
+        assert_eq!(
+            literal(&Value::Float(f64::INFINITY)).unwrap(),
+            "Value::Float(f64::from_bits(0x7ff0000000000000))"
+        );
+        assert_eq!(
+            literal(&Value::from("a \"b\"\n")).unwrap(),
+            "Value::str(\"a \\\"b\\\"\\n\")"
+        );
+        assert_eq!(global("x"), None);
+    }
+
+    #[test]
+    fn test_transpile_unsupported() {
+        let instrs =
+            compile_from_string("let s = sem_create(); wait s; 1", true).expect("Should compile");
+        let err = transpile(&instrs).unwrap_err();
+        assert!(err.msg().starts_with("Can't transpile"), "{}", err.msg());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let manifest = manifest("2 fib");
+        assert!(manifest.starts_with("[package]\nname = \"rst_2_fib\"\n"));
+        assert!(manifest.contains("[workspace]"));
+    }
+}
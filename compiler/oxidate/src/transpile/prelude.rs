@@ -0,0 +1,662 @@
+// The runtime of a RustScript program transpiled to Rust: the values, frames and instructions
+// the program was compiled to, as ignite implements them for a single thread.
+
+use std::{
+    cell::RefCell,
+    fmt::{self, Display},
+    io::Write,
+    rc::Rc,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// A frame of the environment, holding the slots of a block or a call.
+type Env = Rc<RefCell<Frame>>;
+
+struct Frame {
+    parent: Option<Env>,
+    slots: Vec<Value>,
+}
+
+#[derive(Clone)]
+enum Value {
+    Uninitialized,
+    Unit,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(Rc<String>),
+    Array(Rc<Vec<Value>>),
+    Closure(Rc<Closure>),
+    Builtin(Builtin),
+    Iter(Rc<RefCell<Iter>>),
+}
+
+struct Closure {
+    addr: usize,
+    arity: usize,
+    env: Env,
+}
+
+#[derive(Clone, Copy)]
+enum Builtin {
+    Print,
+    Println,
+    Abs,
+    Sqrt,
+    Pow,
+    Sin,
+    Cos,
+    Tan,
+    Log,
+    Min,
+    Max,
+    IntToFloat,
+    FloatToInt,
+    Itoa,
+    Atoi,
+    Len,
+    Exit,
+}
+
+enum Iter {
+    Range { next: i64, end: i64 },
+    Array { elems: Rc<Vec<Value>>, idx: usize },
+    Chars { chars: Vec<char>, idx: usize },
+}
+
+#[derive(Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Gt,
+    Lt,
+    Eq,
+    And,
+    Or,
+    Ge,
+    Le,
+}
+
+#[derive(Clone, Copy)]
+enum UnOp {
+    Neg,
+    Not,
+}
+
+/// What a frame of the runtime stack restores when it is popped.
+enum Saved {
+    Block(Env),
+    Call(Env, usize),
+}
+
+struct Machine {
+    stack: Vec<Value>,
+    env: Env,
+    frames: Vec<Saved>,
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Uninitialized => "Unitialized",
+            Value::Unit => "Unit",
+            Value::Int(_) => "Int",
+            Value::Float(_) => "Float",
+            Value::Bool(_) => "Bool",
+            Value::String(_) => "String",
+            Value::Array(_) => "Array",
+            Value::Closure(_) | Value::Builtin(_) => "Closure",
+            Value::Iter(_) => "Iter",
+        }
+    }
+
+    fn str(s: &str) -> Value {
+        Value::String(Rc::new(s.to_string()))
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Uninitialized => write!(f, "uninitialized"),
+            Value::Unit => write!(f, "()"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Array(elems) => {
+                let elems: Vec<String> = elems.iter().map(|x| x.to_string()).collect();
+                write!(f, "[{}]", elems.join(", "))
+            }
+            Value::Closure(_) | Value::Builtin(_) => write!(f, "closure"),
+            Value::Iter(_) => write!(f, "iter"),
+        }
+    }
+}
+
+fn bad_type(expected: &str, found: &Value) -> String {
+    format!(
+        "Bad type: expected {}, found {}",
+        expected,
+        found.type_name()
+    )
+}
+
+fn op_str(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Gt => ">",
+        BinOp::Lt => "<",
+        BinOp::Eq => "==",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+        BinOp::Ge => ">=",
+        BinOp::Le => "<=",
+    }
+}
+
+/// Equality of values that can be compared, element by element for arrays.
+fn structural_eq(lhs: &Value, rhs: &Value) -> Option<bool> {
+    let eq = match (lhs, rhs) {
+        (Value::Unit, Value::Unit) => true,
+        (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+        (Value::Float(lhs), Value::Float(rhs)) => lhs == rhs,
+        (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+        (Value::String(lhs), Value::String(rhs)) => lhs == rhs,
+        (Value::Array(lhs), Value::Array(rhs)) => {
+            if lhs.len() != rhs.len() {
+                return Some(false);
+            }
+            for (lhs, rhs) in lhs.iter().zip(rhs.iter()) {
+                if !structural_eq(lhs, rhs)? {
+                    return Some(false);
+                }
+            }
+            true
+        }
+        _ => return None,
+    };
+    Some(eq)
+}
+
+/// Arithmetic on ints, which fails on overflow and division by zero.
+fn int_arith(op: BinOp, lhs: i64, rhs: i64) -> Result<i64> {
+    let expr = || format!("{} {} {}", lhs, op_str(op), rhs);
+
+    if matches!(op, BinOp::Div | BinOp::Mod) && rhs == 0 {
+        return Err(format!("Division by zero: {}", expr()));
+    }
+
+    let res = match op {
+        BinOp::Add => lhs.checked_add(rhs),
+        BinOp::Sub => lhs.checked_sub(rhs),
+        BinOp::Mul => lhs.checked_mul(rhs),
+        BinOp::Div => lhs.checked_div(rhs),
+        _ => lhs.checked_rem(rhs),
+    };
+
+    res.ok_or_else(|| format!("Integer overflow: {}", expr()))
+}
+
+fn binop(op: BinOp, lhs: Value, rhs: Value) -> Result<Value> {
+    if let BinOp::Eq = op {
+        if let Some(eq) = structural_eq(&lhs, &rhs) {
+            return Ok(Value::Bool(eq));
+        }
+    }
+
+    let unsupported = || {
+        format!(
+            "Unsupported operation {} on type {}",
+            op_str(op),
+            rhs.type_name()
+        )
+    };
+
+    let res = match (&lhs, &rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                Value::Int(int_arith(op, *lhs, *rhs)?)
+            }
+            BinOp::Gt => Value::Bool(lhs > rhs),
+            BinOp::Lt => Value::Bool(lhs < rhs),
+            BinOp::Ge => Value::Bool(lhs >= rhs),
+            BinOp::Le => Value::Bool(lhs <= rhs),
+            _ => return Err(unsupported()),
+        },
+        (Value::Float(lhs), Value::Float(rhs)) => match op {
+            BinOp::Add => Value::Float(lhs + rhs),
+            BinOp::Sub => Value::Float(lhs - rhs),
+            BinOp::Mul => Value::Float(lhs * rhs),
+            BinOp::Div => Value::Float(lhs / rhs),
+            BinOp::Gt => Value::Bool(lhs > rhs),
+            BinOp::Lt => Value::Bool(lhs < rhs),
+            BinOp::Ge => Value::Bool(lhs >= rhs),
+            BinOp::Le => Value::Bool(lhs <= rhs),
+            _ => return Err(unsupported()),
+        },
+        (Value::Bool(lhs), Value::Bool(rhs)) => match op {
+            BinOp::And => Value::Bool(*lhs && *rhs),
+            BinOp::Or => Value::Bool(*lhs || *rhs),
+            _ => return Err(unsupported()),
+        },
+        (Value::String(lhs), Value::String(rhs)) => match op {
+            BinOp::Add => Value::String(Rc::new(format!("{}{}", lhs, rhs))),
+            BinOp::Gt => Value::Bool(lhs > rhs),
+            BinOp::Lt => Value::Bool(lhs < rhs),
+            BinOp::Ge => Value::Bool(lhs >= rhs),
+            BinOp::Le => Value::Bool(lhs <= rhs),
+            _ => return Err(unsupported()),
+        },
+        (Value::Int(_), Value::Float(_)) | (Value::Float(_), Value::Int(_)) => {
+            return Err(format!(
+                "Can't apply {} to Int and Float, convert one operand with int_to_float or float_to_int",
+                op_str(op)
+            ))
+        }
+        (lhs, rhs) if lhs.type_name() == rhs.type_name() => return Err(unsupported()),
+        (lhs, rhs) => {
+            return Err(format!(
+                "Type mismatch: expected {}, found {}",
+                lhs.type_name(),
+                rhs.type_name()
+            ))
+        }
+    };
+
+    Ok(res)
+}
+
+fn unop(op: UnOp, val: Value) -> Result<Value> {
+    match (op, &val) {
+        (UnOp::Neg, Value::Int(i)) => i
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or_else(|| format!("Integer overflow: -({})", i)),
+        (UnOp::Not, Value::Int(i)) => Ok(Value::Int(!i)),
+        (UnOp::Neg, Value::Float(x)) => Ok(Value::Float(-x)),
+        (UnOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+        (op, val) => {
+            let op = match op {
+                UnOp::Neg => "-",
+                UnOp::Not => "!",
+            };
+            Err(format!(
+                "Unsupported operation {} on type {}",
+                op,
+                val.type_name()
+            ))
+        }
+    }
+}
+
+fn to_int(val: &Value) -> Result<i64> {
+    match val {
+        Value::Int(i) => Ok(*i),
+        _ => Err(bad_type("Int", val)),
+    }
+}
+
+fn to_float(val: &Value) -> Result<f64> {
+    match val {
+        Value::Float(x) => Ok(*x),
+        _ => Err(bad_type("Float", val)),
+    }
+}
+
+fn len(val: &Value) -> Result<usize> {
+    match val {
+        Value::Array(elems) => Ok(elems.len()),
+        Value::String(s) => Ok(s.chars().count()),
+        _ => Err(bad_type("Array or String", val)),
+    }
+}
+
+/// Apply the builtin to the arguments, returning its result if it has one.
+fn apply_builtin(builtin: Builtin, args: Vec<Value>) -> Result<Option<Value>> {
+    let arg = |i: usize| &args[i];
+    let float = |f: fn(f64) -> f64| to_float(arg(0)).map(|x| Value::Float(f(x)));
+
+    let res = match builtin {
+        Builtin::Print => {
+            for arg in args.iter() {
+                print!("{}", arg);
+            }
+            return Ok(None);
+        }
+        Builtin::Println => {
+            let line: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            println!("{}", line.concat());
+            return Ok(None);
+        }
+        Builtin::Abs => match arg(0) {
+            Value::Int(i) => Value::Int(i.abs()),
+            Value::Float(x) => Value::Float(x.abs()),
+            val => {
+                return Err(format!(
+                    "Bad type, expected Integer or Float, found {}",
+                    val.type_name()
+                ))
+            }
+        },
+        Builtin::Sqrt => float(f64::sqrt)?,
+        Builtin::Sin => float(f64::sin)?,
+        Builtin::Cos => float(f64::cos)?,
+        Builtin::Tan => float(f64::tan)?,
+        Builtin::Log => float(|x| x.log(10.0))?,
+        Builtin::Pow => Value::Float(to_float(arg(0))?.powf(to_float(arg(1))?)),
+        Builtin::Min | Builtin::Max => match (arg(0), arg(1)) {
+            (Value::Int(a), Value::Int(b)) if matches!(builtin, Builtin::Min) => {
+                Value::Int(*a.min(b))
+            }
+            (Value::Int(a), Value::Int(b)) => Value::Int(*a.max(b)),
+            (Value::Float(a), Value::Float(b)) if matches!(builtin, Builtin::Min) => {
+                Value::Float(a.min(*b))
+            }
+            (Value::Float(a), Value::Float(b)) => Value::Float(a.max(*b)),
+            (a, b) => {
+                return Err(format!(
+                    "Type mismatch, expected {}, found {}",
+                    a.type_name(),
+                    b.type_name()
+                ))
+            }
+        },
+        Builtin::IntToFloat => Value::Float(to_int(arg(0))? as f64),
+        Builtin::FloatToInt => Value::Int(to_float(arg(0))? as i64),
+        Builtin::Itoa => Value::str(&to_int(arg(0))?.to_string()),
+        Builtin::Atoi => match arg(0) {
+            Value::String(s) => Value::Int(s.parse().map_err(|err| format!("{}", err))?),
+            val => return Err(bad_type("String", val)),
+        },
+        Builtin::Len => Value::Int(len(arg(0))? as i64),
+        Builtin::Exit => {
+            let code = to_int(arg(0))?;
+            let _ = std::io::stdout().flush();
+            // Statuses are truncated to a byte, as by the exit of a process
+            std::process::exit(code as u8 as i32);
+        }
+    };
+
+    Ok(Some(res))
+}
+
+impl Machine {
+    fn new() -> Machine {
+        Machine {
+            stack: vec![],
+            env: Rc::new(RefCell::new(Frame {
+                parent: None,
+                slots: vec![],
+            })),
+            frames: vec![],
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, val: Value) {
+        self.stack.push(val);
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    #[inline]
+    fn pop_bool(&mut self) -> Result<bool> {
+        match self.pop() {
+            Value::Bool(b) => Ok(b),
+            val => Err(bad_type("Bool", &val)),
+        }
+    }
+
+    #[inline]
+    fn peek_bool(&self) -> Result<bool> {
+        match self.stack.last().expect("operand stack underflow") {
+            Value::Bool(b) => Ok(*b),
+            val => Err(bad_type("Bool", val)),
+        }
+    }
+
+    /// The frame the given number of levels up the environment.
+    #[inline]
+    fn frame(&self, depth: usize, idx: usize) -> Result<Env> {
+        let mut env = self.env.clone();
+        for _ in 0..depth {
+            let parent = env.borrow().parent.clone();
+            env = parent.ok_or_else(|| format!("Unbounded slot: {} at depth {}", idx, depth))?;
+        }
+        Ok(env)
+    }
+
+    #[inline]
+    fn slot(&self, depth: usize, idx: usize) -> Result<Value> {
+        let env = self.frame(depth, idx)?;
+        let val = env.borrow().slots.get(idx).cloned();
+        val.ok_or_else(|| format!("Unbounded slot: {} at depth {}", idx, depth))
+    }
+
+    #[inline]
+    fn set_slot(&mut self, depth: usize, idx: usize, val: Value) -> Result<()> {
+        let env = self.frame(depth, idx)?;
+        let mut env = env.borrow_mut();
+        let slot = env
+            .slots
+            .get_mut(idx)
+            .ok_or_else(|| format!("Unbounded slot: {} at depth {}", idx, depth))?;
+        *slot = val;
+        Ok(())
+    }
+
+    #[inline]
+    fn ld_slot(&mut self, depth: usize, idx: usize) -> Result<()> {
+        let val = self.slot(depth, idx)?;
+        self.push(val);
+        Ok(())
+    }
+
+    #[inline]
+    fn assign_slot(&mut self, depth: usize, idx: usize) -> Result<()> {
+        let val = self.pop();
+        self.set_slot(depth, idx, val)
+    }
+
+    fn extend(&mut self, slots: Vec<Value>, parent: Env) {
+        self.env = Rc::new(RefCell::new(Frame {
+            parent: Some(parent),
+            slots,
+        }));
+    }
+
+    fn enter_scope(&mut self, len: usize) {
+        self.frames.push(Saved::Block(self.env.clone()));
+        self.extend(vec![Value::Uninitialized; len], self.env.clone());
+    }
+
+    fn exit_scope(&mut self) {
+        match self.frames.pop().expect("runtime stack underflow") {
+            Saved::Block(env) | Saved::Call(env, _) => self.env = env,
+        }
+    }
+
+    fn closure(&mut self, addr: usize, arity: usize) {
+        let env = self.env.clone();
+        self.push(Value::Closure(Rc::new(Closure { addr, arity, env })));
+    }
+
+    /// Call the closure below the arguments, returning the address to continue at.
+    #[inline]
+    fn call(&mut self, arity: usize, ret: usize) -> Result<usize> {
+        let args = self.stack.split_off(self.stack.len() - arity);
+
+        match self.pop() {
+            Value::Builtin(builtin) => {
+                if let Some(val) = apply_builtin(builtin, args)? {
+                    self.push(val);
+                }
+                Ok(ret)
+            }
+            Value::Closure(closure) => {
+                if closure.arity != arity {
+                    return Err(format!(
+                        "Arity and params mismatch: arity {}, found {} params",
+                        arity, closure.arity
+                    ));
+                }
+                self.frames.push(Saved::Call(self.env.clone(), ret));
+                self.extend(args, closure.env.clone());
+                Ok(closure.addr)
+            }
+            val => Err(bad_type("Closure", &val)),
+        }
+    }
+
+    /// Return from the innermost call, returning the address to continue at.
+    #[inline]
+    fn reset(&mut self) -> usize {
+        loop {
+            match self.frames.pop().expect("runtime stack underflow") {
+                Saved::Block(_) => (),
+                Saved::Call(env, ret) => {
+                    self.env = env;
+                    return ret;
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn binop(&mut self, op: BinOp) -> Result<()> {
+        let rhs = self.pop();
+        let lhs = self.pop();
+        let val = binop(op, lhs, rhs)?;
+        self.push(val);
+        Ok(())
+    }
+
+    #[inline]
+    fn unop(&mut self, op: UnOp) -> Result<()> {
+        let val = self.pop();
+        let val = unop(op, val)?;
+        self.push(val);
+        Ok(())
+    }
+
+    fn new_array(&mut self, len: usize) {
+        let elems = self.stack.split_off(self.stack.len() - len);
+        self.push(Value::Array(Rc::new(elems)));
+    }
+
+    fn append(&mut self) -> Result<()> {
+        let val = self.pop();
+        match self.pop() {
+            Value::Array(mut elems) => {
+                Rc::make_mut(&mut elems).push(val);
+                self.push(Value::Array(elems));
+                Ok(())
+            }
+            arr => Err(bad_type("Array", &arr)),
+        }
+    }
+
+    fn ld_elem(&mut self) -> Result<()> {
+        let idx = self.pop();
+        let val = self.pop();
+        let idx = to_int(&idx)?;
+
+        let elem = usize::try_from(idx).ok().and_then(|i| match &val {
+            Value::Array(elems) => elems.get(i).cloned(),
+            Value::String(s) => s.chars().nth(i).map(|c| Value::str(&c.to_string())),
+            _ => None,
+        });
+
+        match elem {
+            Some(elem) => {
+                self.push(elem);
+                Ok(())
+            }
+            None => Err(format!(
+                "Index {} out of bounds for {} of length {}",
+                idx,
+                val.type_name(),
+                len(&val)?
+            )),
+        }
+    }
+
+    fn iter(&mut self) -> Result<()> {
+        let iter = match self.pop() {
+            Value::Array(elems) => Iter::Array { elems, idx: 0 },
+            Value::String(s) => Iter::Chars {
+                chars: s.chars().collect(),
+                idx: 0,
+            },
+            val => return Err(bad_type("Array or String", &val)),
+        };
+        self.push(Value::Iter(Rc::new(RefCell::new(iter))));
+        Ok(())
+    }
+
+    fn iter_range(&mut self) -> Result<()> {
+        let end = self.pop();
+        let start = self.pop();
+        let iter = Iter::Range {
+            next: to_int(&start)?,
+            end: to_int(&end)?,
+        };
+        self.push(Value::Iter(Rc::new(RefCell::new(iter))));
+        Ok(())
+    }
+
+    /// Push the next value of the iterator on the stack, returning false if it has none left.
+    #[inline]
+    fn next(&mut self) -> Result<bool> {
+        let iter = match self.pop() {
+            Value::Iter(iter) => iter,
+            val => return Err(bad_type("Iter", &val)),
+        };
+
+        let val = match &mut *iter.borrow_mut() {
+            Iter::Range { next, end } => (*next < *end).then(|| {
+                *next += 1;
+                Value::Int(*next - 1)
+            }),
+            Iter::Array { elems, idx } => elems.get(*idx).cloned().inspect(|_| *idx += 1),
+            Iter::Chars { chars, idx } => chars.get(*idx).map(|c| {
+                *idx += 1;
+                Value::str(&c.to_string())
+            }),
+        };
+
+        match val {
+            Some(val) => {
+                self.push(val);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+fn main() {
+    let mut m = Machine::new();
+
+    if let Err(err) = run(&mut m) {
+        let _ = std::io::stdout().flush();
+        eprintln!("error[runtime]: {}", err);
+        std::process::exit(1);
+    }
+
+    if let Some(val) = m.stack.last() {
+        println!("{}", val);
+    }
+}
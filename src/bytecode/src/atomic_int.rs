@@ -0,0 +1,39 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use crate::W;
+
+/// An int shared by threads, read and updated in a single step by the atomic builtins.
+pub type AtomicInt = W<Arc<Mutex<i64>>>;
+
+impl AtomicInt {
+    pub fn new(value: i64) -> Self {
+        Self(Arc::new(Mutex::new(value)))
+    }
+}
+
+impl Default for AtomicInt {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl PartialEq for AtomicInt {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for AtomicInt {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Debug for AtomicInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AtomicInt({})", self.lock().unwrap())
+    }
+}
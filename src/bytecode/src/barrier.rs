@@ -0,0 +1,44 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use crate::W;
+
+/// The state of a barrier, the number of threads that must arrive before they are all released,
+/// and the number of threads that have arrived so far.
+#[derive(Debug, Default)]
+pub struct BarrierState {
+    pub parties: u64,
+    pub arrived: u64,
+}
+
+pub type Barrier = W<Arc<Mutex<BarrierState>>>;
+
+impl Barrier {
+    pub fn new(parties: u64) -> Self {
+        Self(Arc::new(Mutex::new(BarrierState {
+            parties,
+            arrived: 0,
+        })))
+    }
+}
+
+impl PartialEq for Barrier {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for Barrier {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Debug for Barrier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock().unwrap();
+        write!(f, "Barrier({}/{})", state.arrived, state.parties)
+    }
+}
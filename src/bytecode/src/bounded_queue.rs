@@ -0,0 +1,40 @@
+use std::{cell::RefCell, collections::VecDeque, fmt::Debug, rc::Rc};
+
+use crate::Value;
+
+/// The state of a bounded queue: the values pushed and not yet popped, at most cap of them.
+#[derive(Debug, Default)]
+pub struct BoundedQueueState {
+    pub cap: usize,
+    pub buf: VecDeque<Value>,
+}
+
+/// A queue of values shared by producer and consumer threads, where pushing blocks while it is full
+/// and popping blocks while it is empty. Unlike a channel it is never closed, so neither can fail.
+/// Copies of a queue share its state, which is behind a RefCell for the same reason as a channel's.
+#[derive(Clone)]
+pub struct BoundedQueue(pub Rc<RefCell<BoundedQueueState>>);
+
+impl BoundedQueue {
+    /// An empty queue holding at most cap values.
+    pub fn new(cap: usize) -> Self {
+        let state = BoundedQueueState {
+            cap,
+            buf: VecDeque::with_capacity(cap),
+        };
+        BoundedQueue(Rc::new(RefCell::new(state)))
+    }
+}
+
+impl PartialEq for BoundedQueue {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Debug for BoundedQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.0.borrow();
+        write!(f, "BoundedQueue({}/{})", state.buf.len(), state.cap)
+    }
+}
@@ -0,0 +1,47 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{type_of, ByteCodeError, Closure, FnType, Value, W};
+
+pub const LEN_SYM: &str = "len";
+
+pub fn len() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: LEN_SYM.into(),
+        prms: vec!["a".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// The number of elements of the array, or the number of chars of the string.
+/// Strings are indexed and sliced by chars, so this is the length those are bounded by,
+/// while string_len is the number of bytes.
+pub fn len_impl(a: &Value) -> Result<usize> {
+    match a {
+        Value::Array(elems) => Ok(elems.len()),
+        Value::String(s) => Ok(s.chars().count()),
+        _ => Err(ByteCodeError::BadType {
+            expected: "Array or String".to_string(),
+            found: type_of(a).to_string(),
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_len() {
+        let arr = Value::from(vec![Value::Int(1), Value::Int(2)]);
+        assert_eq!(len_impl(&arr).unwrap(), 2);
+        assert_eq!(len_impl(&Value::from(vec![])).unwrap(), 0);
+        assert_eq!(len_impl(&Value::from("héllo")).unwrap(), 5);
+        assert!(len_impl(&Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,3 @@
+pub use len::*;
+
+mod len;
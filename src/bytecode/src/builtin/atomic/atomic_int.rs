@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{AtomicInt, Closure, FnType, Value, W};
+
+pub const ATOMIC_INT_SYM: &str = "atomic_int";
+
+pub fn atomic_int() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: ATOMIC_INT_SYM.into(),
+        prms: vec!["v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn atomic_int_impl(v: &Value) -> Result<Value> {
+    let v: i64 = v.try_into()?;
+    Ok(AtomicInt::new(v).into())
+}
@@ -0,0 +1,34 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{AtomicInt, Closure, FnType, Value, W};
+
+pub const COMPARE_AND_SWAP_SYM: &str = "compare_and_swap";
+
+pub fn compare_and_swap() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: COMPARE_AND_SWAP_SYM.into(),
+        prms: vec!["a".into(), "current".into(), "new".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Stores the new value if the atomic holds the current value, and gives back the value it held,
+/// so the swap happened if that is the current value.
+pub fn compare_and_swap_impl(a: &Value, current: &Value, new: &Value) -> Result<Value> {
+    let a: AtomicInt = a.clone().try_into()?;
+    let current: i64 = current.try_into()?;
+    let new: i64 = new.try_into()?;
+
+    let mut guard = a.lock().unwrap();
+    let prev = *guard;
+    if prev == current {
+        *guard = new;
+    }
+
+    Ok(Value::Int(prev))
+}
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const FETCH_ADD_SYM: &str = "fetch_add";
+
+/// The implementation lives in the VM since the addition overflows as the runtime is set to.
+pub fn fetch_add() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: FETCH_ADD_SYM.into(),
+        prms: vec!["a".into(), "v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{AtomicInt, Closure, FnType, Value, W};
+
+pub const LOAD_SYM: &str = "load";
+
+pub fn load() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: LOAD_SYM.into(),
+        prms: vec!["a".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn load_impl(a: &Value) -> Result<Value> {
+    let a: AtomicInt = a.clone().try_into()?;
+    let v = *a.lock().unwrap();
+    Ok(Value::Int(v))
+}
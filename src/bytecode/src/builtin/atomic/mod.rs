@@ -0,0 +1,11 @@
+pub use atomic_int::*;
+pub use compare_and_swap::*;
+pub use fetch_add::*;
+pub use load::*;
+pub use store::*;
+
+mod atomic_int;
+mod compare_and_swap;
+mod fetch_add;
+mod load;
+mod store;
@@ -0,0 +1,25 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{AtomicInt, Closure, FnType, Value, W};
+
+pub const STORE_SYM: &str = "store";
+
+pub fn store() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: STORE_SYM.into(),
+        prms: vec!["a".into(), "v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn store_impl(a: &Value, v: &Value) -> Result<()> {
+    let a: AtomicInt = a.clone().try_into()?;
+    let v: i64 = v.try_into()?;
+    *a.lock().unwrap() = v;
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const BARRIER_CREATE_SYM: &str = "barrier_create";
+
+/// The implementation lives in the VM since it needs to check the number of parties is positive.
+pub fn barrier_create() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: BARRIER_CREATE_SYM.into(),
+        prms: vec!["n".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
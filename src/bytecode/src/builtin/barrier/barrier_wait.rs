@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const BARRIER_WAIT_SYM: &str = "barrier_wait";
+
+/// The implementation lives in the VM since it needs to block the current thread until all parties arrive.
+pub fn barrier_wait() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: BARRIER_WAIT_SYM.into(),
+        prms: vec!["b".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,5 @@
+pub use barrier_create::*;
+pub use barrier_wait::*;
+
+mod barrier_create;
+mod barrier_wait;
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const CLOSE_SYM: &str = "close";
+
+/// The implementation lives in the VM since it needs to wake up the threads waiting on the channel.
+pub fn close() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: CLOSE_SYM.into(),
+        prms: vec!["ch".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
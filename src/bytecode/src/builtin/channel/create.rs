@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const CHANNEL_SYM: &str = "channel";
+
+/// The implementation lives in the VM since it needs to check the capacity is positive.
+pub fn channel() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: CHANNEL_SYM.into(),
+        prms: vec!["cap".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
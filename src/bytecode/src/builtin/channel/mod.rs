@@ -0,0 +1,9 @@
+pub use close::*;
+pub use create::*;
+pub use recv::*;
+pub use send::*;
+
+mod close;
+mod create;
+mod recv;
+mod send;
@@ -0,0 +1,18 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const RECV_SYM: &str = "recv";
+
+/// The implementation lives in the VM since it needs to block the thread while the channel is empty,
+/// and to wake up a thread waiting to send.
+pub fn recv() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: RECV_SYM.into(),
+        prms: vec!["ch".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,18 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const SEND_SYM: &str = "send";
+
+/// The implementation lives in the VM since it needs to block the thread while the channel is full,
+/// and to hand the value to a thread waiting to receive.
+pub fn send() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: SEND_SYM.into(),
+        prms: vec!["ch".into(), "val".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,20 @@
+use std::rc::Weak;
+
+use crate::{Closure, CondVar, FnType, Value, W};
+
+pub const CV_CREATE_SYM: &str = "cv_create";
+
+pub fn cv_create() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: CV_CREATE_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn cv_create_impl() -> Value {
+    CondVar::default().into()
+}
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const CV_NOTIFY_ONE_SYM: &str = "cv_notify_one";
+
+/// The implementation lives in the VM since it needs to wake up a thread blocked on the condition variable.
+pub fn cv_notify_one() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: CV_NOTIFY_ONE_SYM.into(),
+        prms: vec!["cv".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
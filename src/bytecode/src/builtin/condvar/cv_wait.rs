@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const CV_WAIT_SYM: &str = "cv_wait";
+
+/// The implementation lives in the VM since it needs to block the current thread on the condition variable.
+pub fn cv_wait() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: CV_WAIT_SYM.into(),
+        prms: vec!["cv".into(), "mutex".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
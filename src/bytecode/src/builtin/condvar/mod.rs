@@ -0,0 +1,9 @@
+pub use cv_create::*;
+pub use cv_notify_all::*;
+pub use cv_notify_one::*;
+pub use cv_wait::*;
+
+mod cv_create;
+mod cv_notify_all;
+mod cv_notify_one;
+mod cv_wait;
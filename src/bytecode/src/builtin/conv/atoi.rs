@@ -2,18 +2,19 @@ use std::rc::Weak;
 
 use anyhow::Result;
 
-use crate::{FnType, Value, W};
+use crate::{Closure, FnType, Value, W};
 
 pub const ATOI_SYM: &str = "atoi";
 
 pub fn atoi() -> Value {
-    Value::Closure {
+    Closure {
         fn_type: FnType::Builtin,
         sym: ATOI_SYM.into(),
         prms: vec!["s".into()],
         addr: 0,
         env: W(Weak::new()),
     }
+    .into()
 }
 
 pub fn atoi_impl(s: &Value) -> Result<Value> {
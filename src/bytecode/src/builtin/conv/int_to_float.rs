@@ -2,20 +2,21 @@ use std::rc::Weak;
 
 use anyhow::Result;
 
-use crate::{FnType, Value, W};
+use crate::{Closure, FnType, Value, W};
 pub const INT_TO_FLOAT_SYM: &str = "int_to_float";
 
 pub fn int_to_float() -> Value {
-    Value::Closure {
+    Closure {
         fn_type: FnType::Builtin,
         sym: INT_TO_FLOAT_SYM.into(),
         prms: vec!["x".into()],
         addr: 0,
         env: W(Weak::new()),
     }
+    .into()
 }
 
 pub fn int_to_float_impl(x: &Value) -> Result<Value> {
-    let x: i64 = x.clone().try_into()?;
+    let x: i64 = x.try_into()?;
     Ok(Value::Float(x as f64))
 }
@@ -2,21 +2,22 @@ use std::rc::Weak;
 
 use anyhow::Result;
 
-use crate::{FnType, Value, W};
+use crate::{Closure, FnType, Value, W};
 
 pub const ITOA_SYM: &str = "itoa";
 
 pub fn itoa() -> Value {
-    Value::Closure {
+    Closure {
         fn_type: FnType::Builtin,
         sym: ITOA_SYM.into(),
         prms: vec!["i".into()],
         addr: 0,
         env: W(Weak::new()),
     }
+    .into()
 }
 
 pub fn itoa_impl(i: &Value) -> Result<Value> {
-    let i: i64 = i.clone().try_into()?;
-    Ok(Value::String(i.to_string()))
+    let i: i64 = i.try_into()?;
+    Ok(i.to_string().into())
 }
@@ -0,0 +1,38 @@
+use std::{path::Path, rc::Weak};
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const FILE_EXISTS_SYM: &str = "file_exists";
+
+pub fn file_exists() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: FILE_EXISTS_SYM.into(),
+        prms: vec!["path".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Whether there is a file or directory at the path. A path that can't be looked at, e.g. for lack of permission,
+/// does not exist as far as the script can tell.
+pub fn file_exists_impl(path: &Value) -> Result<bool> {
+    let path: &str = path.try_into()?;
+    Ok(Path::new(path).exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_exists() {
+        let dir = std::env::temp_dir();
+        assert!(file_exists_impl(&Value::from(dir.to_str().unwrap())).unwrap());
+        assert!(!file_exists_impl(&Value::from("/rustscript/missing.txt")).unwrap());
+        assert!(file_exists_impl(&Value::Unit).is_err());
+    }
+}
@@ -0,0 +1,18 @@
+pub use file_exists::*;
+pub use read_file::*;
+pub use write_file::*;
+
+mod file_exists;
+mod read_file;
+mod write_file;
+
+use crate::{Value, Variant};
+
+/// The result of a file operation as a script sees it: `Ok` of the value, or `Err` of a message naming the file,
+/// so the script can handle the error rather than stop.
+fn io_result(path: &str, res: std::io::Result<Value>) -> Value {
+    match res {
+        Ok(val) => Variant::Ok(val).into(),
+        Err(err) => Variant::Err(format!("{}: {}", path, err).into()).into(),
+    }
+}
@@ -0,0 +1,76 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, W};
+
+use super::io_result;
+
+pub const READ_FILE_SYM: &str = "read_file";
+pub const READ_LINES_SYM: &str = "read_lines";
+
+fn read_fn(sym: &str) -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: sym.into(),
+        prms: vec!["path".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn read_file() -> Value {
+    read_fn(READ_FILE_SYM)
+}
+
+pub fn read_lines() -> Value {
+    read_fn(READ_LINES_SYM)
+}
+
+/// `Ok` of the contents of the file, or `Err` of why it could not be read.
+pub fn read_file_impl(path: &Value) -> Result<Value> {
+    let path: &str = path.try_into()?;
+    let res = std::fs::read_to_string(path).map(Value::from);
+    Ok(io_result(path, res))
+}
+
+/// `Ok` of the lines of the file as an array of strings, without their line endings,
+/// or `Err` of why it could not be read.
+pub fn read_lines_impl(path: &Value) -> Result<Value> {
+    let path: &str = path.try_into()?;
+    let res = std::fs::read_to_string(path).map(|contents| {
+        let lines: Vec<Value> = contents.lines().map(Value::from).collect();
+        Value::from(lines)
+    });
+    Ok(io_result(path, res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{unwrap_err_impl, unwrap_impl};
+
+    #[test]
+    fn test_read_file() {
+        let path = std::env::temp_dir().join("rustscript_read_file.txt");
+        std::fs::write(&path, "a\nb\r\n\nc").unwrap();
+        let path = Value::from(path.to_str().unwrap());
+
+        let contents = unwrap_impl(&read_file_impl(&path).unwrap()).unwrap();
+        assert_eq!(contents, Value::from("a\nb\r\n\nc"));
+
+        let lines = unwrap_impl(&read_lines_impl(&path).unwrap()).unwrap();
+        assert_eq!(lines.to_string(), "[a, b, , c]");
+
+        let missing = Value::from("/rustscript/missing.txt");
+        let err = unwrap_err_impl(&read_file_impl(&missing).unwrap()).unwrap();
+        assert!(err.to_string().starts_with("/rustscript/missing.txt: "));
+        assert!(read_lines_impl(&missing)
+            .unwrap()
+            .to_string()
+            .starts_with("Err("));
+
+        assert!(read_file_impl(&Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use std::{io::Write, rc::Weak};
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, W};
+
+use super::io_result;
+
+pub const WRITE_FILE_SYM: &str = "write_file";
+pub const APPEND_FILE_SYM: &str = "append_file";
+
+fn write_fn(sym: &str) -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: sym.into(),
+        prms: vec!["path".into(), "contents".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn write_file() -> Value {
+    write_fn(WRITE_FILE_SYM)
+}
+
+pub fn append_file() -> Value {
+    write_fn(APPEND_FILE_SYM)
+}
+
+/// Write the contents to the file, creating it or replacing what it held.
+/// `Ok` of unit, or `Err` of why it could not be written.
+pub fn write_file_impl(path: &Value, contents: &Value) -> Result<Value> {
+    let path: &str = path.try_into()?;
+    let contents: &str = contents.try_into()?;
+    let res = std::fs::write(path, contents).map(|_| Value::Unit);
+    Ok(io_result(path, res))
+}
+
+/// Write the contents to the end of the file, creating it if it does not exist.
+/// `Ok` of unit, or `Err` of why it could not be written.
+pub fn append_file_impl(path: &Value, contents: &Value) -> Result<Value> {
+    let path: &str = path.try_into()?;
+    let contents: &str = contents.try_into()?;
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map(|_| Value::Unit);
+    Ok(io_result(path, res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::is_variant_impl;
+
+    #[test]
+    fn test_write_file() {
+        let path = std::env::temp_dir().join("rustscript_write_file.txt");
+        let _ = std::fs::remove_file(&path);
+        let file = Value::from(path.to_str().unwrap());
+
+        let ok = append_file_impl(&file, &Value::from("a\n")).unwrap();
+        assert_eq!(ok.to_string(), "Ok(())");
+        append_file_impl(&file, &Value::from("b\n")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\n");
+
+        write_file_impl(&file, &Value::from("c")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "c");
+        std::fs::remove_file(&path).unwrap();
+
+        let dir = Value::from(std::env::temp_dir().to_str().unwrap());
+        let err = write_file_impl(&dir, &Value::from("c")).unwrap();
+        assert_eq!(is_variant_impl("is_err", &err), Some(true));
+
+        assert!(write_file_impl(&file, &Value::Int(1)).is_err());
+    }
+}
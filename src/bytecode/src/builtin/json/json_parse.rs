@@ -0,0 +1,68 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, Variant, W};
+
+pub const JSON_PARSE_SYM: &str = "json_parse";
+
+pub fn json_parse() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: JSON_PARSE_SYM.into(),
+        prms: vec!["s".into(), "example".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// `Ok` of the value the JSON text stands for, of the same shape as the example, or `Err` of why it could not be parsed
+/// or is of another shape, see [`Value::from_json_as`]. Objects are parsed into instances of the struct of the example.
+pub fn json_parse_impl(s: &Value, example: &Value) -> Result<Value> {
+    let s: &str = s.try_into()?;
+    let res = serde_json::from_str(s)
+        .map_err(|err| err.to_string())
+        .and_then(|json| Value::from_json_as(json, example).map_err(|err| err.to_string()));
+
+    let variant = match res {
+        Ok(val) => Variant::Ok(val),
+        Err(err) => Variant::Err(err.into()),
+    };
+    Ok(variant.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{unwrap_err_impl, unwrap_impl};
+
+    #[test]
+    fn test_json_parse() {
+        let example = Value::from(vec![Value::from(vec![Value::Float(0.0)])]);
+        let val =
+            unwrap_impl(&json_parse_impl(&Value::from(" [[1, 2.5], []] "), &example).unwrap());
+        assert_eq!(
+            val.unwrap(),
+            Value::from(vec![
+                Value::from(vec![Value::Float(1.0), Value::Float(2.5)]),
+                Value::from(Vec::<Value>::new()),
+            ])
+        );
+
+        let err =
+            unwrap_err_impl(&json_parse_impl(&Value::from("[1,"), &example).unwrap()).unwrap();
+        assert_eq!(
+            err,
+            Value::from("EOF while parsing a value at line 1 column 3")
+        );
+
+        // The text must be of the shape of the example
+        let example = Value::from(vec![Value::Int(0)]);
+        let err = unwrap_err_impl(&json_parse_impl(&Value::from("[1, \"a\"]"), &example).unwrap())
+            .unwrap();
+        assert_eq!(err, Value::from("Bad type, expected integer, found string"));
+
+        assert!(json_parse_impl(&Value::Int(1), &example).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, Variant, W};
+
+pub const JSON_STRINGIFY_SYM: &str = "json_stringify";
+
+pub fn json_stringify() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: JSON_STRINGIFY_SYM.into(),
+        prms: vec!["value".into(), "pretty".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// `Ok` of the value as JSON text, indented over several lines if pretty, or `Err` of why it has no JSON form,
+/// e.g. it is a closure or a float that is not finite.
+pub fn json_stringify_impl(val: &Value, pretty: &Value) -> Result<Value> {
+    let pretty: bool = pretty.try_into()?;
+    let res = val.to_json().map(|json| {
+        if pretty {
+            serde_json::to_string_pretty(&json)
+        } else {
+            serde_json::to_string(&json)
+        }
+        .expect("JSON values can always be written")
+    });
+
+    let variant = match res {
+        Ok(s) => Variant::Ok(s.into()),
+        Err(err) => Variant::Err(err.to_string().into()),
+    };
+    Ok(variant.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{unwrap_err_impl, unwrap_impl};
+
+    #[test]
+    fn test_json_stringify() {
+        let val = Value::from(vec![
+            Value::Int(1),
+            Value::from(vec![Value::Unit, Value::from("a")]),
+        ]);
+
+        let s = unwrap_impl(&json_stringify_impl(&val, &Value::Bool(false)).unwrap()).unwrap();
+        assert_eq!(s, Value::from("[1,[null,\"a\"]]"));
+
+        let s = unwrap_impl(&json_stringify_impl(&val, &Value::Bool(true)).unwrap()).unwrap();
+        assert_eq!(s, Value::from("[\n  1,\n  [\n    null,\n    \"a\"\n  ]\n]"));
+
+        let err = unwrap_err_impl(
+            &json_stringify_impl(&Value::Float(f64::NAN), &Value::Bool(false)).unwrap(),
+        );
+        assert_eq!(
+            err.unwrap(),
+            Value::from("Bad type, expected finite Float, found NaN")
+        );
+
+        assert!(json_stringify_impl(&val, &Value::Int(1)).is_err());
+    }
+}
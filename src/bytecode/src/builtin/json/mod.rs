@@ -0,0 +1,5 @@
+pub use json_parse::*;
+pub use json_stringify::*;
+
+mod json_parse;
+mod json_stringify;
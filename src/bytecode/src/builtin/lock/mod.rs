@@ -0,0 +1,5 @@
+pub use mutex::*;
+pub use rwlock::*;
+
+mod mutex;
+mod rwlock;
@@ -0,0 +1,22 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Semaphore, Value, W};
+
+pub const MUTEX_SYM: &str = "mutex";
+
+pub fn mutex() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: MUTEX_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// A mutex is a semaphore that one thread can hold at a time, so it can be waited on and posted,
+/// and passed to cv_wait, like any other semaphore.
+pub fn mutex_impl() -> Value {
+    Semaphore::new(1).into()
+}
@@ -0,0 +1,20 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, RwLock, Value, W};
+
+pub const RWLOCK_SYM: &str = "rwlock";
+
+pub fn rwlock() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: RWLOCK_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn rwlock_impl() -> Value {
+    RwLock::new().into()
+}
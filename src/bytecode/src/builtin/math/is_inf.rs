@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const IS_INF_SYM: &str = "is_inf";
+
+pub fn is_inf() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: IS_INF_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Whether the float is positive or negative infinity, e.g. the result of `1.0 / 0.0`.
+pub fn is_inf_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.try_into()?;
+    Ok(Value::Bool(x.is_infinite()))
+}
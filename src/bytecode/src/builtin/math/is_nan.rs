@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const IS_NAN_SYM: &str = "is_nan";
+
+pub fn is_nan() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: IS_NAN_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Whether the float is NaN, e.g. the result of `0.0 / 0.0`.
+pub fn is_nan_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.try_into()?;
+    Ok(Value::Bool(x.is_nan()))
+}
@@ -1,19 +1,25 @@
 pub use abs::*;
 pub use cos::*;
+pub use is_inf::*;
+pub use is_nan::*;
 pub use log::*;
 pub use max::*;
 pub use min::*;
 pub use pow::*;
+pub use random_int::*;
 pub use sin::*;
 pub use sqrt::*;
 pub use tan::*;
 
 mod abs;
 mod cos;
+mod is_inf;
+mod is_nan;
 mod log;
 mod max;
 mod min;
 mod pow;
+mod random_int;
 mod sin;
 mod sqrt;
 mod tan;
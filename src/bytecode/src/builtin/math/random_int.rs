@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const RANDOM_INT_SYM: &str = "random_int";
+
+/// The implementation lives in the VM since the random number generator is kept by the runtime.
+pub fn random_int() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: RANDOM_INT_SYM.into(),
+        prms: vec!["lo".into(), "hi".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
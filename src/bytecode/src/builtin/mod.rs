@@ -1,17 +1,47 @@
+pub use array::*;
+pub use atomic::*;
+pub use barrier::*;
+pub use channel::*;
+pub use condvar::*;
 pub use constants::*;
 pub use conv::*;
+pub use fs::*;
+pub use json::*;
+pub use lock::*;
 pub use math::*;
+pub use process::*;
+pub use queue::*;
+pub use reflect::*;
 pub use semaphore::*;
 pub use stdin::*;
 pub use stdout::*;
 pub use string::*;
+pub use testing::*;
+pub use thread::*;
+pub use variant::*;
+pub use wait_group::*;
 
+mod array;
+mod atomic;
+mod barrier;
+mod channel;
+mod condvar;
 mod constants;
 mod conv;
+mod fs;
+mod json;
+mod lock;
 mod math;
+mod process;
+mod queue;
+mod reflect;
 mod semaphore;
 mod stdin;
 mod stdout;
 mod string;
+mod testing;
+mod thread;
+mod variant;
+mod wait_group;
 
 pub const BUILTIN_SYM: &str = "BUILTIN";
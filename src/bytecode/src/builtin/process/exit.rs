@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const EXIT_SYM: &str = "exit";
+
+/// The implementation lives in the VM since it needs to stop the runtime.
+pub fn exit() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: EXIT_SYM.into(),
+        prms: vec!["code".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,5 @@
+pub use exit::*;
+pub use panic::*;
+
+mod exit;
+mod panic;
@@ -0,0 +1,37 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, Closure, FnType, Value, W};
+
+pub const PANIC_SYM: &str = "panic";
+
+pub fn panic() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: PANIC_SYM.into(),
+        prms: vec!["msg".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Fail with the given message, which is caught by the nearest enclosing catch block.
+pub fn panic_impl(msg: Value) -> Result<()> {
+    let msg: String = msg.try_into()?;
+    Err(ByteCodeError::Panic(msg).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic() {
+        let err = panic_impl(Value::from("boom")).unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+
+        assert!(panic_impl(Value::Int(1)).is_err());
+    }
+}
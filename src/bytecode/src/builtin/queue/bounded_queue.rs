@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const BOUNDED_QUEUE_SYM: &str = "bounded_queue";
+
+/// The implementation lives in the VM since it needs to check the capacity is positive.
+pub fn bounded_queue() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: BOUNDED_QUEUE_SYM.into(),
+        prms: vec!["cap".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
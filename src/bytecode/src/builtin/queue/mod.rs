@@ -0,0 +1,7 @@
+pub use bounded_queue::*;
+pub use pop::*;
+pub use push::*;
+
+mod bounded_queue;
+mod pop;
+mod push;
@@ -0,0 +1,18 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const POP_SYM: &str = "pop";
+
+/// The implementation lives in the VM since it needs to block the thread while the queue is empty,
+/// and to wake up a thread waiting to push.
+pub fn pop() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: POP_SYM.into(),
+        prms: vec!["q".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
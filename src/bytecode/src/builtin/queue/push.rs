@@ -0,0 +1,18 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const PUSH_SYM: &str = "push";
+
+/// The implementation lives in the VM since it needs to block the thread while the queue is full,
+/// and to hand the value to a thread waiting to pop.
+pub fn push() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: PUSH_SYM.into(),
+        prms: vec!["q".into(), "val".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,68 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const IS_INT_SYM: &str = "is_int";
+pub const IS_FLOAT_SYM: &str = "is_float";
+pub const IS_BOOL_SYM: &str = "is_bool";
+pub const IS_STRING_SYM: &str = "is_string";
+pub const IS_UNIT_SYM: &str = "is_unit";
+
+fn is_type(sym: &str) -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: sym.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn is_int() -> Value {
+    is_type(IS_INT_SYM)
+}
+
+pub fn is_float() -> Value {
+    is_type(IS_FLOAT_SYM)
+}
+
+pub fn is_bool() -> Value {
+    is_type(IS_BOOL_SYM)
+}
+
+pub fn is_string() -> Value {
+    is_type(IS_STRING_SYM)
+}
+
+pub fn is_unit() -> Value {
+    is_type(IS_UNIT_SYM)
+}
+
+/// Whether the value has the type the `is_` builtin named sym tests for, or None if sym is not one of them.
+pub fn is_type_impl(sym: &str, x: &Value) -> Option<bool> {
+    let is = match sym {
+        IS_INT_SYM => matches!(x, Value::Int(_)),
+        IS_FLOAT_SYM => matches!(x, Value::Float(_)),
+        IS_BOOL_SYM => matches!(x, Value::Bool(_)),
+        IS_STRING_SYM => matches!(x, Value::String(_)),
+        IS_UNIT_SYM => matches!(x, Value::Unit),
+        _ => return None,
+    };
+
+    Some(is)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_type() {
+        assert_eq!(is_type_impl(IS_INT_SYM, &Value::Int(1)), Some(true));
+        assert_eq!(is_type_impl(IS_FLOAT_SYM, &Value::Int(1)), Some(false));
+        assert_eq!(is_type_impl(IS_STRING_SYM, &Value::from("s")), Some(true));
+        assert_eq!(is_type_impl(IS_UNIT_SYM, &Value::Unit), Some(true));
+        assert_eq!(is_type_impl("print", &Value::Unit), None);
+    }
+}
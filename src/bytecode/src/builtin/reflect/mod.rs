@@ -0,0 +1,5 @@
+pub use is_type::*;
+pub use type_of::*;
+
+mod is_type;
+mod type_of;
@@ -0,0 +1,89 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, Variant, W};
+
+pub const TYPE_OF_SYM: &str = "typeof";
+
+pub fn type_of() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: TYPE_OF_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// The name of the type of the value as it is written in the language, e.g. `int` or `str`,
+/// so that scripts can compare it with the types they annotate.
+/// Thread handles are ints at runtime, and functions are `fn` whatever their signature.
+/// Structs and values of enums are the name of their struct or enum, and arrays are `array` whatever their elements.
+pub fn type_name(x: &Value) -> &'static str {
+    match x {
+        Value::Unitialized => "uninit",
+        Value::Unit => "()",
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::Bool(_) => "bool",
+        Value::String(_) => "str",
+        Value::Semaphore(_) => "sem",
+        Value::CondVar(_) => "condvar",
+        Value::Barrier(_) => "barrier",
+        Value::WaitGroup(_) => "waitgroup",
+        Value::Channel(_) => "channel",
+        Value::BoundedQueue(_) => "bounded_queue",
+        Value::RwLock(_) => "rwlock",
+        Value::AtomicInt(_) => "atomic_int",
+        Value::Closure(_) => "fn",
+        Value::Variant(variant) => match **variant {
+            Variant::Some(_) | Variant::None => "Option",
+            Variant::Ok(_) | Variant::Err(_) => "Result",
+        },
+        Value::StructType(_) => "struct",
+        Value::Struct(instance) => instance.ty.name.as_str(),
+        Value::Enum(val) => val.ty.as_str(),
+        Value::Array(_) => "array",
+        Value::Iter(_) => "iter",
+        Value::Generator(_) => "generator",
+    }
+}
+
+pub fn type_of_impl(x: &Value) -> Value {
+    type_name(x).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{Enum, Struct, StructType};
+
+    use super::*;
+
+    #[test]
+    fn test_type_of() {
+        assert_eq!(type_of_impl(&Value::Int(1)), Value::from("int"));
+        assert_eq!(type_of_impl(&Value::from("s")), Value::from("str"));
+        assert_eq!(type_of_impl(&Value::Unit), Value::from("()"));
+        assert_eq!(type_of_impl(&type_of()), Value::from("fn"));
+
+        let ty = Rc::new(StructType::new("Point".into(), vec![]));
+        assert_eq!(
+            type_of_impl(&Value::StructType(ty.clone())),
+            Value::from("struct")
+        );
+        let p = Struct { ty, fields: vec![] };
+        assert_eq!(type_of_impl(&p.into()), Value::from("Point"));
+
+        let empty = Enum {
+            ty: "Shape".into(),
+            variant: "Empty".into(),
+            payload: None,
+        };
+        assert_eq!(type_of_impl(&empty.into()), Value::from("Shape"));
+
+        let arr = Value::from(vec![Value::Int(1)]);
+        assert_eq!(type_of_impl(&arr), Value::from("array"));
+    }
+}
@@ -1,5 +1,7 @@
 pub use sem_create::*;
 pub use sem_set::*;
+pub use wait_timeout::*;
 
 mod sem_create;
 mod sem_set;
+mod wait_timeout;
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const WAIT_TIMEOUT_SYM: &str = "wait_timeout";
+
+/// The implementation lives in the VM since it needs to block the current thread with a deadline.
+pub fn wait_timeout() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: WAIT_TIMEOUT_SYM.into(),
+        prms: vec!["sem".into(), "ms".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
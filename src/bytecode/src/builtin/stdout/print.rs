@@ -1,17 +1,18 @@
 use std::rc::Weak;
 
-use crate::{FnType, Value, W};
+use crate::{Closure, FnType, Value, W};
 
 pub const PRINT_SYM: &str = "print";
 
 pub fn print() -> Value {
-    Value::Closure {
+    Closure {
         fn_type: FnType::Builtin,
         sym: PRINT_SYM.into(),
         prms: vec!["s".into()],
         addr: 0,
         env: W(Weak::new()),
     }
+    .into()
 }
 
 pub fn print_impl(v: &Value) {
@@ -23,6 +24,20 @@ pub fn print_impl(v: &Value) {
         Value::Int(i) => print!("{}", i),
         Value::Float(f) => print!("{}", f),
         Value::Semaphore(_) => print!("semaphore"),
+        Value::CondVar(_) => print!("condvar"),
+        Value::Barrier(_) => print!("barrier"),
+        Value::WaitGroup(_) => print!("waitgroup"),
+        Value::Channel(_) => print!("channel"),
+        Value::BoundedQueue(_) => print!("bounded_queue"),
+        Value::RwLock(_) => print!("rwlock"),
+        Value::AtomicInt(_) => print!("atomic_int"),
         Value::Closure { .. } => print!("closure"),
+        Value::Variant(variant) => print!("{}", variant),
+        Value::StructType(ty) => print!("struct {}", ty.name),
+        Value::Struct(instance) => print!("{}", instance),
+        Value::Enum(val) => print!("{}", val),
+        Value::Array(_) => print!("{}", v),
+        Value::Iter(_) => print!("iter"),
+        Value::Generator(_) => print!("generator"),
     }
 }
@@ -1,17 +1,18 @@
 use std::rc::Weak;
 
-use crate::{FnType, Value, W};
+use crate::{Closure, FnType, Value, W};
 
 pub const PRINTLN_SYM: &str = "println";
 
 pub fn println() -> Value {
-    Value::Closure {
+    Closure {
         fn_type: FnType::Builtin,
         sym: PRINTLN_SYM.into(),
         prms: vec!["s".into()],
         addr: 0,
         env: W(Weak::new()),
     }
+    .into()
 }
 
 pub fn println_impl(v: &Value) {
@@ -0,0 +1,39 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const BYTES_SYM: &str = "bytes";
+
+pub fn bytes() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: BYTES_SYM.into(),
+        prms: vec!["s".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// The bytes of the string in UTF-8, as an array of ints.
+/// Strings are indexed by chars, so this is how to get at the bytes of the chars that aren't ASCII.
+pub fn bytes_impl(s: &Value) -> Result<Value> {
+    let s: &str = s.try_into()?;
+    let bytes = s.bytes().map(|b| Value::Int(b as i64)).collect::<Vec<_>>();
+    Ok(bytes.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes() {
+        let res = bytes_impl(&Value::from("aé")).unwrap();
+        assert_eq!(res, Value::from(vec![97.into(), 195.into(), 169.into()]));
+        assert_eq!(bytes_impl(&Value::from("")).unwrap(), Value::from(vec![]));
+        assert!(bytes_impl(&Value::Int(1)).is_err());
+    }
+}
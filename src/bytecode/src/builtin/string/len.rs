@@ -2,20 +2,22 @@ use std::rc::Weak;
 
 use anyhow::Result;
 
-use crate::{FnType, Value, W};
+use crate::{Closure, FnType, Value, W};
 
 pub const STRING_LEN_SYM: &str = "string_len";
 
 pub fn string_len() -> Value {
-    Value::Closure {
+    Closure {
         fn_type: FnType::Builtin,
         sym: STRING_LEN_SYM.into(),
         prms: vec!["s".into()],
         addr: 0,
         env: W(Weak::new()),
     }
+    .into()
 }
 
+/// The number of bytes of the string in UTF-8, which is its length in chars only if it is ASCII.
 pub fn string_len_impl(s: &Value) -> Result<usize> {
     let s: String = s.clone().try_into()?;
     Ok(s.len())
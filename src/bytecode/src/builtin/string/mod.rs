@@ -1,3 +1,5 @@
+pub use bytes::*;
 pub use len::*;
 
+mod bytes;
 mod len;
@@ -0,0 +1,49 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, Closure, FnType, Value, W};
+
+pub const ASSERT_SYM: &str = "assert";
+
+pub fn assert() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: ASSERT_SYM.into(),
+        prms: vec!["left".into(), "right".into(), "op".into(), "expr".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Fail with a report of the asserted expression if the condition is false.
+/// The compiler passes the source text of the condition, and the values of both sides
+/// of it when the condition is a comparison.
+pub fn assert_impl(cond: &Value, expr: &Value, operands: Option<(&Value, &Value)>) -> Result<()> {
+    let cond: bool = cond.clone().try_into()?;
+
+    if cond {
+        return Ok(());
+    }
+
+    let mut report = format!("assertion failed: {}", expr);
+
+    if let Some((left, right)) = operands {
+        report.push_str(&format!(
+            "\n  left: {}\n right: {}",
+            render_operand(left),
+            render_operand(right)
+        ));
+    }
+
+    Err(ByteCodeError::AssertionFailed(report).into())
+}
+
+/// Render an operand of a failed assertion, quoting strings so that whitespace differences are visible.
+pub(super) fn render_operand(v: &Value) -> String {
+    match v {
+        Value::String(s) => format!("{:?}", s),
+        _ => v.to_string(),
+    }
+}
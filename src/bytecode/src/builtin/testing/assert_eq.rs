@@ -0,0 +1,67 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{structural_eq, ByteCodeError, Closure, FnType, Value, W};
+
+use super::assert::render_operand;
+
+pub const ASSERT_EQ_SYM: &str = "assert_eq";
+
+pub fn assert_eq() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: ASSERT_EQ_SYM.into(),
+        prms: vec![
+            "left".into(),
+            "right".into(),
+            "left_expr".into(),
+            "right_expr".into(),
+        ],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+/// Fail with a report of both sides if they are not equal, as compared by `==`.
+/// The compiler passes the source text of each side along with their values.
+pub fn assert_eq_impl(
+    left: &Value,
+    right: &Value,
+    left_expr: &Value,
+    right_expr: &Value,
+) -> Result<()> {
+    if structural_eq(left, right) == Some(true) {
+        return Ok(());
+    }
+
+    let report = format!(
+        "assertion `left == right` failed: {} == {}\n  left: {}\n right: {}",
+        left_expr,
+        right_expr,
+        render_operand(left),
+        render_operand(right)
+    );
+
+    Err(ByteCodeError::AssertionFailed(report).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assert_eq_report() {
+        let x = Value::from("x");
+        let y = Value::from("y");
+
+        assert!(assert_eq_impl(&Value::Int(1), &Value::Int(1), &x, &y).is_ok());
+
+        let err = assert_eq_impl(&Value::Int(1), &Value::from("1"), &x, &y).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "assertion `left == right` failed: x == y\n  left: 1\n right: \"1\""
+        );
+    }
+}
@@ -0,0 +1,5 @@
+pub use assert::*;
+pub use assert_eq::*;
+
+mod assert;
+mod assert_eq;
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const IS_FINISHED_SYM: &str = "is_finished";
+
+/// The implementation lives in the VM since it needs to query the thread table of the runtime.
+pub fn is_finished() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: IS_FINISHED_SYM.into(),
+        prms: vec!["h".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
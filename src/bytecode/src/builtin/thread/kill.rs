@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const KILL_SYM: &str = "kill";
+
+/// The implementation lives in the VM since it needs to move the thread out of the queues of the runtime.
+pub fn kill() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: KILL_SYM.into(),
+        prms: vec!["h".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
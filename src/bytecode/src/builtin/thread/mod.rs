@@ -0,0 +1,15 @@
+pub use is_finished::*;
+pub use kill::*;
+pub use priority::*;
+pub use set_priority::*;
+pub use set_thread_local::*;
+pub use thread_id::*;
+pub use thread_local::*;
+
+mod is_finished;
+mod kill;
+mod priority;
+mod set_priority;
+mod set_thread_local;
+mod thread_id;
+mod thread_local;
@@ -0,0 +1,18 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const PRIORITY_SYM: &str = "priority";
+
+/// The implementation lives in the VM since the priority a thread runs with depends on the threads
+/// waiting on the semaphores it holds.
+pub fn priority() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: PRIORITY_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
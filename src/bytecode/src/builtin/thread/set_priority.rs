@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const SET_PRIORITY_SYM: &str = "set_priority";
+
+/// The implementation lives in the VM since the priority is kept by the current thread.
+pub fn set_priority() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: SET_PRIORITY_SYM.into(),
+        prms: vec!["n".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
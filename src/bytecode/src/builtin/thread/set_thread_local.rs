@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const SET_THREAD_LOCAL_SYM: &str = "set_thread_local";
+
+/// The implementation lives in the VM since the values are kept by the current thread.
+pub fn set_thread_local() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: SET_THREAD_LOCAL_SYM.into(),
+        prms: vec!["key".into(), "val".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
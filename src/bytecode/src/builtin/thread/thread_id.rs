@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{Closure, FnType, ThreadID, Value, W};
+
+pub const THREAD_ID_SYM: &str = "thread_id";
+
+pub fn thread_id() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: THREAD_ID_SYM.into(),
+        prms: vec!["h".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn thread_id_impl(h: &Value) -> Result<Value> {
+    let tid: ThreadID = h.try_into()?;
+    Ok(Value::Int(tid))
+}
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const THREAD_LOCAL_SYM: &str = "thread_local";
+
+/// The implementation lives in the VM since the values are kept by the current thread.
+pub fn thread_local() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: THREAD_LOCAL_SYM.into(),
+        prms: vec!["key".into(), "init".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
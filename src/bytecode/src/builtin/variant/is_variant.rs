@@ -0,0 +1,73 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, Variant, W};
+
+pub const IS_SOME_SYM: &str = "is_some";
+pub const IS_NONE_SYM: &str = "is_none";
+pub const IS_OK_SYM: &str = "is_ok";
+pub const IS_ERR_SYM: &str = "is_err";
+
+fn is_variant(sym: &str) -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: sym.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn is_some() -> Value {
+    is_variant(IS_SOME_SYM)
+}
+
+pub fn is_none() -> Value {
+    is_variant(IS_NONE_SYM)
+}
+
+pub fn is_ok() -> Value {
+    is_variant(IS_OK_SYM)
+}
+
+pub fn is_err() -> Value {
+    is_variant(IS_ERR_SYM)
+}
+
+/// Whether the value is the variant the `is_` builtin named sym tests for, or None if sym is not one of them.
+/// Values of other types are never the variant, so `?` can test for both `None` and `Err` without knowing the type.
+pub fn is_variant_impl(sym: &str, x: &Value) -> Option<bool> {
+    let variant = match x {
+        Value::Variant(variant) => Some(&**variant),
+        _ => None,
+    };
+
+    let is = match sym {
+        IS_SOME_SYM => matches!(variant, Some(Variant::Some(_))),
+        IS_NONE_SYM => matches!(variant, Some(Variant::None)),
+        IS_OK_SYM => matches!(variant, Some(Variant::Ok(_))),
+        IS_ERR_SYM => matches!(variant, Some(Variant::Err(_))),
+        _ => return None,
+    };
+
+    Some(is)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::none;
+
+    #[test]
+    fn test_is_variant() {
+        let some: Value = Variant::Some(Value::Int(1)).into();
+        assert_eq!(is_variant_impl(IS_SOME_SYM, &some), Some(true));
+        assert_eq!(is_variant_impl(IS_NONE_SYM, &some), Some(false));
+        assert_eq!(is_variant_impl(IS_NONE_SYM, &none()), Some(true));
+
+        // An option is neither ok nor err
+        assert_eq!(is_variant_impl(IS_ERR_SYM, &none()), Some(false));
+        assert_eq!(is_variant_impl(IS_OK_SYM, &Value::Int(1)), Some(false));
+        assert_eq!(is_variant_impl("print", &some), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, Variant, W};
+
+pub const SOME_SYM: &str = "Some";
+pub const OK_SYM: &str = "Ok";
+pub const ERR_SYM: &str = "Err";
+pub const NONE_SYM: &str = "None";
+
+fn make(sym: &str) -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: sym.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn some() -> Value {
+    make(SOME_SYM)
+}
+
+pub fn ok() -> Value {
+    make(OK_SYM)
+}
+
+pub fn err() -> Value {
+    make(ERR_SYM)
+}
+
+/// `None` holds nothing, so it is a constant rather than a function.
+pub fn none() -> Value {
+    Variant::None.into()
+}
+
+/// The variant the constructor named sym builds around x, or None if sym is not one of them.
+pub fn make_impl(sym: &str, x: Value) -> Option<Value> {
+    let variant = match sym {
+        SOME_SYM => Variant::Some(x),
+        OK_SYM => Variant::Ok(x),
+        ERR_SYM => Variant::Err(x),
+        _ => return None,
+    };
+
+    Some(variant.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make() {
+        let some = make_impl(SOME_SYM, Value::Int(1)).unwrap();
+        assert_eq!(some, Variant::Some(Value::Int(1)).into());
+        assert_eq!(some.to_string(), "Some(1)");
+
+        let err = make_impl(ERR_SYM, Value::from("oops")).unwrap();
+        assert_eq!(err.to_string(), "Err(oops)");
+
+        assert_eq!(none().to_string(), "None");
+        assert_eq!(make_impl("print", Value::Unit), None);
+    }
+}
@@ -0,0 +1,7 @@
+pub use is_variant::*;
+pub use make::*;
+pub use unwrap::*;
+
+mod is_variant;
+mod make;
+mod unwrap;
@@ -0,0 +1,89 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{type_of, ByteCodeError, Closure, FnType, Value, Variant, W};
+
+pub const UNWRAP_SYM: &str = "unwrap";
+pub const UNWRAP_ERR_SYM: &str = "unwrap_err";
+
+fn unwrap_fn(sym: &str) -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: sym.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn unwrap() -> Value {
+    unwrap_fn(UNWRAP_SYM)
+}
+
+pub fn unwrap_err() -> Value {
+    unwrap_fn(UNWRAP_ERR_SYM)
+}
+
+/// The value in `Some` or `Ok`, or an error naming the variant found.
+pub fn unwrap_impl(x: &Value) -> Result<Value> {
+    match x {
+        Value::Variant(variant) => match &**variant {
+            Variant::Some(val) | Variant::Ok(val) => Ok(val.clone()),
+            Variant::None | Variant::Err(_) => Err(ByteCodeError::UnwrapFailed {
+                method: UNWRAP_SYM.to_string(),
+                found: variant.to_string(),
+            }
+            .into()),
+        },
+        _ => Err(ByteCodeError::BadType {
+            expected: "Option or Result".to_string(),
+            found: type_of(x).to_string(),
+        }
+        .into()),
+    }
+}
+
+/// The value in `Err`, or an error naming the variant found.
+pub fn unwrap_err_impl(x: &Value) -> Result<Value> {
+    match x {
+        Value::Variant(variant) => match &**variant {
+            Variant::Err(val) => Ok(val.clone()),
+            _ => Err(ByteCodeError::UnwrapFailed {
+                method: UNWRAP_ERR_SYM.to_string(),
+                found: variant.to_string(),
+            }
+            .into()),
+        },
+        _ => Err(ByteCodeError::BadType {
+            expected: "Result".to_string(),
+            found: type_of(x).to_string(),
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::none;
+
+    #[test]
+    fn test_unwrap() {
+        let some: Value = Variant::Some(Value::Int(1)).into();
+        assert_eq!(unwrap_impl(&some).unwrap(), Value::Int(1));
+
+        let err = unwrap_impl(&none()).unwrap_err();
+        assert_eq!(err.to_string(), "Called unwrap on None");
+
+        let err: Value = Variant::Err(Value::from("oops")).into();
+        assert_eq!(
+            unwrap_impl(&err).unwrap_err().to_string(),
+            "Called unwrap on Err(oops)"
+        );
+        assert_eq!(unwrap_err_impl(&err).unwrap(), Value::from("oops"));
+
+        assert!(unwrap_impl(&Value::Int(1)).is_err());
+    }
+}
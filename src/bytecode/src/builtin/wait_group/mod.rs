@@ -0,0 +1,9 @@
+pub use wg_add::*;
+pub use wg_create::*;
+pub use wg_done::*;
+pub use wg_wait::*;
+
+mod wg_add;
+mod wg_create;
+mod wg_done;
+mod wg_wait;
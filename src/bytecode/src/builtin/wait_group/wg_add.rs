@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const WG_ADD_SYM: &str = "wg_add";
+
+/// The implementation lives in the VM since it needs to wake up the threads waiting on the wait group when the count reaches 0.
+pub fn wg_add() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: WG_ADD_SYM.into(),
+        prms: vec!["wg".into(), "n".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,20 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, WaitGroup, W};
+
+pub const WG_CREATE_SYM: &str = "wg_create";
+
+pub fn wg_create() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: WG_CREATE_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
+
+pub fn wg_create_impl() -> Value {
+    WaitGroup::default().into()
+}
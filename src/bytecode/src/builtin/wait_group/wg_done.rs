@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const WG_DONE_SYM: &str = "wg_done";
+
+/// The implementation lives in the VM since it needs to wake up the threads waiting on the wait group when the count reaches 0.
+pub fn wg_done() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: WG_DONE_SYM.into(),
+        prms: vec!["wg".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{Closure, FnType, Value, W};
+
+pub const WG_WAIT_SYM: &str = "wg_wait";
+
+/// The implementation lives in the VM since it needs to block the current thread until the count reaches 0.
+pub fn wg_wait() -> Value {
+    Closure {
+        fn_type: FnType::Builtin,
+        sym: WG_WAIT_SYM.into(),
+        prms: vec!["wg".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+    .into()
+}
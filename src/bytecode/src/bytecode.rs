@@ -1,9 +1,7 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::{BinOp, FrameType, UnOp, Value};
-
-/// A symbol is a string that represents a variable name.
-pub type Symbol = String;
+use crate::{BinOp, FrameType, Symbol, UnOp, Value};
 
 /// A thread ID is a unique identifier for a thread.
 pub type ThreadID = i64;
@@ -13,7 +11,8 @@ pub type Address = usize;
 
 /// The bytecode instructions that the VM can execute. See ignite::micro_code crate for more information
 /// and implementation details.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ByteCode {
     /// Signal that the thread has finished executing.
     DONE,
@@ -23,6 +22,10 @@ pub enum ByteCode {
     LD(Symbol),
     /// Load a constant value onto the operant stack.
     LDC(Value),
+    /// Load the value in the given slot of the frame the given number of levels up onto the operant stack.
+    LDSLOT(usize, usize),
+    /// Assign the top of the operant stack to the given slot of the frame the given number of levels up.
+    ASSIGNSLOT(usize, usize),
     /// Pop the top of the operant stack.
     POP,
     /// Perform the given binary operation on the top two elements of the operant stack.
@@ -39,14 +42,20 @@ pub enum ByteCode {
     ENTERSCOPE(Vec<Symbol>),
     /// Exit the current scope.
     EXITSCOPE,
-    /// Load the function with the given number of arguments and the function address onto the operant stack.
-    LDF(usize, Vec<Symbol>),
+    /// Load the function with the given address, name and parameters onto the operant stack.
+    LDF(usize, Symbol, Vec<Symbol>),
     /// Call a function with the given number of arguments.
     CALL(usize),
-    /// Spawn a new thread with the address of the instruction for the child to execute.
-    SPAWN(Address),
+    /// Spawn a new thread with the address of the instruction for the child to execute,
+    /// moving the given number of values from the top of the operand stack to that of the child,
+    /// e.g the function the child calls and its arguments.
+    SPAWN(Address, usize),
     /// Join a thread.
     JOIN,
+    /// Open a scope that records the threads the current thread spawns until the matching JOINSCOPE.
+    SPAWNSCOPE,
+    /// Wait until every thread spawned in the innermost scope has finished, then close the scope.
+    JOINSCOPE,
     /// Yield the current thread.
     YIELD,
     /// Create a new semaphore (Since semaphores must be created at runtime, this is a special instruction.)
@@ -55,6 +64,104 @@ pub enum ByteCode {
     WAIT,
     /// Post the semaphore.
     POST,
+    /// Enter a lock block holding the lock on the operand stack: a mutex, or an rwlock to write to.
+    /// The lock is released when the frame pushed for the block is popped, however the block is left.
+    LOCK,
+    /// Enter a lock block holding the rwlock on the operand stack to read from, alongside other readers.
+    READLOCK,
+    /// Pop one semaphore for each of the given addresses and wait until any of them can be acquired.
+    /// The semaphore is decremented and pc is set to the address paired with it.
+    SELECT(Vec<Address>),
+    /// Enter a try block whose catch block starts at the given address.
+    /// A runtime error before the matching EXITSCOPE jumps there with the error message on the operand stack.
+    TRY(Address),
+    /// Load a new struct type with the given name and fields onto the operant stack.
+    STRUCT(Symbol, Vec<Symbol>),
+    /// Pop the values of the given fields, in the order they are given, and the struct type below them.
+    /// Push an instance of the struct with the fields set to the values.
+    NEWSTRUCT(Vec<Symbol>),
+    /// Pop a struct and push the value of its given field.
+    LDFIELD(Symbol),
+    /// Pop a value and a struct, and push a copy of the struct with the given field set to the value.
+    SETFIELD(Symbol),
+    /// Pop a receiver and push the given method of its type, then the receiver as the first argument to call it with.
+    LDMETHOD(Symbol),
+    /// Pop a closure and a struct type, and add the closure to the methods of the type with the given name.
+    SETMETHOD(Symbol),
+    /// Push the given variant of the enum with the given name, popping the value it holds if the flag is set.
+    NEWVARIANT(Symbol, Symbol, bool),
+    /// Pop a value of an option, result or enum and push whether it is the variant with the given name.
+    TESTVARIANT(Symbol),
+    /// Pop a value of an option, result or enum and push the value its variant holds.
+    LDPAYLOAD,
+    /// Pop the given number of values and push an array of them, in the order they were pushed.
+    NEWARRAY(usize),
+    /// Pop a value and an array, and push the array with the value appended.
+    /// The array is only copied if it is shared, so building an array on the operand stack appends in place.
+    APPEND,
+    /// Pop an index and an array, and push the element at the index.
+    LDELEM,
+    /// Pop the end, the start and an array or string, and push the slice from the start up to but excluding the end.
+    /// A bound that is unit is open, so the slice starts at the beginning or runs to the end.
+    SLICE,
+    /// Pop an array or string and raise an error unless it has the given number of elements or chars,
+    /// or at least that many if the flag is set.
+    CHECKLEN(usize, bool),
+    /// Pop an array or string and push an iterator over its elements or chars.
+    ITER,
+    /// Pop the end and the start of a range and push an iterator over the ints from the start up to but excluding the end.
+    ITERRANGE,
+    /// Pop an iterator and push its next value, or jump to the given address if it has none left.
+    /// A generator is resumed instead, and its next value is pushed when it yields.
+    NEXT(Address),
+    /// Start a generator function: return to the caller with a generator that runs the rest of the body when resumed.
+    GENERATOR,
+    /// Pop a value and suspend the running generator, returning to the loop that resumed it with the value.
+    SUSPEND,
+    /// LDC then BINOP in one step: perform the given binary operation on the top of the operant stack and the constant.
+    LDCBINOP(Value, BinOp),
+    /// LDSLOT, LDSLOT then BINOP in one step: perform the given binary operation on the values in the given slots,
+    /// each given as the number of levels up and the index in the frame.
+    LDSLOTSBINOP((usize, usize), (usize, usize), BinOp),
+    /// Jump to the given address if the top of the operant stack is false, leaving it there, and pop it otherwise.
+    /// Short-circuits `&&` without loading false again where the jumps meet.
+    JOFORPOP(Address),
+    /// Perform the given binary operation on the operands, assigning the result to the given slot of the frame
+    /// the given number of levels up, or pushing it onto the operant stack if there is none.
+    /// An instruction of the register backend, where the slots of the frame are the registers.
+    BINOPR(Option<(usize, usize)>, Operand, Operand, BinOp),
+    /// Assign the operand to the given slot of the frame the given number of levels up.
+    /// An instruction of the register backend, where the slots of the frame are the registers.
+    MOVR((usize, usize), Operand),
+    /// SELECT with arms that can also receive from or send to a channel: pop the operands of each of the given arms
+    /// and wait until any of them is ready. What the arm waits for is done and pc is set to the address paired with it.
+    /// A select whose arms all wait on semaphores is compiled to SELECT, so files that older versions wrote still run.
+    SELECTARMS(Vec<(SelectOp, Address)>),
+}
+
+/// Where an instruction of the register backend reads a value from.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    /// Pop the top of the operant stack. Where both operands of an instruction are popped,
+    /// the right-hand side is the top, as for BINOP.
+    Stack,
+    /// The value in the given slot of the frame the given number of levels up.
+    Slot(usize, usize),
+    /// A constant value.
+    Const(Value),
+}
+
+/// What an arm of a select waits for, with its operands on the operant stack in the order they are given.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectOp {
+    /// Acquire the semaphore.
+    Wait,
+    /// Receive from the channel, pushing Some of the value, or None once the channel is closed and empty.
+    Recv,
+    /// Send the value to the channel, pushing whether it was sent, which it isn't if the channel is closed.
+    Send,
 }
 
 /// For creating ByteCode instructions in a more ergonomic way.
@@ -71,8 +178,8 @@ impl ByteCode {
         ByteCode::LD(sym.into())
     }
 
-    pub fn ldf<T: Into<Symbol>>(addr: usize, prms: Vec<T>) -> Self {
-        ByteCode::LDF(addr, prms.into_iter().map(Into::into).collect())
+    pub fn ldf<T: Into<Symbol>>(addr: usize, sym: impl Into<Symbol>, prms: Vec<T>) -> Self {
+        ByteCode::LDF(addr, sym.into(), prms.into_iter().map(Into::into).collect())
     }
 
     pub fn binop(op: impl Into<BinOp>) -> Self {
@@ -92,7 +199,48 @@ impl ByteCode {
     }
 }
 
-#[cfg(test)]
+impl ByteCode {
+    /// Shift the addresses the instruction jumps to or loads by the offset,
+    /// for code that is moved `offset` instructions further into the program, e.g. appended to a running one.
+    pub fn relocate(&mut self, offset: Address) {
+        self.map_addresses(|addr| addr + offset);
+    }
+
+    /// Replace each address the instruction jumps to or loads by the result of `f` on it,
+    /// e.g. when instructions before it are removed.
+    pub fn map_addresses(&mut self, mut f: impl FnMut(Address) -> Address) {
+        match self {
+            ByteCode::JOF(addr)
+            | ByteCode::JOFORPOP(addr)
+            | ByteCode::GOTO(addr)
+            | ByteCode::LDF(addr, _, _)
+            | ByteCode::SPAWN(addr, _)
+            | ByteCode::TRY(addr)
+            | ByteCode::NEXT(addr) => *addr = f(*addr),
+            ByteCode::SELECT(addrs) => addrs.iter_mut().for_each(|addr| *addr = f(*addr)),
+            ByteCode::SELECTARMS(arms) => arms.iter_mut().for_each(|(_, addr)| *addr = f(*addr)),
+            _ => (),
+        }
+    }
+
+    /// The addresses the instruction jumps to or loads.
+    pub fn addresses(&self) -> Vec<Address> {
+        match self {
+            ByteCode::JOF(addr)
+            | ByteCode::JOFORPOP(addr)
+            | ByteCode::GOTO(addr)
+            | ByteCode::LDF(addr, _, _)
+            | ByteCode::SPAWN(addr, _)
+            | ByteCode::TRY(addr)
+            | ByteCode::NEXT(addr) => vec![*addr],
+            ByteCode::SELECT(addrs) => addrs.clone(),
+            ByteCode::SELECTARMS(arms) => arms.iter().map(|(_, addr)| *addr).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
 mod tests {
     use super::*;
 
@@ -0,0 +1,43 @@
+use std::{cell::RefCell, collections::VecDeque, fmt::Debug, rc::Rc};
+
+use crate::Value;
+
+/// The state of a channel: the values sent and not yet received, at most cap of them, and whether it is closed.
+#[derive(Debug, Default)]
+pub struct ChannelState {
+    pub cap: usize,
+    pub buf: VecDeque<Value>,
+    pub closed: bool,
+}
+
+/// A bounded queue of values that threads send to and receive from.
+/// Copies of a channel share its state. Values hold reference counted pointers, and threads run on a single
+/// thread of the process, so the state is behind a RefCell rather than a lock.
+#[derive(Clone)]
+pub struct Channel(pub Rc<RefCell<ChannelState>>);
+
+impl Channel {
+    /// An open channel holding at most cap values.
+    pub fn new(cap: usize) -> Self {
+        let state = ChannelState {
+            cap,
+            buf: VecDeque::with_capacity(cap),
+            closed: false,
+        };
+        Channel(Rc::new(RefCell::new(state)))
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Debug for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.0.borrow();
+        let closed = if state.closed { ", closed" } else { "" };
+        write!(f, "Channel({}/{}{})", state.buf.len(), state.cap, closed)
+    }
+}
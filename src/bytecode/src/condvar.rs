@@ -0,0 +1,37 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::W;
+
+/// A condition variable. It holds no state of its own, the threads waiting on it
+/// are tracked by the runtime, so it is only compared by identity.
+pub type CondVar = W<Arc<()>>;
+
+impl CondVar {
+    pub fn new() -> Self {
+        Self(Arc::new(()))
+    }
+}
+
+impl Default for CondVar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for CondVar {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for CondVar {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Debug for CondVar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CondVar")
+    }
+}
@@ -7,17 +7,24 @@ use std::{
 
 use anyhow::Result;
 
-use crate::{builtin, ByteCodeError, Symbol, Value};
+#[cfg(feature = "builtins")]
+use crate::builtin;
+use crate::{ByteCodeError, Symbol, Value};
 
 #[derive(Debug, Clone, Default)]
 pub struct Environment {
     pub parent: Option<Weak<RefCell<Environment>>>,
+    /// Names bound by name, used by the global frame.
     pub env: HashMap<Symbol, Value>,
+    /// Names of the slots, kept for lookups by name and debugging.
+    pub syms: Vec<Symbol>,
+    /// Values of the frame's slots, addressed by index.
+    pub slots: Vec<Value>,
 }
 
 impl PartialEq for Environment {
     fn eq(&self, other: &Self) -> bool {
-        self.env == other.env
+        self.env == other.env && self.syms == other.syms && self.slots == other.slots
     }
 }
 
@@ -27,6 +34,8 @@ impl Environment {
         Environment {
             parent: None,
             env: HashMap::new(),
+            syms: Vec::new(),
+            slots: Vec::new(),
         }
     }
 
@@ -36,16 +45,30 @@ impl Environment {
     /// - Logical constants: true, false
     /// - Math constants: PI, E
     /// - Environment constants: MAX_INT, MIN_INT, MAX_FLOAT, MIN_FLOAT, EPSILON
+    /// - Option constants: None
     ///
     /// Built in functions are added to the global environment.
-    /// - Math functions: abs, ceil, floor, round, sqrt, sin, cos, tan, log10, pow
+    /// - Math functions: abs, ceil, floor, round, sqrt, sin, cos, tan, log10, pow, random_int
     /// - String functions: len
     /// - Type conversion functions: int_to_float, float_to_int, atoi, atoi
+    /// - Option and result functions: Some, Ok, Err, is_some, is_none, is_ok, is_err, unwrap, unwrap_err
     /// - Comparison functions: min, max
+    /// - Thread functions: thread_id, is_finished, kill, thread_local, set_thread_local, priority, set_priority
+    /// - Condition variable functions: cv_create, cv_wait, cv_notify_one, cv_notify_all
+    /// - Barrier functions: barrier_create, barrier_wait
+    /// - Wait group functions: wg_create, wg_add, wg_done, wg_wait
+    /// - Channel functions: channel, send, recv, close
+    /// - Bounded queue functions: bounded_queue, push, pop
+    /// - Lock functions: mutex, rwlock
+    /// - Atomic functions: atomic_int, fetch_add, load, store, compare_and_swap
+    /// - Process functions: exit, panic
+    /// - File functions: read_file, write_file, append_file, file_exists, read_lines
+    /// - JSON functions: json_parse, json_stringify
     ///
     /// # Returns
     ///
     /// A wrapped reference to the global environment.
+    #[cfg(feature = "builtins")]
     pub fn new_global_wrapped() -> Rc<RefCell<Self>> {
         let env = Environment::new_wrapped();
 
@@ -59,12 +82,14 @@ impl Environment {
         env.borrow_mut().set(builtin::E_SYM, std::f64::consts::E);
 
         //Environment constants
-        env.borrow_mut().set(builtin::MAX_INT_SYM, std::i64::MAX);
-        env.borrow_mut().set(builtin::MIN_INT_SYM, std::i64::MIN);
-        env.borrow_mut().set(builtin::MAX_FLOAT_SYM, std::f64::MAX);
-        env.borrow_mut().set(builtin::MIN_FLOAT_SYM, std::f64::MIN);
-        env.borrow_mut()
-            .set(builtin::EPSILON_SYM, std::f64::EPSILON);
+        env.borrow_mut().set(builtin::MAX_INT_SYM, i64::MAX);
+        env.borrow_mut().set(builtin::MIN_INT_SYM, i64::MIN);
+        env.borrow_mut().set(builtin::MAX_FLOAT_SYM, f64::MAX);
+        env.borrow_mut().set(builtin::MIN_FLOAT_SYM, f64::MIN);
+        env.borrow_mut().set(builtin::EPSILON_SYM, f64::EPSILON);
+
+        // Option constants
+        env.borrow_mut().set(builtin::NONE_SYM, builtin::none());
 
         // Built in functions
         // Math functions
@@ -74,13 +99,48 @@ impl Environment {
         env.borrow_mut().set(builtin::TAN_SYM, builtin::tan());
         env.borrow_mut().set(builtin::LOG_SYM, builtin::log());
         env.borrow_mut().set(builtin::POW_SYM, builtin::pow());
+        env.borrow_mut()
+            .set(builtin::RANDOM_INT_SYM, builtin::random_int());
         env.borrow_mut().set(builtin::SQRT_SYM, builtin::sqrt());
         env.borrow_mut().set(builtin::MAX_SYM, builtin::max());
         env.borrow_mut().set(builtin::MIN_SYM, builtin::min());
+        env.borrow_mut().set(builtin::IS_NAN_SYM, builtin::is_nan());
+        env.borrow_mut().set(builtin::IS_INF_SYM, builtin::is_inf());
 
         // String functions
         env.borrow_mut()
             .set(builtin::STRING_LEN_SYM, builtin::string_len());
+        env.borrow_mut().set(builtin::BYTES_SYM, builtin::bytes());
+
+        // Array functions
+        env.borrow_mut().set(builtin::LEN_SYM, builtin::len());
+
+        // Type reflection functions
+        env.borrow_mut()
+            .set(builtin::TYPE_OF_SYM, builtin::type_of());
+        env.borrow_mut().set(builtin::IS_INT_SYM, builtin::is_int());
+        env.borrow_mut()
+            .set(builtin::IS_FLOAT_SYM, builtin::is_float());
+        env.borrow_mut()
+            .set(builtin::IS_BOOL_SYM, builtin::is_bool());
+        env.borrow_mut()
+            .set(builtin::IS_STRING_SYM, builtin::is_string());
+        env.borrow_mut()
+            .set(builtin::IS_UNIT_SYM, builtin::is_unit());
+
+        // Option and result functions
+        env.borrow_mut().set(builtin::SOME_SYM, builtin::some());
+        env.borrow_mut().set(builtin::OK_SYM, builtin::ok());
+        env.borrow_mut().set(builtin::ERR_SYM, builtin::err());
+        env.borrow_mut()
+            .set(builtin::IS_SOME_SYM, builtin::is_some());
+        env.borrow_mut()
+            .set(builtin::IS_NONE_SYM, builtin::is_none());
+        env.borrow_mut().set(builtin::IS_OK_SYM, builtin::is_ok());
+        env.borrow_mut().set(builtin::IS_ERR_SYM, builtin::is_err());
+        env.borrow_mut().set(builtin::UNWRAP_SYM, builtin::unwrap());
+        env.borrow_mut()
+            .set(builtin::UNWRAP_ERR_SYM, builtin::unwrap_err());
 
         // Type conversion functions
         env.borrow_mut()
@@ -97,11 +157,107 @@ impl Environment {
         env.borrow_mut()
             .set(builtin::PRINTLN_SYM, builtin::println());
 
+        // File functions
+        env.borrow_mut()
+            .set(builtin::READ_FILE_SYM, builtin::read_file());
+        env.borrow_mut()
+            .set(builtin::WRITE_FILE_SYM, builtin::write_file());
+        env.borrow_mut()
+            .set(builtin::APPEND_FILE_SYM, builtin::append_file());
+        env.borrow_mut()
+            .set(builtin::FILE_EXISTS_SYM, builtin::file_exists());
+        env.borrow_mut()
+            .set(builtin::READ_LINES_SYM, builtin::read_lines());
+
+        // JSON functions
+        env.borrow_mut()
+            .set(builtin::JSON_PARSE_SYM, builtin::json_parse());
+        env.borrow_mut()
+            .set(builtin::JSON_STRINGIFY_SYM, builtin::json_stringify());
+
         // Semaphore functions
         env.borrow_mut()
             .set(builtin::SEM_CREATE_SYM, builtin::sem_create());
         env.borrow_mut()
             .set(builtin::SEM_SET_SYM, builtin::sem_set());
+        env.borrow_mut()
+            .set(builtin::WAIT_TIMEOUT_SYM, builtin::wait_timeout());
+
+        // Thread functions
+        env.borrow_mut()
+            .set(builtin::THREAD_ID_SYM, builtin::thread_id());
+        env.borrow_mut()
+            .set(builtin::IS_FINISHED_SYM, builtin::is_finished());
+        env.borrow_mut().set(builtin::KILL_SYM, builtin::kill());
+        env.borrow_mut()
+            .set(builtin::THREAD_LOCAL_SYM, builtin::thread_local());
+        env.borrow_mut()
+            .set(builtin::SET_THREAD_LOCAL_SYM, builtin::set_thread_local());
+        env.borrow_mut()
+            .set(builtin::PRIORITY_SYM, builtin::priority());
+        env.borrow_mut()
+            .set(builtin::SET_PRIORITY_SYM, builtin::set_priority());
+
+        // Condition variable functions
+        env.borrow_mut()
+            .set(builtin::CV_CREATE_SYM, builtin::cv_create());
+        env.borrow_mut()
+            .set(builtin::CV_WAIT_SYM, builtin::cv_wait());
+        env.borrow_mut()
+            .set(builtin::CV_NOTIFY_ONE_SYM, builtin::cv_notify_one());
+        env.borrow_mut()
+            .set(builtin::CV_NOTIFY_ALL_SYM, builtin::cv_notify_all());
+
+        // Barrier functions
+        env.borrow_mut()
+            .set(builtin::BARRIER_CREATE_SYM, builtin::barrier_create());
+        env.borrow_mut()
+            .set(builtin::BARRIER_WAIT_SYM, builtin::barrier_wait());
+
+        // Wait group functions
+        env.borrow_mut()
+            .set(builtin::WG_CREATE_SYM, builtin::wg_create());
+        env.borrow_mut().set(builtin::WG_ADD_SYM, builtin::wg_add());
+        env.borrow_mut()
+            .set(builtin::WG_DONE_SYM, builtin::wg_done());
+        env.borrow_mut()
+            .set(builtin::WG_WAIT_SYM, builtin::wg_wait());
+
+        // Channel functions
+        env.borrow_mut()
+            .set(builtin::CHANNEL_SYM, builtin::channel());
+        env.borrow_mut().set(builtin::SEND_SYM, builtin::send());
+        env.borrow_mut().set(builtin::RECV_SYM, builtin::recv());
+        env.borrow_mut().set(builtin::CLOSE_SYM, builtin::close());
+
+        // Bounded queue functions
+        env.borrow_mut()
+            .set(builtin::BOUNDED_QUEUE_SYM, builtin::bounded_queue());
+        env.borrow_mut().set(builtin::PUSH_SYM, builtin::push());
+        env.borrow_mut().set(builtin::POP_SYM, builtin::pop());
+
+        // Lock functions
+        env.borrow_mut().set(builtin::MUTEX_SYM, builtin::mutex());
+        env.borrow_mut().set(builtin::RWLOCK_SYM, builtin::rwlock());
+
+        // Atomic functions
+        env.borrow_mut()
+            .set(builtin::ATOMIC_INT_SYM, builtin::atomic_int());
+        env.borrow_mut()
+            .set(builtin::FETCH_ADD_SYM, builtin::fetch_add());
+        env.borrow_mut().set(builtin::LOAD_SYM, builtin::load());
+        env.borrow_mut().set(builtin::STORE_SYM, builtin::store());
+        env.borrow_mut()
+            .set(builtin::COMPARE_AND_SWAP_SYM, builtin::compare_and_swap());
+
+        // Process functions
+        env.borrow_mut().set(builtin::EXIT_SYM, builtin::exit());
+        env.borrow_mut().set(builtin::PANIC_SYM, builtin::panic());
+
+        // Assertions
+        env.borrow_mut().set(builtin::ASSERT_SYM, builtin::assert());
+        env.borrow_mut()
+            .set(builtin::ASSERT_EQ_SYM, builtin::assert_eq());
 
         env
     }
@@ -119,16 +275,25 @@ impl Environment {
     }
 
     /// Get a snapshot of the value of a symbol in the frame at the time of the call.
-    pub fn get(&self, sym: &Symbol) -> Result<Value> {
+    pub fn get(&self, sym: impl Into<Symbol>) -> Result<Value> {
+        let sym = sym.into();
+
         // If the symbol is found in the current environment, return the value.
-        if let Some(val) = self.env.get(sym) {
+        if let Some(idx) = self.slot_of(sym) {
+            return Ok(self.slots[idx].clone());
+        }
+
+        if let Some(val) = self.env.get(&sym) {
             return Ok(val.clone());
         }
 
         // If the symbol is not found in the current environment, search the parent environment.
         let Some(parent) = &self.parent else {
             // If the parent environment is not found, return an error.
-            return Err(ByteCodeError::UnboundedName { name: sym.clone() }.into());
+            return Err(ByteCodeError::UnboundedName {
+                name: sym.to_string(),
+            }
+            .into());
         };
 
         // If the parent environment is found, search the parent environment.
@@ -148,7 +313,99 @@ impl Environment {
     /// * `sym` - The symbol whose value is to be set.
     /// * `val` - The value to be set.
     pub fn set(&mut self, sym: impl Into<Symbol>, val: impl Into<Value>) {
-        self.env.insert(sym.into(), val.into());
+        let sym = sym.into();
+
+        if let Some(idx) = self.slot_of(sym) {
+            self.slots[idx] = val.into();
+            return;
+        }
+
+        self.env.insert(sym, val.into());
+    }
+
+    /// Declare a new slot at the end of the frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `sym` - The name of the slot.
+    /// * `val` - The initial value of the slot.
+    pub fn declare(&mut self, sym: impl Into<Symbol>, val: impl Into<Value>) {
+        self.syms.push(sym.into());
+        self.slots.push(val.into());
+    }
+
+    /// Get a snapshot of the value in the slot `idx` of the frame `depth` levels up the chain.
+    ///
+    /// # Errors
+    ///
+    /// * `ByteCodeError::UnboundedSlot` - If the frame or the slot does not exist.
+    pub fn get_slot(&self, depth: usize, idx: usize) -> Result<Value> {
+        if depth == 0 {
+            return self
+                .slots
+                .get(idx)
+                .cloned()
+                .ok_or_else(|| ByteCodeError::UnboundedSlot { depth, idx }.into());
+        }
+
+        let parent = self.parent_of_slot(depth, idx)?;
+        let parent_ref = parent.borrow();
+        parent_ref.get_slot(depth - 1, idx)
+    }
+
+    /// Update the value in the slot `idx` of the frame `depth` levels up the chain.
+    ///
+    /// # Errors
+    ///
+    /// * `ByteCodeError::UnboundedSlot` - If the frame or the slot does not exist.
+    pub fn update_slot(&mut self, depth: usize, idx: usize, val: impl Into<Value>) -> Result<()> {
+        if depth == 0 {
+            let Some(slot) = self.slots.get_mut(idx) else {
+                return Err(ByteCodeError::UnboundedSlot { depth, idx }.into());
+            };
+            *slot = val.into();
+            return Ok(());
+        }
+
+        let parent = self.parent_of_slot(depth, idx)?;
+        let mut parent_ref = parent.borrow_mut();
+        parent_ref.update_slot(depth - 1, idx, val)
+    }
+
+    /// Clear the frame so that it can be reused, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.parent = None;
+        self.env.clear();
+        self.syms.clear();
+        self.slots.clear();
+    }
+
+    /// Get a snapshot of the bindings of the frame, slots in declaration order followed by names bound by
+    /// name in alphabetical order. The parent frames are not included.
+    pub fn bindings(&self) -> Vec<(Symbol, Value)> {
+        let mut named: Vec<_> = self.env.iter().map(|(s, v)| (*s, v.clone())).collect();
+        named.sort_by_key(|(s, _)| s.as_str());
+
+        self.syms
+            .iter()
+            .copied()
+            .zip(self.slots.iter().cloned())
+            .chain(named)
+            .collect()
+    }
+
+    fn slot_of(&self, sym: Symbol) -> Option<usize> {
+        self.syms.iter().position(|s| *s == sym)
+    }
+
+    fn parent_of_slot(&self, depth: usize, idx: usize) -> Result<Rc<RefCell<Environment>>> {
+        let Some(parent) = &self.parent else {
+            return Err(ByteCodeError::UnboundedSlot { depth, idx }.into());
+        };
+
+        parent
+            .upgrade()
+            .ok_or_else(|| ByteCodeError::EnvironmentDroppedError.into())
     }
 
     /// Update the value of a symbol in the current environment.
@@ -171,7 +428,12 @@ impl Environment {
         let sym = sym.into();
 
         // If the symbol is found in the current environment, update the value.
-        if let Entry::Occupied(mut entry) = self.env.entry(sym.clone()) {
+        if let Some(idx) = self.slot_of(sym) {
+            self.slots[idx] = val.into();
+            return Ok(());
+        }
+
+        if let Entry::Occupied(mut entry) = self.env.entry(sym) {
             entry.insert(val.into());
             return Ok(());
         }
@@ -179,7 +441,10 @@ impl Environment {
         // If the symbol is not found in the current environment, search the parent environment.
         let Some(parent) = &self.parent else {
             // If the parent environment is not found, return an error.
-            return Err(ByteCodeError::UnboundedName { name: sym }.into());
+            return Err(ByteCodeError::UnboundedName {
+                name: sym.to_string(),
+            }
+            .into());
         };
 
         // If the parent environment is found, search the parent environment.
@@ -206,7 +471,7 @@ mod tests {
     fn test_environment() {
         let env = Environment::new_wrapped();
         env.borrow_mut().set("x", 42);
-        assert_eq!(env.borrow().get(&"x".to_string()).unwrap(), Value::Int(42));
+        assert_eq!(env.borrow().get("x").unwrap(), Value::Int(42));
     }
 
     #[test]
@@ -219,14 +484,8 @@ mod tests {
         child_env.borrow_mut().set_parent(parent_env_weak);
         child_env.borrow_mut().set("y", 43);
 
-        assert_eq!(
-            child_env.borrow().get(&"x".to_string()).unwrap(),
-            Value::Int(42)
-        );
-        assert_eq!(
-            child_env.borrow().get(&"y".to_string()).unwrap(),
-            Value::Int(43)
-        );
+        assert_eq!(child_env.borrow().get("x").unwrap(), Value::Int(42));
+        assert_eq!(child_env.borrow().get("y").unwrap(), Value::Int(43));
     }
 
     #[test]
@@ -240,14 +499,50 @@ mod tests {
         child_env.borrow_mut().set("y", 43);
         child_env.borrow_mut().update("x", 44).unwrap();
 
+        assert_eq!(child_env.borrow().get("x").unwrap(), Value::Int(44));
+        assert_eq!(child_env.borrow().get("y").unwrap(), Value::Int(43));
+        assert!(!child_env.borrow().env.contains_key(&"x".into()));
+    }
+
+    #[test]
+    fn test_slot_environment() {
+        let parent_env = Environment::new_wrapped();
+        parent_env.borrow_mut().declare("x", 42);
+        let parent_env_weak = weak_clone(&parent_env);
+
+        let child_env = Environment::new_wrapped();
+        child_env.borrow_mut().set_parent(parent_env_weak);
+        child_env.borrow_mut().declare("y", 43);
+
+        assert_eq!(child_env.borrow().get_slot(0, 0).unwrap(), Value::Int(43));
+        assert_eq!(child_env.borrow().get_slot(1, 0).unwrap(), Value::Int(42));
+
+        child_env.borrow_mut().update_slot(1, 0, 44).unwrap();
+        assert_eq!(child_env.borrow().get("x").unwrap(), Value::Int(44));
+
+        child_env.borrow_mut().update("y", 45).unwrap();
+        assert_eq!(child_env.borrow().get_slot(0, 0).unwrap(), Value::Int(45));
+
+        assert!(child_env.borrow().get_slot(0, 1).is_err());
+        assert!(child_env.borrow().get_slot(2, 0).is_err());
+    }
+
+    #[test]
+    fn test_bindings() {
+        let env = Environment::new_wrapped();
+        env.borrow_mut().declare("y", 1);
+        env.borrow_mut().declare("x", 2);
+        env.borrow_mut().set("b", 3);
+        env.borrow_mut().set("a", 4);
+
         assert_eq!(
-            child_env.borrow().get(&"x".to_string()).unwrap(),
-            Value::Int(44)
-        );
-        assert_eq!(
-            child_env.borrow().get(&"y".to_string()).unwrap(),
-            Value::Int(43)
+            env.borrow().bindings(),
+            vec![
+                ("y".into(), Value::Int(1)),
+                ("x".into(), Value::Int(2)),
+                ("a".into(), Value::Int(4)),
+                ("b".into(), Value::Int(3)),
+            ]
         );
-        assert!(!child_env.borrow().env.contains_key(&"x".to_string()));
     }
 }
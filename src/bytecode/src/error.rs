@@ -11,6 +11,38 @@ pub enum ByteCodeError {
     #[error("Unbounded name: {name}")]
     UnboundedName { name: String },
 
+    #[error("Unbounded slot: {idx} at depth {depth}")]
+    UnboundedSlot { depth: usize, idx: usize },
+
+    #[error("Bad symbol index: {idx} is not in the string table")]
+    BadSymbolIndex { idx: usize },
+
+    #[error("Too many names: the interner holds at most {max_bytes} bytes of names")]
+    InternerFull { max_bytes: usize },
+
+    #[error("The string constants of the program do not match its literal table of {len} entries")]
+    BadLiterals { len: usize },
+
+    #[error(
+        "Unsupported .o2 version {version}, this build reads versions {min} to {max}: recompile the program from source"
+    )]
+    UnsupportedVersion { version: u32, min: u32, max: u32 },
+
+    #[error("Unsupported .o2 header flags {flags:#x}: recompile the program from source")]
+    UnsupportedFlags { flags: u32 },
+
+    #[error("The program is compressed with {0}, but this build has no compression")]
+    CompressionUnavailable(String),
+
+    #[error("{0}")]
+    AssertionFailed(String),
+
+    #[error("{0}")]
+    Panic(String),
+
+    #[error("Called {method} on {found}")]
+    UnwrapFailed { method: String, found: String },
+
     #[error("Environment access after drop")]
     EnvironmentDroppedError,
 }
@@ -0,0 +1,97 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{EnvWeak, StackFrame, Value};
+
+/// Whether a generator can be resumed.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorStatus {
+    /// Created or stopped at a yield, waiting to be resumed.
+    Suspended,
+    /// Resumed and not yet stopped at a yield, so its state is in the thread running it.
+    Running,
+    /// Its body returned or raised an error, so it has no more values.
+    Done,
+}
+
+/// The state a suspended generator is resumed with, like that of a thread: where it stopped, its environment
+/// and the operands and frames it had pushed since it was resumed.
+#[derive(Debug)]
+pub struct GeneratorState {
+    pub status: GeneratorStatus,
+    pub pc: usize,
+    pub env: EnvWeak,
+    pub operand_stack: Vec<Value>,
+    /// The frames above the resume frame, with the operand stack heights of try frames relative to the operands.
+    pub runtime_stack: Vec<StackFrame>,
+    /// Where the loop resuming the generator goes once the generator is done.
+    pub done_addr: usize,
+}
+
+/// The value a call to a generator function gives, resumed by a for loop or comprehension going over it.
+/// Copies of a generator share its state, so resuming one copy advances all of them.
+#[derive(Clone)]
+pub struct Generator(pub Rc<RefCell<GeneratorState>>);
+
+impl Generator {
+    /// A generator that starts running its body at pc in the environment of its parameters once resumed.
+    pub fn new(pc: usize, env: EnvWeak) -> Generator {
+        let state = GeneratorState {
+            status: GeneratorStatus::Suspended,
+            pc,
+            env,
+            operand_stack: vec![],
+            runtime_stack: vec![],
+            done_addr: 0,
+        };
+
+        Generator(Rc::new(RefCell::new(state)))
+    }
+
+    pub fn status(&self) -> GeneratorStatus {
+        self.0.borrow().status
+    }
+
+    /// Mark the generator as done, dropping what it had saved.
+    pub fn finish(&self) {
+        let mut state = self.0.borrow_mut();
+        state.status = GeneratorStatus::Done;
+        state.operand_stack.clear();
+        state.runtime_stack.clear();
+    }
+}
+
+/// Generators are only equal to their copies.
+impl PartialEq for Generator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.0.borrow();
+        write!(f, "Generator({:?}, {})", state.status, state.pc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator() {
+        let gen = Generator::new(42, EnvWeak::default());
+        assert_eq!(gen.status(), GeneratorStatus::Suspended);
+        assert_eq!(gen, gen.clone());
+        assert_ne!(gen, Generator::new(42, EnvWeak::default()));
+
+        gen.0.borrow_mut().operand_stack.push(Value::Int(1));
+        gen.clone().finish();
+        assert_eq!(gen.status(), GeneratorStatus::Done);
+        assert!(gen.0.borrow().operand_stack.is_empty());
+    }
+}
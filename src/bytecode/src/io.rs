@@ -1,13 +1,276 @@
-use std::io::{Read, Write};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{Read, Write},
+    rc::Rc,
+    str::FromStr,
+};
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-use crate::ByteCode;
+use crate::{ByteCode, ByteCodeError, Metadata, Operand, Symbol, Value};
 
-/// Serialize the bytecode to the writer.
-/// The serialized format is:
-/// - 8 bytes for the length of the serialized bytecode
-/// - The serialized bytecode
+/// The contents of a .o2 file. Symbols in the instructions are indices into the string table
+/// of the file rather than the interner of the process that wrote it.
+#[derive(Serialize, Deserialize)]
+struct Program {
+    strings: Vec<String>,
+    instrs: Vec<ByteCode>,
+    /// From version 4, the index into the string table of each string constant of the instructions,
+    /// in the order they appear, the constants themselves being left empty. Identical strings,
+    /// e.g. the same literal in several modules, are then stored once. Before, constants were stored in place.
+    literals: Vec<u32>,
+}
+
+/// The first version storing string constants in the string table, see [`Program::literals`].
+const LITERALS_VERSION: u32 = 4;
+
+/// The bytes a .o2 file of version 2 or later starts with, before its version.
+/// Files of version 1 start with the length of the program instead, which would have to be over 800MB to match.
+pub const O2_MAGIC: [u8; 4] = *b"RSO2";
+
+/// The version of the .o2 format files are written in.
+pub const O2_VERSION: u32 = 4;
+
+/// The oldest version of the .o2 format that can still be read. Files of older versions have to be recompiled.
+pub const O2_MIN_VERSION: u32 = 1;
+
+/// How the serialized program of a .o2 file is compressed, given by the flags of its header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+    Zstd,
+}
+
+/// The bits of the flags of the header giving the compression of the program.
+const COMPRESSION_FLAGS: u32 = 0b11;
+
+/// The bit of the flags of the header set when the build [`Metadata`] follows the flags.
+const METADATA_FLAG: u32 = 0b100;
+
+impl Compression {
+    fn flags(self) -> u32 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_flags(flags: u32) -> Result<Compression, ByteCodeError> {
+        match flags {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Zstd),
+            _ => Err(ByteCodeError::UnsupportedFlags { flags }),
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn compress(self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes),
+            Compression::Deflate => {
+                let level = flate2::Compression::best();
+                let mut encoder = flate2::write::DeflateEncoder::new(vec![], level);
+                encoder.write_all(&bytes)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Zstd => Ok(zstd::encode_all(bytes.as_slice(), 19)?),
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn decompress(self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        let mut decompressed = vec![];
+        match self {
+            Compression::None => return Ok(bytes),
+            Compression::Deflate => {
+                flate2::read::DeflateDecoder::new(bytes.as_slice())
+                    .read_to_end(&mut decompressed)?;
+            }
+            Compression::Zstd => decompressed = zstd::decode_all(bytes.as_slice())?,
+        }
+        Ok(decompressed)
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn compress(self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes),
+            _ => Err(ByteCodeError::CompressionUnavailable(self.to_string()).into()),
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decompress(self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        self.compress(bytes)
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Compression::None => "none",
+            Compression::Deflate => "deflate",
+            Compression::Zstd => "zstd",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "deflate" => Ok(Compression::Deflate),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!(
+                "unknown compression '{}', expected none, deflate or zstd",
+                s
+            )),
+        }
+    }
+}
+
+/// The header of a .o2 file, before the serialized program. The versions of the format are:
+/// - 1: 8 bytes for the length of the serialized program, without a magic number or version
+/// - 2: the 4 bytes of [`O2_MAGIC`], 4 bytes for the version, then 8 bytes for the length of the serialized program
+/// - 3: as 2, with 4 bytes of flags before the length, whose lowest 2 bits give the [`Compression`] of the program,
+///   the length then being that of the compressed program. If bit 2 of the flags is set, the flags are followed by
+///   4 bytes for the length of the serialized build [`Metadata`], then the metadata
+/// - 4: the header of 3, before a program whose string constants are in its string table
+///
+/// In every version, the serialized program is the string table, followed by the bytecode referring to it,
+/// and from version 4 the table of the string constants of the bytecode. Numbers are little-endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    /// How the program is compressed, none before version 3.
+    pub compression: Compression,
+    /// How the program was built, if the compiler recorded it.
+    pub metadata: Option<Metadata>,
+    /// The length of the serialized program, as stored.
+    pub len: u64,
+}
+
+impl Header {
+    /// The number of bytes the header takes in a file of the version, without metadata.
+    pub fn size(version: u32) -> usize {
+        match version {
+            1 => 8,
+            2 => 16,
+            _ => 20,
+        }
+    }
+
+    /// The number of bytes this header takes, with its metadata.
+    pub fn byte_len(&self) -> usize {
+        let metadata = self.metadata.as_ref().map_or(0, |metadata| {
+            let len = bincode::serialized_size(metadata).expect("metadata serializes");
+            4 + len as usize
+        });
+        Header::size(self.version) + metadata
+    }
+
+    /// Read the header, of any version that can be read.
+    ///
+    /// # Errors
+    ///
+    /// If the reader ends before the header does, or the version is not supported.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Header> {
+        let mut start = [0; 8];
+        reader.read_exact(&mut start)?;
+
+        if start[..4] != O2_MAGIC {
+            return Ok(Header {
+                version: 1,
+                compression: Compression::None,
+                metadata: None,
+                len: u64::from_le_bytes(start),
+            });
+        }
+
+        let version = u32::from_le_bytes(start[4..].try_into().expect("4 bytes"));
+        check_version(version)?;
+
+        let mut compression = Compression::None;
+        let mut metadata = None;
+        if version >= 3 {
+            let mut flags = [0; 4];
+            reader.read_exact(&mut flags)?;
+            let flags = u32::from_le_bytes(flags);
+            if flags & !(COMPRESSION_FLAGS | METADATA_FLAG) != 0 {
+                return Err(ByteCodeError::UnsupportedFlags { flags }.into());
+            }
+            compression = Compression::from_flags(flags & COMPRESSION_FLAGS)?;
+
+            if flags & METADATA_FLAG != 0 {
+                let mut len = [0; 4];
+                reader.read_exact(&mut len)?;
+                let len = u32::from_le_bytes(len) as u64;
+                let mut serialized = vec![];
+                reader.take(len).read_to_end(&mut serialized)?;
+                if serialized.len() as u64 != len {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                metadata = Some(bincode::deserialize(&serialized)?);
+            }
+        }
+
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+
+        Ok(Header {
+            version,
+            compression,
+            metadata,
+            len: u64::from_le_bytes(len),
+        })
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if self.version > 1 {
+            writer.write_all(&O2_MAGIC)?;
+            writer.write_all(&self.version.to_le_bytes())?;
+        }
+        if self.version > 2 {
+            let mut flags = self.compression.flags();
+            if self.metadata.is_some() {
+                flags |= METADATA_FLAG;
+            }
+            writer.write_all(&flags.to_le_bytes())?;
+
+            if let Some(metadata) = &self.metadata {
+                let serialized = bincode::serialize(metadata)?;
+                writer.write_all(&(serialized.len() as u32).to_le_bytes())?;
+                writer.write_all(&serialized)?;
+            }
+        }
+        writer.write_all(&self.len.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+fn check_version(version: u32) -> Result<(), ByteCodeError> {
+    if (O2_MIN_VERSION..=O2_VERSION).contains(&version) {
+        return Ok(());
+    }
+
+    Err(ByteCodeError::UnsupportedVersion {
+        version,
+        min: O2_MIN_VERSION,
+        max: O2_VERSION,
+    })
+}
+
+/// Serialize the bytecode to the writer, in the current version of the format, see [`Header`].
 ///
 /// # Arguments
 /// - `bytecode`: The bytecode to serialize
@@ -16,31 +279,301 @@ use crate::ByteCode;
 /// # Returns
 /// - `Result<()>`: The result of the serialization
 pub fn write_bytecode<W: Write>(bytecode: &[ByteCode], writer: &mut W) -> Result<()> {
-    let serialized = bincode::serialize(bytecode)?;
-    let len = serialized.len() as u64;
-    writer.write_all(&len.to_le_bytes())?;
+    write_object(bytecode, O2_VERSION, Compression::None, None, writer)
+}
+
+/// Serialize the bytecode to the writer in the current version of the format, compressing the program,
+/// e.g. for large generated programs shipped where space is short. Readers detect the compression from the header.
+///
+/// # Errors
+///
+/// If the compression is not available in this build, or writing fails.
+pub fn write_bytecode_compressed<W: Write>(
+    bytecode: &[ByteCode],
+    compression: Compression,
+    writer: &mut W,
+) -> Result<()> {
+    write_object(bytecode, O2_VERSION, compression, None, writer)
+}
+
+/// Serialize the bytecode to the writer in the current version of the format, compressed with `compression`,
+/// recording how it was built in the header. Compilers write this, so runtimes and tools can tell where a file came from.
+///
+/// # Errors
+///
+/// If the compression is not available in this build, or writing fails.
+pub fn write_bytecode_metadata<W: Write>(
+    bytecode: &[ByteCode],
+    compression: Compression,
+    metadata: &Metadata,
+    writer: &mut W,
+) -> Result<()> {
+    write_object(bytecode, O2_VERSION, compression, Some(metadata), writer)
+}
+
+/// Serialize the bytecode to the writer in the given version of the format,
+/// e.g. for a runtime built from an older version of the crates.
+///
+/// # Errors
+///
+/// If the version is not one that can be read, or writing fails.
+pub fn write_bytecode_version<W: Write>(
+    bytecode: &[ByteCode],
+    version: u32,
+    writer: &mut W,
+) -> Result<()> {
+    write_object(bytecode, version, Compression::None, None, writer)
+}
+
+fn write_object<W: Write>(
+    bytecode: &[ByteCode],
+    version: u32,
+    compression: Compression,
+    metadata: Option<&Metadata>,
+    writer: &mut W,
+) -> Result<()> {
+    check_version(version)?;
+
+    let mut strings: Vec<String> = vec![];
+    let mut table: HashMap<String, usize> = HashMap::new();
+    let mut index = |s: &str| {
+        *table.entry(s.to_string()).or_insert_with(|| {
+            strings.push(s.to_string());
+            strings.len() - 1
+        })
+    };
+    let mut literals = vec![];
+
+    // Number the symbols and string constants in order of first use, so the table only holds what the program needs,
+    // and names and constants of the same text share an entry
+    let instrs: Vec<ByteCode> = bytecode
+        .iter()
+        .cloned()
+        .map(|instr| {
+            let instr = map_symbols(instr, &mut |sym| Symbol::from_index(index(sym.as_str())));
+            if version < LITERALS_VERSION {
+                return instr;
+            }
+
+            map_constants(instr, &mut |val| match val {
+                Value::String(s) => {
+                    literals.push(index(&s) as u32);
+                    Value::String(Rc::default())
+                }
+                val => val,
+            })
+        })
+        .collect();
+
+    let serialized = if version < LITERALS_VERSION {
+        bincode::serialize(&(strings, instrs))?
+    } else {
+        bincode::serialize(&Program {
+            strings,
+            instrs,
+            literals,
+        })?
+    };
+    let serialized = compression.compress(serialized)?;
+    let header = Header {
+        version,
+        compression,
+        metadata: metadata.cloned(),
+        len: serialized.len() as u64,
+    };
+    header.write(writer)?;
     writer.write_all(&serialized)?;
     Ok(())
 }
 
-/// Deserialize the bytecode from the reader.
-/// The serialized format is:
-/// - 8 bytes for the length of the serialized bytecode
-/// - The serialized bytecode
+/// The contents of a .o2 file as they are stored, for inspecting the file rather than running it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectFile {
+    /// The version of the format the file is in.
+    pub version: u32,
+    /// How the program is compressed in the file.
+    pub compression: Compression,
+    /// How the program was built, if the compiler recorded it.
+    pub metadata: Option<Metadata>,
+    /// The length of the serialized program given by the header, as stored.
+    pub len: u64,
+    /// The string table, in order of first use by the instructions.
+    pub strings: Vec<String>,
+    /// The bytecode, with its symbols mapped to the interned strings of the table.
+    pub instrs: Vec<ByteCode>,
+}
+
+impl ObjectFile {
+    /// The header the file starts with.
+    pub fn header(&self) -> Header {
+        Header {
+            version: self.version,
+            compression: self.compression,
+            metadata: self.metadata.clone(),
+            len: self.len,
+        }
+    }
+}
+
+/// Deserialize the bytecode from the reader, in any version of the format that can still be read, see [`Header`].
+///
+/// The strings are interned, and the symbols of the bytecode are mapped to the interned symbols.
 ///
 /// # Arguments
 /// - `reader`: The reader to read the serialized bytecode from
 ///
 /// # Returns
-/// - `Result<Vec<ByteCode>>`: The result of the deserialization
+/// - `Result<Vec<ByteCode>>`: The deserialized bytecode
 pub fn read_bytecode<R: Read>(reader: &mut R) -> Result<Vec<ByteCode>> {
-    let mut len_bytes = [0; 8];
-    reader.read_exact(&mut len_bytes)?;
-    let len = u64::from_le_bytes(len_bytes) as usize;
-    let mut serialized = vec![0; len];
-    reader.read_exact(&mut serialized)?;
-    let bytecode = bincode::deserialize(&serialized)?;
-    Ok(bytecode)
+    Ok(read_object(reader)?.instrs)
+}
+
+/// Deserialize a .o2 file from the reader, keeping its header and string table along with the bytecode.
+/// See [`read_bytecode`] for the format.
+pub fn read_object<R: Read>(reader: &mut R) -> Result<ObjectFile> {
+    let Header {
+        version,
+        compression,
+        metadata,
+        len,
+    } = Header::read(reader)?;
+    // Read up to the length rather than allocating it upfront, as the header may be corrupt
+    let mut serialized = vec![];
+    reader.take(len).read_to_end(&mut serialized)?;
+    if serialized.len() as u64 != len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    let serialized = compression.decompress(serialized)?;
+    let program: Program = if version < LITERALS_VERSION {
+        let (strings, instrs) = bincode::deserialize(&serialized)?;
+        Program {
+            strings,
+            instrs,
+            literals: vec![],
+        }
+    } else {
+        bincode::deserialize(&serialized)?
+    };
+
+    // Intern the strings used as names, and share one allocation between the uses of each string constant
+    let mut symbols: Vec<Option<Symbol>> = vec![None; program.strings.len()];
+    let mut constants: Vec<Option<Rc<String>>> = vec![None; program.strings.len()];
+    let mut literals = program.literals.iter();
+    let mut bytecode = Vec::with_capacity(program.instrs.len());
+    for instr in program.instrs {
+        let mut bad_index = None;
+        let mut full = None;
+        let mut instr = map_symbols(instr, &mut |sym| match symbols.get_mut(sym.index()) {
+            Some(Some(interned)) => *interned,
+            Some(interned) => match Symbol::try_intern(&program.strings[sym.index()]) {
+                Ok(new) => *interned.insert(new),
+                Err(err) => {
+                    full = Some(err);
+                    sym
+                }
+            },
+            None => {
+                bad_index = Some(sym.index());
+                sym
+            }
+        });
+        if let Some(err) = full {
+            return Err(err.into());
+        }
+
+        if version >= LITERALS_VERSION {
+            let mut missing = false;
+            instr = map_constants(instr, &mut |val| match val {
+                Value::String(_) => {
+                    let Some(&idx) = literals.next() else {
+                        missing = true;
+                        return val;
+                    };
+                    match constants.get_mut(idx as usize) {
+                        Some(constant) => Value::String(
+                            constant
+                                .get_or_insert_with(|| {
+                                    Rc::new(program.strings[idx as usize].clone())
+                                })
+                                .clone(),
+                        ),
+                        None => {
+                            bad_index = Some(idx as usize);
+                            val
+                        }
+                    }
+                }
+                val => val,
+            });
+
+            if missing {
+                let len = program.literals.len();
+                return Err(ByteCodeError::BadLiterals { len }.into());
+            }
+        }
+
+        if let Some(idx) = bad_index {
+            return Err(ByteCodeError::BadSymbolIndex { idx }.into());
+        }
+
+        bytecode.push(instr);
+    }
+
+    if literals.next().is_some() {
+        let len = program.literals.len();
+        return Err(ByteCodeError::BadLiterals { len }.into());
+    }
+
+    Ok(ObjectFile {
+        version,
+        compression,
+        metadata,
+        len,
+        strings: program.strings,
+        instrs: bytecode,
+    })
+}
+
+/// Apply `f` to every symbol in the instruction.
+fn map_symbols(instr: ByteCode, f: &mut impl FnMut(Symbol) -> Symbol) -> ByteCode {
+    match instr {
+        ByteCode::ASSIGN(sym) => ByteCode::ASSIGN(f(sym)),
+        ByteCode::LD(sym) => ByteCode::LD(f(sym)),
+        ByteCode::ENTERSCOPE(syms) => ByteCode::ENTERSCOPE(syms.into_iter().map(f).collect()),
+        ByteCode::LDF(addr, sym, prms) => {
+            ByteCode::LDF(addr, f(sym), prms.into_iter().map(f).collect())
+        }
+        ByteCode::STRUCT(name, fields) => {
+            ByteCode::STRUCT(f(name), fields.into_iter().map(f).collect())
+        }
+        ByteCode::NEWSTRUCT(fields) => ByteCode::NEWSTRUCT(fields.into_iter().map(f).collect()),
+        ByteCode::LDFIELD(field) => ByteCode::LDFIELD(f(field)),
+        ByteCode::SETFIELD(field) => ByteCode::SETFIELD(f(field)),
+        ByteCode::LDMETHOD(method) => ByteCode::LDMETHOD(f(method)),
+        ByteCode::SETMETHOD(method) => ByteCode::SETMETHOD(f(method)),
+        ByteCode::NEWVARIANT(ty, variant, holds) => ByteCode::NEWVARIANT(f(ty), f(variant), holds),
+        ByteCode::TESTVARIANT(variant) => ByteCode::TESTVARIANT(f(variant)),
+        instr => instr,
+    }
+}
+
+/// Apply `f` to every constant in the instruction, in the order they are loaded.
+fn map_constants(instr: ByteCode, f: &mut impl FnMut(Value) -> Value) -> ByteCode {
+    let mut operand = |operand| match operand {
+        Operand::Const(val) => Operand::Const(f(val)),
+        operand => operand,
+    };
+
+    match instr {
+        ByteCode::LDC(val) => ByteCode::LDC(f(val)),
+        ByteCode::LDCBINOP(val, op) => ByteCode::LDCBINOP(f(val), op),
+        ByteCode::BINOPR(dst, lhs, rhs, op) => {
+            let lhs = operand(lhs);
+            ByteCode::BINOPR(dst, lhs, operand(rhs), op)
+        }
+        ByteCode::MOVR(dst, src) => ByteCode::MOVR(dst, operand(src)),
+        instr => instr,
+    }
 }
 
 #[cfg(test)]
@@ -85,4 +618,225 @@ mod tests {
         // remove file
         std::fs::remove_file("test.o2").unwrap();
     }
+
+    #[test]
+    fn test_serialization_string_table() {
+        let bc = vec![
+            ByteCode::enterscope(vec!["x", "f"]),
+            ByteCode::ldf(3, "f", vec!["y"]),
+            ByteCode::assign("f"),
+            ByteCode::ld("x"),
+            ByteCode::ld("println"),
+            ByteCode::STRUCT("P".into(), vec!["a".into()]),
+            ByteCode::NEWSTRUCT(vec!["a".into()]),
+            ByteCode::LDFIELD("a".into()),
+            ByteCode::SETFIELD("a".into()),
+            ByteCode::LDMETHOD("m".into()),
+            ByteCode::SETMETHOD("m".into()),
+            ByteCode::NEWVARIANT("E".into(), "V".into(), false),
+            ByteCode::TESTVARIANT("V".into()),
+        ];
+        let mut serialized = Vec::new();
+        write_bytecode(&bc, &mut serialized).unwrap();
+
+        // Only the symbols used by the program are in the table, in order of first use
+        let program: super::Program = bincode::deserialize(&serialized[20..]).unwrap();
+        assert_eq!(
+            program.strings,
+            vec!["x", "f", "y", "println", "P", "a", "m", "E", "V"]
+        );
+
+        let deserialized = read_bytecode(&mut serialized.as_slice()).unwrap();
+        assert_eq!(bc, deserialized);
+    }
+
+    #[test]
+    fn test_read_object() {
+        let bc = vec![ByteCode::ldc("hi"), ByteCode::assign("s"), ByteCode::DONE];
+        let mut serialized = Vec::new();
+        write_bytecode(&bc, &mut serialized).unwrap();
+
+        let obj = read_object(&mut serialized.as_slice()).unwrap();
+        assert_eq!(obj.version, O2_VERSION);
+        assert_eq!(obj.compression, Compression::None);
+        assert_eq!(obj.len as usize, serialized.len() - 20);
+        assert_eq!(obj.strings, vec!["hi", "s"]);
+        assert_eq!(obj.instrs, bc);
+    }
+
+    #[test]
+    fn test_read_old_versions() {
+        let bc = vec![ByteCode::ld("x"), ByteCode::ldc(1), ByteCode::DONE];
+        let mut current = Vec::new();
+        write_bytecode(&bc, &mut current).unwrap();
+        assert!(current.starts_with(&O2_MAGIC));
+        assert_eq!(current[4..8], O2_VERSION.to_le_bytes());
+
+        // Version 3 stores string constants in place, so has no literal table
+        let mut v3 = Vec::new();
+        write_bytecode_version(&bc, 3, &mut v3).unwrap();
+        assert_eq!(v3.len(), current.len() - 8);
+        let obj = read_object(&mut v3.as_slice()).unwrap();
+        assert_eq!((obj.version, obj.instrs), (3, bc.clone()));
+
+        // Version 1 only has the length before the program
+        let mut v1 = Vec::new();
+        write_bytecode_version(&bc, 1, &mut v1).unwrap();
+        assert_eq!(v1.len(), v3.len() - 12);
+        assert_eq!(v1[..8], ((v1.len() - 8) as u64).to_le_bytes());
+        assert_eq!(v1[8..], v3[20..]);
+
+        let obj = read_object(&mut v1.as_slice()).unwrap();
+        assert_eq!((obj.version, obj.instrs), (1, bc.clone()));
+
+        // Version 2 has no flags
+        let mut v2 = Vec::new();
+        write_bytecode_version(&bc, 2, &mut v2).unwrap();
+        assert_eq!(v2.len(), v3.len() - 4);
+        let obj = read_object(&mut v2.as_slice()).unwrap();
+        assert_eq!((obj.version, obj.instrs), (2, bc));
+    }
+
+    #[test]
+    fn test_literals() {
+        // The same literal in several linked modules, one also used as a name
+        let greeting = "hello from a module of the program";
+        let bc = vec![
+            ByteCode::ldc(greeting),
+            ByteCode::ldc("x"),
+            ByteCode::assign("x"),
+            ByteCode::LDCBINOP(greeting.into(), BinOp::Add),
+            ByteCode::BINOPR(
+                None,
+                Operand::Const(greeting.into()),
+                Operand::Const(1.into()),
+                BinOp::Add,
+            ),
+            ByteCode::MOVR((0, 1), Operand::Const(greeting.into())),
+            ByteCode::DONE,
+        ];
+        let mut serialized = Vec::new();
+        write_bytecode(&bc, &mut serialized).unwrap();
+
+        let program: super::Program = bincode::deserialize(&serialized[20..]).unwrap();
+        assert_eq!(program.strings, vec![greeting, "x"]);
+        assert_eq!(program.literals, vec![0, 1, 0, 0, 0]);
+
+        let mut v3 = Vec::new();
+        write_bytecode_version(&bc, 3, &mut v3).unwrap();
+        // Each further use of the literal takes 12 bytes, for its index and an empty constant, rather than its length
+        assert!(v3.len() - serialized.len() >= 3 * (greeting.len() - 12));
+
+        // The uses of a constant share one string once loaded
+        let instrs = read_bytecode(&mut serialized.as_slice()).unwrap();
+        assert_eq!(instrs, bc);
+        let (ByteCode::LDC(Value::String(first)), ByteCode::LDCBINOP(Value::String(second), _)) =
+            (&instrs[0], &instrs[3])
+        else {
+            panic!("string constants expected");
+        };
+        assert!(std::rc::Rc::ptr_eq(first, second));
+
+        // A literal table that does not match the constants
+        let mut short = program;
+        short.literals.pop();
+        let mut corrupt = serialized[..12].to_vec();
+        let body = bincode::serialize(&short).unwrap();
+        corrupt.extend((body.len() as u64).to_le_bytes());
+        corrupt.extend(body);
+        let err = read_bytecode(&mut corrupt.as_slice()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ByteCodeError>(),
+            Some(ByteCodeError::BadLiterals { len: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_compression() {
+        // A large generated program, which compresses well
+        let bc: Vec<ByteCode> = (0..1000)
+            .flat_map(|i| {
+                [
+                    ByteCode::ldc(i % 10),
+                    ByteCode::ld("println"),
+                    ByteCode::POP,
+                ]
+            })
+            .collect();
+        let mut plain = Vec::new();
+        write_bytecode(&bc, &mut plain).unwrap();
+
+        for compression in [Compression::Deflate, Compression::Zstd] {
+            let mut compressed = Vec::new();
+            write_bytecode_compressed(&bc, compression, &mut compressed).unwrap();
+            assert!(compressed.len() * 10 < plain.len(), "{}", compression);
+
+            let obj = read_object(&mut compressed.as_slice()).unwrap();
+            assert_eq!(obj.compression, compression);
+            assert_eq!(obj.len as usize, compressed.len() - 20);
+            assert_eq!(obj.instrs, bc);
+        }
+
+        // Flags of later versions are rejected
+        plain[8] = 0b1000;
+        let err = read_bytecode(&mut plain.as_slice()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ByteCodeError>(),
+            Some(ByteCodeError::UnsupportedFlags { flags: 0b1000 })
+        ));
+
+        assert_eq!("zstd".parse(), Ok(Compression::Zstd));
+        assert!("lz4".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let bc = vec![ByteCode::ldc(1), ByteCode::DONE];
+        let metadata = Metadata {
+            compiler_version: "0.1.0".to_string(),
+            source_hash: 0xdead_beef,
+            timestamp: 1_700_000_000,
+            opt_level: 2,
+        };
+
+        for compression in [Compression::None, Compression::Zstd] {
+            let mut serialized = Vec::new();
+            write_bytecode_metadata(&bc, compression, &metadata, &mut serialized).unwrap();
+
+            let header = Header::read(&mut serialized.as_slice()).unwrap();
+            assert_eq!(header.metadata.as_ref(), Some(&metadata));
+            assert_eq!(
+                header.byte_len() as u64 + header.len,
+                serialized.len() as u64
+            );
+
+            let obj = read_object(&mut serialized.as_slice()).unwrap();
+            assert_eq!(obj.metadata, Some(metadata.clone()));
+            assert_eq!((obj.compression, obj.instrs), (compression, bc.clone()));
+        }
+
+        let mut plain = Vec::new();
+        write_bytecode(&bc, &mut plain).unwrap();
+        assert_eq!(read_object(&mut plain.as_slice()).unwrap().metadata, None);
+
+        // Metadata cut short
+        let mut serialized = Vec::new();
+        write_bytecode_metadata(&bc, Compression::None, &metadata, &mut serialized).unwrap();
+        assert!(read_bytecode(&mut &serialized[..30]).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let bc = vec![ByteCode::DONE];
+        assert!(write_bytecode_version(&bc, O2_VERSION + 1, &mut Vec::new()).is_err());
+
+        let mut newer = Vec::new();
+        write_bytecode(&bc, &mut newer).unwrap();
+        newer[4..8].copy_from_slice(&(O2_VERSION + 1).to_le_bytes());
+        let err = read_bytecode(&mut newer.as_slice()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ByteCodeError>(),
+            Some(ByteCodeError::UnsupportedVersion { version, .. }) if *version == O2_VERSION + 1
+        ));
+    }
 }
@@ -0,0 +1,143 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{type_of, Array, ByteCodeError, Value};
+
+/// What an iterator goes over, and how far it has got.
+#[derive(Clone, PartialEq)]
+pub enum IterState {
+    /// The ints from next up to but excluding end.
+    Range { next: i64, end: i64 },
+    /// The elements of the array from idx on.
+    Array { elems: Rc<Array>, idx: usize },
+    /// The chars of the string from the byte idx on, each as a string of one char.
+    Chars { s: Rc<String>, idx: usize },
+}
+
+/// An iterator over a range, an array or a string, as for loops and comprehensions go over them.
+/// Copies of an iterator share its state, so advancing the copy loaded from a variable advances the one in the variable.
+#[derive(Clone)]
+pub struct Iter(pub Rc<RefCell<IterState>>);
+
+impl Iter {
+    pub fn range(start: i64, end: i64) -> Iter {
+        IterState::Range { next: start, end }.into()
+    }
+
+    /// An iterator over the elements of an array or the chars of a string.
+    ///
+    /// # Errors
+    ///
+    /// If the value is not an array or a string.
+    pub fn new(val: &Value) -> Result<Iter, ByteCodeError> {
+        let state = match val {
+            Value::Array(elems) => IterState::Array {
+                elems: elems.clone(),
+                idx: 0,
+            },
+            Value::String(s) => IterState::Chars {
+                s: s.clone(),
+                idx: 0,
+            },
+            _ => {
+                return Err(ByteCodeError::BadType {
+                    expected: "Array or String".to_string(),
+                    found: type_of(val).to_string(),
+                })
+            }
+        };
+
+        Ok(state.into())
+    }
+
+    /// Advance the iterator, returning the next value or None if there are no more.
+    pub fn next(&self) -> Option<Value> {
+        match &mut *self.0.borrow_mut() {
+            IterState::Range { next, end } => {
+                if next >= end {
+                    return None;
+                }
+                let val = *next;
+                *next += 1;
+                Some(Value::Int(val))
+            }
+            IterState::Array { elems, idx } => {
+                let val = elems.get(*idx)?.clone();
+                *idx += 1;
+                Some(val)
+            }
+            IterState::Chars { s, idx } => {
+                let c = s[*idx..].chars().next()?;
+                *idx += c.len_utf8();
+                Some(Value::String(Rc::new(c.into())))
+            }
+        }
+    }
+}
+
+impl From<IterState> for Iter {
+    fn from(state: IterState) -> Self {
+        Iter(Rc::new(RefCell::new(state)))
+    }
+}
+
+/// Iterators are only equal to their copies.
+impl PartialEq for Iter {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Debug for IterState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IterState::Range { next, end } => write!(f, "Range({}..{})", next, end),
+            IterState::Array { elems, idx } => write!(f, "Array({:?}, {})", &***elems, idx),
+            IterState::Chars { s, idx } => write!(f, "Chars({:?}, {})", s, idx),
+        }
+    }
+}
+
+impl Debug for Iter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Iter({:?})", self.0.borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(iter: &Iter) -> Vec<Value> {
+        std::iter::from_fn(|| iter.next()).collect()
+    }
+
+    #[test]
+    fn test_iter_range() {
+        let iter = Iter::range(1, 4);
+        assert_eq!(collect(&iter), vec![1.into(), 2.into(), 3.into()]);
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(collect(&Iter::range(3, 1)), vec![]);
+    }
+
+    #[test]
+    fn test_iter_collections() {
+        let arr = Value::from(vec![1.into(), "a".into()]);
+        let iter = Iter::new(&arr).unwrap();
+        assert_eq!(collect(&iter), vec![1.into(), "a".into()]);
+
+        // the copies share the state
+        let iter = Iter::new(&Value::from("hé!")).unwrap();
+        assert_eq!(iter.clone().next(), Some("h".into()));
+        assert_eq!(collect(&iter), vec!["é".into(), "!".into()]);
+        assert_eq!(
+            *iter.0.borrow(),
+            IterState::Chars {
+                s: Rc::new("hé!".to_string()),
+                idx: 4
+            }
+        );
+
+        assert!(Iter::new(&Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use std::rc::Rc;
+
+use serde_json::Number;
+
+use crate::{type_of, ByteCodeError, Struct, Value};
+
+/// The JSON values that have a counterpart in the language without knowing their shape.
+const JSON_VALUES: &str = "null, bool, number, string or array";
+
+/// The values that have a counterpart in JSON.
+const VALUE_VALUES: &str = "Unit, Int, Float, Bool, String, Array or Struct";
+
+/// Conversion of values to and from JSON, for passing structured data between a host and a program.
+///
+/// Struct instances are objects of their fields. The language has no maps yet, so an object can only be
+/// converted back to a struct whose type is known, see [`Value::from_json_as`].
+/// The values that only make sense inside the VM, e.g. semaphores and closures, have no JSON form.
+impl Value {
+    /// Convert JSON to a value: null is unit, integers that fit in 64 bits are Int and other numbers are Float.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is or contains an object, as the struct it stands for is not known.
+    pub fn from_json(json: serde_json::Value) -> Result<Value, ByteCodeError> {
+        let found = match json {
+            serde_json::Value::Null => return Ok(Value::Unit),
+            serde_json::Value::Bool(b) => return Ok(Value::Bool(b)),
+            serde_json::Value::String(s) => return Ok(Value::String(Rc::new(s))),
+            serde_json::Value::Number(n) => {
+                return Ok(match (n.as_i64(), n.as_f64()) {
+                    (Some(i), _) => Value::Int(i),
+                    (None, Some(f)) => Value::Float(f),
+                    (None, None) => unreachable!("JSON numbers are always representable as f64"),
+                })
+            }
+            serde_json::Value::Array(elems) => {
+                let elems = elems
+                    .into_iter()
+                    .map(Value::from_json)
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(Value::from(elems));
+            }
+            serde_json::Value::Object(_) => "object",
+        };
+
+        Err(ByteCodeError::BadType {
+            expected: JSON_VALUES.to_string(),
+            found: found.to_string(),
+        })
+    }
+
+    /// Convert JSON to a value of the same shape as the example: of the same type, with the elements of arrays
+    /// of the shape of the first element of the example array, and the fields of structs, read from the keys
+    /// of an object, of the shape of the fields of the example struct. Keys that are not fields are ignored.
+    /// Any number is a Float for a Float example.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is not of the shape of the example, e.g. an object lacks a field, or the example
+    /// is or contains an empty array or a value with no JSON form.
+    pub fn from_json_as(json: serde_json::Value, example: &Value) -> Result<Value, ByteCodeError> {
+        let val = match (json, example) {
+            (serde_json::Value::Null, Value::Unit) => Value::Unit,
+            (serde_json::Value::Bool(b), Value::Bool(_)) => Value::Bool(b),
+            (serde_json::Value::String(s), Value::String(_)) => Value::String(Rc::new(s)),
+            (serde_json::Value::Number(n), Value::Int(_)) if n.is_i64() => {
+                Value::Int(n.as_i64().expect("The number is an i64"))
+            }
+            (serde_json::Value::Number(n), Value::Float(_)) => Value::Float(
+                n.as_f64()
+                    .expect("JSON numbers are always representable as f64"),
+            ),
+            (serde_json::Value::Array(elems), Value::Array(example)) => {
+                let Some(example) = example.first() else {
+                    return Err(ByteCodeError::BadType {
+                        expected: "an example array with an element".to_string(),
+                        found: "an empty array".to_string(),
+                    });
+                };
+
+                let elems = elems
+                    .into_iter()
+                    .map(|elem| Value::from_json_as(elem, example))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::from(elems)
+            }
+            (serde_json::Value::Object(mut obj), Value::Struct(example)) => {
+                let fields = example
+                    .ty
+                    .fields
+                    .iter()
+                    .zip(&example.fields)
+                    .map(|(field, example)| match obj.remove(field.as_str()) {
+                        Some(json) => Value::from_json_as(json, example),
+                        None => Err(ByteCodeError::BadType {
+                            expected: format!("object with field {}", field),
+                            found: "object without it".to_string(),
+                        }),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Value::from(Struct {
+                    ty: example.ty.clone(),
+                    fields,
+                })
+            }
+            (json, example) => {
+                let expected = match example {
+                    Value::Unit => "null",
+                    Value::Bool(_) => "bool",
+                    Value::Int(_) => "integer",
+                    Value::Float(_) => "number",
+                    Value::String(_) => "string",
+                    Value::Array(_) => "array",
+                    Value::Struct(_) => "object",
+                    _ => {
+                        return Err(ByteCodeError::BadType {
+                            expected: VALUE_VALUES.to_string(),
+                            found: type_of(example).to_string(),
+                        })
+                    }
+                };
+
+                return Err(ByteCodeError::BadType {
+                    expected: expected.to_string(),
+                    found: json_type(&json).to_string(),
+                });
+            }
+        };
+
+        Ok(val)
+    }
+
+    /// Convert the value to JSON, the inverse of [`Value::from_json`], and of [`Value::from_json_as`] for structs.
+    ///
+    /// # Errors
+    ///
+    /// If the value has no counterpart in JSON, or is or contains a float that is not finite.
+    pub fn to_json(&self) -> Result<serde_json::Value, ByteCodeError> {
+        let json = match self {
+            Value::Unit => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Int(i) => serde_json::Value::Number((*i).into()),
+            Value::String(s) => serde_json::Value::String(s.to_string()),
+            Value::Float(f) => match Number::from_f64(*f) {
+                Some(n) => serde_json::Value::Number(n),
+                None => {
+                    return Err(ByteCodeError::BadType {
+                        expected: "finite Float".to_string(),
+                        found: f.to_string(),
+                    })
+                }
+            },
+            Value::Array(elems) => serde_json::Value::Array(
+                elems
+                    .iter()
+                    .map(Value::to_json)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            Value::Struct(s) => serde_json::Value::Object(
+                s.ty.fields
+                    .iter()
+                    .zip(&s.fields)
+                    .map(|(field, val)| Ok((field.to_string(), val.to_json()?)))
+                    .collect::<Result<_, ByteCodeError>>()?,
+            ),
+            _ => {
+                return Err(ByteCodeError::BadType {
+                    expected: VALUE_VALUES.to_string(),
+                    found: type_of(self).to_string(),
+                })
+            }
+        };
+
+        Ok(json)
+    }
+}
+
+/// The name of the kind of the JSON value, for errors.
+fn json_type(json: &serde_json::Value) -> &'static str {
+    match json {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "bool",
+        serde_json::Value::Number(n) if n.is_i64() => "integer",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{Semaphore, StructType};
+
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let values = vec![
+            Value::Unit,
+            Value::Bool(true),
+            Value::Int(-42),
+            Value::Int(i64::MAX),
+            Value::Float(2.5),
+            Value::from("hello"),
+            Value::from(vec![Value::Int(1), Value::from(vec![Value::Unit])]),
+        ];
+
+        for val in values {
+            let json = val.to_json().unwrap();
+            assert_eq!(Value::from_json(json).unwrap(), val);
+        }
+
+        assert_eq!(Value::Float(1.0).to_json().unwrap(), json!(1.0));
+        assert_eq!(Value::from_json(json!(1.0)).unwrap(), Value::Float(1.0));
+        // Too large for an Int
+        assert_eq!(
+            Value::from_json(json!(u64::MAX)).unwrap(),
+            Value::Float(u64::MAX as f64)
+        );
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let err = Value::from_json(json!([1, { "a": 1 }])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bad type, expected null, bool, number, string or array, found object"
+        );
+
+        let err = Value::Semaphore(Semaphore::default())
+            .to_json()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bad type, expected Unit, Int, Float, Bool, String, Array or Struct, found Semaphore"
+        );
+        assert!(Value::Float(f64::NAN).to_json().is_err());
+    }
+
+    #[test]
+    fn test_json_struct() {
+        let ty = Rc::new(StructType::new(
+            "Point".into(),
+            vec!["x".into(), "y".into()],
+        ));
+        let point = |x, y| {
+            Value::from(Struct {
+                ty: ty.clone(),
+                fields: vec![Value::Int(x), Value::Float(y)],
+            })
+        };
+
+        let json = point(1, 2.5).to_json().unwrap();
+        assert_eq!(json, json!({ "x": 1, "y": 2.5 }));
+        assert_eq!(
+            Value::from_json_as(json, &point(0, 0.0)).unwrap(),
+            point(1, 2.5)
+        );
+
+        // Ints are floats for a float field, keys that are not fields are ignored
+        let val = Value::from_json_as(
+            json!([{ "x": 1, "y": 2, "z": 3 }]),
+            &Value::from(vec![point(0, 0.0)]),
+        );
+        assert_eq!(val.unwrap(), Value::from(vec![point(1, 2.0)]));
+
+        let err = Value::from_json_as(json!({ "x": 1 }), &point(0, 0.0)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bad type, expected object with field y, found object without it"
+        );
+        let err = Value::from_json_as(json!({ "x": 1.5, "y": 2 }), &point(0, 0.0)).unwrap_err();
+        assert_eq!(err.to_string(), "Bad type, expected integer, found number");
+        let err =
+            Value::from_json_as(json!([1, "a"]), &Value::from(vec![Value::Int(0)])).unwrap_err();
+        assert_eq!(err.to_string(), "Bad type, expected integer, found string");
+        let err = Value::from_json_as(json!([1]), &Value::from(Vec::<Value>::new())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bad type, expected an example array with an element, found an empty array"
+        );
+    }
+}
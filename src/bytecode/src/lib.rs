@@ -1,20 +1,65 @@
+#[cfg(feature = "concurrency")]
+pub use atomic_int::*;
+#[cfg(feature = "concurrency")]
+pub use barrier::*;
+#[cfg(feature = "concurrency")]
+pub use bounded_queue::*;
 pub use bytecode::*;
+#[cfg(feature = "concurrency")]
+pub use channel::*;
+#[cfg(feature = "concurrency")]
+pub use condvar::*;
 pub use environment::*;
 pub use error::*;
+pub use generator::*;
+#[cfg(feature = "serde")]
 pub use io::*;
+pub use iter::*;
+#[cfg(feature = "serde")]
+pub use metadata::*;
 pub use operator::*;
 pub use prelude::*;
+#[cfg(feature = "concurrency")]
+pub use rwlock::*;
+#[cfg(feature = "concurrency")]
 pub use semaphore::*;
 pub use stack_frame::*;
+pub use symbol::*;
 pub use value::*;
+#[cfg(feature = "concurrency")]
+pub use wait_group::*;
 
+#[cfg(feature = "concurrency")]
+mod atomic_int;
+#[cfg(feature = "concurrency")]
+mod barrier;
+#[cfg(feature = "concurrency")]
+mod bounded_queue;
+#[cfg(feature = "builtins")]
 pub mod builtin;
 mod bytecode;
+#[cfg(feature = "concurrency")]
+mod channel;
+#[cfg(feature = "concurrency")]
+mod condvar;
 mod environment;
 mod error;
+mod generator;
+#[cfg(feature = "serde")]
 mod io;
+mod iter;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+mod metadata;
 mod operator;
 mod prelude;
+#[cfg(feature = "concurrency")]
+mod rwlock;
+#[cfg(feature = "concurrency")]
 mod semaphore;
 mod stack_frame;
+mod symbol;
 mod value;
+#[cfg(feature = "concurrency")]
+mod wait_group;
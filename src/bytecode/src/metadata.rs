@@ -0,0 +1,107 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// How a .o2 file was built, stored in its header when the compiler records it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The version of the compiler that wrote the file.
+    pub compiler_version: String,
+    /// The hash of the source the program was compiled from, see [`Metadata::hash_sources`].
+    pub source_hash: u64,
+    /// When the file was compiled, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// How much the program was optimized, 0 when it was not.
+    pub opt_level: u8,
+}
+
+impl Metadata {
+    /// The metadata of a program compiled now from the sources, in the order they were linked.
+    /// The time is taken from `SOURCE_DATE_EPOCH` if it is set, so builds can be reproduced byte for byte.
+    pub fn new(compiler_version: &str, sources: &[&str], opt_level: u8) -> Metadata {
+        let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.parse().ok())
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs())
+            });
+
+        Metadata {
+            compiler_version: compiler_version.to_string(),
+            source_hash: Metadata::hash_sources(sources),
+            timestamp,
+            opt_level,
+        }
+    }
+
+    /// The 64-bit FNV-1a hash of the sources, stable across platforms and builds unlike the hasher of std.
+    pub fn hash_sources(sources: &[&str]) -> u64 {
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let mut hash = OFFSET;
+        for source in sources {
+            // End each source with a byte that is not in any UTF-8 text, so moving text between them changes the hash
+            for byte in source.bytes().chain([0xff]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(PRIME);
+            }
+        }
+        hash
+    }
+
+    /// Whether a runtime of the version can run programs from the compiler that wrote the file.
+    /// Versions are compatible as in semver: the same major version, and the same minor version before 1.0.
+    pub fn compatible_with(&self, version: &str) -> bool {
+        fn release(version: &str) -> Option<(u64, u64)> {
+            let mut parts = version.split('.');
+            let major = parts.next()?.parse().ok()?;
+            let minor = parts.next()?.parse().ok()?;
+            Some((major, minor))
+        }
+
+        match (release(&self.compiler_version), release(version)) {
+            (Some((0, minor)), Some((0, other))) => minor == other,
+            (Some((major, _)), Some((other, _))) => major == other,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_sources() {
+        assert_eq!(Metadata::hash_sources(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(
+            Metadata::hash_sources(&["let x = 1;"]),
+            Metadata::hash_sources(&["let x = 1;"])
+        );
+        assert_ne!(
+            Metadata::hash_sources(&["let x = 1;"]),
+            Metadata::hash_sources(&["let x = 2;"])
+        );
+        assert_ne!(
+            Metadata::hash_sources(&["ab", "c"]),
+            Metadata::hash_sources(&["a", "bc"])
+        );
+    }
+
+    #[test]
+    fn test_compatible_with() {
+        let metadata = Metadata::new("0.1.0", &["1"], 0);
+        assert!(metadata.compatible_with("0.1.0"));
+        assert!(metadata.compatible_with("0.1.7"));
+        assert!(!metadata.compatible_with("0.2.0"));
+        assert!(!metadata.compatible_with("1.1.0"));
+
+        let metadata = Metadata::new("1.2.0", &["1"], 0);
+        assert!(metadata.compatible_with("1.0.3"));
+        assert!(!metadata.compatible_with("2.2.0"));
+        assert!(!metadata.compatible_with("unknown"));
+    }
+}
@@ -1,6 +1,8 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
 pub enum BinOp {
     /// Addition of two values of the same type (int or float or string)
     Add,
@@ -12,9 +14,9 @@ pub enum BinOp {
     Div,
     /// Modulo of two values of the same type (int)
     Mod,
-    /// Greater than comparison of two values of the same type (int or float)
+    /// Greater than comparison of two values of the same type (int or float or string)
     Gt,
-    /// Less than comparison of two values of the same type (int or float)
+    /// Less than comparison of two values of the same type (int or float or string)
     Lt,
     /// Equality comparison of two values of the same type (bool or int or float or string)
     Eq,
@@ -22,6 +24,10 @@ pub enum BinOp {
     And,
     /// Logical OR of two values of the same type (bool)
     Or,
+    /// Greater than or equal comparison of two values of the same type (int or float or string)
+    Ge,
+    /// Less than or equal comparison of two values of the same type (int or float or string)
+    Le,
 }
 
 impl From<&str> for BinOp {
@@ -34,6 +40,8 @@ impl From<&str> for BinOp {
             "%" => BinOp::Mod,
             ">" => BinOp::Gt,
             "<" => BinOp::Lt,
+            ">=" => BinOp::Ge,
+            "<=" => BinOp::Le,
             "==" => BinOp::Eq,
             "&&" => BinOp::And,
             "||" => BinOp::Or,
@@ -52,6 +60,8 @@ impl From<BinOp> for String {
             BinOp::Mod => "%".to_string(),
             BinOp::Gt => ">".to_string(),
             BinOp::Lt => "<".to_string(),
+            BinOp::Ge => ">=".to_string(),
+            BinOp::Le => "<=".to_string(),
             BinOp::Eq => "==".to_string(),
             BinOp::And => "&&".to_string(),
             BinOp::Or => "||".to_string(),
@@ -59,7 +69,8 @@ impl From<BinOp> for String {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
 pub enum UnOp {
     /// Negation of a value of the same type (int or float)
     Neg,
@@ -0,0 +1,51 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use crate::W;
+
+/// The state of a readers-writer lock, the number of threads holding it to read,
+/// and whether a thread holds it to write.
+#[derive(Debug, Default)]
+pub struct RwLockState {
+    pub readers: u64,
+    pub writer: bool,
+}
+
+pub type RwLock = W<Arc<Mutex<RwLockState>>>;
+
+impl RwLock {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(RwLockState::default())))
+    }
+}
+
+impl Default for RwLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for RwLock {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for RwLock {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Debug for RwLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock().unwrap();
+        write!(
+            f,
+            "RwLock(readers: {}, writer: {})",
+            state.readers, state.writer
+        )
+    }
+}
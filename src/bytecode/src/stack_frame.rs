@@ -1,11 +1,21 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::EnvWeak;
+use crate::{EnvWeak, Symbol, Value};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum FrameType {
     BlockFrame,
     CallFrame,
+    /// Pushed on entering a try block, so that runtime errors unwind to its catch block.
+    TryFrame,
+    /// Pushed on resuming a generator, so that its yield or end returns to the loop that resumed it.
+    ResumeFrame,
+    /// Pushed on entering a lock block, so that its lock is released however the block is left.
+    LockFrame,
+    /// Pushed on entering a block reading from an rwlock, so that it is released however the block is left.
+    ReadLockFrame,
 }
 
 #[derive(Debug, Clone)]
@@ -13,6 +23,12 @@ pub struct StackFrame {
     pub frame_type: FrameType,
     pub address: Option<usize>,
     pub env: EnvWeak,
+    /// The name of the function called, for call frames.
+    pub sym: Option<Symbol>,
+    /// The height of the operand stack on entering a try block, restored when an error is caught.
+    pub operand_len: Option<usize>,
+    /// The lock held by a lock block, released when its frame is popped.
+    pub lock: Option<Value>,
 }
 
 impl StackFrame {
@@ -21,6 +37,9 @@ impl StackFrame {
             frame_type,
             address: None,
             env,
+            sym: None,
+            operand_len: None,
+            lock: None,
         }
     }
 
@@ -29,6 +48,33 @@ impl StackFrame {
             frame_type,
             address: Some(address),
             env,
+            sym: None,
+            operand_len: None,
+            lock: None,
+        }
+    }
+
+    /// A frame for a try block whose catch block starts at `address`.
+    pub fn new_try(env: EnvWeak, address: usize, operand_len: usize) -> Self {
+        StackFrame {
+            frame_type: FrameType::TryFrame,
+            address: Some(address),
+            env,
+            sym: None,
+            operand_len: Some(operand_len),
+            lock: None,
+        }
+    }
+
+    /// A frame for a lock block holding the lock, a LockFrame or a ReadLockFrame.
+    pub fn new_lock(frame_type: FrameType, env: EnvWeak, lock: Value) -> Self {
+        StackFrame {
+            frame_type,
+            address: None,
+            env,
+            sym: None,
+            operand_len: None,
+            lock: Some(lock),
         }
     }
 }
@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    sync::{LazyLock, Mutex},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ByteCodeError;
+
+/// The most bytes of names the interner holds, see [`Symbol::try_intern`].
+pub const MAX_INTERNED_BYTES: usize = 16 << 20;
+
+/// A symbol is an interned variable name.
+///
+/// Symbols are ids into a string table shared by the compiler and the VM, so they are cheap to copy,
+/// compare and hash. The table is process wide, the .o2 file carries its own table which is interned
+/// again when the bytecode is read (see `read_bytecode`).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    strs: Vec<&'static str>,
+    bytes: usize,
+}
+
+impl Interner {
+    fn intern(&mut self, s: &str, max_bytes: usize) -> Result<Symbol, ByteCodeError> {
+        if let Some(sym) = self.ids.get(s) {
+            return Ok(*sym);
+        }
+
+        if self.bytes + s.len() > max_bytes {
+            return Err(ByteCodeError::InternerFull { max_bytes });
+        }
+
+        // Interned strings live for the rest of the process
+        let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+        let sym = Symbol(self.strs.len() as u32);
+        self.strs.push(s);
+        self.ids.insert(s, sym);
+        self.bytes += s.len();
+        Ok(sym)
+    }
+}
+
+/// The interner is global to the process: every compiler and runtime in it shares the table, so symbols can be
+/// passed between them, and the strings are leaked to hand out `&'static str`s. Nothing is ever removed,
+/// so a process that keeps compiling or loading programs with new names, e.g. an embedder evaluating sources
+/// it is sent, grows the table until it holds [`MAX_INTERNED_BYTES`] of names, after which interning fails.
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    /// Intern the string, returning the existing symbol if it was interned before.
+    ///
+    /// # Panics
+    ///
+    /// If the interner is full. The compiler and the paths loading programs use [`Symbol::try_intern`] to fail
+    /// with an error instead, this and the `From` conversions are for names known to be interned or few, e.g. builtins.
+    pub fn intern(s: &str) -> Self {
+        Symbol::try_intern(s).expect("Interner is full")
+    }
+
+    /// Intern the string, returning the existing symbol if it was interned before.
+    ///
+    /// # Errors
+    ///
+    /// If the string is new and the names interned so far, with it, would be more than [`MAX_INTERNED_BYTES`].
+    pub fn try_intern(s: &str) -> Result<Self, ByteCodeError> {
+        let mut interner = INTERNER.lock().expect("Interner lock poisoned");
+        interner.intern(s, MAX_INTERNED_BYTES)
+    }
+
+    /// Create a symbol from a raw index, used for the string table of a .o2 file.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_index(idx: usize) -> Self {
+        Symbol(idx as u32)
+    }
+
+    /// Get the raw index of the symbol.
+    #[cfg(feature = "serde")]
+    pub(crate) fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Get the string the symbol was interned from.
+    pub fn as_str(self) -> &'static str {
+        let interner = INTERNER.lock().expect("Interner lock poisoned");
+        interner.strs[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Symbol::intern(&s)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(s: &String) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(sym: &Symbol) -> Self {
+        *sym
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let x = Symbol::intern("x");
+        let y = Symbol::from("y");
+
+        assert_eq!(x, Symbol::from("x".to_string()));
+        assert_ne!(x, y);
+        assert_eq!(x.as_str(), "x");
+        assert_eq!(y.to_string(), "y");
+    }
+
+    #[test]
+    fn test_interner_bound() {
+        let mut interner = Interner::default();
+        let ab = interner.intern("ab", 4).unwrap();
+        assert!(interner.intern("cd", 4).is_ok());
+
+        // Interned names are still found once the interner is full
+        assert_eq!(interner.intern("ab", 4).unwrap(), ab);
+        assert_eq!(
+            interner.intern("e", 4).unwrap_err().to_string(),
+            "Too many names: the interner holds at most 4 bytes of names"
+        );
+    }
+}
@@ -1,28 +1,267 @@
-use std::fmt::{Debug, Display};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::{ByteCodeError, EnvWeak, Semaphore, Symbol};
+#[cfg(feature = "concurrency")]
+use crate::{AtomicInt, Barrier, BoundedQueue, Channel, CondVar, RwLock, Semaphore, WaitGroup};
+use crate::{ByteCodeError, EnvWeak, Generator, Iter, Symbol};
 
 /// The values that can be stored on the operant stack.
-#[derive(Serialize, Deserialize, Clone, PartialEq)]
+///
+/// Values are cloned whenever they are loaded, so every variant is at most a pointer in size:
+/// scalars are stored inline and heap allocated values are behind a single reference counted pointer.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq)]
 pub enum Value {
     Unitialized,
     Unit,
     Int(i64),
     Float(f64),
     Bool(bool),
-    String(String),
-    #[serde(skip_serializing, skip_deserializing)]
+    String(Rc<String>),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
     Semaphore(Semaphore),
-    #[serde(skip_serializing, skip_deserializing)]
-    Closure {
-        fn_type: FnType,
-        sym: Symbol,
-        prms: Vec<Symbol>,
-        addr: usize,
-        env: EnvWeak,
-    },
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    CondVar(CondVar),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Barrier(Barrier),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    WaitGroup(WaitGroup),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Channel(Channel),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    BoundedQueue(BoundedQueue),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    RwLock(RwLock),
+    #[cfg(feature = "concurrency")]
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    AtomicInt(AtomicInt),
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Closure(Rc<Closure>),
+    Variant(Rc<Variant>),
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    StructType(Rc<StructType>),
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Struct(Rc<Struct>),
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Enum(Rc<Enum>),
+    /// Arrays are immutable like structs, only the array a comprehension is building is appended to in place.
+    Array(Rc<Array>),
+    /// The iterator a for loop or comprehension goes over its iterable with, never bound to a user variable.
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Iter(Iter),
+    /// What a call to a generator function gives, holding the state the generator is resumed with.
+    #[cfg_attr(feature = "serde", serde(skip_serializing, skip_deserializing))]
+    Generator(Generator),
+}
+
+/// A struct declared in the program, which its name is bound to, with the methods of its impl blocks.
+/// Methods are added when the impl blocks run, so they are behind a RefCell.
+pub struct StructType {
+    pub name: Symbol,
+    pub fields: Vec<Symbol>,
+    pub methods: RefCell<HashMap<Symbol, Value>>,
+}
+
+impl StructType {
+    pub fn new(name: Symbol, fields: Vec<Symbol>) -> Self {
+        StructType {
+            name,
+            fields,
+            methods: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Index of the field in the values of the instances
+    pub fn field_idx(&self, field: Symbol) -> Option<usize> {
+        self.fields.iter().position(|f| *f == field)
+    }
+}
+
+/// Struct types are only equal to themselves, two structs with the same name and fields are different types.
+impl PartialEq for StructType {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// An instance of a struct, with the values of the fields in the order they are declared.
+/// Instances are immutable, assigning to a field makes an updated copy.
+#[derive(Clone, PartialEq)]
+pub struct Struct {
+    pub ty: Rc<StructType>,
+    pub fields: Vec<Value>,
+}
+
+impl Struct {
+    pub fn field(&self, field: Symbol) -> Option<&Value> {
+        self.ty.field_idx(field).map(|idx| &self.fields[idx])
+    }
+}
+
+impl Display for Struct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = self
+            .ty
+            .fields
+            .iter()
+            .zip(self.fields.iter())
+            .map(|(name, val)| format!("{}: {}", name, val))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{} {{ {} }}", self.ty.name, fields)
+    }
+}
+
+/// A value of an option or result type, built by the `Some`, `Ok` and `Err` builtins or the `None` constant.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variant {
+    Some(Value),
+    None,
+    Ok(Value),
+    Err(Value),
+}
+
+impl Variant {
+    /// The value the variant holds, if any.
+    pub fn payload(&self) -> Option<&Value> {
+        match self {
+            Variant::Some(val) | Variant::Ok(val) | Variant::Err(val) => Some(val),
+            Variant::None => None,
+        }
+    }
+
+    /// The name of the variant, as written in match patterns.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Some(_) => "Some",
+            Variant::None => "None",
+            Variant::Ok(_) => "Ok",
+            Variant::Err(_) => "Err",
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Variant::Some(val) => write!(f, "Some({})", val),
+            Variant::None => write!(f, "None"),
+            Variant::Ok(val) => write!(f, "Ok({})", val),
+            Variant::Err(val) => write!(f, "Err({})", val),
+        }
+    }
+}
+
+/// A value of an enum declared in the program: one of its variants, with the value it holds if any.
+#[derive(Clone, PartialEq)]
+pub struct Enum {
+    pub ty: Symbol,
+    pub variant: Symbol,
+    pub payload: Option<Value>,
+}
+
+impl Display for Enum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.payload {
+            Some(val) => write!(f, "{}({})", self.variant, val),
+            None => write!(f, "{}", self.variant),
+        }
+    }
+}
+
+/// The elements of an array. A slice of an array shares its buffer and covers a range of it,
+/// so slicing doesn't copy the elements. Arrays deref to the slice of the elements they cover.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<Value>", into = "Vec<Value>"))]
+#[derive(Clone)]
+pub struct Array {
+    buf: Rc<Vec<Value>>,
+    start: usize,
+    end: usize,
+}
+
+impl Array {
+    /// The slice of the elements from start up to but excluding end, sharing the buffer of the array.
+    /// None if the range is not within the bounds of the array.
+    pub fn slice(&self, start: usize, end: usize) -> Option<Array> {
+        if start > end || end > self.len() {
+            return None;
+        }
+
+        Some(Array {
+            buf: self.buf.clone(),
+            start: self.start + start,
+            end: self.start + end,
+        })
+    }
+
+    /// Append the value, in place if the array covers the whole of a buffer no other array shares.
+    /// Otherwise the elements are copied to a new buffer first.
+    pub fn push(&mut self, val: Value) {
+        if self.start != 0 || self.end != self.buf.len() {
+            self.buf = Rc::new(self.to_vec());
+            self.start = 0;
+        }
+
+        Rc::make_mut(&mut self.buf).push(val);
+        self.end = self.buf.len();
+    }
+}
+
+impl std::ops::Deref for Array {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        &self.buf[self.start..self.end]
+    }
+}
+
+/// Arrays are equal if their elements are, whether or not they share a buffer.
+impl PartialEq for Array {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl From<Vec<Value>> for Array {
+    fn from(v: Vec<Value>) -> Self {
+        Array {
+            start: 0,
+            end: v.len(),
+            buf: Rc::new(v),
+        }
+    }
+}
+
+impl From<Array> for Vec<Value> {
+    fn from(v: Array) -> Self {
+        v.to_vec()
+    }
+}
+
+/// A function value, either a user function with the environment it captured or a builtin.
+#[derive(Clone, PartialEq)]
+pub struct Closure {
+    pub fn_type: FnType,
+    pub sym: Symbol,
+    pub prms: Vec<Symbol>,
+    pub addr: usize,
+    pub env: EnvWeak,
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
@@ -40,11 +279,120 @@ pub fn type_of(value: &Value) -> &'static str {
         Value::Float(_) => "Float",
         Value::Bool(_) => "Bool",
         Value::String(_) => "String",
+        #[cfg(feature = "concurrency")]
         Value::Semaphore(_) => "Semaphore",
-        Value::Closure { .. } => "Closure",
+        #[cfg(feature = "concurrency")]
+        Value::CondVar(_) => "CondVar",
+        #[cfg(feature = "concurrency")]
+        Value::Barrier(_) => "Barrier",
+        #[cfg(feature = "concurrency")]
+        Value::WaitGroup(_) => "WaitGroup",
+        #[cfg(feature = "concurrency")]
+        Value::Channel(_) => "Channel",
+        #[cfg(feature = "concurrency")]
+        Value::BoundedQueue(_) => "BoundedQueue",
+        #[cfg(feature = "concurrency")]
+        Value::RwLock(_) => "RwLock",
+        #[cfg(feature = "concurrency")]
+        Value::AtomicInt(_) => "AtomicInt",
+        Value::Closure(_) => "Closure",
+        Value::Variant(variant) => match **variant {
+            Variant::Some(_) | Variant::None => "Option",
+            Variant::Ok(_) | Variant::Err(_) => "Result",
+        },
+        Value::StructType(_) => "StructType",
+        Value::Struct(_) => "Struct",
+        Value::Enum(_) => "Enum",
+        Value::Array(_) => "Array",
+        Value::Iter(_) => "Iter",
+        Value::Generator(_) => "Generator",
     }
 }
 
+/// Compare two values for `==`, shared by the EQ binop and `assert_eq`.
+///
+/// - Unit, ints, floats and bools compare by value. Floats follow IEEE 754, so NaN is not equal to itself.
+/// - Strings compare by their contents.
+/// - Options and results are equal if they are the same variant and hold equal values, compared recursively.
+/// - Semaphores, condition variables, barriers, wait groups, channels, rwlocks, atomic ints and generators compare by identity: a value is only
+///   equal to itself, including copies of it passed around the program.
+/// - Structs are equal if they are of the same struct type and their fields are equal, compared recursively.
+/// - Values of enums are equal if they are the same variant of the same enum and hold equal values, if any.
+/// - Arrays are equal if they have the same length and their elements are equal, compared recursively.
+/// - Functions can't be compared, since closures of the same function can capture different environments.
+///   Neither can struct types, which are only used to build structs, or the iterators of loops.
+///
+/// Returns None if the values can't be compared, i.e. they are of different types or are functions.
+pub fn structural_eq(lhs: &Value, rhs: &Value) -> Option<bool> {
+    let eq = match (lhs, rhs) {
+        (Value::Unit, Value::Unit) => true,
+        (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+        (Value::Float(lhs), Value::Float(rhs)) => lhs == rhs,
+        (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+        (Value::String(lhs), Value::String(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::Semaphore(lhs), Value::Semaphore(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::CondVar(lhs), Value::CondVar(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::Barrier(lhs), Value::Barrier(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::WaitGroup(lhs), Value::WaitGroup(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::Channel(lhs), Value::Channel(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::BoundedQueue(lhs), Value::BoundedQueue(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::RwLock(lhs), Value::RwLock(rhs)) => lhs == rhs,
+        #[cfg(feature = "concurrency")]
+        (Value::AtomicInt(lhs), Value::AtomicInt(rhs)) => lhs == rhs,
+        (Value::Generator(lhs), Value::Generator(rhs)) => lhs == rhs,
+        (Value::Variant(lhs), Value::Variant(rhs)) => match (lhs.as_ref(), rhs.as_ref()) {
+            (Variant::None, Variant::None) => true,
+            (Variant::Some(lhs), Variant::Some(rhs))
+            | (Variant::Ok(lhs), Variant::Ok(rhs))
+            | (Variant::Err(lhs), Variant::Err(rhs)) => return structural_eq(lhs, rhs),
+            // Different variants of the same type, the values they hold are not compared
+            (Variant::Some(_) | Variant::None, Variant::Some(_) | Variant::None)
+            | (Variant::Ok(_) | Variant::Err(_), Variant::Ok(_) | Variant::Err(_)) => false,
+            _ => return None,
+        },
+        (Value::Struct(lhs), Value::Struct(rhs)) if lhs.ty == rhs.ty => {
+            for (lhs, rhs) in lhs.fields.iter().zip(rhs.fields.iter()) {
+                if !structural_eq(lhs, rhs)? {
+                    return Some(false);
+                }
+            }
+            true
+        }
+        (Value::Enum(lhs), Value::Enum(rhs)) if lhs.ty == rhs.ty => {
+            if lhs.variant != rhs.variant {
+                return Some(false);
+            }
+
+            match (&lhs.payload, &rhs.payload) {
+                (Some(lhs), Some(rhs)) => return structural_eq(lhs, rhs),
+                _ => true,
+            }
+        }
+        (Value::Array(lhs), Value::Array(rhs)) => {
+            if lhs.len() != rhs.len() {
+                return Some(false);
+            }
+
+            for (lhs, rhs) in lhs.iter().zip(rhs.iter()) {
+                if !structural_eq(lhs, rhs)? {
+                    return Some(false);
+                }
+            }
+            true
+        }
+        _ => return None,
+    };
+
+    Some(eq)
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let res = match self {
@@ -54,8 +402,37 @@ impl Display for Value {
             Value::Bool(b) => b.to_string(),
             Value::Int(i) => i.to_string(),
             Value::Float(f) => f.to_string(),
+            #[cfg(feature = "concurrency")]
             Value::Semaphore(_) => "semaphore".to_string(),
-            Value::Closure { .. } => "closure".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::CondVar(_) => "condvar".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::Barrier(_) => "barrier".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::WaitGroup(_) => "waitgroup".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::Channel(_) => "channel".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::BoundedQueue(_) => "bounded_queue".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::RwLock(_) => "rwlock".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::AtomicInt(_) => "atomic_int".to_string(),
+            Value::Closure(_) => "closure".to_string(),
+            Value::Variant(variant) => variant.to_string(),
+            Value::StructType(ty) => format!("struct {}", ty.name),
+            Value::Struct(instance) => instance.to_string(),
+            Value::Enum(val) => val.to_string(),
+            Value::Array(elems) => format!(
+                "[{}]",
+                elems
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Iter(_) => "iter".to_string(),
+            Value::Generator(_) => "generator".to_string(),
         };
 
         write!(f, "{}", res)
@@ -71,17 +448,36 @@ impl Debug for Value {
             Value::Bool(b) => b.to_string(),
             Value::Int(i) => i.to_string(),
             Value::Float(f) => f.to_string(),
+            #[cfg(feature = "concurrency")]
             Value::Semaphore(_) => "semaphore".to_string(),
-            Value::Closure {
-                sym,
-                fn_type,
-                prms,
-                addr,
-                ..
-            } => format!(
+            #[cfg(feature = "concurrency")]
+            Value::CondVar(_) => "condvar".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::Barrier(_) => "barrier".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::WaitGroup(_) => "waitgroup".to_string(),
+            #[cfg(feature = "concurrency")]
+            Value::Channel(ch) => format!("{:?}", ch),
+            #[cfg(feature = "concurrency")]
+            Value::BoundedQueue(q) => format!("{:?}", q),
+            #[cfg(feature = "concurrency")]
+            Value::RwLock(rw) => format!("{:?}", rw),
+            #[cfg(feature = "concurrency")]
+            Value::AtomicInt(a) => format!("{:?}", a),
+            Value::Closure(closure) => format!(
                 "Closure {{ sym: {}, fn_type: {:?}, prms: {:?}, addr: {} }}",
-                sym, fn_type, prms, addr
+                closure.sym, closure.fn_type, closure.prms, closure.addr
+            ),
+            Value::Variant(variant) => format!("{:?}", variant),
+            Value::StructType(ty) => format!(
+                "StructType {{ name: {}, fields: {:?} }}",
+                ty.name, ty.fields
             ),
+            Value::Struct(instance) => instance.to_string(),
+            Value::Enum(val) => format!("{}::{}", val.ty, val),
+            Value::Array(elems) => format!("{:?}", &***elems),
+            Value::Iter(iter) => format!("{:?}", iter),
+            Value::Generator(gen) => format!("{:?}", gen),
         };
 
         write!(f, "{}", res)
@@ -114,22 +510,108 @@ impl From<()> for Value {
 
 impl From<String> for Value {
     fn from(v: String) -> Self {
-        Value::String(v)
+        Value::String(Rc::new(v))
     }
 }
 
 impl From<&str> for Value {
     fn from(v: &str) -> Self {
-        Value::String(v.to_string())
+        Value::String(Rc::new(v.to_string()))
+    }
+}
+
+impl From<Closure> for Value {
+    fn from(v: Closure) -> Self {
+        Value::Closure(Rc::new(v))
+    }
+}
+
+impl From<Variant> for Value {
+    fn from(v: Variant) -> Self {
+        Value::Variant(Rc::new(v))
+    }
+}
+
+impl From<Enum> for Value {
+    fn from(v: Enum) -> Self {
+        Value::Enum(Rc::new(v))
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Value::Array(Rc::new(v.into()))
+    }
+}
+
+impl From<Array> for Value {
+    fn from(v: Array) -> Self {
+        Value::Array(Rc::new(v))
+    }
+}
+
+impl From<Struct> for Value {
+    fn from(v: Struct) -> Self {
+        Value::Struct(Rc::new(v))
     }
 }
 
+#[cfg(feature = "concurrency")]
 impl From<Semaphore> for Value {
     fn from(v: Semaphore) -> Self {
         Value::Semaphore(v)
     }
 }
 
+#[cfg(feature = "concurrency")]
+impl From<CondVar> for Value {
+    fn from(v: CondVar) -> Self {
+        Value::CondVar(v)
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl From<Barrier> for Value {
+    fn from(v: Barrier) -> Self {
+        Value::Barrier(v)
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl From<WaitGroup> for Value {
+    fn from(v: WaitGroup) -> Self {
+        Value::WaitGroup(v)
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl From<Channel> for Value {
+    fn from(v: Channel) -> Self {
+        Value::Channel(v)
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl From<BoundedQueue> for Value {
+    fn from(v: BoundedQueue) -> Self {
+        Value::BoundedQueue(v)
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl From<RwLock> for Value {
+    fn from(v: RwLock) -> Self {
+        Value::RwLock(v)
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl From<AtomicInt> for Value {
+    fn from(v: AtomicInt) -> Self {
+        Value::AtomicInt(v)
+    }
+}
+
 impl TryFrom<Value> for () {
     type Error = ByteCodeError;
 
@@ -158,6 +640,20 @@ impl TryFrom<Value> for i64 {
     }
 }
 
+impl TryFrom<&Value> for i64 {
+    type Error = ByteCodeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(i) => Ok(*i),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Int".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
 impl TryFrom<Value> for f64 {
     type Error = ByteCodeError;
 
@@ -172,6 +668,20 @@ impl TryFrom<Value> for f64 {
     }
 }
 
+impl TryFrom<&Value> for f64 {
+    type Error = ByteCodeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Float(f) => Ok(*f),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Float".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
 impl TryFrom<Value> for bool {
     type Error = ByteCodeError;
 
@@ -186,12 +696,26 @@ impl TryFrom<Value> for bool {
     }
 }
 
+impl TryFrom<&Value> for bool {
+    type Error = ByteCodeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Bool".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
 impl TryFrom<Value> for String {
     type Error = ByteCodeError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value {
-            Value::String(s) => Ok(s),
+            Value::String(s) => Ok(Rc::unwrap_or_clone(s)),
             _ => Err(ByteCodeError::TypeMismatch {
                 expected: "String".to_string(),
                 found: format!("{:?}", value),
@@ -200,6 +724,54 @@ impl TryFrom<Value> for String {
     }
 }
 
+impl TryFrom<&Value> for String {
+    type Error = ByteCodeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        <&str>::try_from(value).map(str::to_string)
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = ByteCodeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(s.as_str()),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "String".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+/// An absent value is unit, as the language has no option type: functions with nothing to return produce unit.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Unit, Into::into)
+    }
+}
+
+/// Unit is `None`, any other value is converted to `T`.
+impl<T: TryFrom<Value, Error = ByteCodeError>> TryFrom<Value> for Option<T> {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Unit => Ok(None),
+            _ => T::try_from(value).map(Some).map_err(|err| match err {
+                ByteCodeError::TypeMismatch { expected, found } => ByteCodeError::TypeMismatch {
+                    expected: format!("{} or Unit", expected),
+                    found,
+                },
+                err => err,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
 impl TryFrom<Value> for Semaphore {
     type Error = ByteCodeError;
 
@@ -214,6 +786,111 @@ impl TryFrom<Value> for Semaphore {
     }
 }
 
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for CondVar {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::CondVar(cv) => Ok(cv),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "CondVar".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for Barrier {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Barrier(b) => Ok(b),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Barrier".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for WaitGroup {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::WaitGroup(wg) => Ok(wg),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "WaitGroup".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for Channel {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Channel(ch) => Ok(ch),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Channel".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for BoundedQueue {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::BoundedQueue(q) => Ok(q),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "BoundedQueue".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for RwLock {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::RwLock(rw) => Ok(rw),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "RwLock".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "concurrency")]
+impl TryFrom<Value> for AtomicInt {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::AtomicInt(a) => Ok(a),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "AtomicInt".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,6 +947,178 @@ mod tests {
     fn test_from_string() {
         let string_value: String = "Hello, World!".to_string();
         let value: Value = string_value.clone().into();
-        assert_eq!(value, Value::String(string_value));
+        assert_eq!(value, Value::String(Rc::new(string_value)));
+    }
+
+    #[test]
+    fn test_value_size() {
+        // Scalars are stored inline and heap values behind a single pointer
+        assert_eq!(std::mem::size_of::<Value>(), 16);
+    }
+
+    #[test]
+    fn test_array_slice() {
+        let arr = Array::from(vec![1.into(), 2.into(), 3.into()]);
+        let mut slice = arr.slice(1, 3).unwrap();
+        assert_eq!(*slice, [2.into(), 3.into()]);
+        assert!(Rc::ptr_eq(&arr.buf, &slice.buf));
+
+        assert_eq!(slice.slice(1, 1).unwrap().len(), 0);
+        assert!(slice.slice(1, 3).is_none());
+        assert!(slice.slice(2, 1).is_none());
+
+        // pushing to a slice copies its elements, leaving the array as it was
+        slice.push(4.into());
+        assert_eq!(*slice, [2.into(), 3.into(), 4.into()]);
+        assert_eq!(*arr, [1.into(), 2.into(), 3.into()]);
+        assert!(!Rc::ptr_eq(&arr.buf, &slice.buf));
+    }
+
+    #[test]
+    fn test_try_from_ref() {
+        let v: Value = 42.into();
+        assert_eq!(i64::try_from(&v).unwrap(), 42);
+        assert!(f64::try_from(&v).is_err());
+    }
+
+    #[test]
+    fn test_try_from_str() {
+        let v: Value = "hello".into();
+        assert_eq!(<&str>::try_from(&v).unwrap(), "hello");
+        assert_eq!(String::try_from(&v).unwrap(), "hello");
+
+        let err = <&str>::try_from(&Value::Int(1)).unwrap_err();
+        assert_eq!(err.to_string(), "Type mismatch, expected String, found 1");
+    }
+
+    #[test]
+    fn test_structural_eq() {
+        assert_eq!(structural_eq(&Value::Unit, &Value::Unit), Some(true));
+        assert_eq!(structural_eq(&Value::Int(1), &Value::Int(2)), Some(false));
+        assert_eq!(
+            structural_eq(&Value::Float(f64::NAN), &Value::Float(f64::NAN)),
+            Some(false)
+        );
+        assert_eq!(structural_eq(&"ab".into(), &"ab".into()), Some(true));
+        assert_eq!(structural_eq(&Value::Int(1), &Value::Float(1.0)), None);
+
+        // variants compare what they hold
+        let some = |v: Value| Value::from(Variant::Some(v));
+        assert_eq!(
+            structural_eq(&some("a".into()), &some("a".into())),
+            Some(true)
+        );
+        assert_eq!(structural_eq(&some(1.into()), &some(2.into())), Some(false));
+        assert_eq!(
+            structural_eq(&some(1.into()), &Variant::None.into()),
+            Some(false)
+        );
+        assert_eq!(
+            structural_eq(
+                &Variant::Ok(1.into()).into(),
+                &Variant::Err(1.into()).into()
+            ),
+            Some(false)
+        );
+        assert_eq!(
+            structural_eq(&some(1.into()), &Variant::Ok(1.into()).into()),
+            None
+        );
+        assert_eq!(structural_eq(&some(1.into()), &some(true.into())), None);
+
+        // semaphores compare by identity, functions can't be compared
+        #[cfg(feature = "concurrency")]
+        {
+            let sem = Semaphore::new(1);
+            let sem_val = Value::Semaphore(sem.clone());
+            assert_eq!(structural_eq(&sem_val, &Value::Semaphore(sem)), Some(true));
+            assert_eq!(
+                structural_eq(&sem_val, &Value::Semaphore(Semaphore::new(1))),
+                Some(false)
+            );
+        }
+
+        let f = Value::from(Closure {
+            fn_type: FnType::User,
+            sym: "f".into(),
+            prms: vec![],
+            addr: 0,
+            env: Default::default(),
+        });
+        assert_eq!(structural_eq(&f, &f), None);
+
+        // structs compare their fields, and are only equal to structs of the same type
+        let ty = Rc::new(StructType::new("P".into(), vec!["x".into(), "y".into()]));
+        let point = |x: i64, y: i64| {
+            Value::from(Struct {
+                ty: ty.clone(),
+                fields: vec![x.into(), y.into()],
+            })
+        };
+        assert_eq!(structural_eq(&point(1, 2), &point(1, 2)), Some(true));
+        assert_eq!(structural_eq(&point(1, 2), &point(1, 3)), Some(false));
+
+        let other = Rc::new(StructType::new("P".into(), vec!["x".into(), "y".into()]));
+        let other_point = Value::from(Struct {
+            ty: other,
+            fields: vec![1.into(), 2.into()],
+        });
+        assert_eq!(structural_eq(&point(1, 2), &other_point), None);
+        assert_eq!(point(1, 2).to_string(), "P { x: 1, y: 2 }");
+
+        // values of enums compare their variants, then what they hold
+        let shape = |variant: &str, payload: Option<Value>| {
+            Value::from(Enum {
+                ty: "Shape".into(),
+                variant: variant.into(),
+                payload,
+            })
+        };
+        let circle = shape("Circle", Some(1.into()));
+        assert_eq!(
+            structural_eq(&circle, &shape("Circle", Some(1.into()))),
+            Some(true)
+        );
+        assert_eq!(
+            structural_eq(&circle, &shape("Circle", Some(2.into()))),
+            Some(false)
+        );
+        assert_eq!(structural_eq(&circle, &shape("Empty", None)), Some(false));
+        assert_eq!(
+            structural_eq(&shape("Empty", None), &shape("Empty", None)),
+            Some(true)
+        );
+        assert_eq!(structural_eq(&circle, &some(1.into())), None);
+        assert_eq!(circle.to_string(), "Circle(1)");
+
+        // arrays compare their lengths, then their elements in order
+        let arr =
+            |elems: Vec<i64>| Value::from(elems.into_iter().map(Value::from).collect::<Vec<_>>());
+        assert_eq!(
+            structural_eq(&arr(vec![1, 2]), &arr(vec![1, 2])),
+            Some(true)
+        );
+        assert_eq!(
+            structural_eq(&arr(vec![1, 2]), &arr(vec![2, 1])),
+            Some(false)
+        );
+        assert_eq!(structural_eq(&arr(vec![1]), &arr(vec![1, 2])), Some(false));
+        assert_eq!(structural_eq(&arr(vec![]), &arr(vec![])), Some(true));
+        assert_eq!(arr(vec![1, 2]).to_string(), "[1, 2]");
+    }
+
+    #[test]
+    fn test_option() {
+        assert_eq!(Value::from(Some(1)), Value::Int(1));
+        assert_eq!(Value::from(None::<i64>), Value::Unit);
+
+        assert_eq!(Option::<i64>::try_from(Value::Int(1)).unwrap(), Some(1));
+        assert_eq!(Option::<i64>::try_from(Value::Unit).unwrap(), None);
+
+        let err = Option::<i64>::try_from(Value::Bool(true)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch, expected Int or Unit, found true"
+        );
     }
 }
@@ -0,0 +1,44 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use crate::W;
+
+/// The state of a wait group, the number of outstanding tasks.
+#[derive(Debug, Default)]
+pub struct WaitGroupState {
+    pub count: u64,
+}
+
+pub type WaitGroup = W<Arc<Mutex<WaitGroupState>>>;
+
+impl WaitGroup {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(WaitGroupState::default())))
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for WaitGroup {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for WaitGroup {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl Debug for WaitGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WaitGroup({})", self.lock().unwrap().count)
+    }
+}
@@ -28,6 +28,12 @@ pub enum Token {
     #[token(".")]
     Dot,
 
+    #[token("..")]
+    DotDot,
+
+    #[token("..=")]
+    DotDotEq,
+
     #[token(",")]
     Comma,
 
@@ -79,6 +85,12 @@ pub enum Token {
     #[token(">")]
     Gt,
 
+    #[token("<=")]
+    Le,
+
+    #[token(">=")]
+    Ge,
+
     #[token("-")]
     Minus,
 
@@ -124,18 +136,31 @@ pub enum Token {
     #[token("->")]
     FnDeclReturn,
 
+    #[token("=>")]
+    FatArrow,
+
     #[token("return")]
     Return,
 
     #[regex(r#"[a-zA-Z_][a-zA-Z0-9_]*"#, |lex| lex.slice().to_owned())]
     Ident(String),
 
+    // Label of a loop e.g 'outer, without the quote
+    #[regex(r#"'[a-zA-Z_][a-zA-Z0-9_]*"#, |lex| lex.slice()[1..].to_owned())]
+    Label(String),
+
     #[regex(r#"//[^\n]*"#, comment_callback)]
     Comment,
 
     #[token("loop")]
     Loop,
 
+    #[token("for")]
+    For,
+
+    #[token("in")]
+    In,
+
     #[token("break")]
     Break,
 
@@ -154,6 +179,36 @@ pub enum Token {
     #[token("yield")]
     Yield,
 
+    #[token("select")]
+    Select,
+
+    #[token("scope")]
+    Scope,
+
+    #[token("lock")]
+    Lock,
+
+    #[token("read_lock")]
+    ReadLock,
+
+    #[token("match")]
+    Match,
+
+    #[token("try")]
+    Try,
+
+    #[token("catch")]
+    Catch,
+
+    #[token("struct")]
+    Struct,
+
+    #[token("impl")]
+    Impl,
+
+    #[token("enum")]
+    Enum,
+
     #[token("false", |_| false)]
     #[token("true", |_| true)]
     Bool(bool),
@@ -185,10 +240,13 @@ impl Token {
     pub fn repr(&self) -> String {
         match self {
             Self::Ident(id) => id.to_string(),
+            Self::Label(label) => format!("'{}", label),
             Self::String(str) => str.to_string(),
             Self::Semi => ";".to_string(),
             Self::Colon => ":".to_string(),
             Self::Dot => ".".to_string(),
+            Self::DotDot => "..".to_string(),
+            Self::DotDotEq => "..=".to_string(),
             Self::Comma => ",".to_string(),
             Self::OpenParen => "(".to_string(),
             Self::CloseParen => ")".to_string(),
@@ -205,6 +263,8 @@ impl Token {
             Self::Bang => "!".to_string(),
             Self::Lt => "<".to_string(),
             Self::Gt => ">".to_string(),
+            Self::Le => "<=".to_string(),
+            Self::Ge => ">=".to_string(),
             Self::Minus => "-".to_string(),
             Self::And => "&".to_string(),
             Self::Or => "|".to_string(),
@@ -223,30 +283,51 @@ impl Token {
             Self::LogAnd => "&&".to_string(),
             Self::LogOr => "||".to_string(),
             Self::Loop => "loop".to_string(),
+            Self::For => "for".to_string(),
+            Self::In => "in".to_string(),
             Self::Break => "break".to_string(),
             Self::Comment => "//".to_string(),
             Self::Newline => "\n".to_string(),
             Self::Fn => "fn".to_string(),
             Self::Return => "return".to_string(),
             Self::FnDeclReturn => "->".to_string(),
+            Self::FatArrow => "=>".to_string(),
             Self::Spawn => "spawn".to_string(),
             Self::Join => "join".to_string(),
             Self::Wait => "wait".to_string(),
             Self::Post => "post".to_string(),
             Self::Yield => "yield".to_string(),
+            Self::Select => "select".to_string(),
+            Self::Scope => "scope".to_string(),
+            Self::Lock => "lock".to_string(),
+            Self::ReadLock => "read_lock".to_string(),
+            Self::Match => "match".to_string(),
+            Self::Try => "try".to_string(),
+            Self::Catch => "catch".to_string(),
+            Self::Struct => "struct".to_string(),
+            Self::Impl => "impl".to_string(),
+            Self::Enum => "enum".to_string(),
         }
     }
 }
 
+/// Lex the input, skipping a shebang line at its start (e.g. `#!/usr/bin/env rustscript`)
+/// so that scripts can be run as executables. Spans stay relative to the whole input.
 pub fn lex(input: &str) -> Lexer<'_, Token> {
-    Token::lexer(input)
+    let mut lexer = Token::lexer(input);
+
+    if input.starts_with("#!") {
+        // Stop before the newline, which is still counted as a line
+        lexer.bump(input.find('\n').unwrap_or(input.len()));
+    }
+
+    lexer
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use std::f64;
-    use std::i64;
 
     #[test]
     fn test_bool() {
@@ -430,6 +511,7 @@ mod test {
             Token::Ident("mut".to_string()),
             Token::Ident("continue".to_string()),
             Token::Break,
+            Token::Struct,
         ];
 
         for e in expected {
@@ -535,14 +617,16 @@ mod test {
 
     #[test]
     fn test_lex_comp_ops() {
-        // ==, <, >, &&, ||
-        let t = "== = < > && ||";
+        // ==, <, >, <=, >=, &&, ||
+        let t = "== = < > <= >= && ||";
         let mut lexer = Token::lexer(t);
         let exp: Vec<Token> = vec![
             Token::LogEq,
             Token::Eq,
             Token::Lt,
             Token::Gt,
+            Token::Le,
+            Token::Ge,
             Token::LogAnd,
             Token::LogOr,
         ];
@@ -594,6 +678,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_lex_labels() {
+        let t = "'outer: loop { break 'outer x; }";
+        let exp = vec![
+            Token::Label("outer".to_string()),
+            Token::Colon,
+            Token::Loop,
+            Token::OpenBrace,
+            Token::Break,
+            Token::Label("outer".to_string()),
+            Token::Ident("x".to_string()),
+            Token::Semi,
+            Token::CloseBrace,
+        ];
+
+        let toks: Vec<Token> = Token::lexer(t).map(|tok| tok.unwrap()).collect();
+        assert_eq!(toks, exp);
+    }
+
     #[test]
     fn test_lex_comments() {
         let t = r"
@@ -660,4 +763,201 @@ mod test {
         assert_eq!(lexer.next().unwrap().unwrap(), Token::Post);
         assert_eq!(lexer.next().unwrap().unwrap(), Token::Yield);
     }
+
+    #[test]
+    fn test_lex_select() {
+        let t = r"
+        select { a => { } }
+        ";
+        let mut lexer = Token::lexer(t);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Select);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::OpenBrace);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("a".to_string())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::FatArrow);
+    }
+
+    #[test]
+    fn test_lex_scope() {
+        let t = r"
+        scope { spawn { } }
+        ";
+        let mut lexer = Token::lexer(t);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Scope);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::OpenBrace);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Spawn);
+    }
+
+    #[test]
+    fn test_lex_lock() {
+        let t = r"
+        lock(m) { } read_lock(rw) { }
+        ";
+        let mut lexer = Token::lexer(t);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Lock);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::OpenParen);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("m".to_string())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::CloseParen);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::OpenBrace);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::CloseBrace);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::ReadLock);
+    }
+
+    #[test]
+    fn test_lex_match() {
+        let t = r"
+        match x { None => { } }
+        ";
+        let mut lexer = Token::lexer(t);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Match);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("x".to_string())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::OpenBrace);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("None".to_string())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::FatArrow);
+    }
+
+    #[test]
+    fn test_shebang() {
+        let input = "#!/usr/bin/env rustscript\nlet x = 2;";
+        let mut lexer = lex(input);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Let);
+        assert_eq!(lexer.span(), 26..29);
+        assert_eq!(lexer.extras.0, 1);
+
+        // Only at the very start
+        let mut lexer = lex(" #!x");
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Pound);
+
+        assert_eq!(lex("#!rustscript").next(), None);
+    }
+
+    #[test]
+    fn test_lex_try_catch() {
+        let t = r"
+        try { } catch e { }
+        ";
+        let mut lexer = Token::lexer(t);
+
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Try);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::OpenBrace);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::CloseBrace);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Catch);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("e".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lex_structs() {
+        let t = "struct P { x: int } impl P { } P { x: 1, ..p }.x";
+        let exp = vec![
+            Token::Struct,
+            Token::Ident("P".to_string()),
+            Token::OpenBrace,
+            Token::Ident("x".to_string()),
+            Token::Colon,
+            Token::Ident("int".to_string()),
+            Token::CloseBrace,
+            Token::Impl,
+            Token::Ident("P".to_string()),
+            Token::OpenBrace,
+            Token::CloseBrace,
+            Token::Ident("P".to_string()),
+            Token::OpenBrace,
+            Token::Ident("x".to_string()),
+            Token::Colon,
+            Token::Integer(1),
+            Token::Comma,
+            Token::DotDot,
+            Token::Ident("p".to_string()),
+            Token::CloseBrace,
+            Token::Dot,
+            Token::Ident("x".to_string()),
+        ];
+
+        let toks: Vec<Token> = Token::lexer(t).map(|tok| tok.unwrap()).collect();
+        assert_eq!(toks, exp);
+    }
+
+    #[test]
+    fn test_lex_enums() {
+        let t = "enum Shape { Circle(float), Empty }";
+        let exp = vec![
+            Token::Enum,
+            Token::Ident("Shape".to_string()),
+            Token::OpenBrace,
+            Token::Ident("Circle".to_string()),
+            Token::OpenParen,
+            Token::Ident("float".to_string()),
+            Token::CloseParen,
+            Token::Comma,
+            Token::Ident("Empty".to_string()),
+            Token::CloseBrace,
+        ];
+
+        let toks: Vec<Token> = Token::lexer(t).map(|tok| tok.unwrap()).collect();
+        assert_eq!(toks, exp);
+    }
+
+    #[test]
+    fn test_lex_range_pattern() {
+        let t = "n @ -1..=9 | 0..n";
+        let exp = vec![
+            Token::Ident("n".to_string()),
+            Token::At,
+            Token::Minus,
+            Token::Integer(1),
+            Token::DotDotEq,
+            Token::Integer(9),
+            Token::Or,
+            Token::Integer(0),
+            Token::DotDot,
+            Token::Ident("n".to_string()),
+        ];
+
+        let toks: Vec<Token> = Token::lexer(t).map(|tok| tok.unwrap()).collect();
+        assert_eq!(toks, exp);
+    }
+
+    #[test]
+    fn test_lex_comprehension() {
+        let t = "[x for x in 0..10 if x % 2 == 0]";
+        let exp = vec![
+            Token::OpenBracket,
+            Token::Ident("x".to_string()),
+            Token::For,
+            Token::Ident("x".to_string()),
+            Token::In,
+            Token::Integer(0),
+            Token::DotDot,
+            Token::Integer(10),
+            Token::If,
+            Token::Ident("x".to_string()),
+            Token::Percent,
+            Token::Integer(2),
+            Token::LogEq,
+            Token::Integer(0),
+            Token::CloseBracket,
+        ];
+
+        let toks: Vec<Token> = Token::lexer(t).map(|tok| tok.unwrap()).collect();
+        assert_eq!(toks, exp);
+    }
 }
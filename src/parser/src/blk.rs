@@ -1,5 +1,7 @@
+use crate::BlockSeq;
 use crate::Decl;
 use crate::Expr;
+use crate::LockData;
 use crate::ParseError;
 use crate::Parser;
 use lexer::Token;
@@ -8,7 +10,13 @@ impl<'inp> Parser<'inp> {
     // Invariant: open brace has been consumed and peek is at the first token inside the block
     pub(crate) fn parse_blk(&mut self) -> Result<Decl, ParseError> {
         // BlockSeq - vec decls, last expr
+        let prev_is_top_level = self.is_top_level;
+        let prev_no_struct_lit = self.no_struct_lit;
+        self.is_top_level = false;
+        self.no_struct_lit = false;
         let blk = self.parse_seq()?;
+        self.is_top_level = prev_is_top_level;
+        self.no_struct_lit = prev_no_struct_lit;
         let res = Decl::ExprStmt(Expr::BlockExpr(blk));
         let err = format!("Expected '{}' to close block", Token::CloseBrace);
         self.consume_token_type(Token::CloseBrace, &err)?;
@@ -18,6 +26,66 @@ impl<'inp> Parser<'inp> {
 
         Ok(res)
     }
+
+    // spawn { .. }: the block runs in the new thread, so break, return and yield with a value can't leave it
+    // Invariant: prev_tok is the open brace of the block
+    pub(crate) fn parse_spawn_blk(&mut self) -> Result<Decl, ParseError> {
+        let blk = self.parse_enclosed_blk()?;
+        Ok(Decl::ExprStmt(Expr::SpawnBlockExpr(blk)))
+    }
+
+    // scope { .. }: the threads spawned in the block are joined when it ends, so break, return and yield
+    // with a value can't leave it early
+    // Invariant: prev_tok is scope
+    pub(crate) fn parse_scope_blk(&mut self) -> Result<Decl, ParseError> {
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for scope block", Token::OpenBrace),
+        )?;
+        let blk = self.parse_enclosed_blk()?;
+        Ok(Decl::ExprStmt(Expr::ScopeExpr(blk)))
+    }
+
+    // lock(m) { .. } or read_lock(rw) { .. }: the lock is released however the block is left, so break, return
+    // and ? may leave it
+    // Invariant: prev_tok is lock or read_lock
+    pub(crate) fn parse_lock_blk(&mut self, shared: bool) -> Result<Decl, ParseError> {
+        let kw = self.expect_prev_tok()?.clone();
+        self.consume_token_type(
+            Token::OpenParen,
+            &format!("Expected {} after {}", Token::OpenParen, kw),
+        )?;
+        self.advance();
+        let prev_no_struct_lit = self.no_struct_lit;
+        self.no_struct_lit = false;
+        let lock = self.parse_expr(0);
+        self.no_struct_lit = prev_no_struct_lit;
+        let lock = lock?.to_expr()?;
+        self.consume_token_type(
+            Token::CloseParen,
+            &format!("Expected {} to close lock", Token::CloseParen),
+        )?;
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for lock block", Token::OpenBrace),
+        )?;
+        let blk = self.parse_blk()?.to_block()?;
+        let lock = LockData { lock, shared, blk };
+        Ok(Decl::ExprStmt(Expr::LockExpr(Box::new(lock))))
+    }
+
+    // A block that break, return and yield with a value can't leave
+    // Invariant: prev_tok is the open brace of the block
+    fn parse_enclosed_blk(&mut self) -> Result<BlockSeq, ParseError> {
+        let prev_loops = std::mem::take(&mut self.loops);
+        let prev_is_fn = self.is_fn;
+        self.is_fn = false;
+        let blk = self.parse_blk();
+        self.loops = prev_loops;
+        self.is_fn = prev_is_fn;
+
+        blk?.to_block()
+    }
 }
 
 #[cfg(test)]
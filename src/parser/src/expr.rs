@@ -15,7 +15,11 @@ impl<'inp> Parser<'inp> {
         let mut lhs = match prev_tok {
             Token::OpenParen => {
                 self.advance();
+                // struct expressions are allowed in parens, even in a condition
+                let prev_no_struct_lit = self.no_struct_lit;
+                self.no_struct_lit = false;
                 let lhs = self.parse_expr(0)?;
+                self.no_struct_lit = prev_no_struct_lit;
                 self.consume_token_type(Token::CloseParen, "Expected closing parenthesis")?;
                 Ok(lhs)
             }
@@ -46,6 +50,17 @@ impl<'inp> Parser<'inp> {
             }
             Token::OpenBrace => self.parse_blk(),
             Token::If => self.parse_if_else(min_bp),
+            Token::Select => self.parse_select(),
+            Token::Scope => self.parse_scope_blk(),
+            Token::Lock => self.parse_lock_blk(false),
+            Token::ReadLock => self.parse_lock_blk(true),
+            Token::Match => self.parse_match(),
+            Token::Try => self.parse_try_catch(),
+            Token::Loop => self.parse_loop(None),
+            Token::Label(label) => self.parse_labeled(label.to_owned()),
+            Token::OpenBracket => self.parse_array(),
+            Token::Spawn => self.parse_spawn(),
+            Token::Join => self.parse_join(),
             _ => Err(ParseError::new(&format!(
                 "Unexpected token - not an expression: '{}'",
                 prev_tok
@@ -62,6 +77,13 @@ impl<'inp> Parser<'inp> {
                 || self.is_peek_token_type(Token::OpenBrace)
                 // to deal with comma in func call e.g print(2,3);
                 || self.is_peek_token_type(Token::Comma)
+                // to deal with the end of elements and the parts of comprehensions e.g [x for x in 0..n if x > 2]
+                || self.is_peek_token_type(Token::CloseBracket)
+                || self.is_peek_token_type(Token::For)
+                || self.is_peek_token_type(Token::DotDot)
+                || (self.in_comprehension && self.is_peek_token_type(Token::If))
+                // to deal with the end of the guard of a match arm e.g Some(x) if x > 0 => { .. }
+                || self.is_peek_token_type(Token::FatArrow)
             {
                 break;
             }
@@ -73,6 +95,13 @@ impl<'inp> Parser<'inp> {
                 .clone()
                 .expect("Lexer should not fail");
 
+            // postfix ? binds tighter than any infix operator
+            if tok.eq(&Token::Question) {
+                self.advance();
+                lhs = ExprStmt(Expr::TryExpr(Box::new(lhs.to_expr()?)));
+                continue;
+            }
+
             // dbg!("Prev_tok before from_token:", &self.prev_tok);
             let binop = BinOpType::from_token(&tok);
 
@@ -113,6 +142,41 @@ impl<'inp> Parser<'inp> {
 
         Ok(lhs)
     }
+
+    // spawn f(..), spawn(f, ..) or spawn { .. }, which binds as tightly as a unary operator
+    // so that e.g. spawn f() can be an argument
+    // Invariant: prev_tok is spawn
+    fn parse_spawn(&mut self) -> Result<Decl, ParseError> {
+        if self.is_peek_token_type(Token::OpenParen) {
+            return self.parse_spawn_call();
+        }
+
+        self.advance();
+        if self.expect_prev_tok()?.eq(&Token::OpenBrace) {
+            return self.parse_spawn_blk();
+        }
+
+        let ((), r_bp) = Parser::get_prefix_bp(&UnOpType::Not);
+        let fn_call = self.parse_expr(r_bp)?.to_expr()?;
+        if let Expr::FnCallExpr(fn_data) = fn_call {
+            Ok(ExprStmt(Expr::SpawnExpr(fn_data)))
+        } else {
+            Err(ParseError::new("spawn expected function call or block"))
+        }
+    }
+
+    // join t or join(t), which binds as tightly as a unary operator so that e.g. join(t) + 1 adds to the result
+    // Invariant: prev_tok is join
+    fn parse_join(&mut self) -> Result<Decl, ParseError> {
+        let ((), r_bp) = Parser::get_prefix_bp(&UnOpType::Not);
+        self.advance();
+        let join_id = self.parse_expr(r_bp)?.to_expr()?;
+        if let Expr::Symbol(tid) = join_id {
+            Ok(ExprStmt(Expr::JoinExpr(tid)))
+        } else {
+            Err(ParseError::new("join expected variable for thread to join"))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -134,6 +198,7 @@ mod tests {
         test_parse("2-3+4/5*6-8+9; 2+2;", "((((2-3)+((4/5)*6))-8)+9);(2+2);");
 
         test_parse("let x = 2+3*4-5; 300", "let x = ((2+(3*4))-5);300");
+        test_parse("7%3*2+1", "(((7%3)*2)+1)");
     }
 
     #[test]
@@ -197,9 +262,12 @@ mod tests {
 
     #[test]
     fn test_parse_comp_ops() {
-        // ==, <, >
+        // ==, <, >, <=, >=
         test_parse("2 > 3", "(2>3)");
+        test_parse("x <= y + 1", "(x<=(y+1))");
+        test_parse("x >= y && y >= z", "((x>=y)&&(y>=z))");
         test_parse_err("2 > 3 > 4", "Comparison operators can't be chained", true);
+        test_parse_err("2 <= 3 >= 4", "Comparison operators can't be chained", true);
         test_parse_err(
             "false == 3 > 5",
             "Comparison operators can't be chained",
@@ -12,17 +12,19 @@ use lexer::Token;
 // return stmt is only allowed inside a function
 impl<'inp> Parser<'inp> {
     pub(crate) fn parse_fn_decl(&mut self) -> Result<Decl, ParseError> {
-        let prev_is_loop = self.is_loop;
+        // turn it off because break is not automatically allowed in fn
+        let prev_loops = std::mem::take(&mut self.loops);
         let prev_is_fn = self.is_fn;
+        let prev_is_generator = self.is_generator;
 
-        // turn it off because break is not automatically allowed in fn
-        self.is_loop = false;
         self.is_fn = true;
+        self.is_generator = false;
         let res = self.parse_fn_decl_inner();
 
         // restore
-        self.is_loop = prev_is_loop;
+        self.loops = prev_loops;
         self.is_fn = prev_is_fn;
+        self.is_generator = prev_is_generator;
         res
     }
 
@@ -116,10 +118,38 @@ impl<'inp> Parser<'inp> {
             name: fn_name,
             ret_type: ret_ty,
             body,
+            is_test: false,
+            is_generator: self.is_generator,
         };
 
         Ok(Decl::FnDeclStmt(fn_decl))
     }
+
+    // #[test] fn name() { ... } - prev_tok is #
+    pub(crate) fn parse_test_fn_decl(&mut self) -> Result<Decl, ParseError> {
+        self.consume_token_type(Token::OpenBracket, "Expected '[' after '#' for attribute")?;
+
+        if !self.is_peek_token_type(Token::Ident("test".to_string())) {
+            return Err(ParseError::new("Expected attribute 'test'"));
+        }
+        self.advance();
+
+        self.consume_token_type(Token::CloseBracket, "Expected ']' to close attribute")?;
+        self.consume_token_type(Token::Fn, "Expected function after #[test]")?;
+
+        let mut decl = self.parse_fn_decl()?;
+        if let Decl::FnDeclStmt(ref mut fn_decl) = decl {
+            // the test runner calls test functions without arguments
+            if !fn_decl.params.is_empty() {
+                let e = format!("Test function {} cannot have parameters", fn_decl.name);
+                return Err(ParseError::new(&e));
+            }
+
+            fn_decl.is_test = true;
+        }
+
+        Ok(decl)
+    }
 }
 
 #[cfg(test)]
@@ -443,4 +473,52 @@ mod tests {
             "fn adder (x:int) -> fn(int) -> bool { fn f (y:int) -> bool { ((x+y)>0) };adder };",
         );
     }
+
+    #[test]
+    fn test_parse_generator() {
+        let t = r"
+        fn gen() -> Generator<int> {
+            yield 1;
+            yield 2;
+        }
+        ";
+        test_parse(t, "fn gen () -> Generator<int> { yield 1;yield 2; };");
+
+        // yield with a value makes the fn a generator, but not the fns around it
+        let lex = Token::lexer("fn f() { fn g() { yield 1; } g }");
+        let res = Parser::new(lex).parse().expect("Should parse");
+        let crate::Decl::FnDeclStmt(f) = &res.decls[0] else {
+            panic!("Expected fn decl");
+        };
+        assert!(!f.is_generator);
+        let crate::Decl::FnDeclStmt(g) = &f.body.decls[0] else {
+            panic!("Expected fn decl");
+        };
+        assert!(g.is_generator);
+
+        test_parse_err("yield 1;", "yield with a value outside of fn", true);
+    }
+
+    #[test]
+    fn test_parse_test_fn_decl() {
+        let t = r"
+        #[test]
+        fn adds() {
+            assert_eq(1 + 1, 2);
+        }
+        ";
+        test_parse(t, "#[test] fn adds () { assert_eq((1+1),2); };");
+
+        test_parse_err(
+            "#[test] fn f(x: int) {}",
+            "Test function f cannot have parameters",
+            true,
+        );
+        test_parse_err("#[bench] fn f() {}", "Expected attribute 'test'", true);
+        test_parse_err(
+            "#[test] let x = 2;",
+            "Expected function after #[test]",
+            true,
+        );
+    }
 }
@@ -1,16 +1,16 @@
 use crate::AssignStmtData;
 use crate::Decl;
 use crate::Expr;
+use crate::FieldAssignData;
 use crate::FnCallData;
+use crate::MethodCallData;
 use crate::ParseError;
 use crate::Parser;
 use lexer::Token;
 
 impl<'inp> Parser<'inp> {
     pub fn parse_ident(&mut self, ident: String, min_bp: u8) -> Result<Decl, ParseError> {
-        let sym = Expr::Symbol(ident.to_string());
-
-        // Handle assignment, fn call
+        // Handle assignment, fn call, struct expression, field access and method call
         if let Some(tok) = self.lexer.peek() {
             let tok = tok.as_ref().expect("Lexer should not fail");
 
@@ -27,47 +27,219 @@ impl<'inp> Parser<'inp> {
                 return Ok(Decl::AssignStmt(assign));
             } else if tok.eq(&Token::OpenParen) {
                 // Fn call
-                self.consume_token_type(Token::OpenParen, "Expected '('")?;
-                // dbg!("tok after:", &self.lexer.peek());
+                let (args, names) = self.parse_call_args()?;
 
-                let mut args: Vec<Expr> = vec![];
+                let data = FnCallData {
+                    name: ident,
+                    args,
+                    names,
+                };
 
-                while let Some(tok) = self.lexer.peek() {
-                    let tok = tok.clone();
-                    // stop at )
-                    if tok.clone().unwrap().eq(&Token::CloseParen) {
-                        break;
-                    }
+                let fn_call = Expr::FnCallExpr(data);
 
-                    self.advance(); // put next tok into prev_tok so parse_expr can use it
+                return self.parse_postfix(fn_call, min_bp);
+            } else if tok.eq(&Token::OpenBrace)
+                && !self.no_struct_lit
+                && Parser::is_struct_name(&ident)
+            {
+                // Struct expression Point { x: 1 }
+                let struct_expr = self.parse_struct_expr(ident)?.to_expr()?;
+                return self.parse_postfix(struct_expr, min_bp);
+            }
+        }
 
-                    // let expr = self.parse_expr(min_bp)?.to_expr()?;
-                    // need to reset min_bp when parsing each expr, shouldnt depend on prev
-                    let expr = self.parse_expr(0)?.to_expr()?;
+        self.parse_postfix(Expr::Symbol(ident), min_bp)
+    }
 
-                    // dbg!("Peek after parsing:", &self.lexer.peek(), &expr);
+    /// Parse field accesses, method calls, indexing and slicing after an expression e.g p.x, p.norm(), p.a.b, a[i], a[1..3]
+    /// A chain of fields on a variable followed by '=' is a field assignment e.g p.a.b = 2
+    fn parse_postfix(&mut self, mut expr: Expr, min_bp: u8) -> Result<Decl, ParseError> {
+        let mut fields: Vec<String> = vec![];
 
-                    args.push(expr);
+        loop {
+            if self.consume_opt_token_type(Token::OpenBracket) {
+                expr = self.parse_index(expr)?;
+                fields.clear();
+                continue;
+            }
+
+            if !self.consume_opt_token_type(Token::Dot) {
+                break;
+            }
 
-                    if !self.lexer.peek().eq(&Some(&Ok(Token::CloseParen))) {
-                        self.consume_token_type(
-                            Token::Comma,
-                            "Expected ',' to separate function arguments",
-                        )?;
-                    }
+            crate::expect_token_body!(self.lexer.peek(), Ident, "field or method name")?;
+            let name = Parser::string_from_ident(self.lexer.peek());
+            self.advance();
+
+            if self.is_peek_token_type(Token::OpenParen) {
+                // Method call x.method(..)
+                let (args, names) = self.parse_call_args()?;
+                if !names.is_empty() {
+                    return Err(ParseError::new(
+                        "Named arguments can only be passed to functions, not methods",
+                    ));
                 }
 
-                self.consume_token_type(Token::CloseParen, "Expected ')'")?;
+                let data = MethodCallData {
+                    recv: Box::new(expr),
+                    method: name,
+                    args,
+                };
+
+                expr = Expr::MethodCallExpr(data);
+                fields.clear();
+            } else {
+                expr = Expr::FieldExpr(Box::new(expr), name.clone());
+                fields.push(name);
+            }
+        }
 
-                let data = FnCallData { name: ident, args };
+        // p.a.b = 2: only when the whole chain is fields on a variable
+        if !fields.is_empty() && self.is_peek_token_type(Token::Eq) {
+            let mut base = &expr;
+            while let Expr::FieldExpr(inner, _) = base {
+                base = inner;
+            }
 
-                let fn_call = Expr::FnCallExpr(data);
+            let Expr::Symbol(ident) = base else {
+                return Err(ParseError::new(
+                    "Only fields of a variable can be assigned to",
+                ));
+            };
+
+            let ident = ident.to_string();
+            self.consume_token_type(Token::Eq, "Expected '='")?;
+            self.advance();
+            let expr = self.parse_expr(min_bp)?.to_expr()?;
+
+            let assign = FieldAssignData {
+                ident,
+                fields,
+                expr,
+            };
+
+            return Ok(Decl::FieldAssignStmt(assign));
+        }
+
+        Ok(Decl::ExprStmt(expr))
+    }
+
+    /// Parse the index or the bounds of a slice of an expression e.g a[i], a[1..3], s[..n], s[n..]
+    /// Expects prev_tok to be at '[' and ends with prev_tok at ']'
+    fn parse_index(&mut self, expr: Expr) -> Result<Expr, ParseError> {
+        // struct expressions are allowed in brackets, and if is not a comprehension condition
+        let prev_no_struct_lit = self.no_struct_lit;
+        let prev_in_comprehension = self.in_comprehension;
+        self.no_struct_lit = false;
+        self.in_comprehension = false;
+
+        let res = self.parse_index_bounds(expr);
+
+        self.no_struct_lit = prev_no_struct_lit;
+        self.in_comprehension = prev_in_comprehension;
+        res
+    }
+
+    fn parse_index_bounds(&mut self, expr: Expr) -> Result<Expr, ParseError> {
+        let mut start = None;
+        if !self.is_peek_token_type(Token::DotDot) {
+            self.advance();
+            let idx = self.parse_expr(0)?.to_expr()?;
+
+            if self.consume_opt_token_type(Token::CloseBracket) {
+                return Ok(Expr::IndexExpr(Box::new(expr), Box::new(idx)));
+            }
+            start.replace(Box::new(idx));
+        }
+
+        self.consume_token_type(Token::DotDot, "Expected ']' or '..' after index")?;
+
+        let mut end = None;
+        if !self.is_peek_token_type(Token::CloseBracket) {
+            self.advance();
+            end.replace(Box::new(self.parse_expr(0)?.to_expr()?));
+        }
+
+        self.consume_token_type(Token::CloseBracket, "Expected ']' to close slice")?;
+
+        Ok(Expr::SliceExpr(Box::new(expr), start, end))
+    }
+
+    /// Parse comma separated call arguments, returning the args and the names of the trailing args passed by name.
+    /// Expects peek to be at '(' and ends with peek after ')'
+    pub(crate) fn parse_call_args(&mut self) -> Result<(Vec<Expr>, Vec<String>), ParseError> {
+        self.consume_token_type(Token::OpenParen, "Expected '('")?;
 
-                return Ok(Decl::ExprStmt(fn_call));
+        let mut args: Vec<Expr> = vec![];
+        let mut names: Vec<String> = vec![];
+
+        while let Some(tok) = self.lexer.peek() {
+            let tok = tok.clone();
+            // stop at )
+            if tok.clone().unwrap().eq(&Token::CloseParen) {
+                break;
+            }
+
+            self.advance(); // put next tok into prev_tok so parse_expr can use it
+
+            // Named arg e.g x: 2
+            let colon_next = self.is_peek_token_type(Token::Colon);
+            let named = match &self.prev_tok {
+                Some(Token::Ident(name)) if colon_next => Some(name.to_string()),
+                _ => None,
+            };
+
+            if let Some(name) = named {
+                self.consume_token_type(Token::Colon, "Expected ':'")?;
+                self.advance();
+                names.push(name);
+            } else if !names.is_empty() {
+                return Err(ParseError::new(
+                    "Positional arguments must come before named arguments",
+                ));
+            }
+
+            // need to reset min_bp when parsing each expr, shouldnt depend on prev
+            let expr = self.parse_expr(0)?.to_expr()?;
+
+            args.push(expr);
+
+            if !self.lexer.peek().eq(&Some(&Ok(Token::CloseParen))) {
+                self.consume_token_type(
+                    Token::Comma,
+                    "Expected ',' to separate function arguments",
+                )?;
             }
         }
 
-        Ok(Decl::ExprStmt(sym))
+        self.consume_token_type(Token::CloseParen, "Expected ')'")?;
+
+        Ok((args, names))
+    }
+
+    // spawn(f, ..) calls f with the rest of the args in the new thread, the same as spawn f(..),
+    // and spawn(f(..)) is the call in parentheses
+    // Invariant: prev_tok is spawn and peek is the open paren
+    pub(crate) fn parse_spawn_call(&mut self) -> Result<Decl, ParseError> {
+        let (mut args, names) = self.parse_call_args()?;
+
+        let fn_call = match args.first() {
+            Some(Expr::FnCallExpr(_)) if args.len() == 1 => match args.remove(0) {
+                Expr::FnCallExpr(fn_call) => fn_call,
+                _ => unreachable!("Checked to be a function call"),
+            },
+            Some(Expr::Symbol(_)) if names.len() < args.len() => match args.remove(0) {
+                Expr::Symbol(name) => FnCallData { name, args, names },
+                _ => unreachable!("Checked to be a symbol"),
+            },
+            _ => {
+                return Err(ParseError::new(
+                    "spawn expected a function to call with its arguments",
+                ))
+            }
+        };
+
+        Ok(Decl::ExprStmt(Expr::SpawnExpr(fn_call)))
     }
 }
 
@@ -145,4 +317,57 @@ mod tests {
         test_parse_err("print(}", "Unexpected token - not an expression", true);
         test_parse_err("print(,)", "Unexpected token - not an expression", true);
     }
+
+    #[test]
+    fn test_parse_named_args() {
+        test_parse("draw(x: 1, y: 2)", "draw(x:1,y:2)");
+        test_parse("draw(1, y: 2 + 3);", "draw(1,y:(2+3));");
+        test_parse("spawn draw(y: f(x: 1))", "spawn draw(y:f(x:1))");
+
+        test_parse_err(
+            "draw(x: 1, 2)",
+            "Positional arguments must come before named arguments",
+            true,
+        );
+        test_parse_err(
+            "h.id(x: 1)",
+            "Named arguments can only be passed to functions, not methods",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_method_call() {
+        test_parse("h.is_finished()", "h.is_finished()");
+        test_parse("h.id();", "h.id();");
+        test_parse("let x = h.f(2, y+1);", "let x = h.f(2,(y+1));");
+        test_parse("h.id() + 1", "(h.id()+1)");
+
+        test_parse("f(1).id()", "f(1).id()");
+
+        test_parse_err("h.", "Expected field or method name", true);
+    }
+
+    #[test]
+    fn test_parse_index_and_slice() {
+        test_parse("a[0]", "a[0]");
+        test_parse("let x = a[i+1] * 2;", "let x = (a[(i+1)]*2);");
+        test_parse("a[1..3]", "a[1..3]");
+        test_parse("s[..n]", "s[..n]");
+        test_parse("s[n-1..]", "s[(n-1)..]");
+        test_parse("a[..]", "a[..]");
+        test_parse("f(x)[1..][0]", "f(x)[1..][0]");
+        test_parse("p.xs[0].y", "p.xs[0].y");
+        test_parse(
+            "[x for x in xs[1..] if x > 0]",
+            "[x for x in xs[1..] if (x>0)]",
+        );
+        test_parse(
+            "if a[P { x: 1 }.x] { 1 } else { 2 }",
+            "if a[P { x:1 }.x] { 1 } else { 2 }",
+        );
+
+        test_parse_err("a[1;]", "Expected ']' or '..' after index", true);
+        test_parse_err("a[1..2", "Expected ']' to close slice", true);
+    }
 }
@@ -18,7 +18,7 @@ impl<'inp> Parser<'inp> {
             self.advance();
         }
 
-        let cond = self.parse_expr(min_bp)?.to_expr()?;
+        let cond = self.parse_cond(min_bp)?.to_expr()?;
 
         // go past OpenBrace, put in prev_tok
         self.consume_token_type(
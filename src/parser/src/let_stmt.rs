@@ -1,5 +1,9 @@
+use std::collections::HashSet;
+
 use crate::Decl;
 use crate::Decl::*;
+use crate::DestructurePattern;
+use crate::LetDestructureData;
 use crate::LetStmtData;
 use crate::ParseError;
 use crate::Parser;
@@ -10,10 +14,26 @@ impl<'inp> Parser<'inp> {
     // Parse let statement
     // let x = 2;
     pub(crate) fn parse_let(&mut self) -> Result<Decl, ParseError> {
+        if self.consume_opt_token_type(Token::OpenBracket) {
+            let pat = self.parse_array_pattern()?;
+            return self.parse_let_destructure(pat);
+        }
+
+        if self.is_peek_token_type(Token::OpenParen) {
+            return Err(ParseError::new(
+                "Tuples are not supported, destructure an array with let [a, b] = ...",
+            ));
+        }
+
         crate::expect_token_body!(self.lexer.peek(), Ident, "identifier")?;
         let ident = Parser::string_from_ident(self.lexer.peek());
         self.advance();
 
+        if self.consume_opt_token_type(Token::OpenBrace) {
+            let pat = self.parse_struct_pattern(ident)?;
+            return self.parse_let_destructure(pat);
+        }
+
         let mut type_ann: Option<Type> = None;
 
         // Do nothing if not colon: allow no annotation to let prev tests pass (for now)
@@ -45,6 +65,93 @@ impl<'inp> Parser<'inp> {
 
         Ok(LetStmt(stmt))
     }
+
+    // = expr; after the pattern of a destructuring let
+    fn parse_let_destructure(&mut self, pat: DestructurePattern) -> Result<Decl, ParseError> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        for name in pat.names() {
+            if !seen.insert(name) {
+                let e = format!("'{}' bound more than once in let pattern", name);
+                return Err(ParseError::new(&e));
+            }
+        }
+
+        self.consume_token_type(Token::Eq, "Expected '='")?;
+        self.advance();
+        let expr = self.parse_decl()?.to_expr()?;
+        self.expect_token_type(Token::Semi, "Expected semicolon after let")?;
+
+        Ok(LetDestructureStmt(LetDestructureData { pat, expr }))
+    }
+
+    // x, y, ..rest] - prev_tok is [
+    fn parse_array_pattern(&mut self) -> Result<DestructurePattern, ParseError> {
+        let mut elems: Vec<String> = vec![];
+        let mut rest: Option<String> = None;
+
+        while !self.consume_opt_token_type(Token::CloseBracket) {
+            if rest.is_some() {
+                return Err(ParseError::new(
+                    "Expected ']' after the rest of an array pattern",
+                ));
+            }
+
+            let is_rest = self.consume_opt_token_type(Token::DotDot);
+            crate::expect_token_body!(self.lexer.peek(), Ident, "identifier in array pattern")?;
+            let name = Parser::string_from_ident(self.lexer.peek());
+            self.advance();
+
+            match is_rest {
+                true => rest = Some(name),
+                false => elems.push(name),
+            }
+
+            if !self.is_peek_token_type(Token::CloseBracket) {
+                self.consume_token_type(Token::Comma, "Expected ',' to separate array pattern")?;
+            }
+        }
+
+        Ok(DestructurePattern::Array(elems, rest))
+    }
+
+    // x, y: b, .. } - prev_tok is {
+    fn parse_struct_pattern(&mut self, name: String) -> Result<DestructurePattern, ParseError> {
+        let mut fields: Vec<(String, String)> = vec![];
+        let mut has_rest = false;
+
+        while !self.consume_opt_token_type(Token::CloseBrace) {
+            if has_rest {
+                return Err(ParseError::new("Expected '}' after '..' in struct pattern"));
+            }
+
+            if self.consume_opt_token_type(Token::DotDot) {
+                has_rest = true;
+            } else {
+                crate::expect_token_body!(self.lexer.peek(), Ident, "field in struct pattern")?;
+                let field = Parser::string_from_ident(self.lexer.peek());
+                self.advance();
+
+                let mut binding = field.clone();
+                if self.consume_opt_token_type(Token::Colon) {
+                    crate::expect_token_body!(self.lexer.peek(), Ident, "identifier after ':'")?;
+                    binding = Parser::string_from_ident(self.lexer.peek());
+                    self.advance();
+                }
+
+                if fields.iter().any(|(prev, _)| *prev == field) {
+                    let e = format!("Field '{}' given more than once in struct pattern", field);
+                    return Err(ParseError::new(&e));
+                }
+                fields.push((field, binding));
+            }
+
+            if !self.is_peek_token_type(Token::CloseBrace) {
+                self.consume_token_type(Token::Comma, "Expected ',' to separate struct pattern")?;
+            }
+        }
+
+        Ok(DestructurePattern::Struct(name, fields, has_rest))
+    }
 }
 
 #[cfg(test)]
@@ -128,4 +235,36 @@ pub mod tests {
             "let x : int = (((2*3)+4)-(5+6));let y : bool = (!(!true));",
         );
     }
+
+    #[test]
+    fn test_parse_let_destructure() {
+        test_parse("let [x, y] = a;", "let [x, y] = a;");
+        test_parse("let [x, ..rest] = [1, 2];", "let [x, ..rest] = [1,2];");
+        test_parse("let [..all] = a; all", "let [..all] = a;all");
+        test_parse("let [] = a;", "let [] = a;");
+        test_parse("let P { x, y: b } = p;", "let P { x, y: b } = p;");
+        test_parse("let P { x, .. } = p;", "let P { x, .. } = p;");
+
+        // the names bound are symbols of the block
+        let prog = crate::Parser::new_from_string("let [a, ..b] = xs; let P { x: c } = p;")
+            .parse()
+            .expect("Should parse");
+        assert_eq!(prog.symbols, vec!["a", "b", "c"]);
+
+        test_parse_err(
+            "let [x, ..rest, y] = a;",
+            "Expected ']' after the rest",
+            true,
+        );
+        test_parse_err("let [x, x] = a;", "'x' bound more than once", true);
+        test_parse_err("let P { x, y: x } = p;", "'x' bound more than once", true);
+        test_parse_err(
+            "let P { x, x: y } = p;",
+            "Field 'x' given more than once",
+            true,
+        );
+        test_parse_err("let P { .., x } = p;", "Expected '}' after '..'", true);
+        test_parse_err("let [1] = a;", "Expected identifier in array pattern", true);
+        test_parse_err("let (a, b) = p;", "Tuples are not supported", true);
+    }
 }
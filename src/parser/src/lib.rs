@@ -1,5 +1,6 @@
 use lexer::{lex, Token};
 use logos::Lexer;
+use parse_loop::LoopCtx;
 use std::iter::Peekable;
 use structs::*;
 
@@ -9,8 +10,15 @@ pub mod fn_decl;
 pub mod ident;
 pub mod if_else;
 pub mod let_stmt;
+pub mod named_args;
+pub mod parse_array;
+pub mod parse_enum;
 pub mod parse_loop;
+pub mod parse_match;
+pub mod parse_struct;
+pub mod parse_try_catch;
 pub mod parse_type_ann;
+pub mod select;
 pub mod seq;
 pub mod structs;
 
@@ -20,17 +28,14 @@ macro_rules! expect_token_body {
         let err = Err(ParseError::new(concat!("Expected ", $expected)));
         let pk = $peek;
 
-        if pk.is_none() {
-            err
-        } else {
-            let pk = pk
-                .expect("Peek has something")
-                .as_ref()
-                .expect("Expect lexer to succeed");
+        if let Some(pk) = pk {
+            let pk = pk.as_ref().expect("Expect lexer to succeed");
             match pk {
                 Token::$token(_) => Ok(()),
                 _ => err,
             }
+        } else {
+            err
         }
     }};
 }
@@ -40,8 +45,19 @@ pub(crate) use expect_token_body;
 pub struct Parser<'inp> {
     prev_tok: Option<Token>,
     lexer: Peekable<Lexer<'inp, Token>>,
-    pub is_loop: bool,
+    // Number of tokens consumed, to locate errors
+    consumed: usize,
+    // Loops enclosing what is being parsed in the current fn, innermost last
+    loops: Vec<LoopCtx>,
     pub is_fn: bool,
+    // Structs and impls are only declared outside of blocks
+    is_top_level: bool,
+    // In conditions of if, loop and match, where { starts the block and not a struct expression
+    no_struct_lit: bool,
+    // In the array of a comprehension, where if starts its condition e.g [x for x in xs if x > 2]
+    in_comprehension: bool,
+    // In a fn that has yielded a value so far, which makes it a generator
+    is_generator: bool,
 }
 
 impl<'inp> Parser<'inp> {
@@ -49,8 +65,13 @@ impl<'inp> Parser<'inp> {
         Parser {
             prev_tok: None,
             lexer: lexer.peekable(),
-            is_loop: false,
+            consumed: 0,
+            loops: vec![],
             is_fn: false,
+            is_top_level: true,
+            no_struct_lit: false,
+            in_comprehension: false,
+            is_generator: false,
         }
     }
 
@@ -58,22 +79,21 @@ impl<'inp> Parser<'inp> {
         Parser {
             prev_tok: None,
             lexer: lex(inp).peekable(),
-            is_loop: false,
+            consumed: 0,
+            loops: vec![],
             is_fn: false,
+            is_top_level: true,
+            no_struct_lit: false,
+            in_comprehension: false,
+            is_generator: false,
         }
     }
 
     // Check if peek is a specific token type
     fn is_peek_token_type(&mut self, token: Token) -> bool {
-        let pk = self.lexer.peek();
-        if pk.is_none() {
-            false
-        } else {
-            let pk = pk.unwrap();
-            match pk {
-                Ok(prev) => prev.eq(&token),
-                _ => false,
-            }
+        match self.lexer.peek() {
+            Some(Ok(prev)) => prev.eq(&token),
+            _ => false,
         }
     }
 
@@ -113,9 +133,19 @@ impl<'inp> Parser<'inp> {
             self.prev_tok
                 .replace(val.clone().expect("Expect lexer to succeed"));
             self.lexer.next();
+            self.consumed += 1;
         }
     }
 
+    /// Parse the condition of if, loop or match, where a { after an ident starts the block
+    fn parse_cond(&mut self, min_bp: u8) -> Result<Decl, ParseError> {
+        let prev_no_struct_lit = self.no_struct_lit;
+        self.no_struct_lit = true;
+        let cond = self.parse_expr(min_bp);
+        self.no_struct_lit = prev_no_struct_lit;
+        cond
+    }
+
     // Expect prev_tok to be there (helper method)
     fn expect_prev_tok(&self) -> Result<&Token, ParseError> {
         match &self.prev_tok {
@@ -136,7 +166,7 @@ impl<'inp> Parser<'inp> {
     fn expect_token_for_type_ann(token: Option<&Result<Token, ()>>) -> Result<(), ParseError> {
         if let Some(Ok(tok)) = token {
             match tok {
-                Token::Ident(_) | Token::OpenParen | Token::Fn => Ok(()),
+                Token::Ident(_) | Token::OpenParen | Token::OpenBracket | Token::Fn => Ok(()),
                 _ => {
                     let e = format!(
                         "Expected identifier or '(' for type annotation, got '{}'",
@@ -158,10 +188,14 @@ impl<'inp> Parser<'inp> {
     // (left, right) => left < right means left associative. left > right means right associative. equal => no associativity (error)
     fn get_infix_bp(binop: &BinOpType) -> (u8, u8) {
         match binop {
-            BinOpType::Mul | BinOpType::Div => (8, 9),
+            BinOpType::Mul | BinOpType::Div | BinOpType::Mod => (8, 9),
             BinOpType::Add | BinOpType::Sub => (6, 7),
             // no associativity for comparison ops
-            BinOpType::LogicalEq | BinOpType::Gt | BinOpType::Lt => (5, 5),
+            BinOpType::LogicalEq
+            | BinOpType::Gt
+            | BinOpType::Lt
+            | BinOpType::Ge
+            | BinOpType::Le => (5, 5),
             BinOpType::LogicalAnd => (3, 4),
             BinOpType::LogicalOr => (1, 2),
         }
@@ -188,28 +222,16 @@ impl<'inp> Parser<'inp> {
             | Token::Bang
             | Token::OpenBrace
             | Token::If
+            | Token::Select
+            | Token::Scope
+            | Token::Lock
+            | Token::ReadLock
+            | Token::Match
+            | Token::Try
+            | Token::OpenBracket
+            | Token::Spawn
+            | Token::Join
             | Token::String(_) => self.parse_expr(0),
-            Token::Spawn => {
-                self.advance();
-                let fn_call = self.parse_expr(0)?.to_expr()?;
-                if let Expr::FnCallExpr(fn_data) = fn_call {
-                    let sp = Expr::SpawnExpr(fn_data);
-                    Ok(Decl::ExprStmt(sp))
-                } else {
-                    Err(ParseError::new("spawn expected function call"))
-                }
-            }
-            // join t;
-            Token::Join => {
-                self.advance();
-                let join_id = self.parse_expr(0)?.to_expr()?;
-                if let Expr::Symbol(tid) = join_id {
-                    let j = Expr::JoinExpr(tid);
-                    Ok(Decl::ExprStmt(j))
-                } else {
-                    Err(ParseError::new("join expected variable for thread to join"))
-                }
-            }
             // wait sem;
             Token::Wait => {
                 self.advance();
@@ -229,14 +251,22 @@ impl<'inp> Parser<'inp> {
                     Err(ParseError::new("post expected semaphore variable"))
                 }
             }
-            // if not is_loop, error
-            Token::Break => {
-                if !self.is_loop {
-                    return Err(ParseError::new("break outside of loop"));
+            Token::Break => self.parse_break(),
+            Token::Yield => {
+                if self.is_peek_token_type(Token::Semi) {
+                    return Ok(Decl::YieldStmt);
                 }
-                Ok(Decl::BreakStmt)
+
+                // yield with a value makes the enclosing fn a generator
+                if !self.is_fn {
+                    return Err(ParseError::new("yield with a value outside of fn"));
+                }
+
+                self.advance();
+                let expr = self.parse_expr(0)?.to_expr()?;
+                self.is_generator = true;
+                Ok(Decl::YieldValueStmt(expr))
             }
-            Token::Yield => Ok(Decl::YieldStmt),
             // if not is_fn, err
             Token::Return => {
                 if !self.is_fn {
@@ -254,8 +284,14 @@ impl<'inp> Parser<'inp> {
                 Ok(Decl::ReturnStmt(ret_expr))
             }
             Token::Let => self.parse_let(),
-            Token::Loop => self.parse_loop(),
+            Token::Loop => self.parse_loop(None),
+            Token::Label(label) => self.parse_labeled(label.to_owned()),
+            Token::For => self.parse_for(None),
             Token::Fn => self.parse_fn_decl(),
+            Token::Struct => self.parse_struct_decl(),
+            Token::Impl => self.parse_impl(),
+            Token::Enum => self.parse_enum_decl(),
+            Token::Pound => self.parse_test_fn_decl(),
             _ => Err(ParseError::new(&format!(
                 "Unexpected token: '{}'",
                 prev_tok
@@ -266,6 +302,7 @@ impl<'inp> Parser<'inp> {
     // Implicit block
     pub fn parse(mut self) -> Result<BlockSeq, ParseError> {
         self.parse_seq()
+            .map_err(|err| err.at(self.consumed.checked_sub(1)))
     }
 }
 
@@ -366,6 +403,15 @@ mod tests {
         ";
         test_parse_err(t, "spawn expected function call", true);
 
+        // spawn with the function and its args
+        let t = r"
+        let t = spawn(worker, i, 2);
+        spawn(f);
+        spawn(f(x))
+        ";
+        test_parse(t, "let t = spawn worker(i,2);spawn f();spawn f(x)");
+        test_parse_err("spawn(2, 3)", "spawn expected a function", true);
+
         // join
         let t = r"
         let t = spawn func();
@@ -373,6 +419,63 @@ mod tests {
         ";
         test_parse(t, "let t = spawn func();let res = join t;");
 
+        // spawn blocks, whose value join gives back
+        let t = r"
+        let h = spawn { let x = f(); x * 2 };
+        let res = join(h);
+        ";
+        test_parse(t, "let h = spawn { let x = f();(x*2) };let res = join h;");
+
+        // spawn and join in expression position
+        let t = r"
+        println(join(h));
+        let x = join(h) + 1;
+        let y = 2 * join h;
+        let hs = [spawn f(i), spawn(g, 1), spawn { 3 }];
+        ";
+        test_parse(
+            t,
+            "println(join h);let x = (join h+1);let y = (2*join h);let hs = [spawn f(i),spawn g(1),spawn { 3 }];",
+        );
+        test_parse_err("let x = join(h + 1);", "join expected variable", true);
+        test_parse_err(
+            "fn f() { spawn { return 2; } }",
+            "return outside of fn",
+            true,
+        );
+        test_parse_err("loop { spawn { break; }; }", "break outside of loop", true);
+
+        // scope blocks, which join the threads spawned in them
+        let t = r"
+        let x = scope {
+            spawn f();
+            spawn { g() };
+            2
+        };
+        scope { }
+        ";
+        test_parse(t, "let x = scope { spawn f();spawn { g() };2 };scope {  }");
+        test_parse_err("scope 2", "Expected { for scope block", true);
+        test_parse_err("fn f() { scope { return; } }", "return outside of fn", true);
+        test_parse_err("loop { scope { break; } }", "break outside of loop", true);
+
+        // lock blocks, which may be left early
+        let t = r"
+        let m = mutex();
+        let x = lock(m) { count = count + 1; count };
+        fn f(rw: rwlock) -> int {
+            read_lock(rw) { return 2; }
+        }
+        loop { lock(m) { break; } }
+        ";
+        test_parse(
+            t,
+            "let m = mutex();let x = lock(m) { count = (count+1);count };fn f (rw:rwlock) -> int { read_lock(rw) { return 2; } };loop  { lock(m) { break; } };",
+        );
+        test_parse_err("lock m { }", "Expected ( after lock", true);
+        test_parse_err("read_lock(rw { }", "Expected ) to close lock", true);
+        test_parse_err("lock(m) 2", "Expected { for lock block", true);
+
         // wait and post
         let t = r"
         let sem = sem_create();
@@ -423,4 +526,19 @@ mod tests {
         let t = r#"let t = "hello world"; println(t);"#;
         test_parse(t, "let t = hello world;println(t);");
     }
+
+    #[test]
+    fn test_parse_err_location() {
+        // Fails at the fourth token, the semicolon where an expression was expected
+        let err = Parser::new_from_string("let x = ;").parse().unwrap_err();
+        assert_eq!(err.tok(), Some(3));
+
+        // Fails after the last token
+        let err = Parser::new_from_string("let x = 2").parse().unwrap_err();
+        assert_eq!(err.tok(), Some(3));
+
+        // Fails at the first token
+        let err = Parser::new_from_string(")").parse().unwrap_err();
+        assert_eq!(err.tok(), Some(0));
+    }
 }
@@ -0,0 +1,400 @@
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use crate::structs::{
+    BlockSeq, BreakData, ComprehensionData, Decl, Expr, FnCallData, IfElseData, Iterable, LoopData,
+    SelectOp,
+};
+
+/// Reorder the arguments of calls with named arguments to the order of the parameters, so the calls
+/// are checked and compiled like any other. The callee must be a function declared with fn in scope,
+/// so that its parameter names are known. A program without named arguments is returned as is.
+///
+/// Arguments are evaluated in the order of the parameters, not the order they were written in.
+///
+/// # Errors
+///
+/// If the callee is not known, or an argument name is not a parameter of the callee, is given more than once,
+/// or a parameter is not given an argument.
+pub fn resolve_named_args(program: &BlockSeq) -> Result<Cow<'_, BlockSeq>, String> {
+    let mut resolver = Resolver {
+        scopes: vec![],
+        resolved: false,
+    };
+
+    let mut resolved = program.clone();
+    resolver.resolve_block(&mut resolved, vec![])?;
+
+    if resolver.resolved {
+        Ok(Cow::Owned(resolved))
+    } else {
+        Ok(Cow::Borrowed(program))
+    }
+}
+
+struct Resolver {
+    // Names in scope, innermost last, with the parameter names of those that are functions declared with fn
+    scopes: Vec<Vec<(String, Option<Vec<String>>)>>,
+    // Whether any call was reordered
+    resolved: bool,
+}
+
+impl Resolver {
+    /// Parameter names of the function the name refers to, if it is one declared with fn
+    fn params_of(&self, name: &str) -> Option<&Vec<String>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().find(|(sym, _)| sym == name))
+            .and_then(|(_, params)| params.as_ref())
+    }
+
+    /// Resolve the block in a scope of its symbols and the names bound on entering it, e.g the params of a function
+    fn resolve_block(&mut self, blk: &mut BlockSeq, bound: Vec<String>) -> Result<(), String> {
+        let mut scope: Vec<(String, Option<Vec<String>>)> =
+            bound.into_iter().map(|name| (name, None)).collect();
+        for sym in blk.symbols.iter() {
+            scope.push((sym.to_string(), fn_params(blk, sym)));
+        }
+
+        self.scopes.push(scope);
+        let res = self.resolve_block_body(blk);
+        self.scopes.pop();
+        res
+    }
+
+    fn resolve_block_body(&mut self, blk: &mut BlockSeq) -> Result<(), String> {
+        for decl in blk.decls.iter_mut() {
+            match decl {
+                Decl::LetStmt(stmt) => self.resolve_expr(&mut stmt.expr)?,
+                Decl::LetDestructureStmt(stmt) => self.resolve_expr(&mut stmt.expr)?,
+                Decl::AssignStmt(stmt) => self.resolve_expr(&mut stmt.expr)?,
+                Decl::FieldAssignStmt(stmt) => self.resolve_expr(&mut stmt.expr)?,
+                Decl::ExprStmt(expr)
+                | Decl::ReturnStmt(Some(expr))
+                | Decl::YieldValueStmt(expr)
+                | Decl::BreakStmt(BreakData {
+                    value: Some(expr), ..
+                }) => self.resolve_expr(expr)?,
+                Decl::IfOnlyStmt(if_else) => self.resolve_if_else(if_else)?,
+                Decl::LoopStmt(lp) => self.resolve_loop(lp)?,
+                Decl::ForStmt(lp) => {
+                    self.resolve_iterable(&mut lp.iter)?;
+                    self.resolve_block(&mut lp.body, vec![lp.var.clone()])?;
+                }
+                Decl::FnDeclStmt(fn_decl) => {
+                    let params = fn_decl.params.iter().map(|x| x.name.clone()).collect();
+                    self.resolve_block(&mut fn_decl.body, params)?;
+                }
+                Decl::ImplStmt(impl_data) => {
+                    for method in impl_data.methods.iter_mut() {
+                        let params = method.params.iter().map(|x| x.name.clone()).collect();
+                        self.resolve_block(&mut method.body, params)?;
+                    }
+                }
+                Decl::StructDeclStmt(_)
+                | Decl::EnumDeclStmt(_)
+                | Decl::ReturnStmt(None)
+                | Decl::BreakStmt(_)
+                | Decl::WaitStmt(_)
+                | Decl::PostStmt(_)
+                | Decl::YieldStmt => (),
+            }
+        }
+
+        if let Some(expr) = &mut blk.last_expr {
+            self.resolve_expr(Rc::make_mut(expr))?;
+        }
+
+        Ok(())
+    }
+
+    fn resolve_loop(&mut self, lp: &mut LoopData) -> Result<(), String> {
+        if let Some(cond) = &mut lp.cond {
+            self.resolve_expr(cond)?;
+        }
+        self.resolve_block(&mut lp.body, vec![])
+    }
+
+    fn resolve_if_else(&mut self, if_else: &mut IfElseData) -> Result<(), String> {
+        self.resolve_expr(&mut if_else.cond)?;
+        self.resolve_block(&mut if_else.if_blk, vec![])?;
+        if let Some(else_blk) = &mut if_else.else_blk {
+            self.resolve_block(else_blk, vec![])?;
+        }
+
+        Ok(())
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) -> Result<(), String> {
+        match expr {
+            Expr::UnOpExpr(_, expr) => self.resolve_expr(expr)?,
+            Expr::BinOpExpr(_, lhs, rhs) => {
+                self.resolve_expr(lhs)?;
+                self.resolve_expr(rhs)?;
+            }
+            Expr::BlockExpr(blk) | Expr::SpawnBlockExpr(blk) | Expr::ScopeExpr(blk) => {
+                self.resolve_block(blk, vec![])?
+            }
+            Expr::IfElseExpr(if_else) => self.resolve_if_else(if_else)?,
+            Expr::FnCallExpr(fn_call) | Expr::SpawnExpr(fn_call) => {
+                for arg in fn_call.args.iter_mut() {
+                    self.resolve_expr(arg)?;
+                }
+                self.resolve_call(fn_call)?;
+            }
+            Expr::MethodCallExpr(method_call) => {
+                self.resolve_expr(&mut method_call.recv)?;
+                for arg in method_call.args.iter_mut() {
+                    self.resolve_expr(arg)?;
+                }
+            }
+            Expr::SelectExpr(select) => {
+                for arm in select.arms.iter_mut() {
+                    if let SelectOp::Send(_, val) = &mut arm.op {
+                        self.resolve_expr(val)?;
+                    }
+                    self.resolve_block(&mut arm.blk, arm.bind.iter().cloned().collect())?;
+                }
+            }
+            Expr::MatchExpr(match_data) => {
+                self.resolve_expr(&mut match_data.expr)?;
+                for arm in match_data.arms.iter_mut() {
+                    let bound = arm.pat.bindings();
+
+                    // the value the variant holds is bound in the guard too
+                    if let Some(guard) = &mut arm.guard {
+                        self.scopes
+                            .push(bound.iter().map(|name| (name.clone(), None)).collect());
+                        let res = self.resolve_expr(guard);
+                        self.scopes.pop();
+                        res?;
+                    }
+
+                    self.resolve_block(&mut arm.blk, bound)?;
+                }
+            }
+            Expr::LoopExpr(lp) => self.resolve_loop(lp)?,
+            Expr::LockExpr(lock) => {
+                self.resolve_expr(&mut lock.lock)?;
+                self.resolve_block(&mut lock.blk, vec![])?;
+            }
+            Expr::TryExpr(expr) | Expr::FieldExpr(expr, _) => self.resolve_expr(expr)?,
+            Expr::StructExpr(struct_expr) => {
+                for (_, expr) in struct_expr.fields.iter_mut() {
+                    self.resolve_expr(expr)?;
+                }
+                if let Some(base) = &mut struct_expr.base {
+                    self.resolve_expr(base)?;
+                }
+            }
+            Expr::TryCatchExpr(try_catch) => {
+                self.resolve_block(&mut try_catch.try_blk, vec![])?;
+                let err = vec![try_catch.err.clone()];
+                self.resolve_block(&mut try_catch.catch_blk, err)?;
+            }
+            Expr::ArrayExpr(elems) => {
+                for elem in elems.iter_mut() {
+                    self.resolve_expr(elem)?;
+                }
+            }
+            Expr::ComprehensionExpr(comp) => {
+                self.resolve_iterable(&mut comp.iter)?;
+
+                // the variable is bound in the element and the condition
+                self.scopes.push(vec![(comp.var.clone(), None)]);
+                let res = self.resolve_comprehension_body(comp);
+                self.scopes.pop();
+                res?;
+            }
+            Expr::IndexExpr(expr, idx) => {
+                self.resolve_expr(expr)?;
+                self.resolve_expr(idx)?;
+            }
+            Expr::SliceExpr(expr, start, end) => {
+                self.resolve_expr(expr)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.resolve_expr(bound)?;
+                }
+            }
+            Expr::Symbol(_)
+            | Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::StringLiteral(_)
+            | Expr::JoinExpr(_) => (),
+        }
+
+        Ok(())
+    }
+
+    fn resolve_iterable(&mut self, iter: &mut Iterable) -> Result<(), String> {
+        match iter {
+            Iterable::Range(start, end) => {
+                self.resolve_expr(start)?;
+                self.resolve_expr(end)
+            }
+            Iterable::Collection(arr) => self.resolve_expr(arr),
+        }
+    }
+
+    fn resolve_comprehension_body(&mut self, comp: &mut ComprehensionData) -> Result<(), String> {
+        self.resolve_expr(&mut comp.expr)?;
+        if let Some(cond) = &mut comp.cond {
+            self.resolve_expr(cond)?;
+        }
+
+        Ok(())
+    }
+
+    /// Put the args of a call with named args in the order of the params of the callee
+    fn resolve_call(&mut self, fn_call: &mut FnCallData) -> Result<(), String> {
+        if fn_call.names.is_empty() {
+            return Ok(());
+        }
+
+        let Some(params) = self.params_of(&fn_call.name) else {
+            return Err(format!(
+                "Named arguments need a function declared with fn, '{}' is not one in scope",
+                fn_call.name
+            ));
+        };
+
+        let positional = fn_call.args.len() - fn_call.names.len();
+        if positional > params.len() {
+            return Err(format!(
+                "Function '{}' takes {} arguments but {} were given",
+                fn_call.name,
+                params.len(),
+                fn_call.args.len()
+            ));
+        }
+
+        let mut args: Vec<Option<Expr>> = vec![None; params.len()];
+        let mut given = std::mem::take(&mut fn_call.args).into_iter();
+
+        for (slot, arg) in args.iter_mut().zip(given.by_ref().take(positional)) {
+            *slot = Some(arg);
+        }
+
+        for (name, arg) in fn_call.names.iter().zip(given) {
+            let Some(idx) = params.iter().position(|param| param == name) else {
+                return Err(format!(
+                    "Function '{}' has no parameter named '{}'",
+                    fn_call.name, name
+                ));
+            };
+
+            if args[idx].is_some() {
+                return Err(format!(
+                    "Argument '{}' is given more than once in call to '{}'",
+                    name, fn_call.name
+                ));
+            }
+
+            args[idx] = Some(arg);
+        }
+
+        let mut ordered = vec![];
+        for (param, arg) in params.iter().zip(args) {
+            match arg {
+                Some(arg) => ordered.push(arg),
+                None => {
+                    return Err(format!(
+                        "Missing argument '{}' in call to '{}'",
+                        param, fn_call.name
+                    ))
+                }
+            }
+        }
+
+        fn_call.args = ordered;
+        fn_call.names.clear();
+        self.resolved = true;
+
+        Ok(())
+    }
+}
+
+/// Parameter names of the function the symbol of the block refers to, if every decl of it in the block
+/// is a fn decl with the same parameter names. Otherwise what it refers to depends on where it is used.
+fn fn_params(blk: &BlockSeq, sym: &str) -> Option<Vec<String>> {
+    let mut params: Option<Vec<String>> = None;
+
+    for decl in blk.decls.iter() {
+        match decl {
+            Decl::FnDeclStmt(fn_decl) if fn_decl.name == sym => {
+                let names: Vec<String> = fn_decl.params.iter().map(|x| x.name.clone()).collect();
+                match &params {
+                    Some(prev) if *prev != names => return None,
+                    _ => params = Some(names),
+                }
+            }
+            Decl::LetStmt(stmt) if stmt.ident == sym => return None,
+            Decl::LetDestructureStmt(stmt) if stmt.pat.names().contains(&sym) => return None,
+            _ => (),
+        }
+    }
+
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_named_args;
+    use crate::Parser;
+
+    fn test_resolve(inp: &str, exp: &str) {
+        let prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        let res = resolve_named_args(&prog).expect("Should resolve");
+        assert_eq!(res.to_string(), exp);
+    }
+
+    fn test_resolve_err(inp: &str, exp_err: &str) {
+        let prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        let err = resolve_named_args(&prog).expect_err("Should fail to resolve");
+        assert_eq!(err, exp_err);
+    }
+
+    #[test]
+    fn test_resolve_named_args() {
+        let t = "fn draw(x: int, y: int) {} draw(y: 2, x: 1)";
+        test_resolve(t, "fn draw (x:int, y:int) {  };draw(1,2)");
+
+        // positional args first, and calls before the decl
+        let t = "draw(1, y: 2); fn draw(x: int, y: int) {}";
+        test_resolve(t, "draw(1,2);fn draw (x:int, y:int) {  };");
+
+        // nested calls and blocks
+        let t = "fn f(a: int, b: int) -> int { a } { f(b: f(b: 1, a: 2), a: 3) }";
+        test_resolve(t, "fn f (a:int, b:int) -> int { a };{ f(3,f(2,1)) }");
+    }
+
+    #[test]
+    fn test_resolve_named_args_errs() {
+        let t = "fn draw(x: int, y: int) {} draw(x: 1, z: 2)";
+        test_resolve_err(t, "Function 'draw' has no parameter named 'z'");
+
+        let t = "fn draw(x: int, y: int) {} draw(x: 1, x: 2)";
+        test_resolve_err(t, "Argument 'x' is given more than once in call to 'draw'");
+
+        let t = "fn draw(x: int, y: int) {} draw(1, x: 2)";
+        test_resolve_err(t, "Argument 'x' is given more than once in call to 'draw'");
+
+        let t = "fn draw(x: int, y: int) {} draw(y: 2)";
+        test_resolve_err(t, "Missing argument 'x' in call to 'draw'");
+
+        let t = "println(x: 2)";
+        test_resolve_err(
+            t,
+            "Named arguments need a function declared with fn, 'println' is not one in scope",
+        );
+
+        // shadowed by a param, so the callee is not known
+        let t = "fn draw(x: int) {} fn f(draw: fn(int)) { draw(x: 2) }";
+        test_resolve_err(
+            t,
+            "Named arguments need a function declared with fn, 'draw' is not one in scope",
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::ComprehensionData;
+use crate::Decl;
+use crate::Expr;
+use crate::Iterable;
+use crate::ParseError;
+use crate::Parser;
+use lexer::Token;
+
+impl<'inp> Parser<'inp> {
+    // [1, 2, 3] or a comprehension [x * 2 for x in 0..10 if x % 2 == 0]
+    // Invariant: prev_tok is [
+    pub(crate) fn parse_array(&mut self) -> Result<Decl, ParseError> {
+        // struct expressions are allowed in brackets, even in a condition
+        let prev_no_struct_lit = self.no_struct_lit;
+        let prev_in_comprehension = self.in_comprehension;
+        self.no_struct_lit = false;
+        self.in_comprehension = false;
+
+        let res = self.parse_array_elems();
+
+        self.no_struct_lit = prev_no_struct_lit;
+        self.in_comprehension = prev_in_comprehension;
+        res.map(Decl::ExprStmt)
+    }
+
+    fn parse_array_elems(&mut self) -> Result<Expr, ParseError> {
+        let mut elems: Vec<Expr> = vec![];
+
+        while !self.consume_opt_token_type(Token::CloseBracket) {
+            if self.lexer.peek().is_none() {
+                return Err(ParseError::new("Expected ']' to close array"));
+            }
+
+            self.advance();
+            let elem = self.parse_expr(0)?.to_expr()?;
+
+            if elems.is_empty() && self.is_peek_token_type(Token::For) {
+                return self.parse_comprehension(elem);
+            }
+
+            elems.push(elem);
+
+            if !self.is_peek_token_type(Token::CloseBracket) {
+                self.consume_token_type(Token::Comma, "Expected ',' to separate array elements")?;
+            }
+        }
+
+        Ok(Expr::ArrayExpr(elems))
+    }
+
+    // for x in 0..10 if x % 2 == 0], after the expression of each element
+    // Invariant: peek is at for
+    fn parse_comprehension(&mut self, expr: Expr) -> Result<Expr, ParseError> {
+        self.consume_token_type(Token::For, "Expected 'for' in comprehension")?;
+
+        crate::expect_token_body!(self.lexer.peek(), Ident, "variable name after 'for'")?;
+        let var = Parser::string_from_ident(self.lexer.peek());
+        self.advance();
+
+        self.consume_token_type(
+            Token::In,
+            "Expected 'in' after the variable of a comprehension",
+        )?;
+        self.advance();
+
+        self.in_comprehension = true;
+        let iter = self.parse_iterable()?;
+
+        let mut cond = None;
+        if self.consume_opt_token_type(Token::If) {
+            self.advance();
+            cond = Some(self.parse_expr(0)?.to_expr()?);
+        }
+
+        self.consume_token_type(Token::CloseBracket, "Expected ']' to close comprehension")?;
+
+        let data = ComprehensionData {
+            expr,
+            var,
+            iter,
+            cond,
+        };
+
+        Ok(Expr::ComprehensionExpr(Box::new(data)))
+    }
+
+    // lo..hi or an array or string, what a comprehension or for loop goes over
+    // Invariant: prev_tok is the start of the iterable
+    pub(crate) fn parse_iterable(&mut self) -> Result<Iterable, ParseError> {
+        let start = self.parse_expr(0)?.to_expr()?;
+        if self.consume_opt_token_type(Token::DotDot) {
+            self.advance();
+            let end = self.parse_expr(0)?.to_expr()?;
+            return Ok(Iterable::Range(Box::new(start), Box::new(end)));
+        }
+
+        Ok(Iterable::Collection(Box::new(start)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{test_parse, test_parse_err};
+
+    #[test]
+    fn test_parse_array() {
+        test_parse("[]", "[]");
+        test_parse("[1, 2, 3]", "[1,2,3]");
+        test_parse("let a = [1, 2+3, f(x)];", "let a = [1,(2+3),f(x)];");
+        test_parse("[[1], [2, 3],]", "[[1],[2,3]]");
+        test_parse("let a: [[int]] = [];", "let a : [[int]] = [];");
+        test_parse("[P { x: 1 }]", "[P { x:1 }]");
+
+        test_parse_err("[1, 2,", "Expected ']' to close array", true);
+        test_parse_err(
+            "[1, x for x in xs]",
+            "Expected ',' to separate array elements",
+            true,
+        );
+        test_parse_err(
+            "let a: [int = [];",
+            "Expected ']' to close array type",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_comprehension() {
+        test_parse(
+            "[x * 2 for x in 0..10 if x % 2 == 0]",
+            "[(x*2) for x in 0..10 if ((x%2)==0)]",
+        );
+        test_parse("[x for x in xs]", "[x for x in xs]");
+        test_parse("[x for x in 0..n+1]", "[x for x in 0..(n+1)]");
+        test_parse(
+            "[[y for y in 0..x] for x in [1, 2] if x > 1]",
+            "[[y for y in 0..x] for x in [1,2] if (x>1)]",
+        );
+        test_parse(
+            "if [x for x in xs if x > 0] == [] { 1 } else { 2 }",
+            "if ([x for x in xs if (x>0)]==[]) { 1 } else { 2 }",
+        );
+
+        test_parse_err(
+            "[x for 2 in xs]",
+            "Expected variable name after 'for'",
+            true,
+        );
+        test_parse_err("[x for x xs]", "Expected 'in'", true);
+        test_parse_err("[x for x in xs if x > 0", "Expected ']'", true);
+    }
+}
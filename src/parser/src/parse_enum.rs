@@ -0,0 +1,131 @@
+use crate::Decl;
+use crate::EnumDeclData;
+use crate::ParseError;
+use crate::Parser;
+use crate::Type;
+use lexer::Token;
+
+/// Variants of options and results, which the variants of enums can't be named after
+const BUILTIN_VARIANTS: [&str; 4] = ["Some", "None", "Ok", "Err"];
+
+impl<'inp> Parser<'inp> {
+    // enum Shape { Circle(float), Square(float), Empty }
+    // Invariant: prev_tok is enum
+    pub(crate) fn parse_enum_decl(&mut self) -> Result<Decl, ParseError> {
+        self.expect_top_level("Enums")?;
+
+        crate::expect_token_body!(self.lexer.peek(), Ident, "enum name")?;
+        let name = Parser::string_from_ident(self.lexer.peek());
+        self.advance();
+
+        if !Parser::is_struct_name(&name) {
+            let e = format!("Enum name '{}' must start with an uppercase letter", name);
+            return Err(ParseError::new(&e));
+        }
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for enum variants", Token::OpenBrace),
+        )?;
+
+        let mut variants: Vec<(String, Option<Type>)> = vec![];
+        while !self.is_peek_token_type(Token::CloseBrace) {
+            crate::expect_token_body!(self.lexer.peek(), Ident, "variant name")?;
+            let variant = Parser::string_from_ident(self.lexer.peek());
+            self.advance();
+
+            // variants are matched by name alone, so they must look like variants and be distinct
+            if !Parser::is_struct_name(&variant) || BUILTIN_VARIANTS.contains(&variant.as_str()) {
+                let e = format!(
+                    "Variant name '{}' must start with an uppercase letter and not be one of {}",
+                    variant,
+                    BUILTIN_VARIANTS.join(", ")
+                );
+                return Err(ParseError::new(&e));
+            }
+
+            if variants.iter().any(|(prev, _)| *prev == variant) {
+                let e = format!(
+                    "Variant '{}' declared more than once in enum {}",
+                    variant, name
+                );
+                return Err(ParseError::new(&e));
+            }
+
+            let mut held = None;
+            if self.is_peek_token_type(Token::OpenParen) {
+                self.advance();
+                held = Some(self.parse_type_annotation()?);
+                self.consume_token_type(
+                    Token::CloseParen,
+                    "Expected ')' after the type the variant holds",
+                )?;
+            }
+            variants.push((variant, held));
+
+            if !self.is_peek_token_type(Token::CloseBrace) {
+                self.consume_token_type(Token::Comma, "Expected ',' to separate enum variants")?;
+            }
+        }
+
+        self.consume_token_type(
+            Token::CloseBrace,
+            &format!("Expected {} to close enum variants", Token::CloseBrace),
+        )?;
+
+        if variants.is_empty() {
+            let e = format!("Enum {} must have at least one variant", name);
+            return Err(ParseError::new(&e));
+        }
+
+        Ok(Decl::EnumDeclStmt(EnumDeclData { name, variants }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+
+    #[test]
+    fn test_parse_enum_decl() {
+        let t = "enum Shape { Circle(float), Square(float), Empty }";
+        test_parse(t, "enum Shape { Circle(float), Square(float), Empty };");
+
+        // trailing comma, and variants holding options and other user types
+        let t = "enum E { A(Option<int>), B(Point), }";
+        test_parse(t, "enum E { A(Option<int>), B(Point) };");
+
+        let t = "enum Shape { Empty } let s = Empty; s";
+        test_parse(t, "enum Shape { Empty };let s = Empty;s");
+    }
+
+    #[test]
+    fn test_parse_enum_decl_err() {
+        test_parse_err(
+            "enum shape { Empty }",
+            "Enum name 'shape' must start with an uppercase letter",
+            true,
+        );
+        test_parse_err("enum E { }", "Enum E must have at least one variant", true);
+        test_parse_err(
+            "enum E { A, A }",
+            "Variant 'A' declared more than once in enum E",
+            true,
+        );
+        test_parse_err(
+            "enum E { Some(int) }",
+            "Variant name 'Some' must start with an uppercase letter and not be one of Some, None, Ok, Err",
+            true,
+        );
+        test_parse_err(
+            "enum E { A(int) B }",
+            "Expected ',' to separate enum variants",
+            true,
+        );
+        test_parse_err(
+            "fn f() { enum E { A } }",
+            "Enums can only be declared at the top level",
+            true,
+        );
+    }
+}
@@ -1,12 +1,24 @@
 use lexer::Token;
 
+use crate::BreakData;
 use crate::Decl;
 use crate::Expr;
+use crate::ForData;
 use crate::LoopData;
 use crate::ParseError;
 use crate::Parser;
 
-// Loops are only statements, not expressions
+// A loop enclosing what is being parsed, which break can leave
+#[derive(Debug)]
+pub(crate) struct LoopCtx {
+    label: Option<String>,
+    // Only a loop without a condition can be left with a value, as the others can end without a break
+    takes_value: bool,
+    // Whether a break leaves it with a value, which makes the loop an expression
+    has_value: bool,
+}
+
+// Loops are statements, unless a break leaves them with a value
 /*
 // inf
 loop {
@@ -29,44 +41,57 @@ loop x < 5 {
         break;
     }
 }
+
+// break with a value from nested loops
+let found = 'rows: loop {
+    loop {
+        break 'rows 2;
+    }
+};
 */
 impl<'inp> Parser<'inp> {
-    /*
-
-    */
-    // Ensure is_loop flag is saved and restored as long as valid return. Error crashes the whole parser so it's fine
-    pub(crate) fn parse_loop(&mut self) -> Result<Decl, ParseError> {
-        let prev_is_loop = self.is_loop;
-        let lp = self.parse_loop_inner()?;
-        self.is_loop = prev_is_loop;
-        Ok(lp)
+    // The loop context is pushed for the body and popped after, even on error since it crashes the whole parser anyway
+    // Invariant: prev_tok is loop
+    pub(crate) fn parse_loop(&mut self, label: Option<String>) -> Result<Decl, ParseError> {
+        self.loops.push(LoopCtx {
+            label,
+            takes_value: true,
+            has_value: false,
+        });
+        let lp = self.parse_loop_inner();
+        let ctx = self.loops.pop().expect("Loop context was pushed");
+        let lp = lp?;
+
+        if ctx.has_value {
+            if lp.cond.is_some() {
+                return Err(ParseError::new(
+                    "break with a value can only leave a loop without a condition",
+                ));
+            }
+            return Ok(Decl::ExprStmt(Expr::LoopExpr(Box::new(lp))));
+        }
+
+        Ok(Decl::LoopStmt(lp))
     }
 
-    fn parse_loop_inner(&mut self) -> Result<Decl, ParseError> {
+    fn parse_loop_inner(&mut self) -> Result<LoopData, ParseError> {
         // If token not consumed (no open paren), advance so first token of expr goes into prev_tok
         // allows loop (x < 3) - condition in brackets
         if !self.consume_opt_token_type(Token::OpenParen) {
             self.advance();
         }
 
-        // dbg!("prev_tok after loop:", &self.prev_tok);
-
-        // save and restore prev is_loop e.g when coming from global
-        let prev_is_loop = self.is_loop;
-        self.is_loop = true;
-
-        let cond = self.parse_expr(0)?.to_expr()?;
+        let label = self.loops.last().and_then(|ctx| ctx.label.clone());
+        let cond = self.parse_cond(0)?.to_expr()?;
 
         // If the thing we parsed is a block, this is a loop with just a body and no cond
         if let Expr::BlockExpr(ref blk) = cond {
-            // dbg!("peek after parsing blk:", &self.lexer.peek());
             // next token is NOT OpenBrace: we just parsed body, there is no condition
-            let lp = LoopData {
+            return Ok(LoopData {
+                label,
                 cond: None,
                 body: blk.to_owned(),
-            };
-
-            return Ok(Decl::LoopStmt(lp));
+            });
         }
 
         // go past OpenBrace, put in prev_tok
@@ -75,17 +100,123 @@ impl<'inp> Parser<'inp> {
             &format!("Expected {} for loop block", Token::OpenBrace),
         )?;
 
+        if let Some(ctx) = self.loops.last_mut() {
+            ctx.takes_value = false;
+        }
         let loop_blk = self.parse_blk()?.to_block()?;
 
-        // Ok(Decl::ExprStmt(Expr::Bool(true)))
-        let lp = LoopData {
+        Ok(LoopData {
+            label,
             cond: Some(cond),
             body: loop_blk,
+        })
+    }
+
+    // for x in 0..10 { .. } or for x in xs { .. }
+    // Invariant: prev_tok is for
+    pub(crate) fn parse_for(&mut self, label: Option<String>) -> Result<Decl, ParseError> {
+        crate::expect_token_body!(self.lexer.peek(), Ident, "variable name after 'for'")?;
+        let var = Parser::string_from_ident(self.lexer.peek());
+        self.advance();
+
+        self.consume_token_type(Token::In, "Expected 'in' after the variable of a for loop")?;
+        self.advance();
+
+        // the block after the iterable is the body, not a struct expression
+        let prev_no_struct_lit = self.no_struct_lit;
+        self.no_struct_lit = true;
+        let iter = self.parse_iterable();
+        self.no_struct_lit = prev_no_struct_lit;
+        let iter = iter?;
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for for loop block", Token::OpenBrace),
+        )?;
+
+        self.loops.push(LoopCtx {
+            label: label.clone(),
+            takes_value: false,
+            has_value: false,
+        });
+        let body = self.parse_blk();
+        self.loops.pop();
+        let body = body?.to_block()?;
+
+        Ok(Decl::ForStmt(ForData {
+            label,
+            var,
+            iter,
+            body,
+        }))
+    }
+
+    // 'outer: loop { .. } or 'outer: for x in xs { .. }
+    // Invariant: prev_tok is the label
+    pub(crate) fn parse_labeled(&mut self, label: String) -> Result<Decl, ParseError> {
+        let label_tok = Token::Label(label.clone());
+        self.consume_token_type(
+            Token::Colon,
+            &format!("Expected ':' after label {}", label_tok),
+        )?;
+
+        if self.consume_opt_token_type(Token::Loop) {
+            return self.parse_loop(Some(label));
+        }
+        if self.consume_opt_token_type(Token::For) {
+            return self.parse_for(Some(label));
+        }
+
+        let e = format!("Expected loop or for after label {}", label_tok);
+        Err(ParseError::new(&e))
+    }
+
+    // break, break 'outer, break x or break 'outer x. The value goes until the semicolon or the end of the block
+    // Invariant: prev_tok is break
+    pub(crate) fn parse_break(&mut self) -> Result<Decl, ParseError> {
+        let mut label = None;
+        if let Some(Ok(Token::Label(name))) = self.lexer.peek() {
+            label = Some(name.to_owned());
+            self.advance();
+        }
+
+        // the innermost loop, or the innermost one with the label
+        let target = match &label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|ctx| ctx.label.as_ref() == Some(label)),
+            None => self.loops.len().checked_sub(1),
         };
 
-        self.is_loop = prev_is_loop;
+        let Some(target) = target else {
+            let e = match label {
+                Some(label) => format!(
+                    "No loop labelled {} encloses the break",
+                    Token::Label(label)
+                ),
+                None => "break outside of loop".to_string(),
+            };
+            return Err(ParseError::new(&e));
+        };
 
-        Ok(Decl::LoopStmt(lp))
+        let mut value = None;
+        if self.lexer.peek().is_some()
+            && !self.is_peek_token_type(Token::Semi)
+            && !self.is_peek_token_type(Token::CloseBrace)
+        {
+            if !self.loops[target].takes_value {
+                return Err(ParseError::new(
+                    "break with a value can only leave a loop without a condition",
+                ));
+            }
+
+            self.advance();
+            value = Some(self.parse_expr(0)?.to_expr()?);
+            self.loops[target].has_value = true;
+        }
+
+        Ok(Decl::BreakStmt(BreakData { label, value }))
     }
 }
 
@@ -167,7 +298,7 @@ mod tests {
  
          }
          ";
-        test_parse_err(t, "not an expression: 'loop'", true);
+        test_parse_err(t, "loop is not an expression", true);
 
         let t = "loop x < 5";
         test_parse_err(t, " Expected { for loop block", true);
@@ -188,6 +319,39 @@ mod tests {
         test_parse(t, "loop  { 200; };let x = 0;loop (x<5) { x = (x+1); };");
     }
 
+    #[test]
+    fn test_parse_for() {
+        let t = r"
+        for x in 0..n {
+            print(x);
+        }
+        ";
+        test_parse(t, "for x in 0..n { print(x); };");
+
+        let t = r"
+        for c in s[1..] {
+            if c == p {
+                break;
+            }
+        }
+        ";
+        test_parse(t, "for c in s[1..] { if (c==p) { break; }; };");
+
+        // the block after the iterable is the body even after an identifier
+        let t = "for x in xs { x } 2";
+        test_parse(t, "for x in xs { x };2");
+
+        test_parse_err("let x = for x in xs {};", "for is not an expression", true);
+        test_parse_err("for 2 in xs {}", "Expected variable name after 'for'", true);
+        test_parse_err("for x xs {}", "Expected 'in'", true);
+        test_parse_err("for x in xs", "Expected { for for loop block", true);
+        test_parse_err(
+            "fn f() { for x in xs {} break; }",
+            "break outside of loop",
+            true,
+        );
+    }
+
     #[test]
     fn test_parse_loop_nested() {
         let t = r"
@@ -321,4 +485,104 @@ mod tests {
         ";
         test_parse(t, "loop  { let x = if true { break;3 } else { 5 }; };");
     }
+
+    #[test]
+    fn test_parse_labeled_loops() {
+        let t = r"
+        'outer: loop {
+            for x in xs {
+                if x > 2 {
+                    break 'outer;
+                }
+            }
+        }
+        ";
+        test_parse(
+            t,
+            "'outer: loop  { for x in xs { if (x>2) { break 'outer; }; }; };",
+        );
+
+        let t = r"
+        'rows: for r in rows {
+            'cols: loop c < 3 {
+                break 'rows;
+            }
+        }
+        ";
+        test_parse(
+            t,
+            "'rows: for r in rows { 'cols: loop (c<3) { break 'rows; }; };",
+        );
+
+        // break with a value makes the loop an expression
+        let t = r"
+        let x = loop {
+            break 2;
+        };
+        x
+        ";
+        test_parse(t, "let x = loop  { break 2; };x");
+
+        let t = r"
+        let found = 'search: loop {
+            for x in xs {
+                if x > 2 {
+                    break 'search x * 2;
+                }
+            }
+            break 'search 0;
+        };
+        ";
+        test_parse(
+            t,
+            "let found = 'search: loop  { for x in xs { if (x>2) { break 'search (x*2); }; };break 'search 0; };",
+        );
+
+        // as the last expression of a block, and in the middle of one without a semicolon
+        test_parse("loop { break 1; }", "loop  { break 1; }");
+        test_parse("'a: loop { break 'a 1; } 2", "'a: loop  { break 'a 1; };2");
+
+        // the innermost loop with the label is left
+        test_parse(
+            "'a: loop { 'a: loop { break 'a 1; }; }",
+            "'a: loop  { 'a: loop  { break 'a 1; }; };",
+        );
+    }
+
+    #[test]
+    fn test_parse_labeled_loops_err() {
+        test_parse_err(
+            "loop { break 'outer; }",
+            "No loop labelled 'outer encloses the break",
+            true,
+        );
+        test_parse_err(
+            "'outer: loop { fn f() { loop { break 'outer; } } }",
+            "No loop labelled 'outer encloses the break",
+            true,
+        );
+        test_parse_err("break 'a;", "No loop labelled 'a encloses the break", true);
+        test_parse_err(
+            "loop x < 5 { break 2; }",
+            "break with a value can only leave a loop without a condition",
+            true,
+        );
+        test_parse_err(
+            "for x in xs { break x; }",
+            "break with a value can only leave a loop without a condition",
+            true,
+        );
+        test_parse_err(
+            "'a: loop x { loop { break 'a 1; } }",
+            "break with a value can only leave a loop without a condition",
+            true,
+        );
+        test_parse_err("'a loop { }", "Expected ':' after label 'a", true);
+        test_parse_err("'a: if x { }", "Expected loop or for after label 'a", true);
+        test_parse_err(
+            "let x = loop { break; };",
+            "loop is not an expression",
+            true,
+        );
+    }
 }
@@ -0,0 +1,355 @@
+use crate::Decl;
+use crate::Expr;
+use crate::MatchArm;
+use crate::MatchData;
+use crate::ParseError;
+use crate::Parser;
+use crate::Pattern;
+use lexer::Token;
+
+impl<'inp> Parser<'inp> {
+    // match expr { Some(x) if x > 0 => { .. } Some(x) => { .. } None => { .. } }
+    // Invariant: prev_tok is match
+    pub(crate) fn parse_match(&mut self) -> Result<Decl, ParseError> {
+        self.advance();
+        // parse_expr stops at the { of the arms
+        let expr = self.parse_cond(0)?.to_expr()?;
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for match arms", Token::OpenBrace),
+        )?;
+
+        let mut arms: Vec<MatchArm> = vec![];
+
+        while !self.is_peek_token_type(Token::CloseBrace) {
+            let pat = self.parse_pattern()?;
+
+            // the arm is only taken if the guard is also true
+            let mut guard = None;
+            if self.consume_opt_token_type(Token::If) {
+                self.advance();
+                guard = Some(self.parse_expr(0)?.to_expr()?);
+            }
+
+            self.consume_token_type(
+                Token::FatArrow,
+                &format!("Expected '{}' after match pattern", Token::FatArrow),
+            )?;
+            self.consume_token_type(
+                Token::OpenBrace,
+                &format!("Expected {} for match arm block", Token::OpenBrace),
+            )?;
+
+            let blk = self.parse_blk()?.to_block()?;
+            arms.push(MatchArm { pat, guard, blk });
+
+            // arms can optionally be separated by commas
+            self.consume_opt_token_type(Token::Comma);
+        }
+
+        self.consume_token_type(
+            Token::CloseBrace,
+            &format!("Expected {} to close match", Token::CloseBrace),
+        )?;
+
+        if arms.is_empty() {
+            return Err(ParseError::new("match expected at least one arm"));
+        }
+
+        let data = MatchData { expr, arms };
+        Ok(Decl::ExprStmt(Expr::MatchExpr(Box::new(data))))
+    }
+
+    // A pattern with alternatives separated by | e.g Circle(r) | Square(r).
+    // Expects peek to be at the pattern and ends with peek after it
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let mut alts = vec![self.parse_pattern_alt()?];
+        while self.consume_opt_token_type(Token::Or) {
+            alts.push(self.parse_pattern_alt()?);
+        }
+
+        if alts.len() == 1 {
+            return Ok(alts.remove(0));
+        }
+        Ok(Pattern::Or(alts))
+    }
+
+    // _, a name to bind, an int, bool or string literal, a range of ints e.g 1..=9, a name bound to what a pattern
+    // matches e.g n @ 1..=9, a pattern in brackets, or a variant of an option, result or enum with a pattern for the value it holds
+    // e.g Some(x), None, Ok(Circle(r)), Some(0) or Empty.
+    fn parse_pattern_alt(&mut self) -> Result<Pattern, ParseError> {
+        let tok = match self.lexer.peek() {
+            Some(Ok(tok)) => tok.clone(),
+            _ => return Err(ParseError::new("Expected a pattern for match arm")),
+        };
+        self.advance();
+
+        let variant = match tok {
+            Token::Integer(_) | Token::Minus => return self.parse_int_pattern(),
+            Token::Bool(val) => return Ok(Pattern::Literal(Expr::Bool(val))),
+            Token::String(val) => return Ok(Pattern::Literal(Expr::StringLiteral(val))),
+            // alternatives in brackets e.g n @ (1 | 2)
+            Token::OpenParen => {
+                let pat = self.parse_pattern()?;
+                self.consume_token_type(Token::CloseParen, "Expected ')' to close pattern")?;
+                return Ok(pat);
+            }
+            Token::Ident(name) if name == "_" => return Ok(Pattern::Wildcard),
+            Token::Ident(name) if Parser::is_struct_name(&name) => name,
+            Token::Ident(name) => {
+                // foo(x) is neither a name to bind nor a variant
+                if self.is_peek_token_type(Token::OpenParen) {
+                    let e = format!("Expected a variant for match pattern, got '{}'", name);
+                    return Err(ParseError::new(&e));
+                }
+
+                if self.consume_opt_token_type(Token::At) {
+                    let pat = self.parse_pattern_alt()?;
+                    return Ok(Pattern::At(name, Box::new(pat)));
+                }
+                return Ok(Pattern::Bind(name));
+            }
+            tok => {
+                let e = format!("Expected a pattern for match arm, got '{}'", tok);
+                return Err(ParseError::new(&e));
+            }
+        };
+
+        // variants of enums and None may hold nothing
+        let holds_value = matches!(variant.as_str(), "Some" | "Ok" | "Err");
+        if !holds_value && !self.is_peek_token_type(Token::OpenParen) {
+            return Ok(Pattern::Variant(variant, None));
+        }
+
+        self.consume_token_type(
+            Token::OpenParen,
+            &format!("Expected '(' after {} in match pattern", variant),
+        )?;
+        let pat = self.parse_pattern()?;
+        self.consume_token_type(
+            Token::CloseParen,
+            &format!("Expected ')' to close {} in match pattern", variant),
+        )?;
+
+        Ok(Pattern::Variant(variant, Some(Box::new(pat))))
+    }
+
+    // An int literal e.g -1, or a range of ints e.g 1..=9 whose bounds must be int literals, so they are known
+    // when compiling. Invariant: prev_tok is the int or the minus before it
+    fn parse_int_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let start = self.parse_int_bound()?;
+
+        if self.is_peek_token_type(Token::DotDot) {
+            let e = format!(
+                "Range patterns include their end, write {}..=end instead of {}..end",
+                start, start
+            );
+            return Err(ParseError::new(&e));
+        }
+
+        if !self.consume_opt_token_type(Token::DotDotEq) {
+            return Ok(Pattern::Literal(Expr::Integer(start)));
+        }
+
+        self.advance();
+        let end = self.parse_int_bound()?;
+        if end < start {
+            let e = format!("Range pattern {}..={} matches no ints", start, end);
+            return Err(ParseError::new(&e));
+        }
+
+        Ok(Pattern::Range(start, end))
+    }
+
+    // Invariant: prev_tok is the int or the minus before it
+    fn parse_int_bound(&mut self) -> Result<i64, ParseError> {
+        match self.expect_prev_tok()? {
+            Token::Integer(val) => Ok(*val),
+            Token::Minus => match self.lexer.peek() {
+                Some(Ok(Token::Integer(val))) => {
+                    let val = -val;
+                    self.advance();
+                    Ok(val)
+                }
+                _ => Err(ParseError::new("Expected int after '-' in match pattern")),
+            },
+            tok => {
+                let e = format!("Range pattern bounds must be int literals, got '{}'", tok);
+                Err(ParseError::new(&e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+
+    #[test]
+    fn test_parse_match() {
+        let t = r"
+        match x {
+            Some(v) => { v }
+            None => { 0 }
+        }
+        ";
+        test_parse(t, "match x { Some(v) => { v } None => { 0 } }");
+
+        let t = r"
+        let y = match f(2) {
+            Ok(v) => { println(v); v },
+            Err(e) => { 0 },
+        };
+        y
+        ";
+        test_parse(
+            t,
+            "let y = match f(2) { Ok(v) => { println(v);v } Err(e) => { 0 } };y",
+        );
+
+        // in the middle of a block without semicolon
+        let t = r"
+        match x {
+            None => { }
+        }
+        2
+        ";
+        test_parse(t, "match x { None => {  } };2");
+
+        // variants of enums, which may hold nothing
+        let t = r"
+        match shape {
+            Circle(r) => { r * r }
+            Empty => { 0 }
+        }
+        ";
+        test_parse(t, "match shape { Circle(r) => { (r*r) } Empty => { 0 } }");
+
+        // guards
+        let t = r"
+        match x {
+            Some(n) if n > 0 && n < 10 => { n }
+            Some(n) => { 0 }
+            None if flag => { 1 }
+            None => { 2 }
+        }
+        ";
+        test_parse(
+            t,
+            "match x { Some(n) if ((n>0)&&(n<10)) => { n } Some(n) => { 0 } None if flag => { 1 } None => { 2 } }",
+        );
+
+        // nested patterns, literals, bindings and alternatives
+        let t = r#"
+        match x {
+            Some(Circle(r)) | Some(Square(r)) => { r }
+            Some(Ok(0 | -1)) => { 1 }
+            Some(Err("oops")) | Some(Empty) => { 2 }
+            Some(true) => { 3 }
+            Some(_) => { 4 }
+            other => { 5 }
+        }
+        "#;
+        test_parse(
+            t,
+            r#"match x { Some(Circle(r)) | Some(Square(r)) => { r } Some(Ok(0 | -1)) => { 1 } Some(Err("oops")) | Some(Empty) => { 2 } Some(true) => { 3 } Some(_) => { 4 } other => { 5 } }"#,
+        );
+
+        // ranges, and names bound to what a pattern matches
+        let t = r"
+        match x {
+            -9..=-1 => { 0 }
+            0 | 1..=9 => { 1 }
+            n @ 10..=99 => { n }
+            n @ _ => { n }
+        }
+        ";
+        test_parse(
+            t,
+            "match x { -9..=-1 => { 0 } 0 | 1..=9 => { 1 } n @ 10..=99 => { n } n @ _ => { n } }",
+        );
+        test_parse(
+            "match x { n @ (1 | 2) | n @ 3 => { n } }",
+            "match x { n @ (1 | 2) | n @ 3 => { n } }",
+        );
+    }
+
+    #[test]
+    fn test_parse_try() {
+        test_parse("f(2)?", "f(2)?");
+        test_parse("let x = y? + 1;", "let x = (y?+1);");
+        test_parse("-x?", "(-x?)");
+        test_parse("f(g()?)?", "f(g()?)?");
+    }
+
+    #[test]
+    fn test_parse_match_err() {
+        test_parse_err("match x { }", "match expected at least one arm", true);
+        test_parse_err(
+            "match x { + => { } }",
+            "Expected a pattern for match arm, got '+'",
+            true,
+        );
+        test_parse_err(
+            "match x { foo(v) => { } }",
+            "Expected a variant for match pattern, got 'foo'",
+            true,
+        );
+        test_parse_err(
+            "match x { Circle(+) => { } }",
+            "Expected a pattern for match arm, got '+'",
+            true,
+        );
+        test_parse_err(
+            "match x { Circle(r | ) => { } }",
+            "Expected a pattern for match arm, got ')'",
+            true,
+        );
+        test_parse_err(
+            "match x { -x => { } }",
+            "Expected int after '-' in match pattern",
+            true,
+        );
+        test_parse_err(
+            "match x { 1..=n => { } }",
+            "Range pattern bounds must be int literals, got 'n'",
+            true,
+        );
+        test_parse_err(
+            "match x { 1..=2.5 => { } }",
+            "Range pattern bounds must be int literals, got '2.5'",
+            true,
+        );
+        test_parse_err(
+            "match x { 9..=1 => { } }",
+            "Range pattern 9..=1 matches no ints",
+            true,
+        );
+        test_parse_err(
+            "match x { 1..9 => { } }",
+            "Range patterns include their end, write 1..=end instead of 1..end",
+            true,
+        );
+        test_parse_err(
+            "match x { Some => { } }",
+            "Expected '(' after Some in match pattern",
+            true,
+        );
+        test_parse_err(
+            "match x { None { } }",
+            "Expected '=>' after match pattern",
+            true,
+        );
+        test_parse_err(
+            "match x { None if => { } }",
+            "Unexpected token - not an expression: '=>'",
+            true,
+        );
+        test_parse_err(
+            "match x { None => 2 }",
+            "Expected { for match arm block",
+            true,
+        );
+    }
+}
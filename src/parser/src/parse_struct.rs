@@ -0,0 +1,275 @@
+use crate::Decl;
+use crate::Expr;
+use crate::FnDeclData;
+use crate::ImplData;
+use crate::ParseError;
+use crate::Parser;
+use crate::StructDeclData;
+use crate::StructExprData;
+use crate::Type;
+use lexer::Token;
+
+impl<'inp> Parser<'inp> {
+    /// Struct names start with an uppercase letter, which tells struct expressions e.g Point { x: 1 }
+    /// apart from a variable followed by a block
+    pub(crate) fn is_struct_name(name: &str) -> bool {
+        name.starts_with(|c: char| c.is_ascii_uppercase())
+    }
+
+    pub(crate) fn expect_top_level(&self, what: &str) -> Result<(), ParseError> {
+        if self.is_top_level {
+            Ok(())
+        } else {
+            let e = format!("{} can only be declared at the top level", what);
+            Err(ParseError::new(&e))
+        }
+    }
+
+    fn parse_struct_name(&mut self) -> Result<String, ParseError> {
+        crate::expect_token_body!(self.lexer.peek(), Ident, "struct name")?;
+        let name = Parser::string_from_ident(self.lexer.peek());
+        self.advance();
+
+        if !Parser::is_struct_name(&name) {
+            let e = format!("Struct name '{}' must start with an uppercase letter", name);
+            return Err(ParseError::new(&e));
+        }
+
+        Ok(name)
+    }
+
+    // struct Point { x: int, y: int }
+    // Invariant: prev_tok is struct
+    pub(crate) fn parse_struct_decl(&mut self) -> Result<Decl, ParseError> {
+        self.expect_top_level("Structs")?;
+        let name = self.parse_struct_name()?;
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for struct fields", Token::OpenBrace),
+        )?;
+
+        let mut fields: Vec<(String, Type)> = vec![];
+        while !self.is_peek_token_type(Token::CloseBrace) {
+            crate::expect_token_body!(self.lexer.peek(), Ident, "field name")?;
+            let field = Parser::string_from_ident(self.lexer.peek());
+            self.advance();
+
+            if fields.iter().any(|(prev, _)| *prev == field) {
+                let e = format!(
+                    "Field '{}' declared more than once in struct {}",
+                    field, name
+                );
+                return Err(ParseError::new(&e));
+            }
+
+            self.consume_token_type(Token::Colon, "Expected ':' for the type of the field")?;
+            let ty = self.parse_type_annotation()?;
+            fields.push((field, ty));
+
+            if !self.is_peek_token_type(Token::CloseBrace) {
+                self.consume_token_type(Token::Comma, "Expected ',' to separate struct fields")?;
+            }
+        }
+
+        self.consume_token_type(
+            Token::CloseBrace,
+            &format!("Expected {} to close struct fields", Token::CloseBrace),
+        )?;
+
+        Ok(Decl::StructDeclStmt(StructDeclData { name, fields }))
+    }
+
+    // impl Point { fn norm(self) -> int { .. } }
+    // Invariant: prev_tok is impl
+    pub(crate) fn parse_impl(&mut self) -> Result<Decl, ParseError> {
+        self.expect_top_level("impl blocks")?;
+        let name = self.parse_struct_name()?;
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for impl block", Token::OpenBrace),
+        )?;
+
+        let mut methods = vec![];
+        while !self.is_peek_token_type(Token::CloseBrace) {
+            self.consume_token_type(Token::Fn, "Expected fn for method in impl block")?;
+
+            let Decl::FnDeclStmt(mut method) = self.parse_fn_decl()? else {
+                unreachable!("parse_fn_decl gives a fn decl");
+            };
+
+            // self is the struct the method is called on
+            match method.params.first_mut() {
+                Some(param) if param.name == "self" && param.type_ann.is_none() => {
+                    param.type_ann = Some(Type::Named(name.clone()));
+                }
+                _ => {
+                    let e = format!(
+                        "Method '{}' must take self as its first parameter",
+                        method.name
+                    );
+                    return Err(ParseError::new(&e));
+                }
+            }
+
+            if methods
+                .iter()
+                .any(|prev: &FnDeclData| prev.name == method.name)
+            {
+                let e = format!(
+                    "Method '{}' declared more than once for {}",
+                    method.name, name
+                );
+                return Err(ParseError::new(&e));
+            }
+
+            methods.push(method);
+        }
+
+        self.consume_token_type(
+            Token::CloseBrace,
+            &format!("Expected {} to close impl block", Token::CloseBrace),
+        )?;
+
+        Ok(Decl::ImplStmt(ImplData { name, methods }))
+    }
+
+    // Point { x: 1, y: 2 } or Point { x: 1, ..p }
+    // Invariant: prev_tok is the struct name and peek is at {
+    pub(crate) fn parse_struct_expr(&mut self, name: String) -> Result<Decl, ParseError> {
+        self.consume_token_type(Token::OpenBrace, "Expected '{' for struct fields")?;
+
+        let mut fields: Vec<(String, Expr)> = vec![];
+        let mut base: Option<Box<Expr>> = None;
+
+        while !self.is_peek_token_type(Token::CloseBrace) {
+            // ..p takes the fields not given from p, and must come last
+            if self.consume_opt_token_type(Token::DotDot) {
+                self.advance();
+                base.replace(Box::new(self.parse_expr(0)?.to_expr()?));
+                break;
+            }
+
+            crate::expect_token_body!(self.lexer.peek(), Ident, "field name")?;
+            let field = Parser::string_from_ident(self.lexer.peek());
+            self.advance();
+
+            if fields.iter().any(|(prev, _)| *prev == field) {
+                let e = format!("Field '{}' given more than once", field);
+                return Err(ParseError::new(&e));
+            }
+
+            self.consume_token_type(Token::Colon, "Expected ':' for the value of the field")?;
+            self.advance();
+            let expr = self.parse_expr(0)?.to_expr()?;
+            fields.push((field, expr));
+
+            if !self.is_peek_token_type(Token::CloseBrace) {
+                self.consume_token_type(Token::Comma, "Expected ',' to separate struct fields")?;
+            }
+        }
+
+        self.consume_token_type(
+            Token::CloseBrace,
+            &format!("Expected {} to close struct expression", Token::CloseBrace),
+        )?;
+
+        let data = StructExprData { name, fields, base };
+        Ok(Decl::ExprStmt(Expr::StructExpr(data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{test_parse, test_parse_err};
+
+    #[test]
+    fn test_parse_struct_decl() {
+        let t = "struct Point { x: int, y: int }";
+        test_parse(t, "struct Point { x:int, y:int };");
+
+        let t = "struct Line { from: Point, to: Point, } struct Unit {}";
+        test_parse(t, "struct Line { from:Point, to:Point };struct Unit {  };");
+
+        test_parse_err(
+            "struct point { x: int }",
+            "Struct name 'point' must start with an uppercase letter",
+            true,
+        );
+        test_parse_err(
+            "struct Point { x: int, x: int }",
+            "Field 'x' declared more than once in struct Point",
+            true,
+        );
+        test_parse_err(
+            "fn f() { struct Point { x: int } }",
+            "Structs can only be declared at the top level",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_impl() {
+        let t = "impl Point { fn norm(self) -> int { self.x * self.x } fn add(self, o: Point) {} }";
+        test_parse(
+            t,
+            "impl Point { fn norm (self:Point) -> int { (self.x*self.x) };fn add (self:Point, o:Point) {  }; };",
+        );
+
+        test_parse_err(
+            "impl Point { fn new(x: int) {} }",
+            "Method 'new' must take self as its first parameter",
+            true,
+        );
+        test_parse_err(
+            "impl Point { fn f(self) {} fn f(self) {} }",
+            "Method 'f' declared more than once for Point",
+            true,
+        );
+        test_parse_err(
+            "{ impl Point {} }",
+            "impl blocks can only be declared at the top level",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_struct_expr() {
+        test_parse("Point { x: 1, y: 2 + 3 }", "Point { x:1, y:(2+3) }");
+        test_parse(
+            "let p = Point { x: 1, ..q };",
+            "let p = Point { x:1, ..q };",
+        );
+        test_parse("Point { x: 1 }.x", "Point { x:1 }.x");
+        test_parse("f(Point {})", "f(Point {  })");
+
+        // the { of a lowercase name or a condition starts a block
+        test_parse("if P { 1 } else { 2 }", "if P { 1 } else { 2 }");
+        test_parse("loop x < P { y = 2; }", "loop (x<P) { y = 2; };");
+        test_parse(
+            "if p == (P { x: 1 }) { 1 } else { 2 }",
+            "if (p==P { x:1 }) { 1 } else { 2 }",
+        );
+
+        test_parse_err(
+            "Point { x: 1, x: 2 }",
+            "Field 'x' given more than once",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        test_parse("p.x + p.pos.y", "(p.x+p.pos.y)");
+        test_parse("p.pos.norm().x", "p.pos.norm().x");
+        test_parse("p.x = 2;", "p.x = 2;");
+        test_parse("p.pos.x = p.x + 1;", "p.pos.x = (p.x+1);");
+
+        test_parse_err(
+            "p.f().x = 2;",
+            "Only fields of a variable can be assigned to",
+            true,
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::Decl;
+use crate::Expr;
+use crate::ParseError;
+use crate::Parser;
+use crate::TryCatchData;
+use lexer::Token;
+
+impl<'inp> Parser<'inp> {
+    // try { .. } catch e { .. }
+    // Invariant: prev_tok is try
+    pub(crate) fn parse_try_catch(&mut self) -> Result<Decl, ParseError> {
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for try block", Token::OpenBrace),
+        )?;
+        let try_blk = self.parse_blk()?.to_block()?;
+
+        self.consume_token_type(
+            Token::Catch,
+            &format!("Expected '{}' after try block", Token::Catch),
+        )?;
+        crate::expect_token_body!(
+            self.lexer.peek(),
+            Ident,
+            "identifier to bind the error to after catch"
+        )?;
+        let err = Parser::string_from_ident(self.lexer.peek());
+        self.advance();
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for catch block", Token::OpenBrace),
+        )?;
+        let catch_blk = self.parse_blk()?.to_block()?;
+
+        let data = TryCatchData {
+            try_blk,
+            err,
+            catch_blk,
+        };
+        Ok(Decl::ExprStmt(Expr::TryCatchExpr(Box::new(data))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+
+    #[test]
+    fn test_parse_try_catch() {
+        let t = r"
+        try {
+            f(2)
+        } catch e {
+            println(e);
+            0
+        }
+        ";
+        test_parse(t, "try { f(2) } catch e { println(e);0 }");
+
+        let t = r"
+        let x = 1 + try { let y = f(); y } catch err { 2 };
+        try { g(); } catch e { }
+        x
+        ";
+        test_parse(
+            t,
+            "let x = (1+try { let y = f();y } catch err { 2 });try { g(); } catch e {  };x",
+        );
+    }
+
+    #[test]
+    fn test_parse_try_catch_err() {
+        test_parse_err("try f() catch e { }", "Expected { for try block", true);
+        test_parse_err("try { } e { }", "Expected 'catch' after try block", true);
+        test_parse_err(
+            "try { } catch { }",
+            "Expected identifier to bind the error to after catch",
+            true,
+        );
+        test_parse_err("try { } catch e 2", "Expected { for catch block", true);
+    }
+}
@@ -22,11 +22,62 @@ impl<'inp> Parser<'inp> {
             .expect("Lexer should not fail"); // would have erred earlier
 
         let type_ann = match peek {
+            // Option<T>, Result<T, E>, Generator<T>, tid<T>, Channel<T>, BoundedQueue<T>
+            Token::Ident(id)
+                if id == "Option"
+                    || id == "Result"
+                    || id == "Generator"
+                    || id == "tid"
+                    || id == "Channel"
+                    || id == "BoundedQueue" =>
+            {
+                self.advance();
+                self.consume_token_type(
+                    Token::Lt,
+                    &format!("Expected '<' after {} in type annotation", id),
+                )?;
+
+                let ty = self.parse_type_annotation()?;
+                let ty = if id == "Option" {
+                    Type::Option(Box::new(ty))
+                } else if id == "Generator" {
+                    Type::Generator(Box::new(ty))
+                } else if id == "tid" {
+                    Type::ThreadId(Box::new(ty))
+                } else if id == "Channel" {
+                    Type::Channel(Box::new(ty))
+                } else if id == "BoundedQueue" {
+                    Type::BoundedQueue(Box::new(ty))
+                } else {
+                    self.consume_token_type(
+                        Token::Comma,
+                        "Expected ',' to separate the types of Result",
+                    )?;
+                    let err_ty = self.parse_type_annotation()?;
+                    Type::Result(Box::new(ty), Box::new(err_ty))
+                };
+
+                self.consume_token_type(
+                    Token::Gt,
+                    &format!("Expected '>' to close {} in type annotation", id),
+                )?;
+                Ok(ty)
+            }
             Token::Ident(id) => {
                 let res = Type::from_string(&id);
                 self.advance();
                 res
             }
+            // [T]
+            Token::OpenBracket => {
+                self.advance();
+                let ty = self.parse_type_annotation()?;
+                self.consume_token_type(
+                    Token::CloseBracket,
+                    "Expected ']' to close array type annotation",
+                )?;
+                Ok(Type::Array(Box::new(ty)))
+            }
             Token::OpenParen => {
                 self.advance();
                 if let Some(Ok(Token::CloseParen)) = self.lexer.peek() {
@@ -105,6 +156,34 @@ mod tests {
         test_parse("let x : () = true;", "let x : () = true;");
         test_parse(r"let x : str = 2;", "let x : str = 2;");
         test_parse("let x : sem = 2;", "let x : sem = 2;");
+        test_parse("let x : Option<int> = 2;", "let x : Option<int> = 2;");
+        test_parse(
+            "let x : Result<Option<float>, str> = 2;",
+            "let x : Result<Option<float>, str> = 2;",
+        );
+        test_parse(
+            "fn f(x: Option<int>) -> Result<int, str> { }",
+            "fn f (x:Option<int>) -> Result<int, str> {  };",
+        );
+    }
+
+    #[test]
+    fn test_parse_type_annotations_errs_generic() {
+        test_parse_err(
+            "let x : Option = 2;",
+            "Expected '<' after Option in type annotation",
+            true,
+        );
+        test_parse_err(
+            "let x : Result<int> = 2;",
+            "Expected ',' to separate the types of Result",
+            true,
+        );
+        test_parse_err(
+            "let x : Option<int = 2;",
+            "Expected '>' to close Option in type annotation",
+            true,
+        );
     }
 
     #[test]
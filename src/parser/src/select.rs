@@ -0,0 +1,203 @@
+use crate::Decl;
+use crate::Expr;
+use crate::ParseError;
+use crate::Parser;
+use crate::SelectArm;
+use crate::SelectData;
+use crate::SelectOp;
+use lexer::Token;
+
+impl<'inp> Parser<'inp> {
+    // select { sem => { .. } recv(ch1) -> x => { .. } send(ch2, 1) -> sent => { .. } }
+    // Invariant: prev_tok is select
+    pub(crate) fn parse_select(&mut self) -> Result<Decl, ParseError> {
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for select", Token::OpenBrace),
+        )?;
+
+        let mut arms: Vec<SelectArm> = vec![];
+
+        while !self.is_peek_token_type(Token::CloseBrace) {
+            crate::expect_token_body!(
+                self.lexer.peek(),
+                Ident,
+                "semaphore variable for select arm"
+            )?;
+            let name = Parser::string_from_ident(self.lexer.peek());
+            self.advance();
+
+            // recv and send are only operations on a channel when called, so they can still name semaphores
+            let op = match name.as_str() {
+                "recv" | "send" if self.consume_opt_token_type(Token::OpenParen) => {
+                    self.parse_select_chan_op(&name)?
+                }
+                _ => SelectOp::Wait(name),
+            };
+
+            // the result of a recv or send can be bound for the arm block
+            let mut bind = None;
+            if !matches!(op, SelectOp::Wait(_)) && self.consume_opt_token_type(Token::FnDeclReturn)
+            {
+                crate::expect_token_body!(
+                    self.lexer.peek(),
+                    Ident,
+                    "identifier for the result of select arm"
+                )?;
+                bind = Some(Parser::string_from_ident(self.lexer.peek()));
+                self.advance();
+            }
+
+            self.consume_token_type(
+                Token::FatArrow,
+                &format!("Expected '{}' after select arm", Token::FatArrow),
+            )?;
+            self.consume_token_type(
+                Token::OpenBrace,
+                &format!("Expected {} for select arm block", Token::OpenBrace),
+            )?;
+
+            let blk = self.parse_blk()?.to_block()?;
+            arms.push(SelectArm { op, bind, blk });
+
+            // arms can optionally be separated by commas
+            self.consume_opt_token_type(Token::Comma);
+        }
+
+        self.consume_token_type(
+            Token::CloseBrace,
+            &format!("Expected {} to close select", Token::CloseBrace),
+        )?;
+
+        if arms.is_empty() {
+            return Err(ParseError::new("select expected at least one arm"));
+        }
+
+        Ok(Decl::ExprStmt(Expr::SelectExpr(SelectData { arms })))
+    }
+
+    // recv(ch) or send(ch, val) of a select arm
+    // Invariant: prev_tok is the ( after recv or send
+    fn parse_select_chan_op(&mut self, name: &str) -> Result<SelectOp, ParseError> {
+        crate::expect_token_body!(self.lexer.peek(), Ident, "channel variable for select arm")?;
+        let ch = Parser::string_from_ident(self.lexer.peek());
+        self.advance();
+
+        let op = if name == "send" {
+            self.consume_token_type(
+                Token::Comma,
+                &format!("Expected {} after channel of send arm", Token::Comma),
+            )?;
+            self.advance();
+            SelectOp::Send(ch, self.parse_expr(0)?.to_expr()?)
+        } else {
+            SelectOp::Recv(ch)
+        };
+
+        self.consume_token_type(
+            Token::CloseParen,
+            &format!("Expected {} to close {} arm", Token::CloseParen, name),
+        )?;
+
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+
+    #[test]
+    fn test_parse_select() {
+        let t = r"
+        select {
+            a => { 1 }
+            b => { 2 }
+        }
+        ";
+        test_parse(t, "select { a => { 1 } b => { 2 } }");
+
+        let t = r"
+        let x = select {
+            a => { println(1); 1 },
+            b => { 2 },
+        };
+        x
+        ";
+        test_parse(t, "let x = select { a => { println(1);1 } b => { 2 } };x");
+
+        // in the middle of a block without semicolon
+        let t = r"
+        select {
+            a => { }
+        }
+        2
+        ";
+        test_parse(t, "select { a => {  } };2");
+    }
+
+    #[test]
+    fn test_parse_select_chan() {
+        let t = r"
+        select {
+            recv(ch) -> x => { x }
+            send(out, 1 + 2) -> sent => { sent }
+            a => { }
+        }
+        ";
+        test_parse(
+            t,
+            "select { recv(ch) -> x => { x } send(out, (1+2)) -> sent => { sent } a => {  } }",
+        );
+
+        // the result doesn't have to be bound
+        let t = r"
+        select {
+            recv(ch) => { 1 },
+            send(ch, 2) => { 2 },
+        }
+        ";
+        test_parse(t, "select { recv(ch) => { 1 } send(ch, 2) => { 2 } }");
+
+        // recv and send that aren't called are semaphores
+        test_parse("select { recv => { } }", "select { recv => {  } }");
+    }
+
+    #[test]
+    fn test_parse_select_err() {
+        test_parse_err("select { }", "select expected at least one arm", true);
+        test_parse_err(
+            "select { 2 => { } }",
+            "Expected semaphore variable for select arm",
+            true,
+        );
+        test_parse_err("select { a { } }", "Expected '=>' after select arm", true);
+        test_parse_err("select { a => 2 }", "Expected { for select arm block", true);
+        test_parse_err("select a", "Expected { for select", true);
+        test_parse_err(
+            "select { recv(2) => { } }",
+            "Expected channel variable for select arm",
+            true,
+        );
+        test_parse_err(
+            "select { send(ch) => { } }",
+            "Expected , after channel of send arm",
+            true,
+        );
+        test_parse_err(
+            "select { recv(ch, 1) => { } }",
+            "Expected ) to close recv arm",
+            true,
+        );
+        test_parse_err(
+            "select { recv(ch) -> 1 => { } }",
+            "Expected identifier for the result of select arm",
+            true,
+        );
+        test_parse_err(
+            "select { a -> x => { } }",
+            "Expected '=>' after select arm",
+            true,
+        );
+    }
+}
@@ -11,6 +11,8 @@ impl<'inp> Parser<'inp> {
         let mut decls: Vec<Decl> = vec![];
         let mut symbols: Vec<String> = vec![];
         let mut last_expr: Option<Expr> = None;
+        let mut decl_toks: Vec<usize> = vec![];
+        let mut last_expr_tok: Option<usize> = None;
 
         while self.lexer.peek().is_some() {
             // parsing a block: break so parse_blk can consume CloseBrace
@@ -20,6 +22,7 @@ impl<'inp> Parser<'inp> {
 
             self.advance();
             // dbg!("prev_tok:", &self.prev_tok);
+            let tok = self.consumed - 1;
 
             let expr = self.parse_decl()?;
 
@@ -28,6 +31,16 @@ impl<'inp> Parser<'inp> {
                 symbols.push(data.name.to_owned());
             }
 
+            // Struct names are bound to their type
+            if let Decl::StructDeclStmt(ref data) = expr {
+                symbols.push(data.name.to_owned());
+            }
+
+            // Enum variants are bound to their constructors, or to the value for variants that hold nothing
+            if let Decl::EnumDeclStmt(ref data) = expr {
+                symbols.extend(data.variants.iter().map(|(variant, _)| variant.to_owned()));
+            }
+
             // if ends with semicolon: statement, advance past semi
             if self.is_peek_token_type(Token::Semi) {
                 // parse_let doesn't consume the semicolon but does check peek for Semi, so we will definitely run this if expr was let
@@ -37,7 +50,12 @@ impl<'inp> Parser<'inp> {
                     symbols.push(stmt.ident.to_owned());
                 }
 
+                if let Decl::LetDestructureStmt(ref stmt) = expr {
+                    symbols.extend(stmt.pat.names().into_iter().map(String::from));
+                }
+
                 decls.push(expr);
+                decl_toks.push(tok);
 
                 self.advance();
                 continue;
@@ -48,6 +66,7 @@ impl<'inp> Parser<'inp> {
                 let to_expr = expr.to_expr();
                 if to_expr.is_ok() {
                     last_expr.replace(to_expr?);
+                    last_expr_tok = Some(tok);
                     break;
                 }
             }
@@ -61,6 +80,7 @@ impl<'inp> Parser<'inp> {
                 .unwrap_or(false)
             {
                 decls.push(expr);
+                decl_toks.push(tok);
             }
             // Syntax error
             else {
@@ -72,6 +92,8 @@ impl<'inp> Parser<'inp> {
             decls,
             last_expr: last_expr.map(Rc::new),
             symbols,
+            decl_toks,
+            last_expr_tok,
         })
     }
 }
@@ -1,16 +1,21 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
 use lexer::Token;
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum BinOpType {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
     Gt,
     Lt,
+    Ge,
+    Le,
     LogicalEq,
     LogicalAnd,
     LogicalOr,
@@ -23,8 +28,11 @@ impl BinOpType {
             Token::Minus => Ok(Self::Sub),
             Token::Star => Ok(Self::Mul),
             Token::Slash => Ok(Self::Div),
+            Token::Percent => Ok(Self::Mod),
             Token::Gt => Ok(Self::Gt),
             Token::Lt => Ok(Self::Lt),
+            Token::Ge => Ok(Self::Ge),
+            Token::Le => Ok(Self::Le),
             Token::LogEq => Ok(Self::LogicalEq),
             Token::LogAnd => Ok(Self::LogicalAnd),
             Token::LogOr => Ok(Self::LogicalOr),
@@ -43,8 +51,11 @@ impl Display for BinOpType {
             BinOpType::Sub => "-",
             BinOpType::Mul => "*",
             BinOpType::Div => "/",
+            BinOpType::Mod => "%",
             BinOpType::Lt => "<",
             BinOpType::Gt => ">",
+            BinOpType::Le => "<=",
+            BinOpType::Ge => ">=",
             BinOpType::LogicalEq => "==",
             BinOpType::LogicalAnd => "&&",
             BinOpType::LogicalOr => "||",
@@ -53,7 +64,7 @@ impl Display for BinOpType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum UnOpType {
     Negate,
     Not,
@@ -71,15 +82,24 @@ impl Display for UnOpType {
 }
 
 // Function call
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FnCallData {
     pub name: String,
     pub args: Vec<Expr>,
+    // Names of the trailing args passed by name e.g draw(1, y: 2), in the order they were written
+    pub names: Vec<String>,
 }
 
 impl Display for FnCallData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let args: Vec<String> = self.args.iter().map(|x| x.to_string()).collect();
+        let positional = self.args.len() - self.names.len();
+        let mut args: Vec<String> = self.args[..positional]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        for (name, arg) in self.names.iter().zip(self.args[positional..].iter()) {
+            args.push(format!("{}:{}", name, arg));
+        }
         let args = args.join(",");
 
         let s = format!("{}({})", self.name, args);
@@ -88,8 +108,270 @@ impl Display for FnCallData {
     }
 }
 
+// Method call e.g h.is_finished()
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodCallData {
+    pub recv: Box<Expr>,
+    pub method: String,
+    pub args: Vec<Expr>,
+}
+
+impl Display for MethodCallData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|x| x.to_string()).collect();
+        let args = args.join(",");
+
+        write!(f, "{}.{}({})", self.recv, self.method, args)
+    }
+}
+
+// What an arm of a select waits for
+#[derive(Debug, Clone, Serialize)]
+pub enum SelectOp {
+    // sem => { .. } acquires the semaphore
+    Wait(String),
+    // recv(ch) -> x => { .. } receives from the channel, x is None once it is closed and empty
+    Recv(String),
+    // send(ch, val) -> sent => { .. } sends the value to the channel, sent is false if it is closed
+    Send(String, Expr),
+}
+
+impl Display for SelectOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectOp::Wait(sem) => write!(f, "{}", sem),
+            SelectOp::Recv(ch) => write!(f, "recv({})", ch),
+            SelectOp::Send(ch, val) => write!(f, "send({}, {})", ch, val),
+        }
+    }
+}
+
+// Arm of a select e.g sem => { .. } or recv(ch) -> x => { .. }, binding the result of a recv or send if named
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectArm {
+    pub op: SelectOp,
+    pub bind: Option<String>,
+    pub blk: BlockSeq,
+}
+
+impl Display for SelectArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.bind {
+            Some(bind) => write!(f, "{} -> {} => {{ {} }}", self.op, bind, self.blk),
+            None => write!(f, "{} => {{ {} }}", self.op, self.blk),
+        }
+    }
+}
+
+// select blocks until one of the arms is ready, then runs that arm
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectData {
+    pub arms: Vec<SelectArm>,
+}
+
+impl Display for SelectData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arms: Vec<String> = self.arms.iter().map(|x| x.to_string()).collect();
+        write!(f, "select {{ {} }}", arms.join(" "))
+    }
+}
+
+// Pattern of a match arm, which can be nested e.g Some(Circle(r)) and have alternatives e.g 1 | 2 | 3
+#[derive(Debug, Clone, Serialize)]
+pub enum Pattern {
+    // _ matches any value without binding it
+    Wildcard,
+    // matches any value, binding it to the name
+    Bind(String),
+    // int, bool or string literal, matching equal values
+    Literal(Expr),
+    // ints from the start up to and including the end e.g 1..=9, whose bounds are int literals
+    Range(i64, i64),
+    // matches what the pattern does, binding the value to the name e.g n @ 10..=20
+    At(String, Box<Pattern>),
+    // variant of an option, result or enum with a pattern for the value it holds if any e.g Some(x) or Empty
+    Variant(String, Option<Box<Pattern>>),
+    // matches if any of the alternatives does e.g Circle(r) | Square(r)
+    Or(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Names the pattern binds, in the order they appear.
+    /// Alternatives bind the same names, so those of the first are taken.
+    pub fn bindings(&self) -> Vec<String> {
+        match self {
+            Pattern::Wildcard
+            | Pattern::Literal(_)
+            | Pattern::Range(..)
+            | Pattern::Variant(_, None) => vec![],
+            Pattern::Bind(name) => vec![name.to_string()],
+            Pattern::At(name, pat) => {
+                let mut names = vec![name.to_string()];
+                names.extend(pat.bindings());
+                names
+            }
+            Pattern::Variant(_, Some(pat)) => pat.bindings(),
+            Pattern::Or(alts) => alts.first().map(|pat| pat.bindings()).unwrap_or_default(),
+        }
+    }
+
+    /// Whether the pattern matches every value
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Bind(_) => true,
+            Pattern::Literal(_) | Pattern::Range(..) | Pattern::Variant(..) => false,
+            Pattern::At(_, pat) => pat.is_irrefutable(),
+            Pattern::Or(alts) => alts.iter().any(|pat| pat.is_irrefutable()),
+        }
+    }
+
+    /// The alternatives of the pattern, or the pattern itself if it has none.
+    /// Names bound with @ are left out, leaving the values the alternatives match.
+    pub fn alternatives(&self) -> Vec<&Pattern> {
+        match self {
+            Pattern::Or(alts) => alts.iter().flat_map(|pat| pat.alternatives()).collect(),
+            Pattern::At(_, pat) => pat.alternatives(),
+            pat => vec![pat],
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Bind(name) => write!(f, "{}", name),
+            Pattern::Literal(Expr::StringLiteral(lit)) => write!(f, "\"{}\"", lit),
+            Pattern::Literal(lit) => write!(f, "{}", lit),
+            Pattern::Range(start, end) => write!(f, "{}..={}", start, end),
+            Pattern::At(name, pat) if matches!(**pat, Pattern::Or(_)) => {
+                write!(f, "{} @ ({})", name, pat)
+            }
+            Pattern::At(name, pat) => write!(f, "{} @ {}", name, pat),
+            Pattern::Variant(variant, Some(pat)) => write!(f, "{}({})", variant, pat),
+            Pattern::Variant(variant, None) => write!(f, "{}", variant),
+            Pattern::Or(alts) => {
+                let alts: Vec<String> = alts.iter().map(|pat| pat.to_string()).collect();
+                write!(f, "{}", alts.join(" | "))
+            }
+        }
+    }
+}
+
+// Arm of a match e.g Some(x) => { .. }, with an optional guard e.g Some(x) if x > 0 => { .. }
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchArm {
+    pub pat: Pattern,
+    pub guard: Option<Expr>,
+    pub blk: BlockSeq,
+}
+
+impl Display for MatchArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.guard {
+            Some(guard) => write!(f, "{} if {} => {{ {} }}", self.pat, guard, self.blk),
+            None => write!(f, "{} => {{ {} }}", self.pat, self.blk),
+        }
+    }
+}
+
+// match runs the first arm whose pattern matches the value
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchData {
+    pub expr: Expr,
+    pub arms: Vec<MatchArm>,
+}
+
+impl Display for MatchData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arms: Vec<String> = self.arms.iter().map(|x| x.to_string()).collect();
+        write!(f, "match {} {{ {} }}", self.expr, arms.join(" "))
+    }
+}
+
+// try runs the block, and the catch block with the error message bound to err if a runtime error occurs in it
+#[derive(Debug, Clone, Serialize)]
+pub struct TryCatchData {
+    pub try_blk: BlockSeq,
+    pub err: String,
+    pub catch_blk: BlockSeq,
+}
+
+impl Display for TryCatchData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "try {{ {} }} catch {} {{ {} }}",
+            self.try_blk, self.err, self.catch_blk
+        )
+    }
+}
+
+// Struct expression e.g Point { x: 1, y: 2 }, or Point { x: 1, ..p } to take the other fields from p
+#[derive(Debug, Clone, Serialize)]
+pub struct StructExprData {
+    pub name: String,
+    pub fields: Vec<(String, Expr)>,
+    pub base: Option<Box<Expr>>,
+}
+
+impl Display for StructExprData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, expr)| format!("{}:{}", name, expr))
+            .collect();
+        if let Some(base) = &self.base {
+            fields.push(format!("..{}", base));
+        }
+
+        write!(f, "{} {{ {} }}", self.name, fields.join(", "))
+    }
+}
+
+// What a comprehension or for loop iterates over: the ints of a range lo..hi (excluding hi), the elements of an array or the chars of a string
+#[derive(Debug, Clone, Serialize)]
+pub enum Iterable {
+    Range(Box<Expr>, Box<Expr>),
+    Collection(Box<Expr>),
+}
+
+impl Display for Iterable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Iterable::Range(lo, hi) => write!(f, "{}..{}", lo, hi),
+            Iterable::Collection(arr) => write!(f, "{}", arr),
+        }
+    }
+}
+
+// List comprehension e.g [x * 2 for x in 0..10 if x % 2 == 0], which builds an array in a loop
+#[derive(Debug, Clone, Serialize)]
+pub struct ComprehensionData {
+    pub expr: Expr,
+    pub var: String,
+    pub iter: Iterable,
+    pub cond: Option<Expr>,
+}
+
+impl Display for ComprehensionData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cond = self
+            .cond
+            .as_ref()
+            .map(|cond| format!(" if {}", cond))
+            .unwrap_or_default();
+        write!(
+            f,
+            "[{} for {} in {}{}]",
+            self.expr, self.var, self.iter, cond
+        )
+    }
+}
+
 // Different from bytecode Value because values on op stack might be different (e.g fn call)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Expr {
     Symbol(String),
     Integer(i64),
@@ -101,10 +383,34 @@ pub enum Expr {
     BlockExpr(BlockSeq), // expr can be a block
     IfElseExpr(Box<IfElseData>),
     FnCallExpr(FnCallData),
+    MethodCallExpr(MethodCallData),
     SpawnExpr(FnCallData),
+    // spawn { .. } runs the block in a new thread, which finishes with the value of the block
+    SpawnBlockExpr(BlockSeq),
+    // scope { .. } joins the threads spawned in the block before giving its value
+    ScopeExpr(BlockSeq),
+    // lock(m) { .. } holds the lock while the block runs, releasing it however the block is left
+    LockExpr(Box<LockData>),
     // Because join can return something so must be able to assign to it
     // String is the symbol of the thread id to join
     JoinExpr(String),
+    SelectExpr(SelectData),
+    MatchExpr(Box<MatchData>),
+    // loop that is left with a value by break e.g 'outer: loop { break 'outer 2; }
+    LoopExpr(Box<LoopData>),
+    // expr? returns the None or Err from the enclosing function, else gives the value held
+    TryExpr(Box<Expr>),
+    TryCatchExpr(Box<TryCatchData>),
+    StructExpr(StructExprData),
+    // Field of a struct e.g p.x
+    FieldExpr(Box<Expr>, String),
+    // Array literal e.g [1, 2, 3]
+    ArrayExpr(Vec<Expr>),
+    ComprehensionExpr(Box<ComprehensionData>),
+    // Element of an array e.g a[i]
+    IndexExpr(Box<Expr>, Box<Expr>),
+    // Slice of an array or string e.g a[1..3] or s[..n], where a missing bound is open
+    SliceExpr(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
 }
 
 impl Display for Expr {
@@ -124,23 +430,114 @@ impl Display for Expr {
             // Expr::BlockExpr(seq) => seq.to_string(),
             Expr::IfElseExpr(expr) => expr.to_string(),
             Expr::FnCallExpr(expr) => expr.to_string(),
+            Expr::MethodCallExpr(expr) => expr.to_string(),
             Expr::SpawnExpr(expr) => format!("spawn {}", expr),
+            Expr::SpawnBlockExpr(blk) => format!("spawn {{ {} }}", blk),
+            Expr::ScopeExpr(blk) => format!("scope {{ {} }}", blk),
+            Expr::LockExpr(lock) => lock.to_string(),
             Expr::JoinExpr(sym) => format!("join {}", sym),
+            Expr::SelectExpr(select) => select.to_string(),
+            Expr::MatchExpr(match_data) => match_data.to_string(),
+            Expr::LoopExpr(lp) => lp.to_string(),
+            Expr::TryExpr(expr) => format!("{}?", expr),
+            Expr::TryCatchExpr(try_catch) => try_catch.to_string(),
+            Expr::StructExpr(struct_expr) => struct_expr.to_string(),
+            Expr::FieldExpr(expr, field) => format!("{}.{}", expr, field),
             Expr::StringLiteral(str) => str.to_string(),
+            Expr::ArrayExpr(elems) => {
+                let elems: Vec<String> = elems.iter().map(|x| x.to_string()).collect();
+                format!("[{}]", elems.join(","))
+            }
+            Expr::ComprehensionExpr(comp) => comp.to_string(),
+            Expr::IndexExpr(expr, idx) => format!("{}[{}]", expr, idx),
+            Expr::SliceExpr(expr, start, end) => {
+                let bound = |b: &Option<Box<Expr>>| b.as_ref().map(|b| b.to_string());
+                format!(
+                    "{}[{}..{}]",
+                    expr,
+                    bound(start).unwrap_or_default(),
+                    bound(end).unwrap_or_default()
+                )
+            }
         };
 
         write!(f, "{}", string)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct LetStmtData {
     pub ident: String,
     pub expr: Expr,
     pub type_ann: Option<Type>,
 }
 
-#[derive(Debug, Clone)]
+// Left hand side of a let that takes its value apart
+#[derive(Debug, Clone, Serialize)]
+pub enum DestructurePattern {
+    // [x, y, ..rest]: the names of the elements in order, and the name of the array of the rest if any
+    Array(Vec<String>, Option<String>),
+    // Point { x, y: b, .. }: the struct, each field with the name it is bound to, and whether other fields are left out
+    Struct(String, Vec<(String, String)>, bool),
+}
+
+impl DestructurePattern {
+    /// Names the pattern binds, in order
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            DestructurePattern::Array(elems, rest) => elems
+                .iter()
+                .chain(rest.iter())
+                .map(|name| name.as_str())
+                .collect(),
+            DestructurePattern::Struct(_, fields, _) => {
+                fields.iter().map(|(_, name)| name.as_str()).collect()
+            }
+        }
+    }
+}
+
+impl Display for DestructurePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DestructurePattern::Array(elems, rest) => {
+                let mut elems = elems.clone();
+                if let Some(rest) = rest {
+                    elems.push(format!("..{}", rest));
+                }
+                write!(f, "[{}]", elems.join(", "))
+            }
+            DestructurePattern::Struct(name, fields, has_rest) => {
+                let mut fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, name)| match field == name {
+                        true => field.to_string(),
+                        false => format!("{}: {}", field, name),
+                    })
+                    .collect();
+                if *has_rest {
+                    fields.push("..".to_string());
+                }
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+        }
+    }
+}
+
+// let [x, y] = arr; or let Point { x, y } = p;
+#[derive(Debug, Clone, Serialize)]
+pub struct LetDestructureData {
+    pub pat: DestructurePattern,
+    pub expr: Expr,
+}
+
+impl Display for LetDestructureData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "let {} = {}", self.pat, self.expr)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct AssignStmtData {
     pub ident: String,
     pub expr: Expr,
@@ -164,7 +561,27 @@ impl Display for AssignStmtData {
     }
 }
 
-#[derive(Debug, Clone)]
+// Assignment to a field of a struct, which may be nested e.g p.pos.x = 2
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldAssignData {
+    pub ident: String,
+    pub fields: Vec<String>,
+    pub expr: Expr,
+}
+
+impl Display for FieldAssignData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{} = {}",
+            self.ident,
+            self.fields.join("."),
+            self.expr
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct IfElseData {
     pub cond: Expr,
     pub if_blk: BlockSeq,
@@ -183,8 +600,36 @@ impl Display for IfElseData {
     }
 }
 
-#[derive(Debug, Clone)]
+// The label of a loop, written before it e.g 'outer: loop { .. }
+fn label_str(label: &Option<String>) -> String {
+    label
+        .as_ref()
+        .map(|label| format!("{}: ", Token::Label(label.to_owned())))
+        .unwrap_or_default()
+}
+
+// lock(m) { .. } takes a mutex, or an rwlock to write to. read_lock(rw) { .. } shares an rwlock with other readers
+#[derive(Debug, Clone, Serialize)]
+pub struct LockData {
+    pub lock: Expr,
+    pub shared: bool,
+    pub blk: BlockSeq,
+}
+
+impl Display for LockData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kw = if self.shared {
+            Token::ReadLock
+        } else {
+            Token::Lock
+        };
+        write!(f, "{}({}) {{ {} }}", kw, self.lock, self.blk)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct LoopData {
+    pub label: Option<String>,
     pub cond: Option<Expr>,
     pub body: BlockSeq,
 }
@@ -197,11 +642,60 @@ impl Display for LoopData {
             .map(|x| x.to_string())
             .unwrap_or("".to_string());
         let body_str = format!("{{ {} }}", self.body);
-        write!(f, "loop {} {}", cond_str, body_str)
+        write!(
+            f,
+            "{}loop {} {}",
+            label_str(&self.label),
+            cond_str,
+            body_str
+        )
+    }
+}
+
+// for x in 0..10 { .. } or for x in xs { .. }, a loop over the values of an iterable
+#[derive(Debug, Clone, Serialize)]
+pub struct ForData {
+    pub label: Option<String>,
+    pub var: String,
+    pub iter: Iterable,
+    pub body: BlockSeq,
+}
+
+impl Display for ForData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}for {} in {} {{ {} }}",
+            label_str(&self.label),
+            self.var,
+            self.iter,
+            self.body
+        )
+    }
+}
+
+// break, break 'outer, or break 'outer x which leaves the loop labelled 'outer with x as its value.
+// Without a label, break leaves the innermost loop
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakData {
+    pub label: Option<String>,
+    pub value: Option<Expr>,
+}
+
+impl Display for BreakData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = Token::Break.to_string();
+        if let Some(label) = &self.label {
+            s.push_str(&format!(" {}", Token::Label(label.to_owned())));
+        }
+        if let Some(value) = &self.value {
+            s.push_str(&format!(" {}", value));
+        }
+        write!(f, "{}", s)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 // function parameter
 pub struct FnParam {
     pub name: String,
@@ -221,12 +715,16 @@ impl Display for FnParam {
 }
 
 // Fn Decl
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FnDeclData {
     pub name: String,
     pub params: Vec<FnParam>,
     pub ret_type: Type,
     pub body: BlockSeq,
+    // marked with #[test], to be run by the test runner
+    pub is_test: bool,
+    // yields values, so calling it returns a generator instead of running the body
+    pub is_generator: bool,
 }
 
 impl Display for FnDeclData {
@@ -240,27 +738,104 @@ impl Display for FnDeclData {
             format!(" -> {} ", self.ret_type)
         };
 
+        let attr = if self.is_test { "#[test] " } else { "" };
+
         let s = format!(
-            "fn {} ({}){}{{ {} }}",
-            self.name, params, ret_type_str, self.body
+            "{}fn {} ({}){}{{ {} }}",
+            attr, self.name, params, ret_type_str, self.body
         );
         write!(f, "{}", s)
     }
 }
 
+// struct Point { x: int, y: int }
+#[derive(Debug, Clone, Serialize)]
+pub struct StructDeclData {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+impl Display for StructDeclData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, ty)| format!("{}:{}", name, ty))
+            .collect();
+        write!(f, "struct {} {{ {} }}", self.name, fields.join(", "))
+    }
+}
+
+// enum Shape { Circle(float), Empty }, where each variant holds at most one value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnumDeclData {
+    pub name: String,
+    pub variants: Vec<(String, Option<Type>)>,
+}
+
+impl EnumDeclData {
+    /// The type of the value the variant holds, which is None for variants that hold no value.
+    /// None if the enum has no such variant.
+    pub fn variant(&self, name: &str) -> Option<&Option<Type>> {
+        self.variants
+            .iter()
+            .find(|(variant, _)| variant == name)
+            .map(|(_, ty)| ty)
+    }
+}
+
+impl Display for EnumDeclData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let variants: Vec<String> = self
+            .variants
+            .iter()
+            .map(|(name, ty)| match ty {
+                Some(ty) => format!("{}({})", name, ty),
+                None => name.to_string(),
+            })
+            .collect();
+        write!(f, "enum {} {{ {} }}", self.name, variants.join(", "))
+    }
+}
+
+// Methods of a struct, called with the struct as self e.g impl Point { fn norm(self) -> int { .. } }
+#[derive(Debug, Clone, Serialize)]
+pub struct ImplData {
+    pub name: String,
+    pub methods: Vec<FnDeclData>,
+}
+
+impl Display for ImplData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let methods = self
+            .methods
+            .iter()
+            .map(|x| x.to_string() + ";")
+            .collect::<String>();
+        write!(f, "impl {} {{ {} }}", self.name, methods)
+    }
+}
+
 // Later: LetStmt, IfStmt, FnDef, etc.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Decl {
     LetStmt(LetStmtData),
+    LetDestructureStmt(LetDestructureData),
     AssignStmt(AssignStmtData),
+    FieldAssignStmt(FieldAssignData),
     ExprStmt(Expr),
     // if with no else should only be stmt. use same struct because compilation is very similar to if-else
     IfOnlyStmt(IfElseData),
-    // loop is always a stmt (for now)
+    // loop is a stmt unless a break leaves it with a value, which makes it a LoopExpr
     LoopStmt(LoopData),
+    ForStmt(ForData),
     FnDeclStmt(FnDeclData),
+    // struct, enum and impl are only at the top level
+    StructDeclStmt(StructDeclData),
+    EnumDeclStmt(EnumDeclData),
+    ImplStmt(ImplData),
     // only inside loop
-    BreakStmt,
+    BreakStmt(BreakData),
     // only inside fn
     ReturnStmt(Option<Expr>),
     // wait sem; - stmt only
@@ -269,6 +844,8 @@ pub enum Decl {
     PostStmt(String),
     // yield; - no args
     YieldStmt,
+    // yield x; - only inside fn, which makes it a generator
+    YieldValueStmt(Expr),
 }
 
 impl Decl {
@@ -280,21 +857,35 @@ impl Decl {
             Self::LetStmt(ref stmt) => {
                 Err(ParseError::new(&format!("'{}' is not an expression", stmt)))
             }
+            Self::LetDestructureStmt(ref stmt) => {
+                Err(ParseError::new(&format!("'{}' is not an expression", stmt)))
+            }
             Self::AssignStmt(ref stmt) => {
                 Err(ParseError::new(&format!("'{}' is not an expression", stmt)))
             }
+            Self::FieldAssignStmt(ref stmt) => {
+                Err(ParseError::new(&format!("'{}' is not an expression", stmt)))
+            }
             Self::IfOnlyStmt(_) => Err(ParseError::new(
                 "if without else branch is not an expression",
             )),
             Self::FnDeclStmt(_) => {
                 Err(ParseError::new("Function declaration is not an expression"))
             }
+            Self::StructDeclStmt(_) => {
+                Err(ParseError::new("Struct declaration is not an expression"))
+            }
+            Self::EnumDeclStmt(_) => Err(ParseError::new("Enum declaration is not an expression")),
+            Self::ImplStmt(_) => Err(ParseError::new("impl is not an expression")),
             Self::LoopStmt(_) => Err(ParseError::new("loop is not an expression")),
-            Self::BreakStmt => Err(ParseError::new("break is not an expression")),
+            Self::ForStmt(_) => Err(ParseError::new("for is not an expression")),
+            Self::BreakStmt(_) => Err(ParseError::new("break is not an expression")),
             Self::ReturnStmt(_) => Err(ParseError::new("return is not an expression")),
             Self::WaitStmt(_) => Err(ParseError::new("wait is not an expression")),
             Self::PostStmt(_) => Err(ParseError::new("post is not an expression")),
-            Self::YieldStmt => Err(ParseError::new("yield is not an expression")),
+            Self::YieldStmt | Self::YieldValueStmt(_) => {
+                Err(ParseError::new("yield is not an expression"))
+            }
             Self::ExprStmt(expr) => Ok(expr.clone()),
         }
     }
@@ -320,11 +911,17 @@ impl Display for Decl {
         let string = match self {
             Decl::ExprStmt(expr) => expr.to_string(),
             Decl::LetStmt(stmt) => stmt.to_string(),
+            Decl::LetDestructureStmt(stmt) => stmt.to_string(),
             Decl::AssignStmt(stmt) => stmt.to_string(),
+            Decl::FieldAssignStmt(stmt) => stmt.to_string(),
             Decl::IfOnlyStmt(expr) => expr.to_string(),
             Decl::LoopStmt(lp) => lp.to_string(),
-            Decl::BreakStmt => Token::Break.to_string(),
+            Decl::ForStmt(lp) => lp.to_string(),
+            Decl::BreakStmt(brk) => brk.to_string(),
             Decl::FnDeclStmt(fn_decl) => fn_decl.to_string(),
+            Decl::StructDeclStmt(struct_decl) => struct_decl.to_string(),
+            Decl::EnumDeclStmt(enum_decl) => enum_decl.to_string(),
+            Decl::ImplStmt(impl_data) => impl_data.to_string(),
             Decl::ReturnStmt(expr) => {
                 let str = expr
                     .clone()
@@ -340,6 +937,7 @@ impl Display for Decl {
             Decl::WaitStmt(sym) => format!("wait {}", sym),
             Decl::PostStmt(sym) => format!("post {}", sym),
             Decl::YieldStmt => "yield".to_string(),
+            Decl::YieldValueStmt(expr) => format!("yield {}", expr),
         };
 
         write!(f, "{}", string)
@@ -348,12 +946,19 @@ impl Display for Decl {
 
 // Last expression is value of program semantics (else Unit type)
 // Program is either one declaration or a sequence of declarations with optional last expression
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BlockSeq {
     pub decls: Vec<Decl>,
     pub last_expr: Option<Rc<Expr>>,
     // List of top level uninitialised symbols (variable/func declarations)
     pub symbols: Vec<String>,
+    /// The index in the token stream of the first token of each declaration, to map code back to the source.
+    /// Empty if the block was not parsed, e.g. made up by the compiler.
+    #[serde(skip)]
+    pub decl_toks: Vec<usize>,
+    /// The index in the token stream of the first token of the last expression, if it was parsed.
+    #[serde(skip)]
+    pub last_expr_tok: Option<usize>,
 }
 
 impl Display for BlockSeq {
@@ -372,17 +977,56 @@ impl Display for BlockSeq {
     }
 }
 
+impl BlockSeq {
+    /// Indices of the fn decls that are hoisted to the start of the block, so they can be called
+    /// from anywhere in it: the first decl of each name. Later decls of the same name shadow it in place.
+    pub fn hoisted_fns(&self) -> Vec<usize> {
+        let mut names: Vec<&str> = vec![];
+        let mut hoisted = vec![];
+
+        for (idx, decl) in self.decls.iter().enumerate() {
+            if let Decl::FnDeclStmt(fn_decl) = decl {
+                if !names.contains(&fn_decl.name.as_str()) {
+                    names.push(&fn_decl.name);
+                    hoisted.push(idx);
+                }
+            }
+        }
+
+        hoisted
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ParseError {
     msg: String,
+    // Index in the token stream of the last token consumed before failing, set once the error reaches the top
+    tok: Option<usize>,
 }
 
 impl ParseError {
     pub fn new(err: &str) -> ParseError {
         ParseError {
             msg: err.to_owned(),
+            tok: None,
         }
     }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// The index in the token stream of the last token the parser consumed before failing,
+    /// which is the offending token or the one the parser expected something else after.
+    /// `None` if it failed before consuming any token.
+    pub fn tok(&self) -> Option<usize> {
+        self.tok
+    }
+
+    pub(crate) fn at(mut self, tok: Option<usize>) -> ParseError {
+        self.tok = self.tok.or(tok);
+        self
+    }
 }
 
 impl Display for ParseError {
@@ -396,7 +1040,7 @@ impl std::error::Error for ParseError {}
 
 // Type of a function value - subset of FnDeclData
 // Params: care only about types not names
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct FnTypeData {
     pub params: Vec<Type>,
     pub ret_type: Type,
@@ -429,8 +1073,32 @@ impl Display for FnTypeData {
     }
 }
 
+// Fields of a struct, and the methods of its impl blocks with self as their first parameter
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StructTypeData {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    pub methods: Vec<(String, FnTypeData)>,
+}
+
+impl StructTypeData {
+    pub fn field(&self, name: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, ty)| ty)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&FnTypeData> {
+        self.methods
+            .iter()
+            .find(|(method, _)| method == name)
+            .map(|(_, ty)| ty)
+    }
+}
+
 // Type annotation corresponding to compile time types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Type {
     Int,
     Float,
@@ -438,9 +1106,25 @@ pub enum Type {
     String,
     UserFn(Box<FnTypeData>),
     BuiltInFn, // type checking done separately since it can be polymorphic unlike user fn
-    ThreadId,  // result of spawn
+    ThreadId(Box<Type>), // result of spawn, with the type of the value the thread finishes with
     Semaphore,
-    Unit,        // void type like Rust
+    CondVar,
+    Barrier,
+    WaitGroup,
+    RwLock,
+    AtomicInt,
+    Channel(Box<Type>),      // channel of values of the type, returned by channel
+    BoundedQueue(Box<Type>), // bounded queue of values of the type, returned by bounded_queue
+    Option(Box<Type>),
+    Result(Box<Type>, Box<Type>),
+    Array(Box<Type>),
+    Generator(Box<Type>), // generator yielding values of the type, returned by calling a generator function
+    Named(String),        // value of the struct or enum with the name
+    StructDef(Box<StructTypeData>), // the struct itself, which its name is bound to
+    EnumDef(Box<EnumDeclData>), // the enum itself, which its name is bound to
+    Generic(String), // Parameter of a generic function e.g 'a in fn('a) -> 'a, inferred for unannotated parameters
+    Unknown,         // Parameter of None, Ok or Err that is not known yet e.g Option<_> for None
+    Unit,            // void type like Rust
     Unitialised, // Type for variables that exist in a block but not yet declared - only used for TyEnv
 }
 
@@ -455,6 +1139,112 @@ impl Type {
 }
 
 impl Type {
+    /// The type both types can be, filling in the unknown parameters of one with those of the other,
+    /// e.g `Option<_>` (the type of `None`) and `Option<int>` give `Option<int>`. None if they differ.
+    /// Generic types are only inferred for programs inference has checked, so they can be any type here.
+    pub fn unify(&self, other: &Type) -> Option<Type> {
+        match (self, other) {
+            (Type::Unknown, ty) | (ty, Type::Unknown) => Some(ty.clone()),
+            (Type::Generic(_), ty) | (ty, Type::Generic(_)) => Some(ty.clone()),
+            (Type::Option(a), Type::Option(b)) => Some(Type::Option(Box::new(a.unify(b)?))),
+            (Type::Array(a), Type::Array(b)) => Some(Type::Array(Box::new(a.unify(b)?))),
+            (Type::Generator(a), Type::Generator(b)) => {
+                Some(Type::Generator(Box::new(a.unify(b)?)))
+            }
+            (Type::ThreadId(a), Type::ThreadId(b)) => Some(Type::ThreadId(Box::new(a.unify(b)?))),
+            (Type::Channel(a), Type::Channel(b)) => Some(Type::Channel(Box::new(a.unify(b)?))),
+            (Type::BoundedQueue(a), Type::BoundedQueue(b)) => {
+                Some(Type::BoundedQueue(Box::new(a.unify(b)?)))
+            }
+            (Type::Result(a_ok, a_err), Type::Result(b_ok, b_err)) => Some(Type::Result(
+                Box::new(a_ok.unify(b_ok)?),
+                Box::new(a_err.unify(b_err)?),
+            )),
+            (Type::UserFn(a), Type::UserFn(b)) if a.params.len() == b.params.len() => {
+                let params = a
+                    .params
+                    .iter()
+                    .zip(b.params.iter())
+                    .map(|(a, b)| a.unify(b))
+                    .collect::<Option<Vec<_>>>()?;
+                let ret_type = a.ret_type.unify(&b.ret_type)?;
+                Some(Type::UserFn(Box::new(FnTypeData { params, ret_type })))
+            }
+            (a, b) if a == b => Some(a.clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of the other type can be used where this type is expected.
+    pub fn matches(&self, other: &Type) -> bool {
+        self.unify(other).is_some()
+    }
+
+    /// Bind the generic types in either type to the parts of the other type in the same place,
+    /// e.g `Option<'a>` and `Option<int>` bind 'a to int. Each generic function has its own generic types,
+    /// so those of a function and of the generic functions passed to it are bound together.
+    pub fn bind_generics(&self, other: &Type, bound: &mut HashMap<String, Type>) {
+        match (self.resolve_generic(bound), other.resolve_generic(bound)) {
+            (Type::Generic(a), Type::Generic(b)) if a == b => (),
+            (Type::Generic(name), ty) | (ty, Type::Generic(name)) => {
+                bound.insert(name, ty);
+            }
+            (Type::Option(a), Type::Option(b))
+            | (Type::Array(a), Type::Array(b))
+            | (Type::Generator(a), Type::Generator(b))
+            | (Type::ThreadId(a), Type::ThreadId(b))
+            | (Type::Channel(a), Type::Channel(b))
+            | (Type::BoundedQueue(a), Type::BoundedQueue(b)) => a.bind_generics(&b, bound),
+            (Type::Result(a_ok, a_err), Type::Result(b_ok, b_err)) => {
+                a_ok.bind_generics(&b_ok, bound);
+                a_err.bind_generics(&b_err, bound);
+            }
+            (Type::UserFn(a), Type::UserFn(b)) => {
+                for (a, b) in a.params.iter().zip(b.params.iter()) {
+                    a.bind_generics(b, bound);
+                }
+                a.ret_type.bind_generics(&b.ret_type, bound);
+            }
+            _ => (),
+        }
+    }
+
+    /// What the type is bound to, if it is a bound generic type.
+    fn resolve_generic(&self, bound: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Generic(name) => match bound.get(name) {
+                Some(ty) => ty.resolve_generic(bound),
+                None => self.clone(),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// The type with its bound generic types replaced by what they are bound to.
+    pub fn subst_generics(&self, bound: &HashMap<String, Type>) -> Type {
+        match self.resolve_generic(bound) {
+            Type::Option(ty) => Type::Option(Box::new(ty.subst_generics(bound))),
+            Type::Array(ty) => Type::Array(Box::new(ty.subst_generics(bound))),
+            Type::Generator(ty) => Type::Generator(Box::new(ty.subst_generics(bound))),
+            Type::ThreadId(ty) => Type::ThreadId(Box::new(ty.subst_generics(bound))),
+            Type::Channel(ty) => Type::Channel(Box::new(ty.subst_generics(bound))),
+            Type::BoundedQueue(ty) => Type::BoundedQueue(Box::new(ty.subst_generics(bound))),
+            Type::Result(ok, err) => Type::Result(
+                Box::new(ok.subst_generics(bound)),
+                Box::new(err.subst_generics(bound)),
+            ),
+            Type::UserFn(fn_ty) => Type::UserFn(Box::new(FnTypeData {
+                params: fn_ty
+                    .params
+                    .iter()
+                    .map(|p| p.subst_generics(bound))
+                    .collect(),
+                ret_type: fn_ty.ret_type.subst_generics(bound),
+            })),
+            ty => ty,
+        }
+    }
+
     /// Converts string to primitive type.
     pub fn from_string(input: &str) -> Result<Type, ParseError> {
         match input {
@@ -463,6 +1253,13 @@ impl Type {
             "float" => Ok(Self::Float),
             "str" => Ok(Self::String),
             "sem" => Ok(Self::Semaphore),
+            "condvar" => Ok(Self::CondVar),
+            "barrier" => Ok(Self::Barrier),
+            "waitgroup" => Ok(Self::WaitGroup),
+            "rwlock" => Ok(Self::RwLock),
+            "atomic_int" => Ok(Self::AtomicInt),
+            // Checked to be a declared struct or enum by the type checker
+            _ if crate::Parser::is_struct_name(input) => Ok(Self::Named(input.to_string())),
             _ => Err(ParseError::new(&format!(
                 "Unknown primitive type: {}",
                 input
@@ -482,8 +1279,24 @@ impl Display for Type {
             Self::BuiltInFn => "builtin_fn".to_string(),
             Self::String => "str".to_string(),
             Self::UserFn(fn_ty) => fn_ty.to_string(),
-            Self::ThreadId => "tid".to_string(),
+            Self::ThreadId(ty) => format!("tid<{}>", ty),
             Self::Semaphore => "sem".to_string(),
+            Self::CondVar => "condvar".to_string(),
+            Self::Barrier => "barrier".to_string(),
+            Self::WaitGroup => "waitgroup".to_string(),
+            Self::RwLock => "rwlock".to_string(),
+            Self::AtomicInt => "atomic_int".to_string(),
+            Self::Channel(ty) => format!("Channel<{}>", ty),
+            Self::BoundedQueue(ty) => format!("BoundedQueue<{}>", ty),
+            Self::Option(ty) => format!("Option<{}>", ty),
+            Self::Result(ok, err) => format!("Result<{}, {}>", ok, err),
+            Self::Array(ty) => format!("[{}]", ty),
+            Self::Generator(ty) => format!("Generator<{}>", ty),
+            Self::Named(name) => name.to_string(),
+            Self::StructDef(def) => format!("struct {}", def.name),
+            Self::EnumDef(def) => format!("enum {}", def.name),
+            Self::Generic(name) => name.to_string(),
+            Self::Unknown => "_".to_string(),
         };
 
         write!(f, "{}", string)
@@ -1,5 +1,5 @@
 use crate::type_checker::{new_env_with_syms, CheckResult, TypeChecker, TypeErrors};
-use parser::structs::{BlockSeq, FnParam, Type};
+use parser::structs::{BlockSeq, Decl, FnParam, FnTypeData, Type};
 
 impl<'prog> TypeChecker<'prog> {
     /// Takes optional vector of fn params to add as type annotations before checking blk
@@ -8,19 +8,54 @@ impl<'prog> TypeChecker<'prog> {
         program: &BlockSeq,
         fn_params: Vec<FnParam>,
     ) -> Result<CheckResult, TypeErrors> {
-        let mut errs = TypeErrors::new();
         // map bindings to types
         // let mut ty_env: HashMap<String, Type> = HashMap::new();
         // let mut ty_env = TyEnv::new();
         let env = new_env_with_syms(program.symbols.clone());
         self.envs.push(env);
 
+        let res = self.check_block_body(program, fn_params);
+        self.envs.pop();
+        res
+    }
+
+    /// Check the block in the innermost env, which holds the block's symbols.
+    pub(crate) fn check_block_body(
+        &mut self,
+        program: &BlockSeq,
+        fn_params: Vec<FnParam>,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut errs = TypeErrors::new();
+
         // if fn_params, add their type annotations
         // assert all args have ty ann
         if !fn_params.is_empty() {
             self.assign_param_types(fn_params)?;
         }
 
+        self.declare_structs(program)?;
+        self.declare_enums(program)?;
+
+        // Functions are hoisted, so they can be called before they are declared e.g for mutual recursion
+        for idx in program.hoisted_fns() {
+            if let Decl::FnDeclStmt(fn_decl) = &program.decls[idx] {
+                let params: Option<Vec<Type>> = fn_decl
+                    .params
+                    .iter()
+                    .map(|param| param.type_ann.clone())
+                    .collect();
+
+                // Missing annotations are reported when the decl itself is checked
+                if let Some(params) = params {
+                    let fn_ty = FnTypeData {
+                        params,
+                        ret_type: fn_decl.ret_type.clone(),
+                    };
+                    self.assign_ident(&fn_decl.name, Type::UserFn(Box::new(fn_ty)))?;
+                }
+            }
+        }
+
         // to check if the block has a decl that forces it to break or forces it to return
         // must_break can be used to accept inf loop with no cond that has no nested break in a function
         let mut must_break = false;
@@ -47,7 +82,6 @@ impl<'prog> TypeChecker<'prog> {
         // return errors for decls first if any, without checking expr
         // because expr may be dependent
         if !errs.is_ok() {
-            self.envs.pop();
             return Err(errs);
         }
 
@@ -65,8 +99,6 @@ impl<'prog> TypeChecker<'prog> {
             let res = self.check_expr(last);
             match res {
                 Ok(expr_res) => {
-                    self.envs.pop();
-
                     // propagate must_break/ret from above decls if there
                     let res = CheckResult {
                         must_break: blk_res.must_break || expr_res.must_break,
@@ -79,8 +111,6 @@ impl<'prog> TypeChecker<'prog> {
             };
         }
 
-        self.envs.pop();
-
         // blk has no last_expr
         if errs.is_ok() {
             Ok(blk_res)
@@ -0,0 +1,309 @@
+use crate::type_checker::{new_env_with_syms, CheckResult, TypeChecker, TypeErrors};
+use parser::structs::{ComprehensionData, Expr, Iterable, Type};
+
+impl<'prog> TypeChecker<'prog> {
+    /// The elements of an array must all have the same type. The type of an empty array is known once it is used.
+    pub(crate) fn check_array_expr(&mut self, elems: &[Expr]) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+        let mut elem_tys: Vec<CheckResult> = vec![];
+
+        for elem in elems.iter() {
+            match self.check_expr(elem) {
+                Ok(res) => elem_tys.push(res),
+                Err(mut errs) => ty_errs.append(&mut errs),
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        let mut res = CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        };
+        let mut elem_ty = Type::Unknown;
+
+        for elem_res in elem_tys.iter() {
+            res = CheckResult::combine(&res, elem_res);
+            elem_ty = match elem_ty.unify(&elem_res.ty) {
+                Some(ty) => ty,
+                None => {
+                    let e = format!(
+                        "Array elements have type mismatch - expected: {}, got: {}",
+                        elem_ty, elem_res.ty
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+            };
+        }
+
+        res.ty = Type::Array(Box::new(elem_ty));
+        Ok(res)
+    }
+
+    /*
+    0. Check the comprehension iterates over a range of ints or an array
+    1. Check the element and the condition with the variable bound to an int or an element of the array
+    2. The condition must be a bool
+    3. The comprehension is an array of the type of the element
+    */
+    pub(crate) fn check_comprehension(
+        &mut self,
+        comp: &ComprehensionData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let (var_ty, mut res) = self.check_iterable(&comp.iter)?;
+
+        self.envs.push(new_env_with_syms(vec![comp.var.clone()]));
+        self.assign_ident(&comp.var, var_ty)?;
+        let body = self.check_comprehension_body(comp);
+        self.envs.pop();
+
+        let expr_res = body?;
+        res.ty = Type::Array(Box::new(expr_res.ty));
+        Ok(res)
+    }
+
+    /// The type of the values the iterable gives, and the result of checking it
+    pub(crate) fn check_iterable(
+        &mut self,
+        iter: &Iterable,
+    ) -> Result<(Type, CheckResult), TypeErrors> {
+        match iter {
+            Iterable::Range(start, end) => {
+                let start_res = self.check_expr(start)?;
+                let end_res = self.check_expr(end)?;
+
+                for bound in [&start_res.ty, &end_res.ty] {
+                    if !bound.eq(&Type::Int) {
+                        let e = format!(
+                            "Expected type '{}' for the bounds of a range but got '{}'",
+                            Type::Int,
+                            bound
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+
+                Ok((Type::Int, CheckResult::combine(&start_res, &end_res)))
+            }
+            Iterable::Collection(coll) => {
+                let mut coll_res = self.check_expr(coll)?;
+                let elem_ty = match &coll_res.ty {
+                    Type::Generator(elem_ty) => Some(*elem_ty.clone()),
+                    ty => TypeChecker::elem_type(ty),
+                };
+                let Some(elem_ty) = elem_ty else {
+                    let e = format!(
+                        "Can't iterate over type '{}', expected a range, an array, a string or a generator",
+                        coll_res.ty
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                };
+
+                coll_res.ty = Type::Unit;
+                Ok((elem_ty, coll_res))
+            }
+        }
+    }
+
+    /// The type of the elements of an array, or of the chars of a string, which are strings of one char
+    pub(crate) fn elem_type(ty: &Type) -> Option<Type> {
+        match ty {
+            Type::Array(elem_ty) => Some(*elem_ty.clone()),
+            Type::String => Some(Type::String),
+            _ => None,
+        }
+    }
+
+    /// Arrays and strings are indexed by ints, giving an element of the array or a char of the string
+    pub(crate) fn check_index_expr(
+        &mut self,
+        expr: &Expr,
+        idx: &Expr,
+    ) -> Result<CheckResult, TypeErrors> {
+        let expr_res = self.check_expr(expr)?;
+        let Some(elem_ty) = TypeChecker::elem_type(&expr_res.ty) else {
+            let e = format!(
+                "Can't index into type '{}', expected an array or a string",
+                expr_res.ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        };
+
+        let idx_res = self.check_index_bound(idx)?;
+        let mut res = CheckResult::combine(&expr_res, &idx_res);
+        res.ty = elem_ty;
+        Ok(res)
+    }
+
+    /// Arrays and strings are sliced by ints, giving an array or a string of the part of it within the bounds
+    pub(crate) fn check_slice_expr(
+        &mut self,
+        expr: &Expr,
+        bounds: [&Option<Box<Expr>>; 2],
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut res = self.check_expr(expr)?;
+        if !matches!(res.ty, Type::Array(_) | Type::String) {
+            let e = format!(
+                "Can't slice type '{}', expected an array or a string",
+                res.ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        let ty = res.ty.clone();
+        for bound in bounds.into_iter().flatten() {
+            let bound_res = self.check_index_bound(bound)?;
+            res = CheckResult::combine(&res, &bound_res);
+        }
+
+        res.ty = ty;
+        Ok(res)
+    }
+
+    fn check_index_bound(&mut self, idx: &Expr) -> Result<CheckResult, TypeErrors> {
+        let res = self.check_expr(idx)?;
+        if !res.ty.eq(&Type::Int) {
+            let e = format!(
+                "Expected type '{}' for index but got '{}'",
+                Type::Int,
+                res.ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(res)
+    }
+
+    fn check_comprehension_body(
+        &mut self,
+        comp: &ComprehensionData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+
+        if let Some(cond) = &comp.cond {
+            match self.check_expr(cond) {
+                Ok(res) if res.ty.eq(&Type::Bool) => (),
+                Ok(res) => {
+                    let e = format!(
+                        "Expected type '{}' for comprehension condition but got '{}'",
+                        Type::Bool,
+                        res.ty
+                    );
+                    ty_errs.add(&e);
+                }
+                Err(mut errs) => ty_errs.append(&mut errs),
+            }
+        }
+
+        match self.check_expr(&comp.expr) {
+            Ok(res) if ty_errs.is_ok() => Ok(res),
+            Ok(_) => Err(ty_errs),
+            Err(mut errs) => {
+                ty_errs.append(&mut errs);
+                Err(ty_errs)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass, expect_pass_str};
+
+    #[test]
+    fn test_type_check_arrays() {
+        expect_pass("[1, 2, 3]", Type::Array(Box::new(Type::Int)));
+        expect_pass_str("[[1], []]", "[[int]]");
+        expect_pass_str("[None, Some(1)]", "[Option<int>]");
+        expect_pass_str("let a: [str] = []; a", "[str]");
+        expect_pass("len([1, 2]) + 1", Type::Int);
+        expect_pass("[1] == [2, 3]", Type::Bool);
+
+        expect_err(
+            "[1, true]",
+            "[TypeError]: Array elements have type mismatch - expected: int, got: bool",
+            false,
+        );
+        expect_err(
+            "let a: [int] = [true];",
+            "[TypeError]: 'a' has declared type [int] but assigned type [bool]",
+            false,
+        );
+        expect_err(
+            "len(1)",
+            "[TypeError]: Expected array or string for 'len' but got int",
+            false,
+        );
+        expect_err("fn f() {} [f] == [f]", "functions can't be compared", true);
+    }
+
+    #[test]
+    fn test_type_check_comprehensions() {
+        expect_pass_str("[x * 2 for x in 0..10 if x % 2 == 0]", "[int]");
+        expect_pass_str("let n = 3; [x > 1 for x in 0..n]", "[bool]");
+        expect_pass_str(r#"[string_len(s) for s in ["a", "bc"]]"#, "[int]");
+        expect_pass_str("[[y for y in 0..x] for x in 0..3]", "[[int]]");
+
+        // the variable is only bound in the comprehension
+        expect_err(
+            "[x for x in 0..3]; x",
+            "[TypeError]: Identifier 'x' not declared",
+            false,
+        );
+        expect_err(
+            "[x for x in 0..2.5]",
+            "[TypeError]: Expected type 'int' for the bounds of a range but got 'float'",
+            false,
+        );
+        expect_err(
+            "[x for x in 3]",
+            "[TypeError]: Can't iterate over type 'int', expected a range, an array, a string or a generator",
+            false,
+        );
+        expect_err(
+            "[x for x in 0..3 if x]",
+            "[TypeError]: Expected type 'bool' for comprehension condition but got 'int'",
+            false,
+        );
+    }
+
+    #[test]
+    fn test_type_check_index_and_slice() {
+        expect_pass("let a = [1, 2]; a[0] + 1", Type::Int);
+        expect_pass_str("let a = [[true]]; a[0]", "[bool]");
+        expect_pass_str("let a = [1, 2, 3]; a[1..len(a)]", "[int]");
+        expect_pass_str(r#"let s = "hello"; s[..2]"#, "str");
+        expect_pass_str("let a = [1]; a[..]", "[int]");
+        expect_pass_str(r#"let s = "héllo"; s[1]"#, "str");
+        expect_pass_str(r#"[c == "a" for c in "ab"]"#, "[bool]");
+        expect_pass_str(r#"[c for c in "ab"]"#, "[str]");
+        expect_pass(r#"len("héllo") + len([1])"#, Type::Int);
+        expect_pass_str(r#"bytes("é")"#, "[int]");
+
+        expect_err(
+            "let x = 1; x[0]",
+            "[TypeError]: Can't index into type 'int', expected an array or a string",
+            false,
+        );
+        expect_err(
+            "let x = true; x[1..]",
+            "[TypeError]: Can't slice type 'bool', expected an array or a string",
+            false,
+        );
+        expect_err(
+            "let a = [1]; a[true]",
+            "[TypeError]: Expected type 'int' for index but got 'bool'",
+            false,
+        );
+        expect_err(
+            "let a = [1]; a[..1.5]",
+            "[TypeError]: Expected type 'int' for index but got 'float'",
+            false,
+        );
+    }
+}
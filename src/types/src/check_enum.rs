@@ -0,0 +1,145 @@
+use parser::structs::{BlockSeq, Decl, EnumDeclData, FnTypeData, Type};
+
+use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+
+impl<'prog> TypeChecker<'prog> {
+    /// The variants of the enum with the name in scope
+    pub(crate) fn get_enum(&self, name: &str) -> Result<EnumDeclData, TypeErrors> {
+        match self.get_type(name) {
+            Ok(Type::EnumDef(def)) => Ok(*def),
+            _ => {
+                let e = format!("'{}' is not an enum", name);
+                Err(TypeErrors::new_err(&e))
+            }
+        }
+    }
+
+    /// Enums can be used anywhere in the block, like structs. Bind each enum of the block to its variants,
+    /// and each variant to its constructor, or to a value of the enum for variants that hold nothing.
+    pub(crate) fn declare_enums(&mut self, program: &BlockSeq) -> Result<(), TypeErrors> {
+        let mut declared: Vec<(&str, &str)> = vec![];
+
+        for decl in program.decls.iter() {
+            let Decl::EnumDeclStmt(enum_decl) = decl else {
+                continue;
+            };
+
+            // The name is only bound for the checker, variants are what the program refers to
+            if let Some(env) = self.envs.last_mut() {
+                env.insert(
+                    enum_decl.name.clone(),
+                    Type::EnumDef(Box::new(enum_decl.clone())),
+                );
+            }
+
+            for (variant, held) in enum_decl.variants.iter() {
+                if let Some((_, other)) = declared.iter().find(|(prev, _)| prev == variant) {
+                    let e = format!(
+                        "Variant '{}' of {} is already a variant of {}",
+                        variant, enum_decl.name, other
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+                declared.push((variant, &enum_decl.name));
+
+                let ty = Type::Named(enum_decl.name.clone());
+                let ty = match held {
+                    Some(held) => Type::UserFn(Box::new(FnTypeData {
+                        params: vec![held.clone()],
+                        ret_type: ty,
+                    })),
+                    None => ty,
+                };
+                self.assign_ident(variant, ty)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_enum_decl(
+        &mut self,
+        enum_decl: &EnumDeclData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+
+        for (_, held) in enum_decl.variants.iter() {
+            if let Some(Err(mut errs)) = held.as_ref().map(|ty| self.check_type_known(ty)) {
+                ty_errs.append(&mut errs);
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        Ok(CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_enums() {
+        let t = r"
+        enum Shape { Circle(float), Square(float), Empty }
+        let s = Circle(2.0);
+        let e : Shape = Empty;
+        s
+        ";
+        expect_pass(t, Type::Named("Shape".to_string()));
+
+        // used before declared, holding structs and other enums
+        let t = r"
+        fn area(s: Shape) -> Shape { s }
+        let s = area(Rect(Size { w: 1, h: Some(2) }));
+        enum Shape { Rect(Size), Other(Kind) }
+        enum Kind { A, B }
+        struct Size { w: int, h: Option<int> }
+        Other(B)
+        ";
+        expect_pass(t, Type::Named("Shape".to_string()));
+    }
+
+    #[test]
+    fn test_type_check_enums_fails() {
+        expect_err(
+            "enum Shape { Circle(float) } Circle(2)",
+            "[TypeError]: Mismatched types in function call: got ((int)) but expected ((float))",
+            true,
+        );
+        expect_err(
+            "enum Shape { Circle(Size) }",
+            "[TypeError]: Unknown type 'Size'",
+            true,
+        );
+        expect_err(
+            "enum A { X } enum B { X(int) }",
+            "[TypeError]: Variant 'X' of B is already a variant of A",
+            true,
+        );
+        expect_err(
+            "enum Shape { Empty } let x : int = Empty;",
+            "[TypeError]: 'x' has declared type int but assigned type Shape",
+            true,
+        );
+        expect_err(
+            "enum Shape { Empty } Shape",
+            "[TypeError]: Enum 'Shape' can't be used as a value",
+            true,
+        );
+        expect_err(
+            "enum Shape { Empty } let s = Empty; s.x",
+            "[TypeError]: 'Shape' is not a struct",
+            true,
+        );
+    }
+}
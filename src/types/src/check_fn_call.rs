@@ -1,11 +1,28 @@
+use std::collections::HashMap;
+
 use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
 use parser::structs::{FnCallData, Type};
 
 // Ideally these constants should be shared across type checker and VM but I don't want to waste time refactoring
 const READ_LINE: &str = "read_line";
+const READ_FILE: &str = "read_file";
+const WRITE_FILE: &str = "write_file";
+const APPEND_FILE: &str = "append_file";
+const FILE_EXISTS: &str = "file_exists";
+const READ_LINES: &str = "read_lines";
+const JSON_PARSE: &str = "json_parse";
+const JSON_STRINGIFY: &str = "json_stringify";
 const PRINT: &str = "print";
 const PRINTLN: &str = "println";
 const STRING_LEN: &str = "string_len";
+const BYTES: &str = "bytes";
+const LEN: &str = "len";
+const TYPE_OF: &str = "typeof";
+const IS_INT: &str = "is_int";
+const IS_FLOAT: &str = "is_float";
+const IS_BOOL: &str = "is_bool";
+const IS_STRING: &str = "is_string";
+const IS_UNIT: &str = "is_unit";
 const MIN: &str = "min";
 const MAX: &str = "max";
 const ABS: &str = "abs";
@@ -13,20 +30,90 @@ const COS: &str = "cos";
 const SIN: &str = "sin";
 const TAN: &str = "tan";
 const SQRT: &str = "sqrt";
+const IS_NAN: &str = "is_nan";
+const IS_INF: &str = "is_inf";
 const LOG: &str = "log";
 const POW: &str = "pow";
+const RANDOM_INT: &str = "random_int";
 const ITOA: &str = "itoa";
 const ATOI: &str = "atoi";
 const FLOAT_TO_INT: &str = "float_to_int";
 const INT_TO_FLOAT: &str = "int_to_float";
 const SEM_CREATE: &str = "sem_create";
 const SEM_SET: &str = "sem_set";
+const WAIT_TIMEOUT: &str = "wait_timeout";
+pub(crate) const THREAD_ID: &str = "thread_id";
+pub(crate) const IS_FINISHED: &str = "is_finished";
+const KILL: &str = "kill";
+const THREAD_LOCAL: &str = "thread_local";
+const SET_THREAD_LOCAL: &str = "set_thread_local";
+const PRIORITY: &str = "priority";
+const SET_PRIORITY: &str = "set_priority";
+const CV_CREATE: &str = "cv_create";
+const CV_WAIT: &str = "cv_wait";
+const CV_NOTIFY_ONE: &str = "cv_notify_one";
+const CV_NOTIFY_ALL: &str = "cv_notify_all";
+const BARRIER_CREATE: &str = "barrier_create";
+const BARRIER_WAIT: &str = "barrier_wait";
+const WG_CREATE: &str = "wg_create";
+const WG_ADD: &str = "wg_add";
+const WG_DONE: &str = "wg_done";
+const WG_WAIT: &str = "wg_wait";
+const CHANNEL: &str = "channel";
+const SEND: &str = "send";
+const RECV: &str = "recv";
+const CLOSE: &str = "close";
+const BOUNDED_QUEUE: &str = "bounded_queue";
+const PUSH: &str = "push";
+const POP: &str = "pop";
+const MUTEX: &str = "mutex";
+const RWLOCK: &str = "rwlock";
+const ATOMIC_INT: &str = "atomic_int";
+const FETCH_ADD: &str = "fetch_add";
+const LOAD: &str = "load";
+const STORE: &str = "store";
+const COMPARE_AND_SWAP: &str = "compare_and_swap";
+const EXIT: &str = "exit";
+const PANIC: &str = "panic";
+const ASSERT: &str = "assert";
+const ASSERT_EQ: &str = "assert_eq";
+const SOME: &str = "Some";
+const OK: &str = "Ok";
+const ERR: &str = "Err";
+const IS_SOME: &str = "is_some";
+const IS_NONE: &str = "is_none";
+const IS_OK: &str = "is_ok";
+const IS_ERR: &str = "is_err";
+const UNWRAP: &str = "unwrap";
+const UNWRAP_ERR: &str = "unwrap_err";
+// Constant, not a function
+pub(crate) const NONE: &str = "None";
+
+/// A thread finishing with a value of any type, which the builtins taking a thread accept.
+pub(crate) fn any_thread() -> Type {
+    Type::ThreadId(Box::new(Type::Unknown))
+}
 
-const BUILTINS: [&str; 19] = [
+const BUILTINS: [&str; 82] = [
     READ_LINE,
+    READ_FILE,
+    WRITE_FILE,
+    APPEND_FILE,
+    FILE_EXISTS,
+    READ_LINES,
+    JSON_PARSE,
+    JSON_STRINGIFY,
     PRINT,
     PRINTLN,
     STRING_LEN,
+    BYTES,
+    LEN,
+    TYPE_OF,
+    IS_INT,
+    IS_FLOAT,
+    IS_BOOL,
+    IS_STRING,
+    IS_UNIT,
     MIN,
     MAX,
     ABS,
@@ -34,14 +121,62 @@ const BUILTINS: [&str; 19] = [
     SIN,
     TAN,
     SQRT,
+    IS_NAN,
+    IS_INF,
     LOG,
     POW,
+    RANDOM_INT,
     ITOA,
     ATOI,
     FLOAT_TO_INT,
     INT_TO_FLOAT,
     SEM_CREATE,
     SEM_SET,
+    WAIT_TIMEOUT,
+    THREAD_ID,
+    IS_FINISHED,
+    KILL,
+    THREAD_LOCAL,
+    SET_THREAD_LOCAL,
+    PRIORITY,
+    SET_PRIORITY,
+    CV_CREATE,
+    CV_WAIT,
+    CV_NOTIFY_ONE,
+    CV_NOTIFY_ALL,
+    BARRIER_CREATE,
+    BARRIER_WAIT,
+    WG_CREATE,
+    WG_ADD,
+    WG_DONE,
+    WG_WAIT,
+    CHANNEL,
+    SEND,
+    RECV,
+    CLOSE,
+    BOUNDED_QUEUE,
+    PUSH,
+    POP,
+    MUTEX,
+    RWLOCK,
+    ATOMIC_INT,
+    FETCH_ADD,
+    LOAD,
+    STORE,
+    COMPARE_AND_SWAP,
+    EXIT,
+    PANIC,
+    ASSERT,
+    ASSERT_EQ,
+    SOME,
+    OK,
+    ERR,
+    IS_SOME,
+    IS_NONE,
+    IS_OK,
+    IS_ERR,
+    UNWRAP,
+    UNWRAP_ERR,
 ];
 
 impl<'prog> TypeChecker<'prog> {
@@ -87,7 +222,7 @@ impl<'prog> TypeChecker<'prog> {
 
         let mut mismatch = false;
         for (arg, param) in arg_types.iter().zip(param_types.iter()) {
-            if *arg != *param {
+            if !param.matches(arg) {
                 mismatch = true;
                 break;
             }
@@ -119,6 +254,42 @@ impl<'prog> TypeChecker<'prog> {
                 TypeChecker::check_arg_params_match(name, &arg_types, &[])?;
                 Type::String
             }
+            // string -> Result<string, string>
+            READ_FILE => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                Type::Result(Box::new(Type::String), Box::new(Type::String))
+            }
+            // (string, string) -> Result<(), string>
+            WRITE_FILE | APPEND_FILE => {
+                let params = [Type::String, Type::String];
+                TypeChecker::check_arg_params_match(name, &arg_types, &params)?;
+                Type::Result(Box::new(Type::Unit), Box::new(Type::String))
+            }
+            // string -> bool
+            FILE_EXISTS => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                Type::Bool
+            }
+            // string -> Result<[string], string>
+            READ_LINES => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                let lines = Type::Array(Box::new(Type::String));
+                Type::Result(Box::new(lines), Box::new(Type::String))
+            }
+            // (string, T) -> Result<T, string>, the value is checked to be of the shape of the example when parsed
+            JSON_PARSE => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
+                let params = [Type::String, arg_types[1].clone()];
+                TypeChecker::check_arg_params_match(name, &arg_types, &params)?;
+                Type::Result(Box::new(arg_types[1].clone()), Box::new(Type::String))
+            }
+            // (any, bool) -> Result<string, string>
+            JSON_STRINGIFY => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
+                let params = [arg_types[0].clone(), Type::Bool];
+                TypeChecker::check_arg_params_match(name, &arg_types, &params)?;
+                Type::Result(Box::new(Type::String), Box::new(Type::String))
+            }
             // (any) -> ()
             PRINT => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
@@ -134,6 +305,33 @@ impl<'prog> TypeChecker<'prog> {
                 TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
                 Type::Int
             }
+            // (string) => [int]
+            BYTES => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                Type::Array(Box::new(Type::Int))
+            }
+            // ([t]) => int or (string) => int
+            LEN => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                if !matches!(arg_types[0], Type::Array(_) | Type::String) {
+                    let e = format!(
+                        "Expected array or string for '{}' but got {}",
+                        name, arg_types[0]
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+                Type::Int
+            }
+            // (any) -> string
+            TYPE_OF => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::String
+            }
+            // (any) -> bool
+            IS_INT | IS_FLOAT | IS_BOOL | IS_STRING | IS_UNIT => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::Bool
+            }
             // (int, int) => int or (float, float) => float
             MIN => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
@@ -235,6 +433,11 @@ impl<'prog> TypeChecker<'prog> {
                     }
                 }
             }
+            // float -> bool
+            IS_NAN | IS_INF => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Float])?;
+                Type::Bool
+            }
             // float -> float
             LOG => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
@@ -250,6 +453,11 @@ impl<'prog> TypeChecker<'prog> {
                 }
             }
             // float, float => float
+            // (int, int) -> int
+            RANDOM_INT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int, Type::Int])?;
+                Type::Int
+            }
             POW => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
                 match (arg_types.first().unwrap(), arg_types.get(1).unwrap()) {
@@ -329,6 +537,319 @@ impl<'prog> TypeChecker<'prog> {
                 // Fill out this block
                 todo!()
             }
+            // (sem, int) -> bool
+            WAIT_TIMEOUT => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Semaphore, Type::Int],
+                )?;
+                Type::Bool
+            }
+            // tid -> int
+            THREAD_ID => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[any_thread()])?;
+                Type::Int
+            }
+            // tid -> bool
+            IS_FINISHED => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[any_thread()])?;
+                Type::Bool
+            }
+            // tid -> ()
+            KILL => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[any_thread()])?;
+                Type::Unit
+            }
+            // (str, T) -> T, where the value of the key in the current thread starts as the value given,
+            // and the VM checks that a value set before has the type of the value given
+            THREAD_LOCAL | SET_THREAD_LOCAL => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
+                let val = arg_types[1].clone();
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::String, val.clone()],
+                )?;
+                if name == THREAD_LOCAL {
+                    val
+                } else {
+                    Type::Unit
+                }
+            }
+            // () -> int
+            PRIORITY => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::Int
+            }
+            // int -> ()
+            SET_PRIORITY => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Unit
+            }
+            // int -> ()
+            EXIT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Unit
+            }
+            // str -> never returns, so it can stand in for a value of any type
+            PANIC => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                Type::Unknown
+            }
+            // () -> condvar
+            CV_CREATE => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::CondVar
+            }
+            // (condvar, sem) -> ()
+            CV_WAIT => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::CondVar, Type::Semaphore],
+                )?;
+                Type::Unit
+            }
+            // condvar -> ()
+            CV_NOTIFY_ONE | CV_NOTIFY_ALL => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::CondVar])?;
+                Type::Unit
+            }
+            // int -> barrier
+            BARRIER_CREATE => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Barrier
+            }
+            // barrier -> ()
+            BARRIER_WAIT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Barrier])?;
+                Type::Unit
+            }
+            // () -> waitgroup
+            WG_CREATE => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::WaitGroup
+            }
+            // (waitgroup, int) -> ()
+            WG_ADD => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::WaitGroup, Type::Int],
+                )?;
+                Type::Unit
+            }
+            // waitgroup -> ()
+            WG_DONE | WG_WAIT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::WaitGroup])?;
+                Type::Unit
+            }
+            // () -> sem, which one thread holds at a time
+            MUTEX => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::Semaphore
+            }
+            // () -> rwlock
+            RWLOCK => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::RwLock
+            }
+            // int -> atomic_int
+            ATOMIC_INT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::AtomicInt
+            }
+            // (atomic_int, int) -> int, the value held before the addition
+            FETCH_ADD => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::AtomicInt, Type::Int],
+                )?;
+                Type::Int
+            }
+            // atomic_int -> int
+            LOAD => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::AtomicInt])?;
+                Type::Int
+            }
+            // (atomic_int, int) -> ()
+            STORE => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::AtomicInt, Type::Int],
+                )?;
+                Type::Unit
+            }
+            // (atomic_int, int, int) -> int, the value held before, which is the current value if it was swapped
+            COMPARE_AND_SWAP => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::AtomicInt, Type::Int, Type::Int],
+                )?;
+                Type::Int
+            }
+            // int -> Channel<_>, a let without an annotation is annotated by infer::annotate with the type sent or received
+            CHANNEL => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Channel(Box::new(Type::Unknown))
+            }
+            // (Channel<T>, T) -> bool
+            SEND => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
+                let val = arg_types[1].clone();
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Channel(Box::new(val.clone())), val],
+                )?;
+                Type::Bool
+            }
+            // Channel<T> -> Option<T>
+            RECV => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types.first().unwrap() {
+                    Type::Channel(ty) => Type::Option(ty.clone()),
+                    _ => {
+                        let e = format!(
+                            "Expected Channel but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // Channel<T> -> ()
+            CLOSE => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Channel(Box::new(Type::Unknown))],
+                )?;
+                Type::Unit
+            }
+            // int -> BoundedQueue<_>, a let without an annotation is annotated by infer::annotate with the type pushed or popped
+            BOUNDED_QUEUE => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::BoundedQueue(Box::new(Type::Unknown))
+            }
+            // (BoundedQueue<T>, T) -> ()
+            PUSH => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
+                let val = arg_types[1].clone();
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::BoundedQueue(Box::new(val.clone())), val],
+                )?;
+                Type::Unit
+            }
+            // BoundedQueue<T> -> T
+            POP => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types.first().unwrap() {
+                    Type::BoundedQueue(ty) => *ty.clone(),
+                    _ => {
+                        let e = format!(
+                            "Expected BoundedQueue but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // bool -> ()
+            ASSERT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Bool])?;
+                Type::Unit
+            }
+            // (T, T) -> ()
+            ASSERT_EQ => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 2)?;
+                let (left, right) = (arg_types.first().unwrap(), arg_types.get(1).unwrap());
+                if !left.matches(right) {
+                    let e = format!(
+                        "Expected two arguments of the same type but got {}",
+                        TypeChecker::get_type_string(&arg_types)
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+                Type::Unit
+            }
+            // T -> Option<T>
+            SOME => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::Option(Box::new(arg_types[0].clone()))
+            }
+            // T -> Result<T, _>
+            OK => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::Result(Box::new(arg_types[0].clone()), Box::new(Type::Unknown))
+            }
+            // E -> Result<_, E>
+            ERR => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::Result(Box::new(Type::Unknown), Box::new(arg_types[0].clone()))
+            }
+            // Option<T> -> bool
+            IS_SOME | IS_NONE => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types.first().unwrap() {
+                    Type::Option(_) => Type::Bool,
+                    _ => {
+                        let e = format!(
+                            "Expected Option but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // Result<T, E> -> bool
+            IS_OK | IS_ERR => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types.first().unwrap() {
+                    Type::Result(..) => Type::Bool,
+                    _ => {
+                        let e = format!(
+                            "Expected Result but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // Option<T> -> T or Result<T, E> -> T
+            UNWRAP => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types.first().unwrap() {
+                    Type::Option(ty) | Type::Result(ty, _) => *ty.clone(),
+                    _ => {
+                        let e = format!(
+                            "Expected Option or Result but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // Result<T, E> -> E
+            UNWRAP_ERR => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types.first().unwrap() {
+                    Type::Result(_, err) => *err.clone(),
+                    _ => {
+                        let e = format!(
+                            "Expected Result but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
             _ => todo!(),
         };
 
@@ -385,7 +906,20 @@ impl<'prog> TypeChecker<'prog> {
             let param_types: Vec<Type> = ty.params.iter().map(|x| x.to_owned()).collect();
 
             TypeChecker::check_arg_params_match(&fn_call.name, &arg_types, &param_types)?;
-            check_res.ty = ty.ret_type;
+
+            // Generic functions return the types their generic parameters are called with
+            let mut bound = HashMap::new();
+            for (param, arg) in param_types.iter().zip(arg_types.iter()) {
+                param.bind_generics(arg, &mut bound);
+            }
+            check_res.ty = ty.ret_type.subst_generics(&bound);
+        } else {
+            // Functions declared later in the block without full annotations are still uninitialised here
+            let ty = self.get_type(&fn_call.name)?;
+            if !ty.eq(&Type::Unitialised) {
+                let e = format!("Can't call '{}' of type '{}'", fn_call.name, ty);
+                return Err(TypeErrors::new_err(&e));
+            }
         }
         // dbg!("fn_ty", fn_ty);
         // check_res.ty = fn_ty;
@@ -398,7 +932,7 @@ impl<'prog> TypeChecker<'prog> {
 mod tests {
     use parser::structs::Type;
 
-    use crate::type_checker::{expect_err, expect_pass};
+    use crate::type_checker::{expect_err, expect_pass, expect_pass_str};
 
     use super::BUILTINS;
 
@@ -452,6 +986,49 @@ mod tests {
         expect_err(t, "Mismatched types in function call:", true);
     }
 
+    #[test]
+    fn test_type_check_named_args() {
+        // args are checked against the params of the same name
+        let t = r"
+        fn f(n: int, b: bool) -> int {
+            2
+        }
+        f(b: true, n: 3)
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        fn f(n: int, b: bool) -> int {
+            2
+        }
+        f(n: true, b: 3)
+        ";
+        expect_err(t, "Mismatched types in function call:", true);
+
+        let t = r"
+        fn f(n: int, b: bool) -> int {
+            2
+        }
+        f(2, n: 3)
+        ";
+        expect_err(
+            t,
+            "Argument 'n' is given more than once in call to 'f'",
+            true,
+        );
+
+        expect_err(
+            "let f = 2; f(n: 3)",
+            "Named arguments need a function declared with fn, 'f' is not one in scope",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_type_check_call_non_fn() {
+        expect_err("let x = 2; x(1)", "Can't call 'x' of type 'int'", true);
+    }
+
     #[test]
     fn test_type_check_builtin_sym() {
         for &builtin in BUILTINS.iter() {
@@ -463,6 +1040,20 @@ mod tests {
     fn test_type_check_builtin_functions() {
         expect_pass("let x : () = print(2); x", Type::Unit);
 
+        // Test is_nan and is_inf
+        expect_pass("let x : bool = is_nan(0.0 / 0.0); x", Type::Bool);
+        expect_pass("is_inf(1.0 / 0.0)", Type::Bool);
+        expect_err("is_nan(1)", "Mismatched types", true);
+
+        // Test typeof and is_
+        expect_pass("let x : str = typeof(2); x", Type::String);
+        expect_pass("let x : bool = is_float(2); x", Type::Bool);
+        expect_err(
+            "is_int(1, 2)",
+            "takes 1 arguments but 2 were supplied",
+            true,
+        );
+
         // Test min
         expect_pass("let x : int = min(2, 3); x", Type::Int);
         expect_pass("let x : float = min(2.0, 3.0); x", Type::Float);
@@ -492,6 +1083,45 @@ mod tests {
 
         // Test pow
         expect_pass("let x : float = pow(2.0, 3.0); x", Type::Float);
+        expect_pass("random_int(0, 6) + 1", Type::Int);
+        expect_err(
+            "random_int(0.0, 6)",
+            "got ((float, int)) but expected ((int, int))",
+            true,
+        );
+
+        // Test file functions
+        expect_pass_str(r#"read_file("a.txt")"#, "Result<str, str>");
+        expect_pass_str(r#"read_lines("a.txt")"#, "Result<[str], str>");
+        expect_pass_str(r#"write_file("a.txt", "a")"#, "Result<(), str>");
+        expect_pass_str(r#"append_file("a.txt", "a")"#, "Result<(), str>");
+        expect_pass(r#"let x : bool = file_exists("a.txt"); x"#, Type::Bool);
+        expect_pass_str(
+            r#"fn count(path: str) -> Result<int, str> { Ok(len(read_lines(path)?)) } count("a.txt")"#,
+            "Result<int, str>",
+        );
+        expect_err(
+            r#"write_file("a.txt", 1)"#,
+            "got ((str, int)) but expected ((str, str))",
+            true,
+        );
+
+        // Test json_parse and json_stringify
+        expect_pass(
+            r#"let x : [int] = unwrap(json_parse("[1, 2]", [0])); x"#,
+            Type::Array(Box::new(Type::Int)),
+        );
+        expect_err(
+            r#"let x : [int] = unwrap(json_parse("[1, 2]", ["a"])); x"#,
+            "has declared type [int] but assigned type [str]",
+            true,
+        );
+        expect_pass_str(r#"json_stringify([1, 2], true)"#, "Result<str, str>");
+        expect_err(
+            r#"json_stringify("a", 1)"#,
+            "got ((str, int)) but expected ((str, bool))",
+            true,
+        );
 
         // Test itoa
         // expect_pass("let x : string = itoa(123); x", Type::String);
@@ -507,5 +1137,170 @@ mod tests {
 
         // Test sem
         expect_pass("let x = sem_create(); x", Type::Semaphore);
+
+        // Test locks
+        expect_pass("let m : sem = mutex(); m", Type::Semaphore);
+        expect_pass("let rw : rwlock = rwlock(); rw", Type::RwLock);
+
+        // Test atomic int
+        expect_pass("let a : atomic_int = atomic_int(0); a", Type::AtomicInt);
+        expect_pass(
+            "let a = atomic_int(0); store(a, 2); fetch_add(a, 1) + load(a) + compare_and_swap(a, 3, 4)",
+            Type::Int,
+        );
+        expect_err(
+            "let a = atomic_int(0); fetch_add(a, 1.0)",
+            "got ((atomic_int, float)) but expected ((atomic_int, int))",
+            true,
+        );
+        expect_pass(
+            "let cv = cv_create(); let m = mutex(); lock(m) { cv_wait(cv, m); }",
+            Type::Unit,
+        );
+
+        // Test kill
+        expect_pass(
+            "fn f() {} let h = spawn f(); let x : () = kill(h); x",
+            Type::Unit,
+        );
+        // Test thread locals
+        expect_pass(r#"thread_local("seed", 42) + 1"#, Type::Int);
+        expect_pass(r#"set_thread_local("buf", [1, 2])"#, Type::Unit);
+        expect_err(
+            "thread_local(1, 2)",
+            "got ((int, int)) but expected ((str, int))",
+            true,
+        );
+
+        // Test priorities
+        expect_pass("set_priority(2); priority() + 1", Type::Int);
+        expect_err(
+            "set_priority(true)",
+            "Mismatched types in function call: got ((bool)) but expected ((int))",
+            true,
+        );
+
+        // Test condvar
+        expect_pass("let x : condvar = cv_create(); x", Type::CondVar);
+        expect_pass(
+            "let cv = cv_create(); let m = sem_create(); cv_wait(cv, m); cv_notify_one(cv); cv_notify_all(cv)",
+            Type::Unit,
+        );
+        expect_err(
+            "let cv = cv_create(); cv_wait(cv, cv)",
+            "Mismatched types in function call: got ((condvar, condvar)) but expected ((condvar, sem))",
+            true,
+        );
+
+        // Test wait_timeout
+        expect_pass(
+            "let s = sem_create(); let x : bool = wait_timeout(s, 100); x",
+            Type::Bool,
+        );
+
+        // Test barrier and wait group
+        expect_pass("let b : barrier = barrier_create(2); b", Type::Barrier);
+        expect_pass("let b = barrier_create(2); barrier_wait(b)", Type::Unit);
+        expect_pass("let wg : waitgroup = wg_create(); wg", Type::WaitGroup);
+        expect_pass(
+            "let wg = wg_create(); wg_add(wg, 2); wg_done(wg); wg_wait(wg)",
+            Type::Unit,
+        );
+        expect_err(
+            "let wg = wg_create(); wg_add(wg, true)",
+            "Mismatched types in function call: got ((waitgroup, bool)) but expected ((waitgroup, int))",
+            true,
+        );
+
+        // Test channels
+        expect_pass(
+            "let ch : Channel<int> = channel(2); ch",
+            Type::Channel(Box::new(Type::Int)),
+        );
+        expect_pass(
+            "let ch : Channel<int> = channel(2); let sent : bool = send(ch, 1); close(ch); recv(ch)",
+            Type::Option(Box::new(Type::Int)),
+        );
+        // without an annotation, the type of the values is inferred from what is sent or received
+        expect_pass(
+            "let ch = channel(2); send(ch, true); recv(ch)",
+            Type::Option(Box::new(Type::Bool)),
+        );
+        expect_pass(
+            "let ch = channel(4); send(ch, 1); let x = unwrap(recv(ch)); x + 1",
+            Type::Int,
+        );
+        expect_pass(
+            "let ch = channel(4); let x : int = unwrap(recv(ch)); x",
+            Type::Int,
+        );
+        expect_err(
+            r#"let ch = channel(4); send(ch, "a"); let x : int = unwrap(recv(ch)); x + 1"#,
+            "'x' has declared type int but inferred type str",
+            true,
+        );
+        expect_err(
+            "let ch = channel(4); close(ch);",
+            "Can't infer the type of the values of 'ch', add a type annotation",
+            true,
+        );
+        expect_err(
+            "let ch : Channel<int> = channel(2); send(ch, true)",
+            "Mismatched types in function call: got ((Channel<int>, bool)) but expected ((Channel<bool>, bool))",
+            true,
+        );
+        expect_err(
+            "let ch : Channel<int> = channel(true);",
+            "Mismatched types in function call: got ((bool)) but expected ((int))",
+            true,
+        );
+        expect_err("recv(2)", "Expected Channel but got (int)", true);
+
+        // Test bounded queues
+        expect_pass(
+            "let q : BoundedQueue<int> = bounded_queue(2); push(q, 1); pop(q)",
+            Type::Int,
+        );
+        expect_err(
+            "let q : BoundedQueue<int> = bounded_queue(2); push(q, true)",
+            "Mismatched types in function call: got ((BoundedQueue<int>, bool)) but expected ((BoundedQueue<bool>, bool))",
+            true,
+        );
+        expect_err("pop(2)", "Expected BoundedQueue but got (int)", true);
+
+        // without an annotation, the type of the values is inferred from what is pushed or popped
+        expect_pass(
+            "let q = bounded_queue(2); push(q, 3); pop(q) * 2",
+            Type::Int,
+        );
+        expect_err(
+            r#"let q = bounded_queue(2); push(q, "s"); let y : int = pop(q); y * 2"#,
+            "'y' has declared type int but inferred type str",
+            true,
+        );
+        expect_err(
+            "let q = bounded_queue(2); q",
+            "Can't infer the type of the values of 'q', add a type annotation",
+            true,
+        );
+
+        // Test assert
+        expect_pass("assert(1 < 2); assert_eq(2, 3)", Type::Unit);
+        expect_err(
+            "assert(2)",
+            "Mismatched types in function call: got ((int)) but expected ((bool))",
+            true,
+        );
+        expect_err(
+            "assert_eq(2, true)",
+            "Expected two arguments of the same type but got (int, bool)",
+            true,
+        );
+
+        expect_err(
+            "kill(2)",
+            "Mismatched types in function call: got ((int)) but expected ((tid<_>))",
+            true,
+        );
     }
 }
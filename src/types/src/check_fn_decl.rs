@@ -7,8 +7,35 @@ impl<'prog> TypeChecker<'prog> {
         &mut self,
         fn_decl: &FnDeclData,
     ) -> Result<CheckResult, TypeErrors> {
-        self.fn_type_stack.push(fn_decl.ret_type.clone());
-        let res = self.check_fn_decl_inner(fn_decl);
+        self.check_fn_decl_with_stacks(fn_decl, true)
+    }
+
+    /// Check the function with the type its returns give on the fn stack. A generator gives its elements with yield,
+    /// so the type of its elements goes on the yield stack instead and its returns, which end it, give unit.
+    pub(crate) fn check_fn_decl_with_stacks(
+        &mut self,
+        fn_decl: &FnDeclData,
+        bind_name: bool,
+    ) -> Result<CheckResult, TypeErrors> {
+        if !fn_decl.is_generator {
+            self.fn_type_stack.push(fn_decl.ret_type.clone());
+            let res = self.check_fn_decl_inner(fn_decl, bind_name);
+            self.fn_type_stack.pop();
+            return res;
+        }
+
+        let Type::Generator(elem_ty) = &fn_decl.ret_type else {
+            let e = format!(
+                "Generator '{}' must have return type 'Generator<T>' but has '{}'",
+                fn_decl.name, fn_decl.ret_type
+            );
+            return Err(TypeErrors::new_err(&e));
+        };
+
+        self.fn_type_stack.push(Type::Unit);
+        self.yield_type_stack.push(*elem_ty.clone());
+        let res = self.check_fn_decl_inner(fn_decl, bind_name);
+        self.yield_type_stack.pop();
         self.fn_type_stack.pop();
         res
     }
@@ -20,12 +47,18 @@ impl<'prog> TypeChecker<'prog> {
     // AND (somewhere in the block we encounter a terminating decl/ last_expr OR the
     // last expression of the block has the same type as the ty_ann)
     // Everything after a must_return is ignored. function returns unit => don't need must_return, but nested ret cannot return anything else
-    fn check_fn_decl_inner(&mut self, fn_decl: &FnDeclData) -> Result<CheckResult, TypeErrors> {
+    // Methods are not bound to their name, so bind_name is false for them
+    pub(crate) fn check_fn_decl_inner(
+        &mut self,
+        fn_decl: &FnDeclData,
+        bind_name: bool,
+    ) -> Result<CheckResult, TypeErrors> {
         // Assert all params have type ann and add their types
         let mut param_types: Vec<Type> = vec![];
 
         for param in fn_decl.params.iter() {
             if let Some(ty) = &param.type_ann {
+                self.check_type_known(ty)?;
                 param_types.push(ty.to_owned());
             } else {
                 let e = format!("Parameter '{}' has no type annotation", param.name);
@@ -33,6 +66,8 @@ impl<'prog> TypeChecker<'prog> {
             }
         }
 
+        self.check_type_known(&fn_decl.ret_type)?;
+
         let fn_ty = FnTypeData {
             params: param_types,
             ret_type: fn_decl.ret_type.clone(),
@@ -48,7 +83,9 @@ impl<'prog> TypeChecker<'prog> {
         };
 
         // Before checking block, add this fn to env to support recursion
-        self.assign_ident(&fn_decl.name, fn_ty.clone())?; // should work because of enterscope
+        if bind_name {
+            self.assign_ident(&fn_decl.name, fn_ty.clone())?; // should work because of enterscope
+        }
 
         // dbg!("FN_PARAMS:", &fn_decl.params, &fn_decl.name);
 
@@ -60,21 +97,27 @@ impl<'prog> TypeChecker<'prog> {
             return Ok(fn_res);
         }
 
+        // The body of a generator gives unit, whatever it yields
+        let body_ty = match fn_decl.is_generator {
+            true => Type::Unit,
+            false => fn_decl.ret_type.clone(),
+        };
+
         // check blk_ty matches overall ret type only if last_expr exists
         if fn_decl.body.last_expr.is_some() {
-            if blk_res.ty.eq(&fn_decl.ret_type) {
+            if body_ty.matches(&blk_res.ty) {
                 return Ok(fn_res);
             } else {
                 let e = format!(
                     "Function '{}' has return type '{}' but found block type '{}'",
-                    fn_decl.name, fn_decl.ret_type, blk_res.ty
+                    fn_decl.name, body_ty, blk_res.ty
                 );
                 return Err(TypeErrors::new_err(&e));
             }
         }
 
         // if no must_return, and no last_expr, and overall type is not Unit, err
-        if !fn_decl.ret_type.eq(&Type::Unit) {
+        if !body_ty.eq(&Type::Unit) {
             let e = format!(
                 "Function '{}' might not return '{}'",
                 fn_decl.name, fn_decl.ret_type
@@ -132,20 +175,24 @@ mod tests {
 
     #[test]
     fn test_type_check_fn_decl_fails() {
-        // param has no ty ann
+        // param has no ty ann and is only used with an overloaded operator, so its type can't be inferred
         let t = r"
         fn f(x : int, y) {
-
+            y + y;
         }
         ";
-        expect_err(t, "[TypeError]: Parameter 'y' has no type annotation", true);
+        expect_err(
+            t,
+            "[TypeError]: Can't infer the type of parameter 'y' of 'f', add a type annotation",
+            true,
+        );
 
         let t = r"
-        fn fac(n) {
-
+        fn neg(n) {
+            -n
         }
         ";
-        expect_err(t, "Parameter 'n' has no type annotation", true);
+        expect_err(t, "Can't infer the type of parameter 'n' of 'neg'", true);
     }
 
     #[test]
@@ -300,6 +347,39 @@ mod tests {
         ";
         expect_pass_str(t, "fn(int) -> int");
 
+        // Mutually recursive, called before declared
+        let t = r"
+        fn is_even(n: int) -> bool {
+            if n == 0 {
+                return true;
+            }
+            is_odd(n - 1)
+        }
+        fn is_odd(n: int) -> bool {
+            if n == 0 {
+                return false;
+            }
+            is_even(n - 1)
+        }
+        is_odd(7)
+        ";
+        expect_pass(t, Type::Bool);
+
+        // Forward references are checked against the later signature
+        let t = r"
+        fn f() -> int {
+            g(true)
+        }
+        fn g(x: int) -> int {
+            x
+        }
+        ";
+        expect_err(
+            t,
+            "Mismatched types in function call: got ((bool)) but expected ((int))",
+            true,
+        );
+
         // should fail bc n has type int but x has type bool
         // need to add type assignments for params before going in
         let t = r"
@@ -365,4 +445,79 @@ mod tests {
         ";
         expect_pass_str(t, "fn(int) -> fn(int) -> int");
     }
+
+    #[test]
+    fn test_type_check_generator() {
+        let t = r"
+        fn gen(n: int) -> Generator<int> {
+            yield n;
+            if n > 0 {
+                return;
+            }
+            yield 2;
+        }
+        gen
+        ";
+        expect_pass_str(t, "fn(int) -> Generator<int>");
+
+        // the type of the elements is inferred from the yields
+        let t = r#"
+        fn gen() {
+            yield "a";
+        }
+        gen
+        "#;
+        expect_pass_str(t, "fn() -> Generator<str>");
+
+        let t = r"
+        fn gen() -> Generator<int> {
+            yield 1;
+        }
+        let total = 0;
+        for x in gen() {
+            total = total + x;
+        }
+        [x > 0 for x in gen()]
+        ";
+        expect_pass_str(t, "[bool]");
+
+        let t = r"
+        fn gen() -> Generator<int> {
+            yield true;
+        }
+        ";
+        expect_err(t, "Expected type 'int' for yield but got 'bool'", true);
+
+        let t = r"
+        fn gen() {
+            yield 1;
+            yield true;
+        }
+        ";
+        expect_err(t, "Expected type 'int' for yield but got 'bool'", true);
+
+        let t = r"
+        fn gen() -> int {
+            yield 1;
+        }
+        ";
+        expect_err(
+            t,
+            "Generator 'gen' must have return type 'Generator<T>' but has 'int'",
+            true,
+        );
+
+        // the body gives unit, and returns end the generator without a value
+        let t = r"
+        fn gen() -> Generator<int> {
+            yield 1;
+            return 2;
+        }
+        ";
+        expect_err(
+            t,
+            "Expected function return type '()' but return statement has type 'int'",
+            true,
+        );
+    }
 }
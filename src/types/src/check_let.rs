@@ -1,10 +1,14 @@
 use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
-use parser::structs::LetStmtData;
+use parser::structs::{DestructurePattern, LetDestructureData, LetStmtData, Type};
 
 impl<'prog> TypeChecker<'prog> {
     pub(crate) fn check_let(&mut self, stmt: &LetStmtData) -> Result<CheckResult, TypeErrors> {
         let mut ty_errs = TypeErrors::new();
 
+        if let Some(ty_ann) = &stmt.type_ann {
+            self.check_type_known(ty_ann)?;
+        }
+
         let mut expr_type: Option<CheckResult> = None;
         match self.check_expr(&stmt.expr) {
             Ok(res) => {
@@ -51,7 +55,7 @@ impl<'prog> TypeChecker<'prog> {
             (Some(expr_res), Some(ty_ann)) => {
                 self.assign_ident(&stmt.ident.to_owned(), ty_ann.to_owned())?;
 
-                if !ty_ann.eq(&expr_res.ty) {
+                if !ty_ann.matches(&expr_res.ty) {
                     let string = format!(
                         "'{}' has declared type {} but assigned type {}",
                         stmt.ident, ty_ann, expr_res.ty
@@ -70,13 +74,81 @@ impl<'prog> TypeChecker<'prog> {
             }
         }
     }
+
+    /// An array pattern binds elements of an array or chars of a string, and the rest as a value of the same type.
+    /// A struct pattern binds fields of a struct of its type, and names all of them unless it has '..'
+    pub(crate) fn check_let_destructure(
+        &mut self,
+        stmt: &LetDestructureData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut res = self.check_expr(&stmt.expr)?;
+
+        match &stmt.pat {
+            DestructurePattern::Array(elems, rest) => {
+                let Some(elem_ty) = TypeChecker::elem_type(&res.ty) else {
+                    let e = format!(
+                        "Can't destructure type '{}' with array pattern {}, expected an array or a string",
+                        res.ty, stmt.pat
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                };
+
+                for name in elems.iter() {
+                    self.assign_ident(name, elem_ty.clone())?;
+                }
+                if let Some(rest) = rest {
+                    self.assign_ident(rest, res.ty.clone())?;
+                }
+            }
+            DestructurePattern::Struct(name, fields, has_rest) => {
+                let def = self.get_struct(name)?;
+                if !res.ty.eq(&Type::Named(def.name.clone())) {
+                    let e = format!(
+                        "Can't destructure type '{}' with struct pattern {}",
+                        res.ty, stmt.pat
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+
+                let mut ty_errs = TypeErrors::new();
+                for (field, binding) in fields.iter() {
+                    match def.field(field) {
+                        Some(ty) => self.assign_ident(binding, ty.clone())?,
+                        None => {
+                            let e = format!("Struct {} has no field '{}'", def.name, field);
+                            ty_errs.add(&e);
+                        }
+                    }
+                }
+
+                if !has_rest {
+                    for (field, _) in def.fields.iter() {
+                        if !fields.iter().any(|(given, _)| given == field) {
+                            let e = format!(
+                                "Missing field '{}' for {} in let pattern, add '..' to leave it out",
+                                field, def.name
+                            );
+                            ty_errs.add(&e);
+                        }
+                    }
+                }
+
+                if !ty_errs.is_ok() {
+                    return Err(ty_errs);
+                }
+            }
+        }
+
+        res.ty = Type::Unit;
+        Ok(res)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use parser::structs::Type;
 
-    use crate::type_checker::{expect_err, expect_pass};
+    use crate::type_checker::{expect_err, expect_pass, expect_pass_str};
 
     #[test]
     fn test_type_check_sym_advanced() {
@@ -162,4 +234,70 @@ mod tests {
             false,
         );
     }
+
+    #[test]
+    fn test_type_check_let_destructure() {
+        let t = r#"
+        let [a, b, ..rest] = [1, 2, 3];
+        let [c] = "é";
+        let [..all] = "ab";
+        [a + b, len(rest)]
+        "#;
+        expect_pass_str(t, "[int]");
+
+        let t = r"
+        struct P { x: int, y: bool }
+        let P { x, y: b } = P { x: 1, y: true };
+        let P { x: z, .. } = P { x: 2, y: false };
+        if b { x + z } else { 0 }
+        ";
+        expect_pass(t, Type::Int);
+
+        // the types are inferred for unannotated params too
+        let t = r"
+        struct P { x: int, y: bool }
+        fn f(p, xs) {
+            let P { x, .. } = p;
+            let [first, ..others] = [x];
+            let [a] = xs;
+            a + first
+        }
+        f
+        ";
+        expect_pass_str(t, "fn(P, [int]) -> int");
+
+        expect_err(
+            "let [a] = 1;",
+            "Can't destructure type 'int' with array pattern [a], expected an array or a string",
+            true,
+        );
+        expect_err("let [a] = [1]; a = true;", "declared with type int", true);
+
+        let t = r"
+        struct P { x: int, y: bool }
+        struct Q { x: int }
+        let Q { x } = P { x: 1, y: true };
+        ";
+        expect_err(
+            t,
+            "Can't destructure type 'P' with struct pattern Q { x }",
+            true,
+        );
+
+        let t = r"
+        struct P { x: int, y: bool }
+        let P { x } = P { x: 1, y: true };
+        ";
+        expect_err(
+            t,
+            "Missing field 'y' for P in let pattern, add '..' to leave it out",
+            true,
+        );
+
+        let t = r"
+        struct P { x: int }
+        let P { z, .. } = P { x: 1 };
+        ";
+        expect_err(t, "Struct P has no field 'z'", true);
+    }
 }
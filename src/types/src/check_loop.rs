@@ -1,10 +1,27 @@
-use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
-use parser::structs::{LoopData, Type};
+use crate::type_checker::{new_env_with_syms, CheckResult, TypeChecker, TypeErrors};
+use parser::structs::{BreakData, ForData, LoopData, Type};
 
 impl<'prog> TypeChecker<'prog> {
     // if loop cond present, must be bool. else just check blks.
-    // break in a blk is a stmt, is unit type.
+    // break in a blk is a stmt, is unit type. The loop has the type of the values breaks leave it with, or unit
     pub(crate) fn check_loop(&mut self, loop_data: &LoopData) -> Result<CheckResult, TypeErrors> {
+        self.loop_type_stack.push((loop_data.label.clone(), None));
+        let res = self.check_loop_inner(loop_data);
+        let (_, break_ty) = self
+            .loop_type_stack
+            .pop()
+            .expect("Loop type stack should be present since pushed earlier");
+        res?;
+
+        // TODO: a loop with no cond and no must_break in its block has must_return = true
+        Ok(CheckResult {
+            ty: break_ty.unwrap_or(Type::Unit),
+            must_break: false, // loop never contributes to must_break of outer
+            must_return: false,
+        })
+    }
+
+    fn check_loop_inner(&mut self, loop_data: &LoopData) -> Result<(), TypeErrors> {
         let mut ty_errs = TypeErrors::new();
 
         // if condition: check has type bool. add errs if any
@@ -34,17 +51,74 @@ impl<'prog> TypeChecker<'prog> {
             ty_errs.append(errs);
         }
 
-        // TODO: a loop with no cond and no must_break in its block has must_return = true
         if ty_errs.is_ok() {
-            Ok(CheckResult {
-                ty: Type::Unit,
-                must_break: false, // loop never contributes to must_break of outer
-                must_return: false,
-            })
+            Ok(())
         } else {
             Err(ty_errs)
         }
     }
+
+    // the variable is bound to an int of the range or an element of the array or string in the body
+    pub(crate) fn check_for(&mut self, for_data: &ForData) -> Result<CheckResult, TypeErrors> {
+        let (var_ty, _) = self.check_iterable(&for_data.iter)?;
+
+        self.envs
+            .push(new_env_with_syms(vec![for_data.var.clone()]));
+        self.loop_type_stack.push((for_data.label.clone(), None));
+        let check_blk = self
+            .assign_ident(&for_data.var, var_ty)
+            .and_then(|_| self.check_block(&for_data.body, vec![]));
+        self.loop_type_stack.pop();
+        self.envs.pop();
+
+        check_blk?;
+        Ok(CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        })
+    }
+
+    // break leaves the innermost loop, or the innermost one with its label. All the values
+    // a loop is left with must have the same type, where break without a value leaves it with unit
+    pub(crate) fn check_break(&mut self, brk: &BreakData) -> Result<CheckResult, TypeErrors> {
+        let ty = match &brk.value {
+            Some(expr) => self.check_expr(expr)?.ty,
+            None => Type::Unit,
+        };
+
+        let target = match &brk.label {
+            Some(label) => self
+                .loop_type_stack
+                .iter_mut()
+                .rev()
+                .find(|(lp_label, _)| lp_label.as_ref() == Some(label)),
+            None => self.loop_type_stack.last_mut(),
+        };
+
+        if let Some((_, break_ty)) = target {
+            match break_ty {
+                None => *break_ty = Some(ty),
+                Some(prev) => match prev.unify(&ty) {
+                    Some(ty) => *prev = ty,
+                    None => {
+                        let e = format!(
+                            "break values have type mismatch - expected: {}, got: {}",
+                            prev, ty
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                },
+            }
+        }
+
+        // must_break base case
+        Ok(CheckResult {
+            ty: Type::Unit,
+            must_break: true,
+            must_return: false,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -99,6 +173,47 @@ mod tests {
         expect_pass(t, Type::Unit);
     }
 
+    #[test]
+    fn test_type_check_for() {
+        let t = r#"
+        let total = 0;
+        for x in 0..10 {
+            total = total + x;
+        }
+        for x in [1, 2] {
+            total = total + x;
+        }
+        for c in "abc" {
+            if c == "b" {
+                break;
+            }
+        }
+        "#;
+        expect_pass(t, Type::Unit);
+
+        // the variable is only bound in the body
+        expect_err(
+            "for x in 0..2 {} x",
+            "[TypeError]: Identifier 'x' not declared",
+            true,
+        );
+        expect_err(
+            "for x in [true] { x + 1; }",
+            "Can't apply '+' to types 'bool' and 'int'",
+            true,
+        );
+        expect_err(
+            "for x in 5 {}",
+            "Can't iterate over type 'int', expected a range, an array, a string or a generator",
+            true,
+        );
+        expect_err(
+            "for x in 0..true {}",
+            "Expected type 'int' for the bounds of a range but got 'bool'",
+            true,
+        );
+    }
+
     #[test]
     fn test_type_check_errs() {
         // cond has errs
@@ -120,6 +235,11 @@ mod tests {
             "Expected type 'bool' for loop predicate but got 'int'",
             true,
         );
+        expect_err(
+            r#"loop "go" { break; }"#,
+            "Expected type 'bool' for loop predicate but got 'str'",
+            true,
+        );
 
         // cond and blk have errs
         let t = r"
@@ -193,4 +313,65 @@ mod tests {
             true,
         );
     }
+
+    #[test]
+    fn test_type_check_break_values() {
+        let t = r"
+        let x = loop {
+            break 2;
+        };
+        x
+        ";
+        expect_pass(t, Type::Int);
+
+        // breaks from inner loops to a labelled one
+        let t = r"
+        let xs = [3, 8, 1];
+        'search: loop {
+            for x in xs {
+                if x > 5 {
+                    break 'search Some(x);
+                }
+            }
+            loop {
+                break;
+            }
+            break 'search None;
+        }
+        ";
+        expect_pass(t, Type::Option(Box::new(Type::Int)));
+
+        // as the last expression of a fn
+        let t = r"
+        fn first_over(xs: [int], n: int) -> int {
+            let i = 0;
+            loop {
+                if xs[i] > n {
+                    break xs[i];
+                }
+                i = i + 1;
+            }
+        }
+        first_over([1, 5], 2)
+        ";
+        expect_pass(t, Type::Int);
+
+        expect_err(
+            "loop { if true { break 2; } break true; }",
+            "break values have type mismatch - expected: int, got: bool",
+            true,
+        );
+        expect_err(
+            "let x: int = loop { break 2.5; };",
+            "'x' has declared type int but assigned type float",
+            true,
+        );
+        // break without a value leaves the loop with unit
+        expect_err(
+            "'a: loop { loop { break 'a; } break 'a 1; }",
+            "break values have type mismatch - expected: (), got: int",
+            true,
+        );
+        expect_err("loop { break x; }", "Identifier 'x' not declared", true);
+    }
 }
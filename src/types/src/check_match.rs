@@ -0,0 +1,787 @@
+use crate::type_checker::{new_env_with_syms, CheckResult, TypeChecker, TypeErrors};
+use parser::structs::{Expr, FnParam, MatchData, Pattern, Type};
+
+impl<'prog> TypeChecker<'prog> {
+    /*
+    0. Check the pattern of every arm against the type of the matched expr, collecting the names it binds.
+       Only Options, Results and enums have variants, and alternatives must bind the same names to the same types
+    1. An arm can't be reached if the arms without a guard before it match all the values its pattern does
+    2. Check every guard is a bool and every arm block, with the names of the pattern bound, and collect errors
+    3. The arms without a guard must match every value
+    4. No errs: arms that don't terminate must all have the same type, as for select
+    */
+    pub(crate) fn check_match(
+        &mut self,
+        match_data: &MatchData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let expr_res = self.check_expr(&match_data.expr)?;
+        let ty = &expr_res.ty;
+
+        let mut ty_errs = TypeErrors::new();
+        let mut arm_tys: Vec<CheckResult> = vec![];
+        let mut unguarded: Vec<&Pattern> = vec![];
+
+        for arm in match_data.arms.iter() {
+            let binds = match self.check_pattern(&arm.pat, ty) {
+                Ok(binds) => binds,
+                Err(mut errs) => {
+                    ty_errs.append(&mut errs);
+                    continue;
+                }
+            };
+
+            for alt in arm.pat.alternatives() {
+                if !self.covers(&unguarded, alt, ty) {
+                    continue;
+                }
+
+                let e = match alt {
+                    Pattern::Variant(variant, sub)
+                        if sub.as_ref().is_none_or(|sub| sub.is_irrefutable())
+                            && unguarded.iter().any(|pat| {
+                                pat.alternatives().iter().any(
+                                    |prev| matches!(prev, Pattern::Variant(v, _) if v == variant),
+                                )
+                            }) =>
+                    {
+                        format!("Variant '{}' is matched more than once", variant)
+                    }
+                    _ => format!(
+                        "Pattern '{}' can't be reached as the arms before it match all its values",
+                        alt
+                    ),
+                };
+                ty_errs.add(&e);
+            }
+
+            // an arm with a guard may not be taken, so later arms can still match its values
+            if arm.guard.is_none() {
+                unguarded.push(&arm.pat);
+            }
+
+            let params: Vec<FnParam> = binds
+                .into_iter()
+                .map(|(name, ty)| FnParam {
+                    name,
+                    type_ann: Some(ty),
+                })
+                .collect();
+
+            if let Some(guard) = &arm.guard {
+                if let Err(mut errs) = self.check_guard(guard, &params) {
+                    ty_errs.append(&mut errs);
+                }
+            }
+
+            match self.check_block(&arm.blk, params) {
+                Ok(res) => arm_tys.push(res),
+                Err(mut errs) => ty_errs.append(&mut errs),
+            }
+        }
+
+        let missing = self.missing_patterns(&unguarded, ty);
+        if !missing.is_empty() {
+            let mut e = format!(
+                "match on '{}' is missing an arm for {}",
+                ty,
+                missing.join(" and ")
+            );
+            if match_data.arms.iter().any(|arm| arm.guard.is_some()) {
+                e.push_str(", arms with a guard don't count as they may not be taken");
+            }
+            ty_errs.add(&e);
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        // arms that must break / return don't contribute to the overall type
+        let mut overall_ty: Option<Type> = None;
+        for arm_ty in arm_tys.iter() {
+            if arm_ty.must_break || arm_ty.must_return {
+                continue;
+            }
+
+            match &overall_ty {
+                None => overall_ty = Some(arm_ty.ty.clone()),
+                Some(ty) => match ty.unify(&arm_ty.ty) {
+                    Some(ty) => overall_ty = Some(ty),
+                    None => {
+                        let e = format!(
+                            "match arms have type mismatch - expected: {}, got: {}",
+                            ty, arm_ty.ty
+                        );
+                        ty_errs.add(&e);
+                        return Err(ty_errs);
+                    }
+                },
+            }
+        }
+
+        Ok(CheckResult {
+            ty: overall_ty.unwrap_or(Type::Unit),
+            must_break: expr_res.must_break || arm_tys.iter().all(|x| x.must_break),
+            must_return: expr_res.must_return || arm_tys.iter().all(|x| x.must_return),
+        })
+    }
+
+    /// The variants of an Option, Result or enum and the type of the value each holds, if any
+    fn variants_of(&self, ty: &Type) -> Option<Vec<(String, Option<Type>)>> {
+        match ty {
+            Type::Option(ty) => Some(vec![
+                ("Some".to_string(), Some(*ty.clone())),
+                ("None".to_string(), None),
+            ]),
+            Type::Result(ok, err) => Some(vec![
+                ("Ok".to_string(), Some(*ok.clone())),
+                ("Err".to_string(), Some(*err.clone())),
+            ]),
+            Type::Named(name) => self.get_enum(name).ok().map(|def| def.variants),
+            _ => None,
+        }
+    }
+
+    /// Check the pattern can match values of the type, giving the names it binds and their types
+    fn check_pattern(
+        &mut self,
+        pat: &Pattern,
+        ty: &Type,
+    ) -> Result<Vec<(String, Type)>, TypeErrors> {
+        match pat {
+            Pattern::Wildcard => Ok(vec![]),
+            Pattern::Bind(name) => Ok(vec![(name.to_string(), ty.clone())]),
+            Pattern::Literal(lit) => {
+                let lit_ty = self.check_expr(lit)?.ty;
+                if !ty.matches(&lit_ty) {
+                    let e = format!("Pattern '{}' can't match type '{}'", pat, ty);
+                    return Err(TypeErrors::new_err(&e));
+                }
+                Ok(vec![])
+            }
+            Pattern::Range(..) => {
+                if !ty.matches(&Type::Int) {
+                    let e = format!("Pattern '{}' can't match type '{}'", pat, ty);
+                    return Err(TypeErrors::new_err(&e));
+                }
+                Ok(vec![])
+            }
+            Pattern::At(name, sub) => {
+                let mut binds = vec![(name.to_string(), ty.clone())];
+                for (bound, bound_ty) in self.check_pattern(sub, ty)? {
+                    if bound == *name {
+                        let e = format!("'{}' is bound more than once in pattern '{}'", name, pat);
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                    binds.push((bound, bound_ty));
+                }
+                Ok(binds)
+            }
+            Pattern::Variant(variant, sub) => {
+                let held_ty = self
+                    .variants_of(ty)
+                    .and_then(|variants| variants.into_iter().find(|(v, _)| v == variant));
+                let Some((_, held_ty)) = held_ty else {
+                    let e = format!("Pattern '{}' can't match type '{}'", pat, ty);
+                    return Err(TypeErrors::new_err(&e));
+                };
+
+                match (sub, held_ty) {
+                    (Some(sub), Some(held_ty)) => self.check_pattern(sub, &held_ty),
+                    (None, None) => Ok(vec![]),
+                    (Some(_), None) => {
+                        let e = format!("Variant '{}' holds no value to bind", variant);
+                        Err(TypeErrors::new_err(&e))
+                    }
+                    (None, Some(held_ty)) => {
+                        let e = format!(
+                            "Variant '{}' holds a value of type {}, which must be bound e.g {}(x)",
+                            variant, held_ty, variant
+                        );
+                        Err(TypeErrors::new_err(&e))
+                    }
+                }
+            }
+            Pattern::Or(alts) => {
+                let mut alt_binds = vec![];
+                for alt in alts.iter() {
+                    alt_binds.push(self.check_pattern(alt, ty)?);
+                }
+
+                let first = alt_binds.first().cloned().unwrap_or_default();
+                for binds in alt_binds.iter().skip(1) {
+                    let mut names: Vec<&String> = binds.iter().map(|(name, _)| name).collect();
+                    let mut first_names: Vec<&String> =
+                        first.iter().map(|(name, _)| name).collect();
+                    names.sort();
+                    first_names.sort();
+                    if names != first_names {
+                        let e =
+                            format!("Alternatives of pattern '{}' must bind the same names", pat);
+                        return Err(TypeErrors::new_err(&e));
+                    }
+
+                    for (name, bound_ty) in binds.iter() {
+                        let Some((_, first_ty)) = first.iter().find(|(first, _)| first == name)
+                        else {
+                            continue;
+                        };
+                        if !first_ty.matches(bound_ty) {
+                            let e = format!(
+                                "'{}' is bound to type '{}' and type '{}' in pattern '{}'",
+                                name, first_ty, bound_ty, pat
+                            );
+                            return Err(TypeErrors::new_err(&e));
+                        }
+                    }
+                }
+
+                Ok(first)
+            }
+        }
+    }
+
+    /// Whether the patterns match every value the pattern does. Expects the patterns to have been checked.
+    fn covers(&self, pats: &[&Pattern], pat: &Pattern, ty: &Type) -> bool {
+        let alts: Vec<&Pattern> = pats.iter().flat_map(|pat| pat.alternatives()).collect();
+        if alts.iter().any(|alt| alt.is_irrefutable()) {
+            return true;
+        }
+
+        match pat {
+            Pattern::Wildcard | Pattern::Bind(_) => self.missing_patterns(pats, ty).is_empty(),
+            Pattern::Literal(Expr::Integer(val)) => Self::ints_cover(&alts, *val, *val),
+            Pattern::Literal(lit) => alts.iter().any(
+                |alt| matches!(alt, Pattern::Literal(other) if other.to_string() == lit.to_string()),
+            ),
+            Pattern::Range(start, end) => Self::ints_cover(&alts, *start, *end),
+            Pattern::At(_, pat) => self.covers(pats, pat, ty),
+
+            Pattern::Variant(variant, sub) => {
+                let subs = Self::variant_subpatterns(&alts, variant);
+                if subs.iter().any(|sub| sub.is_none()) {
+                    return true;
+                }
+
+                let held_ty = self
+                    .variants_of(ty)
+                    .and_then(|variants| variants.into_iter().find(|(v, _)| v == variant))
+                    .and_then(|(_, held_ty)| held_ty);
+                match (sub, held_ty) {
+                    (Some(sub), Some(held_ty)) => {
+                        let subs: Vec<&Pattern> = subs.into_iter().flatten().collect();
+                        !subs.is_empty() && self.covers(&subs, sub, &held_ty)
+                    }
+                    _ => false,
+                }
+            }
+            Pattern::Or(alts) => alts.iter().all(|alt| self.covers(pats, alt, ty)),
+        }
+    }
+
+    /// Patterns for the values of the type the patterns don't match, e.g None or Some(_).
+    /// Only the variants of Options, Results and enums, and true and false for bools, can all be matched
+    /// without a pattern matching every value. Expects the patterns to have been checked.
+    fn missing_patterns(&self, pats: &[&Pattern], ty: &Type) -> Vec<String> {
+        let alts: Vec<&Pattern> = pats.iter().flat_map(|pat| pat.alternatives()).collect();
+        if alts.iter().any(|alt| alt.is_irrefutable()) {
+            return vec![];
+        }
+
+        if let Some(variants) = self.variants_of(ty) {
+            let mut missing = vec![];
+            for (variant, held_ty) in variants.iter() {
+                let subs = Self::variant_subpatterns(&alts, variant);
+                if subs.is_empty() {
+                    missing.push(variant.to_string());
+                    continue;
+                }
+
+                let (Some(held_ty), false) = (held_ty, subs.iter().any(|sub| sub.is_none())) else {
+                    continue;
+                };
+                let subs: Vec<&Pattern> = subs.into_iter().flatten().collect();
+                for sub in self.missing_patterns(&subs, held_ty) {
+                    missing.push(format!("{}({})", variant, sub));
+                }
+            }
+            return missing;
+        }
+
+        if ty.eq(&Type::Bool) {
+            return ["true", "false"]
+                .into_iter()
+                .filter(|lit| {
+                    !alts.iter().any(
+                        |alt| matches!(alt, Pattern::Literal(other) if other.to_string() == *lit),
+                    )
+                })
+                .map(String::from)
+                .collect();
+        }
+
+        vec!["_".to_string()]
+    }
+
+    /// Whether the int literals and ranges of the alternatives match every int from start to end
+    fn ints_cover(alts: &[&Pattern], start: i64, end: i64) -> bool {
+        let mut ranges: Vec<(i64, i64)> = alts
+            .iter()
+            .filter_map(|alt| match alt {
+                Pattern::Literal(Expr::Integer(val)) => Some((*val, *val)),
+                Pattern::Range(start, end) => Some((*start, *end)),
+                _ => None,
+            })
+            .collect();
+        ranges.sort();
+
+        // the first int not matched yet, None once every int up to i64::MAX is
+        let mut next = Some(start);
+        for (range_start, range_end) in ranges {
+            match next {
+                Some(int) if int > end => break,
+                Some(int) if range_start <= int && int <= range_end => {
+                    next = range_end.checked_add(1);
+                }
+                Some(int) if int < range_start => return false,
+                _ => (),
+            }
+        }
+
+        next.is_none_or(|int| int > end)
+    }
+
+    /// The patterns for the value the variant holds, in the alternatives matching the variant
+    fn variant_subpatterns<'pat>(
+        alts: &[&'pat Pattern],
+        variant: &str,
+    ) -> Vec<Option<&'pat Pattern>> {
+        alts.iter()
+            .filter_map(|alt| match alt {
+                Pattern::Variant(v, sub) if v == variant => Some(sub.as_deref()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The guard of a match arm must be a bool, with the value of the variant bound as in the arm block
+    fn check_guard(&mut self, guard: &Expr, params: &[FnParam]) -> Result<(), TypeErrors> {
+        let syms = params.iter().map(|param| param.name.clone()).collect();
+        self.envs.push(new_env_with_syms(syms));
+        for param in params.iter() {
+            if let Some(ty) = &param.type_ann {
+                self.assign_ident(&param.name, ty.clone())?;
+            }
+        }
+        let res = self.check_expr(guard);
+        self.envs.pop();
+
+        let res = res?;
+        if !res.ty.eq(&Type::Bool) {
+            let e = format!(
+                "Expected type '{}' for match guard but got '{}'",
+                Type::Bool,
+                res.ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(())
+    }
+
+    /// expr? gives the value held by Some or Ok, and returns None or Err from the enclosing function.
+    /// So the function must return an Option if expr is an Option, or a Result with the same error type.
+    pub(crate) fn check_try(
+        &mut self,
+        expr: &parser::structs::Expr,
+    ) -> Result<CheckResult, TypeErrors> {
+        let res = self.check_expr(expr)?;
+
+        let Some(fn_ty) = self.fn_type_stack.last() else {
+            let e = "'?' can only be used inside a function";
+            return Err(TypeErrors::new_err(e));
+        };
+
+        let ty = match (&res.ty, fn_ty) {
+            (Type::Option(ty), Type::Option(_)) => *ty.clone(),
+            (Type::Result(ty, err), Type::Result(_, fn_err)) if fn_err.matches(err) => *ty.clone(),
+            (Type::Result(_, err), Type::Result(_, fn_err)) => {
+                let e = format!(
+                    "'?' can't return error type '{}' from a function returning errors of type '{}'",
+                    err, fn_err
+                );
+                return Err(TypeErrors::new_err(&e));
+            }
+            (Type::Option(_) | Type::Result(..), fn_ty) => {
+                let e = format!(
+                    "'?' on type '{}' can't be used in a function returning '{}'",
+                    res.ty, fn_ty
+                );
+                return Err(TypeErrors::new_err(&e));
+            }
+            (ty, _) => {
+                let e = format!(
+                    "Can't apply '?' to type '{}', expected an Option or Result",
+                    ty
+                );
+                return Err(TypeErrors::new_err(&e));
+            }
+        };
+
+        Ok(CheckResult {
+            ty,
+            must_break: res.must_break,
+            must_return: res.must_return,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass, expect_pass_str};
+
+    #[test]
+    fn test_type_check_option_result() {
+        expect_pass_str("Some(2)", "Option<int>");
+        expect_pass_str("None", "Option<_>");
+        expect_pass_str("Ok(2)", "Result<int, _>");
+        expect_pass_str("Err(\"oops\")", "Result<_, str>");
+
+        // unknown parameters are filled in from the annotation
+        let t = r#"
+        let x : Option<int> = None;
+        let y : Result<int, str> = Err("oops");
+        x = Some(3);
+        unwrap(x) + unwrap(y)
+        "#;
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        fn find(n : int) -> Option<int> {
+            if n > 0 {
+                Some(n)
+            } else {
+                None
+            }
+        }
+        is_some(find(2)) && is_none(find(0))
+        ";
+        expect_pass(t, Type::Bool);
+
+        expect_err(
+            "let x : Option<int> = Some(true);",
+            "'x' has declared type Option<int> but assigned type Option<bool>",
+            true,
+        );
+        expect_err(
+            "is_ok(Some(2))",
+            "Expected Result but got (Option<int>)",
+            true,
+        );
+        expect_err("unwrap(2)", "Expected Option or Result but got (int)", true);
+    }
+
+    #[test]
+    fn test_type_check_match() {
+        let t = r"
+        let x : Option<int> = Some(2);
+        match x {
+            Some(v) => { v + 1 }
+            None => { 0 }
+        }
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r#"
+        let x : Result<int, str> = Err("oops");
+        let s : str = match x {
+            Err(e) => { e }
+            Ok(v) => { itoa(v) }
+        };
+        s
+        "#;
+        expect_pass(t, Type::String);
+
+        // arms that return don't contribute to the type
+        let t = r"
+        fn f(x : Option<int>) -> int {
+            let v = match x {
+                Some(v) => { v }
+                None => { return 0; }
+            };
+            v * 2
+        }
+        f(None)
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        enum Shape { Circle(float), Square(float), Empty }
+        fn area(s: Shape) -> float {
+            match s {
+                Circle(r) => { 3.14 * r * r }
+                Square(x) => { x * x }
+                Empty => { 0.0 }
+            }
+        }
+        area(Square(2.0))
+        ";
+        expect_pass(t, Type::Float);
+
+        // guards see the value held, and a variant can be matched again after an arm with a guard
+        let t = r#"
+        fn sign(x : Option<int>) -> str {
+            match x {
+                Some(n) if n > 0 => { "positive" }
+                Some(n) if n < 0 => { "negative" }
+                Some(n) => { "zero" }
+                None => { "none" }
+            }
+        }
+        sign(Some(2))
+        "#;
+        expect_pass(t, Type::String);
+
+        // nested patterns, literals and alternatives sharing an arm
+        let t = r#"
+        enum Shape { Circle(int), Square(int), Empty }
+        fn size(s: Option<Shape>) -> int {
+            match s {
+                Some(Circle(0) | Square(0)) | Some(Empty) => { 0 }
+                Some(Circle(r) | Square(r)) => { r }
+                None => { -1 }
+            }
+        }
+        fn name(n: int) -> str {
+            match n {
+                1 | 2 | 3 => { "small" }
+                -1 => { "negative one" }
+                other => { itoa(other) }
+            }
+        }
+        fn flag(b: Option<bool>) -> int {
+            match b {
+                Some(true) => { 1 }
+                Some(false) => { 0 }
+                None => { -1 }
+            }
+        }
+        name(size(Some(Circle(2))) + flag(None))
+        "#;
+        expect_pass(t, Type::String);
+
+        // ranges, and names bound to what a pattern matches
+        let t = r#"
+        fn digits(n: int) -> int {
+            match n {
+                -9..=9 => { 1 }
+                m @ 10..=99 | m @ -99..=-10 => { 2 }
+                m @ _ if m > 0 => { digits(m / 10) + 1 }
+                _ => { digits(-n) }
+            }
+        }
+        digits(42)
+        "#;
+        expect_pass(t, Type::Int);
+
+        let t = r#"
+        let s = "a";
+        match s {
+            "a" => { 1 }
+            _ => { 2 }
+        }
+        "#;
+        expect_pass(t, Type::Int);
+    }
+
+    #[test]
+    fn test_type_check_match_errs() {
+        expect_err(
+            "match 2 { None => { } }",
+            "Pattern 'None' can't match type 'int'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(v) => { v } }",
+            "match on 'Option<int>' is missing an arm for None",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Ok(v) => { v } None => { 0 } Some(v) => { v } }",
+            "Pattern 'Ok(v)' can't match type 'Option<int>'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { None => { 0 } None => { 0 } Some(v) => { v } }",
+            "Variant 'None' is matched more than once",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(v) => { v } None => { true } }",
+            "match arms have type mismatch - expected: int, got: bool",
+            true,
+        );
+        expect_err(
+            "enum Shape { Circle(float), Empty } match Empty { Circle => { 0.0 } Empty(x) => { x } }",
+            "Variant 'Circle' holds a value of type float, which must be bound e.g Circle(x)",
+            true,
+        );
+        expect_err(
+            "enum Shape { Circle(float), Empty } match Empty { Circle(r) => { r } Empty(x) => { x } }",
+            "Variant 'Empty' holds no value to bind",
+            true,
+        );
+        expect_err(
+            "enum Shape { Circle(float), Square(float), Empty } match Empty { Circle(r) => { r } }",
+            "match on 'Shape' is missing an arm for Square and Empty",
+            true,
+        );
+        expect_err(
+            "enum Shape { Empty } match Empty { Some(x) => { x } Empty => { 0 } }",
+            "Pattern 'Some(x)' can't match type 'Shape'",
+            true,
+        );
+        // the value held is only bound in its arm
+        expect_err(
+            "match Some(2) { Some(v) => { v } None => { v } }",
+            "Identifier 'v' not declared",
+            true,
+        );
+
+        // guards
+        expect_err(
+            "match Some(2) { Some(v) if v => { v } Some(v) => { v } None => { 0 } }",
+            "Expected type 'bool' for match guard but got 'int'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(v) if v > 0 => { v } None => { 0 } }",
+            "match on 'Option<int>' is missing an arm for Some, arms with a guard don't count as they may not be taken",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(v) => { v } Some(v) if v > 0 => { v } None => { 0 } }",
+            "Variant 'Some' is matched more than once",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(v) if w > 0 => { v } Some(v) => { v } None => { 0 } }",
+            "Identifier 'w' not declared",
+            true,
+        );
+
+        // nested patterns and alternatives
+        expect_err(
+            "match Some(2) { Some(1) => { 1 } None => { 0 } }",
+            "match on 'Option<int>' is missing an arm for Some(_)",
+            true,
+        );
+        expect_err(
+            "match Some(Some(true)) { Some(Some(true)) => { 1 } Some(None) => { 0 } }",
+            "match on 'Option<Option<bool>>' is missing an arm for Some(Some(false)) and None",
+            true,
+        );
+        expect_err(
+            "match 2 { 1 | 2 => { 1 } }",
+            "match on 'int' is missing an arm for _",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(true) => { 1 } _ => { 0 } }",
+            "Pattern 'true' can't match type 'int'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(x) | None => { 1 } }",
+            "Alternatives of pattern 'Some(x) | None' must bind the same names",
+            true,
+        );
+        expect_err(
+            "let r : Result<int, str> = Ok(2); match r { Ok(x) | Err(x) => { 1 } }",
+            "'x' is bound to type 'int' and type 'str' in pattern 'Ok(x) | Err(x)'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { Some(1) | Some(2) => { 1 } Some(2) => { 2 } _ => { 0 } }",
+            "Pattern 'Some(2)' can't be reached as the arms before it match all its values",
+            true,
+        );
+        expect_err(
+            "match 5 { 1..=9 => { 1 } 0 | 5 => { 0 } _ => { 2 } }",
+            "Pattern '5' can't be reached as the arms before it match all its values",
+            true,
+        );
+        expect_err(
+            "match 5 { 1..=9 => { 1 } n @ 2..=4 => { n } _ => { 2 } }",
+            "Pattern '2..=4' can't be reached as the arms before it match all its values",
+            true,
+        );
+        expect_err(
+            "match 5 { 0..=9 => { 1 } 11..=100 => { 0 } }",
+            "match on 'int' is missing an arm for _",
+            true,
+        );
+        expect_err(
+            "match true { 1..=2 => { 1 } _ => { 0 } }",
+            "Pattern '1..=2' can't match type 'bool'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { x @ Some(x) => { 1 } _ => { 0 } }",
+            "'x' is bound more than once in pattern 'x @ Some(x)'",
+            true,
+        );
+        expect_err(
+            "match Some(2) { x => { 1 } None => { 0 } }",
+            "Pattern 'None' can't be reached as the arms before it match all its values",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_type_check_try() {
+        let t = r#"
+        fn parse(s : str) -> Result<int, str> {
+            if string_len(s) > 0 {
+                Ok(atoi(s))
+            } else {
+                Err("empty")
+            }
+        }
+
+        fn double(s : str) -> Result<int, str> {
+            let n = parse(s)?;
+            Ok(n * 2)
+        }
+        double("21")
+        "#;
+        expect_pass_str(t, "Result<int, str>");
+
+        let t = r"
+        fn first(x : Option<int>) -> Option<bool> {
+            Some(x? > 0)
+        }
+        first(None)
+        ";
+        expect_pass_str(t, "Option<bool>");
+
+        expect_err("Some(2)?", "'?' can only be used inside a function", true);
+        expect_err(
+            "fn f() -> int { Some(2)? }",
+            "'?' on type 'Option<int>' can't be used in a function returning 'int'",
+            true,
+        );
+        expect_err(
+            "fn f(x : Result<int, int>) -> Result<int, str> { Ok(x?) }",
+            "'?' can't return error type 'int' from a function returning errors of type 'str'",
+            true,
+        );
+        expect_err(
+            "fn f(x : int) -> Option<int> { Some(x?) }",
+            "Can't apply '?' to type 'int', expected an Option or Result",
+            true,
+        );
+    }
+}
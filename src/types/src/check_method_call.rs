@@ -0,0 +1,86 @@
+use parser::structs::{FnCallData, MethodCallData, Type};
+
+use crate::{
+    check_fn_call::{IS_FINISHED, THREAD_ID},
+    type_checker::{CheckResult, TypeChecker, TypeErrors},
+};
+
+impl<'prog> TypeChecker<'prog> {
+    /// Method calls are resolved on the type of the receiver and checked as a call to the corresponding
+    /// builtin with the receiver as the first argument.
+    pub(crate) fn check_method_call(
+        &mut self,
+        method_call: &MethodCallData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let recv_res = self.check_expr(&method_call.recv)?;
+
+        if let Type::Named(name) = &recv_res.ty {
+            return self.check_struct_method_call(name, recv_res.clone(), method_call);
+        }
+
+        let builtin = match (&recv_res.ty, method_call.method.as_str()) {
+            (Type::ThreadId(_), "id") => THREAD_ID,
+            (Type::ThreadId(_), "is_finished") => IS_FINISHED,
+            (ty, method) => {
+                let e = format!("No method '{}' found for type '{}'", method, ty);
+                return Err(TypeErrors::new_err(&e));
+            }
+        };
+
+        let mut args = vec![*method_call.recv.clone()];
+        args.extend(method_call.args.iter().cloned());
+
+        let fn_call = FnCallData {
+            name: builtin.to_string(),
+            args,
+            names: vec![],
+        };
+
+        self.check_fn_call(&fn_call)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_thread_handle_methods() {
+        let t = r"
+        fn f() {}
+        let h = spawn f();
+        h.id()
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        fn f() {}
+        let h = spawn f();
+        let done : bool = h.is_finished();
+        done
+        ";
+        expect_pass(t, Type::Bool);
+
+        let t = r"
+        fn f() {}
+        let h = spawn f();
+        h.id(2)
+        ";
+        expect_err(t, "takes 1 arguments but 2 were supplied", true);
+
+        let t = r"
+        let x = 2;
+        x.is_finished()
+        ";
+        expect_err(t, "No method 'is_finished' found for type 'int'", true);
+
+        let t = r"
+        fn f() {}
+        let h = spawn f();
+        h.foo()
+        ";
+        expect_err(t, "No method 'foo' found for type 'tid<()>'", true);
+    }
+}
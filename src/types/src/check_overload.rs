@@ -0,0 +1,165 @@
+use parser::structs::{BinOpType, Type};
+
+use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+
+// The methods of structs that + and == call
+const ADD_METHOD: &str = "add";
+const EQ_METHOD: &str = "eq";
+
+impl<'prog> TypeChecker<'prog> {
+    /// The method of a struct that overloads the operator, if it can be overloaded.
+    pub(crate) fn overload_method(op: &BinOpType) -> Option<&'static str> {
+        match op {
+            BinOpType::Add => Some(ADD_METHOD),
+            BinOpType::LogicalEq => Some(EQ_METHOD),
+            _ => None,
+        }
+    }
+
+    /// Check the operator as a call to the method of the struct on the left that overloads it, e.g `a + b` as `a.add(b)`.
+    /// None if the left is not a struct with such a method, so the operator is checked as usual.
+    pub(crate) fn check_overloaded_binop(
+        &self,
+        op: &BinOpType,
+        l_type: &CheckResult,
+        r_type: &CheckResult,
+    ) -> Option<Result<CheckResult, TypeErrors>> {
+        let Type::Named(name) = &l_type.ty else {
+            return None;
+        };
+        let method_name = TypeChecker::overload_method(op)?;
+        let def = self.get_struct(name).ok()?;
+        let method = def.method(method_name)?;
+
+        let [_, param] = method.params.as_slice() else {
+            let e = format!(
+                "Method '{}' of {} must take self and one other parameter to overload '{}'",
+                method_name, name, op
+            );
+            return Some(Err(TypeErrors::new_err(&e)));
+        };
+
+        if matches!(op, BinOpType::LogicalEq) && !method.ret_type.eq(&Type::Bool) {
+            let e = format!(
+                "Method '{}' of {} must return bool to overload '{}'",
+                method_name, name, op
+            );
+            return Some(Err(TypeErrors::new_err(&e)));
+        }
+
+        if !param.matches(&r_type.ty) {
+            let e = format!(
+                "{}, '{}' of {} takes '{}'",
+                TypeChecker::binop_err(op, &l_type.ty, &r_type.ty),
+                method_name,
+                name,
+                param
+            );
+            return Some(Err(TypeErrors::new_err(&e)));
+        }
+
+        let mut res = CheckResult::combine(l_type, r_type);
+        res.ty = method.ret_type.clone();
+        Some(Ok(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_overloads() {
+        let t = r"
+        struct V { x: int }
+        impl V {
+            fn add(self, other: V) -> V {
+                V { x: self.x + other.x }
+            }
+        }
+        let v = V { x: 1 } + V { x: 2 };
+        v.x
+        ";
+        expect_pass(t, Type::Int);
+
+        // the right can be of another type, and the result is the return type of the method
+        let t = r"
+        struct V { x: int }
+        impl V {
+            fn add(self, n: int) -> int {
+                self.x + n
+            }
+            fn eq(self, other: V) -> bool {
+                true
+            }
+        }
+        let v = V { x: 1 };
+        if v == v { v + 2 } else { 0 }
+        ";
+        expect_pass(t, Type::Int);
+
+        // without an eq method, == is structural
+        let t = r"
+        struct V { x: int }
+        V { x: 1 } == V { x: 1 }
+        ";
+        expect_pass(t, Type::Bool);
+
+        let t = r"
+        struct V { x: int }
+        V { x: 1 } + V { x: 1 }
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Can't apply '+' to types 'V' and 'V'",
+            false,
+        );
+
+        let t = r"
+        struct V { x: int }
+        impl V {
+            fn add(self, other: V) -> V {
+                other
+            }
+        }
+        V { x: 1 } + 1
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Can't apply '+' to types 'V' and 'int', 'add' of V takes 'V'",
+            false,
+        );
+
+        let t = r"
+        struct V { x: int }
+        impl V {
+            fn eq(self, other: V) -> int {
+                1
+            }
+        }
+        V { x: 1 } == V { x: 1 }
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Method 'eq' of V must return bool to overload '=='",
+            false,
+        );
+
+        let t = r"
+        struct V { x: int }
+        impl V {
+            fn add(self) -> V {
+                self
+            }
+        }
+        V { x: 1 } + V { x: 1 }
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Method 'add' of V must take self and one other parameter to overload '+'",
+            false,
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+use parser::structs::{FnParam, SelectData, SelectOp, Type};
+
+impl<'prog> TypeChecker<'prog> {
+    /*
+    0. Check every arm waits on a semaphore, or receives from or sends a value of the right type to a channel
+    1. Check every arm block, with the result of a recv or send bound, and collect errors
+    2. No errs: arms that don't terminate must all have the same type
+    3. select only terminates if every arm terminates
+    */
+    pub(crate) fn check_select(&mut self, select: &SelectData) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+        let mut arm_tys: Vec<CheckResult> = vec![];
+
+        for arm in select.arms.iter() {
+            let res_ty = match self.check_select_op(&arm.op) {
+                Ok(ty) => ty,
+                Err(mut errs) => {
+                    ty_errs.append(&mut errs);
+                    Type::Unknown
+                }
+            };
+
+            let params: Vec<FnParam> = arm
+                .bind
+                .iter()
+                .map(|name| FnParam {
+                    name: name.clone(),
+                    type_ann: Some(res_ty.clone()),
+                })
+                .collect();
+
+            match self.check_block(&arm.blk, params) {
+                Ok(res) => arm_tys.push(res),
+                Err(mut errs) => ty_errs.append(&mut errs),
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        // arms that must break / return don't contribute to the overall type
+        let mut overall_ty: Option<Type> = None;
+        for arm_ty in arm_tys.iter() {
+            if arm_ty.must_break || arm_ty.must_return {
+                continue;
+            }
+
+            match &overall_ty {
+                None => overall_ty = Some(arm_ty.ty.clone()),
+                Some(ty) if !ty.matches(&arm_ty.ty) => {
+                    let e = format!(
+                        "select arms have type mismatch - expected: {}, got: {}",
+                        ty, arm_ty.ty
+                    );
+                    ty_errs.add(&e);
+                    return Err(ty_errs);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(CheckResult {
+            ty: overall_ty.unwrap_or(Type::Unit),
+            must_break: arm_tys.iter().all(|x| x.must_break),
+            must_return: arm_tys.iter().all(|x| x.must_return),
+        })
+    }
+
+    /// Check what the arm waits on, returning the type of its result: Option<T> for a recv on Channel<T>,
+    /// bool for a send and unit for a semaphore.
+    fn check_select_op(&mut self, op: &SelectOp) -> Result<Type, TypeErrors> {
+        match op {
+            SelectOp::Wait(sem) => {
+                let ty = self.get_type(sem)?;
+                if !ty.eq(&Type::Semaphore) {
+                    let e = format!(
+                        "Expected type '{}' for select arm '{}', got '{}'",
+                        Type::Semaphore,
+                        sem,
+                        ty
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+                Ok(Type::Unit)
+            }
+            SelectOp::Recv(ch) => Ok(Type::Option(Box::new(self.select_chan_elem(op, ch)?))),
+            SelectOp::Send(ch, val) => {
+                let elem = self.select_chan_elem(op, ch)?;
+                let val_ty = self.check_expr(val)?.ty;
+                if !elem.matches(&val_ty) {
+                    let e = format!(
+                        "Expected type '{}' for the value sent in select arm '{}', got '{}'",
+                        elem, op, val_ty
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+                Ok(Type::Bool)
+            }
+        }
+    }
+
+    /// The type of the values of the channel of a recv or send arm.
+    fn select_chan_elem(&self, op: &SelectOp, ch: &str) -> Result<Type, TypeErrors> {
+        match self.get_type(ch)? {
+            Type::Channel(elem) => Ok(*elem),
+            ty => {
+                let e = format!("Expected Channel for select arm '{}', got '{}'", op, ty);
+                Err(TypeErrors::new_err(&e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_select_basic() {
+        let t = r"
+        let a = sem_create();
+        let b = sem_create();
+        select {
+            a => { 2; }
+            b => { 3; }
+        }
+        ";
+        expect_pass(t, Type::Unit);
+
+        let t = r"
+        let a = sem_create();
+        let b = sem_create();
+        let x : int = select {
+            a => { 2 }
+            b => { 3 }
+        };
+        x
+        ";
+        expect_pass(t, Type::Int);
+    }
+
+    #[test]
+    fn test_type_check_select_chan() {
+        let t = r"
+        let ch : Channel<int> = channel(1);
+        let out : Channel<bool> = channel(1);
+        let a = sem_create();
+        select {
+            recv(ch) -> x => { match x { Some(v) => { v } None => { 0 } } }
+            send(out, true) -> sent => { if sent { 1 } else { 0 } }
+            a => { 2 }
+        }
+        ";
+        expect_pass(t, Type::Int);
+
+        // the type of the values of an unannotated channel is inferred from the arms
+        let t = r"
+        let ch = channel(1);
+        select {
+            send(ch, 1) => { }
+        }
+        let x : Option<int> = select {
+            recv(ch) -> x => { x }
+        };
+        x
+        ";
+        expect_pass(t, Type::Option(Box::new(Type::Int)));
+    }
+
+    #[test]
+    fn test_type_check_select_errs() {
+        let t = r"
+        let a = 2;
+        select {
+            a => { 2; }
+        }
+        ";
+        expect_err(t, "Expected type 'sem' for select arm 'a', got 'int'", true);
+
+        let t = r"
+        let a = sem_create();
+        let b = sem_create();
+        select {
+            a => { 2 }
+            b => { true }
+        }
+        ";
+        expect_err(
+            t,
+            "select arms have type mismatch - expected: int, got: bool",
+            true,
+        );
+
+        // errors from arms are collected
+        let t = r"
+        let a = sem_create();
+        select {
+            a => { 2 + true; }
+            c => { 3; }
+        }
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Identifier 'c' not declared",
+            false,
+        );
+
+        let t = r"
+        let a = sem_create();
+        select {
+            recv(a) -> x => { }
+        }
+        ";
+        expect_err(
+            t,
+            "Expected Channel for select arm 'recv(a)', got 'sem'",
+            true,
+        );
+
+        let t = r"
+        let ch : Channel<int> = channel(1);
+        select {
+            send(ch, true) => { }
+        }
+        ";
+        expect_err(
+            t,
+            "Expected type 'int' for the value sent in select arm 'send(ch, true)', got 'bool'",
+            true,
+        );
+
+        let t = r"
+        let ch : Channel<int> = channel(1);
+        select {
+            recv(ch) -> x => { x + 1 }
+        }
+        ";
+        expect_err(t, "Can't apply '+' to types 'Option<int>' and 'int'", true);
+
+        let t = r"
+        let ch = channel(1);
+        select {
+            send(ch, 1) => { }
+            recv(ch) -> x => { let y : Option<str> = x; }
+        }
+        ";
+        expect_err(
+            t,
+            "'y' has declared type Option<str> but inferred type Option<int>",
+            true,
+        );
+    }
+}
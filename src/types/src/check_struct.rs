@@ -0,0 +1,485 @@
+use parser::structs::{
+    BlockSeq, Decl, Expr, FieldAssignData, FnDeclData, FnTypeData, ImplData, MethodCallData,
+    StructDeclData, StructExprData, StructTypeData, Type,
+};
+
+use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+
+impl<'prog> TypeChecker<'prog> {
+    /// The fields and methods of the struct with the name in scope
+    pub(crate) fn get_struct(&self, name: &str) -> Result<StructTypeData, TypeErrors> {
+        match self.get_type(name) {
+            Ok(Type::StructDef(def)) => Ok(*def),
+            _ => {
+                let e = format!("'{}' is not a struct", name);
+                Err(TypeErrors::new_err(&e))
+            }
+        }
+    }
+
+    /// Set the type of the identifier in the innermost env it is declared in
+    fn set_type(&mut self, ident: &str, ty: Type) {
+        if let Some(env) = self
+            .envs
+            .iter_mut()
+            .rev()
+            .find(|env| env.contains_key(ident))
+        {
+            env.insert(ident.to_string(), ty);
+        }
+    }
+
+    /// Error if the type refers to a struct or enum that is not declared
+    pub(crate) fn check_type_known(&self, ty: &Type) -> Result<(), TypeErrors> {
+        match ty {
+            Type::Named(name) => {
+                if let Ok(Type::StructDef(_) | Type::EnumDef(_)) = self.get_type(name) {
+                    Ok(())
+                } else {
+                    let e = format!("Unknown type '{}'", name);
+                    Err(TypeErrors::new_err(&e))
+                }
+            }
+            Type::Option(ty)
+            | Type::Array(ty)
+            | Type::Generator(ty)
+            | Type::Channel(ty)
+            | Type::BoundedQueue(ty) => self.check_type_known(ty),
+            Type::Result(ok, err) => {
+                self.check_type_known(ok)?;
+                self.check_type_known(err)
+            }
+            Type::UserFn(fn_ty) => {
+                for param in fn_ty.params.iter() {
+                    self.check_type_known(param)?;
+                }
+                self.check_type_known(&fn_ty.ret_type)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Structs and their methods can be used anywhere in the block, like functions.
+    /// Bind each struct of the block to its fields, then add the methods of the impl blocks to them.
+    pub(crate) fn declare_structs(&mut self, program: &BlockSeq) -> Result<(), TypeErrors> {
+        for decl in program.decls.iter() {
+            if let Decl::StructDeclStmt(struct_decl) = decl {
+                let def = StructTypeData {
+                    name: struct_decl.name.clone(),
+                    fields: struct_decl.fields.clone(),
+                    methods: vec![],
+                };
+                self.assign_ident(&struct_decl.name, Type::StructDef(Box::new(def)))?;
+            }
+        }
+
+        for decl in program.decls.iter() {
+            if let Decl::ImplStmt(impl_data) = decl {
+                let mut def = self.get_struct(&impl_data.name)?;
+
+                for method in impl_data.methods.iter() {
+                    if def.method(&method.name).is_some() {
+                        let e = format!(
+                            "Method '{}' declared more than once for {}",
+                            method.name, def.name
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+
+                    // Missing annotations are reported when the method itself is checked
+                    let params: Option<Vec<Type>> = method
+                        .params
+                        .iter()
+                        .map(|param| param.type_ann.clone())
+                        .collect();
+
+                    if let Some(params) = params {
+                        let fn_ty = FnTypeData {
+                            params,
+                            ret_type: method.ret_type.clone(),
+                        };
+                        def.methods.push((method.name.clone(), fn_ty));
+                    }
+                }
+
+                self.set_type(&impl_data.name, Type::StructDef(Box::new(def)));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_struct_decl(
+        &mut self,
+        struct_decl: &StructDeclData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+
+        for (_, ty) in struct_decl.fields.iter() {
+            if let Err(mut errs) = self.check_type_known(ty) {
+                ty_errs.append(&mut errs);
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        Ok(CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        })
+    }
+
+    pub(crate) fn check_impl(&mut self, impl_data: &ImplData) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+
+        for method in impl_data.methods.iter() {
+            if let Err(mut errs) = self.check_method_decl(method) {
+                ty_errs.append(&mut errs);
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        Ok(CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        })
+    }
+
+    /// Methods are checked like functions, but are not bound to their name
+    fn check_method_decl(&mut self, method: &FnDeclData) -> Result<CheckResult, TypeErrors> {
+        self.check_fn_decl_with_stacks(method, false)
+    }
+
+    pub(crate) fn check_struct_expr(
+        &mut self,
+        struct_expr: &StructExprData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let def = self.get_struct(&struct_expr.name)?;
+        let mut ty_errs = TypeErrors::new();
+
+        let ty = Type::Named(def.name.clone());
+        let mut check_res = CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        };
+
+        for (field, expr) in struct_expr.fields.iter() {
+            let expr_res = match self.check_expr(expr) {
+                Ok(res) => res,
+                Err(mut errs) => {
+                    ty_errs.append(&mut errs);
+                    continue;
+                }
+            };
+            check_res = CheckResult::combine(&check_res, &expr_res);
+
+            match def.field(field) {
+                Some(ty) if ty.matches(&expr_res.ty) => (),
+                Some(ty) => {
+                    let e = format!(
+                        "Field '{}' of {} has type {} but was given type {}",
+                        field, def.name, ty, expr_res.ty
+                    );
+                    ty_errs.add(&e);
+                }
+                None => {
+                    let e = format!("Struct {} has no field '{}'", def.name, field);
+                    ty_errs.add(&e);
+                }
+            }
+        }
+
+        match &struct_expr.base {
+            Some(base) => {
+                let base_res = self.check_expr(base)?;
+                if !ty.eq(&base_res.ty) {
+                    let e = format!(
+                        "Expected base of type {} for the other fields but found type {}",
+                        def.name, base_res.ty
+                    );
+                    ty_errs.add(&e);
+                }
+                check_res = CheckResult::combine(&check_res, &base_res);
+            }
+            None => {
+                for (field, _) in def.fields.iter() {
+                    if !struct_expr.fields.iter().any(|(given, _)| given == field) {
+                        let e = format!("Missing field '{}' for {}", field, def.name);
+                        ty_errs.add(&e);
+                    }
+                }
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        check_res.ty = ty;
+        Ok(check_res)
+    }
+
+    /// The type of the field of a value of the type
+    fn field_type(&self, ty: &Type, field: &str) -> Result<Type, TypeErrors> {
+        let Type::Named(name) = ty else {
+            let e = format!("No field '{}' on type '{}'", field, ty);
+            return Err(TypeErrors::new_err(&e));
+        };
+
+        let def = self.get_struct(name)?;
+        match def.field(field) {
+            Some(ty) => Ok(ty.clone()),
+            None => {
+                let e = format!("Struct {} has no field '{}'", name, field);
+                Err(TypeErrors::new_err(&e))
+            }
+        }
+    }
+
+    pub(crate) fn check_field_expr(
+        &mut self,
+        expr: &Expr,
+        field: &str,
+    ) -> Result<CheckResult, TypeErrors> {
+        let expr_res = self.check_expr(expr)?;
+        let ty = self.field_type(&expr_res.ty, field)?;
+
+        Ok(CheckResult { ty, ..expr_res })
+    }
+
+    pub(crate) fn check_field_assign(
+        &mut self,
+        stmt: &FieldAssignData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut field_ty = self.get_type_if_init(&stmt.ident)?;
+        for field in stmt.fields.iter() {
+            field_ty = self.field_type(&field_ty, field)?;
+        }
+
+        let expr_res = self.check_expr(&stmt.expr)?;
+        if !field_ty.matches(&expr_res.ty) {
+            let e = format!(
+                "'{}.{}' has type {} but assigned type {}",
+                stmt.ident,
+                stmt.fields.join("."),
+                field_ty,
+                expr_res.ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(CheckResult {
+            ty: Type::Unit,
+            ..expr_res
+        })
+    }
+
+    /// Check a call to a method of a struct, with the receiver as self
+    pub(crate) fn check_struct_method_call(
+        &mut self,
+        name: &str,
+        recv_res: CheckResult,
+        method_call: &MethodCallData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let def = self.get_struct(name)?;
+        let Some(method) = def.method(&method_call.method) else {
+            let e = format!(
+                "No method '{}' found for type '{}'",
+                method_call.method, name
+            );
+            return Err(TypeErrors::new_err(&e));
+        };
+
+        let mut check_res = recv_res;
+        let mut arg_types: Vec<Type> = vec![];
+        for arg in method_call.args.iter() {
+            let arg_res = self.check_expr(arg)?;
+            check_res = CheckResult::combine(&check_res, &arg_res);
+            arg_types.push(arg_res.ty);
+        }
+
+        // self is the receiver
+        TypeChecker::check_arg_params_match(&method_call.method, &arg_types, &method.params[1..])?;
+        check_res.ty = method.ret_type.clone();
+
+        Ok(check_res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_structs() {
+        let t = r"
+        struct Point { x: int, y: int }
+        let p = Point { x: 1, y: 2 };
+        p.x + p.y
+        ";
+        expect_pass(t, Type::Int);
+
+        // used before declared, and nested
+        let t = r"
+        let l = Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } };
+        struct Line { from: Point, to: Point }
+        struct Point { x: int, y: int }
+        l.to
+        ";
+        expect_pass(t, Type::Named("Point".to_string()));
+
+        let t = r"
+        struct Point { x: int, y: int }
+        let p = Point { x: 1, y: 2 };
+        let q : Point = Point { y: 5, ..p };
+        q.y = q.x;
+        q
+        ";
+        expect_pass(t, Type::Named("Point".to_string()));
+
+        let t = r"
+        struct Point { x: int, y: int }
+        struct Line { from: Point, to: Point }
+        fn origin(l: Line) -> bool {
+            l.from.x == 0 && l.from.y == 0
+        }
+        let p = Point { x: 0, y: 0 };
+        let l = Line { from: p, to: p };
+        l.from.x = 2;
+        origin(l)
+        ";
+        expect_pass(t, Type::Bool);
+    }
+
+    #[test]
+    fn test_type_check_structs_fails() {
+        let t = "struct Point { x: int, y: int } Point { x: 1 }";
+        expect_err(t, "Missing field 'y' for Point", true);
+
+        let t = "struct Point { x: int } Point { x: 1, z: 2 }";
+        expect_err(t, "Struct Point has no field 'z'", true);
+
+        let t = "struct Point { x: int } Point { x: true }";
+        expect_err(
+            t,
+            "Field 'x' of Point has type int but was given type bool",
+            true,
+        );
+
+        let t = "struct Point { x: int } struct Pair { x: int } Point { ..Pair { x: 1 } }";
+        expect_err(
+            t,
+            "Expected base of type Point for the other fields but found type Pair",
+            true,
+        );
+
+        let t = "Point { x: 1 }";
+        expect_err(t, "'Point' is not a struct", true);
+
+        let t = "struct Point { x: int } let p = Point { x: 1 }; p.y";
+        expect_err(t, "Struct Point has no field 'y'", true);
+
+        let t = "let x = 2; x.y";
+        expect_err(t, "No field 'y' on type 'int'", true);
+
+        let t = "struct Point { x: int } let p = Point { x: 1 }; p.x = 2.0;";
+        expect_err(t, "'p.x' has type int but assigned type float", true);
+
+        let t = "struct Line { from: Pointt }";
+        expect_err(t, "Unknown type 'Pointt'", true);
+
+        let t = "fn f(p: Pointt) {}";
+        expect_err(t, "Unknown type 'Pointt'", true);
+
+        let t = "struct Point { x: int } let p = Point;";
+        expect_err(t, "Struct 'Point' can't be used as a value", true);
+    }
+
+    #[test]
+    fn test_type_check_methods() {
+        let t = r"
+        struct Point { x: int, y: int }
+        impl Point {
+            fn norm(self) -> int {
+                self.x * self.x + self.y * self.y
+            }
+
+            fn add(self, other: Point) -> Point {
+                Point { x: self.x + other.x, y: self.y + other.y }
+            }
+        }
+        let p = Point { x: 1, y: 2 };
+        p.add(p).norm()
+        ";
+        expect_pass(t, Type::Int);
+
+        // methods can call each other, in impl blocks before the struct
+        let t = r"
+        impl Counter {
+            fn twice(self) -> Counter {
+                self.incr().incr()
+            }
+        }
+        impl Counter {
+            fn incr(self) -> Counter {
+                Counter { n: self.n + 1 }
+            }
+        }
+        struct Counter { n: int }
+        Counter { n: 0 }.twice().n
+        ";
+        expect_pass(t, Type::Int);
+
+        // params of methods are inferred
+        let t = r"
+        struct Point { x: int }
+        impl Point {
+            fn shift(self, d) -> Point {
+                Point { x: self.x + d }
+            }
+        }
+        Point { x: 1 }.shift(2).x
+        ";
+        expect_pass(t, Type::Int);
+    }
+
+    #[test]
+    fn test_type_check_methods_fails() {
+        let t = "struct Point { x: int } impl Point { fn f(self) -> int { true } }";
+        expect_err(
+            t,
+            "Function 'f' has return type 'int' but found block type 'bool'",
+            true,
+        );
+
+        let t = "struct Point { x: int } Point { x: 1 }.f()";
+        expect_err(t, "No method 'f' found for type 'Point'", true);
+
+        let t = r"
+        struct Point { x: int }
+        impl Point { fn f(self, y: int) {} }
+        Point { x: 1 }.f(true)
+        ";
+        expect_err(
+            t,
+            "Mismatched types in function call: got ((bool)) but expected ((int))",
+            true,
+        );
+
+        let t = "impl Point { fn f(self) {} }";
+        expect_err(t, "'Point' is not a struct", true);
+
+        let t = "struct P {} impl P { fn f(self) {} } impl P { fn f(self) {} }";
+        expect_err(t, "Method 'f' declared more than once for P", true);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+use parser::structs::{FnParam, TryCatchData, Type};
+
+impl<'prog> TypeChecker<'prog> {
+    /*
+    0. Check the try block, and the catch block with the error message bound as a str, and collect errors
+    1. No errs: blocks that don't terminate must have the same type, as for if/else
+    2. The try block can fail at any point, so the whole only breaks / returns if both blocks do
+    */
+    pub(crate) fn check_try_catch(
+        &mut self,
+        try_catch: &TryCatchData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+
+        let try_res = self.check_block(&try_catch.try_blk, vec![]);
+
+        let err_param = FnParam {
+            name: try_catch.err.to_string(),
+            type_ann: Some(Type::String),
+        };
+        let catch_res = self.check_block(&try_catch.catch_blk, vec![err_param]);
+
+        let (try_res, catch_res) = match (try_res, catch_res) {
+            (Ok(try_res), Ok(catch_res)) => (try_res, catch_res),
+            (try_res, catch_res) => {
+                for mut errs in [try_res.err(), catch_res.err()].into_iter().flatten() {
+                    ty_errs.append(&mut errs);
+                }
+                return Err(ty_errs);
+            }
+        };
+
+        let must_break = try_res.must_break && catch_res.must_break;
+        let must_return = try_res.must_return && catch_res.must_return;
+
+        let ty = if try_res.must_break || try_res.must_return {
+            catch_res.ty
+        } else if catch_res.must_break || catch_res.must_return {
+            try_res.ty
+        } else {
+            let Some(ty) = try_res.ty.unify(&catch_res.ty) else {
+                let e = format!(
+                    "try and catch blocks have type mismatch - expected: {}, got: {}",
+                    try_res.ty, catch_res.ty
+                );
+                return Err(TypeErrors::new_err(&e));
+            };
+            ty
+        };
+
+        Ok(CheckResult {
+            ty,
+            must_break,
+            must_return,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_try_catch() {
+        let t = r#"
+        let x = try {
+            panic("boom");
+            1
+        } catch e {
+            println(e);
+            2
+        };
+        x + 1
+        "#;
+        expect_pass(t, Type::Int);
+
+        // the error is bound as a string
+        let t = r"
+        try { 2; } catch e { e; }
+        try { itoa(2) } catch e { e }
+        ";
+        expect_pass(t, Type::String);
+
+        // blocks that return don't contribute to the type, but the whole only returns if both do
+        let t = r"
+        fn f() -> int {
+            let x = try { return 2; } catch e { 3 };
+            x
+        }
+        f()
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        fn f() -> int {
+            try { return 2; } catch e { return 3; }
+        }
+        f()
+        ";
+        expect_pass(t, Type::Int);
+
+        // panic never returns, so it stands in for a value of any type
+        let t = r#"
+        fn f(x : int) -> int {
+            if x > 0 {
+                x
+            } else {
+                panic("negative")
+            }
+        }
+        f(2)
+        "#;
+        expect_pass(t, Type::Int);
+
+        expect_err(
+            "try { 2 } catch e { true }",
+            "try and catch blocks have type mismatch - expected: int, got: bool",
+            true,
+        );
+        expect_err(
+            "try { 2 } catch e { e + 1 }",
+            "Can't apply '+' to types 'str' and 'int'",
+            true,
+        );
+        expect_err(
+            "try { x } catch e { y }",
+            "[TypeError]: Identifier 'x' not declared\n[TypeError]: Identifier 'y' not declared",
+            false,
+        );
+        expect_err(
+            "panic(2)",
+            "Mismatched types in function call: got ((int)) but expected ((str))",
+            true,
+        );
+    }
+}
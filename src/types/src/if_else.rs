@@ -70,9 +70,9 @@ impl<'prog> TypeChecker<'prog> {
             let overall_ty = match (if_terms, else_terms) {
                 // no terminate: return out
                 (false, false) => {
-                    if if_ty.ty.eq(&else_ty.ty) {
+                    if let Some(ty) = if_ty.ty.unify(&else_ty.ty) {
                         if ty_errs.is_ok() {
-                            return Ok(if_ty);
+                            return Ok(CheckResult { ty, ..if_ty });
                         } else {
                             return Err(ty_errs);
                         }
@@ -184,6 +184,23 @@ mod tests {
         ";
         expect_err(t, "Expected type 'bool' for if condition, got 'int'", true);
 
+        // no truthiness for ints, strings or floats
+        expect_err(
+            "if 1 { }",
+            "Expected type 'bool' for if condition, got 'int'",
+            true,
+        );
+        expect_err(
+            r#"if "" { 2 } else { 3 }"#,
+            "Expected type 'bool' for if condition, got 'str'",
+            true,
+        );
+        expect_err(
+            "if 0.0 { }",
+            "Expected type 'bool' for if condition, got 'float'",
+            true,
+        );
+
         // cond has err when if-else types match
         let t = r"let x = 2; let y = 3; if !x == y { 20 } else { 30 }";
         expect_err(t, "Can't apply logical NOT to type int", true);
@@ -211,7 +228,7 @@ mod tests {
             2.56+2;
         }
         ";
-        expect_err(t,  "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: 'x' has declared type bool but assigned type float\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'float' and 'int'", false);
+        expect_err(t,  "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: 'x' has declared type bool but assigned type float\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'float' and 'int', convert one side with int_to_float or float_to_int", false);
 
         // cond + else err
         let t = r"
@@ -231,7 +248,7 @@ mod tests {
              300;
          }
          ";
-        expect_err(t, "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: Can't apply '+' to types 'int' and 'float'", false);
+        expect_err(t, "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: Can't apply '+' to types 'int' and 'float', convert one side with int_to_float or float_to_int", false);
     }
 
     #[test]
@@ -244,7 +261,7 @@ mod tests {
             300+false;
          }
          ";
-        expect_err(t,  "[TypeError]: Can't apply '+' to types 'int' and 'float'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
+        expect_err(t,  "[TypeError]: Can't apply '+' to types 'int' and 'float', convert one side with int_to_float or float_to_int\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
 
         // if only
         let t = r"
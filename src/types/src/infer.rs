@@ -0,0 +1,2068 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
+
+use parser::structs::{
+    BinOpType, BlockSeq, BreakData, ComprehensionData, Decl, DestructurePattern, Expr, FnCallData,
+    FnDeclData, FnTypeData, IfElseData, Iterable, LetStmtData, LoopData, MatchData, Pattern,
+    SelectData, SelectOp, StructExprData, StructTypeData, TryCatchData, Type, UnOpType,
+};
+
+use crate::{
+    check_fn_call::{any_thread, IS_FINISHED, NONE, THREAD_ID},
+    type_checker::{Env, TypeChecker, TypeErrors},
+};
+
+/// A type during inference: a concrete type, a function, option, result, array, generator or thread type whose parts may not be known yet,
+/// or a variable standing for a type that is not known yet.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Var(usize),
+    Con(Type),
+    Fn(Vec<Ty>, Box<Ty>),
+    Option(Box<Ty>),
+    Result(Box<Ty>, Box<Ty>),
+    Array(Box<Ty>),
+    Generator(Box<Ty>),
+    Thread(Box<Ty>),
+    Channel(Box<Ty>),
+    BoundedQueue(Box<Ty>),
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::Var(_) => write!(f, "_"),
+            Ty::Con(ty) => write!(f, "{}", ty),
+            Ty::Fn(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match **ret {
+                    Ty::Con(Type::Unit) => write!(f, "fn({})", params),
+                    _ => write!(f, "fn({}) -> {}", params, ret),
+                }
+            }
+            Ty::Option(ty) => write!(f, "Option<{}>", ty),
+            Ty::Result(ok, err) => write!(f, "Result<{}, {}>", ok, err),
+            Ty::Array(ty) => write!(f, "[{}]", ty),
+            Ty::Generator(ty) => write!(f, "Generator<{}>", ty),
+            Ty::Thread(ty) => write!(f, "tid<{}>", ty),
+            Ty::Channel(ty) => write!(f, "Channel<{}>", ty),
+            Ty::BoundedQueue(ty) => write!(f, "BoundedQueue<{}>", ty),
+        }
+    }
+}
+
+/// Hindley-Milner style inference of the types of function parameters that have no annotation,
+/// and of the return types of the functions with such parameters when they have none.
+///
+/// Every expression is given a type, with a fresh variable where it is not known yet, and the uses of the
+/// expression are unified with it: e.g `a + 1` makes `a` an int, and `if a {}` makes it a bool.
+///
+/// Functions are generalized once their body is inferred: the variables of their type that nothing outside
+/// the function constrains become generic, and each use of the function gets fresh variables for them.
+/// So `fn id(x) { x }` has type `fn('a) -> 'a` and can be called with an int and a bool. The checker has
+/// no overloading, so variables used with arithmetic, comparisons, fields or methods stay monomorphic.
+///
+/// Inference only fills in the annotations the checker needs, so its errors are only reported for programs
+/// with unannotated parameters, or channels and bounded queues bound without the type of their values.
+/// Programs that are fully annotated are left to the checker as before.
+struct Infer {
+    /// The type each variable is bound to, if any.
+    subst: Vec<Option<Ty>>,
+    scopes: Vec<HashMap<String, Ty>>,
+    /// The return types of the functions being inferred, innermost last.
+    ret_stack: Vec<Ty>,
+    /// The types of the elements of the generators being inferred, innermost last.
+    yield_stack: Vec<Ty>,
+    /// The labels of the loops being inferred and the types of the values breaks leave them with, innermost last.
+    loop_stack: Vec<(Option<String>, Ty)>,
+    /// The types of the parameters of every function, and its return type if it is inferred,
+    /// in the order the functions appear in the program.
+    fn_sigs: Vec<(Vec<Ty>, Option<Ty>)>,
+    /// The types of the variables bound without an annotation to a new channel or bounded queue, whose values
+    /// are typed by what is sent to or received from it, in the order the lets appear in the program.
+    let_tys: Vec<Option<Ty>>,
+    /// The names of the generalized variables, which are instantiated afresh at each use.
+    generics: HashMap<usize, String>,
+    /// The variables of the generic types of the programs checked before, by name.
+    generic_vars: HashMap<String, usize>,
+    /// The types used with overloaded operators, which can't be generic.
+    overloaded: Vec<Ty>,
+    /// Whether some parameter, channel or bounded queue has no annotation.
+    needed: bool,
+    errs: TypeErrors,
+}
+
+impl Infer {
+    fn new(envs: &[Env]) -> Infer {
+        let mut infer = Infer {
+            subst: vec![],
+            scopes: vec![],
+            ret_stack: vec![],
+            yield_stack: vec![],
+            loop_stack: vec![],
+            fn_sigs: vec![],
+            let_tys: vec![],
+            generics: HashMap::new(),
+            generic_vars: HashMap::new(),
+            overloaded: vec![],
+            needed: false,
+            errs: TypeErrors::new(),
+        };
+
+        // The bindings of the programs checked before, as for the inputs of a REPL
+        for env in envs.iter() {
+            let scope = env
+                .iter()
+                .map(|(name, ty)| (name.to_owned(), infer.ty_of(ty)))
+                .collect();
+            infer.scopes.push(scope);
+        }
+
+        infer
+    }
+
+    fn fresh(&mut self) -> Ty {
+        self.subst.push(None);
+        Ty::Var(self.subst.len() - 1)
+    }
+
+    fn ty_of(&mut self, ty: &Type) -> Ty {
+        match ty {
+            Type::UserFn(fn_ty) => {
+                let params = fn_ty.params.iter().map(|p| self.ty_of(p)).collect();
+                Ty::Fn(params, Box::new(self.ty_of(&fn_ty.ret_type)))
+            }
+            Type::Option(ty) => Ty::Option(Box::new(self.ty_of(ty))),
+            Type::Array(ty) => Ty::Array(Box::new(self.ty_of(ty))),
+            Type::Generator(ty) => Ty::Generator(Box::new(self.ty_of(ty))),
+            Type::ThreadId(ty) => Ty::Thread(Box::new(self.ty_of(ty))),
+            Type::Channel(ty) => Ty::Channel(Box::new(self.ty_of(ty))),
+            Type::BoundedQueue(ty) => Ty::BoundedQueue(Box::new(self.ty_of(ty))),
+            Type::Result(ok, err) => {
+                Ty::Result(Box::new(self.ty_of(ok)), Box::new(self.ty_of(err)))
+            }
+            Type::Generic(name) => match self.generic_vars.get(name) {
+                Some(v) => Ty::Var(*v),
+                None => {
+                    let v = self.subst.len();
+                    self.subst.push(None);
+                    self.generic_vars.insert(name.to_string(), v);
+                    self.generics.insert(v, name.to_string());
+                    Ty::Var(v)
+                }
+            },
+            // Not known until assigned, or checked separately
+            Type::BuiltInFn | Type::Unitialised | Type::Unknown => self.fresh(),
+            ty => Ty::Con(ty.clone()),
+        }
+    }
+
+    /// The type with every bound variable replaced by what it is bound to.
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Var(v) => match &self.subst[*v] {
+                Some(ty) => self.resolve(ty),
+                None => ty.clone(),
+            },
+            Ty::Con(_) => ty.clone(),
+            Ty::Fn(params, ret) => Ty::Fn(
+                params.iter().map(|p| self.resolve(p)).collect(),
+                Box::new(self.resolve(ret)),
+            ),
+            Ty::Option(ty) => Ty::Option(Box::new(self.resolve(ty))),
+            Ty::Array(ty) => Ty::Array(Box::new(self.resolve(ty))),
+            Ty::Generator(ty) => Ty::Generator(Box::new(self.resolve(ty))),
+            Ty::Thread(ty) => Ty::Thread(Box::new(self.resolve(ty))),
+            Ty::Channel(ty) => Ty::Channel(Box::new(self.resolve(ty))),
+            Ty::BoundedQueue(ty) => Ty::BoundedQueue(Box::new(self.resolve(ty))),
+            Ty::Result(ok, err) => {
+                Ty::Result(Box::new(self.resolve(ok)), Box::new(self.resolve(err)))
+            }
+        }
+    }
+
+    /// The concrete type, if the type has no unbound variables left other than generalized ones.
+    fn to_type(&self, ty: &Ty) -> Option<Type> {
+        match self.resolve(ty) {
+            Ty::Var(v) => self
+                .generics
+                .get(&v)
+                .map(|name| Type::Generic(name.clone())),
+            Ty::Con(ty) => Some(ty),
+            Ty::Option(ty) => Some(Type::Option(Box::new(self.to_type(&ty)?))),
+            Ty::Array(ty) => Some(Type::Array(Box::new(self.to_type(&ty)?))),
+            Ty::Generator(ty) => Some(Type::Generator(Box::new(self.to_type(&ty)?))),
+            Ty::Thread(ty) => Some(Type::ThreadId(Box::new(self.to_type(&ty)?))),
+            Ty::Channel(ty) => Some(Type::Channel(Box::new(self.to_type(&ty)?))),
+            Ty::BoundedQueue(ty) => Some(Type::BoundedQueue(Box::new(self.to_type(&ty)?))),
+            Ty::Result(ok, err) => Some(Type::Result(
+                Box::new(self.to_type(&ok)?),
+                Box::new(self.to_type(&err)?),
+            )),
+            Ty::Fn(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|p| self.to_type(p))
+                    .collect::<Option<Vec<_>>>()?;
+                let ret_type = self.to_type(&ret)?;
+                Some(Type::UserFn(Box::new(FnTypeData { params, ret_type })))
+            }
+        }
+    }
+
+    fn occurs(&self, v: usize, ty: &Ty) -> bool {
+        let mut vars = vec![];
+        self.free_vars(ty, &mut vars);
+        vars.contains(&v)
+    }
+
+    /// Add the unbound variables of the type that are not in vars yet, in the order they appear in.
+    fn free_vars(&self, ty: &Ty, vars: &mut Vec<usize>) {
+        match self.resolve(ty) {
+            Ty::Var(v) => {
+                if !vars.contains(&v) {
+                    vars.push(v);
+                }
+            }
+            Ty::Con(_) => (),
+            Ty::Fn(params, ret) => {
+                for param in params.iter() {
+                    self.free_vars(param, vars);
+                }
+                self.free_vars(&ret, vars);
+            }
+            Ty::Option(ty)
+            | Ty::Array(ty)
+            | Ty::Generator(ty)
+            | Ty::Thread(ty)
+            | Ty::Channel(ty)
+            | Ty::BoundedQueue(ty) => self.free_vars(&ty, vars),
+            Ty::Result(ok, err) => {
+                self.free_vars(&ok, vars);
+                self.free_vars(&err, vars);
+            }
+        }
+    }
+
+    /// Make the variables of the type of the function that nothing outside it constrains generic.
+    fn generalize(&mut self, name: &str, ty: &Ty) {
+        let mut env_vars = vec![];
+        for (i, scope) in self.scopes.iter().enumerate() {
+            for (sym, sym_ty) in scope.iter() {
+                // The function itself, bound in the innermost scope
+                if i == self.scopes.len() - 1 && sym == name {
+                    continue;
+                }
+                self.free_vars(sym_ty, &mut env_vars);
+            }
+        }
+        let stacks = self.ret_stack.iter().chain(self.yield_stack.iter());
+        for ty in stacks.chain(self.overloaded.iter()) {
+            self.free_vars(ty, &mut env_vars);
+        }
+
+        // Named in the order they appear in the type
+        let mut vars = vec![];
+        self.free_vars(ty, &mut vars);
+
+        for v in vars {
+            if env_vars.contains(&v) || self.generics.contains_key(&v) {
+                continue;
+            }
+            let name = self.generic_name();
+            self.generics.insert(v, name);
+        }
+    }
+
+    /// The first of 'a to 'z, then 'a1 to 'z1 and so on, that is not the name of a generic type yet.
+    /// Names are not reused, so the generic types of functions passed to each other can be told apart.
+    fn generic_name(&self) -> String {
+        let names: HashSet<&String> = self.generics.values().collect();
+        (0..)
+            .map(|idx: usize| {
+                let letter = (b'a' + (idx % 26) as u8) as char;
+                match idx / 26 {
+                    0 => format!("'{}", letter),
+                    n => format!("'{}{}", letter, n),
+                }
+            })
+            .find(|name| !names.contains(name))
+            .expect("There are infinitely many names")
+    }
+
+    /// The type with fresh variables for its generalized ones.
+    fn instantiate(&mut self, ty: &Ty) -> Ty {
+        let mut fresh = HashMap::new();
+        self.instantiate_with(&self.resolve(ty), &mut fresh)
+    }
+
+    fn instantiate_with(&mut self, ty: &Ty, fresh: &mut HashMap<usize, Ty>) -> Ty {
+        match ty {
+            Ty::Var(v) if self.generics.contains_key(v) => match fresh.get(v) {
+                Some(ty) => ty.clone(),
+                None => {
+                    let ty = self.fresh();
+                    fresh.insert(*v, ty.clone());
+                    ty
+                }
+            },
+            Ty::Var(_) | Ty::Con(_) => ty.clone(),
+            Ty::Fn(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|p| self.instantiate_with(p, fresh))
+                    .collect();
+                Ty::Fn(params, Box::new(self.instantiate_with(ret, fresh)))
+            }
+            Ty::Option(ty) => Ty::Option(Box::new(self.instantiate_with(ty, fresh))),
+            Ty::Array(ty) => Ty::Array(Box::new(self.instantiate_with(ty, fresh))),
+            Ty::Generator(ty) => Ty::Generator(Box::new(self.instantiate_with(ty, fresh))),
+            Ty::Thread(ty) => Ty::Thread(Box::new(self.instantiate_with(ty, fresh))),
+            Ty::Channel(ty) => Ty::Channel(Box::new(self.instantiate_with(ty, fresh))),
+            Ty::BoundedQueue(ty) => Ty::BoundedQueue(Box::new(self.instantiate_with(ty, fresh))),
+            Ty::Result(ok, err) => Ty::Result(
+                Box::new(self.instantiate_with(ok, fresh)),
+                Box::new(self.instantiate_with(err, fresh)),
+            ),
+        }
+    }
+
+    /// Make the two types equal by binding variables, returning false if they can't be.
+    fn unify(&mut self, a: &Ty, b: &Ty) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Ty::Var(v), Ty::Var(w)) if v == w => true,
+            (Ty::Var(v), ty) | (ty, Ty::Var(v)) => {
+                if self.occurs(v, &ty) {
+                    return false;
+                }
+                self.subst[v] = Some(ty);
+                true
+            }
+            (Ty::Con(a), Ty::Con(b)) => a.matches(&b),
+            (Ty::Fn(a_params, a_ret), Ty::Fn(b_params, b_ret)) => {
+                a_params.len() == b_params.len()
+                    && a_params
+                        .iter()
+                        .zip(b_params.iter())
+                        .all(|(a, b)| self.unify(a, b))
+                    && self.unify(&a_ret, &b_ret)
+            }
+            (Ty::Option(a), Ty::Option(b))
+            | (Ty::Array(a), Ty::Array(b))
+            | (Ty::Generator(a), Ty::Generator(b))
+            | (Ty::Thread(a), Ty::Thread(b))
+            | (Ty::Channel(a), Ty::Channel(b))
+            | (Ty::BoundedQueue(a), Ty::BoundedQueue(b)) => self.unify(&a, &b),
+            (Ty::Result(a_ok, a_err), Ty::Result(b_ok, b_err)) => {
+                self.unify(&a_ok, &b_ok) && self.unify(&a_err, &b_err)
+            }
+            _ => false,
+        }
+    }
+
+    /// Unify the found type with the expected one, adding the error made by err from the resolved types if they differ.
+    fn expect(&mut self, expected: &Ty, found: &Ty, err: impl FnOnce(&Ty, &Ty) -> String) {
+        if !self.unify(expected, found) {
+            let e = err(&self.resolve(expected), &self.resolve(found));
+            self.errs.add(&e);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn bind(&mut self, name: &str, ty: Ty) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+
+    /// The type of a symbol, instantiated if it is generic, or a fresh variable if it is not declared,
+    /// which the checker reports.
+    fn symbol(&mut self, name: &str) -> Ty {
+        match self.lookup(name) {
+            Some(ty) => self.instantiate(&ty),
+            None => self.fresh(),
+        }
+    }
+
+    /// Whether the block ends in a return or break, so it has no type of its own.
+    fn diverges(blk: &BlockSeq) -> bool {
+        blk.decls
+            .iter()
+            .any(|decl| matches!(decl, Decl::ReturnStmt(_) | Decl::BreakStmt(_)))
+    }
+
+    fn infer_block(&mut self, blk: &BlockSeq, params: Vec<(String, Ty)>) -> Ty {
+        self.scopes.push(params.into_iter().collect());
+
+        // Functions can be called before they are declared in the block
+        for decl in blk.decls.iter() {
+            if let Decl::FnDeclStmt(fn_decl) = decl {
+                let ty = self.fresh();
+                self.bind(&fn_decl.name, ty);
+            }
+        }
+
+        self.declare_structs(blk);
+        self.declare_enums(blk);
+
+        for decl in blk.decls.iter() {
+            self.infer_decl(decl);
+        }
+
+        let ty = match &blk.last_expr {
+            Some(expr) => self.infer_expr(expr),
+            None => Ty::Con(Type::Unit),
+        };
+
+        self.scopes.pop();
+        ty
+    }
+
+    /// Bind the structs of the block to their fields, with the methods whose parameters are all annotated.
+    /// Calls to the other methods are inferred to be of fresh types, and are left to the checker.
+    fn declare_structs(&mut self, blk: &BlockSeq) {
+        for decl in blk.decls.iter() {
+            if let Decl::StructDeclStmt(struct_decl) = decl {
+                let def = StructTypeData {
+                    name: struct_decl.name.clone(),
+                    fields: struct_decl.fields.clone(),
+                    methods: vec![],
+                };
+                self.bind(&struct_decl.name, Ty::Con(Type::StructDef(Box::new(def))));
+            }
+        }
+
+        for decl in blk.decls.iter() {
+            let Decl::ImplStmt(impl_data) = decl else {
+                continue;
+            };
+
+            let Some(scope) = self
+                .scopes
+                .iter_mut()
+                .rev()
+                .find(|scope| scope.contains_key(&impl_data.name))
+            else {
+                continue;
+            };
+
+            if let Some(Ty::Con(Type::StructDef(def))) = scope.get_mut(&impl_data.name) {
+                for method in impl_data.methods.iter() {
+                    let params: Option<Vec<Type>> = method
+                        .params
+                        .iter()
+                        .map(|param| param.type_ann.clone())
+                        .collect();
+
+                    if let Some(params) = params {
+                        let fn_ty = FnTypeData {
+                            params,
+                            ret_type: method.ret_type.clone(),
+                        };
+                        def.methods.push((method.name.clone(), fn_ty));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Bind the enums of the block to their variants, and each variant to its constructor or to a value of the enum.
+    fn declare_enums(&mut self, blk: &BlockSeq) {
+        for decl in blk.decls.iter() {
+            let Decl::EnumDeclStmt(enum_decl) = decl else {
+                continue;
+            };
+
+            self.bind(
+                &enum_decl.name,
+                Ty::Con(Type::EnumDef(Box::new(enum_decl.clone()))),
+            );
+
+            for (variant, held) in enum_decl.variants.iter() {
+                let ty = Ty::Con(Type::Named(enum_decl.name.clone()));
+                let ty = match held {
+                    Some(held) => Ty::Fn(vec![self.ty_of(held)], Box::new(ty)),
+                    None => ty,
+                };
+                self.bind(variant, ty);
+            }
+        }
+    }
+
+    /// The fields and methods of the struct with the name in scope, if it is one
+    fn struct_def(&self, name: &str) -> Option<StructTypeData> {
+        match self.lookup(name) {
+            Some(Ty::Con(Type::StructDef(def))) => Some(*def),
+            _ => None,
+        }
+    }
+
+    /// The type of the field of a value of the type, or a fresh variable if it is not known
+    fn field_ty(&mut self, ty: &Ty, field: &str) -> Ty {
+        let field_ty = match self.resolve(ty) {
+            Ty::Con(Type::Named(name)) => self
+                .struct_def(&name)
+                .and_then(|def| def.field(field).cloned()),
+            _ => None,
+        };
+
+        match field_ty {
+            Some(ty) => self.ty_of(&ty),
+            None => self.fresh(),
+        }
+    }
+
+    /// The type of the block, or a fresh variable if it diverges so that it takes the type of the branches it is unified with.
+    fn infer_branch(&mut self, blk: &BlockSeq) -> Ty {
+        let ty = self.infer_block(blk, vec![]);
+        if Infer::diverges(blk) {
+            self.fresh()
+        } else {
+            ty
+        }
+    }
+
+    fn infer_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::LetStmt(stmt) => {
+                // Recorded before the expression, as its lets are visited after it when annotating
+                let idx = self.let_tys.len();
+                self.let_tys.push(None);
+
+                let ty = self.infer_expr(&stmt.expr);
+                if Infer::needs_elem_ann(stmt) {
+                    self.needed = true;
+                    self.let_tys[idx] = Some(ty.clone());
+                }
+                if let Some(ann) = &stmt.type_ann {
+                    let ann = self.ty_of(ann);
+                    self.expect(&ann, &ty, |ann, ty| {
+                        format!(
+                            "'{}' has declared type {} but inferred type {}",
+                            stmt.ident, ann, ty
+                        )
+                    });
+                }
+                self.bind(&stmt.ident, ty);
+            }
+            Decl::LetDestructureStmt(stmt) => {
+                let ty = self.infer_expr(&stmt.expr);
+                match &stmt.pat {
+                    DestructurePattern::Array(elems, rest) => {
+                        let elem_ty = self.elem_ty(&ty, |found| {
+                            format!(
+                                "Can't destructure type '{}' with array pattern {}, expected an array or a string",
+                                found, stmt.pat
+                            )
+                        });
+                        for name in elems.iter() {
+                            self.bind(name, elem_ty.clone());
+                        }
+                        if let Some(rest) = rest {
+                            self.bind(rest, ty);
+                        }
+                    }
+                    DestructurePattern::Struct(name, fields, _) => {
+                        self.expect(&Ty::Con(Type::Named(name.clone())), &ty, |_, found| {
+                            format!(
+                                "Can't destructure type '{}' with struct pattern {}",
+                                found, stmt.pat
+                            )
+                        });
+                        for (field, binding) in fields.iter() {
+                            let field_ty = self.field_ty(&ty, field);
+                            self.bind(binding, field_ty);
+                        }
+                    }
+                }
+            }
+            Decl::AssignStmt(stmt) => {
+                let ty = self.infer_expr(&stmt.expr);
+                let sym_ty = self.symbol(&stmt.ident);
+                self.expect(&sym_ty, &ty, |sym_ty, ty| {
+                    format!(
+                        "'{}' declared with type {} but assigned type {}",
+                        stmt.ident, sym_ty, ty
+                    )
+                });
+            }
+            Decl::FieldAssignStmt(stmt) => {
+                let ty = self.infer_expr(&stmt.expr);
+                let mut field_ty = self.symbol(&stmt.ident);
+                for field in stmt.fields.iter() {
+                    field_ty = self.field_ty(&field_ty, field);
+                }
+                self.expect(&field_ty, &ty, |field_ty, ty| {
+                    format!(
+                        "'{}.{}' has type {} but assigned type {}",
+                        stmt.ident,
+                        stmt.fields.join("."),
+                        field_ty,
+                        ty
+                    )
+                });
+            }
+            Decl::ExprStmt(expr) => {
+                self.infer_expr(expr);
+            }
+            Decl::IfOnlyStmt(if_else) => {
+                self.infer_if_else(if_else);
+            }
+            Decl::LoopStmt(lp) => {
+                self.infer_loop(lp);
+            }
+            Decl::ForStmt(lp) => {
+                let var_ty = self.infer_iterable(&lp.iter);
+                let ty = self.fresh();
+                self.loop_stack.push((lp.label.clone(), ty));
+                self.infer_block(&lp.body, vec![(lp.var.clone(), var_ty)]);
+                self.loop_stack.pop();
+            }
+            Decl::BreakStmt(brk) => self.infer_break(brk),
+            Decl::FnDeclStmt(fn_decl) => self.infer_fn_decl(fn_decl, true),
+            Decl::ImplStmt(impl_data) => {
+                for method in impl_data.methods.iter() {
+                    self.infer_fn_decl(method, false);
+                }
+            }
+            Decl::ReturnStmt(ret_expr) => {
+                let ty = match ret_expr {
+                    Some(expr) => self.infer_expr(expr),
+                    None => Ty::Con(Type::Unit),
+                };
+
+                if let Some(ret) = self.ret_stack.last().cloned() {
+                    self.expect(&ret, &ty, |ret, ty| {
+                        format!(
+                            "Expected function return type '{}' but return statement has type '{}'",
+                            ret, ty
+                        )
+                    });
+                }
+            }
+            Decl::YieldValueStmt(expr) => {
+                let ty = self.infer_expr(expr);
+                if let Some(elem) = self.yield_stack.last().cloned() {
+                    self.expect(&elem, &ty, |elem, ty| {
+                        format!("Expected type '{}' for yield but got '{}'", elem, ty)
+                    });
+                }
+            }
+            Decl::WaitStmt(sem) | Decl::PostStmt(sem) => {
+                let ty = self.symbol(sem);
+                self.expect(&Ty::Con(Type::Semaphore), &ty, |exp, ty| {
+                    format!("Expected type '{}' for '{}', inferred '{}'", exp, sem, ty)
+                });
+            }
+            Decl::StructDeclStmt(_) | Decl::EnumDeclStmt(_) | Decl::YieldStmt => {}
+        }
+    }
+
+    /// Whether the let binds a new channel or bounded queue with no annotation, so the checker can't tell
+    /// the type of its values from the call alone.
+    fn needs_elem_ann(stmt: &LetStmtData) -> bool {
+        stmt.type_ann.is_none()
+            && matches!(&stmt.expr, Expr::FnCallExpr(fn_call)
+                if fn_call.name == "channel" || fn_call.name == "bounded_queue")
+    }
+
+    /// The type of the values breaks leave the loop with, which is unit if they have none.
+    fn infer_loop(&mut self, lp: &LoopData) -> Ty {
+        if let Some(cond) = &lp.cond {
+            let ty = self.infer_expr(cond);
+            self.expect(&Ty::Con(Type::Bool), &ty, |exp, ty| {
+                format!(
+                    "Expected type '{}' for loop condition, inferred '{}'",
+                    exp, ty
+                )
+            });
+        }
+
+        let ty = self.fresh();
+        self.loop_stack.push((lp.label.clone(), ty));
+        self.infer_block(&lp.body, vec![]);
+        let (_, ty) = self.loop_stack.pop().expect("Loop was pushed");
+
+        // A loop with no break has no value, like one left by break without a value
+        match self.resolve(&ty) {
+            Ty::Var(_) => Ty::Con(Type::Unit),
+            ty => ty,
+        }
+    }
+
+    fn infer_break(&mut self, brk: &BreakData) {
+        let ty = match &brk.value {
+            Some(expr) => self.infer_expr(expr),
+            None => Ty::Con(Type::Unit),
+        };
+
+        let target = match &brk.label {
+            Some(label) => self
+                .loop_stack
+                .iter()
+                .rev()
+                .find(|(lp_label, _)| lp_label.as_ref() == Some(label)),
+            None => self.loop_stack.last(),
+        };
+
+        if let Some((_, loop_ty)) = target.cloned() {
+            self.expect(&loop_ty, &ty, |loop_ty, ty| {
+                format!(
+                    "break values have type mismatch - expected: {}, got: {}",
+                    loop_ty, ty
+                )
+            });
+        }
+    }
+
+    // Methods are not bound to their name, so bind_name is false for them, and they are not generalized
+    fn infer_fn_decl(&mut self, fn_decl: &FnDeclData, bind_name: bool) {
+        let mut params = vec![];
+        for param in fn_decl.params.iter() {
+            let ty = match &param.type_ann {
+                Some(ty) => self.ty_of(ty),
+                None => {
+                    self.needed = true;
+                    self.fresh()
+                }
+            };
+            params.push((param.name.clone(), ty));
+        }
+
+        // A function with parameters left to inference has its return type inferred too if it has none,
+        // and so does a generator, which gives a Generator of what it yields
+        let infer_ret = fn_decl.ret_type == Type::Unit
+            && (fn_decl.is_generator
+                || fn_decl.params.iter().any(|param| param.type_ann.is_none()));
+        let ret = match (infer_ret, fn_decl.is_generator) {
+            (true, true) => {
+                self.needed = true;
+                Ty::Generator(Box::new(self.fresh()))
+            }
+            (true, false) => self.fresh(),
+            (false, _) => self.ty_of(&fn_decl.ret_type),
+        };
+        let fn_ty = Ty::Fn(
+            params.iter().map(|(_, ty)| ty.clone()).collect(),
+            Box::new(ret.clone()),
+        );
+
+        // Bound before the body is inferred to support recursion
+        if bind_name {
+            let decl_ty = self.symbol(&fn_decl.name);
+            self.unify(&decl_ty, &fn_ty);
+            self.bind(&fn_decl.name, fn_ty.clone());
+        }
+        let param_tys = params.iter().map(|(_, ty)| ty.clone()).collect();
+        self.fn_sigs
+            .push((param_tys, infer_ret.then(|| ret.clone())));
+
+        // The body of a generator and its returns, which end it, give unit
+        let body_ret = if fn_decl.is_generator {
+            let elem = match self.resolve(&ret) {
+                Ty::Generator(elem) => *elem,
+                // Left to the checker to report
+                _ => self.fresh(),
+            };
+            self.yield_stack.push(elem);
+            Ty::Con(Type::Unit)
+        } else {
+            ret.clone()
+        };
+
+        self.ret_stack.push(body_ret.clone());
+        let body_ty = self.infer_block(&fn_decl.body, params);
+        self.ret_stack.pop();
+        if fn_decl.is_generator {
+            self.yield_stack.pop();
+        }
+
+        // A body with no last expression gives unit, unless it returns
+        let body_ty = match &fn_decl.body.last_expr {
+            Some(_) => Some(body_ty),
+            None if infer_ret && !Infer::diverges(&fn_decl.body) => Some(Ty::Con(Type::Unit)),
+            None => None,
+        };
+        if let Some(body_ty) = body_ty {
+            self.expect(&body_ret, &body_ty, |ret, body_ty| {
+                format!(
+                    "Function '{}' has return type '{}' but found block type '{}'",
+                    fn_decl.name, ret, body_ty
+                )
+            });
+        }
+
+        if bind_name {
+            self.generalize(&fn_decl.name, &fn_ty);
+        }
+    }
+
+    fn infer_if_else(&mut self, if_else: &IfElseData) -> Ty {
+        let cond = self.infer_expr(&if_else.cond);
+        self.expect(&Ty::Con(Type::Bool), &cond, |exp, cond| {
+            format!(
+                "Expected type '{}' for if condition, inferred '{}'",
+                exp, cond
+            )
+        });
+
+        let if_ty = self.infer_branch(&if_else.if_blk);
+        let Some(else_blk) = &if_else.else_blk else {
+            return Ty::Con(Type::Unit);
+        };
+
+        let else_ty = self.infer_branch(else_blk);
+        self.expect(&if_ty, &else_ty, |if_ty, else_ty| {
+            format!(
+                "if-else has type mismatch - consequent: {}, alternative: {}",
+                if_ty, else_ty
+            )
+        });
+
+        if_ty
+    }
+
+    fn infer_select(&mut self, select: &SelectData) -> Ty {
+        let ty = self.fresh();
+        for arm in select.arms.iter() {
+            let res = match &arm.op {
+                SelectOp::Wait(sem) => {
+                    let sem_ty = self.symbol(sem);
+                    self.expect(&Ty::Con(Type::Semaphore), &sem_ty, |exp, sem_ty| {
+                        format!(
+                            "Expected type '{}' for select arm '{}', inferred '{}'",
+                            exp, sem, sem_ty
+                        )
+                    });
+                    Ty::Con(Type::Unit)
+                }
+                // The values sent or received in a select are those of the channel, as for send and recv
+                SelectOp::Recv(ch) | SelectOp::Send(ch, _) => {
+                    let elem = self.fresh();
+                    let ch_ty = self.symbol(ch);
+                    self.expect(
+                        &Ty::Channel(Box::new(elem.clone())),
+                        &ch_ty,
+                        |exp, ch_ty| {
+                            format!(
+                                "Expected type '{}' for select arm '{}', inferred '{}'",
+                                exp, arm.op, ch_ty
+                            )
+                        },
+                    );
+
+                    match &arm.op {
+                        SelectOp::Send(_, val) => {
+                            let val_ty = self.infer_expr(val);
+                            self.expect(&elem, &val_ty, |elem, val_ty| {
+                                format!(
+                                    "Expected type '{}' for the value sent in select arm '{}', inferred '{}'",
+                                    elem, arm.op, val_ty
+                                )
+                            });
+                            Ty::Con(Type::Bool)
+                        }
+                        _ => Ty::Option(Box::new(elem)),
+                    }
+                }
+            };
+
+            let params = arm
+                .bind
+                .iter()
+                .map(|name| (name.clone(), res.clone()))
+                .collect();
+            let arm_ty = self.infer_block(&arm.blk, params);
+            if Infer::diverges(&arm.blk) {
+                continue;
+            }
+
+            self.expect(&ty, &arm_ty, |ty, arm_ty| {
+                format!(
+                    "select arms have type mismatch - expected: {}, got: {}",
+                    ty, arm_ty
+                )
+            });
+        }
+
+        ty
+    }
+
+    fn infer_match(&mut self, match_data: &MatchData) -> Ty {
+        let expr = self.infer_expr(&match_data.expr);
+
+        let ty = self.fresh();
+        for arm in match_data.arms.iter() {
+            // The variants tell what is matched on e.g for an unannotated parameter
+            let mut params = vec![];
+            self.infer_pattern(&expr, &arm.pat, &mut params);
+
+            if let Some(guard) = &arm.guard {
+                self.scopes.push(params.iter().cloned().collect());
+                let guard_ty = self.infer_expr(guard);
+                self.scopes.pop();
+
+                self.expect(&Ty::Con(Type::Bool), &guard_ty, |bool_ty, found| {
+                    format!(
+                        "Expected type '{}' for match guard but got '{}'",
+                        bool_ty, found
+                    )
+                });
+            }
+
+            let arm_ty = self.infer_block(&arm.blk, params);
+            if Infer::diverges(&arm.blk) {
+                continue;
+            }
+
+            self.expect(&ty, &arm_ty, |ty, arm_ty| {
+                format!(
+                    "match arms have type mismatch - expected: {}, got: {}",
+                    ty, arm_ty
+                )
+            });
+        }
+
+        ty
+    }
+
+    fn infer_try_catch(&mut self, try_catch: &TryCatchData) -> Ty {
+        let err = (try_catch.err.to_string(), Ty::Con(Type::String));
+        let blks = [
+            (&try_catch.try_blk, vec![]),
+            (&try_catch.catch_blk, vec![err]),
+        ];
+
+        let ty = self.fresh();
+        for (blk, params) in blks {
+            let blk_ty = self.infer_block(blk, params);
+            if Infer::diverges(blk) {
+                continue;
+            }
+
+            self.expect(&ty, &blk_ty, |ty, blk_ty| {
+                format!(
+                    "try and catch blocks have type mismatch - expected: {}, got: {}",
+                    ty, blk_ty
+                )
+            });
+        }
+
+        ty
+    }
+
+    /// Unify the type matched on with what the pattern matches, collecting the types of the names it binds.
+    /// Mismatches are left to the checker, which reports them with the patterns.
+    fn infer_pattern(&mut self, ty: &Ty, pat: &Pattern, binds: &mut Vec<(String, Ty)>) {
+        match pat {
+            Pattern::Wildcard => (),
+            Pattern::Bind(name) => match binds.iter().find(|(bound, _)| bound == name) {
+                // bound again by another alternative
+                Some((_, bound_ty)) => {
+                    let bound_ty = bound_ty.clone();
+                    self.unify(&bound_ty, ty);
+                }
+                None => binds.push((name.to_string(), ty.clone())),
+            },
+            Pattern::Literal(lit) => {
+                let lit_ty = self.infer_expr(lit);
+                self.unify(ty, &lit_ty);
+            }
+            Pattern::Range(..) => {
+                self.unify(ty, &Ty::Con(Type::Int));
+            }
+            Pattern::At(name, pat) => {
+                self.infer_pattern(ty, &Pattern::Bind(name.to_string()), binds);
+                self.infer_pattern(ty, pat, binds);
+            }
+            Pattern::Variant(variant, sub) => {
+                let held = self.held_ty(ty, variant);
+                if let Some(sub) = sub {
+                    self.infer_pattern(&held, sub, binds);
+                }
+            }
+            Pattern::Or(alts) => {
+                for alt in alts.iter() {
+                    self.infer_pattern(ty, alt, binds);
+                }
+            }
+        }
+    }
+
+    /// The type of the value the variant holds, unifying the type matched on with the option,
+    /// result or enum of the variant.
+    fn held_ty(&mut self, ty: &Ty, variant: &str) -> Ty {
+        let held = self.fresh();
+        let matched = match variant {
+            "Some" | "None" => Ty::Option(Box::new(held.clone())),
+            "Ok" => Ty::Result(Box::new(held.clone()), Box::new(self.fresh())),
+            "Err" => Ty::Result(Box::new(self.fresh()), Box::new(held.clone())),
+            variant => match self.lookup(variant) {
+                Some(Ty::Fn(params, enum_ty)) => {
+                    if let Some(param) = params.first() {
+                        self.unify(&held, param);
+                    }
+                    *enum_ty
+                }
+                Some(enum_ty) => enum_ty,
+                None => return held,
+            },
+        };
+
+        self.unify(ty, &matched);
+        held
+    }
+
+    /// The type of the value an option or result holds when unwrapped, or a fresh variable if it is not known.
+    fn unwrapped_ty(&mut self, ty: &Ty, variant: &str) -> Ty {
+        match (self.resolve(ty), variant) {
+            (Ty::Option(ty), "Some") | (Ty::Result(ty, _), "Some") => *ty,
+            (Ty::Result(_, err), "Err") => *err,
+            _ => self.fresh(),
+        }
+    }
+
+    fn infer_binop(&mut self, op: &BinOpType, lhs: &Expr, rhs: &Expr) -> Ty {
+        let l_ty = self.infer_expr(lhs);
+        let r_ty = self.infer_expr(rhs);
+        let err = |l: &Ty, r: &Ty| TypeChecker::binop_err(op, l, r);
+
+        // Structs overload operators with methods, whose types are checked by the checker
+        if let Ty::Con(Type::Named(name)) = self.resolve(&l_ty) {
+            let method = TypeChecker::overload_method(op)
+                .and_then(|method| self.struct_def(&name)?.method(method).cloned());
+            if let Some(method) = method {
+                if let Some(param) = method.params.get(1) {
+                    let param = self.ty_of(param);
+                    self.unify(&param, &r_ty);
+                }
+                return self.ty_of(&method.ret_type);
+            }
+        }
+
+        match op {
+            BinOpType::Add | BinOpType::Sub | BinOpType::Mul | BinOpType::Div => {
+                self.expect(&l_ty, &r_ty, err);
+                self.overloaded.push(l_ty.clone());
+                l_ty
+            }
+            // Only ints have a remainder
+            BinOpType::Mod => {
+                let int_ty = Ty::Con(Type::Int);
+                if !(self.unify(&int_ty, &l_ty) && self.unify(&int_ty, &r_ty)) {
+                    let e = err(&self.resolve(&l_ty), &self.resolve(&r_ty));
+                    self.errs.add(&e);
+                }
+                int_ty
+            }
+            BinOpType::Gt | BinOpType::Lt | BinOpType::Ge | BinOpType::Le => {
+                self.expect(&l_ty, &r_ty, err);
+                self.overloaded.push(l_ty);
+                Ty::Con(Type::Bool)
+            }
+            // Any two values of the same type can be compared
+            BinOpType::LogicalEq => {
+                self.expect(&l_ty, &r_ty, err);
+                Ty::Con(Type::Bool)
+            }
+            BinOpType::LogicalAnd | BinOpType::LogicalOr => {
+                let bool_ty = Ty::Con(Type::Bool);
+                if !(self.unify(&bool_ty, &l_ty) && self.unify(&bool_ty, &r_ty)) {
+                    let e = err(&self.resolve(&l_ty), &self.resolve(&r_ty));
+                    self.errs.add(&e);
+                }
+                bool_ty
+            }
+        }
+    }
+
+    /// The type of a call to a builtin. Builtins that are polymorphic in ways a function type can't express
+    /// leave their arguments unconstrained, and are checked by the checker.
+    fn infer_builtin_call(&mut self, name: &str, args: Vec<Ty>) -> Ty {
+        let (params, ret) = match name {
+            "read_line" => (vec![], Type::String),
+            "read_file" => (vec![Type::String], io_result(Type::String)),
+            "write_file" | "append_file" => {
+                (vec![Type::String, Type::String], io_result(Type::Unit))
+            }
+            "file_exists" => (vec![Type::String], Type::Bool),
+            "read_lines" => (
+                vec![Type::String],
+                io_result(Type::Array(Box::new(Type::String))),
+            ),
+            "string_len" | "atoi" => (vec![Type::String], Type::Int),
+            "bytes" => (vec![Type::String], Type::Array(Box::new(Type::Int))),
+            "itoa" => (vec![Type::Int], Type::String),
+            "cos" | "sin" | "tan" | "sqrt" | "log" => (vec![Type::Float], Type::Float),
+            "pow" => (vec![Type::Float, Type::Float], Type::Float),
+            "random_int" => (vec![Type::Int, Type::Int], Type::Int),
+            "is_nan" | "is_inf" => (vec![Type::Float], Type::Bool),
+            "float_to_int" => (vec![Type::Float], Type::Int),
+            "int_to_float" => (vec![Type::Int], Type::Float),
+            "sem_create" | "mutex" => (vec![], Type::Semaphore),
+            "rwlock" => (vec![], Type::RwLock),
+            "atomic_int" => (vec![Type::Int], Type::AtomicInt),
+            "fetch_add" => (vec![Type::AtomicInt, Type::Int], Type::Int),
+            "load" => (vec![Type::AtomicInt], Type::Int),
+            "store" => (vec![Type::AtomicInt, Type::Int], Type::Unit),
+            "compare_and_swap" => (vec![Type::AtomicInt, Type::Int, Type::Int], Type::Int),
+            "wait_timeout" => (vec![Type::Semaphore, Type::Int], Type::Bool),
+            THREAD_ID => (vec![any_thread()], Type::Int),
+            IS_FINISHED => (vec![any_thread()], Type::Bool),
+            "kill" => (vec![any_thread()], Type::Unit),
+            "priority" => (vec![], Type::Int),
+            "exit" | "set_priority" => (vec![Type::Int], Type::Unit),
+            // Never returns, so it can stand in for a value of any type
+            "panic" => (vec![Type::String], Type::Unknown),
+            "cv_create" => (vec![], Type::CondVar),
+            "cv_wait" => (vec![Type::CondVar, Type::Semaphore], Type::Unit),
+            "cv_notify_one" | "cv_notify_all" => (vec![Type::CondVar], Type::Unit),
+            "barrier_create" => (vec![Type::Int], Type::Barrier),
+            "barrier_wait" => (vec![Type::Barrier], Type::Unit),
+            "wg_create" => (vec![], Type::WaitGroup),
+            "wg_add" => (vec![Type::WaitGroup, Type::Int], Type::Unit),
+            "wg_done" | "wg_wait" => (vec![Type::WaitGroup], Type::Unit),
+            "assert" => (vec![Type::Bool], Type::Unit),
+            // The values sent to a channel are the values received from it
+            "channel" | "send" | "recv" | "close" => {
+                let elem = self.fresh();
+                let ch = Ty::Channel(Box::new(elem.clone()));
+                let (params, ret) = match name {
+                    "channel" => (vec![Ty::Con(Type::Int)], ch),
+                    "send" => (vec![ch, elem], Ty::Con(Type::Bool)),
+                    "recv" => (vec![ch], Ty::Option(Box::new(elem))),
+                    _ => (vec![ch], Ty::Con(Type::Unit)),
+                };
+                self.expect_args(name, &params, &args);
+                return ret;
+            }
+            // The values pushed to a bounded queue are the values popped from it
+            "bounded_queue" | "push" | "pop" => {
+                let elem = self.fresh();
+                let q = Ty::BoundedQueue(Box::new(elem.clone()));
+                let (params, ret) = match name {
+                    "bounded_queue" => (vec![Ty::Con(Type::Int)], q),
+                    "push" => (vec![q, elem], Ty::Con(Type::Unit)),
+                    _ => (vec![q], elem),
+                };
+                self.expect_args(name, &params, &args);
+                return ret;
+            }
+            // The value of a key in the current thread has the type of the value it starts as
+            "thread_local" | "set_thread_local" => {
+                let val = self.fresh();
+                let params = [Ty::Con(Type::String), val.clone()];
+                self.expect_args(name, &params, &args);
+                if name == "thread_local" {
+                    return val;
+                }
+                return Ty::Con(Type::Unit);
+            }
+            // (int, int) -> int or (float, float) -> float
+            "min" | "max" => {
+                if let [a, b] = args.as_slice() {
+                    self.unify(a, b);
+                    self.overloaded.push(a.clone());
+                    return a.clone();
+                }
+                return self.fresh();
+            }
+            // int -> int or float -> float
+            "abs" => {
+                let ty = args.first().cloned().unwrap_or_else(|| self.fresh());
+                self.overloaded.push(ty.clone());
+                return ty;
+            }
+            "len" => {
+                if let Some(arg) = args.first() {
+                    self.elem_ty(arg, |arg| {
+                        format!(
+                            "Expected an array or a string for argument 1 of 'len', inferred '{}'",
+                            arg
+                        )
+                    });
+                }
+                return Ty::Con(Type::Int);
+            }
+            "json_parse" => {
+                let example = self.fresh();
+                self.expect_args(name, &[Ty::Con(Type::String), example.clone()], &args);
+                return Ty::Result(Box::new(example), Box::new(Ty::Con(Type::String)));
+            }
+            "json_stringify" => {
+                let params = [self.fresh(), Ty::Con(Type::Bool)];
+                self.expect_args(name, &params, &args);
+                return self.ty_of(&io_result(Type::String));
+            }
+            "typeof" => return Ty::Con(Type::String),
+            "is_int" | "is_float" | "is_bool" | "is_string" | "is_unit" => {
+                return Ty::Con(Type::Bool)
+            }
+            "print" | "println" | "assert_eq" | "sem_set" => return Ty::Con(Type::Unit),
+            "Some" | "Ok" | "Err" => {
+                let held = Box::new(args.first().cloned().unwrap_or_else(|| self.fresh()));
+                return match name {
+                    "Some" => Ty::Option(held),
+                    "Ok" => Ty::Result(held, Box::new(self.fresh())),
+                    _ => Ty::Result(Box::new(self.fresh()), held),
+                };
+            }
+            "is_some" | "is_none" | "is_ok" | "is_err" => return Ty::Con(Type::Bool),
+            // unwrap takes an option or a result, which is left to the checker if it is not known yet
+            "unwrap" | "unwrap_err" => {
+                let variant = if name == "unwrap" { "Some" } else { "Err" };
+                return match args.first() {
+                    Some(arg) => self.unwrapped_ty(arg, variant),
+                    None => self.fresh(),
+                };
+            }
+            _ => return self.fresh(),
+        };
+
+        let params: Vec<Ty> = params.into_iter().map(Ty::Con).collect();
+        self.expect_args(name, &params, &args);
+
+        self.ty_of(&ret)
+    }
+
+    fn expect_args(&mut self, name: &str, params: &[Ty], args: &[Ty]) {
+        // The checker reports the wrong number of arguments
+        if params.len() != args.len() {
+            return;
+        }
+
+        for (i, (param, arg)) in params.iter().zip(args.iter()).enumerate() {
+            self.expect(param, arg, |param, arg| {
+                format!(
+                    "Expected type '{}' for argument {} of '{}', inferred '{}'",
+                    param,
+                    i + 1,
+                    name,
+                    arg
+                )
+            });
+        }
+    }
+
+    fn infer_fn_call(&mut self, fn_call: &FnCallData) -> Ty {
+        let args: Vec<Ty> = fn_call
+            .args
+            .iter()
+            .map(|arg| self.infer_expr(arg))
+            .collect();
+
+        if TypeChecker::is_builtin_fn(&fn_call.name) {
+            return self.infer_builtin_call(&fn_call.name, args);
+        }
+
+        let ret = self.fresh();
+        let Some(callee) = self.lookup(&fn_call.name) else {
+            return ret;
+        };
+        let callee = self.instantiate(&callee);
+
+        let call_ty = Ty::Fn(args, Box::new(ret.clone()));
+        if !self.unify(&callee, &call_ty) {
+            let callee = self.resolve(&callee);
+            let e = match callee {
+                Ty::Fn(..) => format!(
+                    "Function '{}' has type '{}' but is called with arguments '{}'",
+                    fn_call.name,
+                    callee,
+                    self.resolve(&call_ty)
+                ),
+                _ => format!(
+                    "Can't call '{}', its type is inferred to be '{}'",
+                    fn_call.name, callee
+                ),
+            };
+            self.errs.add(&e);
+        }
+
+        ret
+    }
+
+    fn infer_struct_expr(&mut self, struct_expr: &StructExprData) -> Ty {
+        let def = self.struct_def(&struct_expr.name);
+
+        for (field, expr) in struct_expr.fields.iter() {
+            let ty = self.infer_expr(expr);
+            let Some(field_ty) = def.as_ref().and_then(|def| def.field(field)) else {
+                continue;
+            };
+
+            let field_ty = self.ty_of(field_ty);
+            self.expect(&field_ty, &ty, |field_ty, ty| {
+                format!(
+                    "Field '{}' of {} has type {} but was given type {}",
+                    field, struct_expr.name, field_ty, ty
+                )
+            });
+        }
+
+        let ty = Ty::Con(Type::Named(struct_expr.name.clone()));
+        if let Some(base) = &struct_expr.base {
+            let base_ty = self.infer_expr(base);
+            self.unify(&ty, &base_ty);
+        }
+
+        ty
+    }
+
+    fn infer_expr(&mut self, expr: &Expr) -> Ty {
+        match expr {
+            Expr::Integer(_) => Ty::Con(Type::Int),
+            Expr::Float(_) => Ty::Con(Type::Float),
+            Expr::Bool(_) => Ty::Con(Type::Bool),
+            Expr::StringLiteral(_) => Ty::Con(Type::String),
+            Expr::Symbol(ident) => {
+                if TypeChecker::is_builtin_fn(ident) {
+                    return self.fresh();
+                }
+                if ident == NONE {
+                    return Ty::Option(Box::new(self.fresh()));
+                }
+                self.symbol(ident)
+            }
+            Expr::UnOpExpr(op, expr) => {
+                let ty = self.infer_expr(expr);
+                match op {
+                    UnOpType::Not => self.expect(&Ty::Con(Type::Bool), &ty, |_, ty| {
+                        format!("Can't apply logical NOT to type {}", ty)
+                    }),
+                    UnOpType::Negate => self.overloaded.push(ty.clone()),
+                }
+                ty
+            }
+            Expr::BinOpExpr(op, lhs, rhs) => self.infer_binop(op, lhs, rhs),
+            Expr::BlockExpr(blk) | Expr::ScopeExpr(blk) => self.infer_block(blk, vec![]),
+            Expr::LockExpr(lock) => {
+                // a mutex is a sem, so only a read lock knows the type of its lock
+                let ty = self.infer_expr(&lock.lock);
+                if lock.shared {
+                    self.expect(&Ty::Con(Type::RwLock), &ty, |exp, ty| {
+                        format!("Expected type '{}' for read_lock, inferred '{}'", exp, ty)
+                    });
+                }
+                self.infer_block(&lock.blk, vec![])
+            }
+            Expr::IfElseExpr(if_else) => self.infer_if_else(if_else),
+            Expr::FnCallExpr(fn_call) => self.infer_fn_call(fn_call),
+            Expr::MethodCallExpr(method_call) => {
+                let recv = self.infer_expr(&method_call.recv);
+                self.overloaded.push(recv.clone());
+                let args: Vec<Ty> = method_call
+                    .args
+                    .iter()
+                    .map(|arg| self.infer_expr(arg))
+                    .collect();
+
+                if let Ty::Con(Type::Named(name)) = self.resolve(&recv) {
+                    let method = self
+                        .struct_def(&name)
+                        .and_then(|def| def.method(&method_call.method).cloned());
+                    let Some(method) = method else {
+                        return self.fresh();
+                    };
+
+                    // self is the receiver
+                    for (param, arg) in method.params.iter().skip(1).zip(args.iter()) {
+                        let param = self.ty_of(param);
+                        self.unify(&param, arg);
+                    }
+                    return self.ty_of(&method.ret_type);
+                }
+
+                match method_call.method.as_str() {
+                    "id" | "is_finished" => {
+                        let thread = Ty::Thread(Box::new(self.fresh()));
+                        self.unify(&thread, &recv);
+                        let ret = if method_call.method == "id" {
+                            Type::Int
+                        } else {
+                            Type::Bool
+                        };
+                        Ty::Con(ret)
+                    }
+                    _ => self.fresh(),
+                }
+            }
+            Expr::SpawnExpr(fn_call) => {
+                let ty = self.infer_fn_call(fn_call);
+                Ty::Thread(Box::new(ty))
+            }
+            Expr::SpawnBlockExpr(blk) => {
+                let ty = self.infer_block(blk, vec![]);
+                Ty::Thread(Box::new(ty))
+            }
+            // the value the thread finishes with
+            Expr::JoinExpr(tid) => {
+                let ty = self.symbol(tid);
+                let res = self.fresh();
+                self.expect(&Ty::Thread(Box::new(res.clone())), &ty, |exp, ty| {
+                    format!("Expected type '{}' for join, inferred '{}'", exp, ty)
+                });
+                res
+            }
+            Expr::SelectExpr(select) => self.infer_select(select),
+            Expr::MatchExpr(match_data) => self.infer_match(match_data),
+            Expr::LoopExpr(lp) => self.infer_loop(lp),
+            Expr::TryExpr(expr) => {
+                let ty = self.infer_expr(expr);
+                self.unwrapped_ty(&ty, "Some")
+            }
+            Expr::TryCatchExpr(try_catch) => self.infer_try_catch(try_catch),
+            Expr::StructExpr(struct_expr) => self.infer_struct_expr(struct_expr),
+            Expr::FieldExpr(expr, field) => {
+                let ty = self.infer_expr(expr);
+                self.overloaded.push(ty.clone());
+                self.field_ty(&ty, field)
+            }
+            Expr::ArrayExpr(elems) => {
+                let elem_ty = self.fresh();
+                for elem in elems.iter() {
+                    let ty = self.infer_expr(elem);
+                    self.expect(&elem_ty, &ty, |expected, found| {
+                        format!(
+                            "Array elements have type mismatch - expected: {}, got: {}",
+                            expected, found
+                        )
+                    });
+                }
+                Ty::Array(Box::new(elem_ty))
+            }
+            Expr::ComprehensionExpr(comp) => self.infer_comprehension(comp),
+            Expr::IndexExpr(expr, idx) => {
+                let ty = self.infer_expr(expr);
+                let elem_ty = self.elem_ty(&ty, |found| {
+                    format!(
+                        "Can't index into type '{}', expected an array or a string",
+                        found
+                    )
+                });
+                self.infer_index_bound(idx);
+                elem_ty
+            }
+            // Arrays and strings can be sliced, so the type must be known
+            Expr::SliceExpr(expr, start, end) => {
+                let ty = self.infer_expr(expr);
+                self.overloaded.push(ty.clone());
+                for bound in [start, end].into_iter().flatten() {
+                    self.infer_index_bound(bound);
+                }
+                ty
+            }
+        }
+    }
+
+    /// The type of the elements of an array, or of the chars of a string if the type is known to be a string.
+    /// Otherwise the type is taken to be an array.
+    fn elem_ty(&mut self, ty: &Ty, err: impl Fn(&Ty) -> String) -> Ty {
+        if self.resolve(ty) == Ty::Con(Type::String) {
+            return Ty::Con(Type::String);
+        }
+
+        let elem_ty = self.fresh();
+        self.expect(&Ty::Array(Box::new(elem_ty.clone())), ty, |_, found| {
+            err(found)
+        });
+        elem_ty
+    }
+
+    fn infer_index_bound(&mut self, idx: &Expr) {
+        let ty = self.infer_expr(idx);
+        self.expect(&Ty::Con(Type::Int), &ty, |int_ty, found| {
+            format!("Expected type '{}' for index but got '{}'", int_ty, found)
+        });
+    }
+
+    /// The type of the values the iterable gives
+    fn infer_iterable(&mut self, iter: &Iterable) -> Ty {
+        match iter {
+            Iterable::Range(start, end) => {
+                let int_ty = Ty::Con(Type::Int);
+                for bound in [start, end] {
+                    let ty = self.infer_expr(bound);
+                    self.expect(&int_ty, &ty, |int_ty, found| {
+                        format!(
+                            "Expected type '{}' for the bounds of a range but got '{}'",
+                            int_ty, found
+                        )
+                    });
+                }
+                int_ty
+            }
+            Iterable::Collection(coll) => {
+                let ty = self.infer_expr(coll);
+                if let Ty::Generator(elem) = self.resolve(&ty) {
+                    return *elem;
+                }
+
+                self.elem_ty(&ty, |found| {
+                    format!(
+                        "Can't iterate over type '{}', expected a range, an array, a string or a generator",
+                        found
+                    )
+                })
+            }
+        }
+    }
+
+    fn infer_comprehension(&mut self, comp: &ComprehensionData) -> Ty {
+        let var_ty = self.infer_iterable(&comp.iter);
+        self.scopes
+            .push(HashMap::from([(comp.var.clone(), var_ty)]));
+
+        if let Some(cond) = &comp.cond {
+            let ty = self.infer_expr(cond);
+            self.expect(&Ty::Con(Type::Bool), &ty, |bool_ty, found| {
+                format!(
+                    "Expected type '{}' for comprehension condition but got '{}'",
+                    bool_ty, found
+                )
+            });
+        }
+        let ty = self.infer_expr(&comp.expr);
+
+        self.scopes.pop();
+        Ty::Array(Box::new(ty))
+    }
+}
+
+/// Call f on every function declaration in the block, in the order they appear in the program.
+/// A declaration inference fills in the annotations of.
+enum Inferred<'a> {
+    Fn(&'a mut FnDeclData),
+    Let(&'a mut LetStmtData),
+}
+
+fn for_each_inferred(blk: &mut BlockSeq, f: &mut impl FnMut(Inferred)) {
+    for decl in blk.decls.iter_mut() {
+        match decl {
+            Decl::LetStmt(stmt) => {
+                f(Inferred::Let(stmt));
+                for_each_inferred_in_expr(&mut stmt.expr, f);
+            }
+            Decl::LetDestructureStmt(stmt) => for_each_inferred_in_expr(&mut stmt.expr, f),
+            Decl::AssignStmt(stmt) => for_each_inferred_in_expr(&mut stmt.expr, f),
+            Decl::FieldAssignStmt(stmt) => for_each_inferred_in_expr(&mut stmt.expr, f),
+            Decl::ExprStmt(expr)
+            | Decl::ReturnStmt(Some(expr))
+            | Decl::YieldValueStmt(expr)
+            | Decl::BreakStmt(BreakData {
+                value: Some(expr), ..
+            }) => for_each_inferred_in_expr(expr, f),
+            Decl::IfOnlyStmt(if_else) => for_each_inferred_in_if_else(if_else, f),
+            Decl::LoopStmt(lp) => for_each_inferred_in_loop(lp, f),
+            Decl::ForStmt(lp) => {
+                for_each_inferred_in_iterable(&mut lp.iter, f);
+                for_each_inferred(&mut lp.body, f);
+            }
+            Decl::FnDeclStmt(fn_decl) => {
+                f(Inferred::Fn(fn_decl));
+                for_each_inferred(&mut fn_decl.body, f);
+            }
+            Decl::ImplStmt(impl_data) => {
+                for method in impl_data.methods.iter_mut() {
+                    f(Inferred::Fn(method));
+                    for_each_inferred(&mut method.body, f);
+                }
+            }
+            Decl::StructDeclStmt(_)
+            | Decl::EnumDeclStmt(_)
+            | Decl::ReturnStmt(None)
+            | Decl::BreakStmt(_)
+            | Decl::WaitStmt(_)
+            | Decl::PostStmt(_)
+            | Decl::YieldStmt => (),
+        }
+    }
+
+    if let Some(expr) = &mut blk.last_expr {
+        for_each_inferred_in_expr(Rc::make_mut(expr), f);
+    }
+}
+
+fn for_each_inferred_in_if_else(if_else: &mut IfElseData, f: &mut impl FnMut(Inferred)) {
+    for_each_inferred_in_expr(&mut if_else.cond, f);
+    for_each_inferred(&mut if_else.if_blk, f);
+    if let Some(else_blk) = &mut if_else.else_blk {
+        for_each_inferred(else_blk, f);
+    }
+}
+
+fn for_each_inferred_in_loop(lp: &mut LoopData, f: &mut impl FnMut(Inferred)) {
+    if let Some(cond) = &mut lp.cond {
+        for_each_inferred_in_expr(cond, f);
+    }
+    for_each_inferred(&mut lp.body, f);
+}
+
+fn for_each_inferred_in_iterable(iter: &mut Iterable, f: &mut impl FnMut(Inferred)) {
+    match iter {
+        Iterable::Range(start, end) => {
+            for_each_inferred_in_expr(start, f);
+            for_each_inferred_in_expr(end, f);
+        }
+        Iterable::Collection(arr) => for_each_inferred_in_expr(arr, f),
+    }
+}
+
+fn for_each_inferred_in_expr(expr: &mut Expr, f: &mut impl FnMut(Inferred)) {
+    match expr {
+        Expr::UnOpExpr(_, expr) => for_each_inferred_in_expr(expr, f),
+        Expr::BinOpExpr(_, lhs, rhs) => {
+            for_each_inferred_in_expr(lhs, f);
+            for_each_inferred_in_expr(rhs, f);
+        }
+        Expr::BlockExpr(blk) | Expr::SpawnBlockExpr(blk) | Expr::ScopeExpr(blk) => {
+            for_each_inferred(blk, f)
+        }
+        Expr::IfElseExpr(if_else) => for_each_inferred_in_if_else(if_else, f),
+        Expr::FnCallExpr(fn_call) | Expr::SpawnExpr(fn_call) => {
+            for arg in fn_call.args.iter_mut() {
+                for_each_inferred_in_expr(arg, f);
+            }
+        }
+        Expr::MethodCallExpr(method_call) => {
+            for_each_inferred_in_expr(&mut method_call.recv, f);
+            for arg in method_call.args.iter_mut() {
+                for_each_inferred_in_expr(arg, f);
+            }
+        }
+        Expr::SelectExpr(select) => {
+            for arm in select.arms.iter_mut() {
+                if let SelectOp::Send(_, val) = &mut arm.op {
+                    for_each_inferred_in_expr(val, f);
+                }
+                for_each_inferred(&mut arm.blk, f);
+            }
+        }
+        Expr::MatchExpr(match_data) => {
+            for_each_inferred_in_expr(&mut match_data.expr, f);
+            for arm in match_data.arms.iter_mut() {
+                if let Some(guard) = &mut arm.guard {
+                    for_each_inferred_in_expr(guard, f);
+                }
+                for_each_inferred(&mut arm.blk, f);
+            }
+        }
+        Expr::LoopExpr(lp) => for_each_inferred_in_loop(lp, f),
+        Expr::LockExpr(lock) => {
+            for_each_inferred_in_expr(&mut lock.lock, f);
+            for_each_inferred(&mut lock.blk, f);
+        }
+        Expr::TryExpr(expr) | Expr::FieldExpr(expr, _) => for_each_inferred_in_expr(expr, f),
+        Expr::StructExpr(struct_expr) => {
+            for (_, expr) in struct_expr.fields.iter_mut() {
+                for_each_inferred_in_expr(expr, f);
+            }
+            if let Some(base) = &mut struct_expr.base {
+                for_each_inferred_in_expr(base, f);
+            }
+        }
+        Expr::TryCatchExpr(try_catch) => {
+            for_each_inferred(&mut try_catch.try_blk, f);
+            for_each_inferred(&mut try_catch.catch_blk, f);
+        }
+        Expr::ArrayExpr(elems) => {
+            for elem in elems.iter_mut() {
+                for_each_inferred_in_expr(elem, f);
+            }
+        }
+        Expr::ComprehensionExpr(comp) => {
+            for_each_inferred_in_iterable(&mut comp.iter, f);
+            if let Some(cond) = &mut comp.cond {
+                for_each_inferred_in_expr(cond, f);
+            }
+            for_each_inferred_in_expr(&mut comp.expr, f);
+        }
+        Expr::IndexExpr(expr, idx) => {
+            for_each_inferred_in_expr(expr, f);
+            for_each_inferred_in_expr(idx, f);
+        }
+        Expr::SliceExpr(expr, start, end) => {
+            for_each_inferred_in_expr(expr, f);
+            for bound in [start, end].into_iter().flatten() {
+                for_each_inferred_in_expr(bound, f);
+            }
+        }
+        Expr::Symbol(_)
+        | Expr::Integer(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::StringLiteral(_)
+        | Expr::JoinExpr(_) => (),
+    }
+}
+
+/// Infer the types of the parameters that have no annotation, in the scope of envs, returning the program
+/// with them annotated for the checker. The lets of new channels and bounded queues with no annotation are
+/// annotated with the type of the values first sent to or received from them, e.g `let ch = channel(4)`
+/// followed by `send(ch, 1)` becomes `let ch : Channel<int> = channel(4)`.
+/// A program whose parameters, channels and bounded queues are all annotated is returned as is.
+///
+/// # Errors
+///
+/// The type mismatches found by inference, with the inferred types, and the parameters, channels and
+/// bounded queues whose type could not be inferred.
+pub fn annotate<'prog>(
+    program: &'prog BlockSeq,
+    envs: &[Env],
+) -> Result<Cow<'prog, BlockSeq>, TypeErrors> {
+    let mut infer = Infer::new(envs);
+    infer.infer_block(program, vec![]);
+
+    if !infer.needed {
+        return Ok(Cow::Borrowed(program));
+    }
+
+    if !infer.errs.is_ok() {
+        return Err(infer.errs);
+    }
+
+    let mut program = program.clone();
+    let mut fn_sigs = infer.fn_sigs.iter();
+    let mut let_tys = infer.let_tys.iter();
+    let mut errs = TypeErrors::new();
+
+    for_each_inferred(&mut program, &mut |decl| {
+        let fn_decl = match decl {
+            Inferred::Fn(fn_decl) => fn_decl,
+            Inferred::Let(stmt) => {
+                let ty = let_tys
+                    .next()
+                    .expect("Inference visits the same let statements");
+                let Some(ty) = ty else {
+                    return;
+                };
+
+                match infer.to_type(ty) {
+                    Some(ty) => stmt.type_ann = Some(ty),
+                    None => {
+                        let e = format!(
+                            "Can't infer the type of the values of '{}', add a type annotation",
+                            stmt.ident
+                        );
+                        errs.add(&e);
+                    }
+                }
+                return;
+            }
+        };
+
+        let (param_tys, ret_ty) = fn_sigs
+            .next()
+            .expect("Inference visits the same function declarations");
+
+        for (param, ty) in fn_decl.params.iter_mut().zip(param_tys.iter()) {
+            if param.type_ann.is_some() {
+                continue;
+            }
+
+            match infer.to_type(ty) {
+                Some(ty) => param.type_ann = Some(ty),
+                None => {
+                    let e = format!(
+                        "Can't infer the type of parameter '{}' of '{}', add a type annotation",
+                        param.name, fn_decl.name
+                    );
+                    errs.add(&e);
+                }
+            }
+        }
+
+        if let Some(ret_ty) = ret_ty {
+            match infer.to_type(ret_ty) {
+                Some(ty) => fn_decl.ret_type = ty,
+                None => {
+                    let e = format!(
+                        "Can't infer the return type of '{}', add a type annotation",
+                        fn_decl.name
+                    );
+                    errs.add(&e);
+                }
+            }
+        }
+    });
+
+    if !errs.is_ok() {
+        return Err(errs);
+    }
+
+    Ok(Cow::Owned(program))
+}
+
+/// The result of a builtin working with files, whose error is a message.
+fn io_result(ok: Type) -> Type {
+    Type::Result(Box::new(ok), Box::new(Type::String))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass, expect_pass_str};
+
+    #[test]
+    fn test_infer_params() {
+        let t = r"
+        fn add(a, b) -> int {
+            a + b
+        }
+        add(1, 2)
+        ";
+        expect_pass(t, Type::Int);
+
+        // from the uses in the body
+        let t = r"
+        fn f(x, y, s) -> bool {
+            if y {
+                println(x * 2.0);
+            }
+            string_len(s) > 0
+        }
+        f
+        ";
+        expect_pass_str(t, "fn(float, bool, str) -> bool");
+
+        // from the calls
+        let t = r"
+        fn id(x) -> int {
+            let y = x;
+            return y;
+        }
+        fn call(g, n) -> int {
+            g(n)
+        }
+        call(id, 2)
+        ";
+        expect_pass(t, Type::Int);
+
+        // recursion
+        let t = r"
+        fn fac(n) -> int {
+            if n < 1 {
+                return 1;
+            }
+            n * fac(n - 1)
+        }
+        fac
+        ";
+        expect_pass_str(t, "fn(int) -> int");
+
+        // from the variants matched on, and the constructors called
+        let t = r"
+        enum Shape { Circle(float), Empty }
+        fn area(s) -> float {
+            match s {
+                Circle(r) => { r * r }
+                Empty => { 0.0 }
+            }
+        }
+        fn make(r) -> Shape {
+            Circle(r)
+        }
+        area(make(2.0))
+        ";
+        expect_pass(t, Type::Float);
+
+        // from the values sent to and received from a channel
+        let t = r"
+        fn produce(ch, n) {
+            send(ch, n * 2);
+        }
+        fn consume(ch) -> int {
+            match recv(ch) {
+                Some(v) => { v }
+                None => { 0 }
+            }
+        }
+        produce
+        ";
+        expect_pass_str(t, "fn(Channel<int>, int)");
+
+        // from the values pushed to and popped from a bounded queue
+        let t = r"
+        fn consume(q) -> int {
+            pop(q) + 1
+        }
+        consume
+        ";
+        expect_pass_str(t, "fn(BoundedQueue<int>) -> int");
+
+        // from the lock read
+        let t = r"
+        fn get(rw, x) -> int {
+            read_lock(rw) { x }
+        }
+        get
+        ";
+        expect_pass_str(t, "fn(rwlock, int) -> int");
+    }
+
+    #[test]
+    fn test_infer_errors() {
+        let t = r"
+        fn f(x) {
+            if x + 1 {
+            }
+        }
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Expected type 'bool' for if condition, inferred 'int'",
+            false,
+        );
+
+        let t = r"
+        fn f(x) -> int {
+            let y = x + 1;
+            y(2)
+        }
+        ";
+        expect_err(t, "Can't call 'y', its type is inferred to be 'int'", true);
+
+        let t = r"
+        fn f(g) -> int {
+            g(1) + g(true)
+        }
+        ";
+        expect_err(
+            t,
+            "Function 'g' has type 'fn(int) -> _' but is called with arguments 'fn(bool) -> _'",
+            true,
+        );
+
+        // only compared, which can't be done generically
+        let t = r"
+        fn f(x) -> bool {
+            x < x
+        }
+        ";
+        expect_err(
+            t,
+            "Can't infer the type of parameter 'x' of 'f', add a type annotation",
+            true,
+        );
+
+        // each use of a generic function is instantiated, but the uses still have to agree with each other
+        let t = r"
+        fn id(x) { x }
+        id(1) + id(true)
+        ";
+        expect_err(
+            t,
+            "[TypeError]: Can't apply '+' to types 'int' and 'bool'",
+            false,
+        );
+
+        let t = r"
+        fn wrap(x) { Some(x) }
+        let y: Option<str> = wrap(1);
+        ";
+        expect_err(
+            t,
+            "'y' has declared type Option<str> but inferred type Option<int>",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_infer_generics() {
+        let t = r"
+        fn id(x) { x }
+        id
+        ";
+        expect_pass_str(t, "fn('a) -> 'a");
+
+        let t = r"
+        fn id(x) { x }
+        let n = id(1);
+        if id(true) { n + 1 } else { n }
+        ";
+        expect_pass(t, Type::Int);
+
+        // unused parameters are generic, and so are the functions taking generic functions
+        let t = r"
+        fn first(a, b) { a }
+        fn apply(f, x) { f(x) }
+        apply(first, 2)
+        ";
+        expect_err(
+            t,
+            "Function 'apply' has type 'fn(fn(_) -> _, _) -> _' but is called with arguments 'fn(fn(_, _) -> _, int) -> _'",
+            true,
+        );
+
+        let t = r"
+        fn first(a, b) { a }
+        fn apply(f, x) { f(x) }
+        fn inc(n: int) -> int { n + 1 }
+        apply(inc, first(3, true))
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r#"
+        fn apply(f, x) { f(x) }
+        fn id(x) { x }
+        apply(id, "s")
+        "#;
+        expect_pass(t, Type::String);
+
+        // generic containers
+        let t = r"
+        fn wrap(x) { Some(x) }
+        fn get(o, d) {
+            match o {
+                Some(v) => { v }
+                None => { d }
+            }
+        }
+        wrap
+        ";
+        expect_pass_str(t, "fn('a) -> Option<'a>");
+
+        let t = r#"
+        fn wrap(x) { Some(x) }
+        fn get(o, d) {
+            match o {
+                Some(v) => { v }
+                None => { d }
+            }
+        }
+        if get(wrap(true), false) { get(wrap("a"), "b") } else { get(None, "c") }
+        "#;
+        expect_pass(t, Type::String);
+
+        let t = r"
+        fn pair_ok(x, e) {
+            if x == x { Ok(x) } else { Err(e) }
+        }
+        pair_ok
+        ";
+        expect_pass_str(t, "fn('a, 'b) -> Result<'a, 'b>");
+
+        // functions using operators of their parameters stay monomorphic
+        let t = r"
+        fn double(x) { x + x }
+        double(2)
+        ";
+        expect_pass(t, Type::Int);
+    }
+}
@@ -1,7 +1,16 @@
 pub mod blk;
+pub mod check_array;
+pub mod check_enum;
 pub mod check_fn_call;
 pub mod check_fn_decl;
 pub mod check_let;
 pub mod check_loop;
+pub mod check_match;
+pub mod check_method_call;
+pub mod check_overload;
+pub mod check_select;
+pub mod check_struct;
+pub mod check_try_catch;
 pub mod if_else;
+pub mod infer;
 pub mod type_checker;
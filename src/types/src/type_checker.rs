@@ -1,6 +1,8 @@
-use parser::{structs::*, Parser};
+use parser::{named_args::resolve_named_args, structs::*, Parser};
 use std::{collections::HashMap, fmt::Display};
 
+use crate::{check_fn_call::NONE, infer};
+
 use parser::structs::{BlockSeq, Decl, Expr, Type};
 
 #[derive(Debug, PartialEq)]
@@ -40,6 +42,10 @@ impl TypeErrors {
     pub fn is_ok(&self) -> bool {
         self.errs.is_empty()
     }
+
+    pub fn errs(&self) -> &[String] {
+        &self.errs
+    }
 }
 
 impl Display for TypeErrors {
@@ -56,7 +62,7 @@ impl Display for TypeErrors {
 
 impl std::error::Error for TypeErrors {}
 
-type Env = HashMap<String, Type>;
+pub type Env = HashMap<String, Type>;
 
 pub fn new_env_with_syms(syms: Vec<String>) -> Env {
     let mut env: Env = HashMap::new();
@@ -93,6 +99,10 @@ pub struct TypeChecker<'prog> {
     pub(crate) envs: Vec<Env>,
     // stores type of function currently being checked at top (empty if not checking function)
     pub(crate) fn_type_stack: Vec<Type>,
+    // stores type of the elements of the generator currently being checked at top
+    pub(crate) yield_type_stack: Vec<Type>,
+    // stores label of each loop being checked, innermost at top, with the type of the values breaks leave it with so far
+    pub(crate) loop_type_stack: Vec<(Option<String>, Option<Type>)>,
 }
 
 impl<'prog> TypeChecker<'prog> {
@@ -101,6 +111,8 @@ impl<'prog> TypeChecker<'prog> {
             program,
             envs: vec![],
             fn_type_stack: vec![],
+            yield_type_stack: vec![],
+            loop_type_stack: vec![],
         }
     }
 
@@ -110,6 +122,11 @@ impl<'prog> TypeChecker<'prog> {
             return Ok(Type::BuiltInFn);
         }
 
+        // The type of None is only known once it is used with an Option of a known type
+        if ident == NONE {
+            return Ok(Type::Option(Box::new(Type::Unknown)));
+        }
+
         for env in self.envs.iter().rev() {
             let ty = env.get(ident);
             if let Some(ty) = ty {
@@ -216,14 +233,38 @@ impl<'prog> TypeChecker<'prog> {
         }
     }
 
-    // Add, Sub, Mul, Div where allowed are (int, int) and (float, float)
+    /// The error for applying op to the types. Ints and floats are never converted implicitly,
+    /// so mixing them in arithmetic or a comparison points at the conversion builtins.
+    pub(crate) fn binop_err(op: &BinOpType, l: impl Display, r: impl Display) -> String {
+        let (l, r) = (l.to_string(), r.to_string());
+        let mut e = format!("Can't apply '{}' to types '{}' and '{}'", op, l, r);
+
+        let (int, float) = (Type::Int.to_string(), Type::Float.to_string());
+        if (l == int && r == float) || (l == float && r == int) {
+            e.push_str(", convert one side with int_to_float or float_to_int");
+        }
+
+        e
+    }
+
+    /// Whether the type is a function, or an option, result or array that can hold one.
+    fn holds_fn(ty: &Type) -> bool {
+        match ty {
+            Type::UserFn(_) | Type::BuiltInFn => true,
+            Type::Option(ty) | Type::Array(ty) => TypeChecker::holds_fn(ty),
+            Type::Result(ok, err) => TypeChecker::holds_fn(ok) || TypeChecker::holds_fn(err),
+            _ => false,
+        }
+    }
+
+    // Add, Sub, Mul, Div where allowed are (int, int) and (float, float), Mod only (int, int)
     fn check_math_ops(
         op: &BinOpType,
         left_ty: &CheckResult,
         right_ty: &CheckResult,
     ) -> Result<CheckResult, TypeErrors> {
         match op {
-            BinOpType::Add | BinOpType::Sub | BinOpType::Div | BinOpType::Mul => {
+            BinOpType::Add | BinOpType::Sub | BinOpType::Div | BinOpType::Mul | BinOpType::Mod => {
                 match (&left_ty.ty, &right_ty.ty) {
                     (Type::Int, Type::Int) => {
                         let res = CheckResult {
@@ -234,7 +275,7 @@ impl<'prog> TypeChecker<'prog> {
 
                         Ok(res)
                     }
-                    (Type::Float, Type::Float) => {
+                    (Type::Float, Type::Float) if !matches!(op, BinOpType::Mod) => {
                         let res = CheckResult {
                             ty: Type::Float,
                             must_break: left_ty.must_break || right_ty.must_break,
@@ -244,10 +285,7 @@ impl<'prog> TypeChecker<'prog> {
                         Ok(res)
                     }
                     _ => {
-                        let e = format!(
-                            "Can't apply '{}' to types '{}' and '{}'",
-                            op, left_ty.ty, right_ty.ty
-                        );
+                        let e = TypeChecker::binop_err(op, &left_ty.ty, &right_ty.ty);
                         Err(TypeErrors::new_err(&e))
                     }
                 }
@@ -283,22 +321,25 @@ impl<'prog> TypeChecker<'prog> {
         let l_type = l_type?;
         let r_type = r_type?;
 
-        let err = format!(
-            "Can't apply '{}' to types '{}' and '{}'",
-            op, l_type.ty, r_type.ty
-        );
+        if let Some(res) = self.check_overloaded_binop(op, &l_type, &r_type) {
+            return res;
+        }
+
+        let err_msg = TypeChecker::binop_err(op, &l_type.ty, &r_type.ty);
 
-        let err: Result<_, TypeErrors> = Err(TypeErrors::new_err(&err));
+        let err: Result<_, TypeErrors> = Err(TypeErrors::new_err(&err_msg));
 
         match op {
-            BinOpType::Add | BinOpType::Sub | BinOpType::Div | BinOpType::Mul => {
+            BinOpType::Add | BinOpType::Sub | BinOpType::Div | BinOpType::Mul | BinOpType::Mod => {
                 TypeChecker::check_math_ops(op, &l_type, &r_type)
             }
-            // (num, num) => bool
-            BinOpType::Gt | BinOpType::Lt => {
+            // (num, num) => bool or (str, str) => bool
+            BinOpType::Gt | BinOpType::Lt | BinOpType::Ge | BinOpType::Le => {
                 if matches!(
                     (l_type.ty, r_type.ty),
-                    (Type::Int, Type::Int) | (Type::Float, Type::Float)
+                    (Type::Int, Type::Int)
+                        | (Type::Float, Type::Float)
+                        | (Type::String, Type::String)
                 ) {
                     // Ok(Type::Bool)
                     let res = CheckResult {
@@ -327,9 +368,14 @@ impl<'prog> TypeChecker<'prog> {
                     err
                 }
             }
-            // (t, t) => bool
+            // (t, t) => bool, for any t the VM can compare, i.e. not holding functions
             BinOpType::LogicalEq => {
-                if l_type.ty.eq(&r_type.ty) {
+                if TypeChecker::holds_fn(&l_type.ty) || TypeChecker::holds_fn(&r_type.ty) {
+                    let e = format!("{}, functions can't be compared", err_msg);
+                    return Err(TypeErrors::new_err(&e));
+                }
+
+                if l_type.ty.matches(&r_type.ty) {
                     let res = CheckResult {
                         ty: Type::Bool,
                         must_break: l_type.must_break || r_type.must_break,
@@ -373,6 +419,16 @@ impl<'prog> TypeChecker<'prog> {
                 // self.ty_env.borrow().get(ident)?
                 let sym_ty = self.get_type(ident)?;
 
+                if let Type::StructDef(_) = sym_ty {
+                    let e = format!("Struct '{}' can't be used as a value", ident);
+                    return Err(TypeErrors::new_err(&e));
+                }
+
+                if let Type::EnumDef(_) = sym_ty {
+                    let e = format!("Enum '{}' can't be used as a value", ident);
+                    return Err(TypeErrors::new_err(&e));
+                }
+
                 CheckResult {
                     ty: sym_ty,
                     must_break: false,
@@ -388,20 +444,81 @@ impl<'prog> TypeChecker<'prog> {
             Expr::BlockExpr(blk) => return self.check_block(blk, vec![]),
             Expr::IfElseExpr(if_else) => return self.check_if_else(if_else),
             Expr::FnCallExpr(fn_call) => return self.check_fn_call(fn_call),
+            Expr::MethodCallExpr(method_call) => return self.check_method_call(method_call),
+            Expr::SelectExpr(select) => return self.check_select(select),
+            Expr::MatchExpr(match_data) => return self.check_match(match_data),
+            Expr::LoopExpr(lp) => return self.check_loop(lp),
+            Expr::TryExpr(expr) => return self.check_try(expr),
+            Expr::TryCatchExpr(try_catch) => return self.check_try_catch(try_catch),
+            Expr::StructExpr(struct_expr) => return self.check_struct_expr(struct_expr),
+            Expr::FieldExpr(expr, field) => return self.check_field_expr(expr, field),
+            Expr::ArrayExpr(elems) => return self.check_array_expr(elems),
+            Expr::ComprehensionExpr(comp) => return self.check_comprehension(comp),
+            Expr::IndexExpr(expr, idx) => return self.check_index_expr(expr, idx),
+            Expr::SliceExpr(expr, start, end) => return self.check_slice_expr(expr, [start, end]),
             Expr::SpawnExpr(fn_call) => {
-                self.check_fn_call(fn_call)?;
+                let res = self.check_fn_call(fn_call)?;
                 CheckResult {
-                    ty: Type::ThreadId,
+                    ty: Type::ThreadId(Box::new(res.ty)),
                     must_break: false,
                     must_return: false,
                 }
             }
-            // TODO: return join type based on function that was called
-            // Need to track spawn / join calls at compile time
-            Expr::JoinExpr(_) => CheckResult {
-                ty: Type::Unit,
-                must_break: false,
-                must_return: false,
+            Expr::SpawnBlockExpr(blk) => {
+                // the block runs in the new thread, so '?' can't return from the enclosing fn
+                let fn_types = std::mem::take(&mut self.fn_type_stack);
+                let res = self.check_block(blk, vec![]);
+                self.fn_type_stack = fn_types;
+                CheckResult {
+                    ty: Type::ThreadId(Box::new(res?.ty)),
+                    must_break: false,
+                    must_return: false,
+                }
+            }
+            Expr::ScopeExpr(blk) => {
+                // '?' can't return from the enclosing fn before the threads spawned in the block are joined
+                let fn_types = std::mem::take(&mut self.fn_type_stack);
+                let res = self.check_block(blk, vec![]);
+                self.fn_type_stack = fn_types;
+                CheckResult {
+                    ty: res?.ty,
+                    must_break: false,
+                    must_return: false,
+                }
+            }
+            // the lock is released however the block is left, so break, return and '?' may leave it
+            Expr::LockExpr(lock) => {
+                let lock_ty = self.check_expr(&lock.lock)?.ty;
+                match (&lock_ty, lock.shared) {
+                    (Type::Semaphore, false) | (Type::RwLock, _) => (),
+                    (_, false) => {
+                        let e = format!(
+                            "Expected a mutex or rwlock to lock but '{}' has type '{}'",
+                            lock.lock, lock_ty
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                    (_, true) => {
+                        let e = format!(
+                            "Expected an rwlock to read_lock but '{}' has type '{}'",
+                            lock.lock, lock_ty
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+                return self.check_block(&lock.blk, vec![]);
+            }
+            // join gives the value the thread finishes with
+            Expr::JoinExpr(tid) => match self.get_type(tid)? {
+                Type::ThreadId(ty) => CheckResult {
+                    ty: *ty,
+                    must_break: false,
+                    must_return: false,
+                },
+                ty => {
+                    let e = format!("Expected a thread to join but '{}' has type '{}'", tid, ty);
+                    return Err(TypeErrors::new_err(&e));
+                }
             },
         };
 
@@ -417,6 +534,7 @@ impl<'prog> TypeChecker<'prog> {
         // dbg!("Type checking decl:", decl);
         match decl {
             Decl::LetStmt(stmt) => self.check_let(stmt),
+            Decl::LetDestructureStmt(stmt) => self.check_let_destructure(stmt),
             // Type check the expr and return any errors
             Decl::ExprStmt(expr) => self.check_expr(expr),
             // Check if sym is declared already. Then check expr matches type at decl
@@ -424,7 +542,7 @@ impl<'prog> TypeChecker<'prog> {
                 let sym_ty = self.get_type_if_init(&stmt.ident.to_owned())?;
                 let exp_ty = self.check_expr(&stmt.expr)?;
 
-                if !sym_ty.eq(&exp_ty.ty) {
+                if !sym_ty.matches(&exp_ty.ty) {
                     let e = format!(
                         "'{}' declared with type {} but assigned type {}",
                         stmt.ident, sym_ty, exp_ty.ty
@@ -440,17 +558,15 @@ impl<'prog> TypeChecker<'prog> {
 
                 Ok(res)
             }
+            Decl::FieldAssignStmt(stmt) => self.check_field_assign(stmt),
             Decl::IfOnlyStmt(if_else) => self.check_if_else(if_else),
             Decl::LoopStmt(lp) => self.check_loop(lp),
-            Decl::BreakStmt => {
-                // must_break base case
-                Ok(CheckResult {
-                    ty: Type::Unit,
-                    must_break: true,
-                    must_return: false,
-                })
-            }
+            Decl::ForStmt(lp) => self.check_for(lp),
+            Decl::BreakStmt(brk) => self.check_break(brk),
             Decl::FnDeclStmt(fn_decl) => self.check_fn_decl(fn_decl),
+            Decl::StructDeclStmt(struct_decl) => self.check_struct_decl(struct_decl),
+            Decl::EnumDeclStmt(enum_decl) => self.check_enum_decl(enum_decl),
+            Decl::ImplStmt(impl_data) => self.check_impl(impl_data),
             // TODO: check nested returns with fn stack
             Decl::ReturnStmt(ret_expr) => {
                 // dbg!("fn_stack at return:", &self.fn_type_stack);
@@ -474,7 +590,7 @@ impl<'prog> TypeChecker<'prog> {
                     .fn_type_stack
                     .last()
                     .expect("Should have type in fn_stack");
-                if !res.ty.eq(fn_ty) {
+                if !fn_ty.matches(&res.ty) {
                     let e = format!(
                         "Expected function return type '{}' but return statement has type '{}'",
                         fn_ty, res.ty
@@ -484,6 +600,25 @@ impl<'prog> TypeChecker<'prog> {
 
                 Ok(res)
             }
+            Decl::YieldValueStmt(expr) => {
+                let mut res = self.check_expr(expr)?;
+
+                // expect because parser makes the fn with the yield a generator
+                let yield_ty = self
+                    .yield_type_stack
+                    .last()
+                    .expect("Should have type in yield_stack");
+                if !yield_ty.matches(&res.ty) {
+                    let e = format!(
+                        "Expected type '{}' for yield but got '{}'",
+                        yield_ty, res.ty
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+
+                res.ty = Type::Unit;
+                Ok(res)
+            }
             Decl::WaitStmt(_) => Ok(CheckResult {
                 ty: Type::Unit,
                 must_break: false,
@@ -505,10 +640,35 @@ impl<'prog> TypeChecker<'prog> {
     }
 
     pub fn type_check(mut self) -> Result<Type, TypeErrors> {
-        let ty = self.check_block(self.program, vec![])?;
+        // Check calls with named args as the calls with the args in order they are compiled to
+        let program = resolve_named_args(self.program).map_err(|e| TypeErrors::new_err(&e))?;
+        // Fill in the types of unannotated parameters first
+        let program = infer::annotate(&program, &self.envs)?;
+        let ty = self.check_block(&program, vec![])?;
         // dbg!(&ty);
         Ok(ty.ty)
     }
+
+    /// Type check the program in the scope of the top-level bindings of the programs checked before it,
+    /// as for the inputs of a REPL. If the program type checks, its own top-level bindings are added to envs.
+    pub fn type_check_in(mut self, envs: &mut Vec<Env>) -> Result<Type, TypeErrors> {
+        let program = resolve_named_args(self.program).map_err(|e| TypeErrors::new_err(&e))?;
+        let program = infer::annotate(&program, envs)?;
+        self.envs = std::mem::take(envs);
+        self.envs.push(new_env_with_syms(program.symbols.clone()));
+
+        let res = self.check_block_body(&program, vec![]);
+
+        let env = self.envs.pop().expect("Env of the program was pushed");
+        *envs = std::mem::take(&mut self.envs);
+
+        let ty = res?.ty;
+        if !env.is_empty() {
+            envs.push(env);
+        }
+
+        Ok(ty)
+    }
 }
 
 impl Default for TypeErrors {
@@ -553,8 +713,8 @@ pub fn expect_err(inp: &str, exp_err: &str, contains: bool) {
 
 #[cfg(test)]
 mod tests {
-    use super::{expect_err, expect_pass};
-    use parser::structs::Type;
+    use super::{expect_err, expect_pass, TypeChecker};
+    use parser::{structs::Type, Parser};
 
     #[test]
     fn test_type_check_basic() {
@@ -635,6 +795,13 @@ mod tests {
             true,
         );
         expect_err("let x : bool = true +2;", "apply", true);
+
+        expect_pass("7 % 3", Type::Int);
+        expect_err(
+            "7.0 % 3.0",
+            "[TypeError]: Can't apply '%' to types 'float' and 'float'",
+            false,
+        );
     }
 
     #[test]
@@ -682,6 +849,28 @@ mod tests {
         expect_pass("let x = true; true && false || x", Type::Bool);
     }
 
+    #[test]
+    fn test_type_check_mixed_numeric() {
+        // ints and floats are not widened, in arithmetic or comparisons
+        expect_err(
+            "1 + 2.5",
+            "[TypeError]: Can't apply '+' to types 'int' and 'float', convert one side with int_to_float or float_to_int",
+            false,
+        );
+        expect_err(
+            "2.5 < 1",
+            "Can't apply '<' to types 'float' and 'int', convert",
+            true,
+        );
+        expect_err(
+            "1 == 1.0",
+            "Can't apply '==' to types 'int' and 'float', convert",
+            true,
+        );
+        expect_pass("int_to_float(1) + 2.5", Type::Float);
+        expect_pass("1 < float_to_int(2.5)", Type::Bool);
+    }
+
     #[test]
     fn test_type_check_binops_cmp() {
         // ==, >, <
@@ -696,6 +885,20 @@ mod tests {
             true,
         );
 
+        // options and results compare what they hold, functions can't be compared
+        expect_pass("Some(2) == None", Type::Bool);
+        expect_pass(r#"Ok(2) == Err("e")"#, Type::Bool);
+        expect_err(
+            "fn f() {} f == f",
+            "Can't apply '==' to types 'fn()' and 'fn()', functions can't be compared",
+            true,
+        );
+        expect_err(
+            "fn f() {} Some(f) == None",
+            "functions can't be compared",
+            true,
+        );
+
         // >
         expect_pass("2 > 3", Type::Bool);
         expect_pass("2.5 > 3.2", Type::Bool);
@@ -714,6 +917,24 @@ mod tests {
             true,
         );
 
+        // <=, >=
+        expect_pass("2 <= 3", Type::Bool);
+        expect_pass("2.5 >= 3.2", Type::Bool);
+        expect_err(
+            "2 >= 3.0",
+            "Can't apply '>=' to types 'int' and 'float', convert one side with int_to_float or float_to_int",
+            true,
+        );
+
+        // strings are ordered lexicographically
+        expect_pass(r#""apple" < "banana""#, Type::Bool);
+        expect_pass(r#""b" >= "a""#, Type::Bool);
+        expect_err(
+            r#""a" > 2"#,
+            "Can't apply '>' to types 'str' and 'int'",
+            true,
+        );
+
         // mix
         expect_pass("false == (3 > 5)", Type::Bool);
         expect_err(
@@ -758,4 +979,128 @@ mod tests {
         let t = r"let t = sem_create(); t";
         expect_pass(t, Type::Semaphore);
     }
+
+    #[test]
+    fn type_check_spawn_join() {
+        let t = r"
+        fn f() -> int { 2 }
+        let h = spawn f();
+        join h
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        let x = 2;
+        let h : tid<bool> = spawn { let y = x * 2; y > 3 };
+        join(h)
+        ";
+        expect_pass(t, Type::Bool);
+
+        let t = r"let h = spawn { 2 }; h";
+        expect_pass(t, Type::ThreadId(Box::new(Type::Int)));
+
+        let t = r"let h : tid<int> = spawn { true };";
+        expect_err(
+            t,
+            "'h' has declared type tid<int> but assigned type tid<bool>",
+            true,
+        );
+
+        let t = r"let x = 2; join x";
+        expect_err(t, "Expected a thread to join but 'x' has type 'int'", true);
+
+        // the block runs in the new thread, so '?' can't return from the enclosing fn
+        let t = r"
+        fn f(x: Option<int>) -> Option<int> {
+            let h = spawn { x? };
+            let r = join h;
+            Some(r)
+        }
+        ";
+        expect_err(t, "'?' can only be used inside a function", true);
+    }
+
+    #[test]
+    fn type_check_scope() {
+        let t = r"
+        let h = scope {
+            spawn { 1 };
+            spawn { true }
+        };
+        join h
+        ";
+        expect_pass(t, Type::Bool);
+
+        let t = r"scope { 2; }";
+        expect_pass(t, Type::Unit);
+
+        let t = r"
+        fn f(x: Option<int>) -> Option<int> {
+            scope { x? };
+            None
+        }
+        ";
+        expect_err(t, "'?' can only be used inside a function", true);
+    }
+
+    #[test]
+    fn type_check_lock() {
+        let t = r"
+        let m = mutex();
+        let rw = rwlock();
+        let x = lock(m) { 2 };
+        lock(rw) { x = x + 1; }
+        read_lock(rw) { x }
+        ";
+        expect_pass(t, Type::Int);
+
+        // the lock is released however the block is left
+        let t = r"
+        fn f(x: Option<int>, rw: rwlock) -> Option<int> {
+            let y = read_lock(rw) { x? };
+            loop {
+                lock(rw) { break; }
+            }
+            Some(y)
+        }
+        f(Some(2), rwlock())
+        ";
+        expect_pass(t, Type::Option(Box::new(Type::Int)));
+
+        let t = r"let x = 2; lock(x) { }";
+        expect_err(
+            t,
+            "Expected a mutex or rwlock to lock but 'x' has type 'int'",
+            true,
+        );
+
+        let t = r"read_lock(mutex()) { }";
+        expect_err(
+            t,
+            "Expected an rwlock to read_lock but 'mutex()' has type 'sem'",
+            true,
+        );
+    }
+
+    #[test]
+    fn type_check_in_envs() {
+        let mut envs = vec![];
+        let check = |inp: &str, envs: &mut Vec<_>| {
+            let prog = Parser::new_from_string(inp).parse().expect("Should parse");
+            TypeChecker::new(&prog).type_check_in(envs)
+        };
+
+        assert_eq!(check("let x = 2;", &mut envs), Ok(Type::Unit));
+        assert_eq!(check("x + 1", &mut envs), Ok(Type::Int));
+        assert_eq!(envs.len(), 1);
+
+        // A program that fails to type check adds no bindings
+        assert!(check("let y = 2; let z: bool = y;", &mut envs).is_err());
+        assert!(check("y", &mut envs).is_err());
+        assert_eq!(envs.len(), 1);
+
+        // Shadowing a binding of an earlier program
+        assert_eq!(check("let x = true; x", &mut envs), Ok(Type::Bool));
+        assert_eq!(check("!x", &mut envs), Ok(Type::Bool));
+    }
 }
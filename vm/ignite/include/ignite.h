@@ -0,0 +1,55 @@
+/*
+ * The C interface of the ignite VM, built with the ffi feature:
+ *
+ *     cargo rustc -p ignite --lib --release --features ffi --crate-type cdylib
+ *
+ * A host creates a VM, loads the contents of a .o2 file, runs it and reads its result, then destroys the VM:
+ *
+ *     IgniteVm *vm = ignite_vm_new();
+ *     if (ignite_vm_load(vm, bytes, len) != 0 || ignite_vm_run(vm) != 0) {
+ *         fprintf(stderr, "%s\n", ignite_vm_error(vm));
+ *     } else if (ignite_vm_result(vm) != NULL) {
+ *         printf("%s\n", ignite_vm_result(vm));
+ *     }
+ *     ignite_vm_free(vm);
+ *
+ * Calls return 0 on success, -1 on error, or -2 if the VM panicked, which drops the loaded program.
+ * Strings returned by the VM are owned by it, and valid until the next call on it.
+ * A VM must only be used by one thread at a time.
+ */
+
+#ifndef IGNITE_H
+#define IGNITE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct IgniteVm IgniteVm;
+
+/* Create a VM with no program. */
+IgniteVm *ignite_vm_new(void);
+
+/* Load the contents of a .o2 file, replacing the program. Returns 0 on success, -1 on error, -2 on a panic. */
+int ignite_vm_load(IgniteVm *vm, const uint8_t *bytes, size_t len);
+
+/* Run the loaded program to completion. Returns 0 on success, -1 on error, -2 on a panic. */
+int ignite_vm_run(IgniteVm *vm);
+
+/* The final value of the program that last ran, or NULL if it left none. */
+const char *ignite_vm_result(const IgniteVm *vm);
+
+/* The error of the last call that failed, or NULL if it succeeded. */
+const char *ignite_vm_error(const IgniteVm *vm);
+
+/* Destroy the VM. Does nothing if vm is NULL. */
+void ignite_vm_free(IgniteVm *vm);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,719 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+};
+
+use anyhow::{Error, Result};
+use bytecode::{type_of, ByteCode};
+use compiler::compiler::compile_from_string_with_debug_info;
+use serde_json::{json, Value as Json};
+
+use crate::{
+    debugger::{Debugger, StopReason},
+    load_program, Runtime,
+};
+
+/// How execution resumes once the response to a request has been sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+    /// The client is done configuring, stop on entry or run until a breakpoint.
+    Entry,
+    Continue,
+    Next,
+    StepIn,
+}
+
+/// A session of the Debug Adapter Protocol with a single client, driving a debugger over the program the
+/// client launches.
+///
+/// A .rst source is compiled when it is launched, with the line each statement starts on, so breakpoints
+/// can be set on its lines and the stack trace gives the line of each frame. .o2 files carry no source
+/// positions, so breakpoints are only set on instructions there, and the disassembly is the source that is shown.
+/// Data breakpoints are the debugger's watchpoints.
+pub struct DapSession<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    seq: u64,
+    dbg: Option<Debugger>,
+    /// The path of the launched source, if it is one.
+    source: Option<String>,
+    /// The address of the first instruction of each statement of the launched source and the line it starts on.
+    lines: Vec<(usize, usize)>,
+    /// The addresses of the breakpoints set on lines and on instructions, which the debugger has all of.
+    line_breakpoints: Vec<usize>,
+    instruction_breakpoints: Vec<usize>,
+    stop_on_entry: bool,
+    resume: Option<Resume>,
+    disconnected: bool,
+}
+
+impl<R: BufRead, W: Write> DapSession<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        DapSession {
+            reader,
+            writer,
+            seq: 0,
+            dbg: None,
+            source: None,
+            lines: vec![],
+            line_breakpoints: vec![],
+            instruction_breakpoints: vec![],
+            stop_on_entry: false,
+            resume: None,
+            disconnected: false,
+        }
+    }
+
+    /// Handle requests until the client disconnects or closes the connection.
+    ///
+    /// # Errors
+    ///
+    /// If the connection fails or a message is malformed. Failed requests are reported to the client instead.
+    pub fn run(mut self) -> Result<()> {
+        while let Some(req) = self.read_message()? {
+            let command = req["command"].as_str().unwrap_or_default().to_string();
+            let body = self.handle(&command, &req["arguments"]);
+            self.respond(&req, body)?;
+
+            if command == "initialize" {
+                self.event("initialized", json!({}))?;
+            }
+
+            if let Some(resume) = self.resume.take() {
+                self.resume(resume)?;
+            }
+
+            if self.disconnected {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, command: &str, args: &Json) -> Result<Json> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsDisassembleRequest": true,
+                "supportsDataBreakpoints": true,
+            })),
+            "launch" => {
+                let program = args["program"]
+                    .as_str()
+                    .ok_or_else(|| Error::msg("launch requires a program"))?;
+
+                let rt = if Path::new(program)
+                    .extension()
+                    .is_some_and(|ext| ext == "rst")
+                {
+                    let src = std::fs::read_to_string(program)?;
+                    let (instrs, debug_info) = compile_from_string_with_debug_info(&src, true)?;
+                    self.source = Some(program.to_string());
+                    self.lines = debug_info.lines(&src);
+                    Runtime::new(instrs)
+                } else {
+                    load_program(program.to_string())?
+                };
+                self.dbg = Some(Debugger::new(rt));
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                Ok(json!({}))
+            }
+            "configurationDone" => {
+                self.resume = Some(Resume::Entry);
+                Ok(json!({}))
+            }
+            "setBreakpoints" => {
+                self.line_breakpoints.clear();
+
+                let mut breakpoints = vec![];
+                for bp in args["breakpoints"].as_array().into_iter().flatten() {
+                    let line = bp["line"].as_u64().unwrap_or(0) as usize;
+
+                    // A line without a statement, e.g. a comment, breaks at the next line that has one
+                    let stmt = self
+                        .lines
+                        .iter()
+                        .filter(|(_, stmt_line)| *stmt_line >= line)
+                        .min_by_key(|(pc, stmt_line)| (*stmt_line, *pc));
+
+                    match stmt {
+                        Some(&(pc, line)) => {
+                            self.line_breakpoints.push(pc);
+                            breakpoints.push(json!({ "verified": true, "line": line }));
+                        }
+                        None if self.source.is_none() => breakpoints.push(json!({
+                            "verified": false,
+                            "message": "Compiled .o2 files have no lines, launch the .rst source or set breakpoints in the disassembly",
+                        })),
+                        None => breakpoints.push(json!({
+                            "verified": false,
+                            "message": "No code at or after the line",
+                        })),
+                    }
+                }
+
+                self.set_breakpoints()?;
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setInstructionBreakpoints" => {
+                self.instruction_breakpoints.clear();
+                let len = self.debugger()?.runtime().instrs.len();
+
+                let mut breakpoints = vec![];
+                for bp in args["breakpoints"].as_array().into_iter().flatten() {
+                    let pc = bp["instructionReference"]
+                        .as_str()
+                        .and_then(|pc| pc.parse::<i64>().ok())
+                        .map(|pc| pc + bp["offset"].as_i64().unwrap_or(0));
+
+                    match pc {
+                        Some(pc) if pc >= 0 && (pc as usize) < len => {
+                            self.instruction_breakpoints.push(pc as usize);
+                            breakpoints.push(json!({
+                                "verified": true,
+                                "instructionReference": pc.to_string(),
+                            }));
+                        }
+                        _ => breakpoints.push(json!({
+                            "verified": false,
+                            "message": "No instruction at the address",
+                        })),
+                    }
+                }
+
+                self.set_breakpoints()?;
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "dataBreakpointInfo" => {
+                let name = args["name"].as_str().unwrap_or_default();
+                Ok(json!({
+                    "dataId": name,
+                    "description": format!("Assignments to {}", name),
+                    "accessTypes": ["write"],
+                }))
+            }
+            "setDataBreakpoints" => {
+                let dbg = self.debugger_mut()?;
+                let old: Vec<_> = dbg.watchpoints().collect();
+                for sym in old {
+                    dbg.remove_watchpoint(sym);
+                }
+
+                let mut breakpoints = vec![];
+                for bp in args["breakpoints"].as_array().into_iter().flatten() {
+                    match bp["dataId"].as_str() {
+                        Some(name) => {
+                            dbg.add_watchpoint(name);
+                            breakpoints.push(json!({ "verified": true }));
+                        }
+                        None => breakpoints.push(json!({ "verified": false })),
+                    }
+                }
+
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "threads" => {
+                let dbg = self.debugger()?;
+                let threads: Vec<Json> = std::iter::once(dbg.thread_id())
+                    .chain(dbg.ready_queue())
+                    .chain(dbg.blocked_queue())
+                    .map(|id| json!({ "id": id, "name": format!("thread {}", id) }))
+                    .collect();
+                Ok(json!({ "threads": threads }))
+            }
+            "stackTrace" => {
+                let dbg = self.debugger()?;
+                let thread_id = args["threadId"].as_i64().unwrap_or(dbg.thread_id());
+
+                // Call frames are only tracked by name for the running thread
+                let frames: Vec<(String, usize)> = if thread_id == dbg.thread_id() {
+                    dbg.runtime()
+                        .stack_trace(dbg.pc())
+                        .frames
+                        .into_iter()
+                        .map(|frame| match frame.sym {
+                            Some(sym) => (sym.to_string(), frame.pc),
+                            None => ("<top level>".to_string(), frame.pc),
+                        })
+                        .collect()
+                } else {
+                    let thread = dbg
+                        .runtime()
+                        .find_thread(thread_id)
+                        .ok_or_else(|| Error::msg(format!("No thread {}", thread_id)))?;
+                    vec![(format!("thread {}", thread_id), thread.pc)]
+                };
+
+                let frames: Vec<Json> = frames
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, (name, pc))| {
+                        let mut frame = json!({
+                            "id": id,
+                            "name": name,
+                            "line": line_of(&self.lines, pc).unwrap_or(0),
+                            "column": 0,
+                            "instructionPointerReference": pc.to_string(),
+                        });
+                        if let Some(source) = &self.source {
+                            frame["source"] = json!({ "path": source });
+                        }
+                        frame
+                    })
+                    .collect();
+                let total = frames.len();
+                Ok(json!({ "stackFrames": frames, "totalFrames": total }))
+            }
+            "scopes" => {
+                let dbg = self.debugger()?;
+                let envs = dbg.environments();
+
+                // The global environment holds the builtins, which would drown out the program's names
+                let scopes: Vec<Json> = (0..envs.len().saturating_sub(1))
+                    .map(|depth| {
+                        let name = if depth == 0 {
+                            "Locals".to_string()
+                        } else {
+                            format!("Enclosing {}", depth)
+                        };
+                        json!({
+                            "name": name,
+                            "variablesReference": depth + 1,
+                            "expensive": false,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "scopes": scopes }))
+            }
+            "variables" => {
+                let dbg = self.debugger()?;
+                let depth = args["variablesReference"].as_u64().unwrap_or(0) as usize;
+                let envs = dbg.environments();
+
+                let variables: Vec<Json> = depth
+                    .checked_sub(1)
+                    .and_then(|depth| envs.get(depth))
+                    .into_iter()
+                    .flatten()
+                    .map(|(sym, val)| {
+                        json!({
+                            "name": sym.to_string(),
+                            "value": format!("{:?}", val),
+                            "type": type_of(val).to_string(),
+                            "variablesReference": 0,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "variables": variables }))
+            }
+            "disassemble" => {
+                let dbg = self.debugger()?;
+                let start = args["memoryReference"]
+                    .as_str()
+                    .and_then(|pc| pc.parse::<i64>().ok())
+                    .unwrap_or(0)
+                    + args["instructionOffset"].as_i64().unwrap_or(0);
+                let count = args["instructionCount"].as_i64().unwrap_or(0);
+
+                let instructions: Vec<Json> = (start..start + count)
+                    .map(|pc| {
+                        let instr = usize::try_from(pc)
+                            .ok()
+                            .and_then(|pc| dbg.runtime().instrs.get(pc));
+                        disassemble(pc, instr)
+                    })
+                    .collect();
+                Ok(json!({ "instructions": instructions }))
+            }
+            "continue" => {
+                self.resume = Some(Resume::Continue);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                self.resume = Some(Resume::Next);
+                Ok(json!({}))
+            }
+            "stepIn" => {
+                self.resume = Some(Resume::StepIn);
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                self.disconnected = true;
+                Ok(json!({}))
+            }
+            _ => Err(Error::msg(format!("Unsupported request: {}", command))),
+        }
+    }
+
+    /// Resume execution and report to the client why it stopped.
+    fn resume(&mut self, resume: Resume) -> Result<()> {
+        let stop_on_entry = self.stop_on_entry;
+        let line_breakpoints = self.line_breakpoints.clone();
+        let dbg = self.debugger_mut()?;
+
+        let stop = match resume {
+            Resume::Entry if stop_on_entry => {
+                let thread_id = dbg.thread_id();
+                return self.event(
+                    "stopped",
+                    json!({ "reason": "entry", "threadId": thread_id, "allThreadsStopped": true }),
+                );
+            }
+            Resume::Entry | Resume::Continue => dbg.cont(),
+            Resume::Next => dbg.next(),
+            Resume::StepIn => dbg.step(),
+        };
+
+        let (reason, description) = match stop {
+            Ok(StopReason::Step) => ("step", None),
+            Ok(StopReason::Breakpoint(pc)) if line_breakpoints.contains(&pc) => {
+                ("breakpoint", None)
+            }
+            Ok(StopReason::Breakpoint(_)) => ("instruction breakpoint", None),
+            Ok(StopReason::Watchpoint(event)) => (
+                "data breakpoint",
+                Some(format!(
+                    "{} changed from {:?} to {:?} by thread {}",
+                    event.sym, event.old, event.new, event.thread_id
+                )),
+            ),
+            Ok(StopReason::Done) => {
+                let result = dbg.operand_stack().last().map(|val| format!("{}\n", val));
+                if let Some(result) = result {
+                    self.event("output", json!({ "category": "stdout", "output": result }))?;
+                }
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                return self.event("terminated", json!({}));
+            }
+            Err(err) => {
+                let output = format!("[RuntimeError]: {:?}\n", err);
+                self.event("output", json!({ "category": "stderr", "output": output }))?;
+                self.event("exited", json!({ "exitCode": 1 }))?;
+                return self.event("terminated", json!({}));
+            }
+        };
+
+        let thread_id = self.debugger()?.thread_id();
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": thread_id,
+                "allThreadsStopped": true,
+            }),
+        )
+    }
+
+    /// Give the debugger the breakpoints set on lines and on instructions.
+    fn set_breakpoints(&mut self) -> Result<()> {
+        let pcs: Vec<usize> = self
+            .line_breakpoints
+            .iter()
+            .chain(self.instruction_breakpoints.iter())
+            .copied()
+            .collect();
+
+        let dbg = self.debugger_mut()?;
+        let old: Vec<usize> = dbg.breakpoints().collect();
+        for pc in old {
+            dbg.remove_breakpoint(pc);
+        }
+        for pc in pcs {
+            dbg.add_breakpoint(pc);
+        }
+
+        Ok(())
+    }
+
+    fn debugger(&self) -> Result<&Debugger> {
+        self.dbg
+            .as_ref()
+            .ok_or_else(|| Error::msg("No program has been launched"))
+    }
+
+    fn debugger_mut(&mut self) -> Result<&mut Debugger> {
+        self.dbg
+            .as_mut()
+            .ok_or_else(|| Error::msg("No program has been launched"))
+    }
+}
+
+/// Messages.
+impl<R: BufRead, W: Write> DapSession<R, W> {
+    /// Read the next message, or `None` if the client closed the connection.
+    fn read_message(&mut self) -> Result<Option<Json>> {
+        let mut len = None;
+
+        loop {
+            let mut header = String::new();
+            if self.reader.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(val) = header.strip_prefix("Content-Length:") {
+                len = Some(val.trim().parse::<usize>()?);
+            }
+        }
+
+        let len = len.ok_or_else(|| Error::msg("Message without a Content-Length header"))?;
+        let mut content = vec![0; len];
+        self.reader.read_exact(&mut content)?;
+
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    fn send(&mut self, mut msg: Json) -> Result<()> {
+        self.seq += 1;
+        msg["seq"] = json!(self.seq);
+
+        let content = msg.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    fn respond(&mut self, req: &Json, body: Result<Json>) -> Result<()> {
+        let mut res = json!({
+            "type": "response",
+            "request_seq": req["seq"],
+            "command": req["command"],
+        });
+
+        match body {
+            Ok(body) => {
+                res["success"] = json!(true);
+                res["body"] = body;
+            }
+            Err(err) => {
+                res["success"] = json!(false);
+                res["message"] = json!(err.to_string());
+            }
+        }
+
+        self.send(res)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+/// The line of the statement the instruction at the address belongs to, the innermost one starting before it.
+fn line_of(lines: &[(usize, usize)], pc: usize) -> Option<usize> {
+    lines
+        .iter()
+        .filter(|(start, _)| *start <= pc)
+        .max_by_key(|(start, _)| *start)
+        .map(|(_, line)| *line)
+}
+
+fn disassemble(pc: i64, instr: Option<&ByteCode>) -> Json {
+    match instr {
+        Some(instr) => json!({ "address": pc.to_string(), "instruction": format!("{:?}", instr) }),
+        None => json!({
+            "address": pc.to_string(),
+            "instruction": "<out of bounds>",
+            "presentationHint": "invalid",
+        }),
+    }
+}
+
+/// Serve a single DAP client on the port of localhost until it disconnects.
+/// The protocol runs over TCP rather than stdio so that the output of the program does not interleave
+/// with the messages.
+///
+/// # Errors
+///
+/// If the port cannot be listened on or the connection fails.
+pub fn ignite_dap(port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Listening for a DAP client on 127.0.0.1:{}", port);
+
+    let (stream, _) = listener.accept()?;
+    let reader = BufReader::new(stream.try_clone()?);
+    DapSession::new(reader, stream).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn request(seq: u64, command: &str, args: Json) -> String {
+        let content = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": args,
+        })
+        .to_string();
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    fn messages(out: &[u8]) -> Vec<Json> {
+        let mut reader = Cursor::new(out);
+        let mut session = DapSession::new(&mut reader, vec![]);
+        let mut msgs = vec![];
+        while let Some(msg) = session.read_message().unwrap() {
+            msgs.push(msg);
+        }
+        msgs
+    }
+
+    #[test]
+    fn test_dap_session() -> Result<()> {
+        // let x = 1; x = 2; x
+        let instrs = vec![
+            ByteCode::enterscope(vec!["x"]),
+            ByteCode::ldc(1),
+            ByteCode::assign("x"),
+            ByteCode::ldc(2),
+            ByteCode::assign("x"),
+            ByteCode::ld("x"),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
+        let program = std::env::temp_dir().join(format!("dap_{}.o2", rand::random::<u64>()));
+        let mut file = std::fs::File::create(&program)?;
+        bytecode::write_bytecode(&instrs, &mut file)?;
+
+        let input = [
+            request(1, "initialize", json!({})),
+            request(2, "launch", json!({ "program": program, "stopOnEntry": true })),
+            request(
+                3,
+                "setInstructionBreakpoints",
+                json!({ "breakpoints": [{ "instructionReference": "3" }, { "instructionReference": "99" }] }),
+            ),
+            request(4, "configurationDone", json!({})),
+            request(5, "continue", json!({ "threadId": 1 })),
+            request(6, "variables", json!({ "variablesReference": 1 })),
+            request(7, "setDataBreakpoints", json!({ "breakpoints": [{ "dataId": "x" }] })),
+            request(8, "continue", json!({ "threadId": 1 })),
+            request(9, "continue", json!({ "threadId": 1 })),
+            request(10, "disconnect", json!({})),
+        ]
+        .concat();
+
+        let mut out = vec![];
+        DapSession::new(Cursor::new(input.into_bytes()), &mut out).run()?;
+        std::fs::remove_file(program)?;
+
+        let msgs = messages(&out);
+        let kinds: Vec<String> = msgs
+            .iter()
+            .map(|msg| match msg["type"].as_str() {
+                Some("event") => format!("event {}", msg["event"].as_str().unwrap()),
+                _ => msg["command"].as_str().unwrap().to_string(),
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                "initialize",
+                "event initialized",
+                "launch",
+                "setInstructionBreakpoints",
+                "configurationDone",
+                "event stopped",
+                "continue",
+                "event stopped",
+                "variables",
+                "setDataBreakpoints",
+                "continue",
+                "event stopped",
+                "continue",
+                "event output",
+                "event exited",
+                "event terminated",
+                "disconnect",
+            ]
+        );
+
+        assert!(msgs
+            .iter()
+            .all(|msg| msg["type"] == "event" || msg["success"] == true));
+
+        let verified: Vec<&Json> = msgs[3]["body"]["breakpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bp| &bp["verified"])
+            .collect();
+        assert_eq!(verified, vec![true, false]);
+
+        assert_eq!(msgs[5]["body"]["reason"], "entry");
+        assert_eq!(msgs[7]["body"]["reason"], "instruction breakpoint");
+        assert_eq!(msgs[8]["body"]["variables"][0]["name"], "x");
+        assert_eq!(msgs[8]["body"]["variables"][0]["value"], "1");
+        assert_eq!(msgs[11]["body"]["reason"], "data breakpoint");
+        assert_eq!(
+            msgs[11]["body"]["description"],
+            "x changed from 1 to 2 by thread 1"
+        );
+        assert_eq!(msgs[13]["body"]["output"], "2\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dap_line_breakpoints() -> Result<()> {
+        let src = "let x = 1;\n\n// y\nlet y = x + 1;\ny\n";
+        let program = std::env::temp_dir().join(format!("dap_{}.rst", rand::random::<u64>()));
+        std::fs::write(&program, src)?;
+
+        let input = [
+            request(1, "initialize", json!({})),
+            request(2, "launch", json!({ "program": program })),
+            request(
+                3,
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "line": 2 }, { "line": 9 }] }),
+            ),
+            request(4, "configurationDone", json!({})),
+            request(5, "stackTrace", json!({ "threadId": 1 })),
+            request(6, "disconnect", json!({})),
+        ]
+        .concat();
+
+        let mut out = vec![];
+        DapSession::new(Cursor::new(input.into_bytes()), &mut out).run()?;
+        std::fs::remove_file(&program)?;
+
+        let msgs = messages(&out);
+        assert!(msgs
+            .iter()
+            .all(|msg| msg["type"] == "event" || msg["success"] == true));
+
+        // The blank line breaks at the next line with a statement, and there is none after the last one
+        let breakpoints = &msgs[3]["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["line"], 4);
+        assert_eq!(breakpoints[1]["verified"], false);
+
+        assert_eq!(msgs[5]["body"]["reason"], "breakpoint");
+        let frame = &msgs[6]["body"]["stackFrames"][0];
+        assert_eq!(frame["line"], 4);
+        assert_eq!(frame["source"]["path"], program.to_str().unwrap());
+
+        Ok(())
+    }
+}
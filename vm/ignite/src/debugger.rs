@@ -0,0 +1,737 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use bytecode::{ByteCode, FrameType, Symbol, ThreadID, Value};
+#[cfg(feature = "repl")]
+use rustyline::DefaultEditor;
+
+use crate::{step, Runtime};
+
+#[cfg(feature = "repl")]
+const DEBUGGER_HELP: &str = "\
+Commands:
+    s, step           execute one instruction, stepping into calls
+    n, next           execute one instruction, stepping over calls
+    c, continue       run until a breakpoint is reached or the program is done
+    b, break <pc>     set a breakpoint at the pc
+    d, delete <pc>    remove the breakpoint at the pc
+    breakpoints       list the breakpoints
+    watch <name>      stop whenever a variable with the name is assigned to, by any thread
+    unwatch <name>    remove the watchpoint on the name
+    watchpoints       list the watched names
+    save <file>       save a snapshot of the program, which can be resumed with ignite resume <file>
+    w, where          show the current thread, pc and instruction
+    bt, backtrace     show the call frames of the current thread
+    stack             show the operand stack of the current thread
+    env               show the environments of the current thread, excluding the global environment
+    threads           show the ready and blocked queues
+    inspect [thread]  show the environment of the thread, the current one by default, with the frames
+                      captured by closures and reference counts of each frame
+    h, help           show this message
+    q, quit           exit the debugger";
+
+/// Why the debugger stopped executing the program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// The requested instructions were executed.
+    Step,
+    /// The current thread reached the breakpoint at the given PC.
+    Breakpoint(usize),
+    /// An instruction assigned to a watched name.
+    Watchpoint(WatchEvent),
+    /// The program is done.
+    Done,
+}
+
+/// An assignment to a watched name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchEvent {
+    pub sym: Symbol,
+    /// The thread that executed the assignment.
+    pub thread_id: ThreadID,
+    /// The PC of the assignment.
+    pub pc: usize,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// A debugger over the runtime, executing the program under the control of the user.
+/// Execution stops at breakpoints, which are set by PC, and at watchpoints, which are set by name.
+/// The state of the runtime can be inspected whenever it is stopped.
+pub struct Debugger {
+    rt: Runtime,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<Symbol>,
+}
+
+impl Debugger {
+    pub fn new(rt: Runtime) -> Self {
+        Debugger {
+            rt,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn runtime(&self) -> &Runtime {
+        &self.rt
+    }
+}
+
+/// Breakpoints.
+impl Debugger {
+    /// Set a breakpoint at the given PC, returning false if there already is one.
+    pub fn add_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.insert(pc)
+    }
+
+    /// Remove the breakpoint at the given PC, returning false if there is none.
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    /// The PCs of the breakpoints, in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        !self.rt.is_done() && self.breakpoints.contains(&self.rt.current_thread.pc)
+    }
+}
+
+/// Watchpoints.
+impl Debugger {
+    /// Watch the name, stopping whenever a variable with the name is assigned to in any environment
+    /// by any thread. Returns false if the name is already watched.
+    pub fn add_watchpoint(&mut self, sym: impl Into<Symbol>) -> bool {
+        self.watchpoints.insert(sym.into())
+    }
+
+    /// Stop watching the name, returning false if it is not watched.
+    pub fn remove_watchpoint(&mut self, sym: impl Into<Symbol>) -> bool {
+        self.watchpoints.remove(&sym.into())
+    }
+
+    /// The watched names.
+    pub fn watchpoints(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.watchpoints.iter().copied()
+    }
+
+    /// The watched name the instruction assigns to if executed by the current thread, with the current
+    /// value of the variable.
+    fn watched_write(&self, instr: &ByteCode) -> Option<(Symbol, Value)> {
+        let env = self.rt.current_thread.env.upgrade()?;
+
+        match instr {
+            ByteCode::ASSIGN(sym) if self.watchpoints.contains(sym) => {
+                let val = env.borrow().get(*sym).ok()?;
+                Some((*sym, val))
+            }
+            // The register backend assigns to slots directly, without an ASSIGNSLOT
+            ByteCode::ASSIGNSLOT(depth, idx)
+            | ByteCode::MOVR((depth, idx), _)
+            | ByteCode::BINOPR(Some((depth, idx)), ..)
+                if !self.watchpoints.is_empty() =>
+            {
+                let mut frame = env;
+                for _ in 0..*depth {
+                    let parent = frame.borrow().parent.as_ref()?.upgrade()?;
+                    frame = parent;
+                }
+
+                let frame = frame.borrow();
+                let sym = *frame.syms.get(*idx)?;
+                if !self.watchpoints.contains(&sym) {
+                    return None;
+                }
+
+                Some((sym, frame.slots.get(*idx)?.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Execution.
+impl Debugger {
+    /// Execute a single instruction, stepping into calls.
+    ///
+    /// # Errors
+    ///
+    /// If an error occurs during execution.
+    pub fn step(&mut self) -> Result<StopReason> {
+        let instr_count = self.rt.instr_count;
+        let mut write = None;
+
+        // The scheduler and the garbage collector may run without executing an instruction
+        while !self.rt.is_done() && self.rt.instr_count == instr_count {
+            // The scheduler may switch threads before executing, so check the instruction of every tick.
+            // A tick that executes an instruction executes the one the current thread is at beforehand.
+            write = self.current_instr().and_then(|instr| {
+                let (sym, old) = self.watched_write(instr)?;
+                Some((instr.clone(), sym, old, self.thread_id(), self.pc()))
+            });
+
+            step(&mut self.rt)?;
+        }
+
+        if let Some((instr, sym, old, thread_id, pc)) = write {
+            if let Some((_, new)) = self.watched_write(&instr) {
+                return Ok(StopReason::Watchpoint(WatchEvent {
+                    sym,
+                    thread_id,
+                    pc,
+                    old,
+                    new,
+                }));
+            }
+        }
+
+        if self.rt.is_done() {
+            return Ok(StopReason::Done);
+        }
+
+        Ok(StopReason::Step)
+    }
+
+    /// Execute instructions until the current thread is back at the same call depth, stepping over calls.
+    /// Stops early if a breakpoint is reached or a watched name is assigned to.
+    ///
+    /// # Errors
+    ///
+    /// If an error occurs during execution.
+    // Named after the debugger command, it is not an iterator
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<StopReason> {
+        let thread_id = self.rt.current_thread.thread_id;
+        let call_depth = self.call_depth();
+
+        loop {
+            match self.step()? {
+                StopReason::Step => (),
+                stop => return Ok(stop),
+            }
+
+            if self.at_breakpoint() {
+                return Ok(StopReason::Breakpoint(self.rt.current_thread.pc));
+            }
+
+            if self.rt.current_thread.thread_id == thread_id && self.call_depth() <= call_depth {
+                return Ok(StopReason::Step);
+            }
+        }
+    }
+
+    /// Execute instructions until a breakpoint is reached, a watched name is assigned to or the program is done.
+    ///
+    /// # Errors
+    ///
+    /// If an error occurs during execution.
+    pub fn cont(&mut self) -> Result<StopReason> {
+        loop {
+            match self.step()? {
+                StopReason::Step => (),
+                stop => return Ok(stop),
+            }
+
+            if self.at_breakpoint() {
+                return Ok(StopReason::Breakpoint(self.rt.current_thread.pc));
+            }
+        }
+    }
+}
+
+/// Inspection.
+impl Debugger {
+    /// The thread ID of the current thread.
+    pub fn thread_id(&self) -> ThreadID {
+        self.rt.current_thread.thread_id
+    }
+
+    /// The PC of the next instruction of the current thread.
+    pub fn pc(&self) -> usize {
+        self.rt.current_thread.pc
+    }
+
+    /// The next instruction of the current thread, if the PC is in bounds.
+    pub fn current_instr(&self) -> Option<&ByteCode> {
+        self.rt.instrs.get(self.rt.current_thread.pc)
+    }
+
+    /// The operand stack of the current thread, with the top of the stack last.
+    pub fn operand_stack(&self) -> &[Value] {
+        &self.rt.current_thread.operand_stack
+    }
+
+    /// The number of call frames on the runtime stack of the current thread.
+    pub fn call_depth(&self) -> usize {
+        self.rt
+            .current_thread
+            .runtime_stack
+            .iter()
+            .filter(|frame| frame.frame_type == FrameType::CallFrame)
+            .count()
+    }
+
+    /// The bindings of each frame of the environment of the current thread, innermost frame first.
+    pub fn environments(&self) -> Vec<Vec<(Symbol, Value)>> {
+        let mut envs = vec![];
+        let mut env = self.rt.current_thread.env.upgrade();
+
+        while let Some(frame) = env {
+            let frame = frame.borrow();
+            envs.push(frame.bindings());
+            env = frame.parent.as_ref().and_then(|parent| parent.upgrade());
+        }
+
+        envs
+    }
+
+    /// The IDs of the threads in the ready queue, in scheduling order.
+    pub fn ready_queue(&self) -> Vec<ThreadID> {
+        self.rt.ready_queue.iter().map(|t| t.thread_id).collect()
+    }
+
+    /// The IDs of the threads in the blocked queue.
+    pub fn blocked_queue(&self) -> Vec<ThreadID> {
+        self.rt
+            .blocked_queue
+            .iter()
+            .map(|(t, _)| t.thread_id)
+            .collect()
+    }
+}
+
+/// Run the program in the interactive debugger, reading commands from the terminal until the user quits.
+///
+/// # Errors
+///
+/// If the terminal cannot be read from. Runtime errors of the program are printed instead.
+#[cfg(feature = "repl")]
+pub fn ignite_debugger(rt: Runtime) -> Result<()> {
+    let mut dbg = Debugger::new(rt);
+    let mut rl = DefaultEditor::new()?;
+    let mut done = false;
+
+    println!("Welcome to the RustScript debugger! Type help for a list of commands.");
+    print_location(&dbg);
+
+    while let Ok(inp) = rl.readline("(dbg) ") {
+        let inp = inp.trim();
+
+        if inp.is_empty() {
+            continue;
+        }
+
+        rl.add_history_entry(inp)?;
+
+        let mut words = inp.split_whitespace();
+        let cmd = words.next().unwrap_or_default();
+        let arg = words.next();
+
+        let stop = match (cmd, arg) {
+            ("s" | "step", None) if !done => dbg.step(),
+            ("n" | "next", None) if !done => dbg.next(),
+            ("c" | "continue", None) if !done => dbg.cont(),
+            ("s" | "step" | "n" | "next" | "c" | "continue", None) => {
+                println!("The program is done.");
+                continue;
+            }
+            ("b" | "break", Some(pc)) => {
+                match pc.parse() {
+                    Ok(pc) => {
+                        dbg.add_breakpoint(pc);
+                        println!("Breakpoint at pc={}", pc);
+                    }
+                    Err(_) => println!("Invalid pc: {}", pc),
+                }
+                continue;
+            }
+            ("d" | "delete", Some(pc)) => {
+                match pc.parse() {
+                    Ok(pc) if dbg.remove_breakpoint(pc) => (),
+                    Ok(pc) => println!("No breakpoint at pc={}", pc),
+                    Err(_) => println!("Invalid pc: {}", pc),
+                }
+                continue;
+            }
+            ("save", Some(path)) => {
+                let saved = std::fs::File::create(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|mut file| dbg.runtime().save_snapshot(&mut file));
+
+                match saved {
+                    Ok(()) => println!("Saved snapshot to {}", path),
+                    Err(err) => println!("Could not save snapshot: {}", err),
+                }
+                continue;
+            }
+            ("watch", Some(name)) => {
+                dbg.add_watchpoint(name);
+                println!("Watching {}", name);
+                continue;
+            }
+            ("unwatch", Some(name)) => {
+                if !dbg.remove_watchpoint(name) {
+                    println!("{} is not watched", name);
+                }
+                continue;
+            }
+            ("watchpoints", None) => {
+                for sym in dbg.watchpoints() {
+                    println!("{}", sym);
+                }
+                continue;
+            }
+            ("breakpoints", None) => {
+                for pc in dbg.breakpoints() {
+                    println!("pc={}: {}", pc, disassemble(dbg.runtime().instrs.get(pc)));
+                }
+                continue;
+            }
+            ("w" | "where", None) => {
+                print_location(&dbg);
+                continue;
+            }
+            ("bt" | "backtrace", None) => {
+                println!("{}", dbg.runtime().stack_trace(dbg.pc()));
+                continue;
+            }
+            ("stack", None) => {
+                println!("{:?}", dbg.operand_stack());
+                continue;
+            }
+            ("env", None) => {
+                let envs = dbg.environments();
+                // The global environment holds the builtins, which would drown out the program's names
+                for (depth, bindings) in envs.iter().take(envs.len().saturating_sub(1)).enumerate()
+                {
+                    println!("[{}] {:?}", depth, bindings);
+                }
+                continue;
+            }
+            ("threads", None) => {
+                println!("Current: {}", dbg.thread_id());
+                println!("Ready: {:?}", dbg.ready_queue());
+                println!("Blocked: {:?}", dbg.blocked_queue());
+                continue;
+            }
+            ("inspect", arg) => {
+                let thread_id = match arg.map(str::parse) {
+                    None => dbg.thread_id(),
+                    Some(Ok(thread_id)) => thread_id,
+                    Some(Err(_)) => {
+                        println!("Invalid thread: {}", arg.unwrap_or_default());
+                        continue;
+                    }
+                };
+
+                match dbg.runtime().inspect_env(thread_id) {
+                    Some(report) => println!("{}", report),
+                    None => println!("No environment for thread {}", thread_id),
+                }
+                continue;
+            }
+            ("h" | "help", None) => {
+                println!("{}", DEBUGGER_HELP);
+                continue;
+            }
+            ("q" | "quit", None) => break,
+            _ => {
+                println!(
+                    "Unknown command: {}. Type help for a list of commands.",
+                    inp
+                );
+                continue;
+            }
+        };
+
+        match stop {
+            Ok(StopReason::Done) => {
+                done = true;
+                println!("The program is done.");
+
+                if let Some(val) = dbg.operand_stack().last() {
+                    bytecode::builtin::println_impl(val);
+                }
+            }
+            Ok(StopReason::Breakpoint(pc)) => {
+                println!("Breakpoint at pc={}", pc);
+                print_location(&dbg);
+            }
+            Ok(StopReason::Watchpoint(event)) => {
+                println!(
+                    "Watchpoint {}: thread {} at pc={} changed {:?} to {:?}",
+                    event.sym, event.thread_id, event.pc, event.old, event.new
+                );
+                print_location(&dbg);
+            }
+            Ok(StopReason::Step) => print_location(&dbg),
+            Err(err) => {
+                println!("[RuntimeError]: {:?}", err);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "repl")]
+fn print_location(dbg: &Debugger) {
+    println!(
+        "thread {} pc={}: {}",
+        dbg.thread_id(),
+        dbg.pc(),
+        disassemble(dbg.current_instr())
+    );
+}
+
+#[cfg(feature = "repl")]
+fn disassemble(instr: Option<&ByteCode>) -> String {
+    match instr {
+        Some(instr) => format!("{:?}", instr),
+        None => "<out of bounds>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytecode::{BinOp, Operand};
+
+    use super::*;
+
+    fn fn_call_program() -> Vec<ByteCode> {
+        // fn simple(n) {
+        //     return n;
+        // }
+        // simple(42)
+        vec![
+            ByteCode::enterscope(vec!["simple"]),
+            ByteCode::ldf(3, "simple", vec!["n"]),
+            ByteCode::GOTO(5),
+            ByteCode::ld("n"),
+            ByteCode::RESET(FrameType::CallFrame),
+            ByteCode::assign("simple"),
+            ByteCode::ld("simple"),
+            ByteCode::ldc(42),
+            ByteCode::CALL(1),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ]
+    }
+
+    #[test]
+    fn test_step() -> Result<()> {
+        let mut dbg = Debugger::new(Runtime::new(fn_call_program()));
+
+        assert_eq!(dbg.step()?, StopReason::Step);
+        assert_eq!(dbg.pc(), 1);
+        assert_eq!(
+            dbg.current_instr(),
+            Some(&ByteCode::ldf(3, "simple", vec!["n"]))
+        );
+
+        // Step into the call
+        for _ in 0..6 {
+            dbg.step()?;
+        }
+        assert_eq!(dbg.pc(), 3);
+        assert_eq!(dbg.call_depth(), 1);
+        assert_eq!(dbg.environments()[0], vec![("n".into(), Value::Int(42))]);
+
+        dbg.step()?;
+        assert_eq!(dbg.operand_stack(), &[Value::Int(42)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next() -> Result<()> {
+        let mut dbg = Debugger::new(Runtime::new(fn_call_program()));
+
+        while dbg.pc() != 8 {
+            dbg.step()?;
+        }
+
+        // Step over the call
+        assert_eq!(dbg.next()?, StopReason::Step);
+        assert_eq!(dbg.pc(), 9);
+        assert_eq!(dbg.call_depth(), 0);
+        assert_eq!(dbg.operand_stack(), &[Value::Int(42)]);
+
+        // Unless there is a breakpoint in the function
+        let mut dbg = Debugger::new(Runtime::new(fn_call_program()));
+        dbg.add_breakpoint(4);
+
+        while dbg.pc() != 8 {
+            dbg.step()?;
+        }
+
+        assert_eq!(dbg.next()?, StopReason::Breakpoint(4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cont() -> Result<()> {
+        let mut dbg = Debugger::new(Runtime::new(fn_call_program()));
+        assert!(dbg.add_breakpoint(3));
+        assert!(dbg.add_breakpoint(9));
+        assert!(!dbg.add_breakpoint(9));
+
+        assert_eq!(dbg.cont()?, StopReason::Breakpoint(3));
+        assert_eq!(dbg.cont()?, StopReason::Breakpoint(9));
+        assert_eq!(dbg.cont()?, StopReason::Done);
+        assert_eq!(dbg.step()?, StopReason::Done);
+
+        assert_eq!(dbg.operand_stack(), &[Value::Int(42)]);
+
+        // Removed breakpoints are not hit
+        let mut dbg = Debugger::new(Runtime::new(fn_call_program()));
+        dbg.add_breakpoint(3);
+        assert!(dbg.remove_breakpoint(3));
+        assert!(!dbg.remove_breakpoint(3));
+        assert_eq!(dbg.cont()?, StopReason::Done);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchpoint() -> Result<()> {
+        // let x = 1;
+        // join spawn { x = 2; };
+        // x
+        let instrs = vec![
+            ByteCode::enterscope(vec!["x"]),
+            ByteCode::ldc(1),
+            ByteCode::assign("x"),
+            ByteCode::SPAWN(7, 0),
+            ByteCode::JOIN,
+            ByteCode::POP,
+            ByteCode::GOTO(12),
+            ByteCode::POP,
+            ByteCode::ldc(2),
+            ByteCode::ASSIGNSLOT(0, 0),
+            ByteCode::ldc(Value::Unit),
+            ByteCode::DONE,
+            ByteCode::ld("x"),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
+        let mut dbg = Debugger::new(Runtime::new(instrs.clone()));
+        assert!(dbg.add_watchpoint("x"));
+        assert!(!dbg.add_watchpoint("x"));
+
+        assert_eq!(
+            dbg.cont()?,
+            StopReason::Watchpoint(WatchEvent {
+                sym: "x".into(),
+                thread_id: 1,
+                pc: 2,
+                old: Value::Unitialized,
+                new: Value::Int(1),
+            })
+        );
+        assert_eq!(
+            dbg.cont()?,
+            StopReason::Watchpoint(WatchEvent {
+                sym: "x".into(),
+                thread_id: 2,
+                pc: 9,
+                old: Value::Int(1),
+                new: Value::Int(2),
+            })
+        );
+        assert_eq!(dbg.cont()?, StopReason::Done);
+        assert_eq!(dbg.operand_stack(), &[Value::Int(2)]);
+
+        // Removed watchpoints are not hit
+        let mut dbg = Debugger::new(Runtime::new(instrs));
+        dbg.add_watchpoint("x");
+        assert!(dbg.remove_watchpoint("x"));
+        assert!(!dbg.remove_watchpoint("x"));
+        assert_eq!(dbg.cont()?, StopReason::Done);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchpoint_register() -> Result<()> {
+        // let x = 1;
+        // x = x + 2;
+        // x * 3;
+        // x
+        // lowered for the register backend
+        let instrs = vec![
+            ByteCode::enterscope(vec!["x"]),
+            ByteCode::MOVR((0, 0), Operand::Const(Value::Int(1))),
+            ByteCode::BINOPR(
+                Some((0, 0)),
+                Operand::Slot(0, 0),
+                Operand::Const(Value::Int(2)),
+                BinOp::Add,
+            ),
+            ByteCode::BINOPR(
+                None,
+                Operand::Slot(0, 0),
+                Operand::Const(Value::Int(3)),
+                BinOp::Mul,
+            ),
+            ByteCode::POP,
+            ByteCode::ld("x"),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
+        let mut dbg = Debugger::new(Runtime::new(instrs));
+        dbg.add_watchpoint("x");
+
+        assert_eq!(
+            dbg.cont()?,
+            StopReason::Watchpoint(WatchEvent {
+                sym: "x".into(),
+                thread_id: 1,
+                pc: 1,
+                old: Value::Unitialized,
+                new: Value::Int(1),
+            })
+        );
+        assert_eq!(
+            dbg.cont()?,
+            StopReason::Watchpoint(WatchEvent {
+                sym: "x".into(),
+                thread_id: 1,
+                pc: 2,
+                old: Value::Int(1),
+                new: Value::Int(3),
+            })
+        );
+        // The result of the multiplication is pushed, not assigned
+        assert_eq!(dbg.cont()?, StopReason::Done);
+        assert_eq!(dbg.operand_stack(), &[Value::Int(3)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_queues() -> Result<()> {
+        let instrs = vec![ByteCode::SPAWN(2, 0), ByteCode::DONE, ByteCode::DONE];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_time_quantum(Duration::from_millis(u64::MAX));
+        let mut dbg = Debugger::new(rt);
+
+        assert_eq!(dbg.ready_queue(), Vec::<ThreadID>::new());
+        dbg.step()?;
+        assert_eq!(dbg.thread_id(), 1);
+        assert_eq!(dbg.ready_queue(), vec![2]);
+        assert_eq!(dbg.blocked_queue(), Vec::<ThreadID>::new());
+
+        Ok(())
+    }
+}
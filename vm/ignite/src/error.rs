@@ -1,5 +1,10 @@
+use std::fmt::Display;
+
+use bytecode::ThreadID;
 use thiserror::Error;
 
+use crate::{Capability, StackTrace};
+
 #[derive(Error, Debug)]
 pub enum VmError {
     #[error("I/O error: {0}")]
@@ -26,6 +31,42 @@ pub enum VmError {
     #[error("PC out of bounds: {0}")]
     PcOutOfBounds(usize),
 
+    #[error("Instruction budget exceeded at pc {0}")]
+    BudgetExceeded(usize),
+
+    #[error("Interrupted")]
+    Interrupted,
+
+    #[error("Stack overflow at pc={pc}, call depth={depth}")]
+    StackOverflow { pc: usize, depth: usize },
+
+    #[error("Operand stack overflow at pc={pc}, size={size}")]
+    OperandStackOverflow { pc: usize, size: usize },
+
+    #[error("Out of memory: the program holds about {used} bytes, over the limit of {limit}")]
+    OutOfMemory { used: usize, limit: usize },
+
+    #[error("{builtin} needs the {capability} capability, which the runtime is denied")]
+    CapabilityDenied {
+        builtin: String,
+        capability: Capability,
+    },
+
+    #[error("Can't hot reload: {0}")]
+    HotReload(String),
+
+    #[error("The run diverged from the replayed one after {instr} instructions: thread {thread_id} can't run next")]
+    ReplayDiverged { instr: u64, thread_id: ThreadID },
+
+    #[error("Invalid switch log: {0}")]
+    InvalidSwitchLog(String),
+
+    #[error("The JIT does not support this host: {0}")]
+    JitUnsupported(String),
+
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+
     #[error("Bad type: expected {expected}, found {found}")]
     BadType { expected: String, found: String },
 
@@ -35,6 +76,17 @@ pub enum VmError {
     #[error("Unsupported operation {0} on type {1}")]
     UnsupportedOperation(String, String),
 
+    #[error(
+        "Can't apply {0} to Int and Float, convert one operand with int_to_float or float_to_int"
+    )]
+    MixedNumeric(String),
+
+    #[error("Integer overflow: {0}")]
+    IntegerOverflow(String),
+
+    #[error("Division by zero: {0}")]
+    DivisionByZero(String),
+
     #[error("Type mismatch: expected {expected}, found {found}")]
     TypeMismatch { expected: String, found: String },
 
@@ -47,6 +99,80 @@ pub enum VmError {
     #[error("Environment access after drop")]
     EnvironmentDroppedError,
 
+    #[error("Name already bound: {0}")]
+    NameAlreadyBound(String),
+
+    #[error("No program loaded")]
+    NoProgramLoaded,
+
+    #[error("The program has not run to completion")]
+    ProgramNotDone,
+
     #[error("Unknown builtin: {sym}")]
     UnknownBuiltin { sym: String },
+
+    #[error("No field {field} on {ty}")]
+    NoSuchField { ty: String, field: String },
+
+    #[error("No method {method} on {ty}")]
+    NoSuchMethod { ty: String, method: String },
+
+    #[error("No overload of {op} for {lhs} and {rhs}, only + and == can be overloaded with add and eq methods")]
+    NoOverload {
+        op: String,
+        lhs: String,
+        rhs: String,
+    },
+
+    #[error("Can't destructure {ty} of length {len} into {expected} elements")]
+    LengthMismatch {
+        ty: String,
+        expected: String,
+        len: usize,
+    },
+
+    #[error("No scope to join the spawned threads of")]
+    NoSpawnScope,
+
+    #[error("Generator resumed while it is already running")]
+    GeneratorRunning,
+
+    #[error("Index {idx} out of bounds for {ty} of length {len}")]
+    IndexOutOfBounds { ty: String, idx: i64, len: usize },
+
+    #[error("Slice {start}..{end} out of bounds for {ty} of length {len}")]
+    SliceOutOfBounds {
+        ty: String,
+        start: i64,
+        end: i64,
+        len: usize,
+    },
+}
+
+/// The context attached to errors escaping the run loop, locating where in the program the error occurred.
+#[derive(Debug)]
+pub struct RuntimeErrorContext {
+    pub thread_id: ThreadID,
+    /// The PC of the instruction that failed.
+    pub pc: usize,
+    /// The disassembly of the instruction that failed, if the PC is in bounds.
+    pub instr: Option<String>,
+    pub trace: StackTrace,
+}
+
+impl Display for RuntimeErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Runtime error in thread {} at pc={}",
+            self.thread_id, self.pc
+        )?;
+
+        match &self.instr {
+            Some(instr) => writeln!(f, ": {}", instr)?,
+            None => writeln!(f, ": <out of bounds>")?,
+        }
+
+        write!(f, "{}", self.trace)
+    }
 }
@@ -0,0 +1,234 @@
+// The C interface of the VM, for programs not written in Rust that embed it.
+// Build it as a shared library with `cargo rustc -p ignite --lib --features ffi --crate-type cdylib`,
+// the declarations for C and an example are in include/ignite.h.
+
+use std::{
+    any::Any,
+    ffi::{c_char, c_int, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use anyhow::Result;
+use bytecode::read_bytecode;
+
+use crate::{run, Runtime, VmError};
+
+/// A VM created by [`ignite_vm_new`], opaque to the host.
+#[derive(Default)]
+pub struct IgniteVm {
+    rt: Option<Runtime>,
+    /// The final value of the program, if it ran and left one.
+    result: Option<CString>,
+    /// The error of the last call that failed.
+    error: Option<CString>,
+}
+
+impl IgniteVm {
+    /// Record the outcome of a call, returning the status the host sees: 0 if it succeeded, -1 otherwise.
+    fn status(&mut self, res: Result<()>) -> c_int {
+        match res {
+            Ok(()) => {
+                self.error = None;
+                0
+            }
+            Err(err) => {
+                self.error = Some(c_string(format!("{:#}", err)));
+                -1
+            }
+        }
+    }
+
+    /// Make a call, catching a panic so it does not unwind into the host, which is undefined behavior.
+    /// Returns the status of the call, or -2 if it panicked, after which the program is dropped
+    /// as its state may be inconsistent.
+    fn call(&mut self, f: impl FnOnce(&mut IgniteVm) -> Result<()>) -> c_int {
+        match panic::catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(res) => self.status(res),
+            Err(payload) => {
+                self.rt = None;
+                self.result = None;
+                self.error = Some(c_string(format!(
+                    "The VM panicked: {}",
+                    panic_message(&*payload)
+                )));
+                -2
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let rt = self.rt.as_mut().ok_or(VmError::NoProgramLoaded)?;
+        self.result = None;
+        run(rt)?;
+
+        // The program called exit, so there is no result
+        if rt.exit_code.is_some() {
+            return Ok(());
+        }
+
+        self.result = rt
+            .current_thread
+            .operand_stack
+            .last()
+            .map(|val| c_string(val.to_string()));
+
+        Ok(())
+    }
+}
+
+/// The message a panic was raised with, if it was raised with one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown cause"
+    }
+}
+
+/// A C string of the text, whose interior nul bytes, which C strings cannot hold, are escaped.
+fn c_string(s: String) -> CString {
+    CString::new(s.replace('\0', "\\0")).expect("Nul bytes were escaped")
+}
+
+/// Create a VM with no program, to be destroyed with [`ignite_vm_free`].
+#[no_mangle]
+pub extern "C" fn ignite_vm_new() -> *mut IgniteVm {
+    Box::into_raw(Box::default())
+}
+
+/// Load the contents of a .o2 file into the VM, replacing the program it had.
+/// Returns 0 on success, -1 if the bytes are not a valid .o2 file, or -2 if the VM panicked.
+///
+/// # Safety
+///
+/// `vm` must come from [`ignite_vm_new`], and `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ignite_vm_load(vm: *mut IgniteVm, bytes: *const u8, len: usize) -> c_int {
+    let vm = &mut *vm;
+    let mut bytes = std::slice::from_raw_parts(bytes, len);
+
+    vm.call(|vm| {
+        let instrs = read_bytecode(&mut bytes)?;
+        vm.rt = Some(Runtime::new(instrs));
+        vm.result = None;
+        Ok(())
+    })
+}
+
+/// Run the loaded program to completion.
+/// Returns 0 on success, -1 if no program is loaded or the program failed, or -2 if the VM panicked,
+/// after which no program is loaded.
+///
+/// # Safety
+///
+/// `vm` must come from [`ignite_vm_new`].
+#[no_mangle]
+pub unsafe extern "C" fn ignite_vm_run(vm: *mut IgniteVm) -> c_int {
+    let vm = &mut *vm;
+    vm.call(IgniteVm::run)
+}
+
+/// The final value of the program that last ran, formatted as ignite prints it, or NULL if it left none.
+/// The string is owned by the VM, and valid until the next call on it.
+///
+/// # Safety
+///
+/// `vm` must come from [`ignite_vm_new`].
+#[no_mangle]
+pub unsafe extern "C" fn ignite_vm_result(vm: *const IgniteVm) -> *const c_char {
+    (*vm).result.as_ref().map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// The error of the last call that failed, or NULL if it succeeded.
+/// The string is owned by the VM, and valid until the next call on it.
+///
+/// # Safety
+///
+/// `vm` must come from [`ignite_vm_new`].
+#[no_mangle]
+pub unsafe extern "C" fn ignite_vm_error(vm: *const IgniteVm) -> *const c_char {
+    (*vm).error.as_ref().map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// Destroy the VM. Does nothing if `vm` is NULL.
+///
+/// # Safety
+///
+/// `vm` must come from [`ignite_vm_new`], and not be used after.
+#[no_mangle]
+pub unsafe extern "C" fn ignite_vm_free(vm: *mut IgniteVm) {
+    if !vm.is_null() {
+        drop(Box::from_raw(vm));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use bytecode::{write_bytecode, BinOp, ByteCode};
+
+    use super::*;
+
+    fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+        (!s.is_null()).then(|| unsafe { CStr::from_ptr(s) }.to_str().unwrap())
+    }
+
+    #[test]
+    fn test_ffi_run() {
+        let instrs = vec![
+            ByteCode::ldc(40),
+            ByteCode::ldc(2),
+            ByteCode::BINOP(BinOp::Add),
+            ByteCode::DONE,
+        ];
+        let mut bytes = vec![];
+        write_bytecode(&instrs, &mut bytes).unwrap();
+
+        unsafe {
+            let vm = ignite_vm_new();
+            assert_eq!(ignite_vm_load(vm, bytes.as_ptr(), bytes.len()), 0);
+            assert_eq!(ignite_vm_run(vm), 0);
+            assert_eq!(to_str(ignite_vm_result(vm)), Some("42"));
+            assert_eq!(to_str(ignite_vm_error(vm)), None);
+            ignite_vm_free(vm);
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        unsafe {
+            let vm = ignite_vm_new();
+            assert_eq!(ignite_vm_run(vm), -1);
+            assert_eq!(to_str(ignite_vm_error(vm)), Some("No program loaded"));
+
+            let bytes = b"not bytecode";
+            assert_eq!(ignite_vm_load(vm, bytes.as_ptr(), bytes.len()), -1);
+            assert!(to_str(ignite_vm_error(vm)).is_some());
+            assert_eq!(to_str(ignite_vm_result(vm)), None);
+            ignite_vm_free(vm);
+        }
+    }
+
+    #[test]
+    fn test_ffi_panic() {
+        let instrs = vec![ByteCode::ldc(1), ByteCode::DONE];
+        let mut bytes = vec![];
+        write_bytecode(&instrs, &mut bytes).unwrap();
+
+        unsafe {
+            let vm = ignite_vm_new();
+            assert_eq!(ignite_vm_load(vm, bytes.as_ptr(), bytes.len()), 0);
+
+            // A panic is reported as a status, and drops the program
+            assert_eq!((*vm).call(|_| panic!("boom")), -2);
+            assert_eq!(to_str(ignite_vm_error(vm)), Some("The VM panicked: boom"));
+            assert_eq!(ignite_vm_run(vm), -1);
+            assert_eq!(to_str(ignite_vm_error(vm)), Some("No program loaded"));
+            ignite_vm_free(vm);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::{atomic::AtomicBool, Arc, Once, OnceLock};
+
+/// The exit status of a program stopped by Ctrl-C, as of a process killed by SIGINT.
+pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+/// The flag the handler of Ctrl-C sets.
+static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+static INSTALL_HANDLER: Once = Once::new();
+
+/// Handle Ctrl-C (SIGINT) by setting the returned flag instead of killing the process.
+/// A runtime given the flag with `Runtime::set_interrupt_flag` stops before its next instruction,
+/// with the stack trace of the current thread.
+///
+/// The handler is installed on the first call, and later calls give the same flag.
+/// It only runs once, so a second Ctrl-C kills the process, e.g. if the program is waiting for input.
+/// On targets without signals no handler is installed, and nothing sets the flag.
+pub fn interrupt_on_ctrl_c() -> Arc<AtomicBool> {
+    let flag = Arc::clone(INTERRUPT.get_or_init(Arc::default));
+    INSTALL_HANDLER.call_once(install_handler);
+    flag
+}
+
+#[cfg(unix)]
+fn install_handler() {
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+    extern "C" fn handle_sigint(_: nix::libc::c_int) {
+        // Only the atomic store is safe to do in a signal handler, and the flag is set before the handler is installed
+        if let Some(flag) = INTERRUPT.get() {
+            flag.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    let action = SigAction::new(
+        SigHandler::Handler(handle_sigint),
+        SaFlags::SA_RESETHAND | SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+
+    // The handler only touches the flag, so it can't interfere with the rest of the process
+    if let Err(err) = unsafe { sigaction(Signal::SIGINT, &action) } {
+        eprintln!("Failed to handle Ctrl-C: {}", err);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_handler() {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use nix::sys::signal::{raise, Signal};
+
+    use super::*;
+
+    #[test]
+    fn test_interrupt_on_ctrl_c() {
+        let flag = interrupt_on_ctrl_c();
+        assert!(Arc::ptr_eq(&flag, &interrupt_on_ctrl_c()));
+        assert!(!flag.load(Ordering::Relaxed));
+
+        raise(Signal::SIGINT).unwrap();
+        assert!(flag.load(Ordering::Relaxed));
+    }
+}
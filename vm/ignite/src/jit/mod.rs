@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bytecode::{Address, Value};
+use cranelift_codegen::{settings, settings::Configurable, Context};
+use cranelift_frontend::FunctionBuilderContext;
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+
+use crate::{Runtime, VmError};
+
+use self::translate::{from_native, to_native, translate, NativeFn, Ty, STATUS_DONE};
+
+mod translate;
+
+/// The number of calls after which a function is compiled to native code, by default.
+pub const DEFAULT_JIT_THRESHOLD: u64 = 1000;
+
+/// What a function is run by once it is hot.
+enum Compiled {
+    Native(NativeFn),
+    /// The function has instructions native code can't run, or hit a guard, so it is left to the interpreter for good.
+    Interpreted,
+}
+
+/// What the JIT has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitStats {
+    /// The number of functions compiled to native code.
+    pub compiled: usize,
+    /// The number of hot functions left to the interpreter, as they have instructions native code can't run.
+    pub rejected: usize,
+    /// The number of calls run by native code.
+    pub native_calls: u64,
+    /// The number of calls of compiled functions run by the interpreter instead, as they hit a guard:
+    /// an argument of another type than it was compiled for, an overflow or division by zero, or a long-running loop.
+    pub fallbacks: u64,
+}
+
+/// Compiles hot functions to native code with cranelift, counting the calls of each function until it is hot.
+/// Only functions of ints and bools that don't call other functions or read variables of the scopes they were defined
+/// in are compiled, as native code can't run the rest of the instructions. A compiled function is specialized to the
+/// types of the arguments of the call that made it hot, and guards against others.
+pub struct Jit {
+    threshold: u64,
+    module: JITModule,
+    ctx: Context,
+    builder_ctx: FunctionBuilderContext,
+    /// The number of calls of each function not yet compiled, by its address.
+    calls: HashMap<Address, u64>,
+    compiled: HashMap<Address, Compiled>,
+    stats: JitStats,
+}
+
+impl Jit {
+    /// # Errors
+    ///
+    /// If cranelift does not support the host.
+    pub fn new(threshold: u64) -> Result<Jit, VmError> {
+        let err = |msg: String| VmError::JitUnsupported(msg);
+
+        let mut flags = settings::builder();
+        flags
+            .set("use_colocated_libcalls", "false")
+            .map_err(|e| err(e.to_string()))?;
+        flags
+            .set("is_pic", "false")
+            .map_err(|e| err(e.to_string()))?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| err(e.to_string()))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| err(e.to_string()))?;
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+
+        Ok(Jit {
+            threshold,
+            ctx: module.make_context(),
+            module,
+            builder_ctx: FunctionBuilderContext::new(),
+            calls: HashMap::new(),
+            compiled: HashMap::new(),
+            stats: JitStats::default(),
+        })
+    }
+
+    /// The number of calls after which a function is compiled.
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+}
+
+/// Running hot functions as native code.
+impl Runtime {
+    /// Compile functions called more than `threshold` times to native code, see [`Jit`].
+    /// Instructions run by native code are not counted, so the JIT is off while coverage is recorded,
+    /// instructions are limited or traced, or in debug mode.
+    ///
+    /// # Errors
+    ///
+    /// If cranelift does not support the host.
+    pub fn set_jit(&mut self, threshold: u64) -> Result<(), VmError> {
+        self.jit = Some(Jit::new(threshold)?);
+        Ok(())
+    }
+
+    /// What the JIT has done so far, if it is on.
+    pub fn jit_stats(&self) -> Option<JitStats> {
+        self.jit.as_ref().map(|jit| jit.stats)
+    }
+
+    /// Run the call of the function at the address with the arguments as native code, returning the value it returns,
+    /// or None if the interpreter has to run it. Counts the call, compiling the function once it is hot.
+    pub(crate) fn jit_call(&mut self, addr: Address, args: &[Value]) -> Option<Value> {
+        let counts_instrs = self.coverage.is_some()
+            || self.instr_budget.is_some()
+            || self.thread_instr_budget.is_some()
+            || self.trace_sink.is_some()
+            || self.debug;
+        if counts_instrs {
+            return None;
+        }
+        let jit = self.jit.as_mut()?;
+
+        if !jit.compiled.contains_key(&addr) {
+            let calls = jit.calls.entry(addr).or_insert(0);
+            *calls += 1;
+            if *calls < jit.threshold {
+                return None;
+            }
+            jit.calls.remove(&addr);
+
+            let params: Option<Vec<Ty>> = args.iter().map(Ty::of).collect();
+            let native = params.and_then(|params| {
+                translate(
+                    &mut jit.module,
+                    &mut jit.ctx,
+                    &mut jit.builder_ctx,
+                    &self.instrs,
+                    addr,
+                    &params,
+                )
+            });
+            let compiled = match native {
+                Some(native) => {
+                    jit.stats.compiled += 1;
+                    Compiled::Native(native)
+                }
+                None => {
+                    jit.stats.rejected += 1;
+                    Compiled::Interpreted
+                }
+            };
+            jit.compiled.insert(addr, compiled);
+        }
+
+        let Some(Compiled::Native(native)) = jit.compiled.get(&addr) else {
+            return None;
+        };
+
+        let guarded = args
+            .iter()
+            .zip(native.params.iter())
+            .all(|(arg, ty)| Ty::of(arg) == Some(*ty));
+        if !guarded {
+            jit.stats.fallbacks += 1;
+            return None;
+        }
+
+        let args: Vec<i64> = args.iter().map(to_native).collect();
+        let mut ret = 0;
+        let status = (native.code)(args.as_ptr(), &mut ret);
+
+        if status != STATUS_DONE {
+            // The function has no effects, so the interpreter runs it again from the start
+            jit.stats.fallbacks += 1;
+            jit.compiled.insert(addr, Compiled::Interpreted);
+            return None;
+        }
+
+        jit.stats.native_calls += 1;
+        Some(from_native(native.ret, ret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytecode::Value;
+    use compiler::compiler::compile_from_string;
+
+    use crate::{run, IntOverflow, RuntimeConfig};
+
+    use super::*;
+
+    fn run_jit(src: &str, threshold: u64) -> Result<(Value, JitStats)> {
+        let instrs = compile_from_string(src, true)?;
+        let mut rt = Runtime::new(instrs);
+        rt.set_jit(threshold)?;
+        run(&mut rt)?;
+        let val = rt.current_thread.operand_stack.pop().unwrap();
+        Ok((val, rt.jit_stats().unwrap()))
+    }
+
+    #[test]
+    fn test_jit() -> Result<()> {
+        let src = r"
+        fn sum_to(n: int) -> int {
+            let i = 0;
+            let s = 0;
+            loop i < n && s >= 0 {
+                s = s + i % 7;
+                i = i + 1;
+            }
+            return s;
+        }
+        fn is_even(n: int) -> bool {
+            if n < 0 { -n % 2 == 0 } else { !(n % 2 == 1) }
+        }
+        let total = 0;
+        let evens = 0;
+        let k = 0;
+        loop k < 20 {
+            total = total + sum_to(k);
+            if is_even(k - 10) { evens = evens + 1; }
+            k = k + 1;
+        }
+        total * 100 + evens
+        ";
+
+        let (interpreted, _) = run_jit(src, u64::MAX)?;
+        let (val, stats) = run_jit(src, 5)?;
+        assert_eq!(val, interpreted);
+        assert_eq!(
+            stats,
+            JitStats {
+                compiled: 2,
+                rejected: 0,
+                native_calls: 32,
+                fallbacks: 0,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_jit_rejects() -> Result<()> {
+        // Calls, floats and variables of the enclosing scope are left to the interpreter
+        let src = r"
+        fn fib(n: int) -> int {
+            if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+        }
+        fn half(x: float) -> float { x / 2.0 }
+        let offset = 1;
+        fn shift(n: int) -> int { n + offset }
+        half(1.0);
+        half(3.0);
+        shift(1) + shift(2) + fib(5)
+        ";
+
+        let (val, stats) = run_jit(src, 2)?;
+        assert_eq!(val, Value::Int(10));
+        assert_eq!(stats.compiled, 0);
+        assert_eq!(stats.rejected, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jit_guards() -> Result<()> {
+        let src = r"
+        fn add(a: int, b: int) -> int { a + b }
+        let x = add(1, 2);
+        let y = add(3, 4);
+        let z = add(9223372036854775807, 1);
+        x + y + add(5, 6) + z
+        ";
+
+        // Overflow is left to the interpreter, which wraps as it is set to, and the function is not run natively again
+        let instrs = compile_from_string(src, true)?;
+        let config = RuntimeConfig::default().int_overflow(IntOverflow::Wrap);
+        let mut rt = Runtime::with_config(instrs, config);
+        rt.set_jit(2)?;
+        run(&mut rt)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Int(i64::MIN + 21))
+        );
+        let stats = rt.jit_stats().unwrap();
+        assert_eq!((stats.native_calls, stats.fallbacks), (1, 1));
+
+        // Traps as it would without the JIT
+        let mut rt = Runtime::new(compile_from_string(src, true)?);
+        rt.set_jit(2)?;
+        assert!(run(&mut rt).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,629 @@
+use std::collections::{HashMap, HashSet};
+
+use bytecode::{Address, BinOp, ByteCode, FrameType, Operand, UnOp, Value};
+use cranelift_codegen::{
+    ir::{condcodes::IntCC, types::I64, AbiParam, Block, InstBuilder, MemFlagsData},
+    Context,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::JITModule;
+use cranelift_module::Module;
+
+/// The number of backward jumps a native function may take before it falls back to the interpreter,
+/// so that a long-running loop is not run without the scheduler for good.
+const FUEL: i64 = 10_000_000;
+
+/// The status a native function returns with if it ran to the end, rather than hitting a guard.
+pub(crate) const STATUS_DONE: i64 = 0;
+
+/// The types of values native code works with, all held in an i64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Ty {
+    Int,
+    Bool,
+    Unit,
+}
+
+impl Ty {
+    pub(crate) fn of(val: &Value) -> Option<Ty> {
+        match val {
+            Value::Int(_) => Some(Ty::Int),
+            Value::Bool(_) => Some(Ty::Bool),
+            Value::Unit => Some(Ty::Unit),
+            _ => None,
+        }
+    }
+
+    fn binop(op: &BinOp, lhs: Ty, rhs: Ty) -> Option<Ty> {
+        match (op, lhs, rhs) {
+            (BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod, Ty::Int, Ty::Int) => {
+                Some(Ty::Int)
+            }
+            (BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Eq, Ty::Int, Ty::Int) => {
+                Some(Ty::Bool)
+            }
+            (BinOp::Eq | BinOp::And | BinOp::Or, Ty::Bool, Ty::Bool) => Some(Ty::Bool),
+            _ => None,
+        }
+    }
+
+    fn unop(op: &UnOp, ty: Ty) -> Option<Ty> {
+        match (op, ty) {
+            (UnOp::Neg, Ty::Int) => Some(Ty::Int),
+            (UnOp::Not, Ty::Bool) => Some(Ty::Bool),
+            _ => None,
+        }
+    }
+}
+
+/// The value as it is held in native code.
+pub(crate) fn to_native(val: &Value) -> i64 {
+    match val {
+        Value::Int(n) => *n,
+        Value::Bool(b) => *b as i64,
+        _ => 0,
+    }
+}
+
+/// The value of the type held in native code.
+pub(crate) fn from_native(ty: Ty, n: i64) -> Value {
+    match ty {
+        Ty::Int => Value::Int(n),
+        Ty::Bool => Value::Bool(n != 0),
+        Ty::Unit => Value::Unit,
+    }
+}
+
+/// The types of the operand stack and the slots of the frames of the function before an instruction runs.
+/// The frames are those the function entered, the parameters first, as the frames it was defined in are not read.
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    stack: Vec<Ty>,
+    scopes: Vec<Vec<Option<Ty>>>,
+}
+
+impl State {
+    fn pop(&mut self) -> Option<Ty> {
+        self.stack.pop()
+    }
+
+    /// The frame of the slot the given number of levels up, counted from the parameters.
+    fn level(&self, depth: usize) -> Option<usize> {
+        self.scopes.len().checked_sub(depth + 1)
+    }
+
+    fn slot(&self, depth: usize, idx: usize) -> Option<Ty> {
+        *self.scopes.get(self.level(depth)?)?.get(idx)?
+    }
+
+    fn assign(&mut self, depth: usize, idx: usize, ty: Ty) -> Option<()> {
+        let level = self.level(depth)?;
+        let slot = self.scopes.get_mut(level)?.get_mut(idx)?;
+        match slot {
+            Some(old) if *old != ty => None,
+            _ => {
+                *slot = Some(ty);
+                Some(())
+            }
+        }
+    }
+
+    fn operand(&mut self, operand: &Operand) -> Option<Ty> {
+        match operand {
+            Operand::Stack => self.pop(),
+            Operand::Slot(depth, idx) => self.slot(*depth, *idx),
+            Operand::Const(val) => Ty::of(val),
+        }
+    }
+
+    /// The states the instruction at pc leads to, or None if native code can't run it.
+    fn step(&self, pc: Address, instr: &ByteCode) -> Option<Step> {
+        let mut next = self.clone();
+        let binop = |next: &mut State, op: &BinOp, rhs: Ty, lhs: Ty| {
+            next.stack.push(Ty::binop(op, lhs, rhs)?);
+            Some(())
+        };
+
+        match instr {
+            ByteCode::LDC(val) => next.stack.push(Ty::of(val)?),
+            ByteCode::LDSLOT(depth, idx) => next.stack.push(self.slot(*depth, *idx)?),
+            ByteCode::ASSIGNSLOT(depth, idx) => {
+                let ty = next.pop()?;
+                next.assign(*depth, *idx, ty)?;
+            }
+            ByteCode::POP => {
+                next.pop()?;
+            }
+            ByteCode::BINOP(op) => {
+                let (rhs, lhs) = (next.pop()?, next.pop()?);
+                binop(&mut next, op, rhs, lhs)?;
+            }
+            ByteCode::UNOP(op) => {
+                let ty = next.pop()?;
+                next.stack.push(Ty::unop(op, ty)?);
+            }
+            ByteCode::LDCBINOP(val, op) => {
+                let lhs = next.pop()?;
+                binop(&mut next, op, Ty::of(val)?, lhs)?;
+            }
+            ByteCode::LDSLOTSBINOP(lhs, rhs, op) => {
+                let (rhs, lhs) = (self.slot(rhs.0, rhs.1)?, self.slot(lhs.0, lhs.1)?);
+                binop(&mut next, op, rhs, lhs)?;
+            }
+            ByteCode::BINOPR(dst, lhs, rhs, op) => {
+                let rhs = next.operand(rhs)?;
+                let lhs = next.operand(lhs)?;
+                binop(&mut next, op, rhs, lhs)?;
+                if let Some((depth, idx)) = dst {
+                    let ty = next.pop()?;
+                    next.assign(*depth, *idx, ty)?;
+                }
+            }
+            ByteCode::MOVR((depth, idx), src) => {
+                let ty = next.operand(src)?;
+                next.assign(*depth, *idx, ty)?;
+            }
+            ByteCode::ENTERSCOPE(syms) => next.scopes.push(vec![None; syms.len()]),
+            ByteCode::EXITSCOPE => {
+                // The frame of the parameters is left by returning
+                if next.scopes.len() < 2 {
+                    return None;
+                }
+                next.scopes.pop();
+            }
+            ByteCode::JOF(addr) => {
+                if next.pop()? != Ty::Bool {
+                    return None;
+                }
+                return Some(Step::to(vec![(pc + 1, next.clone()), (*addr, next)]));
+            }
+            ByteCode::JOFORPOP(addr) => {
+                if *next.stack.last()? != Ty::Bool {
+                    return None;
+                }
+                let taken = next.clone();
+                next.pop();
+                return Some(Step::to(vec![(pc + 1, next), (*addr, taken)]));
+            }
+            ByteCode::GOTO(addr) => return Some(Step::to(vec![(*addr, next)])),
+            ByteCode::RESET(FrameType::CallFrame) => {
+                return Some(Step {
+                    succs: vec![],
+                    returned: Some(next.pop()?),
+                })
+            }
+            _ => return None,
+        }
+
+        Some(Step::to(vec![(pc + 1, next)]))
+    }
+}
+
+/// Where an instruction leads.
+struct Step {
+    /// The instructions that may run next, with the state before them.
+    succs: Vec<(Address, State)>,
+    /// The type of the value returned, if the instruction returns.
+    returned: Option<Ty>,
+}
+
+impl Step {
+    fn to(succs: Vec<(Address, State)>) -> Step {
+        Step {
+            succs,
+            returned: None,
+        }
+    }
+}
+
+/// The code of a function, found by the analysis to be one native code can run.
+struct Analysis {
+    /// The end of the body of the function, which starts at its address.
+    end: Address,
+    /// The state before each instruction of the body that is reached.
+    states: HashMap<Address, State>,
+    /// The instructions that start a block: the first, those jumped to, and those after a jump.
+    leaders: HashSet<Address>,
+    ret: Ty,
+}
+
+/// Follow every path through the function from its address, with the types of the arguments,
+/// checking that each instruction is one native code can run and that the types agree where paths meet.
+fn analyze(instrs: &[ByteCode], addr: Address, params: &[Ty]) -> Option<Analysis> {
+    // A function is loaded by LDF and skipped over by the GOTO just before its body
+    let Some(ByteCode::GOTO(end)) = addr.checked_sub(1).and_then(|pc| instrs.get(pc)) else {
+        return None;
+    };
+    let end = *end;
+
+    let entry = State {
+        stack: vec![],
+        scopes: vec![params.iter().copied().map(Some).collect()],
+    };
+    let mut states = HashMap::from([(addr, entry)]);
+    let mut leaders = HashSet::from([addr]);
+    let mut ret = None;
+    let mut worklist = vec![addr];
+
+    while let Some(pc) = worklist.pop() {
+        if !(addr..end).contains(&pc) {
+            return None;
+        }
+
+        let instr = &instrs[pc];
+        let Step { succs, returned } = states[&pc].step(pc, instr)?;
+
+        if let Some(ty) = returned {
+            if ret.is_some_and(|ret| ret != ty) {
+                return None;
+            }
+            ret = Some(ty);
+        }
+
+        let jumps = matches!(
+            instr,
+            ByteCode::JOF(_) | ByteCode::JOFORPOP(_) | ByteCode::GOTO(_) | ByteCode::RESET(_)
+        );
+        for (succ, state) in succs {
+            if jumps {
+                leaders.insert(succ);
+            }
+            match states.get(&succ) {
+                Some(seen) if *seen != state => return None,
+                Some(_) => (),
+                None => {
+                    states.insert(succ, state);
+                    worklist.push(succ);
+                }
+            }
+        }
+    }
+
+    Some(Analysis {
+        end,
+        states,
+        leaders,
+        ret: ret?,
+    })
+}
+
+/// A function compiled to native code.
+pub(crate) struct NativeFn {
+    /// Called with a pointer to the arguments and one to write the value returned to,
+    /// returns [`STATUS_DONE`], or another status if it hit a guard and has to be run by the interpreter.
+    pub(crate) code: extern "C" fn(*const i64, *mut i64) -> i64,
+    pub(crate) params: Vec<Ty>,
+    pub(crate) ret: Ty,
+}
+
+/// Emits the instructions of a function in the order of their addresses, into the block of the last leader.
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    /// The operand stack, a variable for each height.
+    stack: Vec<Variable>,
+    /// The types of the values on the operand stack before the instruction being emitted.
+    types: Vec<Ty>,
+    /// The slots of the frames of the function, by the frame counted from the parameters and the index.
+    slots: HashMap<(usize, usize), Variable>,
+    /// The number of frames the instruction being emitted is in.
+    levels: usize,
+    /// The types of the slots before the instruction being emitted.
+    scopes: Vec<Vec<Option<Ty>>>,
+    blocks: HashMap<Address, Block>,
+    fuel: Variable,
+    bail: Block,
+    ret_ptr: cranelift_codegen::ir::Value,
+}
+
+impl Translator<'_> {
+    fn push(&mut self, val: cranelift_codegen::ir::Value, ty: Ty) {
+        let height = self.types.len();
+        if height == self.stack.len() {
+            let var = self.builder.declare_var(I64);
+            self.stack.push(var);
+        }
+        self.builder.def_var(self.stack[height], val);
+        self.types.push(ty);
+    }
+
+    fn pop(&mut self) -> (cranelift_codegen::ir::Value, Ty) {
+        let ty = self.types.pop().expect("checked by the analysis");
+        (self.builder.use_var(self.stack[self.types.len()]), ty)
+    }
+
+    fn slot(&mut self, depth: usize, idx: usize) -> Variable {
+        let level = self.levels - 1 - depth;
+        if let Some(var) = self.slots.get(&(level, idx)) {
+            return *var;
+        }
+        let var = self.builder.declare_var(I64);
+        self.slots.insert((level, idx), var);
+        var
+    }
+
+    fn load(&mut self, depth: usize, idx: usize) -> (cranelift_codegen::ir::Value, Ty) {
+        let var = self.slot(depth, idx);
+        let level = self.levels - 1 - depth;
+        let ty = self.scopes[level][idx].expect("checked by the analysis");
+        (self.builder.use_var(var), ty)
+    }
+
+    fn assign(&mut self, depth: usize, idx: usize, val: cranelift_codegen::ir::Value) {
+        let var = self.slot(depth, idx);
+        self.builder.def_var(var, val);
+    }
+
+    fn constant(&mut self, val: &Value) -> (cranelift_codegen::ir::Value, Ty) {
+        let ty = Ty::of(val).expect("checked by the analysis");
+        (self.builder.ins().iconst(I64, to_native(val)), ty)
+    }
+
+    fn operand(&mut self, operand: &Operand) -> (cranelift_codegen::ir::Value, Ty) {
+        match operand {
+            Operand::Stack => self.pop(),
+            Operand::Slot(depth, idx) => self.load(*depth, *idx),
+            Operand::Const(val) => self.constant(val),
+        }
+    }
+
+    /// Fall back to the interpreter if the condition holds, otherwise go on in a new block.
+    fn guard(&mut self, cond: cranelift_codegen::ir::Value) {
+        let ok = self.builder.create_block();
+        self.builder.ins().brif(cond, self.bail, &[], ok, &[]);
+        self.builder.switch_to_block(ok);
+    }
+
+    fn binop(
+        &mut self,
+        op: &BinOp,
+        (lhs, ty): (cranelift_codegen::ir::Value, Ty),
+        (rhs, _): (cranelift_codegen::ir::Value, Ty),
+    ) {
+        let cmp = |t: &mut Self, cc: IntCC| {
+            let cond = t.builder.ins().icmp(cc, lhs, rhs);
+            t.builder.ins().uextend(I64, cond)
+        };
+
+        let val = match (op, ty) {
+            // Overflow falls back to the interpreter, which wraps, saturates or traps as the runtime is set to
+            (BinOp::Add | BinOp::Sub | BinOp::Mul, Ty::Int) => {
+                let (val, overflow) = match op {
+                    BinOp::Add => self.builder.ins().sadd_overflow(lhs, rhs),
+                    BinOp::Sub => self.builder.ins().ssub_overflow(lhs, rhs),
+                    _ => self.builder.ins().smul_overflow(lhs, rhs),
+                };
+                self.guard(overflow);
+                val
+            }
+            (BinOp::Div | BinOp::Mod, Ty::Int) => {
+                let by_zero = self.builder.ins().icmp_imm_s(IntCC::Equal, rhs, 0);
+                self.guard(by_zero);
+                let min = self.builder.ins().icmp_imm_s(IntCC::Equal, lhs, i64::MIN);
+                let minus_one = self.builder.ins().icmp_imm_s(IntCC::Equal, rhs, -1);
+                let overflow = self.builder.ins().band(min, minus_one);
+                self.guard(overflow);
+                match op {
+                    BinOp::Div => self.builder.ins().sdiv(lhs, rhs),
+                    _ => self.builder.ins().srem(lhs, rhs),
+                }
+            }
+            (BinOp::Lt, _) => cmp(self, IntCC::SignedLessThan),
+            (BinOp::Le, _) => cmp(self, IntCC::SignedLessThanOrEqual),
+            (BinOp::Gt, _) => cmp(self, IntCC::SignedGreaterThan),
+            (BinOp::Ge, _) => cmp(self, IntCC::SignedGreaterThanOrEqual),
+            (BinOp::Eq, _) => cmp(self, IntCC::Equal),
+            (BinOp::And, _) => self.builder.ins().band(lhs, rhs),
+            (BinOp::Or, _) => self.builder.ins().bor(lhs, rhs),
+            _ => unreachable!("checked by the analysis"),
+        };
+
+        let ty = Ty::binop(op, ty, ty).expect("checked by the analysis");
+        self.push(val, ty);
+    }
+
+    fn block(&self, addr: Address) -> Block {
+        self.blocks[&addr]
+    }
+
+    /// Emit the instruction, returning whether it ends its block.
+    fn emit(&mut self, pc: Address, instr: &ByteCode) -> bool {
+        match instr {
+            ByteCode::LDC(val) => {
+                let (val, ty) = self.constant(val);
+                self.push(val, ty);
+            }
+            ByteCode::LDSLOT(depth, idx) => {
+                let (val, ty) = self.load(*depth, *idx);
+                self.push(val, ty);
+            }
+            ByteCode::ASSIGNSLOT(depth, idx) => {
+                let (val, _) = self.pop();
+                self.assign(*depth, *idx, val);
+            }
+            ByteCode::POP => {
+                self.pop();
+            }
+            ByteCode::BINOP(op) => {
+                let rhs = self.pop();
+                let lhs = self.pop();
+                self.binop(op, lhs, rhs);
+            }
+            ByteCode::UNOP(op) => {
+                let (val, ty) = self.pop();
+                let val = match op {
+                    UnOp::Neg => {
+                        let min = self.builder.ins().icmp_imm_s(IntCC::Equal, val, i64::MIN);
+                        self.guard(min);
+                        self.builder.ins().ineg(val)
+                    }
+                    UnOp::Not => self.builder.ins().bxor_imm_s(val, 1),
+                };
+                self.push(val, ty);
+            }
+            ByteCode::LDCBINOP(val, op) => {
+                let rhs = self.constant(val);
+                let lhs = self.pop();
+                self.binop(op, lhs, rhs);
+            }
+            ByteCode::LDSLOTSBINOP(lhs, rhs, op) => {
+                let rhs = self.load(rhs.0, rhs.1);
+                let lhs = self.load(lhs.0, lhs.1);
+                self.binop(op, lhs, rhs);
+            }
+            ByteCode::BINOPR(dst, lhs, rhs, op) => {
+                let rhs = self.operand(rhs);
+                let lhs = self.operand(lhs);
+                self.binop(op, lhs, rhs);
+                if let Some((depth, idx)) = dst {
+                    let (val, _) = self.pop();
+                    self.assign(*depth, *idx, val);
+                }
+            }
+            ByteCode::MOVR((depth, idx), src) => {
+                let (val, _) = self.operand(src);
+                self.assign(*depth, *idx, val);
+            }
+            ByteCode::ENTERSCOPE(_) | ByteCode::EXITSCOPE => (),
+            ByteCode::JOF(addr) => {
+                let (cond, _) = self.pop();
+                let (next, target) = (self.block(pc + 1), self.block(*addr));
+                self.builder.ins().brif(cond, next, &[], target, &[]);
+                return true;
+            }
+            ByteCode::JOFORPOP(addr) => {
+                let cond = self.builder.use_var(self.stack[self.types.len() - 1]);
+                let (next, target) = (self.block(pc + 1), self.block(*addr));
+                self.builder.ins().brif(cond, next, &[], target, &[]);
+                return true;
+            }
+            ByteCode::GOTO(addr) => {
+                let target = self.block(*addr);
+                if *addr <= pc {
+                    let fuel = self.builder.use_var(self.fuel);
+                    let fuel = self.builder.ins().iadd_imm_s(fuel, -1);
+                    self.builder.def_var(self.fuel, fuel);
+                    self.builder.ins().brif(fuel, target, &[], self.bail, &[]);
+                } else {
+                    self.builder.ins().jump(target, &[]);
+                }
+                return true;
+            }
+            ByteCode::RESET(_) => {
+                let (val, _) = self.pop();
+                self.builder
+                    .ins()
+                    .store(MemFlagsData::trusted(), val, self.ret_ptr, 0);
+                let status = self.builder.ins().iconst(I64, STATUS_DONE);
+                self.builder.ins().return_(&[status]);
+                return true;
+            }
+            _ => unreachable!("checked by the analysis"),
+        }
+        false
+    }
+}
+
+/// Compile the function at the address to native code, specialized to arguments of the types,
+/// or None if it has instructions native code can't run, e.g. calls or loads of values other than ints and bools,
+/// or reads variables of the scopes it was defined in.
+pub(crate) fn translate(
+    module: &mut JITModule,
+    ctx: &mut Context,
+    builder_ctx: &mut FunctionBuilderContext,
+    instrs: &[ByteCode],
+    addr: Address,
+    params: &[Ty],
+) -> Option<NativeFn> {
+    let analysis = analyze(instrs, addr, params)?;
+
+    let ptr = module.target_config().pointer_type();
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(ptr));
+    sig.params.push(AbiParam::new(ptr));
+    sig.returns.push(AbiParam::new(I64));
+    ctx.func.signature = sig.clone();
+
+    let mut builder = FunctionBuilder::new(&mut ctx.func, builder_ctx);
+    let entry = builder.create_block();
+    let bail = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    let mut leaders: Vec<Address> = analysis.leaders.iter().copied().collect();
+    leaders.sort();
+    let blocks = leaders
+        .iter()
+        .map(|pc| (*pc, builder.create_block()))
+        .collect();
+
+    builder.switch_to_block(entry);
+    let (args_ptr, ret_ptr) = (
+        builder.block_params(entry)[0],
+        builder.block_params(entry)[1],
+    );
+    let fuel = builder.declare_var(I64);
+    let fuel_val = builder.ins().iconst(I64, FUEL);
+    builder.def_var(fuel, fuel_val);
+
+    let mut t = Translator {
+        builder,
+        stack: vec![],
+        types: vec![],
+        slots: HashMap::new(),
+        levels: 1,
+        scopes: vec![],
+        blocks,
+        fuel,
+        bail,
+        ret_ptr,
+    };
+    for idx in 0..params.len() {
+        let arg = t
+            .builder
+            .ins()
+            .load(I64, MemFlagsData::trusted(), args_ptr, (idx * 8) as i32);
+        t.assign(0, idx, arg);
+    }
+    let first = t.block(addr);
+    t.builder.ins().jump(first, &[]);
+
+    let mut ended = true;
+    for (pc, instr) in instrs.iter().enumerate().take(analysis.end).skip(addr) {
+        let Some(state) = analysis.states.get(&pc) else {
+            continue;
+        };
+        if let Some(block) = t.blocks.get(&pc).copied() {
+            if !ended {
+                t.builder.ins().jump(block, &[]);
+            }
+            t.builder.switch_to_block(block);
+        }
+        t.types = state.stack.clone();
+        t.scopes = state.scopes.clone();
+        t.levels = state.scopes.len();
+        ended = t.emit(pc, instr);
+    }
+
+    t.builder.switch_to_block(bail);
+    let status = t.builder.ins().iconst(I64, STATUS_DONE + 1);
+    t.builder.ins().return_(&[status]);
+
+    t.builder.seal_all_blocks();
+    t.builder.finalize(module.target_config());
+
+    let id = module.declare_anonymous_function(&sig).ok()?;
+    let defined = module.define_function(id, ctx);
+    module.clear_context(ctx);
+    defined.ok()?;
+    module.finalize_definitions().ok()?;
+
+    let code = module.get_finalized_function(id);
+    // SAFETY: the function was compiled with this signature
+    let code = unsafe {
+        std::mem::transmute::<*const u8, extern "C" fn(*const i64, *mut i64) -> i64>(code)
+    };
+
+    Some(NativeFn {
+        code,
+        params: params.to_vec(),
+        ret: analysis.ret,
+    })
+}
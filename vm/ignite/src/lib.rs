@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use anyhow::Result;
+use bytecode::read_object;
+
+pub use crate::dap::ignite_dap;
+#[cfg(feature = "repl")]
+pub use crate::debugger::ignite_debugger;
+pub use crate::debugger::{Debugger, StopReason, WatchEvent};
+pub use crate::error::*;
+pub use crate::interrupt::*;
+#[cfg(feature = "jit")]
+pub use crate::jit::*;
+#[cfg(feature = "repl")]
+pub use crate::repl::ignite_repl;
+pub use crate::runtime::*;
+pub use crate::thread::*;
+
+mod dap;
+mod debugger;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod interrupt;
+#[cfg(feature = "jit")]
+mod jit;
+mod micro_code;
+#[cfg(feature = "repl")]
+mod repl;
+mod runtime;
+mod thread;
+
+/// Load the program in the .o2 file into a new runtime.
+pub fn load_program(file: String) -> Result<Runtime> {
+    // Check if the file exists
+    if !Path::new(&file).exists() {
+        return Err(VmError::FileDoesNotExist(file).into());
+    }
+
+    // check file extension
+    if Path::new(&file).extension().unwrap() != "o2" {
+        return Err(VmError::NotO2File(file).into());
+    }
+
+    // Deserialize the program
+    let obj = read_object(&mut std::fs::File::open(&file)?)?;
+
+    if let Some(warning) = compatibility_warning(&file, obj.metadata.as_ref()) {
+        eprintln!("{}", warning);
+    }
+
+    Ok(Runtime::new(obj.instrs))
+}
+
+/// The warning to give before running a program built by a compiler whose version may generate bytecode
+/// this VM runs differently. Programs without metadata are not checked, as their compiler is unknown.
+pub fn compatibility_warning(file: &str, metadata: Option<&bytecode::Metadata>) -> Option<String> {
+    let metadata = metadata?;
+    let version = env!("CARGO_PKG_VERSION");
+    if metadata.compatible_with(version) {
+        return None;
+    }
+
+    Some(format!(
+        "warning: {} was compiled by compiler {}, which may not be compatible with this VM {}: recompile it if it misbehaves",
+        file, metadata.compiler_version, version
+    ))
+}
@@ -1,26 +1,18 @@
 use std::path::Path;
-use std::time::Duration;
 
 use anyhow::{Error, Result};
-use bytecode::{builtin, read_bytecode};
-use clap::Parser;
-use repl::ignite_repl;
-use runtime::*;
-
-pub use crate::error::*;
-pub use crate::thread::*;
-
-mod error;
-mod micro_code;
-mod repl;
-mod runtime;
-mod thread;
+use bytecode::builtin;
+use clap::{Parser, Subcommand};
+use ignite::*;
 
 #[derive(Parser, Debug)]
 #[command(name = "Ignite")]
 #[command(version = "0.1.0")]
 #[command(about = "Virtual Machine for RustScript", long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// File name of the program to run, must be a .o2 file.
     file: Option<String>,
 
@@ -28,69 +20,108 @@ struct Args {
     #[arg(long, short)]
     repl: bool,
 
-    /// Set custom time quantum for the VM in milliseconds.
-    /// Default is 100ms.
-    #[arg(short, long)]
-    quantum: Option<u64>,
-
-    /// Set custom garbage collection interval for the VM in milliseconds.
-    /// Default is 1000ms.
-    #[arg(short, long)]
-    gc_interval: Option<u64>,
+    #[command(flatten)]
+    runtime: RuntimeArgs,
 
     /// Turn debugging information on
     #[arg(short, long)]
     debug: bool,
 
+    /// Log each executed instruction to stderr.
+    #[arg(long)]
+    trace: bool,
+
+    /// Log scheduling events, e.g. threads spawned, blocked and woken, to stderr.
+    #[arg(long)]
+    sched_events: bool,
+
     /// If present, does not type check in REPL. Ignored if only running bytecode.
     #[arg(short)]
     notype: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the program in the interactive debugger.
+    Debug {
+        /// File name of the program to debug, must be a .o2 file.
+        file: String,
+    },
+    /// Serve the Debug Adapter Protocol on a port of localhost, for debugging from an editor.
+    /// The program is given by the launch request of the client.
+    Dap {
+        /// Port to listen on.
+        #[arg(long, default_value_t = 4711)]
+        port: u16,
+    },
+    /// Resume a program from a snapshot saved by the debugger.
+    Resume {
+        /// File name of the snapshot.
+        file: String,
+    },
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
-    let file_provided = args.file.is_some();
-
-    if args.repl {
-        // TODO: if file provided, run the file and pass generated context to REPL
-        ignite_repl(!args.notype)?;
-        return Ok(()); // REPL done: exit
-    } else if !args.repl && !file_provided {
-        return Err(Error::msg("File should be provided if not launching REPL."));
-    }
-
-    let file = args.file.expect("File was provided");
 
-    // Check if the file exists
-    if !Path::new(&file).exists() {
-        return Err(VmError::FileDoesNotExist(file).into());
+    let (mut rt, debugger) = match args.command {
+        Some(Command::Debug { file }) => (load_program(file)?, true),
+        Some(Command::Dap { port }) => return ignite_dap(port),
+        Some(Command::Resume { file }) => {
+            if !Path::new(&file).exists() {
+                return Err(VmError::FileDoesNotExist(file).into());
+            }
+
+            let mut file = std::fs::File::open(file)?;
+            (Runtime::load_snapshot(&mut file)?, false)
+        }
+        None if args.repl => {
+            // TODO: if file provided, run the file and pass generated context to REPL
+            ignite_repl(!args.notype)?;
+            return Ok(()); // REPL done: exit
+        }
+        None => {
+            let Some(file) = args.file else {
+                return Err(Error::msg("File should be provided if not launching REPL."));
+            };
+            (load_program(file)?, false)
+        }
+    };
+
+    rt.set_config(args.runtime.config().debug(args.debug));
+
+    if args.trace {
+        rt.set_trace_sink(std::io::stderr());
     }
 
-    // check file extension
-    if Path::new(&file).extension().unwrap() != "o2" {
-        return Err(VmError::NotO2File(file).into());
+    if args.sched_events {
+        rt.subscribe(|event: &SchedulerEvent| eprintln!("{}", event));
     }
 
-    // Deserialize the program
-    let mut file = std::fs::File::open(file)?;
-    let bytecode_vec = read_bytecode(&mut file)?;
+    if debugger {
+        return ignite_debugger(rt);
+    }
 
-    let mut rt = Runtime::new(bytecode_vec);
+    rt.set_interrupt_flag(interrupt_on_ctrl_c());
+    args.runtime.start_switch_log(&mut rt)?;
+    let res = run(&mut rt);
+    args.runtime.save_switch_log(&rt)?;
 
-    if let Some(quantum) = args.quantum {
-        rt.set_time_quantum(Duration::from_millis(quantum));
-    }
+    if let Err(err) = res {
+        if !rt.interrupted() {
+            return Err(err);
+        }
 
-    if let Some(gc_interval) = args.gc_interval {
-        rt.set_gc_interval(Duration::from_millis(gc_interval));
+        // Stopped by Ctrl-C, with the stack trace of where
+        eprintln!("{:?}", err);
+        std::process::exit(INTERRUPTED_EXIT_CODE.into());
     }
 
-    if args.debug {
-        rt.set_debug_mode();
+    // The program called exit, so there is no result
+    if let Some(code) = rt.exit_code {
+        std::process::exit(code as i32);
     }
 
-    let rt = run(rt)?;
-
     // Print last value on op stack if there (result of program)
     let top = rt.current_thread.operand_stack.last();
 
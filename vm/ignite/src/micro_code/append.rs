@@ -0,0 +1,67 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use bytecode::{type_of, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a value and an array, and push the array with the value appended.
+/// The array is only copied if it is shared, so an array built on the operand stack is appended to in place.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to append in.
+///
+/// # Errors
+///
+/// If the operand stack does not contain 2 values, or the value below the top is not an array.
+#[inline]
+pub fn append(rt: &mut Runtime) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let arr = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let Value::Array(mut elems) = arr else {
+        return Err(VmError::BadType {
+            expected: "Array".to_string(),
+            found: type_of(&arr).to_string(),
+        }
+        .into());
+    };
+
+    Rc::make_mut(&mut elems).push(val);
+    rt.current_thread.operand_stack.push(Value::Array(elems));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append() {
+        let mut rt = Runtime::new(vec![]);
+        let arr = Value::from(vec![1.into()]);
+        rt.current_thread.operand_stack.push(arr.clone());
+        rt.current_thread.operand_stack.push(2.into());
+        append(&mut rt).unwrap();
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::from(vec![1.into(), 2.into()]))
+        );
+
+        // the shared array is left as is
+        assert_eq!(arr, Value::from(vec![1.into()]));
+
+        rt.current_thread.operand_stack.push(1.into());
+        rt.current_thread.operand_stack.push(2.into());
+        assert!(append(&mut rt).is_err());
+    }
+}
@@ -1,28 +1,108 @@
+use std::{io::Write, time::Duration};
+
 use anyhow::Result;
-use bytecode::{builtin, Value};
+use bytecode::{
+    builtin, AtomicInt, Barrier, BinOp, BoundedQueue, Channel, CondVar, Semaphore, ThreadID, Value,
+    WaitGroup,
+};
 
 use crate::{Runtime, VmError};
 
+use super::{
+    barrier_wait, binop, chan_close, chan_recv, chan_send, cv_notify_all, cv_notify_one, cv_wait,
+    kill, queue_pop, queue_push, wait_timeout, wg_add, wg_wait,
+};
+
 #[inline]
-pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Runtime> {
+pub fn apply_builtin(rt: &mut Runtime, sym: &str, args: Vec<Value>) -> Result<()> {
+    rt.check_capability(sym)?;
+
     match sym {
         builtin::READ_LINE_SYM => {
-            let input = builtin::read_line_impl()?;
-            rt.current_thread.operand_stack.push(Value::String(input));
+            let input = match rt.stdin.as_mut() {
+                Some(stdin) => builtin::read_line_impl(stdin)?,
+                None => builtin::read_line_impl(&mut std::io::stdin().lock())?,
+            };
+            rt.current_thread.operand_stack.push(input.into());
         }
         builtin::PRINT_SYM => {
             for arg in args {
-                builtin::print_impl(&arg);
+                write!(rt.stdout, "{}", arg)?;
             }
         }
         builtin::PRINTLN_SYM => {
             for arg in args[..args.len() - 1].iter() {
-                builtin::print_impl(arg);
+                write!(rt.stdout, "{}", arg)?;
             }
             if let Some(arg) = args.last() {
-                builtin::println_impl(arg);
+                writeln!(rt.stdout, "{}", arg)?;
             }
         }
+        builtin::READ_FILE_SYM | builtin::READ_LINES_SYM => {
+            let path = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let res = if sym == builtin::READ_FILE_SYM {
+                builtin::read_file_impl(path)?
+            } else {
+                builtin::read_lines_impl(path)?
+            };
+            rt.current_thread.operand_stack.push(res);
+        }
+        builtin::WRITE_FILE_SYM | builtin::APPEND_FILE_SYM => {
+            let path = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let contents = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let res = if sym == builtin::WRITE_FILE_SYM {
+                builtin::write_file_impl(path, contents)?
+            } else {
+                builtin::append_file_impl(path, contents)?
+            };
+            rt.current_thread.operand_stack.push(res);
+        }
+        builtin::FILE_EXISTS_SYM => {
+            let path = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let exists = builtin::file_exists_impl(path)?;
+            rt.current_thread.operand_stack.push(Value::Bool(exists));
+        }
+        builtin::JSON_PARSE_SYM => {
+            let s = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let example = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let res = builtin::json_parse_impl(s, example)?;
+            rt.current_thread.operand_stack.push(res);
+        }
+        builtin::JSON_STRINGIFY_SYM => {
+            let val = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let pretty = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let res = builtin::json_stringify_impl(val, pretty)?;
+            rt.current_thread.operand_stack.push(res);
+        }
         builtin::STRING_LEN_SYM => {
             let s = args.first().ok_or(VmError::InsufficientArguments {
                 expected: 1,
@@ -32,6 +112,86 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             let len = builtin::string_len_impl(s)?;
             rt.current_thread.operand_stack.push(Value::Int(len as i64));
         }
+        builtin::BYTES_SYM => {
+            let s = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let bytes = builtin::bytes_impl(s)?;
+            rt.current_thread.operand_stack.push(bytes);
+        }
+        builtin::LEN_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let len = builtin::len_impl(a)?;
+            rt.current_thread.operand_stack.push(Value::Int(len as i64));
+        }
+        builtin::TYPE_OF_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            rt.current_thread
+                .operand_stack
+                .push(builtin::type_of_impl(x));
+        }
+        builtin::IS_INT_SYM
+        | builtin::IS_FLOAT_SYM
+        | builtin::IS_BOOL_SYM
+        | builtin::IS_STRING_SYM
+        | builtin::IS_UNIT_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let is = builtin::is_type_impl(sym, x).expect("sym is an is_ builtin");
+            rt.current_thread.operand_stack.push(Value::Bool(is));
+        }
+        builtin::SOME_SYM | builtin::OK_SYM | builtin::ERR_SYM => {
+            let x = args
+                .into_iter()
+                .next()
+                .ok_or(VmError::InsufficientArguments {
+                    expected: 1,
+                    got: 0,
+                })?;
+
+            let variant = builtin::make_impl(sym, x).expect("sym is a variant constructor");
+            rt.current_thread.operand_stack.push(variant);
+        }
+        builtin::IS_SOME_SYM | builtin::IS_NONE_SYM | builtin::IS_OK_SYM | builtin::IS_ERR_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let is = builtin::is_variant_impl(sym, x).expect("sym is an is_ builtin");
+            rt.current_thread.operand_stack.push(Value::Bool(is));
+        }
+        builtin::UNWRAP_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let val = builtin::unwrap_impl(x)?;
+            rt.current_thread.operand_stack.push(val);
+        }
+        builtin::UNWRAP_ERR_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let val = builtin::unwrap_err_impl(x)?;
+            rt.current_thread.operand_stack.push(val);
+        }
         builtin::MIN_SYM => {
             let v1 = args.first().ok_or(VmError::InsufficientArguments {
                 expected: 2,
@@ -103,6 +263,24 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             let sqrt = builtin::sqrt_impl(x)?;
             rt.current_thread.operand_stack.push(sqrt);
         }
+        builtin::IS_NAN_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let is_nan = builtin::is_nan_impl(x)?;
+            rt.current_thread.operand_stack.push(is_nan);
+        }
+        builtin::IS_INF_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let is_inf = builtin::is_inf_impl(x)?;
+            rt.current_thread.operand_stack.push(is_inf);
+        }
         builtin::LOG_SYM => {
             let x = args.first().ok_or(VmError::InsufficientArguments {
                 expected: 1,
@@ -125,6 +303,19 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             let pow = builtin::pow_impl(x, y)?;
             rt.current_thread.operand_stack.push(pow);
         }
+        builtin::RANDOM_INT_SYM => {
+            let lo = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let hi = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let n = rt.random_int(lo.try_into()?, hi.try_into()?)?;
+            rt.current_thread.operand_stack.push(n.into());
+        }
         builtin::ITOA_SYM => {
             let x = args.first().ok_or(VmError::InsufficientArguments {
                 expected: 1,
@@ -177,20 +368,455 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
 
             builtin::sem_set_impl(sem, val)?;
         }
+        builtin::THREAD_ID_SYM => {
+            let h = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let tid = builtin::thread_id_impl(h)?;
+            rt.current_thread.operand_stack.push(tid);
+        }
+        builtin::IS_FINISHED_SYM => {
+            let h = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let tid: ThreadID = h.try_into()?;
+            let is_finished = rt.is_finished(tid);
+            rt.current_thread
+                .operand_stack
+                .push(Value::Bool(is_finished));
+        }
+        builtin::KILL_SYM => {
+            let h = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let tid: ThreadID = h.try_into()?;
+            kill(rt, tid)?;
+        }
+        // The value of the key in the current thread, which starts as init the first time the thread asks for it.
+        // The key may have been set to a value of another type than init's, which the checker can't tell as
+        // keys are strings computed at runtime, so the type of the value is checked against init's.
+        builtin::THREAD_LOCAL_SYM => {
+            let key = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let init = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let key: String = key.clone().try_into()?;
+            let val = rt
+                .current_thread
+                .locals
+                .entry(key.clone())
+                .or_insert_with(|| init.clone())
+                .clone();
+            if builtin::type_name(&val) != builtin::type_name(init) {
+                return Err(VmError::TypeMismatch {
+                    expected: format!("{} for thread local '{}'", builtin::type_name(init), key),
+                    found: builtin::type_name(&val).to_string(),
+                }
+                .into());
+            }
+            rt.current_thread.operand_stack.push(val);
+        }
+        builtin::SET_THREAD_LOCAL_SYM => {
+            let key = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let val = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let key: String = key.clone().try_into()?;
+            rt.current_thread.locals.insert(key, val.clone());
+        }
+        builtin::PRIORITY_SYM => {
+            let priority = rt.effective_priority(&rt.current_thread);
+            rt.current_thread.operand_stack.push(priority.into());
+        }
+        builtin::SET_PRIORITY_SYM => {
+            let n = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            rt.current_thread.priority = n.try_into()?;
+        }
+        builtin::EXIT_SYM => {
+            let code = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            // Stops every thread, not just the one calling exit
+            rt.exit_code = Some(code.try_into()?);
+            rt.done = true;
+        }
+        builtin::PANIC_SYM => {
+            let msg = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            builtin::panic_impl(msg.clone())?;
+        }
+        builtin::CV_CREATE_SYM => {
+            let cv = builtin::cv_create_impl();
+            rt.current_thread.operand_stack.push(cv);
+        }
+        builtin::CV_WAIT_SYM => {
+            let cv = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let mutex = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let cv: CondVar = cv.clone().try_into()?;
+            let mutex: Semaphore = mutex.clone().try_into()?;
+            cv_wait(rt, cv, mutex)?;
+        }
+        builtin::CV_NOTIFY_ONE_SYM => {
+            let cv = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let cv: CondVar = cv.clone().try_into()?;
+            cv_notify_one(rt, cv)?;
+        }
+        builtin::CV_NOTIFY_ALL_SYM => {
+            let cv = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let cv: CondVar = cv.clone().try_into()?;
+            cv_notify_all(rt, cv)?;
+        }
+        builtin::WAIT_TIMEOUT_SYM => {
+            let sem = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let ms = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let sem: Semaphore = sem.clone().try_into()?;
+            let ms: i64 = ms.try_into()?;
+            let timeout = Duration::from_millis(ms.max(0) as u64);
+            wait_timeout(rt, sem, timeout)?;
+        }
+        builtin::BARRIER_CREATE_SYM => {
+            let n = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let n: i64 = n.try_into()?;
+            if n <= 0 {
+                return Err(VmError::IllegalArgument(format!(
+                    "barrier needs a positive number of parties, got {}",
+                    n
+                ))
+                .into());
+            }
+
+            rt.current_thread
+                .operand_stack
+                .push(Barrier::new(n as u64).into());
+        }
+        builtin::BARRIER_WAIT_SYM => {
+            let b = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let b: Barrier = b.clone().try_into()?;
+            barrier_wait(rt, b)?;
+        }
+        builtin::WG_CREATE_SYM => {
+            let wg = builtin::wg_create_impl();
+            rt.current_thread.operand_stack.push(wg);
+        }
+        builtin::WG_ADD_SYM => {
+            let wg = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let n = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let wg: WaitGroup = wg.clone().try_into()?;
+            let n: i64 = n.try_into()?;
+            wg_add(rt, wg, n)?;
+        }
+        builtin::WG_DONE_SYM => {
+            let wg = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let wg: WaitGroup = wg.clone().try_into()?;
+            wg_add(rt, wg, -1)?;
+        }
+        builtin::WG_WAIT_SYM => {
+            let wg = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let wg: WaitGroup = wg.clone().try_into()?;
+            wg_wait(rt, wg)?;
+        }
+        builtin::MUTEX_SYM => {
+            let m = builtin::mutex_impl();
+            rt.current_thread.operand_stack.push(m);
+        }
+        builtin::RWLOCK_SYM => {
+            let rw = builtin::rwlock_impl();
+            rt.current_thread.operand_stack.push(rw);
+        }
+        builtin::ATOMIC_INT_SYM => {
+            let v = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let a = builtin::atomic_int_impl(v)?;
+            rt.current_thread.operand_stack.push(a);
+        }
+        // The sum overflows as the runtime is set to, and the value held before it is given back.
+        builtin::FETCH_ADD_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let v = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let a: AtomicInt = a.clone().try_into()?;
+            let v: i64 = v.try_into()?;
+
+            let mut guard = a.lock().unwrap();
+            let prev = *guard;
+            *guard = rt.int_overflow.apply(BinOp::Add, prev, v)?;
+            drop(guard); // Unlock the atomic int.
+
+            rt.current_thread.operand_stack.push(Value::Int(prev));
+        }
+        builtin::LOAD_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let v = builtin::load_impl(a)?;
+            rt.current_thread.operand_stack.push(v);
+        }
+        builtin::STORE_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let v = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            builtin::store_impl(a, v)?;
+        }
+        builtin::COMPARE_AND_SWAP_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let current = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let new = args.get(2).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+
+            let prev = builtin::compare_and_swap_impl(a, current, new)?;
+            rt.current_thread.operand_stack.push(prev);
+        }
+        builtin::CHANNEL_SYM => {
+            let cap = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let cap: i64 = cap.try_into()?;
+            if cap <= 0 {
+                return Err(VmError::IllegalArgument(format!(
+                    "channel needs a positive capacity, got {}",
+                    cap
+                ))
+                .into());
+            }
+
+            rt.current_thread
+                .operand_stack
+                .push(Channel::new(cap as usize).into());
+        }
+        builtin::SEND_SYM => {
+            let ch = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let val = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let ch: Channel = ch.clone().try_into()?;
+            chan_send(rt, ch, val.clone())?;
+        }
+        builtin::RECV_SYM => {
+            let ch = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let ch: Channel = ch.clone().try_into()?;
+            chan_recv(rt, ch)?;
+        }
+        builtin::CLOSE_SYM => {
+            let ch = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let ch: Channel = ch.clone().try_into()?;
+            chan_close(rt, ch)?;
+        }
+        builtin::BOUNDED_QUEUE_SYM => {
+            let cap = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let cap: i64 = cap.try_into()?;
+            if cap <= 0 {
+                return Err(VmError::IllegalArgument(format!(
+                    "bounded_queue needs a positive capacity, got {}",
+                    cap
+                ))
+                .into());
+            }
+
+            rt.current_thread
+                .operand_stack
+                .push(BoundedQueue::new(cap as usize).into());
+        }
+        builtin::PUSH_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let val = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let q: BoundedQueue = q.clone().try_into()?;
+            queue_push(rt, q, val.clone())?;
+        }
+        builtin::POP_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let q: BoundedQueue = q.clone().try_into()?;
+            queue_pop(rt, q)?;
+        }
+        // The compiler passes the source text of the condition as the last argument.
+        // A comparison is passed as its operands and operator, so the report can show both sides,
+        // otherwise the condition is passed with unit in place of the second operand and operator.
+        builtin::ASSERT_SYM => match args.as_slice() {
+            [cond, _, Value::Unit, expr] => builtin::assert_impl(cond, expr, None)?,
+            [lhs, rhs, op, expr] => {
+                let op: String = op.clone().try_into()?;
+                let op = match op.as_str() {
+                    "<" | ">" | "<=" | ">=" | "==" => BinOp::from(op.as_str()),
+                    _ => return Err(VmError::IllegalArgument(op).into()),
+                };
+
+                rt.current_thread.operand_stack.push(lhs.clone());
+                rt.current_thread.operand_stack.push(rhs.clone());
+                binop(rt, op)?;
+
+                let cond = rt
+                    .current_thread
+                    .operand_stack
+                    .pop()
+                    .ok_or(VmError::OperandStackUnderflow)?;
+                builtin::assert_impl(&cond, expr, Some((lhs, rhs)))?;
+            }
+            _ => {
+                return Err(VmError::InsufficientArguments {
+                    expected: 4,
+                    got: args.len(),
+                }
+                .into())
+            }
+        },
+        // The compiler passes the source text of both sides after their values.
+        builtin::ASSERT_EQ_SYM => match args.as_slice() {
+            [left, right, left_expr, right_expr] => {
+                builtin::assert_eq_impl(left, right, left_expr, right_expr)?
+            }
+            _ => {
+                return Err(VmError::InsufficientArguments {
+                    expected: 4,
+                    got: args.len(),
+                }
+                .into())
+            }
+        },
         _ => {
-            return Err(VmError::UnknownBuiltin {
-                sym: sym.to_string(),
+            if let Some(f) = rt.async_host_fn(sym) {
+                return rt.await_host(f(args));
             }
-            .into());
+
+            let f = rt.host_fn(sym).ok_or_else(|| VmError::UnknownBuiltin {
+                sym: sym.to_string(),
+            })?;
+
+            let val = f(args)?;
+            rt.current_thread.operand_stack.push(val);
         }
     }
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{SeededRng, ThreadState, MAIN_THREAD_ID};
     use anyhow::Ok;
     use bytecode::{builtin::*, type_of, Semaphore};
 
@@ -201,28 +827,44 @@ mod tests {
 
         // Stdout
         let sym = PRINT_SYM;
-        let args = vec![Value::String(hello_world.clone())];
+        let args = vec![Value::from(hello_world.as_str())];
         println!("Expect to see 'Hello, world!':");
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         println!();
 
         let sym = PRINTLN_SYM;
-        let args = vec![Value::String(hello_world.clone())];
+        let args = vec![Value::from(hello_world.as_str())];
         println!("Expect to see 'Hello, world!':");
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
 
         let sym = STRING_LEN_SYM;
-        let args = vec![Value::String(hello_world.clone())];
-        rt = apply_builtin(rt, sym, args)?;
+        let args = vec![Value::from(hello_world.as_str())];
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(hello_world.clone().len() as i64),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
+        let sym = LEN_SYM;
+        let args = vec![Value::from(vec![Value::Int(1), Value::Int(2)])];
+        apply_builtin(&mut rt, sym, args)?;
+        assert_eq!(
+            Value::Int(2),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let sym = BYTES_SYM;
+        let args = vec![Value::from("é")];
+        apply_builtin(&mut rt, sym, args)?;
+        assert_eq!(
+            Value::from(vec![Value::Int(195), Value::Int(169)]),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
         // Conv
         let sym = INT_TO_FLOAT_SYM;
         let args = vec![Value::Int(42)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
 
         let expected = Value::Float(42.0);
         let actual = rt.current_thread.operand_stack.pop().unwrap();
@@ -230,44 +872,44 @@ mod tests {
 
         let sym = FLOAT_TO_INT_SYM;
         let args = vec![Value::Float(42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
 
         let expected = Value::Int(42);
         let actual = rt.current_thread.operand_stack.pop().unwrap();
         assert_eq!(expected, actual);
 
         let sym = ATOI_SYM;
-        let args = vec![Value::String("42".to_string())];
-        rt = apply_builtin(rt, sym, args)?;
+        let args = vec![Value::from("42")];
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(42),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
-        let args: Vec<Value> = vec![Value::String("forty-two".to_string())];
-        let result = apply_builtin(rt, sym, args);
+        let args: Vec<Value> = vec![Value::from("forty-two")];
+        let result = apply_builtin(&mut rt, sym, args);
         assert!(result.is_err());
 
         let mut rt = Runtime::default();
         let sym = ITOA_SYM;
         let args = vec![Value::Int(42)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
-            Value::String("42".to_string()),
+            Value::from("42"),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         // Math
         let sym = MIN_SYM;
         let args = vec![Value::Int(42), Value::Int(24)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(24),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(42.0), Value::Float(24.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(24.0),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -275,14 +917,14 @@ mod tests {
 
         let sym = MAX_SYM;
         let args = vec![Value::Int(42), Value::Int(24)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(42),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(42.0), Value::Float(24.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -290,14 +932,14 @@ mod tests {
 
         let sym = ABS_SYM;
         let args = vec![Value::Int(-42)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(42),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(-42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -305,14 +947,14 @@ mod tests {
 
         let sym = COS_SYM;
         let args = vec![Value::Float(0.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(0.0_f64.cos()),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(std::f64::consts::PI)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(std::f64::consts::PI.cos()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -320,14 +962,14 @@ mod tests {
 
         let sym = SIN_SYM;
         let args = vec![Value::Float(0.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(0.0),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(std::f64::consts::PI)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(std::f64::consts::PI.sin()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -335,14 +977,14 @@ mod tests {
 
         let sym = TAN_SYM;
         let args = vec![Value::Float(0.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(0.0),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(std::f64::consts::PI)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(std::f64::consts::PI.tan()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -350,35 +992,65 @@ mod tests {
 
         let sym = SQRT_SYM;
         let args = vec![Value::Float(42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0_f64.sqrt()),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(102934.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(102934.0_f64.sqrt()),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
+        let args = vec![Value::Float(f64::NAN)];
+        apply_builtin(&mut rt, IS_NAN_SYM, args)?;
+        assert_eq!(
+            Value::Bool(true),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let args = vec![Value::Float(f64::NEG_INFINITY)];
+        apply_builtin(&mut rt, IS_INF_SYM, args)?;
+        assert_eq!(
+            Value::Bool(true),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let args = vec![Value::Float(1.0)];
+        apply_builtin(&mut rt, IS_INF_SYM, args)?;
+        assert_eq!(
+            Value::Bool(false),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
         let sym = POW_SYM;
         let args = vec![Value::Float(2.0), Value::Float(3.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(2.0_f64.powf(3.0)),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(2.0), Value::Int(3)];
-        let result = apply_builtin(rt, sym, args);
+        let result = apply_builtin(&mut rt, sym, args);
         assert!(result.is_err());
 
+        rt.set_rng(SeededRng::new(7));
+        for _ in 0..10 {
+            apply_builtin(&mut rt, RANDOM_INT_SYM, vec![Value::Int(-2), Value::Int(3)])?;
+            let n: i64 = rt.current_thread.operand_stack.pop().unwrap().try_into()?;
+            assert!((-2..3).contains(&n));
+        }
+        let args = vec![Value::Int(3), Value::Int(3)];
+        assert!(apply_builtin(&mut rt, RANDOM_INT_SYM, args).is_err());
+
         let mut rt = Runtime::default();
         let sym = LOG_SYM;
         let args = vec![Value::Float(42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0_f64.log(10.0)),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -386,7 +1058,7 @@ mod tests {
 
         let sym = SEM_CREATE_SYM;
         let args = vec![];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             type_of(&Value::Semaphore(Semaphore::default())),
             type_of(&rt.current_thread.operand_stack.pop().unwrap())
@@ -395,9 +1067,153 @@ mod tests {
         let sym = SEM_SET_SYM;
         let sem = Semaphore::default();
         let args = vec![sem.clone().into(), Value::Int(42)];
-        _ = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         let sem_guard = sem.lock().unwrap();
         assert_eq!(42, *sem_guard);
+        drop(sem_guard);
+
+        // Thread
+        let sym = THREAD_ID_SYM;
+        let args = vec![Value::Int(MAIN_THREAD_ID)];
+        apply_builtin(&mut rt, sym, args)?;
+        assert_eq!(
+            Value::Int(MAIN_THREAD_ID),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let sym = IS_FINISHED_SYM;
+        let args = vec![Value::Int(MAIN_THREAD_ID)];
+        apply_builtin(&mut rt, sym, args)?;
+        assert_eq!(
+            Value::Bool(false),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        rt.set_thread_state(MAIN_THREAD_ID, ThreadState::Done);
+        let args = vec![Value::Int(MAIN_THREAD_ID)];
+        apply_builtin(&mut rt, sym, args)?;
+        assert_eq!(
+            Value::Bool(true),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        // Thread locals
+        let args = vec![Value::from("seed"), Value::Int(1)];
+        apply_builtin(&mut rt, THREAD_LOCAL_SYM, args.clone())?;
+        assert_eq!(
+            Value::Int(1),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+        let set_args = vec![Value::from("seed"), Value::Int(2)];
+        apply_builtin(&mut rt, SET_THREAD_LOCAL_SYM, set_args)?;
+        apply_builtin(&mut rt, THREAD_LOCAL_SYM, args.clone())?;
+        assert_eq!(
+            Value::Int(2),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        // The value must have the type of init
+        let str_args = vec![Value::from("seed"), Value::from("x")];
+        let err = apply_builtin(&mut rt, THREAD_LOCAL_SYM, str_args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch: expected str for thread local 'seed', found int"
+        );
+
+        // A spawned thread starts without the values of the thread spawning it
+        let child = rt.current_thread.spawn_child(MAIN_THREAD_ID + 1, 0);
+        let main = std::mem::replace(&mut rt.current_thread, child);
+        apply_builtin(&mut rt, THREAD_LOCAL_SYM, args)?;
+        assert_eq!(
+            Value::Int(1),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+        rt.current_thread = main;
+
+        // Priority
+        apply_builtin(&mut rt, SET_PRIORITY_SYM, vec![Value::Int(3)])?;
+        apply_builtin(&mut rt, PRIORITY_SYM, vec![])?;
+        assert_eq!(
+            Value::Int(3),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+        rt.current_thread.priority = 0;
+
+        // Option and result
+        let args = vec![Value::Int(42)];
+        apply_builtin(&mut rt, SOME_SYM, args)?;
+        let some = rt.current_thread.operand_stack.pop().unwrap();
+        assert_eq!(some.to_string(), "Some(42)");
+
+        apply_builtin(&mut rt, IS_SOME_SYM, vec![some.clone()])?;
+        assert_eq!(
+            Value::Bool(true),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        apply_builtin(&mut rt, UNWRAP_SYM, vec![some])?;
+        assert_eq!(
+            Value::Int(42),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let res = apply_builtin(&mut rt, UNWRAP_SYM, vec![none()]);
+        assert_eq!(res.unwrap_err().to_string(), "Called unwrap on None");
+
+        // Atomic int
+        apply_builtin(&mut rt, ATOMIC_INT_SYM, vec![Value::Int(1)])?;
+        let a = rt.current_thread.operand_stack.pop().unwrap();
+        apply_builtin(&mut rt, FETCH_ADD_SYM, vec![a.clone(), Value::Int(2)])?;
+        assert_eq!(
+            Value::Int(1),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let args = vec![a.clone(), Value::Int(1), Value::Int(5)];
+        apply_builtin(&mut rt, COMPARE_AND_SWAP_SYM, args)?;
+        assert_eq!(
+            Value::Int(3),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+        let args = vec![a.clone(), Value::Int(3), Value::Int(5)];
+        apply_builtin(&mut rt, COMPARE_AND_SWAP_SYM, args)?;
+        assert_eq!(
+            Value::Int(3),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        apply_builtin(&mut rt, LOAD_SYM, vec![a.clone()])?;
+        assert_eq!(
+            Value::Int(5),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        apply_builtin(&mut rt, STORE_SYM, vec![a.clone(), Value::Int(i64::MAX)])?;
+        let res = apply_builtin(&mut rt, FETCH_ADD_SYM, vec![a.clone(), Value::Int(1)]);
+        assert!(res.is_err());
+        apply_builtin(&mut rt, LOAD_SYM, vec![a])?;
+        assert_eq!(
+            Value::Int(i64::MAX),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        // Bounded queue
+        let res = apply_builtin(&mut rt, BOUNDED_QUEUE_SYM, vec![Value::Int(0)]);
+        assert!(res.is_err());
+        apply_builtin(&mut rt, BOUNDED_QUEUE_SYM, vec![Value::Int(2)])?;
+        let q = rt.current_thread.operand_stack.pop().unwrap();
+        apply_builtin(&mut rt, PUSH_SYM, vec![q.clone(), Value::Int(1)])?;
+        apply_builtin(&mut rt, PUSH_SYM, vec![q.clone(), Value::Int(2)])?;
+        assert!(rt.current_thread.operand_stack.is_empty());
+        apply_builtin(&mut rt, POP_SYM, vec![q])?;
+        assert_eq!(
+            Value::Int(1),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        // Process
+        let res = apply_builtin(&mut rt, PANIC_SYM, vec![Value::from("boom")]);
+        assert_eq!(res.unwrap_err().to_string(), "boom");
 
         Ok(())
     }
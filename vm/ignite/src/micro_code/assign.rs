@@ -16,7 +16,7 @@ use crate::{Runtime, VmError};
 /// If the stack is empty.
 /// If the symbol is not found in the environment chain.
 #[inline]
-pub fn assign(mut rt: Runtime, sym: Symbol) -> Result<Runtime> {
+pub fn assign(rt: &mut Runtime, sym: Symbol) -> Result<()> {
     let val = rt
         .current_thread
         .operand_stack
@@ -29,7 +29,7 @@ pub fn assign(mut rt: Runtime, sym: Symbol) -> Result<Runtime> {
         .borrow_mut()
         .update(sym, val)?;
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -49,24 +49,14 @@ mod tests {
             .set("x", Value::Unitialized);
         rt.current_thread.operand_stack.push(Value::Int(42));
 
-        rt = assign(rt, "x".to_string()).unwrap();
+        assign(&mut rt, "x".into()).unwrap();
 
         assert_ne!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"x".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("x")?,
             Value::Unitialized
         );
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"x".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("x")?,
             Value::Int(42)
         );
 
@@ -88,24 +78,19 @@ mod tests {
 
         rt.current_thread.env = child_weak;
         rt.current_thread.operand_stack.push(Value::Int(123));
-        rt = assign(rt, "x".to_string()).unwrap();
+        assign(&mut rt, "x".into()).unwrap();
 
-        assert_eq!(parent_env.borrow().get(&"x".to_string())?, Value::Int(123));
+        assert_eq!(parent_env.borrow().get("x")?, Value::Int(123));
         // The child environment should not be updated.
-        assert!(!child_env.borrow().env.contains_key(&"x".to_string()));
+        assert!(!child_env.borrow().env.contains_key(&"x".into()));
 
         rt.current_thread.operand_stack.push(Value::Int(789));
-        rt = assign(rt, "y".to_string()).unwrap();
+        assign(&mut rt, "y".into()).unwrap();
 
-        assert!(parent_env.borrow().get(&"y".to_string()).is_err());
-        assert_eq!(child_env.borrow().get(&"y".to_string())?, Value::Int(789));
+        assert!(parent_env.borrow().get("y").is_err());
+        assert_eq!(child_env.borrow().get("y")?, Value::Int(789));
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"y".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("y")?,
             Value::Int(789)
         );
 
@@ -0,0 +1,65 @@
+use anyhow::Result;
+
+use crate::{Runtime, VmError};
+
+/// Assign a value to a slot resolved at compile time.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the instruction on.
+///
+/// * `depth` - The number of frames to walk up the environment chain.
+///
+/// * `idx` - The index of the slot in that frame.
+///
+/// # Errors
+///
+/// If the stack is empty.
+/// If the frame or the slot is not found.
+#[inline]
+pub fn assign_slot(rt: &mut Runtime, depth: usize, idx: usize) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    rt.current_thread
+        .env
+        .upgrade()
+        .ok_or(VmError::EnvironmentDroppedError)?
+        .borrow_mut()
+        .update_slot(depth, idx, val)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use crate::extend_environment;
+
+    use super::*;
+
+    #[test]
+    fn test_assign_slot() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["x"], vec![Value::Unitialized])?;
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["y"], vec![Value::Unitialized])?;
+
+        rt.current_thread.operand_stack.push(Value::Int(42));
+        assign_slot(&mut rt, 1, 0)?;
+        rt.current_thread.operand_stack.push(Value::Int(43));
+        assign_slot(&mut rt, 0, 0)?;
+
+        let env = rt.current_thread.env.upgrade().unwrap();
+        assert_eq!(env.borrow().get("x")?, Value::Int(42));
+        assert_eq!(env.borrow().get("y")?, Value::Int(43));
+
+        rt.current_thread.operand_stack.push(Value::Int(44));
+        assert!(assign_slot(&mut rt, 2, 0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Ok, Result};
+use bytecode::Barrier;
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, WakeSource};
+
+/// Arrive at the barrier and wait for the other parties.
+/// If the current thread is the last party to arrive, all threads waiting on the barrier are moved
+/// to the ready queue and the barrier is reset so it can be reused. The current thread continues execution.
+///
+/// Otherwise, the current thread is blocked.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to arrive at the barrier in.
+///
+/// * `barrier` - The barrier to arrive at.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn barrier_wait(rt: &mut Runtime, barrier: Barrier) -> Result<()> {
+    let mut state = barrier.lock().unwrap();
+    state.arrived += 1;
+
+    if state.arrived >= state.parties {
+        state.arrived = 0;
+        drop(state); // Unlock the barrier.
+
+        rt.wake_blocked(|source| source.is_barrier(&barrier));
+        return Ok(());
+    }
+
+    drop(state); // Unlock the barrier.
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::Barrier),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, vec![WakeSource::Barrier(barrier)]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        micro_code::{spawn, yield_},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_barrier_wait() -> Result<()> {
+        let mut rt = Runtime::default();
+        let barrier = Barrier::new(2);
+        spawn(&mut rt, 0, 0)?;
+
+        // The main thread arrives first and is blocked.
+        barrier_wait(&mut rt, barrier.clone())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+
+        // The child thread arrives last, releasing the main thread.
+        barrier_wait(&mut rt, barrier.clone())?;
+        assert!(rt.blocked_queue.is_empty());
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        assert_eq!(barrier.lock().unwrap().arrived, 0);
+
+        yield_(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        Ok(())
+    }
+}
@@ -1,14 +1,24 @@
 use anyhow::Result;
-use bytecode::{type_of, BinOp, Value};
+use bytecode::{structural_eq, type_of, BinOp, Symbol, Value};
 
-use crate::{Runtime, VmError};
+use crate::{micro_code::call, Runtime, VmError};
+
+// The methods of structs that + and == call
+const ADD_METHOD: &str = "add";
+const EQ_METHOD: &str = "eq";
 
 /// Executes a binary operation on the top two values of the stack.
 /// It pops the two values off the top of the stack, applies the
 /// operation, and pushes the result back onto the stack.
 /// Note the top of the stack is the right-hand side of the operation.
 /// The second-to-top of the stack is the left-hand side of the operation.
-/// The two values must be of the same type.
+/// The two values must be of the same type. Ints and floats are not converted implicitly,
+/// so arithmetic and comparisons between them fail, as they do in the type checker.
+/// Float arithmetic follows IEEE 754, e.g. `1.0 / 0.0` is infinity and `0.0 / 0.0` is NaN,
+/// while dividing an int by 0 is an error.
+/// Equality is structural, as described by `bytecode::structural_eq`, and strings are ordered lexicographically.
+/// Structs overload `+` and `==` with their `add` and `eq` methods, which are called with the operands
+/// and return to the instruction after this one. Structs without an `eq` method are compared structurally.
 ///
 /// # Arguments
 ///
@@ -19,9 +29,10 @@ use crate::{Runtime, VmError};
 /// # Errors
 ///
 /// If the stack has fewer than two values or the operation is not supported
-/// for the types of the values on the stack.
+/// for the types of the values on the stack, or overloaded for the structs on it, if an int is divided by 0,
+/// or if int arithmetic overflows and the runtime is set to trap on overflow.
 #[inline]
-pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
+pub fn binop(rt: &mut Runtime, op: BinOp) -> Result<()> {
     let rhs_val = rt
         .current_thread
         .operand_stack
@@ -33,39 +44,59 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?;
 
-    match (lhs_val.clone(), rhs_val.clone()) {
-        (Value::Unit, Value::Unit) => {
-            let result = match op {
-                BinOp::Eq => Value::Bool(true),
-                _ => {
-                    return Err(VmError::UnsupportedOperation(
-                        op.into(),
-                        type_of(&rhs_val).to_string(),
-                    )
-                    .into())
-                }
-            };
-            rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+    if let Value::Struct(instance) = &lhs_val {
+        let method = match op {
+            BinOp::Add => Some(ADD_METHOD),
+            BinOp::Eq => Some(EQ_METHOD),
+            _ => None,
+        };
+        let closure = method.and_then(|method| {
+            let methods = instance.ty.methods.borrow();
+            methods.get(&Symbol::from(method)).cloned()
+        });
+
+        if let Some(closure) = closure {
+            rt.current_thread.operand_stack.push(closure);
+            rt.current_thread.operand_stack.push(lhs_val);
+            rt.current_thread.operand_stack.push(rhs_val);
+            return call(rt, 2);
+        }
+    }
+
+    let is_struct = |val: &Value| matches!(val, Value::Struct(_));
+    if op != BinOp::Eq && (is_struct(&lhs_val) || is_struct(&rhs_val)) {
+        let name = |val: &Value| match val {
+            Value::Struct(instance) => instance.ty.name.to_string(),
+            _ => type_of(val).to_string(),
+        };
+        return Err(VmError::NoOverload {
+            op: op.into(),
+            lhs: name(&lhs_val),
+            rhs: name(&rhs_val),
+        }
+        .into());
+    }
+
+    // Equality is structural and shared with assert_eq, values that can't be compared fall through to the errors below
+    if op == BinOp::Eq {
+        if let Some(eq) = structural_eq(&lhs_val, &rhs_val) {
+            rt.current_thread.operand_stack.push(Value::Bool(eq));
+            return Ok(());
         }
+    }
+
+    match (lhs_val.clone(), rhs_val.clone()) {
         (Value::Int(lhs), Value::Int(rhs)) => {
             let result = match op {
-                BinOp::Add => Value::Int(lhs + rhs),  // Addition
-                BinOp::Sub => Value::Int(lhs - rhs),  // Subtraction
-                BinOp::Mul => Value::Int(lhs * rhs),  // Multiplication
-                BinOp::Div => Value::Int(lhs / rhs),  // Division
-                BinOp::Mod => Value::Int(lhs % rhs),  // Modulus
-                BinOp::Gt => Value::Bool(lhs > rhs),  // Greater Than
-                BinOp::Lt => Value::Bool(lhs < rhs),  // Less Than
-                BinOp::Eq => Value::Bool(lhs == rhs), // Equality
-                BinOp::And => {
-                    return Err(VmError::UnsupportedOperation(
-                        op.into(),
-                        type_of(&rhs_val).to_string(),
-                    )
-                    .into())
+                // Addition, subtraction, multiplication, division and modulus, overflowing as the runtime is set to
+                BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                    Value::Int(rt.int_overflow.apply(op, lhs, rhs)?)
                 }
-                BinOp::Or => {
+                BinOp::Gt => Value::Bool(lhs > rhs), // Greater Than
+                BinOp::Lt => Value::Bool(lhs < rhs), // Less Than
+                BinOp::Ge => Value::Bool(lhs >= rhs), // Greater Than or Equal
+                BinOp::Le => Value::Bool(lhs <= rhs), // Less Than or Equal
+                _ => {
                     return Err(VmError::UnsupportedOperation(
                         op.into(),
                         type_of(&rhs_val).to_string(),
@@ -74,7 +105,7 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::Float(lhs), Value::Float(rhs)) => {
             let result = match op {
@@ -84,22 +115,9 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 BinOp::Div => Value::Float(lhs / rhs), // Division
                 BinOp::Gt => Value::Bool(lhs > rhs),   // Greater Than
                 BinOp::Lt => Value::Bool(lhs < rhs),   // Less Than
-                BinOp::Eq => Value::Bool(lhs == rhs),  // Equality
-                BinOp::Or => {
-                    return Err(VmError::UnsupportedOperation(
-                        op.into(),
-                        type_of(&rhs_val).to_string(),
-                    )
-                    .into())
-                }
-                BinOp::And => {
-                    return Err(VmError::UnsupportedOperation(
-                        op.into(),
-                        type_of(&rhs_val).to_string(),
-                    )
-                    .into())
-                }
-                BinOp::Mod => {
+                BinOp::Ge => Value::Bool(lhs >= rhs),  // Greater Than or Equal
+                BinOp::Le => Value::Bool(lhs <= rhs),  // Less Than or Equal
+                _ => {
                     return Err(VmError::UnsupportedOperation(
                         op.into(),
                         type_of(&rhs_val).to_string(),
@@ -108,13 +126,12 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::Bool(lhs), Value::Bool(rhs)) => {
             let result = match op {
                 BinOp::And => Value::Bool(lhs && rhs), // Logical And
                 BinOp::Or => Value::Bool(lhs || rhs),  // Logical Or
-                BinOp::Eq => Value::Bool(lhs == rhs),  // Equality
                 _ => {
                     return Err(VmError::UnsupportedOperation(
                         op.into(),
@@ -124,12 +141,16 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::String(lhs), Value::String(rhs)) => {
             let result = match op {
-                BinOp::Add => Value::String(lhs + &rhs),
-                BinOp::Eq => Value::Bool(lhs == rhs),
+                BinOp::Add => format!("{lhs}{rhs}").into(),
+                // Lexicographic by bytes, which for UTF-8 is the order of the code points
+                BinOp::Gt => Value::Bool(lhs > rhs),
+                BinOp::Lt => Value::Bool(lhs < rhs),
+                BinOp::Ge => Value::Bool(lhs >= rhs),
+                BinOp::Le => Value::Bool(lhs <= rhs),
                 _ => {
                     return Err(VmError::UnsupportedOperation(
                         op.into(),
@@ -139,23 +160,13 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
-        (Value::Semaphore(s1), Value::Semaphore(s2)) => {
-            let result = match op {
-                BinOp::Eq => Value::Bool(s1 == s2),
-                _ => {
-                    return Err(VmError::UnsupportedOperation(
-                        op.into(),
-                        type_of(&rhs_val).to_string(),
-                    )
-                    .into())
-                }
-            };
-            rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+        (Value::Int(_), Value::Float(_)) | (Value::Float(_), Value::Int(_)) => {
+            Err(VmError::MixedNumeric(op.into()).into())
         }
-        (Value::Closure { .. }, Value::Closure { .. }) => {
+        // Only compared for equality, and closures not even that
+        (lhs, rhs) if type_of(&lhs) == type_of(&rhs) => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&rhs_val).to_string()).into())
         }
         _ => Err(VmError::TypeMismatch {
@@ -169,178 +180,312 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytecode::{BinOp, Semaphore, Value};
+    use std::rc::Rc;
+
+    use bytecode::{BinOp, Closure, FnType, Semaphore, Struct, StructType, Value, Variant};
 
     use crate::micro_code::ldc;
 
     #[test]
     fn test_binop() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Add).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(84)
         );
 
-        rt = ldc(rt, Value::Int(1)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Sub).unwrap();
+        ldc(&mut rt, Value::Int(1)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Sub).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(-1)
         );
 
-        rt = ldc(rt, Value::Int(21)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Mul).unwrap();
+        ldc(&mut rt, Value::Int(21)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Mul).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(42)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Div).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Div).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(42)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Mod).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Mod).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(0)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Gt).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Gt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Lt).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Lt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        let result = binop(rt, BinOp::Add);
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        let result = binop(&mut rt, BinOp::Add);
         assert!(result.is_err());
 
+        // Comparisons are not widened either
+        ldc(&mut rt, Value::Int(1)).unwrap();
+        ldc(&mut rt, Value::Float(2.5)).unwrap();
+        let result = binop(&mut rt, BinOp::Lt).unwrap_err();
+        assert_eq!(
+            result.to_string(),
+            "Can't apply < to Int and Float, convert one operand with int_to_float or float_to_int"
+        );
+
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Add).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(84.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Sub).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Sub).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(0.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Mul).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Mul).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(1764.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Div).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Div).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(1.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(22.0)).unwrap();
-        rt = binop(rt, BinOp::Gt).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(22.0)).unwrap();
+        binop(&mut rt, BinOp::Gt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(22.0)).unwrap();
-        rt = binop(rt, BinOp::Lt).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(22.0)).unwrap();
+        binop(&mut rt, BinOp::Lt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(22.0)).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(22.0)).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = binop(rt, BinOp::And).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        binop(&mut rt, BinOp::And).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = binop(rt, BinOp::Or).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        binop(&mut rt, BinOp::Or).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::String("hello".into())).unwrap();
-        rt = ldc(rt, Value::String(" world".into())).unwrap();
-        rt = binop(rt, BinOp::Add).unwrap();
+        ldc(&mut rt, Value::from("hello")).unwrap();
+        ldc(&mut rt, Value::from(" world")).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
-            Value::String("hello world".into())
+            Value::from("hello world")
         );
 
-        rt = ldc(rt, Value::String("hello".into())).unwrap();
-        rt = ldc(rt, Value::String(" world".into())).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, Value::from("hello")).unwrap();
+        ldc(&mut rt, Value::from(" world")).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
         let sem: Value = Semaphore::new(1).into();
-        rt = ldc(rt, sem.clone()).unwrap();
-        rt = ldc(rt, sem).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, sem.clone()).unwrap();
+        ldc(&mut rt, sem).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_binop_ordering() {
+        let mut rt = Runtime::new(vec![]);
+
+        for (lhs, rhs, op, exp) in [
+            (Value::Int(2), Value::Int(2), BinOp::Ge, true),
+            (Value::Int(2), Value::Int(3), BinOp::Le, true),
+            (Value::Float(2.5), Value::Float(2.0), BinOp::Le, false),
+            (Value::Float(f64::NAN), Value::Float(1.0), BinOp::Le, false),
+            // Strings are ordered lexicographically
+            (Value::from("apple"), Value::from("banana"), BinOp::Lt, true),
+            (Value::from("b"), Value::from("abc"), BinOp::Gt, true),
+            (Value::from("ab"), Value::from("abc"), BinOp::Lt, true),
+            (Value::from("Z"), Value::from("a"), BinOp::Lt, true),
+            (Value::from("abc"), Value::from("abc"), BinOp::Ge, true),
+            (Value::from(""), Value::from("a"), BinOp::Le, true),
+        ] {
+            ldc(&mut rt, lhs).unwrap();
+            ldc(&mut rt, rhs).unwrap();
+            binop(&mut rt, op).unwrap();
+            assert_eq!(
+                rt.current_thread.operand_stack.pop().unwrap(),
+                Value::Bool(exp)
+            );
+        }
+
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        assert!(binop(&mut rt, BinOp::Ge).is_err());
+    }
+
+    #[test]
+    fn test_binop_structural_eq() {
+        let mut rt = Runtime::new(vec![]);
+
+        // Variants compare the values they hold
+        let ok = |s: &str| Value::from(Variant::Ok(Variant::Some(s.into()).into()));
+        ldc(&mut rt, ok("a")).unwrap();
+        ldc(&mut rt, ok("a")).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
+
+        ldc(&mut rt, ok("a")).unwrap();
+        ldc(&mut rt, ok("b")).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::Bool(false)
+        );
+
+        // Functions can't be compared
+        let f = Value::from(Closure {
+            fn_type: FnType::User,
+            sym: "f".into(),
+            prms: vec![],
+            addr: 0,
+            env: Default::default(),
+        });
+        ldc(&mut rt, f.clone()).unwrap();
+        ldc(&mut rt, f).unwrap();
+        let err = binop(&mut rt, BinOp::Eq).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported operation == on type Closure");
+
+        ldc(&mut rt, Value::Int(1)).unwrap();
+        ldc(&mut rt, Value::Float(1.0)).unwrap();
+        assert!(binop(&mut rt, BinOp::Eq).is_err());
+    }
+
+    #[test]
+    fn test_binop_overload() {
+        let mut rt = Runtime::new(vec![]);
+
+        let ty = Rc::new(StructType::new("P".into(), vec!["x".into()]));
+        let add = Value::from(Closure {
+            fn_type: FnType::User,
+            sym: "add".into(),
+            prms: vec!["self".into(), "other".into()],
+            addr: 42,
+            env: Default::default(),
+        });
+        ty.methods.borrow_mut().insert("add".into(), add);
+        let p = Value::from(Struct {
+            ty: ty.clone(),
+            fields: vec![1.into()],
+        });
+
+        // + calls the add method of the struct with both operands
+        ldc(&mut rt, p.clone()).unwrap();
+        ldc(&mut rt, p.clone()).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
+        assert_eq!(rt.current_thread.pc, 42);
+        assert!(rt.current_thread.operand_stack.is_empty());
+        assert_eq!(rt.current_thread.runtime_stack.len(), 1);
+
+        // == without an eq method is structural
+        ldc(&mut rt, p.clone()).unwrap();
+        ldc(&mut rt, p.clone()).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::Bool(true)
+        );
+
+        ldc(&mut rt, p.clone()).unwrap();
+        ldc(&mut rt, p.clone()).unwrap();
+        let err = binop(&mut rt, BinOp::Sub).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No overload of - for P and P, only + and == can be overloaded with add and eq methods"
+        );
+
+        ldc(&mut rt, Value::Int(1)).unwrap();
+        ldc(&mut rt, p).unwrap();
+        let err = binop(&mut rt, BinOp::Add).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No overload of + for Int and P, only + and == can be overloaded with add and eq methods"
+        );
     }
 }
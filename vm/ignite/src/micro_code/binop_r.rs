@@ -0,0 +1,92 @@
+use anyhow::Result;
+use bytecode::{BinOp, Operand};
+
+use crate::{
+    micro_code::{assign_slot, binop, mov_r::operand},
+    Runtime,
+};
+
+/// Executes a binary operation on two operands, as loading them onto the stack then BINOP would,
+/// and assigns the result to a slot resolved at compile time, or pushes it onto the stack if there is none.
+/// The right-hand side is read first, so that where both are on the stack it is the top, as for [`binop`].
+/// Operations that may call the `add` or `eq` method of a struct only return to the next instruction,
+/// so the compiler has them push the result, unless the left-hand side is a constant and never a struct.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the operation on.
+///
+/// * `dst` - The number of frames up and the index of the slot to assign the result to, if any.
+///
+/// * `lhs` - The left-hand side of the operation.
+///
+/// * `rhs` - The right-hand side of the operation.
+///
+/// * `op` - The operation to execute.
+///
+/// # Errors
+///
+/// If an operand or the slot is not found, or the binary operation fails, see [`binop`].
+#[inline]
+pub fn binop_r(
+    rt: &mut Runtime,
+    dst: Option<(usize, usize)>,
+    lhs: Operand,
+    rhs: Operand,
+    op: BinOp,
+) -> Result<()> {
+    let rhs = operand(rt, rhs)?;
+    let lhs = operand(rt, lhs)?;
+    rt.current_thread.operand_stack.push(lhs);
+    rt.current_thread.operand_stack.push(rhs);
+    binop(rt, op)?;
+
+    match dst {
+        Some((depth, idx)) => assign_slot(rt, depth, idx),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use crate::{extend_environment, micro_code::ld_slot};
+
+    use super::*;
+
+    #[test]
+    fn test_binop_r() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["x", "y"], vec![10, 3])?;
+
+        binop_r(
+            &mut rt,
+            Some((0, 1)),
+            Operand::Slot(0, 0),
+            Operand::Slot(0, 1),
+            BinOp::Sub,
+        )?;
+        assert!(rt.current_thread.operand_stack.is_empty());
+        ld_slot(&mut rt, 0, 1)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(7)]);
+
+        // The right-hand side is the top of the stack
+        rt.current_thread.operand_stack.push(Value::Int(2));
+        binop_r(&mut rt, None, Operand::Stack, Operand::Stack, BinOp::Sub)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(5)]);
+
+        binop_r(
+            &mut rt,
+            None,
+            Operand::Const(Value::Int(1)),
+            Operand::Stack,
+            BinOp::Lt,
+        )?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Bool(true)]);
+
+        assert!(binop_r(&mut rt, None, Operand::Stack, Operand::Stack, BinOp::Add).is_err());
+        Ok(())
+    }
+}
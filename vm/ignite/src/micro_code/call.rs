@@ -1,5 +1,5 @@
 use anyhow::Result;
-use bytecode::{type_of, FnType, FrameType, StackFrame, Value};
+use bytecode::{type_of, Closure, FnType, FrameType, StackFrame, Value, W};
 
 use crate::{extend_environment, Runtime, VmError};
 
@@ -12,8 +12,8 @@ use super::apply_builtin;
 /// Then it pops the closure from the operand stack.
 /// It checks that the closure is a closure and that the arity of the closure matches the number of arguments.
 /// If the closure is a builtin function it applies the builtin function and returns.
-/// Otherwise it creates a new stack frame with the environment of the closure and the address of the closure.
-/// It extends the environment with the parameters and arguments.
+/// Otherwise it creates a new stack frame preserving the caller's environment and return address.
+/// It extends the environment of the closure with the parameters and arguments.
 /// It sets the program counter to the address of the closure. Essentially calling the function.
 ///
 /// # Arguments
@@ -26,8 +26,9 @@ use super::apply_builtin;
 ///
 /// If the operand stack does not contain enough values to pop (arity + 1).
 /// If the closure is not of type closure or the arity of the closure does not match the number of arguments.
+/// If the runtime stack is already at the maximum call depth.
 #[inline]
-pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
+pub fn call(rt: &mut Runtime, arity: usize) -> Result<()> {
     let mut args = Vec::new();
     args.reserve_exact(arity);
 
@@ -48,14 +49,7 @@ pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?;
 
-    let Value::Closure {
-        fn_type,
-        sym,
-        prms,
-        addr,
-        env,
-    } = value
-    else {
+    let Value::Closure(closure) = value else {
         return Err(VmError::BadType {
             expected: "Closure".to_string(),
             found: type_of(&value).to_string(),
@@ -63,6 +57,14 @@ pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
         .into());
     };
 
+    let Closure {
+        fn_type,
+        sym,
+        prms,
+        addr,
+        env,
+    } = closure.as_ref();
+
     if prms.len() != arity {
         return Err(VmError::ArityParamsMismatch {
             arity,
@@ -75,17 +77,27 @@ pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
         return apply_builtin(rt, sym.as_str(), args);
     }
 
+    #[cfg(feature = "jit")]
+    if let Some(val) = rt.jit_call(*addr, &args) {
+        rt.current_thread.operand_stack.push(val);
+        return Ok(());
+    }
+
     let frame = StackFrame {
         frame_type: FrameType::CallFrame,
-        env: env.clone(),
+        env: W(rt.current_thread.env.clone()),
         address: Some(rt.current_thread.pc),
+        sym: Some(*sym),
+        operand_len: None,
+        lock: None,
     };
 
+    rt.check_call_depth()?;
     rt.current_thread.runtime_stack.push(frame);
-    rt = extend_environment(rt, env.0, prms, args)?;
-    rt.current_thread.pc = addr;
+    extend_environment(rt, env.0.clone(), prms.clone(), args)?;
+    rt.current_thread.pc = *addr;
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -95,22 +107,27 @@ mod tests {
 
     #[test]
     fn test_call() -> Result<()> {
-        let rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
-        let result = call(rt, 0);
+        let mut rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
+        let result = call(&mut rt, 0);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
-        rt.current_thread.operand_stack.push(Value::Closure {
+        let caller_env = rt.current_thread.env.clone();
+        rt.current_thread.operand_stack.push(Value::from(Closure {
             fn_type: FnType::User,
-            sym: "Closure".to_string(),
+            sym: "Closure".into(),
             prms: vec![],
             addr: 123,
             env: Default::default(),
-        });
+        }));
 
-        let rt = call(rt, 0)?;
+        call(&mut rt, 0)?;
         assert_eq!(rt.current_thread.pc, 123);
 
+        // The frame restores the caller's environment on return, not the closure's
+        let frame = rt.current_thread.runtime_stack.last().unwrap();
+        assert!(frame.env.0.ptr_eq(&caller_env));
+
         Ok(())
     }
 }
@@ -0,0 +1,88 @@
+use anyhow::{Ok, Result};
+use bytecode::{Channel, Variant};
+
+use crate::Runtime;
+
+use super::{chan_recv::take_receiver, chan_send::take_sender};
+
+/// Close the channel, so that sending to it fails and receiving from it gives None once it is empty.
+/// Closing a channel that is already closed does nothing.
+///
+/// Every thread blocked on the channel is moved to the ready queue:
+///   - Threads receiving from it get None, since it is empty while they wait.
+///   - Threads sending to it get false, and the values they were sending are dropped.
+///
+/// The current thread continues execution.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to close the channel in.
+///
+/// * `ch` - The channel to close.
+#[inline]
+pub fn chan_close(rt: &mut Runtime, ch: Channel) -> Result<()> {
+    ch.0.borrow_mut().closed = true;
+
+    while let Some(mut receiver) = take_receiver(rt, &ch) {
+        receiver.operand_stack.push(Variant::None.into());
+        rt.wake(receiver);
+    }
+
+    while let Some((mut sender, _)) = take_sender(rt, &ch) {
+        sender.operand_stack.push(false.into());
+        rt.wake(sender);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        micro_code::{chan_recv, chan_send, spawn},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_chan_close() -> Result<()> {
+        let mut rt = Runtime::default();
+        let empty = Channel::new(1);
+        let full = Channel::new(1);
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+
+        // The main thread waits to receive, and the first child waits to send.
+        chan_recv(&mut rt, empty.clone())?;
+        chan_send(&mut rt, full.clone(), 1.into())?;
+        rt.current_thread.operand_stack.pop();
+        chan_send(&mut rt, full.clone(), 2.into())?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 2);
+        assert_eq!(rt.blocked_queue.len(), 2);
+
+        chan_close(&mut rt, empty.clone())?;
+        chan_close(&mut rt, full.clone())?;
+        assert!(rt.blocked_queue.is_empty());
+
+        let main = rt
+            .ready_queue
+            .iter()
+            .find(|t| t.thread_id == MAIN_THREAD_ID);
+        assert_eq!(
+            main.unwrap().operand_stack.last(),
+            Some(&Variant::None.into())
+        );
+        let sender = rt
+            .ready_queue
+            .iter()
+            .find(|t| t.thread_id == MAIN_THREAD_ID + 1);
+        assert_eq!(sender.unwrap().operand_stack.last(), Some(&false.into()));
+
+        // Closing again does nothing.
+        chan_close(&mut rt, full.clone())?;
+        assert_eq!(full.0.borrow().buf.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Ok, Result};
+use bytecode::{Channel, Variant};
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, Thread, ThreadState, WakeSource};
+
+use super::chan_send::take_sender;
+
+/// Receive the oldest value in the channel, pushing Some of it onto the operand stack.
+/// If a thread is blocked sending to the channel, the value it is sending takes the freed place in the channel,
+/// and the thread is moved to the ready queue with true pushed onto its operand stack. See [`take_sender`].
+///
+/// If the channel is empty and closed, None is pushed, since nothing will be sent to it anymore.
+///
+/// Otherwise, the channel is empty and the current thread is blocked until a thread sends to it or it is closed.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to receive in.
+///
+/// * `ch` - The channel to receive from.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn chan_recv(rt: &mut Runtime, ch: Channel) -> Result<()> {
+    let mut state = ch.0.borrow_mut();
+
+    if let Some(val) = state.buf.pop_front() {
+        rt.current_thread
+            .operand_stack
+            .push(Variant::Some(val).into());
+
+        if let Some((mut sender, val)) = take_sender(rt, &ch) {
+            state.buf.push_back(val);
+            sender.operand_stack.push(true.into());
+            rt.wake(sender);
+        }
+
+        return Ok(());
+    }
+
+    if state.closed {
+        rt.current_thread.operand_stack.push(Variant::None.into());
+        return Ok(());
+    }
+
+    drop(state); // Release the channel.
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::Channel),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    let source = WakeSource::ChannelRecv { ch, addr: None };
+    rt.push_blocked(current_thread, vec![source]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+/// Remove the thread the scheduler wakes up of those blocked receiving from the channel, if any.
+/// A thread receiving in a select continues from the address of its arm.
+pub(crate) fn take_receiver(rt: &mut Runtime, ch: &Channel) -> Option<Thread> {
+    let (mut receiver, sources) = rt.take_waiter(|source| source.is_channel_recv(ch))?;
+    let addr = sources.iter().find_map(|source| match source {
+        WakeSource::ChannelRecv { ch: c, addr } if c == ch => *addr,
+        _ => None,
+    });
+
+    if let Some(addr) = addr {
+        receiver.pc = addr;
+    }
+    Some(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        micro_code::{chan_send, spawn},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_chan_recv() -> Result<()> {
+        let mut rt = Runtime::default();
+        let ch = Channel::new(1);
+        spawn(&mut rt, 0, 0)?;
+
+        // The channel is empty, so the main thread is blocked.
+        chan_recv(&mut rt, ch.clone())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+
+        // The child sends, handing the value to the main thread.
+        chan_send(&mut rt, ch.clone(), 1.into())?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(true.into()));
+        assert!(rt.blocked_queue.is_empty());
+        assert!(ch.0.borrow().buf.is_empty());
+        let main = rt.ready_queue.back().unwrap();
+        assert_eq!(main.thread_id, MAIN_THREAD_ID);
+        assert_eq!(
+            main.operand_stack.last(),
+            Some(&Variant::Some(1.into()).into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chan_recv_wakes_sender() -> Result<()> {
+        let mut rt = Runtime::default();
+        let ch = Channel::new(1);
+        spawn(&mut rt, 0, 0)?;
+
+        // The main thread fills the channel and is blocked sending another value.
+        chan_send(&mut rt, ch.clone(), 1.into())?;
+        rt.current_thread.operand_stack.pop();
+        chan_send(&mut rt, ch.clone(), 2.into())?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+
+        // The child receives the oldest value, and the value of the main thread takes its place.
+        chan_recv(&mut rt, ch.clone())?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Variant::Some(1.into()).into())
+        );
+        assert_eq!(ch.0.borrow().buf.front(), Some(&2.into()));
+        let main = rt.ready_queue.back().unwrap();
+        assert_eq!(main.operand_stack.last(), Some(&true.into()));
+
+        // Once closed, the remaining value is still received before None.
+        ch.0.borrow_mut().closed = true;
+        chan_recv(&mut rt, ch.clone())?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Variant::Some(2.into()).into())
+        );
+        chan_recv(&mut rt, ch.clone())?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Variant::None.into())
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Ok, Result};
+use bytecode::{Channel, Value, Variant};
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, Thread, ThreadState, WakeSource};
+
+use super::chan_recv::take_receiver;
+
+/// Send the value to the channel, pushing whether it was sent onto the operand stack.
+/// - If the channel is closed, the value is dropped and false is pushed.
+/// - If a thread is blocked receiving from the channel, the value is handed to the first such thread,
+///   which is moved to the ready queue, and true is pushed. See [`take_receiver`].
+/// - If the channel has room, the value is added to it and true is pushed.
+///
+/// Otherwise, the channel is full and the current thread is blocked until a thread receives from it
+/// or it is closed. The value waits in what the current thread is blocked on until then.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to send in.
+///
+/// * `ch` - The channel to send to.
+///
+/// * `val` - The value to send.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn chan_send(rt: &mut Runtime, ch: Channel, val: Value) -> Result<()> {
+    let mut state = ch.0.borrow_mut();
+
+    if state.closed {
+        rt.current_thread.operand_stack.push(false.into());
+        return Ok(());
+    }
+
+    // A thread only waits to receive while the channel is empty, so the value goes straight to it.
+    if let Some(mut receiver) = take_receiver(rt, &ch) {
+        receiver.operand_stack.push(Variant::Some(val).into());
+        rt.wake(receiver);
+        rt.current_thread.operand_stack.push(true.into());
+        return Ok(());
+    }
+
+    if state.buf.len() < state.cap {
+        state.buf.push_back(val);
+        rt.current_thread.operand_stack.push(true.into());
+        return Ok(());
+    }
+
+    drop(state); // Release the channel.
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::Channel),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    let source = WakeSource::ChannelSend {
+        ch,
+        val,
+        addr: None,
+    };
+    rt.push_blocked(current_thread, vec![source]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+/// Remove the thread the scheduler wakes up of those blocked sending to the channel, if any,
+/// with the value it is sending. A thread sending in a select continues from the address of its arm.
+pub(crate) fn take_sender(rt: &mut Runtime, ch: &Channel) -> Option<(Thread, Value)> {
+    let (mut sender, sources) = rt.take_waiter(|source| source.is_channel_send(ch))?;
+    let (val, addr) = sources.into_iter().find_map(|source| match source {
+        WakeSource::ChannelSend { ch: c, val, addr } if &c == ch => Some((val, addr)),
+        _ => None,
+    })?;
+
+    if let Some(addr) = addr {
+        sender.pc = addr;
+    }
+    Some((sender, val))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_chan_send() -> Result<()> {
+        let mut rt = Runtime::default();
+        let ch = Channel::new(1);
+
+        // The channel has room.
+        chan_send(&mut rt, ch.clone(), 1.into())?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(true.into()));
+        assert_eq!(ch.0.borrow().buf.len(), 1);
+
+        // The channel is full, so the main thread is blocked with the value it is sending.
+        spawn(&mut rt, 0, 0)?;
+        chan_send(&mut rt, ch.clone(), 2.into())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        let (_, sources) = rt.blocked_queue.front().unwrap();
+        assert!(matches!(&sources[0], WakeSource::ChannelSend { val, .. } if val == &2.into()));
+
+        // Sending to a closed channel fails.
+        let closed = Channel::new(1);
+        closed.0.borrow_mut().closed = true;
+        chan_send(&mut rt, closed.clone(), 3.into())?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(false.into()));
+        assert!(closed.0.borrow().buf.is_empty());
+
+        Ok(())
+    }
+}
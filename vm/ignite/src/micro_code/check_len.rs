@@ -0,0 +1,79 @@
+use anyhow::Result;
+use bytecode::{builtin, type_of};
+
+use crate::{Runtime, VmError};
+
+/// Pop an array or string and check it has the number of elements or chars a destructuring let takes apart.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the operation on.
+///
+/// * `n` - The number of elements the pattern names.
+///
+/// * `at_least` - Whether the pattern binds the rest, so that it can have more than `n` elements.
+///
+/// # Errors
+///
+/// If the operand stack is empty, the value is not an array or string, or its length does not match.
+#[inline]
+pub fn check_len(rt: &mut Runtime, n: usize, at_least: bool) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let len = builtin::len_impl(&val)?;
+    if len == n || (at_least && len > n) {
+        return Ok(());
+    }
+
+    let expected = match at_least {
+        true => format!("at least {}", n),
+        false => n.to_string(),
+    };
+    Err(VmError::LengthMismatch {
+        ty: type_of(&val).to_string(),
+        expected,
+        len,
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_check_len() {
+        let mut rt = Runtime::new(vec![]);
+        let arr = Value::from(vec![1.into(), 2.into()]);
+
+        rt.current_thread.operand_stack.push(arr.clone());
+        check_len(&mut rt, 2, false).unwrap();
+        assert!(rt.current_thread.operand_stack.is_empty());
+
+        rt.current_thread.operand_stack.push(arr.clone());
+        check_len(&mut rt, 1, true).unwrap();
+
+        rt.current_thread.operand_stack.push(arr.clone());
+        let err = check_len(&mut rt, 3, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't destructure Array of length 2 into at least 3 elements"
+        );
+
+        rt.current_thread.operand_stack.push(arr);
+        assert!(check_len(&mut rt, 1, false).is_err());
+
+        // strings are counted by chars
+        rt.current_thread.operand_stack.push("hé".into());
+        check_len(&mut rt, 2, false).unwrap();
+
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(check_len(&mut rt, 1, false).is_err());
+    }
+}
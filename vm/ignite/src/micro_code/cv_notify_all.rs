@@ -0,0 +1,65 @@
+use anyhow::{Ok, Result};
+use bytecode::CondVar;
+
+use crate::Runtime;
+
+use super::cv_notify_one;
+
+/// Wake up all the threads blocked on the condition variable.
+/// Each woken thread reacquires its mutex as described in `cv_notify_one`.
+/// The current thread continues execution.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to notify the condition variable in.
+///
+/// * `cv` - The condition variable to notify.
+#[inline]
+pub fn cv_notify_all(rt: &mut Runtime, cv: CondVar) -> Result<()> {
+    while rt
+        .blocked_queue
+        .iter()
+        .any(|(_, sources)| sources.iter().any(|source| source.is_cond_var(&cv)))
+    {
+        cv_notify_one(rt, cv.clone())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Semaphore;
+
+    use crate::{
+        micro_code::{cv_wait, spawn},
+        ThreadState, MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_cv_notify_all() -> Result<()> {
+        let mut rt = Runtime::default();
+        let cv = CondVar::new();
+        let mutex = Semaphore::new(0);
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+        cv_wait(&mut rt, cv.clone(), mutex.clone())?; // main thread waits
+        cv_wait(&mut rt, cv.clone(), mutex.clone())?; // first child waits
+
+        // The mutex is free, so the first woken thread acquires it and the other waits on it.
+        *mutex.lock().unwrap() = 1;
+        cv_notify_all(&mut rt, cv.clone())?;
+        assert_eq!(
+            rt.ready_queue.pop_front().unwrap().thread_id,
+            MAIN_THREAD_ID
+        );
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Ready));
+        let (thread, sources) = rt.blocked_queue.front().unwrap();
+        assert_eq!(thread.thread_id, MAIN_THREAD_ID + 1);
+        assert!(sources[0].is_semaphore(&mutex));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{Ok, Result};
+use bytecode::CondVar;
+
+use crate::{Runtime, SchedulerEventKind, ThreadState, WakeSource};
+
+use super::lock::acquires_lock;
+
+/// Wake up a thread blocked on the condition variable, if any, the first by default as the scheduler picks.
+/// The woken thread reacquires the mutex it released when it started waiting:
+///   - If the mutex is available, it is decremented and the thread is moved to the ready queue.
+///   - Otherwise, the thread stays in the blocked queue, waiting on the mutex.
+///
+/// The current thread continues execution.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to notify the condition variable in.
+///
+/// * `cv` - The condition variable to notify.
+#[inline]
+pub fn cv_notify_one(rt: &mut Runtime, cv: CondVar) -> Result<()> {
+    let Some((mut thread, sources)) = rt.take_waiter(|source| source.is_cond_var(&cv)) else {
+        // If no blocked threads are found, nothing needs to be done.
+        return Ok(());
+    };
+
+    let Some(mutex) = sources.into_iter().find_map(|source| match source {
+        WakeSource::CondVar { mutex, .. } => Some(mutex),
+        _ => None,
+    }) else {
+        unreachable!("Thread should be waiting on the condition variable");
+    };
+
+    let mut mutex_guard = mutex.lock().unwrap();
+
+    if *mutex_guard > 0 {
+        *mutex_guard -= 1;
+        drop(mutex_guard); // Unlock the semaphore.
+
+        if acquires_lock(&thread, &mutex) {
+            thread.held_semaphores.push(mutex);
+        }
+        rt.set_thread_state(thread.thread_id, ThreadState::Ready);
+        rt.emit_event(thread.thread_id, SchedulerEventKind::Woken);
+        rt.push_ready(thread);
+    } else {
+        drop(mutex_guard); // Unlock the semaphore.
+
+        rt.push_blocked(thread, vec![WakeSource::new(mutex)]);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Semaphore;
+
+    use crate::{
+        micro_code::{cv_wait, lock, post, spawn, yield_},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_cv_notify_one() -> Result<()> {
+        let mut rt = Runtime::default();
+        let cv = CondVar::new();
+        let mutex = Semaphore::new(1);
+        rt.current_thread.operand_stack.push(mutex.clone().into());
+        lock(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        cv_wait(&mut rt, cv.clone(), mutex.clone())?;
+
+        // The child thread acquires the mutex, so the main thread waits on the mutex after being notified.
+        *mutex.lock().unwrap() = 0;
+        cv_notify_one(&mut rt, cv.clone())?;
+        let (thread, sources) = rt.blocked_queue.front().unwrap();
+        assert_eq!(thread.thread_id, MAIN_THREAD_ID);
+        assert!(sources[0].is_semaphore(&mutex));
+
+        // Once the child thread posts the mutex, the main thread is ready and holds the mutex.
+        rt.current_thread.operand_stack.push(mutex.clone().into());
+        post(&mut rt)?;
+        assert!(rt.blocked_queue.is_empty());
+        assert_eq!(*mutex.lock().unwrap(), 0);
+        yield_(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert_eq!(rt.current_thread.held_semaphores, vec![mutex]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cv_notify_one_no_waiters() -> Result<()> {
+        let mut rt = Runtime::default();
+        cv_notify_one(&mut rt, CondVar::new())?;
+        assert!(rt.blocked_queue.is_empty());
+        assert!(rt.ready_queue.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{Ok, Result};
+use bytecode::{CondVar, Semaphore};
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, WakeSource};
+
+use super::post::release;
+
+/// Atomically release the mutex and block the current thread on the condition variable.
+/// The mutex is expected to be a semaphore held by the current thread.
+///   - The mutex is released, which may wake up a thread blocked on it.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// Once notified, the thread reacquires the mutex before it continues execution.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to block the current thread in.
+///
+/// * `cv` - The condition variable to wait on.
+///
+/// * `mutex` - The semaphore to release while waiting.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue after the current thread is blocked.
+#[inline]
+pub fn cv_wait(rt: &mut Runtime, cv: CondVar, mutex: Semaphore) -> Result<()> {
+    // The current thread no longer holds the mutex.
+    let held = &mut rt.current_thread.held_semaphores;
+    if let Some(i) = held.iter().position(|held_sem| held_sem == &mutex) {
+        held.remove(i);
+    }
+
+    release(rt, mutex.clone())?;
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::CondVar),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, vec![WakeSource::new_cond_var(cv, mutex)]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_cv_wait() -> Result<()> {
+        let mut rt = Runtime::default();
+        let cv = CondVar::new();
+        let mutex = Semaphore::new(0);
+        rt.current_thread.held_semaphores.push(mutex.clone());
+        spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        cv_wait(&mut rt, cv.clone(), mutex.clone())?;
+
+        // The mutex is released and the main thread is blocked on the condition variable.
+        assert_eq!(*mutex.lock().unwrap(), 1);
+        let (thread, sources) = rt.blocked_queue.front().unwrap();
+        assert_eq!(thread.thread_id, MAIN_THREAD_ID);
+        assert!(thread.held_semaphores.is_empty());
+        assert!(sources[0].is_cond_var(&cv));
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+
+        Ok(())
+    }
+}
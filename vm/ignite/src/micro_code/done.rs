@@ -1,6 +1,6 @@
 use anyhow::{Ok, Result};
 
-use crate::{Runtime, VmError, MAIN_THREAD_ID};
+use crate::{Runtime, SchedulerEventKind, ThreadState, MAIN_THREAD_ID};
 
 /// Set the state of the runtime to done if the current thread is the main thread.
 /// Otherwise, set the current thread to zombie and yield to the next ready thread.
@@ -13,23 +13,25 @@ use crate::{Runtime, VmError, MAIN_THREAD_ID};
 ///
 /// * If the current thread is not the main thread and there are no threads in the ready queue.
 #[inline]
-pub fn done(mut rt: Runtime) -> Result<Runtime> {
+pub fn done(rt: &mut Runtime) -> Result<()> {
     // If the current thread is the main thread, then we are done
     if rt.current_thread.thread_id == MAIN_THREAD_ID {
+        rt.set_thread_state(MAIN_THREAD_ID, ThreadState::Done);
+        rt.emit_event(MAIN_THREAD_ID, SchedulerEventKind::Finished);
         rt.done = true;
-        Ok(rt)
+        Ok(())
     // Otherwise we will set the current thread to zombie and yield
     } else {
-        let current_thread = rt.current_thread;
-        let current_thread_id = current_thread.thread_id;
+        let current_thread_id = rt.current_thread.thread_id;
+        rt.set_thread_state(current_thread_id, ThreadState::Done);
+        rt.emit_event(current_thread_id, SchedulerEventKind::Finished);
+        let current_thread = std::mem::take(&mut rt.current_thread);
         rt.zombie_threads.insert(current_thread_id, current_thread);
 
-        let next_ready_thread = rt
-            .ready_queue
-            .pop_front()
-            .ok_or(VmError::NoThreadsInReadyQueue)?;
+        let next_ready_thread = rt.pop_ready_thread()?;
         rt.current_thread = next_ready_thread;
-        Ok(rt)
+        rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+        Ok(())
     }
 }
 
@@ -42,7 +44,7 @@ mod tests {
     #[test]
     fn test_done_01() -> Result<()> {
         let mut rt = Runtime::new(vec![]);
-        rt = done(rt)?;
+        done(&mut rt)?;
 
         // The main thread should be done
         assert!(rt.done);
@@ -53,15 +55,16 @@ mod tests {
     #[test]
     fn test_done_02() -> Result<()> {
         let mut rt = Runtime::new(vec![]);
-        rt = spawn(rt, 0)?;
-        rt = yield_(rt)?; // Yield the control to the child thread
-        rt = done(rt)?;
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?; // Yield the control to the child thread
+        done(&mut rt)?;
 
         // The main thread should not be done
         assert!(!rt.done);
         // The child thread should be in the zombie threads
         let child_thread_id = MAIN_THREAD_ID + 1;
         assert!(rt.zombie_threads.contains_key(&child_thread_id));
+        assert!(rt.is_finished(child_thread_id));
         // The current thread should be the main thread
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
 
@@ -14,9 +14,11 @@ use crate::{extend_environment, Runtime};
 ///
 /// # Errors
 ///
-/// Infallible.
+/// If the runtime stack is already at the maximum call depth.
 #[inline]
-pub fn enter_scope(mut rt: Runtime, syms: Vec<Symbol>) -> Result<Runtime> {
+pub fn enter_scope(rt: &mut Runtime, syms: Vec<Symbol>) -> Result<()> {
+    rt.check_call_depth()?;
+
     let current_env = rt.current_thread.env.clone();
 
     // Preserve the current environment in a stack frame
@@ -31,9 +33,9 @@ pub fn enter_scope(mut rt: Runtime, syms: Vec<Symbol>) -> Result<Runtime> {
         .collect::<Vec<Value>>();
 
     let current_env = rt.current_thread.env.clone();
-    rt = extend_environment(rt, current_env, syms, uninitialized)?;
+    extend_environment(rt, current_env, syms, uninitialized)?;
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -59,7 +61,7 @@ mod tests {
             .borrow_mut()
             .set("b", 123);
 
-        rt = enter_scope(rt, vec!["c".to_string(), "d".to_string()]).unwrap();
+        enter_scope(&mut rt, vec!["c".into(), "d".into()]).unwrap();
 
         assert_eq!(rt.current_thread.runtime_stack.len(), 1);
         assert!(rt
@@ -71,39 +73,19 @@ mod tests {
             .parent
             .is_some());
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"a".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("a")?,
             Value::Int(42)
         );
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"b".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("b")?,
             Value::Int(123)
         );
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"c".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("c")?,
             Value::Unitialized
         );
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"d".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("d")?,
             Value::Unitialized
         );
 
@@ -2,7 +2,10 @@ use anyhow::Result;
 
 use crate::{Runtime, VmError};
 
+use super::lock::unlock;
+
 /// Exit the current scope and restores the previous environment.
+/// Exiting a lock block releases the lock it holds.
 ///
 /// # Arguments
 ///
@@ -12,15 +15,16 @@ use crate::{Runtime, VmError};
 ///
 /// If the runtime stack is empty.
 #[inline]
-pub fn exit_scope(mut rt: Runtime) -> Result<Runtime> {
+pub fn exit_scope(rt: &mut Runtime) -> Result<()> {
     let prev_frame = rt
         .current_thread
         .runtime_stack
         .pop()
         .ok_or(VmError::RuntimeStackUnderflow)?;
 
+    unlock(rt, &prev_frame)?;
     rt.current_thread.env = prev_frame.env.0;
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -46,25 +50,15 @@ mod tests {
         rt.current_thread.env = env_b_weak;
 
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"a".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("a")?,
             Value::Int(123)
         );
 
-        rt = exit_scope(rt).unwrap();
+        exit_scope(&mut rt).unwrap();
 
         assert_eq!(rt.current_thread.runtime_stack.len(), 0);
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"a".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("a")?,
             Value::Int(42)
         );
 
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use bytecode::{FrameType, Generator, Value, W};
+
+use crate::{Runtime, VmError};
+
+/// Start a generator function, which is the first instruction of its body. Instead of running the body,
+/// return from the call with a generator that runs the rest of the body in the environment of the parameters
+/// once it is resumed.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the instruction in.
+///
+/// # Errors
+///
+/// If the top of the runtime stack is not the call frame of the generator function.
+#[inline]
+pub fn generator(rt: &mut Runtime) -> Result<()> {
+    let thread = &mut rt.current_thread;
+    let frame = thread
+        .runtime_stack
+        .pop()
+        .ok_or(VmError::RuntimeStackUnderflow)?;
+
+    if frame.frame_type != FrameType::CallFrame {
+        return Err(VmError::RuntimeStackUnderflow.into());
+    }
+
+    let gen = Generator::new(thread.pc, W(thread.env.clone()));
+    thread.env = frame.env.0;
+    thread.pc = frame.address.unwrap_or_default();
+    thread.operand_stack.push(Value::Generator(gen));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{ByteCode, Closure, FnType, GeneratorStatus};
+
+    use crate::micro_code::call;
+
+    use super::*;
+
+    #[test]
+    fn test_generator() -> Result<()> {
+        let mut rt = Runtime::new(vec![ByteCode::DONE]);
+        assert!(generator(&mut rt).is_err());
+
+        let caller_env = rt.current_thread.env.clone();
+        rt.current_thread.pc = 5;
+        rt.current_thread.operand_stack.push(Value::from(Closure {
+            fn_type: FnType::User,
+            sym: "gen".into(),
+            prms: vec!["n".into()],
+            addr: 123,
+            env: Default::default(),
+        }));
+        rt.current_thread.operand_stack.push(1.into());
+        call(&mut rt, 1)?;
+        let params_env = rt.current_thread.env.clone();
+
+        // as if GENERATOR at 123 was fetched
+        rt.current_thread.pc = 124;
+        generator(&mut rt)?;
+
+        assert_eq!(rt.current_thread.pc, 5);
+        assert!(rt.current_thread.env.ptr_eq(&caller_env));
+        assert!(rt.current_thread.runtime_stack.is_empty());
+
+        let Some(Value::Generator(gen)) = rt.current_thread.operand_stack.pop() else {
+            panic!("Expected a generator");
+        };
+        let state = gen.0.borrow();
+        assert_eq!(state.status, GeneratorStatus::Suspended);
+        assert_eq!(state.pc, 124);
+        assert!(state.env.0.ptr_eq(&params_env));
+
+        Ok(())
+    }
+}
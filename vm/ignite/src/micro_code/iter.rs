@@ -0,0 +1,52 @@
+use anyhow::Result;
+use bytecode::{Iter, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop an array or string and push an iterator over its elements or chars.
+/// A generator is pushed back as it is, since NEXT resumes it.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to create the iterator in.
+///
+/// # Errors
+///
+/// If the operand stack is empty or the value is not an array or a string.
+#[inline]
+pub fn iter(rt: &mut Runtime) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    if let Value::Generator(_) = val {
+        rt.current_thread.operand_stack.push(val);
+        return Ok(());
+    }
+
+    let iter = Iter::new(&val)?;
+    rt.current_thread.operand_stack.push(Value::Iter(iter));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter() {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push("ab".into());
+        iter(&mut rt).unwrap();
+        let Some(Value::Iter(it)) = rt.current_thread.operand_stack.pop() else {
+            panic!("Expected an iterator");
+        };
+        assert_eq!(it.next(), Some("a".into()));
+
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(iter(&mut rt).is_err());
+        assert!(iter(&mut rt).is_err());
+    }
+}
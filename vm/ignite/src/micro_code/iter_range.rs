@@ -0,0 +1,67 @@
+use anyhow::Result;
+use bytecode::{type_of, Iter, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop the end and the start of a range and push an iterator over the ints from the start up to but excluding the end.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to create the iterator in.
+///
+/// # Errors
+///
+/// If the operand stack does not contain 2 values or they are not ints.
+#[inline]
+pub fn iter_range(rt: &mut Runtime) -> Result<()> {
+    let end = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let start = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let (Value::Int(start), Value::Int(end)) = (&start, &end) else {
+        let found = if let Value::Int(_) = start {
+            &end
+        } else {
+            &start
+        };
+        return Err(VmError::BadType {
+            expected: "Int".to_string(),
+            found: type_of(found).to_string(),
+        }
+        .into());
+    };
+
+    let iter = Iter::range(*start, *end);
+    rt.current_thread.operand_stack.push(Value::Iter(iter));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter_range() {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(1.into());
+        rt.current_thread.operand_stack.push(3.into());
+        iter_range(&mut rt).unwrap();
+        let Some(Value::Iter(it)) = rt.current_thread.operand_stack.pop() else {
+            panic!("Expected an iterator");
+        };
+        assert_eq!(it.next(), Some(1.into()));
+        assert_eq!(it.next(), Some(2.into()));
+        assert_eq!(it.next(), None);
+
+        rt.current_thread.operand_stack.push(1.into());
+        rt.current_thread.operand_stack.push(true.into());
+        assert!(iter_range(&mut rt).is_err());
+    }
+}
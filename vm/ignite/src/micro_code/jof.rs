@@ -1,8 +1,11 @@
 use anyhow::Result;
+use bytecode::{type_of, Value};
 
 use crate::{Runtime, VmError};
 
 /// Jumps to the given program counter if the top of the stack is false.
+/// Conditions are never coerced, the type checker rejects non-bool conditions
+/// and this is the backstop for bytecode that did not go through it.
 ///
 /// # Arguments
 ///
@@ -12,44 +15,57 @@ use crate::{Runtime, VmError};
 ///
 /// # Errors
 ///
-/// If the stack is empty or the top of the stack is not a boolean.
+/// If the stack is empty, or BadType if the top of the stack is not a boolean.
 #[inline]
-pub fn jof(mut rt: Runtime, pc: usize) -> Result<Runtime> {
+pub fn jof(rt: &mut Runtime, pc: usize) -> Result<()> {
     let cond = rt
         .current_thread
         .operand_stack
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?;
 
-    let b: bool = cond.try_into()?;
+    let Value::Bool(b) = cond else {
+        return Err(VmError::BadType {
+            expected: "Bool".to_string(),
+            found: type_of(&cond).to_string(),
+        }
+        .into());
+    };
+
     if !b {
         rt.current_thread.pc = pc;
     }
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytecode::Value;
 
     use crate::micro_code::ldc;
 
     #[test]
     fn test_jof() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = jof(rt, 123).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        jof(&mut rt, 123).unwrap();
         assert_eq!(rt.current_thread.pc, 123);
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = jof(rt, 42).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        jof(&mut rt, 42).unwrap();
         assert_eq!(rt.current_thread.pc, 0);
 
-        rt = ldc(rt, Value::Unit).unwrap();
-        let result = jof(rt, 42);
-        assert!(result.is_err());
+        // No truthiness: ints, units and strings are not conditions
+        for v in [Value::Unit, Value::Int(1), Value::from("true")] {
+            ldc(&mut rt, v).unwrap();
+            let err = jof(&mut rt, 42).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<VmError>(),
+                Some(VmError::BadType { .. })
+            ));
+            assert_eq!(rt.current_thread.pc, 0);
+        }
     }
 }
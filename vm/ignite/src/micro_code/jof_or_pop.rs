@@ -0,0 +1,72 @@
+use anyhow::Result;
+use bytecode::{type_of, Value};
+
+use crate::{Runtime, VmError};
+
+/// Jumps to the given program counter if the top of the stack is false, leaving it on the stack
+/// as the value of the `&&` being short-circuited, and pops it otherwise.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the operation on.
+///
+/// * `pc` - The program counter to jump to.
+///
+/// # Errors
+///
+/// If the stack is empty, or BadType if the top of the stack is not a boolean.
+#[inline]
+pub fn jof_or_pop(rt: &mut Runtime, pc: usize) -> Result<()> {
+    let cond = rt
+        .current_thread
+        .operand_stack
+        .last()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    match cond {
+        Value::Bool(false) => rt.current_thread.pc = pc,
+        Value::Bool(true) => {
+            rt.current_thread.operand_stack.pop();
+        }
+        _ => {
+            return Err(VmError::BadType {
+                expected: "Bool".to_string(),
+                found: type_of(cond).to_string(),
+            }
+            .into())
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::micro_code::ldc;
+
+    #[test]
+    fn test_jof_or_pop() {
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        jof_or_pop(&mut rt, 123).unwrap();
+        assert_eq!(rt.current_thread.pc, 123);
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Bool(false)]);
+
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        jof_or_pop(&mut rt, 42).unwrap();
+        assert_eq!(rt.current_thread.pc, 0);
+        assert!(rt.current_thread.operand_stack.is_empty());
+
+        ldc(&mut rt, Value::Int(1)).unwrap();
+        let err = jof_or_pop(&mut rt, 42).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::BadType { .. })
+        ));
+
+        assert!(jof_or_pop(&mut Runtime::new(vec![]), 42).is_err());
+    }
+}
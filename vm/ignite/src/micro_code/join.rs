@@ -22,7 +22,7 @@ use super::yield_;
 /// * If the operand stack is empty.
 /// * If the value on the operand stack is not an integer.
 #[inline]
-pub fn join(mut rt: Runtime) -> Result<Runtime> {
+pub fn join(rt: &mut Runtime) -> Result<()> {
     let tid: i64 = rt
         .current_thread
         .operand_stack
@@ -35,8 +35,8 @@ pub fn join(mut rt: Runtime) -> Result<Runtime> {
         // If the thread to join is not found, we need to yield control and try again
         rt.current_thread.pc -= 1; // Decrement the program counter to re-execute the join instruction
         rt.current_thread.operand_stack.push(tid.into()); // Add the pid back to the operand stack
-        let rt = yield_(rt)?;
-        return Ok(rt);
+        yield_(rt)?;
+        return Ok(());
     };
 
     let result = zombie_thread
@@ -48,7 +48,7 @@ pub fn join(mut rt: Runtime) -> Result<Runtime> {
     drop(zombie_thread);
 
     rt.current_thread.operand_stack.push(result);
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -66,13 +66,13 @@ mod tests {
     fn test_join_01() -> Result<()> {
         let mut rt = Runtime::default();
         rt.current_thread.pc = 1; // prevent u64 subtraction overflow
-        rt = spawn(rt, 0)?;
-        rt = join(rt)?;
+        spawn(&mut rt, 0, 0)?;
+        join(&mut rt)?;
         // Add this point, both threads are in the ready state, so join should yield the current thread
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
 
         // Add the parent thread ID to the operand stack of the child
-        rt = yield_(rt)?;
+        yield_(&mut rt)?;
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
 
         // PID should remain on the operand stack
@@ -85,12 +85,12 @@ mod tests {
     fn test_join_02() -> Result<()> {
         let mut rt = Runtime::default();
         rt.current_thread.pc = 1; // prevent u64 subtraction overflow
-        rt = spawn(rt, 0)?;
-        rt = yield_(rt)?; // Yield the parent thread to make the child thread the current thread
-        rt = done(rt)?; // Set the current thread to zombie state
-        rt = yield_(rt)?; // Yield the child thread to make the parent thread the current thread
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?; // Yield the parent thread to make the child thread the current thread
+        done(&mut rt)?; // Set the current thread to zombie state
+        yield_(&mut rt)?; // Yield the child thread to make the parent thread the current thread
 
-        rt = join(rt)?;
+        join(&mut rt)?;
         // Add this point, the thread to join is in zombie state, so the current thread should just continue
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
         // Zombie thread should be deallocated
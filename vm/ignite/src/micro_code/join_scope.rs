@@ -0,0 +1,82 @@
+use anyhow::Result;
+
+use crate::{Runtime, VmError};
+
+use super::yield_;
+
+/// Wait for the threads spawned in the innermost scope of the current thread to finish.
+/// If they have all finished, the scope is closed and the current thread continues, with the value of the block
+/// still on its operand stack. Their results stay with the zombie threads, so handles that leave the block can still be joined.
+/// Otherwise, the current thread will yield and try again, like JOIN.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to join the scope in.
+///
+/// # Errors
+///
+/// If the current thread has no open scope.
+/// If the current thread yields and there are no threads in the ready queue.
+#[inline]
+pub fn join_scope(rt: &mut Runtime) -> Result<()> {
+    let tid = rt.current_thread.thread_id;
+    let mut scopes = rt.spawn_scopes.remove(&tid).unwrap_or_default();
+    let Some(scope) = scopes.last_mut() else {
+        return Err(VmError::NoSpawnScope.into());
+    };
+
+    scope.threads.retain(|t| !rt.is_finished(*t));
+    let running = !scope.threads.is_empty();
+    if !running {
+        scopes.pop();
+    }
+    if !scopes.is_empty() {
+        rt.spawn_scopes.insert(tid, scopes);
+    }
+
+    if running {
+        rt.current_thread.pc -= 1; // Decrement the program counter to re-execute the join instruction
+        yield_(rt)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        micro_code::{done, spawn, spawn_scope},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_join_scope() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        spawn_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+
+        // The child is still running, so the current thread yields to it
+        join_scope(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        assert_eq!(rt.spawn_scopes[&MAIN_THREAD_ID].len(), 1);
+
+        done(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        join_scope(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert!(rt.spawn_scopes.is_empty());
+
+        // The result of the child can still be joined
+        assert!(rt.zombie_threads.contains_key(&(MAIN_THREAD_ID + 1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_scope_without_scope() {
+        let mut rt = Runtime::default();
+        assert!(join_scope(&mut rt).is_err());
+    }
+}
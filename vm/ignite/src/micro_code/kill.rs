@@ -0,0 +1,253 @@
+use anyhow::{Ok, Result};
+use bytecode::{FrameType, Semaphore, StackFrame, ThreadID, Value};
+
+use crate::{Runtime, SchedulerEventKind, Thread, ThreadState, VmError, MAIN_THREAD_ID};
+
+use super::{lock::release_rwlock, post::release};
+
+/// Kill the thread with the given ID.
+/// The thread is removed from whichever queue it is in and finishes with unit as its result,
+/// so joining a killed thread produces unit.
+/// The mutexes and rwlocks the thread holds in lock blocks are released, which may wake up threads blocked on them.
+/// Semaphores it waited on outside of a lock block are not posted back, as the thread has consumed them.
+/// Killing a thread that has already finished does nothing.
+///
+/// If the thread to kill is the current thread, it finishes as if it executed DONE:
+///   - If it is the main thread, the program is done.
+///   - Otherwise, the next ready thread is popped from the ready queue and set as the current thread.
+///
+/// Killing the main thread from another thread also ends the program.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to kill the thread in.
+///
+/// * `tid` - The ID of the thread to kill.
+///
+/// # Errors
+///
+/// If no thread with the given ID has been created.
+/// If the current thread is killed and there are no threads in the ready queue.
+#[inline]
+pub fn kill(rt: &mut Runtime, tid: ThreadID) -> Result<()> {
+    match rt.thread_state(tid) {
+        None => return Err(VmError::IllegalArgument(format!("no thread with ID {}", tid)).into()),
+        Some(ThreadState::Done) => return Ok(()),
+        Some(_) => (),
+    }
+
+    if tid == rt.current_thread.thread_id {
+        if tid == MAIN_THREAD_ID {
+            let held = std::mem::take(&mut rt.current_thread.held_semaphores);
+            release_all(rt, held)?;
+            let frames = std::mem::take(&mut rt.current_thread.runtime_stack);
+            release_rwlocks(rt, &frames);
+            rt.set_thread_state(MAIN_THREAD_ID, ThreadState::Done);
+            rt.emit_event(MAIN_THREAD_ID, SchedulerEventKind::Finished);
+            rt.done = true;
+            return Ok(());
+        }
+
+        let next_ready_thread = rt.pop_ready_thread()?;
+        let current_thread = std::mem::replace(&mut rt.current_thread, next_ready_thread);
+        rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+
+        return finish(rt, current_thread);
+    }
+
+    let ready_idx = rt.ready_queue.iter().position(|t| t.thread_id == tid);
+    let blocked_idx = rt
+        .blocked_queue
+        .iter()
+        .position(|(t, _)| t.thread_id == tid);
+
+    let thread = if let Some(i) = ready_idx {
+        rt.ready_queue.remove(i)
+    } else if let Some(i) = blocked_idx {
+        rt.blocked_queue.remove(i).map(|(t, _)| t)
+    } else {
+        None
+    };
+
+    let Some(thread) = thread else {
+        return Ok(());
+    };
+
+    finish(rt, thread)
+}
+
+/// Finish a thread that is no longer in any queue, releasing the mutexes and rwlocks it holds.
+fn finish(rt: &mut Runtime, mut thread: Thread) -> Result<()> {
+    let held = std::mem::take(&mut thread.held_semaphores);
+    release_all(rt, held)?;
+    release_rwlocks(rt, &thread.runtime_stack);
+
+    rt.spawn_scopes.remove(&thread.thread_id);
+    rt.set_thread_state(thread.thread_id, ThreadState::Done);
+    rt.emit_event(thread.thread_id, SchedulerEventKind::Finished);
+    if thread.thread_id == MAIN_THREAD_ID {
+        rt.done = true;
+        return Ok(());
+    }
+
+    thread.operand_stack = vec![Value::Unit];
+    thread.runtime_stack.clear();
+    rt.zombie_threads.insert(thread.thread_id, thread);
+    Ok(())
+}
+
+fn release_all(rt: &mut Runtime, sems: Vec<Semaphore>) -> Result<()> {
+    for sem in sems {
+        release(rt, sem)?;
+    }
+    Ok(())
+}
+
+fn release_rwlocks(rt: &mut Runtime, frames: &[StackFrame]) {
+    for frame in frames.iter().rev() {
+        let write = match frame.frame_type {
+            FrameType::LockFrame => true,
+            FrameType::ReadLockFrame => false,
+            _ => continue,
+        };
+        if let Some(Value::RwLock(rw)) = &frame.lock {
+            release_rwlock(rt, rw.clone(), write);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        extend_environment,
+        micro_code::{join, ld, lock, post, spawn, wait, yield_},
+    };
+    use bytecode::RwLock;
+
+    use super::*;
+
+    #[test]
+    fn test_kill_ready() -> Result<()> {
+        let mut rt = Runtime::default();
+        spawn(&mut rt, 0, 0)?;
+        let child_thread_id = MAIN_THREAD_ID + 1;
+        rt.current_thread.operand_stack.clear();
+
+        kill(&mut rt, child_thread_id)?;
+
+        assert!(rt.ready_queue.is_empty());
+        assert!(rt.is_finished(child_thread_id));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        // Joining the killed thread produces unit.
+        rt.current_thread.operand_stack.push(child_thread_id.into());
+        join(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Unit));
+
+        // Killing a finished thread does nothing.
+        kill(&mut rt, child_thread_id)?;
+        assert!(rt.is_finished(child_thread_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kill_releases_semaphores() -> Result<()> {
+        let mut rt = Runtime::default();
+        let sem = Semaphore::new(1);
+        let current_env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear();
+
+        // Main thread holds the semaphore in a lock block, then the child blocks on it.
+        ld(&mut rt, "sem".into())?;
+        lock(&mut rt)?;
+        yield_(&mut rt)?;
+        let child_thread_id = MAIN_THREAD_ID + 1;
+        assert_eq!(rt.current_thread.thread_id, child_thread_id);
+        ld(&mut rt, "sem".into())?;
+        wait(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        // Killing the blocked child removes it from the blocked queue.
+        kill(&mut rt, child_thread_id)?;
+        assert!(rt.blocked_queue.is_empty());
+        assert_eq!(*sem.lock().unwrap(), 0);
+
+        // Killing the main thread releases the semaphore it holds.
+        kill(&mut rt, MAIN_THREAD_ID)?;
+        assert!(rt.done);
+        assert_eq!(*sem.lock().unwrap(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kill_consumer() -> Result<()> {
+        let mut rt = Runtime::default();
+        let items = Semaphore::new(0);
+        let current_env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, current_env, vec!["items"], vec![items.clone()])?;
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear();
+        let child_thread_id = MAIN_THREAD_ID + 1;
+
+        // The child consumes the items the main thread produces, blocking on each until it is posted.
+        for _ in 0..5 {
+            yield_(&mut rt)?;
+            assert_eq!(rt.current_thread.thread_id, child_thread_id);
+            ld(&mut rt, "items".into())?;
+            wait(&mut rt)?;
+            assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+            ld(&mut rt, "items".into())?;
+            post(&mut rt)?;
+        }
+
+        // The consumed items are not posted back when the child is killed.
+        kill(&mut rt, child_thread_id)?;
+        assert!(rt.is_finished(child_thread_id));
+        assert_eq!(*items.lock().unwrap(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kill_releases_rwlocks() -> Result<()> {
+        let mut rt = Runtime::default();
+        let rw = RwLock::new();
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear();
+
+        // The child holds the rwlock in a lock block, then yields.
+        yield_(&mut rt)?;
+        let child_thread_id = MAIN_THREAD_ID + 1;
+        rt.current_thread.operand_stack.push(rw.clone().into());
+        lock(&mut rt)?;
+        yield_(&mut rt)?;
+        assert!(rw.lock().unwrap().writer);
+
+        kill(&mut rt, child_thread_id)?;
+        assert!(!rw.lock().unwrap().writer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kill_current() -> Result<()> {
+        let mut rt = Runtime::default();
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        let child_thread_id = MAIN_THREAD_ID + 1;
+
+        kill(&mut rt, child_thread_id)?;
+
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Running));
+        assert!(rt.zombie_threads.contains_key(&child_thread_id));
+
+        assert!(kill(&mut rt, MAIN_THREAD_ID + 2).is_err());
+
+        Ok(())
+    }
+}
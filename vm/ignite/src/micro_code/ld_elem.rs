@@ -0,0 +1,103 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use bytecode::{builtin, type_of, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop an index and an array or string, and push the element of the array or the char of the string at the index.
+/// Strings are indexed by chars, and a char is a string of length 1.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to load the element in.
+///
+/// # Errors
+///
+/// If the operand stack does not contain 2 values, they are not an array or string and an int,
+/// or the index is out of the bounds of the array or string.
+#[inline]
+pub fn ld_elem(rt: &mut Runtime) -> Result<()> {
+    let idx = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let Value::Int(idx) = idx else {
+        return Err(VmError::BadType {
+            expected: "Int".to_string(),
+            found: type_of(&idx).to_string(),
+        }
+        .into());
+    };
+
+    let elem = usize::try_from(idx).ok().and_then(|i| match &val {
+        Value::Array(elems) => elems.get(i).cloned(),
+        Value::String(s) => s.chars().nth(i).map(|c| Value::String(Rc::new(c.into()))),
+        _ => None,
+    });
+
+    let Some(elem) = elem else {
+        // the length is only needed for the error, and fails if the value is not an array or string
+        let len = builtin::len_impl(&val)?;
+        return Err(VmError::IndexOutOfBounds {
+            ty: type_of(&val).to_string(),
+            idx,
+            len,
+        }
+        .into());
+    };
+
+    rt.current_thread.operand_stack.push(elem);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_ld_elem(val: Value, idx: Value) -> Result<Value> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(val);
+        rt.current_thread.operand_stack.push(idx);
+        ld_elem(&mut rt)?;
+        Ok(rt.current_thread.operand_stack.pop().unwrap())
+    }
+
+    #[test]
+    fn test_ld_elem_array() {
+        let arr = Value::from(vec![1.into(), 2.into()]);
+        assert_eq!(test_ld_elem(arr.clone(), 1.into()).unwrap(), 2.into());
+
+        for idx in [2, -1] {
+            let err = test_ld_elem(arr.clone(), idx.into()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Index {} out of bounds for Array of length 2", idx)
+            );
+        }
+
+        assert!(test_ld_elem(arr, true.into()).is_err());
+        assert!(test_ld_elem(1.into(), 0.into()).is_err());
+    }
+
+    #[test]
+    fn test_ld_elem_string() {
+        let s = Value::from("héllo");
+        assert_eq!(test_ld_elem(s.clone(), 1.into()).unwrap(), "é".into());
+        assert_eq!(test_ld_elem(s.clone(), 4.into()).unwrap(), "o".into());
+
+        // the index is in chars, not bytes
+        let err = test_ld_elem(s, 5.into()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Index 5 out of bounds for String of length 5"
+        );
+    }
+}
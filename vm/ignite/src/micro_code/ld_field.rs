@@ -0,0 +1,72 @@
+use anyhow::Result;
+use bytecode::{type_of, Symbol, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a struct and push the value of the given field.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to load the field in.
+///
+/// * `field` - The field to load.
+///
+/// # Errors
+///
+/// If the operand stack is empty, the value is not a struct, or the struct has no such field.
+#[inline]
+pub fn ld_field(rt: &mut Runtime, field: Symbol) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let Value::Struct(instance) = val else {
+        return Err(VmError::BadType {
+            expected: "Struct".to_string(),
+            found: type_of(&val).to_string(),
+        }
+        .into());
+    };
+
+    let val = instance
+        .field(field)
+        .ok_or_else(|| VmError::NoSuchField {
+            ty: instance.ty.name.to_string(),
+            field: field.to_string(),
+        })?
+        .clone();
+
+    rt.current_thread.operand_stack.push(val);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bytecode::{Struct, StructType};
+
+    use super::*;
+
+    #[test]
+    fn test_ld_field() {
+        let ty = Rc::new(StructType::new("P".into(), vec!["x".into(), "y".into()]));
+        let p = Value::from(Struct {
+            ty,
+            fields: vec![1.into(), 2.into()],
+        });
+
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(p.clone());
+        ld_field(&mut rt, "y".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(2.into()));
+
+        rt.current_thread.operand_stack.push(p);
+        assert!(ld_field(&mut rt, "z".into()).is_err());
+
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(ld_field(&mut rt, "x".into()).is_err());
+    }
+}
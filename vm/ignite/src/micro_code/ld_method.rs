@@ -0,0 +1,79 @@
+use anyhow::Result;
+use bytecode::{builtin, type_of, Symbol, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a receiver and push the given method of its type, then the receiver to call the method with as self.
+/// Structs have the methods of their impl blocks, thread handles have the builtin methods id and is_finished.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to load the method in.
+///
+/// * `method` - The name of the method.
+///
+/// # Errors
+///
+/// If the operand stack is empty, or the receiver has no such method.
+#[inline]
+pub fn ld_method(rt: &mut Runtime, method: Symbol) -> Result<()> {
+    let recv = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let closure = match (&recv, method.as_str()) {
+        (Value::Struct(instance), _) => instance.ty.methods.borrow().get(&method).cloned(),
+        // Thread handles are ints at runtime
+        (Value::Int(_), "id") => Some(builtin::thread_id()),
+        (Value::Int(_), "is_finished") => Some(builtin::is_finished()),
+        _ => None,
+    };
+
+    let closure = closure.ok_or_else(|| VmError::NoSuchMethod {
+        ty: match &recv {
+            Value::Struct(instance) => instance.ty.name.to_string(),
+            _ => type_of(&recv).to_string(),
+        },
+        method: method.to_string(),
+    })?;
+
+    rt.current_thread.operand_stack.push(closure);
+    rt.current_thread.operand_stack.push(recv);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bytecode::{Struct, StructType};
+
+    use super::*;
+
+    #[test]
+    fn test_ld_method() {
+        let ty = Rc::new(StructType::new("P".into(), vec![]));
+        ty.methods.borrow_mut().insert("f".into(), 42.into());
+        let p = Value::from(Struct { ty, fields: vec![] });
+
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(p.clone());
+        ld_method(&mut rt, "f".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(p.clone()));
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(42.into()));
+
+        rt.current_thread.operand_stack.push(1.into());
+        ld_method(&mut rt, "id".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(1.into()));
+        let Some(Value::Closure(closure)) = rt.current_thread.operand_stack.pop() else {
+            panic!("Expected a closure");
+        };
+        assert_eq!(closure.sym, builtin::THREAD_ID_SYM.into());
+
+        rt.current_thread.operand_stack.push(p);
+        let err = ld_method(&mut rt, "g".into()).unwrap_err();
+        assert_eq!(err.to_string(), "No method g on P");
+    }
+}
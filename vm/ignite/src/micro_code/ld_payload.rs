@@ -0,0 +1,75 @@
+use anyhow::Result;
+use bytecode::{type_of, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a value of an option, result or enum, and push the value its variant holds.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to load the value in.
+///
+/// # Errors
+///
+/// If the operand stack is empty, the value is not an option, result or enum, or its variant holds nothing.
+#[inline]
+pub fn ld_payload(rt: &mut Runtime) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let payload = match &val {
+        Value::Variant(variant) => variant.payload(),
+        Value::Enum(variant) => variant.payload.as_ref(),
+        _ => {
+            return Err(VmError::BadType {
+                expected: "Option, Result or Enum".to_string(),
+                found: type_of(&val).to_string(),
+            }
+            .into())
+        }
+    };
+
+    let payload = payload
+        .ok_or_else(|| VmError::IllegalArgument(format!("{} holds no value", val)))?
+        .clone();
+
+    rt.current_thread.operand_stack.push(payload);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{Enum, Variant};
+
+    use super::*;
+
+    #[test]
+    fn test_ld_payload() {
+        let mut rt = Runtime::new(vec![]);
+        let circle = Value::from(Enum {
+            ty: "Shape".into(),
+            variant: "Circle".into(),
+            payload: Some(1.into()),
+        });
+
+        rt.current_thread.operand_stack.push(circle);
+        ld_payload(&mut rt).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(1.into()));
+
+        rt.current_thread
+            .operand_stack
+            .push(Variant::Err("oops".into()).into());
+        ld_payload(&mut rt).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some("oops".into()));
+
+        rt.current_thread.operand_stack.push(Variant::None.into());
+        let err = ld_payload(&mut rt).unwrap_err();
+        assert_eq!(err.to_string(), "Illegal argument: None holds no value");
+
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(ld_payload(&mut rt).is_err());
+    }
+}
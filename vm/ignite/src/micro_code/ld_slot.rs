@@ -0,0 +1,57 @@
+use anyhow::Result;
+
+use crate::{Runtime, VmError};
+
+/// Load a value from a slot resolved at compile time.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the instruction on.
+///
+/// * `depth` - The number of frames to walk up the environment chain.
+///
+/// * `idx` - The index of the slot in that frame.
+///
+/// # Errors
+///
+/// If the frame or the slot is not found.
+#[inline]
+pub fn ld_slot(rt: &mut Runtime, depth: usize, idx: usize) -> Result<()> {
+    let val = rt
+        .current_thread
+        .env
+        .upgrade()
+        .ok_or(VmError::EnvironmentDroppedError)?
+        .borrow()
+        .get_slot(depth, idx)?;
+
+    rt.current_thread.operand_stack.push(val);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use crate::extend_environment;
+
+    use super::*;
+
+    #[test]
+    fn test_ld_slot() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["x", "y"], vec![42, 43])?;
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["z"], vec![44])?;
+
+        ld_slot(&mut rt, 0, 0)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(44)));
+
+        ld_slot(&mut rt, 1, 1)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(43)));
+
+        assert!(ld_slot(&mut rt, 0, 1).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use bytecode::BinOp;
+
+use crate::{
+    micro_code::{binop, ld_slot},
+    Runtime,
+};
+
+/// Loads the values in two slots onto the stack and executes a binary operation on them,
+/// as LDSLOT, LDSLOT then BINOP would, e.g. `a + b` of two locals.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the operation on.
+///
+/// * `lhs` - The number of frames up and the index of the slot of the left-hand side.
+///
+/// * `rhs` - The number of frames up and the index of the slot of the right-hand side.
+///
+/// * `op` - The operation to execute.
+///
+/// # Errors
+///
+/// If either slot is not found, or the binary operation fails, see [`binop`].
+#[inline]
+pub fn ld_slots_binop(
+    rt: &mut Runtime,
+    lhs: (usize, usize),
+    rhs: (usize, usize),
+    op: BinOp,
+) -> Result<()> {
+    ld_slot(rt, lhs.0, lhs.1)?;
+    ld_slot(rt, rhs.0, rhs.1)?;
+    binop(rt, op)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use crate::extend_environment;
+
+    use super::*;
+
+    #[test]
+    fn test_ld_slots_binop() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["x"], vec![10])?;
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["y"], vec![3])?;
+
+        ld_slots_binop(&mut rt, (1, 0), (0, 0), BinOp::Sub)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(7)));
+
+        ld_slots_binop(&mut rt, (0, 0), (0, 0), BinOp::Lt)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Bool(false))
+        );
+
+        assert!(ld_slots_binop(&mut rt, (0, 0), (0, 1), BinOp::Add).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use bytecode::{BinOp, Value};
+
+use crate::{
+    micro_code::{binop, ldc},
+    Runtime,
+};
+
+/// Loads a constant onto the stack and executes a binary operation on it and the value below it,
+/// as LDC then BINOP would, e.g. the `+ 1` of `i + 1`.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the operation on.
+///
+/// * `val` - The constant, the right-hand side of the operation.
+///
+/// * `op` - The operation to execute.
+///
+/// # Errors
+///
+/// If the binary operation fails, see [`binop`].
+#[inline]
+pub fn ldc_binop(rt: &mut Runtime, val: Value, op: BinOp) -> Result<()> {
+    ldc(rt, val)?;
+    binop(rt, op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ldc_binop() {
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Int(41)).unwrap();
+        ldc_binop(&mut rt, Value::Int(1), BinOp::Add).unwrap();
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(42)]);
+
+        ldc_binop(&mut rt, Value::Int(2), BinOp::Sub).unwrap();
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(40)]);
+
+        assert!(ldc_binop(&mut rt, Value::Bool(true), BinOp::Add).is_err());
+    }
+}
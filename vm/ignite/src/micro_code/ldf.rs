@@ -1,5 +1,5 @@
 use anyhow::Result;
-use bytecode::{FnType, Symbol, Value, W};
+use bytecode::{Closure, FnType, Symbol, W};
 
 use crate::Runtime;
 
@@ -11,44 +11,48 @@ use crate::Runtime;
 ///
 /// * `addr` - The address of the closure.
 ///
+/// * `sym` - The name of the function.
+///
 /// * `prms` - The parameters of the closure.
 ///
 /// # Errors
 ///
 /// Infallible.
 #[inline]
-pub fn ldf(mut rt: Runtime, addr: usize, prms: Vec<Symbol>) -> Result<Runtime> {
-    let closure = Value::Closure {
+pub fn ldf(rt: &mut Runtime, addr: usize, sym: Symbol, prms: Vec<Symbol>) -> Result<()> {
+    let closure = Closure {
         fn_type: FnType::User,
-        sym: "Closure".to_string(),
+        sym,
         prms,
         addr,
         env: W(rt.current_thread.env.clone()),
     };
 
-    rt.current_thread.operand_stack.push(closure);
-    Ok(rt)
+    rt.current_thread.operand_stack.push(closure.into());
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use bytecode::Value;
+
     use super::*;
 
     #[test]
     fn test_ldf() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldf(rt, 0, vec!["x".to_string()]).unwrap();
+        ldf(&mut rt, 0, "f".into(), vec!["x".into()]).unwrap();
 
         let closure = rt.current_thread.operand_stack.pop().unwrap();
         assert_ne!(
             &closure,
-            &Value::Closure {
+            &Value::from(Closure {
                 fn_type: FnType::User,
-                sym: "Closure".to_string(),
-                prms: vec!["y".to_string()],
+                sym: "f".into(),
+                prms: vec!["y".into()],
                 addr: 0,
                 env: W(rt.current_thread.env.clone()),
-            }
+            })
         )
     }
 }
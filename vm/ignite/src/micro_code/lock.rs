@@ -0,0 +1,327 @@
+use anyhow::{Ok, Result};
+use bytecode::{ByteCodeError, FrameType, RwLock, Semaphore, StackFrame, Value, W};
+
+use crate::{
+    extend_environment, BlockedOn, Runtime, SchedulerEventKind, Thread, ThreadState, VmError,
+    WakeSource,
+};
+
+use super::{post::release, wait::acquire};
+
+/// Pops a lock off the stack and enters a lock block holding it.
+/// A lock frame holding the lock is pushed onto the runtime stack, so that the lock is released
+/// when the frame is popped, whether the block ends, breaks, returns or is unwound by an error.
+/// Like a try block, a new empty scope is created so that the block is exited with EXITSCOPE.
+///
+/// - A mutex is acquired like a semaphore is waited on, so the current thread may block until it is posted.
+/// - An rwlock is taken to write to it, once no thread reads from or writes to it.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to enter the lock block in.
+///
+/// # Errors
+///
+/// If the stack is empty.
+/// If the top value on stack is not a semaphore or an rwlock.
+/// If the runtime stack is already at the maximum call depth.
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn lock(rt: &mut Runtime) -> Result<()> {
+    let lock = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    match lock {
+        Value::Semaphore(sem) => {
+            push_lock_frame(rt, FrameType::LockFrame, sem.clone().into())?;
+            acquire(rt, sem)
+        }
+        Value::RwLock(rw) => acquire_rwlock(rt, rw, true),
+        val => Err(ByteCodeError::TypeMismatch {
+            expected: "Semaphore or RwLock".to_string(),
+            found: format!("{:?}", val),
+        }
+        .into()),
+    }
+}
+
+/// Pops an rwlock off the stack and enters a lock block reading from it, alongside the other readers.
+/// The rwlock is taken once no thread writes to it or is waiting to, so that readers can't keep a writer waiting forever.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to enter the lock block in.
+///
+/// # Errors
+///
+/// If the stack is empty.
+/// If the top value on stack is not an rwlock.
+/// If the runtime stack is already at the maximum call depth.
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn read_lock(rt: &mut Runtime) -> Result<()> {
+    let rw: RwLock = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?
+        .try_into()?;
+
+    acquire_rwlock(rt, rw, false)
+}
+
+/// Release the lock held by the frame of a lock block, if it is one.
+/// A mutex is posted, handing it off to the first thread blocked on it, and the threads waiting
+/// on an rwlock are woken up to try to take it again once it is free.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime the current thread popped the frame in.
+///
+/// * `frame` - The frame popped off the runtime stack of the current thread.
+///
+/// # Errors
+///
+/// If the lock of a lock frame is not a semaphore or an rwlock.
+#[inline]
+pub(crate) fn unlock(rt: &mut Runtime, frame: &StackFrame) -> Result<()> {
+    let write = match frame.frame_type {
+        FrameType::LockFrame => true,
+        FrameType::ReadLockFrame => false,
+        _ => return Ok(()),
+    };
+
+    match frame.lock.clone() {
+        Some(Value::Semaphore(sem)) => {
+            let held = &mut rt.current_thread.held_semaphores;
+            if let Some(i) = held.iter().position(|held_sem| held_sem == &sem) {
+                held.remove(i);
+            }
+            release(rt, sem)
+        }
+        Some(Value::RwLock(rw)) => {
+            release_rwlock(rt, rw, write);
+            Ok(())
+        }
+        val => Err(ByteCodeError::TypeMismatch {
+            expected: "Semaphore or RwLock".to_string(),
+            found: format!("{:?}", val),
+        }
+        .into()),
+    }
+}
+
+/// Release an rwlock taken to write to it, or one of its readers, waking up the threads waiting
+/// on it once no thread holds it anymore.
+pub(crate) fn release_rwlock(rt: &mut Runtime, rw: RwLock, write: bool) {
+    let mut state = rw.lock().unwrap();
+    if write {
+        state.writer = false;
+    } else {
+        state.readers = state.readers.saturating_sub(1);
+    }
+    let free = !state.writer && state.readers == 0;
+    drop(state); // Unlock the rwlock.
+
+    if free {
+        rt.wake_blocked(|source| source.is_rwlock(&rw));
+    }
+}
+
+/// Whether the thread acquires the mutex to hold it in a lock block, as opposed to waiting on it as a semaphore.
+/// It does when it has more lock frames of the mutex than holds of it, since the frame is pushed before the
+/// mutex is acquired and [cv_wait](super::cv_wait) gives up the hold while waiting inside the block.
+pub(crate) fn acquires_lock(thread: &Thread, sem: &Semaphore) -> bool {
+    let frames = thread
+        .runtime_stack
+        .iter()
+        .filter(|frame| {
+            matches!(frame.frame_type, FrameType::LockFrame)
+                && matches!(&frame.lock, Some(Value::Semaphore(lock)) if lock == sem)
+        })
+        .count();
+    let holds = thread
+        .held_semaphores
+        .iter()
+        .filter(|held_sem| *held_sem == sem)
+        .count();
+    holds < frames
+}
+
+fn push_lock_frame(rt: &mut Runtime, frame_type: FrameType, lock: Value) -> Result<()> {
+    rt.check_call_depth()?;
+
+    let current_env = rt.current_thread.env.clone();
+    let frame = StackFrame::new_lock(frame_type, W(current_env.clone()), lock);
+    rt.current_thread.runtime_stack.push(frame);
+
+    extend_environment::<&str, Value>(rt, current_env, vec![], vec![])
+}
+
+/// Take the rwlock and push the lock frame holding it, or block the current thread until it is released.
+/// A blocked thread executes the instruction again when it is woken up, so the rwlock is put back on its stack.
+fn acquire_rwlock(rt: &mut Runtime, rw: RwLock, write: bool) -> Result<()> {
+    let writer_waiting = rt
+        .blocked_queue
+        .iter()
+        .any(|(_, sources)| sources.iter().any(|s| s.is_rwlock_writer(&rw)));
+
+    let mut state = rw.lock().unwrap();
+    let free = if write {
+        !state.writer && state.readers == 0
+    } else {
+        !state.writer && !writer_waiting
+    };
+
+    if free {
+        if write {
+            state.writer = true;
+        } else {
+            state.readers += 1;
+        }
+        drop(state); // Unlock the rwlock.
+
+        let frame_type = if write {
+            FrameType::LockFrame
+        } else {
+            FrameType::ReadLockFrame
+        };
+        return push_lock_frame(rt, frame_type, rw.into());
+    }
+
+    drop(state); // Unlock the rwlock.
+
+    rt.current_thread.operand_stack.push(rw.clone().into());
+    rt.current_thread.pc -= 1; // Decrement the program counter to re-execute the lock instruction
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::RwLock),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, vec![WakeSource::RwLock { lock: rw, write }]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Semaphore;
+
+    use crate::{
+        micro_code::{exit_scope, reset, spawn, yield_},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_lock_mutex() -> Result<()> {
+        let mut rt = Runtime::default();
+        let sem = Semaphore::new(1);
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear();
+
+        // The main thread holds the mutex in a lock frame.
+        rt.current_thread.operand_stack.push(sem.clone().into());
+        lock(&mut rt)?;
+        assert_eq!(*sem.lock().unwrap(), 0);
+        let frame = rt.current_thread.runtime_stack.last().unwrap();
+        assert_eq!(frame.frame_type, FrameType::LockFrame);
+        assert_eq!(rt.current_thread.held_semaphores, vec![sem.clone()]);
+
+        // The child is blocked on the mutex.
+        yield_(&mut rt)?;
+        rt.current_thread.operand_stack.push(sem.clone().into());
+        lock(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        // Leaving the block hands the mutex to the child.
+        exit_scope(&mut rt)?;
+        assert!(rt.current_thread.held_semaphores.is_empty());
+        assert!(rt.blocked_queue.is_empty());
+        assert_eq!(*sem.lock().unwrap(), 0);
+        assert_eq!(rt.ready_queue.back().unwrap().thread_id, MAIN_THREAD_ID + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_rwlock() -> Result<()> {
+        let mut rt = Runtime::default();
+        let rw = RwLock::new();
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear();
+
+        // The main thread and the first child read at the same time.
+        rt.current_thread.operand_stack.push(rw.clone().into());
+        read_lock(&mut rt)?;
+        yield_(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        rt.current_thread.operand_stack.push(rw.clone().into());
+        read_lock(&mut rt)?;
+        assert_eq!(rw.lock().unwrap().readers, 2);
+        exit_scope(&mut rt)?;
+        assert_eq!(rw.lock().unwrap().readers, 1);
+
+        // The first child is blocked writing until the main thread is done reading.
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        rt.current_thread.operand_stack.push(rw.clone().into());
+        lock(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 2);
+
+        // Readers wait for the blocked writer.
+        rt.current_thread.pc = 1;
+        rt.current_thread.operand_stack.push(rw.clone().into());
+        read_lock(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert_eq!(rw.lock().unwrap().readers, 1);
+        assert_eq!(rt.blocked_queue.len(), 2);
+
+        // The threads waiting take the rwlock again once it is free, the writer first.
+        exit_scope(&mut rt)?;
+        assert_eq!(rw.lock().unwrap().readers, 0);
+        assert!(rt.blocked_queue.is_empty());
+        yield_(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        lock(&mut rt)?;
+        assert!(rw.lock().unwrap().writer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlock_on_return() -> Result<()> {
+        let mut rt = Runtime::default();
+        let rw = RwLock::new();
+        let env = W(rt.current_thread.env.clone());
+        rt.current_thread
+            .runtime_stack
+            .push(StackFrame::new(FrameType::CallFrame, env));
+
+        rt.current_thread.operand_stack.push(rw.clone().into());
+        lock(&mut rt)?;
+        assert!(rw.lock().unwrap().writer);
+
+        // Returning pops the lock frame, releasing the rwlock.
+        reset(&mut rt, FrameType::CallFrame)?;
+        assert!(rt.current_thread.runtime_stack.is_empty());
+        assert!(!rw.lock().unwrap().writer);
+
+        let mut rt = Runtime::default();
+        rt.current_thread.operand_stack.push(2.into());
+        assert!(lock(&mut rt).is_err());
+
+        Ok(())
+    }
+}
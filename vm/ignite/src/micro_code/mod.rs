@@ -1,43 +1,133 @@
+pub use append::append;
 pub use apply_builtin::apply_builtin;
 pub use assign::assign;
+pub use assign_slot::assign_slot;
+pub use barrier_wait::barrier_wait;
 pub use binop::binop;
+pub use binop_r::binop_r;
 pub use call::call;
+pub use chan_close::chan_close;
+pub use chan_recv::chan_recv;
+pub use chan_send::chan_send;
+pub use check_len::check_len;
+pub use cv_notify_all::cv_notify_all;
+pub use cv_notify_one::cv_notify_one;
+pub use cv_wait::cv_wait;
 pub use done::done;
 pub use enter_scope::enter_scope;
 pub use exit_scope::exit_scope;
+pub use generator::generator;
 pub use goto::goto;
+pub use iter::iter;
+pub use iter_range::iter_range;
 pub use jof::jof;
+pub use jof_or_pop::jof_or_pop;
 pub use join::join;
+pub use join_scope::join_scope;
+pub use kill::kill;
 pub use ld::ld;
+pub use ld_elem::ld_elem;
+pub use ld_field::ld_field;
+pub use ld_method::ld_method;
+pub use ld_payload::ld_payload;
+pub use ld_slot::ld_slot;
+pub use ld_slots_binop::ld_slots_binop;
 pub use ldc::ldc;
+pub use ldc_binop::ldc_binop;
 pub use ldf::ldf;
+pub use lock::{lock, read_lock};
+pub use mov_r::mov_r;
+pub use new_array::new_array;
+pub use new_struct::new_struct;
+pub use new_variant::new_variant;
+pub use next::next;
 pub use pop::pop;
 pub use post::post;
+pub use queue_pop::queue_pop;
+pub use queue_push::queue_push;
 pub use reset::reset;
+pub use resume::resume;
+pub use select::select;
 pub use sem_create::sem_create;
+pub use set_field::set_field;
+pub use set_method::set_method;
+pub use slice::slice;
 pub use spawn::spawn;
+pub use spawn_scope::spawn_scope;
+pub use struct_::struct_; // struct is a reserved keyword in Rust
+pub use suspend::suspend;
+pub use test_variant::test_variant;
+pub use try_::{catch, try_}; // try is a reserved keyword in Rust
 pub use unop::unop;
 pub use wait::wait;
+pub use wait_timeout::wait_timeout;
+pub use wg_add::wg_add;
+pub use wg_wait::wg_wait;
 pub use yield_::yield_; // yield is a reserved keyword in Rust
 
+mod append;
 mod apply_builtin;
 mod assign;
+mod assign_slot;
+mod barrier_wait;
 mod binop;
+mod binop_r;
 mod call;
+mod chan_close;
+mod chan_recv;
+mod chan_send;
+mod check_len;
+mod cv_notify_all;
+mod cv_notify_one;
+mod cv_wait;
 mod done;
 mod enter_scope;
 mod exit_scope;
+mod generator;
 mod goto;
+mod iter;
+mod iter_range;
 mod jof;
+mod jof_or_pop;
 mod join;
+mod join_scope;
+mod kill;
 mod ld;
+mod ld_elem;
+mod ld_field;
+mod ld_method;
+mod ld_payload;
+mod ld_slot;
+mod ld_slots_binop;
 mod ldc;
+mod ldc_binop;
 mod ldf;
+mod lock;
+mod mov_r;
+mod new_array;
+mod new_struct;
+mod new_variant;
+mod next;
 mod pop;
 mod post;
+mod queue_pop;
+mod queue_push;
 mod reset;
+mod resume;
+mod select;
 mod sem_create;
+mod set_field;
+mod set_method;
+mod slice;
 mod spawn;
+mod spawn_scope;
+mod struct_; // struct is a reserved keyword in Rust
+mod suspend;
+mod test_variant;
+mod try_; // try is a reserved keyword in Rust
 mod unop;
 mod wait;
+mod wait_timeout;
+mod wg_add;
+mod wg_wait;
 mod yield_; // yield is a reserved keyword in Rust
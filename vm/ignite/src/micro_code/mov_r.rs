@@ -0,0 +1,87 @@
+use anyhow::Result;
+use bytecode::{Operand, Value};
+
+use crate::{Runtime, VmError};
+
+/// Assigns an operand to a slot resolved at compile time, without going through the operand stack.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the instruction on.
+///
+/// * `dst` - The number of frames up and the index of the slot to assign to.
+///
+/// * `src` - The operand to assign.
+///
+/// # Errors
+///
+/// If the operand or the slot is not found.
+#[inline]
+pub fn mov_r(rt: &mut Runtime, dst: (usize, usize), src: Operand) -> Result<()> {
+    let val = operand(rt, src)?;
+    rt.current_thread
+        .env
+        .upgrade()
+        .ok_or(VmError::EnvironmentDroppedError)?
+        .borrow_mut()
+        .update_slot(dst.0, dst.1, val)?;
+
+    Ok(())
+}
+
+/// The value of an operand of an instruction of the register backend, popping it if it is on the operand stack.
+///
+/// # Errors
+///
+/// If the stack is empty, or the frame or the slot is not found.
+#[inline]
+pub(crate) fn operand(rt: &mut Runtime, operand: Operand) -> Result<Value> {
+    let val = match operand {
+        Operand::Stack => rt
+            .current_thread
+            .operand_stack
+            .pop()
+            .ok_or(VmError::OperandStackUnderflow)?,
+        Operand::Slot(depth, idx) => rt
+            .current_thread
+            .env
+            .upgrade()
+            .ok_or(VmError::EnvironmentDroppedError)?
+            .borrow()
+            .get_slot(depth, idx)?,
+        Operand::Const(val) => val,
+    };
+
+    Ok(val)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{extend_environment, micro_code::ld_slot};
+
+    use super::*;
+
+    #[test]
+    fn test_mov_r() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env, vec!["x", "y"], vec![1, 2])?;
+
+        mov_r(&mut rt, (0, 0), Operand::Const(Value::Int(42)))?;
+        mov_r(&mut rt, (0, 1), Operand::Slot(0, 0))?;
+        rt.current_thread.operand_stack.push(Value::Int(43));
+        mov_r(&mut rt, (0, 0), Operand::Stack)?;
+
+        ld_slot(&mut rt, 0, 0)?;
+        ld_slot(&mut rt, 0, 1)?;
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Value::Int(43), Value::Int(42)]
+        );
+
+        rt.current_thread.operand_stack.clear();
+        assert!(mov_r(&mut rt, (0, 0), Operand::Stack).is_err());
+        assert!(mov_r(&mut rt, (0, 2), Operand::Slot(0, 0)).is_err());
+        Ok(())
+    }
+}
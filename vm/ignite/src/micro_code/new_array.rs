@@ -0,0 +1,52 @@
+use anyhow::Result;
+use bytecode::Value;
+
+use crate::{Runtime, VmError};
+
+/// Pop the given number of values and push an array of them, in the order they were pushed.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to create the array in.
+///
+/// * `len` - The number of elements of the array.
+///
+/// # Errors
+///
+/// If the operand stack does not contain enough values to pop.
+#[inline]
+pub fn new_array(rt: &mut Runtime, len: usize) -> Result<()> {
+    let stack = &mut rt.current_thread.operand_stack;
+    if stack.len() < len {
+        return Err(VmError::OperandStackUnderflow.into());
+    }
+
+    let elems = stack.split_off(stack.len() - len);
+    stack.push(Value::from(elems));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_array() {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(1.into());
+        rt.current_thread.operand_stack.push(2.into());
+        new_array(&mut rt, 2).unwrap();
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::from(vec![1.into(), 2.into()]))
+        );
+
+        new_array(&mut rt, 0).unwrap();
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::from(vec![]))
+        );
+
+        assert!(new_array(&mut rt, 1).is_err());
+    }
+}
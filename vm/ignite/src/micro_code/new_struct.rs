@@ -0,0 +1,100 @@
+use anyhow::Result;
+use bytecode::{type_of, Struct, Symbol, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop the values of the given fields and the struct type below them, and push an instance of the struct.
+/// The values are in the order the fields are given, which may differ from the order they are declared in.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to create the struct in.
+///
+/// * `fields` - The fields the values are for.
+///
+/// # Errors
+///
+/// If the operand stack does not contain enough values to pop (fields + 1).
+/// If the value below the fields is not a struct type.
+/// If a field is not a field of the struct, or a field of the struct is not given.
+#[inline]
+pub fn new_struct(rt: &mut Runtime, fields: Vec<Symbol>) -> Result<()> {
+    let stack = &mut rt.current_thread.operand_stack;
+    if stack.len() < fields.len() + 1 {
+        return Err(VmError::OperandStackUnderflow.into());
+    }
+
+    let vals = stack.split_off(stack.len() - fields.len());
+    let ty = stack.pop().ok_or(VmError::OperandStackUnderflow)?;
+
+    let Value::StructType(ty) = ty else {
+        return Err(VmError::BadType {
+            expected: "StructType".to_string(),
+            found: type_of(&ty).to_string(),
+        }
+        .into());
+    };
+
+    let mut slots: Vec<Option<Value>> = vec![None; ty.fields.len()];
+    for (field, val) in fields.into_iter().zip(vals) {
+        let idx = ty.field_idx(field).ok_or_else(|| VmError::NoSuchField {
+            ty: ty.name.to_string(),
+            field: field.to_string(),
+        })?;
+        slots[idx] = Some(val);
+    }
+
+    let mut vals = Vec::with_capacity(slots.len());
+    for (field, val) in ty.fields.iter().zip(slots) {
+        let val = val.ok_or_else(|| {
+            VmError::IllegalArgument(format!("missing field {} of {}", field, ty.name))
+        })?;
+        vals.push(val);
+    }
+
+    let instance = Struct { ty, fields: vals };
+    rt.current_thread.operand_stack.push(instance.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bytecode::StructType;
+
+    use super::*;
+
+    #[test]
+    fn test_new_struct() {
+        let ty = Rc::new(StructType::new("P".into(), vec!["x".into(), "y".into()]));
+
+        let mut rt = Runtime::new(vec![]);
+        let stack = &mut rt.current_thread.operand_stack;
+        stack.push(Value::StructType(ty.clone()));
+        stack.push(2.into());
+        stack.push(1.into());
+        new_struct(&mut rt, vec!["y".into(), "x".into()]).unwrap();
+
+        let val = rt.current_thread.operand_stack.pop().unwrap();
+        assert_eq!(
+            val,
+            Value::from(Struct {
+                ty: ty.clone(),
+                fields: vec![1.into(), 2.into()],
+            })
+        );
+
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread
+            .operand_stack
+            .push(Value::StructType(ty.clone()));
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(new_struct(&mut rt, vec!["x".into()]).is_err());
+
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(Value::StructType(ty));
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(new_struct(&mut rt, vec!["z".into()]).is_err());
+    }
+}
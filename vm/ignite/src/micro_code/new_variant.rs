@@ -0,0 +1,71 @@
+use anyhow::Result;
+use bytecode::{Enum, Symbol};
+
+use crate::{Runtime, VmError};
+
+/// Push the given variant of the enum, popping the value it holds if it holds one.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to create the variant in.
+///
+/// * `ty` - The name of the enum.
+///
+/// * `variant` - The name of the variant.
+///
+/// * `holds_value` - Whether the variant holds the value on top of the operand stack.
+///
+/// # Errors
+///
+/// If the variant holds a value and the operand stack is empty.
+#[inline]
+pub fn new_variant(rt: &mut Runtime, ty: Symbol, variant: Symbol, holds_value: bool) -> Result<()> {
+    let payload = if holds_value {
+        let val = rt
+            .current_thread
+            .operand_stack
+            .pop()
+            .ok_or(VmError::OperandStackUnderflow)?;
+        Some(val)
+    } else {
+        None
+    };
+
+    let val = Enum {
+        ty,
+        variant,
+        payload,
+    };
+    rt.current_thread.operand_stack.push(val.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_new_variant() {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(2.into());
+        new_variant(&mut rt, "Shape".into(), "Circle".into(), true).unwrap();
+        new_variant(&mut rt, "Shape".into(), "Empty".into(), false).unwrap();
+
+        let empty = Value::from(Enum {
+            ty: "Shape".into(),
+            variant: "Empty".into(),
+            payload: None,
+        });
+        let circle = Value::from(Enum {
+            ty: "Shape".into(),
+            variant: "Circle".into(),
+            payload: Some(2.into()),
+        });
+        assert_eq!(rt.current_thread.operand_stack, vec![circle, empty]);
+
+        let mut rt = Runtime::new(vec![]);
+        assert!(new_variant(&mut rt, "Shape".into(), "Circle".into(), true).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use bytecode::{type_of, Value};
+
+use crate::{Runtime, VmError};
+
+use super::resume;
+
+/// Pop an iterator and push its next value, or jump to the given program counter if it has none left.
+/// The iterator is advanced in place, so the copy of it stored in the variable of the loop advances too.
+/// A generator is resumed instead, pushing its next value when it yields or jumping when it is done.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the operation on.
+///
+/// * `pc` - The program counter to jump to when the iterator is done.
+///
+/// # Errors
+///
+/// If the operand stack is empty or the value is not an iterator or a generator, or the generator is already running.
+#[inline]
+pub fn next(rt: &mut Runtime, pc: usize) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    if let Value::Generator(gen) = val {
+        return resume(rt, gen, pc);
+    }
+
+    let Value::Iter(iter) = &val else {
+        return Err(VmError::BadType {
+            expected: "Iter".to_string(),
+            found: type_of(&val).to_string(),
+        }
+        .into());
+    };
+
+    match iter.next() {
+        Some(val) => rt.current_thread.operand_stack.push(val),
+        None => rt.current_thread.pc = pc,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Iter;
+
+    use super::*;
+
+    #[test]
+    fn test_next() {
+        let mut rt = Runtime::new(vec![]);
+        let iter = Value::Iter(Iter::range(0, 1));
+
+        rt.current_thread.operand_stack.push(iter.clone());
+        next(&mut rt, 42).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(0.into()));
+        assert_eq!(rt.current_thread.pc, 0);
+
+        rt.current_thread.operand_stack.push(iter);
+        next(&mut rt, 42).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), None);
+        assert_eq!(rt.current_thread.pc, 42);
+
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(next(&mut rt, 42).is_err());
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::{Ok, Result};
 use bytecode::Semaphore;
 
-use crate::{Runtime, VmError};
+use crate::{Runtime, SchedulerEventKind, ThreadState, VmError, WakeSource};
+
+use super::lock::acquires_lock;
 
 /// Pops a value off the stack.
 /// The value is expected to be a semaphore.
 /// The semaphore is incremented.
-/// If a thread is blocked on this semaphore, the first blocked thread is moved to the ready queue.
+/// If a thread is blocked on this semaphore, the one the scheduler picks, the first blocked by default,
+/// is moved to the ready queue.
+/// If the blocked thread was waiting on the semaphore in a select, it continues from the address of the
+/// corresponding arm.
 /// The current thread continues execution.
 ///
 /// # Arguments
@@ -18,7 +23,7 @@ use crate::{Runtime, VmError};
 /// If the stack is empty.
 /// If the top value on stack is not a semaphore.
 #[inline]
-pub fn post(mut rt: Runtime) -> Result<Runtime> {
+pub fn post(rt: &mut Runtime) -> Result<()> {
     let sem: Semaphore = rt
         .current_thread
         .operand_stack
@@ -26,27 +31,64 @@ pub fn post(mut rt: Runtime) -> Result<Runtime> {
         .ok_or(VmError::OperandStackUnderflow)?
         .try_into()?;
 
+    // The current thread no longer holds the semaphore.
+    let held = &mut rt.current_thread.held_semaphores;
+    if let Some(i) = held.iter().position(|held_sem| held_sem == &sem) {
+        held.remove(i);
+    }
+
+    release(rt, sem)
+}
+
+/// Increments the semaphore and hands it off to a thread blocked on it, if any, as the scheduler picks.
+/// The woken thread is moved to the ready queue, and holds the semaphore if it is the mutex of its lock block.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime the semaphore is released in.
+///
+/// * `sem` - The semaphore to release.
+#[inline]
+pub(crate) fn release(rt: &mut Runtime, sem: Semaphore) -> Result<()> {
     let mut sem_guard = sem.lock().unwrap();
     *sem_guard += 1;
 
-    // Find the first blocked thread that is waiting on the semaphore.
-    let blocked_thread = rt
-        .blocked_queue
-        .iter()
-        .position(|(_, blocking_sem)| blocking_sem == &sem)
-        .map(|i| rt.blocked_queue.remove(i));
+    // Find the blocked thread the scheduler wakes up of those waiting on the semaphore.
+    let blocked_thread = rt.take_waiter(|source| source.is_semaphore(&sem));
 
-    let Some(Some((blocked_thread, _))) = blocked_thread else {
+    let Some((mut blocked_thread, sources)) = blocked_thread else {
         // If no blocked threads are found, nothing needs to be done.
-        return Ok(rt);
+        return Ok(());
     };
 
     *sem_guard -= 1;
     drop(sem_guard); // Unlock the semaphore.
 
+    let addr = sources.iter().find_map(|source| match source {
+        WakeSource::Semaphore { sem: s, addr } if s == &sem => *addr,
+        _ => None,
+    });
+
+    if let Some(addr) = addr {
+        blocked_thread.pc = addr;
+    }
+
+    // A thread waiting with a timeout acquired the semaphore in time.
+    if sources
+        .iter()
+        .any(|source| matches!(source, WakeSource::Timeout(_)))
+    {
+        blocked_thread.operand_stack.push(true.into());
+    }
+
     // Move the blocked thread to the ready queue.
-    rt.ready_queue.push_back(blocked_thread);
-    Ok(rt)
+    if acquires_lock(&blocked_thread, &sem) {
+        blocked_thread.held_semaphores.push(sem);
+    }
+    rt.set_thread_state(blocked_thread.thread_id, ThreadState::Ready);
+    rt.emit_event(blocked_thread.thread_id, SchedulerEventKind::Woken);
+    rt.push_ready(blocked_thread);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -64,10 +106,10 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(0);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = ld(rt, "sem".into())?;
-        rt = post(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, "sem".into())?;
+        post(&mut rt)?;
 
         // Since no threads are blocked on the semaphore, the current thread should continue.
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
@@ -82,13 +124,13 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(0);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = yield_(rt)?; // yield the current thread to child thread
-        rt = ld(rt, "sem".into())?;
-        rt = wait(rt)?;
-        rt = ld(rt, "sem".into())?;
-        rt = post(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        yield_(&mut rt)?; // yield the current thread to child thread
+        ld(&mut rt, "sem".into())?;
+        wait(&mut rt)?;
+        ld(&mut rt, "sem".into())?;
+        post(&mut rt)?;
 
         // Child thread should be moved to the ready queue.
         let child_thread_id = MAIN_THREAD_ID + 1;
@@ -0,0 +1,100 @@
+use anyhow::{Ok, Result};
+use bytecode::BoundedQueue;
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, VmError, WakeSource};
+
+/// Pop the oldest value in the bounded queue, pushing it onto the operand stack.
+/// If a thread is blocked pushing to the queue, the value it is pushing takes the freed place in the queue,
+/// and the thread is moved to the ready queue.
+///
+/// Otherwise, the queue is empty and the current thread is blocked until a thread pushes to it.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to pop in.
+///
+/// * `q` - The bounded queue to pop from.
+///
+/// # Errors
+///
+/// If a blocked pusher has no value on its operand stack.
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn queue_pop(rt: &mut Runtime, q: BoundedQueue) -> Result<()> {
+    let mut state = q.0.borrow_mut();
+
+    if let Some(val) = state.buf.pop_front() {
+        rt.current_thread.operand_stack.push(val);
+
+        if let Some(mut pusher) = rt.take_blocked(|source| source.is_queue_push(&q)) {
+            let val = pusher
+                .operand_stack
+                .pop()
+                .ok_or(VmError::OperandStackUnderflow)?;
+            state.buf.push_back(val);
+            rt.wake(pusher);
+        }
+
+        return Ok(());
+    }
+
+    drop(state); // Release the queue.
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::BoundedQueue),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, vec![WakeSource::QueuePop(q)]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::queue_push, micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_queue_pop() -> Result<()> {
+        let mut rt = Runtime::default();
+        let q = BoundedQueue::new(1);
+
+        // The queue is empty, so the main thread is blocked.
+        spawn(&mut rt, 0, 0)?;
+        queue_pop(&mut rt, q.clone())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+
+        // Pushing hands the value to the main thread instead of adding it to the queue.
+        queue_push(&mut rt, q.clone(), 1.into())?;
+        assert!(q.0.borrow().buf.is_empty());
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Ready));
+        let main = rt.ready_queue.back().unwrap();
+        assert_eq!(main.operand_stack.last(), Some(&1.into()));
+
+        // The main thread blocks pushing to a full queue.
+        let mut rt = Runtime::default();
+        let q = BoundedQueue::new(1);
+        queue_push(&mut rt, q.clone(), 2.into())?;
+        spawn(&mut rt, 0, 0)?;
+        queue_push(&mut rt, q.clone(), 3.into())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+
+        // Popping takes the oldest value and lets the blocked value in.
+        queue_pop(&mut rt, q.clone())?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(2.into()));
+        assert_eq!(q.0.borrow().buf.front(), Some(&3.into()));
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Ready));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{Ok, Result};
+use bytecode::{BoundedQueue, Value};
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, WakeSource};
+
+/// Push the value to the back of the bounded queue. Nothing is pushed onto the operand stack.
+/// - If a thread is blocked popping from the queue, the value is handed to the first such thread,
+///   which is moved to the ready queue.
+/// - If the queue has room, the value is added to it.
+///
+/// Otherwise, the queue is full and the current thread is blocked until a thread pops from it.
+/// The value waits on the operand stack of the current thread until then.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to push in.
+///
+/// * `q` - The bounded queue to push to.
+///
+/// * `val` - The value to push.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn queue_push(rt: &mut Runtime, q: BoundedQueue, val: Value) -> Result<()> {
+    let mut state = q.0.borrow_mut();
+
+    // A thread only waits to pop while the queue is empty, so the value goes straight to it.
+    if let Some(mut popper) = rt.take_blocked(|source| source.is_queue_pop(&q)) {
+        popper.operand_stack.push(val);
+        rt.wake(popper);
+        return Ok(());
+    }
+
+    if state.buf.len() < state.cap {
+        state.buf.push_back(val);
+        return Ok(());
+    }
+
+    drop(state); // Release the queue.
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.current_thread.operand_stack.push(val);
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::BoundedQueue),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, vec![WakeSource::QueuePush(q)]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_queue_push() -> Result<()> {
+        let mut rt = Runtime::default();
+        let q = BoundedQueue::new(1);
+
+        // The queue has room.
+        queue_push(&mut rt, q.clone(), 1.into())?;
+        assert!(rt.current_thread.operand_stack.is_empty());
+        assert_eq!(q.0.borrow().buf.len(), 1);
+
+        // The queue is full, so the main thread is blocked with the value it is pushing.
+        spawn(&mut rt, 0, 0)?;
+        queue_push(&mut rt, q.clone(), 2.into())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        let (main, sources) = rt.blocked_queue.front().unwrap();
+        assert_eq!(main.operand_stack.last(), Some(&2.into()));
+        assert!(sources[0].is_queue_push(&q));
+
+        Ok(())
+    }
+}
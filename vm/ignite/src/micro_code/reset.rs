@@ -1,10 +1,18 @@
 use crate::{Runtime, VmError};
 use anyhow::Result;
-use bytecode::FrameType;
+use bytecode::{FrameType, Value};
+
+use super::lock::unlock;
 
 /// Reset the runtime to the last frame of the given type. This will pop all frames up to and including
 /// the last frame of the given type.
 ///
+/// A generator has no call frame of its own, so returning from it reaches the resume frame of the loop
+/// running it instead. The generator is then done: its operands are dropped along with it, and the loop goes on
+/// at its end.
+///
+/// The locks held by the lock blocks popped along the way are released.
+///
 /// # Arguments
 ///
 /// * `rt` - The runtime to reset.
@@ -15,13 +23,26 @@ use bytecode::FrameType;
 ///
 /// If the runtime stack underflows. i.e. there are no frames of the given type.
 #[inline]
-pub fn reset(mut rt: Runtime, ft: FrameType) -> Result<Runtime> {
+pub fn reset(rt: &mut Runtime, ft: FrameType) -> Result<()> {
     loop {
         let frame = rt
             .current_thread
             .runtime_stack
             .pop()
             .ok_or(VmError::RuntimeStackUnderflow)?;
+        unlock(rt, &frame)?;
+
+        if frame.frame_type == FrameType::ResumeFrame && ft == FrameType::CallFrame {
+            let thread = &mut rt.current_thread;
+            let operand_len = frame.operand_len.unwrap_or_default();
+            thread.operand_stack.truncate(operand_len);
+            if let Some(Value::Generator(gen)) = thread.operand_stack.pop() {
+                thread.pc = gen.0.borrow().done_addr;
+                gen.finish();
+            }
+            thread.env = frame.env.0;
+            break;
+        }
 
         if frame.frame_type != ft {
             continue;
@@ -35,7 +56,7 @@ pub fn reset(mut rt: Runtime, ft: FrameType) -> Result<Runtime> {
         break;
     }
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -65,16 +86,11 @@ mod tests {
 
         assert!(rt.current_thread.runtime_stack.len() == 3);
 
-        rt = reset(rt, FrameType::BlockFrame).unwrap();
+        reset(&mut rt, FrameType::BlockFrame).unwrap();
 
         assert!(rt.current_thread.runtime_stack.len() == 1);
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"a".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("a")?,
             Value::Int(42)
         );
 
@@ -103,7 +119,7 @@ mod tests {
 
         assert!(rt.current_thread.runtime_stack.len() == 3);
 
-        rt = reset(rt, FrameType::BlockFrame).unwrap();
+        reset(&mut rt, FrameType::BlockFrame).unwrap();
 
         assert!(rt.current_thread.runtime_stack.len() == 1);
         assert_eq!(rt.current_thread.pc, 123);
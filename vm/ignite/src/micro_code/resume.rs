@@ -0,0 +1,112 @@
+use anyhow::Result;
+use bytecode::{FrameType, Generator, GeneratorStatus, StackFrame, Value, W};
+
+use crate::{Runtime, VmError};
+
+/// Resume a generator, swapping its saved state into the current thread like a context switch.
+/// The generator is pushed back onto the operand stack under a resume frame remembering where the loop resuming
+/// it goes on, then its frames, operands, environment and program counter are restored.
+/// If the generator is done, jump to the given program counter instead.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to resume the generator in.
+///
+/// * `gen` - The generator to resume.
+///
+/// * `done_addr` - The program counter to jump to once the generator is done.
+///
+/// # Errors
+///
+/// If the generator is already running, i.e. it resumes itself.
+/// If the runtime stack is already at the maximum call depth.
+#[inline]
+pub fn resume(rt: &mut Runtime, gen: Generator, done_addr: usize) -> Result<()> {
+    match gen.status() {
+        GeneratorStatus::Done => {
+            rt.current_thread.pc = done_addr;
+            return Ok(());
+        }
+        GeneratorStatus::Running => return Err(VmError::GeneratorRunning.into()),
+        GeneratorStatus::Suspended => (),
+    }
+
+    rt.check_call_depth()?;
+
+    let thread = &mut rt.current_thread;
+    thread.operand_stack.push(Value::Generator(gen.clone()));
+
+    let base = thread.operand_stack.len();
+    let frame = StackFrame {
+        frame_type: FrameType::ResumeFrame,
+        address: Some(thread.pc),
+        env: W(thread.env.clone()),
+        sym: None,
+        operand_len: Some(base),
+        lock: None,
+    };
+    thread.runtime_stack.push(frame);
+
+    let mut state = gen.0.borrow_mut();
+    state.status = GeneratorStatus::Running;
+    state.done_addr = done_addr;
+
+    for mut frame in state.runtime_stack.drain(..) {
+        frame.operand_len = frame.operand_len.map(|len| len + base);
+        thread.runtime_stack.push(frame);
+    }
+    thread.operand_stack.append(&mut state.operand_stack);
+    thread.env = state.env.0.clone();
+    thread.pc = state.pc;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{weak_clone, Environment};
+
+    use super::*;
+
+    #[test]
+    fn test_resume() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.pc = 7;
+        rt.current_thread.operand_stack.push(1.into());
+
+        let env = Environment::new_wrapped();
+        let gen = Generator::new(42, W(weak_clone(&env)));
+        gen.0.borrow_mut().operand_stack.push(2.into());
+        gen.0
+            .borrow_mut()
+            .runtime_stack
+            .push(StackFrame::new_try(Default::default(), 50, 1));
+
+        resume(&mut rt, gen.clone(), 99)?;
+        assert_eq!(rt.current_thread.pc, 42);
+        assert!(rt.current_thread.env.ptr_eq(&weak_clone(&env)));
+        assert_eq!(gen.status(), GeneratorStatus::Running);
+
+        // the generator sits under its operands, and its try frame is rebased onto them
+        let stack = &rt.current_thread.operand_stack;
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack[1], Value::Generator(gen.clone()));
+        assert_eq!(stack[2], 2.into());
+
+        let frames = &rt.current_thread.runtime_stack;
+        assert_eq!(frames[0].frame_type, FrameType::ResumeFrame);
+        assert_eq!(frames[0].address, Some(7));
+        assert_eq!(frames[0].operand_len, Some(2));
+        assert_eq!(frames[1].operand_len, Some(3));
+
+        // can't resume itself
+        assert!(resume(&mut rt, gen.clone(), 99).is_err());
+
+        // a generator that is done jumps to the end of the loop
+        gen.finish();
+        resume(&mut rt, gen, 99)?;
+        assert_eq!(rt.current_thread.pc, 99);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::{Ok, Result};
+use bytecode::{Address, SelectOp, Value};
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, VmError, WakeSource};
+
+use super::{chan_recv, chan_send};
+
+/// Pops the operands of each of the given arms off the stack, a semaphore for an arm waiting on it,
+/// a channel for an arm receiving from it, and a channel and a value for an arm sending the value to it.
+/// The operands are expected to have been pushed in the same order as the arms.
+/// If any of the arms is ready, pc is set to the address of the first such arm, after:
+///   - decrementing its semaphore, if it is greater than 0.
+///   - receiving from its channel, if the channel has a value or is closed, pushing the result as recv does.
+///   - sending to its channel, if the channel has room or is closed, pushing the result as send does.
+///
+/// If no arm is ready, the current thread is blocked on all of them.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///   - When one of the semaphores is posted, or a thread receives from or sends to one of the channels,
+///     or closes it, the thread continues from the address of the arm with the result pushed, as if it was ready.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to pop the operands off of.
+///
+/// * `arms` - What each arm waits for and the address to jump to.
+///
+/// # Errors
+///
+/// If the stack has fewer values than the operands of the arms.
+/// If any of the popped values is not a semaphore or channel where one is expected.
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn select(rt: &mut Runtime, arms: Vec<(SelectOp, Address)>) -> Result<()> {
+    let mut sources: Vec<WakeSource> = Vec::with_capacity(arms.len());
+    for (op, addr) in arms.into_iter().rev() {
+        // The operands of the last arm are popped first.
+        let source = match op {
+            SelectOp::Wait => WakeSource::new_with_address(pop(rt)?.try_into()?, addr),
+            SelectOp::Recv => WakeSource::ChannelRecv {
+                ch: pop(rt)?.try_into()?,
+                addr: Some(addr),
+            },
+            SelectOp::Send => {
+                let val = pop(rt)?;
+                WakeSource::ChannelSend {
+                    ch: pop(rt)?.try_into()?,
+                    val,
+                    addr: Some(addr),
+                }
+            }
+        };
+        sources.push(source);
+    }
+    sources.reverse();
+
+    if let Some(i) = sources.iter().position(is_ready) {
+        let addr = match sources.swap_remove(i) {
+            WakeSource::Semaphore { sem, addr } => {
+                *sem.lock().unwrap() -= 1;
+                addr
+            }
+            WakeSource::ChannelRecv { ch, addr } => {
+                chan_recv(rt, ch)?;
+                addr
+            }
+            WakeSource::ChannelSend { ch, val, addr } => {
+                chan_send(rt, ch, val)?;
+                addr
+            }
+            _ => None,
+        };
+
+        if let Some(addr) = addr {
+            rt.current_thread.pc = addr;
+        }
+        return Ok(());
+    }
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::Select),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, sources);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+fn pop(rt: &mut Runtime) -> Result<Value> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    Ok(val)
+}
+
+/// Check if the arm can be taken without blocking.
+/// A channel can't be empty with threads blocked sending to it, so one with room can always be sent to.
+fn is_ready(source: &WakeSource) -> bool {
+    match source {
+        WakeSource::Semaphore { sem, .. } => *sem.lock().unwrap() > 0,
+        WakeSource::ChannelRecv { ch, .. } => {
+            let state = ch.0.borrow();
+            !state.buf.is_empty() || state.closed
+        }
+        WakeSource::ChannelSend { ch, .. } => {
+            let state = ch.0.borrow();
+            state.buf.len() < state.cap || state.closed
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{Channel, Semaphore, Variant};
+
+    use crate::{
+        extend_environment,
+        micro_code::{self, ld},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    use SelectOp::*;
+
+    #[test]
+    fn test_select_ready() -> Result<()> {
+        let mut rt = Runtime::default();
+        let sem_a = Semaphore::new(0);
+        let sem_b = Semaphore::new(1);
+        let current_env = rt.current_thread.env.clone();
+        extend_environment(
+            &mut rt,
+            current_env,
+            vec!["a", "b"],
+            vec![sem_a.clone(), sem_b.clone()],
+        )?;
+        ld(&mut rt, "a".into())?;
+        ld(&mut rt, "b".into())?;
+        select(&mut rt, vec![(Wait, 10), (Wait, 20)])?;
+
+        // b is the only semaphore that can be acquired, so its arm is taken.
+        assert_eq!(*sem_a.lock().unwrap(), 0);
+        assert_eq!(*sem_b.lock().unwrap(), 0);
+        assert_eq!(rt.current_thread.pc, 20);
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_blocked() -> Result<()> {
+        let mut rt = Runtime::default();
+        let sem_a = Semaphore::new(0);
+        let sem_b = Semaphore::new(0);
+        let current_env = rt.current_thread.env.clone();
+        extend_environment(
+            &mut rt,
+            current_env,
+            vec!["a", "b"],
+            vec![sem_a.clone(), sem_b.clone()],
+        )?;
+        micro_code::spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, "a".into())?;
+        ld(&mut rt, "b".into())?;
+        select(&mut rt, vec![(Wait, 10), (Wait, 20)])?;
+
+        let child_thread_id = MAIN_THREAD_ID + 1;
+        assert_eq!(rt.current_thread.thread_id, child_thread_id);
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+
+        // Posting b wakes the main thread up at the address of b's arm.
+        ld(&mut rt, "b".into())?;
+        micro_code::post(&mut rt)?;
+
+        assert_eq!(*sem_b.lock().unwrap(), 0);
+        assert!(rt.blocked_queue.is_empty());
+        let woken = rt.ready_queue.pop_front().unwrap();
+        assert_eq!(woken.thread_id, MAIN_THREAD_ID);
+        assert_eq!(woken.pc, 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_chan_ready() -> Result<()> {
+        let mut rt = Runtime::default();
+        let empty = Channel::new(1);
+        let full = Channel::new(1);
+        full.0.borrow_mut().buf.push_back(1.into());
+
+        // Nothing can be received from the empty channel, so the arm sending to it is taken.
+        let push =
+            |rt: &mut Runtime, vals: Vec<Value>| rt.current_thread.operand_stack.extend(vals);
+        push(&mut rt, vec![empty.clone().into()]);
+        push(&mut rt, vec![empty.clone().into(), 2.into()]);
+        select(&mut rt, vec![(Recv, 10), (Send, 20)])?;
+        assert_eq!(rt.current_thread.pc, 20);
+        assert_eq!(rt.current_thread.operand_stack, vec![true.into()]);
+        assert_eq!(empty.0.borrow().buf.front(), Some(&2.into()));
+
+        // Nothing can be sent to the full channel, so the arm receiving from it is taken.
+        rt.current_thread.operand_stack.clear();
+        push(&mut rt, vec![full.clone().into(), 3.into()]);
+        push(&mut rt, vec![full.clone().into()]);
+        select(&mut rt, vec![(Send, 10), (Recv, 20)])?;
+        assert_eq!(rt.current_thread.pc, 20);
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Variant::Some(1.into()).into()]
+        );
+        assert!(full.0.borrow().buf.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_chan_blocked() -> Result<()> {
+        let mut rt = Runtime::default();
+        let empty = Channel::new(1);
+        let full = Channel::new(1);
+        full.0.borrow_mut().buf.push_back(1.into());
+        micro_code::spawn(&mut rt, 0, 0)?;
+        micro_code::spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear(); // drop the ids of the children
+
+        let ops = vec![empty.clone().into(), full.clone().into(), 2.into()];
+        rt.current_thread.operand_stack.extend(ops);
+        select(&mut rt, vec![(Recv, 10), (Send, 20)])?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+
+        // Sending to the empty channel hands the value to the main thread, at the address of the recv arm.
+        micro_code::chan_send(&mut rt, empty.clone(), 3.into())?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(true.into()));
+        assert!(rt.blocked_queue.is_empty());
+        let woken = rt.ready_queue.pop_back().unwrap();
+        assert_eq!(woken.pc, 10);
+        assert_eq!(woken.operand_stack, vec![Variant::Some(3.into()).into()]);
+
+        // Once blocked again, receiving from the full channel takes the value the main thread is sending.
+        rt.current_thread = woken;
+        rt.current_thread.operand_stack.clear();
+        let ops = vec![empty.clone().into(), full.clone().into(), 2.into()];
+        rt.current_thread.operand_stack.extend(ops);
+        select(&mut rt, vec![(Recv, 10), (Send, 20)])?;
+        micro_code::chan_recv(&mut rt, full.clone())?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Variant::Some(1.into()).into())
+        );
+        let woken = rt.ready_queue.pop_back().unwrap();
+        assert_eq!(woken.pc, 20);
+        assert_eq!(woken.operand_stack, vec![true.into()]);
+        assert_eq!(full.0.borrow().buf.front(), Some(&2.into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_chan_closed() -> Result<()> {
+        let mut rt = Runtime::default();
+        let ch = Channel::new(1);
+        micro_code::spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear(); // drop the id of the child
+
+        rt.current_thread.operand_stack.push(ch.clone().into());
+        select(&mut rt, vec![(Recv, 10)])?;
+
+        // Closing the channel wakes the main thread up at its recv arm with None.
+        micro_code::chan_close(&mut rt, ch.clone())?;
+        let woken = rt.ready_queue.pop_back().unwrap();
+        assert_eq!(woken.pc, 10);
+        assert_eq!(woken.operand_stack, vec![Variant::None.into()]);
+
+        // A closed channel is always ready, sending to it fails.
+        rt.current_thread.operand_stack.clear();
+        let ops = vec![ch.clone().into(), 1.into()];
+        rt.current_thread.operand_stack.extend(ops);
+        select(&mut rt, vec![(Send, 20)])?;
+        assert_eq!(rt.current_thread.pc, 20);
+        assert_eq!(rt.current_thread.operand_stack, vec![false.into()]);
+
+        Ok(())
+    }
+}
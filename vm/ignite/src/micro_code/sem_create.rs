@@ -13,11 +13,11 @@ use crate::Runtime;
 ///
 /// Infallible.
 #[inline]
-pub fn sem_create(mut rt: Runtime) -> Result<Runtime> {
+pub fn sem_create(rt: &mut Runtime) -> Result<()> {
     rt.current_thread
         .operand_stack
         .push(Semaphore::new(1).into());
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -29,7 +29,7 @@ mod tests {
     #[test]
     fn test_ldc() {
         let mut rt = Runtime::new(vec![]);
-        rt = sem_create(rt).unwrap();
+        sem_create(&mut rt).unwrap();
         assert_eq!(
             type_of(&rt.current_thread.operand_stack.pop().unwrap()),
             type_of(&Semaphore::new(1).into())
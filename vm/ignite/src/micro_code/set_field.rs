@@ -0,0 +1,96 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use bytecode::{type_of, Symbol, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a value and a struct, and push a copy of the struct with the given field set to the value.
+/// The struct is only copied if it is shared, e.g by a variable it was loaded from.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to set the field in.
+///
+/// * `field` - The field to set.
+///
+/// # Errors
+///
+/// If the operand stack does not contain two values, the value below the top is not a struct,
+/// or the struct has no such field.
+#[inline]
+pub fn set_field(rt: &mut Runtime, field: Symbol) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let instance = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let Value::Struct(mut instance) = instance else {
+        return Err(VmError::BadType {
+            expected: "Struct".to_string(),
+            found: type_of(&instance).to_string(),
+        }
+        .into());
+    };
+
+    let idx = instance
+        .ty
+        .field_idx(field)
+        .ok_or_else(|| VmError::NoSuchField {
+            ty: instance.ty.name.to_string(),
+            field: field.to_string(),
+        })?;
+
+    Rc::make_mut(&mut instance).fields[idx] = val;
+
+    rt.current_thread
+        .operand_stack
+        .push(Value::Struct(instance));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{Struct, StructType};
+
+    use super::*;
+
+    #[test]
+    fn test_set_field() {
+        let ty = Rc::new(StructType::new("P".into(), vec!["x".into(), "y".into()]));
+        let p = Value::from(Struct {
+            ty: ty.clone(),
+            fields: vec![1.into(), 2.into()],
+        });
+
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(p.clone());
+        rt.current_thread.operand_stack.push(3.into());
+        set_field(&mut rt, "x".into()).unwrap();
+
+        let updated = Value::from(Struct {
+            ty,
+            fields: vec![3.into(), 2.into()],
+        });
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(updated));
+
+        // the struct it was copied from is unchanged
+        let Value::Struct(p) = &p else {
+            panic!("Expected a struct");
+        };
+        assert_eq!(p.fields[0], Value::Int(1));
+
+        rt.current_thread
+            .operand_stack
+            .push(Value::Struct(p.clone()));
+        rt.current_thread.operand_stack.push(3.into());
+        assert!(set_field(&mut rt, "z".into()).is_err());
+    }
+}
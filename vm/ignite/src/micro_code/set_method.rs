@@ -0,0 +1,69 @@
+use anyhow::Result;
+use bytecode::{type_of, Symbol, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a closure and a struct type, and add the closure to the methods of the type with the given name.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to set the method in.
+///
+/// * `method` - The name of the method.
+///
+/// # Errors
+///
+/// If the operand stack does not contain two values, or the value below the closure is not a struct type.
+#[inline]
+pub fn set_method(rt: &mut Runtime, method: Symbol) -> Result<()> {
+    let closure = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let ty = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let Value::StructType(ty) = ty else {
+        return Err(VmError::BadType {
+            expected: "StructType".to_string(),
+            found: type_of(&ty).to_string(),
+        }
+        .into());
+    };
+
+    ty.methods.borrow_mut().insert(method, closure);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bytecode::StructType;
+
+    use super::*;
+
+    #[test]
+    fn test_set_method() {
+        let ty = Rc::new(StructType::new("P".into(), vec![]));
+
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread
+            .operand_stack
+            .push(Value::StructType(ty.clone()));
+        rt.current_thread.operand_stack.push(1.into());
+        set_method(&mut rt, "f".into()).unwrap();
+
+        assert!(rt.current_thread.operand_stack.is_empty());
+        assert_eq!(ty.methods.borrow().get(&"f".into()), Some(&Value::Int(1)));
+
+        rt.current_thread.operand_stack.push(1.into());
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(set_method(&mut rt, "f".into()).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use bytecode::{builtin, type_of, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop the end, the start and an array or string, and push the slice from the start up to but excluding the end.
+/// A bound that is unit is open, so a[..n] starts at 0 and a[n..] runs to the length of the array.
+///
+/// Slices of arrays share the buffer of the array. Strings are sliced by chars,
+/// and the string itself is pushed when the slice covers all of it.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to slice in.
+///
+/// # Errors
+///
+/// If the operand stack does not contain 3 values, they are not an array or string and ints or units,
+/// or the bounds are not within the array or string or the start is after the end.
+#[inline]
+pub fn slice(rt: &mut Runtime) -> Result<()> {
+    let end = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let start = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let len = builtin::len_impl(&val)?;
+    let start = bound(&start, 0)?;
+    let end = bound(&end, len as i64)?;
+    let out_of_bounds = || VmError::SliceOutOfBounds {
+        ty: type_of(&val).to_string(),
+        start,
+        end,
+        len,
+    };
+
+    // negative bounds are out of bounds
+    let (Ok(lo), Ok(hi)) = (usize::try_from(start), usize::try_from(end)) else {
+        return Err(out_of_bounds().into());
+    };
+
+    let slice = match &val {
+        Value::Array(elems) => elems.slice(lo, hi).ok_or_else(out_of_bounds)?.into(),
+        Value::String(s) if lo == 0 && hi == len => Value::String(s.clone()),
+        Value::String(s) => {
+            if lo > hi || hi > len {
+                return Err(out_of_bounds().into());
+            }
+
+            let slice: String = s.chars().skip(lo).take(hi - lo).collect();
+            Value::String(Rc::new(slice))
+        }
+        _ => unreachable!("Only arrays and strings have a length"),
+    };
+
+    rt.current_thread.operand_stack.push(slice);
+    Ok(())
+}
+
+/// The bound of a slice, or the default if it is open.
+fn bound(val: &Value, default: i64) -> Result<i64> {
+    match val {
+        Value::Unit => Ok(default),
+        Value::Int(i) => Ok(*i),
+        _ => Err(VmError::BadType {
+            expected: "Int or Unit".to_string(),
+            found: type_of(val).to_string(),
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_slice(val: Value, start: Value, end: Value) -> Result<Value> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(val);
+        rt.current_thread.operand_stack.push(start);
+        rt.current_thread.operand_stack.push(end);
+        slice(&mut rt)?;
+        Ok(rt.current_thread.operand_stack.pop().unwrap())
+    }
+
+    #[test]
+    fn test_slice_array() {
+        let arr = Value::from(vec![1.into(), 2.into(), 3.into(), 4.into()]);
+
+        let res = test_slice(arr.clone(), 1.into(), 3.into()).unwrap();
+        assert_eq!(res, Value::from(vec![2.into(), 3.into()]));
+        let res = test_slice(arr.clone(), Value::Unit, 1.into()).unwrap();
+        assert_eq!(res, Value::from(vec![1.into()]));
+        let res = test_slice(arr.clone(), 3.into(), Value::Unit).unwrap();
+        assert_eq!(res, Value::from(vec![4.into()]));
+        let res = test_slice(arr.clone(), 2.into(), 2.into()).unwrap();
+        assert_eq!(res, Value::from(vec![]));
+
+        // slices of slices index from the start of the slice
+        let res = test_slice(res, 0.into(), 0.into()).unwrap();
+        assert_eq!(res, Value::from(vec![]));
+        let mid = test_slice(arr.clone(), 1.into(), 4.into()).unwrap();
+        let res = test_slice(mid, 1.into(), Value::Unit).unwrap();
+        assert_eq!(res, Value::from(vec![3.into(), 4.into()]));
+
+        for (start, end) in [(0, 5), (-1, 2), (3, 1)] {
+            let err = test_slice(arr.clone(), start.into(), end.into()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Slice {}..{} out of bounds for Array of length 4",
+                    start, end
+                )
+            );
+        }
+
+        assert!(test_slice(arr, true.into(), Value::Unit).is_err());
+        assert!(test_slice(1.into(), Value::Unit, Value::Unit).is_err());
+    }
+
+    #[test]
+    fn test_slice_string() {
+        let s = Value::from("hello");
+
+        let res = test_slice(s.clone(), 1.into(), 3.into()).unwrap();
+        assert_eq!(res, Value::from("el"));
+        let res = test_slice(s.clone(), Value::Unit, Value::Unit).unwrap();
+        assert_eq!(res, s);
+
+        let err = test_slice(s, 2.into(), 6.into()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Slice 2..6 out of bounds for String of length 5"
+        );
+
+        // the bounds are in chars, not bytes
+        let s = Value::from("héllo wörld");
+        let res = test_slice(s.clone(), 1.into(), 2.into()).unwrap();
+        assert_eq!(res, Value::from("é"));
+        let res = test_slice(s.clone(), 6.into(), Value::Unit).unwrap();
+        assert_eq!(res, Value::from("wörld"));
+        assert!(test_slice(s, 0.into(), 12.into()).is_err());
+    }
+}
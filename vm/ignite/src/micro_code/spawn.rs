@@ -1,36 +1,66 @@
 use anyhow::Result;
 
-use crate::Runtime;
+use crate::{Runtime, SchedulerEventKind, ThreadState, VmError};
 
 /// Spawn a child thread that clones the current/parent thread at the time of the spawn.
 /// The child thread is given a unique thread ID.
-/// The child thread is added to the back of the ready queue.
+/// The child thread is added to the ready queue.
+/// The top n values of the operand stack of the parent thread are moved to that of the child thread,
+/// in the same order, so the child can call a function with arguments the parent evaluated.
 /// This thread ID is pushed onto the operand stack of the parent thread.
-/// 0 is pushed onto the operand stack of the child thread.
+/// 0 is pushed onto the operand stack of the child thread, on top of the values moved to it.
 /// The child thread starts execution at the given address.
+/// If the parent thread is in a scope block, the child thread is recorded in the innermost one.
 /// The parent thread continues execution.
 ///
 /// # Arguments
 ///
 /// * `rt` - The runtime to spawn a new thread in.
 ///
+/// * `addr` - The address of the instruction the child thread starts at.
+///
+/// * `n` - The number of values to move to the child thread.
+///
 /// # Errors
 ///
-/// Infallible.
+/// If the operand stack of the parent thread has fewer than n values.
 #[inline]
-pub fn spawn(mut rt: Runtime, addr: usize) -> Result<Runtime> {
+pub fn spawn(rt: &mut Runtime, addr: usize, n: usize) -> Result<()> {
+    let stack = &mut rt.current_thread.operand_stack;
+    let at = stack
+        .len()
+        .checked_sub(n)
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let vals = stack.split_off(at);
+
     rt.thread_count += 1;
 
     let child_thread_id = rt.thread_count;
     let mut child_thread = rt.current_thread.spawn_child(child_thread_id, addr);
 
-    // 0 is pushed onto the operand stack of the child thread.
+    // The values are moved, and 0 is pushed onto the operand stack of the child thread.
+    child_thread.operand_stack.extend(vals);
     child_thread.operand_stack.push(0.into());
     // The child thread ID is pushed onto the operand stack of the parent thread.
     rt.current_thread.operand_stack.push(child_thread_id.into());
 
-    rt.ready_queue.push_back(child_thread);
-    Ok(rt)
+    // The child is joined when the innermost scope block of the parent ends, if it is in one
+    if let Some(scope) = rt
+        .spawn_scopes
+        .get_mut(&rt.current_thread.thread_id)
+        .and_then(|scopes| scopes.last_mut())
+    {
+        scope.threads.push(child_thread_id);
+    }
+    rt.set_thread_state(child_thread_id, ThreadState::Ready);
+    rt.emit_event(
+        child_thread_id,
+        SchedulerEventKind::Spawned {
+            parent: rt.current_thread.thread_id,
+        },
+    );
+    rt.push_ready(child_thread);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -39,10 +69,26 @@ mod tests {
 
     #[test]
     fn test_spawn() -> Result<()> {
-        let rt = Runtime::new(vec![]);
-        let rt = spawn(rt, 0)?;
+        let mut rt = Runtime::new(vec![]);
+        spawn(&mut rt, 0, 0)?;
         assert_eq!(rt.thread_count, 2);
         assert_eq!(rt.ready_queue.len(), 1);
+        assert_eq!(rt.thread_state(2), Some(ThreadState::Ready));
+        Ok(())
+    }
+
+    #[test]
+    fn test_spawn_with_values() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack = vec![1.into(), 2.into(), 3.into()];
+        spawn(&mut rt, 0, 2)?;
+
+        // The child gets the top two values under the 0, and the parent keeps the rest under the child ID.
+        let child = rt.ready_queue.front().unwrap();
+        assert_eq!(child.operand_stack, vec![2.into(), 3.into(), 0.into()]);
+        assert_eq!(rt.current_thread.operand_stack, vec![1.into(), 2.into()]);
+
+        assert!(spawn(&mut rt, 0, 3).is_err());
         Ok(())
     }
 }
@@ -0,0 +1,51 @@
+use anyhow::Result;
+
+use crate::{Runtime, SpawnScope};
+
+/// Open a scope for the current thread. The threads it spawns until the matching JOINSCOPE are recorded
+/// in the scope, along with the height of its runtime stack so that the scope is closed if an error
+/// unwinds the thread past it.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to open the scope in.
+///
+/// # Errors
+///
+/// Infallible.
+#[inline]
+pub fn spawn_scope(rt: &mut Runtime) -> Result<()> {
+    let scope = SpawnScope {
+        depth: rt.current_thread.runtime_stack.len(),
+        threads: vec![],
+    };
+    rt.spawn_scopes
+        .entry(rt.current_thread.thread_id)
+        .or_default()
+        .push(scope);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_spawn_scope() -> Result<()> {
+        let mut rt = Runtime::default();
+        spawn(&mut rt, 0, 0)?;
+        spawn_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        spawn_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+
+        // Only the threads spawned after a scope is opened are recorded in it
+        let scopes = &rt.spawn_scopes[&MAIN_THREAD_ID];
+        assert_eq!(scopes.len(), 2);
+        assert_eq!(scopes[0].threads, vec![MAIN_THREAD_ID + 2]);
+        assert_eq!(scopes[1].threads, vec![MAIN_THREAD_ID + 3]);
+        Ok(())
+    }
+}
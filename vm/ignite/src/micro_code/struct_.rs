@@ -0,0 +1,46 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use bytecode::{StructType, Symbol, Value};
+
+use crate::Runtime;
+
+/// Load a new struct type onto the operand stack, without any methods.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to load the struct type onto.
+///
+/// * `name` - The name of the struct.
+///
+/// * `fields` - The fields of the struct, in the order they are declared.
+///
+/// # Errors
+///
+/// Infallible.
+#[inline]
+pub fn struct_(rt: &mut Runtime, name: Symbol, fields: Vec<Symbol>) -> Result<()> {
+    let ty = StructType::new(name, fields);
+    rt.current_thread
+        .operand_stack
+        .push(Value::StructType(Rc::new(ty)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_struct() {
+        let mut rt = Runtime::new(vec![]);
+        struct_(&mut rt, "P".into(), vec!["x".into()]).unwrap();
+
+        let Some(Value::StructType(ty)) = rt.current_thread.operand_stack.pop() else {
+            panic!("Expected a struct type");
+        };
+        assert_eq!(ty.name, "P".into());
+        assert_eq!(ty.fields, vec![Symbol::from("x")]);
+        assert!(ty.methods.borrow().is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+use bytecode::{type_of, FrameType, GeneratorStatus, Value, W};
+
+use crate::{Runtime, VmError};
+
+/// Pop the value yielded and suspend the running generator, saving the frames above its resume frame and the
+/// operands above the generator into it, so that it carries on from the next instruction when it is resumed.
+/// Then return to the loop that resumed it, with the value on the operand stack.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to suspend the generator in.
+///
+/// # Errors
+///
+/// If the operand stack is empty, or there is no generator running.
+#[inline]
+pub fn suspend(rt: &mut Runtime) -> Result<()> {
+    let thread = &mut rt.current_thread;
+    let val = thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let idx = thread
+        .runtime_stack
+        .iter()
+        .rposition(|frame| frame.frame_type == FrameType::ResumeFrame)
+        .ok_or(VmError::RuntimeStackUnderflow)?;
+
+    let mut frames = thread.runtime_stack.split_off(idx);
+    let resume_frame = frames.remove(0);
+
+    let base = resume_frame.operand_len.unwrap_or_default();
+    if base == 0 || base > thread.operand_stack.len() {
+        return Err(VmError::OperandStackUnderflow.into());
+    }
+    let operands = thread.operand_stack.split_off(base);
+
+    let gen = thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let Value::Generator(gen) = gen else {
+        return Err(VmError::BadType {
+            expected: "Generator".to_string(),
+            found: type_of(&gen).to_string(),
+        }
+        .into());
+    };
+
+    for frame in frames.iter_mut() {
+        frame.operand_len = frame.operand_len.map(|len| len - base);
+    }
+
+    let mut state = gen.0.borrow_mut();
+    state.status = GeneratorStatus::Suspended;
+    state.pc = thread.pc;
+    state.env = W(thread.env.clone());
+    state.operand_stack = operands;
+    state.runtime_stack = frames;
+
+    thread.env = resume_frame.env.0;
+    thread.pc = resume_frame.address.unwrap_or_default();
+    thread.operand_stack.push(val);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{weak_clone, Environment, Generator, StackFrame};
+
+    use crate::micro_code::resume;
+
+    use super::*;
+
+    #[test]
+    fn test_suspend() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        assert!(suspend(&mut rt).is_err());
+
+        let caller_env = rt.current_thread.env.clone();
+        rt.current_thread.pc = 7;
+
+        let env = Environment::new_wrapped();
+        let gen = Generator::new(42, W(weak_clone(&env)));
+        resume(&mut rt, gen.clone(), 99)?;
+
+        // the generator pushes an operand and enters a try block, then yields
+        rt.current_thread.operand_stack.push("a".into());
+        rt.current_thread
+            .runtime_stack
+            .push(StackFrame::new_try(Default::default(), 50, 2));
+        rt.current_thread.operand_stack.push(3.into());
+        rt.current_thread.pc = 45;
+        suspend(&mut rt)?;
+
+        assert_eq!(rt.current_thread.pc, 7);
+        assert!(rt.current_thread.env.ptr_eq(&caller_env));
+        assert_eq!(rt.current_thread.operand_stack, vec![3.into()]);
+        assert!(rt.current_thread.runtime_stack.is_empty());
+
+        let state = gen.0.borrow();
+        assert_eq!(state.status, GeneratorStatus::Suspended);
+        assert_eq!(state.pc, 45);
+        assert_eq!(state.operand_stack, vec!["a".into()]);
+        assert_eq!(state.runtime_stack[0].operand_len, Some(1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::Result;
+use bytecode::{type_of, Symbol, Value};
+
+use crate::{Runtime, VmError};
+
+/// Pop a value of an option, result or enum, and push whether it is the given variant.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to test the value in.
+///
+/// * `variant` - The name of the variant, e.g. Some or Circle.
+///
+/// # Errors
+///
+/// If the operand stack is empty, or the value is not an option, result or enum.
+#[inline]
+pub fn test_variant(rt: &mut Runtime, variant: Symbol) -> Result<()> {
+    let val = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    let is_variant = match &val {
+        Value::Variant(val) => val.name() == variant.as_str(),
+        Value::Enum(val) => val.variant == variant,
+        _ => {
+            return Err(VmError::BadType {
+                expected: "Option, Result or Enum".to_string(),
+                found: type_of(&val).to_string(),
+            }
+            .into())
+        }
+    };
+
+    rt.current_thread.operand_stack.push(is_variant.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{Enum, Variant};
+
+    use super::*;
+
+    #[test]
+    fn test_test_variant() {
+        let mut rt = Runtime::new(vec![]);
+        let circle = Value::from(Enum {
+            ty: "Shape".into(),
+            variant: "Circle".into(),
+            payload: Some(1.into()),
+        });
+
+        rt.current_thread.operand_stack.push(circle.clone());
+        test_variant(&mut rt, "Circle".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(true.into()));
+
+        rt.current_thread.operand_stack.push(circle);
+        test_variant(&mut rt, "Empty".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(false.into()));
+
+        rt.current_thread.operand_stack.push(Variant::None.into());
+        test_variant(&mut rt, "None".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(true.into()));
+
+        rt.current_thread
+            .operand_stack
+            .push(Variant::Ok(1.into()).into());
+        test_variant(&mut rt, "Err".into()).unwrap();
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(false.into()));
+
+        rt.current_thread.operand_stack.push(1.into());
+        assert!(test_variant(&mut rt, "Some".into()).is_err());
+    }
+}
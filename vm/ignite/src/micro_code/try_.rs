@@ -0,0 +1,154 @@
+use anyhow::{Error, Result};
+use bytecode::{FrameType, StackFrame, Value, W};
+
+use crate::{extend_environment, Runtime, VmError};
+
+use super::lock::unlock;
+
+/// Enter a try block. A try frame remembering the catch address and the height of the operand stack
+/// is pushed onto the runtime stack, and a new empty scope is created so that the block is exited with EXITSCOPE.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to enter the try block in.
+///
+/// * `addr` - The address of the catch block.
+///
+/// # Errors
+///
+/// If the runtime stack is already at the maximum call depth.
+#[inline]
+pub fn try_(rt: &mut Runtime, addr: usize) -> Result<()> {
+    rt.check_call_depth()?;
+
+    let current_env = rt.current_thread.env.clone();
+    let operand_len = rt.current_thread.operand_stack.len();
+    let frame = StackFrame::new_try(W(current_env.clone()), addr, operand_len);
+    rt.current_thread.runtime_stack.push(frame);
+
+    extend_environment::<&str, Value>(rt, current_env, vec![], vec![])
+}
+
+/// Unwind the current thread to the innermost try frame, restoring its environment and operand stack,
+/// and jump to its catch block with the message of the error on the operand stack.
+/// The generators whose resume frames are unwound are done, so they are not resumed after the error.
+/// The scope blocks unwound are closed, so the threads spawned in them are not waited for.
+/// The lock blocks unwound release the locks they hold.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime the error occurred in.
+///
+/// * `err` - The error raised by the instruction.
+///
+/// # Errors
+///
+/// The error itself if the thread is not in a try block.
+pub fn catch(rt: &mut Runtime, err: Error) -> Result<()> {
+    let thread = &mut rt.current_thread;
+    let Some(idx) = thread
+        .runtime_stack
+        .iter()
+        .rposition(|frame| frame.frame_type == FrameType::TryFrame)
+    else {
+        return Err(err);
+    };
+
+    for frame in thread.runtime_stack[idx..].iter() {
+        if frame.frame_type != FrameType::ResumeFrame {
+            continue;
+        }
+        let gen_idx = frame.operand_len.unwrap_or_default().wrapping_sub(1);
+        if let Some(Value::Generator(gen)) = thread.operand_stack.get(gen_idx) {
+            gen.finish();
+        }
+    }
+
+    // The scope blocks entered in the try block are left without joining their threads
+    if let Some(scopes) = rt.spawn_scopes.get_mut(&thread.thread_id) {
+        scopes.retain(|scope| scope.depth <= idx);
+    }
+
+    let unwound = thread.runtime_stack.split_off(idx + 1);
+    let frame = thread
+        .runtime_stack
+        .pop()
+        .ok_or(VmError::RuntimeStackUnderflow)?;
+    thread.env = frame.env.0;
+    thread
+        .operand_stack
+        .truncate(frame.operand_len.unwrap_or_default());
+    thread.operand_stack.push(Value::from(err.to_string()));
+    thread.pc = frame.address.unwrap_or_default();
+
+    // The locks held by the lock blocks entered in the try block are released
+    for frame in unwound.iter().rev() {
+        unlock(rt, frame)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{ByteCode, ByteCodeError};
+
+    use crate::micro_code::{call, exit_scope};
+
+    use super::*;
+
+    #[test]
+    fn test_try() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = rt.current_thread.env.clone();
+        rt.current_thread.operand_stack.push(Value::Int(1));
+
+        try_(&mut rt, 123)?;
+        assert_eq!(rt.current_thread.runtime_stack.len(), 1);
+        assert!(!rt.current_thread.env.ptr_eq(&env));
+
+        exit_scope(&mut rt)?;
+        assert!(rt.current_thread.runtime_stack.is_empty());
+        assert!(rt.current_thread.env.ptr_eq(&env));
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_catch() -> Result<()> {
+        let mut rt = Runtime::new(vec![ByteCode::DONE]);
+        let err = ByteCodeError::Panic("boom".to_string());
+        assert!(catch(&mut rt, err.into()).is_err());
+
+        let env = rt.current_thread.env.clone();
+        rt.current_thread.operand_stack.push(Value::Int(1));
+        try_(&mut rt, 123)?;
+        rt.current_thread.operand_stack.push(Value::Int(2));
+
+        // The error is raised in a function called from the try block
+        rt.current_thread
+            .operand_stack
+            .push(Value::from(bytecode::Closure {
+                fn_type: bytecode::FnType::User,
+                sym: "f".into(),
+                prms: vec![],
+                addr: 0,
+                env: Default::default(),
+            }));
+        call(&mut rt, 0)?;
+        assert_eq!(rt.current_thread.runtime_stack.len(), 2);
+
+        let err = ByteCodeError::Panic("boom".to_string());
+        catch(&mut rt, err.into())?;
+        assert!(rt.current_thread.runtime_stack.is_empty());
+        assert!(rt.current_thread.env.ptr_eq(&env));
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Value::Int(1), Value::from("boom")]
+        );
+        assert_eq!(rt.current_thread.pc, 123);
+
+        Ok(())
+    }
+}
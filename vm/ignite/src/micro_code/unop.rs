@@ -15,9 +15,10 @@ use bytecode::{type_of, UnOp, Value};
 /// # Errors
 ///
 /// If the stack is empty or the operation is not supported for
-/// the type of the value on the stack.
+/// the type of the value on the stack, or if negating an int overflows
+/// and the runtime is set to trap on overflow.
 #[inline]
-pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
+pub fn unop(rt: &mut Runtime, op: UnOp) -> Result<()> {
     let val = rt
         .current_thread
         .operand_stack
@@ -28,17 +29,17 @@ pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
         Value::Unit => Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into()),
         Value::Int(i) => {
             let result = match op {
-                UnOp::Neg => Value::Int(-i), // Negation
-                UnOp::Not => Value::Int(!i), // Bitwise Not
+                UnOp::Neg => Value::Int(rt.int_overflow.neg(i)?), // Negation
+                UnOp::Not => Value::Int(!i),                      // Bitwise Not
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         Value::Float(f) => {
             if let UnOp::Neg = op {
                 let result = Value::Float(-f); // Negation
                 rt.current_thread.operand_stack.push(result);
-                Ok(rt)
+                Ok(())
             } else {
                 Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
             }
@@ -47,7 +48,7 @@ pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
             if let UnOp::Not = op {
                 let result = Value::Bool(!b); // Logical Not
                 rt.current_thread.operand_stack.push(result);
-                Ok(rt)
+                Ok(())
             } else {
                 Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
             }
@@ -61,9 +62,41 @@ pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
         Value::Semaphore(_) => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
         }
+        Value::CondVar(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::Barrier(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::WaitGroup(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::Channel(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::BoundedQueue(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::RwLock(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::AtomicInt(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
         Value::Closure { .. } => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
         }
+        Value::Variant(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::StructType(_)
+        | Value::Struct(_)
+        | Value::Enum(_)
+        | Value::Array(_)
+        | Value::Iter(_)
+        | Value::Generator(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
     }
 }
 
@@ -76,47 +109,47 @@ mod tests {
     #[test]
     fn test_unop() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = unop(rt, UnOp::Neg).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        unop(&mut rt, UnOp::Neg).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(-42)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = unop(rt, UnOp::Neg).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        unop(&mut rt, UnOp::Neg).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(-42.0)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = unop(rt, UnOp::Not).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        unop(&mut rt, UnOp::Not).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = unop(rt, UnOp::Not).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        unop(&mut rt, UnOp::Not).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::Unit).unwrap();
-        let result = unop(rt, UnOp::Not);
+        ldc(&mut rt, Value::Unit).unwrap();
+        let result = unop(&mut rt, UnOp::Not);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::String("hello world".into())).unwrap();
-        let result = unop(rt, UnOp::Not);
+        ldc(&mut rt, Value::from("hello world")).unwrap();
+        let result = unop(&mut rt, UnOp::Not);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = unop(rt, UnOp::Not).unwrap();
-        rt = unop(rt, UnOp::Neg).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        unop(&mut rt, UnOp::Not).unwrap();
+        unop(&mut rt, UnOp::Neg).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(43)
@@ -1,7 +1,9 @@
 use anyhow::{Ok, Result};
 use bytecode::Semaphore;
 
-use crate::{Runtime, VmError};
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, VmError, WakeSource};
+
+use super::lock::acquires_lock;
 
 /// Pops a value off the stack.
 /// The value is expected to be a semaphore.
@@ -22,34 +24,59 @@ use crate::{Runtime, VmError};
 /// If the top value on stack is not a semaphore.
 /// If there are no threads in the ready queue when the current thread is blocked.
 #[inline]
-pub fn wait(mut rt: Runtime) -> Result<Runtime> {
+pub fn wait(rt: &mut Runtime) -> Result<()> {
     let sem: Semaphore = rt
         .current_thread
         .operand_stack
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?
         .try_into()?;
+
+    acquire(rt, sem)
+}
+
+/// Decrements the semaphore if it is greater than 0.
+/// Otherwise, the current thread is blocked until the semaphore is handed off to it.
+/// A mutex acquired by a lock block is recorded as held by the thread, to be released if it is killed.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime the semaphore is acquired in.
+///
+/// * `sem` - The semaphore to acquire.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub(crate) fn acquire(rt: &mut Runtime, sem: Semaphore) -> Result<()> {
     let mut sem_guard = sem.lock().unwrap();
 
     if *sem_guard > 0 {
         *sem_guard -= 1;
         drop(sem_guard); //unlock the semaphore
 
-        Ok(rt)
+        if acquires_lock(&rt.current_thread, &sem) {
+            rt.current_thread.held_semaphores.push(sem);
+        }
+        Ok(())
     } else {
         drop(sem_guard); //unlock the semaphore
 
         // Move the current thread to the blocked queue and pop the next ready thread.
-        let current_thread = rt.current_thread;
-        rt.blocked_queue.push_back((current_thread, sem.clone()));
+        rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+        rt.emit_event(
+            rt.current_thread.thread_id,
+            SchedulerEventKind::Blocked(BlockedOn::Semaphore),
+        );
+        let current_thread = std::mem::take(&mut rt.current_thread);
+        rt.push_blocked(current_thread, vec![WakeSource::new(sem.clone())]);
 
-        let next_ready_thread = rt
-            .ready_queue
-            .pop_front()
-            .ok_or(VmError::NoThreadsInReadyQueue)?;
+        let next_ready_thread = rt.pop_ready_thread()?;
 
         rt.current_thread = next_ready_thread;
-        Ok(rt)
+        rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+        Ok(())
     }
 }
 
@@ -68,10 +95,10 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(1);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = micro_code::spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = ld(rt, "sem".into())?;
-        rt = wait(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        micro_code::spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, "sem".into())?;
+        wait(&mut rt)?;
 
         assert_eq!(*sem.lock().unwrap(), 0);
         // Since the semaphore greater than 0, the semaphore should be decremented and the current thread should continue.
@@ -85,10 +112,10 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(0);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = micro_code::spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = ld(rt, "sem".into())?;
-        rt = wait(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        micro_code::spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, "sem".into())?;
+        wait(&mut rt)?;
 
         let child_thread_id = MAIN_THREAD_ID + 1;
         assert_eq!(*sem.lock().unwrap(), 0);
@@ -99,6 +126,7 @@ mod tests {
         );
         // The child thread should be the current thread.
         assert_eq!(rt.current_thread.thread_id, child_thread_id);
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
 
         Ok(())
     }
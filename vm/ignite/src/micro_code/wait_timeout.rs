@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use anyhow::{Ok, Result};
+use bytecode::Semaphore;
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, WakeSource};
+
+/// Wait on the semaphore for at most the given duration.
+/// If the semaphore is greater than 0, the semaphore is decremented and true is pushed onto the operand stack.
+/// The current thread continues execution.
+///
+/// If the semaphore is 0, the current thread sleeps with a deadline in the timer queue.
+///   - The current thread is moved to the blocked queue and set to sleeping until it is woken up.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///   - If the semaphore is posted before the deadline, the thread acquires it and true is pushed onto its operand stack.
+///   - Otherwise, the thread is woken up once the deadline passes and false is pushed onto its operand stack.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to wait in.
+///
+/// * `sem` - The semaphore to wait on.
+///
+/// * `timeout` - The maximum amount of time to wait for.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue and no blocked thread is waiting with a timeout
+/// when the current thread is blocked.
+#[inline]
+pub fn wait_timeout(rt: &mut Runtime, sem: Semaphore, timeout: Duration) -> Result<()> {
+    let mut sem_guard = sem.lock().unwrap();
+
+    if *sem_guard > 0 {
+        *sem_guard -= 1;
+        drop(sem_guard); // Unlock the semaphore.
+
+        rt.current_thread.operand_stack.push(true.into());
+        return Ok(());
+    }
+
+    drop(sem_guard); // Unlock the semaphore.
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    let deadline = rt.now() + timeout;
+    rt.add_timer(deadline, rt.current_thread.thread_id);
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Sleeping);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::Semaphore),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(
+        current_thread,
+        vec![WakeSource::new(sem), WakeSource::Timeout(deadline)],
+    );
+
+    rt.current_thread = rt.pop_ready_thread()?;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Value;
+
+    use crate::{
+        micro_code::{post, spawn},
+        MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_wait_timeout_acquired() -> Result<()> {
+        let mut rt = Runtime::default();
+        let sem = Semaphore::new(1);
+        wait_timeout(&mut rt, sem.clone(), Duration::from_secs(1))?;
+
+        assert_eq!(*sem.lock().unwrap(), 0);
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Bool(true))
+        );
+
+        // The semaphore is posted before the deadline.
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.operand_stack.clear();
+        wait_timeout(&mut rt, sem.clone(), Duration::from_secs(60))?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Sleeping));
+
+        rt.current_thread.operand_stack.push(sem.clone().into());
+        post(&mut rt)?;
+        let mut main_thread = rt.ready_queue.pop_front().unwrap();
+        assert_eq!(main_thread.thread_id, MAIN_THREAD_ID);
+        assert_eq!(main_thread.operand_stack.pop(), Some(Value::Bool(true)));
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Ready));
+
+        // The stale timer does not wake anything up.
+        rt.timer_queue.clear();
+        assert!(!rt.timer_expired());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_timeout_expired() -> Result<()> {
+        let mut rt = Runtime::default();
+        let sem = Semaphore::new(0);
+
+        // No other thread can run, so the runtime sleeps until the deadline passes.
+        wait_timeout(&mut rt, sem.clone(), Duration::from_millis(10))?;
+
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Running));
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Bool(false))
+        );
+        assert!(rt.blocked_queue.is_empty());
+        assert!(rt.timer_queue.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Ok, Result};
+use bytecode::WaitGroup;
+
+use crate::{Runtime, VmError};
+
+/// Add the delta to the count of the wait group. The delta may be negative.
+/// If the count reaches 0, all threads waiting on the wait group are moved to the ready queue.
+/// The current thread continues execution.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to update the wait group in.
+///
+/// * `wg` - The wait group to update.
+///
+/// * `delta` - The amount to add to the count.
+///
+/// # Errors
+///
+/// If the count would become negative.
+#[inline]
+pub fn wg_add(rt: &mut Runtime, wg: WaitGroup, delta: i64) -> Result<()> {
+    let mut state = wg.lock().unwrap();
+
+    let count = state.count as i64 + delta;
+    if count < 0 {
+        return Err(VmError::IllegalArgument("negative wait group count".to_string()).into());
+    }
+
+    state.count = count as u64;
+    drop(state); // Unlock the wait group.
+
+    if count == 0 {
+        rt.wake_blocked(|source| source.is_wait_group(&wg));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::wg_wait, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_wg_add() -> Result<()> {
+        let mut rt = Runtime::default();
+        let wg = WaitGroup::new();
+        wg_add(&mut rt, wg.clone(), 2)?;
+        assert_eq!(wg.lock().unwrap().count, 2);
+
+        // The count going below 0 is an error.
+        assert!(wg_add(&mut Runtime::default(), wg.clone(), -3).is_err());
+
+        crate::micro_code::spawn(&mut rt, 0, 0)?;
+        wg_wait(&mut rt, wg.clone())?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+
+        wg_add(&mut rt, wg.clone(), -1)?;
+        assert_eq!(rt.blocked_queue.len(), 1);
+
+        // The count reaching 0 wakes up the main thread.
+        wg_add(&mut rt, wg.clone(), -1)?;
+        assert!(rt.blocked_queue.is_empty());
+        assert_eq!(rt.ready_queue.front().unwrap().thread_id, MAIN_THREAD_ID);
+
+        Ok(())
+    }
+}
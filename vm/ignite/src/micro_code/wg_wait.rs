@@ -0,0 +1,67 @@
+use anyhow::{Ok, Result};
+use bytecode::WaitGroup;
+
+use crate::{BlockedOn, Runtime, SchedulerEventKind, ThreadState, WakeSource};
+
+/// Wait for the count of the wait group to reach 0.
+/// If the count is 0, the current thread continues execution.
+///
+/// Otherwise, the current thread is blocked.
+///   - The current thread is moved to the blocked queue.
+///   - The next ready thread is popped from the ready queue and set as the current thread.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to wait in.
+///
+/// * `wg` - The wait group to wait on.
+///
+/// # Errors
+///
+/// If there are no threads in the ready queue when the current thread is blocked.
+#[inline]
+pub fn wg_wait(rt: &mut Runtime, wg: WaitGroup) -> Result<()> {
+    if wg.lock().unwrap().count == 0 {
+        return Ok(());
+    }
+
+    // Move the current thread to the blocked queue and pop the next ready thread.
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Blocked);
+    rt.emit_event(
+        rt.current_thread.thread_id,
+        SchedulerEventKind::Blocked(BlockedOn::WaitGroup),
+    );
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_blocked(current_thread, vec![WakeSource::WaitGroup(wg)]);
+
+    let next_ready_thread = rt.pop_ready_thread()?;
+
+    rt.current_thread = next_ready_thread;
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_wg_wait() -> Result<()> {
+        let mut rt = Runtime::default();
+        let wg = WaitGroup::new();
+
+        // The count is 0, so the current thread continues.
+        wg_wait(&mut rt, wg.clone())?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        wg.lock().unwrap().count = 1;
+        spawn(&mut rt, 0, 0)?;
+        wg_wait(&mut rt, wg.clone())?;
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Blocked));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+
+        Ok(())
+    }
+}
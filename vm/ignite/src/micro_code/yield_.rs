@@ -1,12 +1,10 @@
-use std::time::Instant;
-
 use anyhow::Result;
 
-use crate::{Runtime, VmError};
+use crate::{Runtime, SchedulerEventKind, ThreadState, VmError};
 
 /// Yield the current thread in the runtime.
-/// Push the current thread to the back of the ready queue.
-/// Pop the next ready thread from the front of the ready queue and set it as the current thread.
+/// Push the current thread to the ready queue, where the scheduler puts it.
+/// Take the next ready thread from the ready queue, the one the scheduler picks, and set it as the current thread.
 ///
 /// # Arguments
 ///
@@ -16,18 +14,21 @@ use crate::{Runtime, VmError};
 ///
 /// Returns an error if there are no threads in the ready queue.
 #[inline]
-pub fn yield_(mut rt: Runtime) -> Result<Runtime> {
-    let current_thread = rt.current_thread;
-    rt.ready_queue.push_back(current_thread);
+pub fn yield_(rt: &mut Runtime) -> Result<()> {
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Ready);
+    rt.emit_event(rt.current_thread.thread_id, SchedulerEventKind::Preempted);
+    let current_thread = std::mem::take(&mut rt.current_thread);
+    rt.push_ready(current_thread);
 
     let next_ready_thread = rt
-        .ready_queue
-        .pop_front()
+        .take_next_ready()?
         .ok_or(VmError::NoThreadsInReadyQueue)?;
 
     rt.current_thread = next_ready_thread;
-    rt.time = Instant::now(); // Reset the time
-    Ok(rt)
+    rt.set_thread_state(rt.current_thread.thread_id, ThreadState::Running);
+    rt.time = rt.now(); // Reset the time
+    rt.quantum_start = rt.instr_count;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -39,10 +40,15 @@ mod tests {
     #[test]
     fn test_yield() -> Result<()> {
         let mut rt = Runtime::new(vec![]);
-        rt = spawn(rt, 1)?;
-        rt = yield_(rt)?;
+        spawn(&mut rt, 1, 0)?;
+        yield_(&mut rt)?;
 
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        assert_eq!(rt.thread_state(MAIN_THREAD_ID), Some(ThreadState::Ready));
+        assert_eq!(
+            rt.thread_state(MAIN_THREAD_ID + 1),
+            Some(ThreadState::Running)
+        );
 
         Ok(())
     }
@@ -43,18 +43,12 @@ pub fn ignite_repl(type_check: bool) -> Result<()> {
             // dbg!(&compiled);
 
             let mut rt = Runtime::new(compiled);
-            let run_res = run(rt);
 
-            match run_res {
-                Ok(_) => (),
-                Err(err) => {
-                    println!("[RuntimeError]: {}", err);
-                    continue;
-                }
+            if let Err(err) = run(&mut rt) {
+                println!("[RuntimeError]: {:?}", err);
+                continue;
             }
 
-            rt = run_res.unwrap();
-
             let top = rt.current_thread.operand_stack.last();
             dbg!(rt.current_thread.operand_stack.len());
 
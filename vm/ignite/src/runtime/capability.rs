@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use bytecode::builtin;
+use clap::ValueEnum;
+
+use crate::{Runtime, VmError};
+
+/// A group of builtins that reach outside the program, which the embedder can deny a runtime,
+/// e.g. to run untrusted scripts. Builtins outside every group, like print, are always allowed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Reading and writing files.
+    Fs,
+    /// Network access.
+    Net,
+    /// Waiting on the clock, e.g. with a timeout.
+    Time,
+    /// Random numbers.
+    Random,
+    /// Controlling the process running the program, e.g. exiting it.
+    Process,
+}
+
+impl Capability {
+    /// The capability the builtin needs, if any.
+    pub fn of(builtin: &str) -> Option<Capability> {
+        match builtin {
+            builtin::READ_FILE_SYM
+            | builtin::WRITE_FILE_SYM
+            | builtin::APPEND_FILE_SYM
+            | builtin::FILE_EXISTS_SYM
+            | builtin::READ_LINES_SYM => Some(Capability::Fs),
+            builtin::WAIT_TIMEOUT_SYM => Some(Capability::Time),
+            builtin::RANDOM_INT_SYM => Some(Capability::Random),
+            builtin::EXIT_SYM => Some(Capability::Process),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Capability::Fs => "fs",
+            Capability::Net => "net",
+            Capability::Time => "time",
+            Capability::Random => "random",
+            Capability::Process => "process",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Sandboxing of the builtins a program can call.
+impl Runtime {
+    /// Deny the program the builtins that need the capability. Every capability is allowed by default.
+    pub fn deny(&mut self, capability: Capability) {
+        self.denied.insert(capability);
+    }
+
+    /// Allow the program the builtins that need the capability again.
+    pub fn allow(&mut self, capability: Capability) {
+        self.denied.remove(&capability);
+    }
+
+    pub fn is_allowed(&self, capability: Capability) -> bool {
+        !self.denied.contains(&capability)
+    }
+
+    /// Check that the program may call the builtin.
+    ///
+    /// # Errors
+    ///
+    /// If the builtin needs a capability the runtime has been denied.
+    #[inline]
+    pub fn check_capability(&self, builtin: &str) -> Result<(), VmError> {
+        match Capability::of(builtin) {
+            Some(capability) if !self.is_allowed(capability) => Err(VmError::CapabilityDenied {
+                builtin: builtin.to_string(),
+                capability,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_capability() {
+        let mut rt = Runtime::default();
+        assert!(rt.check_capability(builtin::EXIT_SYM).is_ok());
+
+        rt.deny(Capability::Process);
+        let err = rt.check_capability(builtin::EXIT_SYM).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "exit needs the process capability, which the runtime is denied"
+        );
+        assert!(rt.check_capability(builtin::PRINTLN_SYM).is_ok());
+        assert!(rt.check_capability(builtin::RANDOM_INT_SYM).is_ok());
+
+        rt.allow(Capability::Process);
+        assert!(rt.check_capability(builtin::EXIT_SYM).is_ok());
+
+        rt.deny(Capability::Fs);
+        let err = rt.check_capability(builtin::READ_FILE_SYM).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "read_file needs the fs capability, which the runtime is denied"
+        );
+        assert!(rt.check_capability(builtin::FILE_EXISTS_SYM).is_err());
+    }
+}
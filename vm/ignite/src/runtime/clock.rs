@@ -0,0 +1,111 @@
+use std::{cell::Cell, time::Duration};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// The source of time of the runtime, used for time quanta, garbage collection intervals and timeouts.
+/// Times are the duration since the clock started, so that a clock need not be tied to the system clock.
+pub trait Clock {
+    /// The time since the clock started.
+    fn now(&self) -> Duration;
+
+    /// Wait for the duration to pass, when no thread can run until a timeout expires.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The system clock, started when it is created.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The default amount a virtual clock advances each time it is read.
+pub const DEFAULT_VIRTUAL_TICK: Duration = Duration::from_micros(1);
+
+/// A clock that only advances when the runtime reads it, by a fixed tick, or sleeps, by the time slept.
+/// The runtime reads the clock a fixed number of times per instruction, so time is a measure of the work done:
+/// runs on a virtual clock are reproducible, and do not depend on a system clock, which some targets do not have.
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    now: Cell<Duration>,
+    tick: Duration,
+}
+
+impl VirtualClock {
+    pub fn new(tick: Duration) -> Self {
+        VirtualClock {
+            now: Cell::new(Duration::ZERO),
+            tick,
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new(DEFAULT_VIRTUAL_TICK)
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        let now = self.now.get();
+        self.now.set(now + self.tick);
+        now
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+/// The clock of a new runtime: the system clock, except on WebAssembly, where there is none without the host.
+pub fn default_clock() -> Box<dyn Clock> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return Box::new(SystemClock::new());
+
+    #[cfg(target_arch = "wasm32")]
+    return Box::new(VirtualClock::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock() {
+        let clock = VirtualClock::new(Duration::from_millis(1));
+        assert_eq!(clock.now(), Duration::ZERO);
+        assert_eq!(clock.now(), Duration::from_millis(1));
+
+        clock.sleep(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_millis(1002));
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::HashSet, io::BufReader, path::PathBuf, time::Duration};
+
+use crate::{
+    Capability, IntOverflow, PanicPolicy, Runtime, SwitchLog, VmError, WakeupPolicy,
+    DEFAULT_ENV_POOL_CAPACITY, DEFAULT_GC_INTERVAL, DEFAULT_MAX_CALL_DEPTH,
+    DEFAULT_MAX_OPERAND_STACK, DEFAULT_TIME_QUANTUM,
+};
+
+/// The limits and behavior of a runtime, given when it is created, see [`crate::Runtime::with_config`].
+/// The default is the configuration of [`crate::Runtime::new`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeConfig {
+    /// The maximum amount of time a thread can run before it is preempted.
+    pub time_quantum: Duration,
+    /// The interval at which to run the mark and sweep garbage collector.
+    pub gc_interval: Duration,
+    /// The maximum number of frames kept in the environment pool.
+    pub env_pool_capacity: usize,
+    /// The maximum number of instructions the program may execute across all threads, if any.
+    pub instr_budget: Option<u64>,
+    /// The maximum number of instructions a single thread may execute, if any.
+    pub thread_instr_budget: Option<u64>,
+    /// The maximum number of frames on the runtime stack of a thread.
+    pub max_call_depth: usize,
+    /// The maximum number of values on the operand stack of a thread.
+    pub max_operand_stack: usize,
+    /// The maximum memory the environments of the program may hold, in bytes, if any,
+    /// checked whenever the garbage collector runs, see [`crate::Runtime::memory_usage`].
+    pub max_memory: Option<usize>,
+    /// What integer arithmetic does on overflow.
+    pub int_overflow: IntOverflow,
+    /// What happens when a thread does not catch a runtime error.
+    pub panic_policy: PanicPolicy,
+    /// Which thread blocked on a semaphore is woken up when it is posted.
+    pub wakeup_policy: WakeupPolicy,
+    /// The capabilities the program is denied the builtins of.
+    pub denied: HashSet<Capability>,
+    /// The seed to run the program deterministically with, if any, see [`crate::Runtime::set_deterministic`].
+    pub deterministic: Option<u64>,
+    /// If the program runs in debug mode.
+    pub debug: bool,
+    /// The number of calls after which functions are compiled to native code, if any, see [`crate::Runtime::set_jit`].
+    #[cfg(feature = "jit")]
+    pub jit: Option<u64>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            time_quantum: DEFAULT_TIME_QUANTUM,
+            gc_interval: DEFAULT_GC_INTERVAL,
+            env_pool_capacity: DEFAULT_ENV_POOL_CAPACITY,
+            instr_budget: None,
+            thread_instr_budget: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_operand_stack: DEFAULT_MAX_OPERAND_STACK,
+            max_memory: None,
+            int_overflow: IntOverflow::default(),
+            panic_policy: PanicPolicy::default(),
+            wakeup_policy: WakeupPolicy::default(),
+            denied: HashSet::new(),
+            deterministic: None,
+            debug: false,
+            #[cfg(feature = "jit")]
+            jit: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    pub fn time_quantum(mut self, time_quantum: Duration) -> RuntimeConfig {
+        self.time_quantum = time_quantum;
+        self
+    }
+
+    pub fn gc_interval(mut self, gc_interval: Duration) -> RuntimeConfig {
+        self.gc_interval = gc_interval;
+        self
+    }
+
+    pub fn env_pool_capacity(mut self, env_pool_capacity: usize) -> RuntimeConfig {
+        self.env_pool_capacity = env_pool_capacity;
+        self
+    }
+
+    /// Limit the number of instructions the program may execute across all threads.
+    pub fn instr_budget(mut self, budget: u64) -> RuntimeConfig {
+        self.instr_budget = Some(budget);
+        self
+    }
+
+    /// Limit the number of instructions each thread may execute.
+    pub fn thread_instr_budget(mut self, budget: u64) -> RuntimeConfig {
+        self.thread_instr_budget = Some(budget);
+        self
+    }
+
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> RuntimeConfig {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    pub fn max_operand_stack(mut self, max_operand_stack: usize) -> RuntimeConfig {
+        self.max_operand_stack = max_operand_stack;
+        self
+    }
+
+    /// Limit the memory the environments of the program may hold, in bytes.
+    pub fn max_memory(mut self, max_memory: usize) -> RuntimeConfig {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /// What integer arithmetic does when its result does not fit in an i64, trapping by default.
+    pub fn int_overflow(mut self, int_overflow: IntOverflow) -> RuntimeConfig {
+        self.int_overflow = int_overflow;
+        self
+    }
+
+    /// What happens when a thread does not catch a runtime error, stopping the program by default.
+    pub fn panic_policy(mut self, panic_policy: PanicPolicy) -> RuntimeConfig {
+        self.panic_policy = panic_policy;
+        self
+    }
+
+    /// Which thread blocked on a semaphore is woken up when it is posted, the first to block by default.
+    pub fn wakeup_policy(mut self, wakeup_policy: WakeupPolicy) -> RuntimeConfig {
+        self.wakeup_policy = wakeup_policy;
+        self
+    }
+
+    /// Deny the program the builtins that need the capability.
+    pub fn deny(mut self, capability: Capability) -> RuntimeConfig {
+        self.denied.insert(capability);
+        self
+    }
+
+    /// Run the program deterministically, with the scheduling decisions and random numbers given by the seed.
+    pub fn deterministic(mut self, seed: u64) -> RuntimeConfig {
+        self.deterministic = Some(seed);
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> RuntimeConfig {
+        self.debug = debug;
+        self
+    }
+
+    /// Compile functions called more than `threshold` times to native code.
+    #[cfg(feature = "jit")]
+    pub fn jit(mut self, threshold: u64) -> RuntimeConfig {
+        self.jit = Some(threshold);
+        self
+    }
+}
+
+/// The command line flags of the runtime configuration, shared by the CLIs that run programs.
+/// Flags that are not given keep their default.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct RuntimeArgs {
+    /// Set custom time quantum for the VM in milliseconds.
+    /// Default is 100ms.
+    #[arg(short, long)]
+    pub quantum: Option<u64>,
+
+    /// Set custom garbage collection interval for the VM in milliseconds.
+    /// Default is 1000ms.
+    #[arg(short, long)]
+    pub gc_interval: Option<u64>,
+
+    /// Fail once the program has executed this many instructions across all threads.
+    #[arg(long)]
+    pub instr_budget: Option<u64>,
+
+    /// Fail once a single thread has executed this many instructions.
+    #[arg(long)]
+    pub thread_instr_budget: Option<u64>,
+
+    /// Fail when the calls and scopes of a thread are nested deeper than this.
+    #[arg(long)]
+    pub max_call_depth: Option<usize>,
+
+    /// Fail when the operand stack of a thread holds more values than this.
+    #[arg(long)]
+    pub max_operand_stack: Option<usize>,
+
+    /// Fail when the variables of the program hold more than this many bytes, as estimated by the garbage collector.
+    #[arg(long, value_name = "BYTES")]
+    pub max_memory: Option<usize>,
+
+    /// What integer arithmetic does when its result does not fit in 64 bits.
+    #[arg(long, value_enum, default_value_t = IntOverflow::Trap)]
+    pub overflow: IntOverflow,
+
+    /// What happens when a thread does not catch a runtime error: abort stops the program,
+    /// kill-thread kills the thread and runs the others, unless it is the main thread.
+    #[arg(long, value_enum, default_value_t = PanicPolicy::Abort)]
+    pub on_panic: PanicPolicy,
+
+    /// Which thread blocked on a semaphore is woken up when it is posted.
+    #[arg(long, value_enum, default_value_t = WakeupPolicy::Fifo)]
+    pub wakeup: WakeupPolicy,
+
+    /// Deny the program the builtins that need the capability. Can be given more than once.
+    #[arg(long, value_enum)]
+    pub deny: Vec<Capability>,
+
+    /// Run the program deterministically: threads are scheduled by a random number generator seeded with SEED,
+    /// preempted after a fixed number of instructions, and the clock only advances when the program sleeps.
+    #[arg(long, value_name = "SEED")]
+    pub deterministic: Option<u64>,
+
+    /// Record every context switch of the run to the file, to reproduce its interleaving with --replay.
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Force the context switches recorded in the file by --record, running the threads in the same interleaving.
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Compile functions to native code once they are called THRESHOLD times, 1000 if not given as --jit=THRESHOLD.
+    #[cfg(feature = "jit")]
+    #[arg(
+        long,
+        value_name = "THRESHOLD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1000"
+    )]
+    pub jit: Option<u64>,
+}
+
+impl RuntimeArgs {
+    /// The configuration given by the flags.
+    pub fn config(&self) -> RuntimeConfig {
+        let mut config = RuntimeConfig::default()
+            .int_overflow(self.overflow)
+            .panic_policy(self.on_panic)
+            .wakeup_policy(self.wakeup);
+        for capability in self.deny.iter() {
+            config = config.deny(*capability);
+        }
+
+        if let Some(quantum) = self.quantum {
+            config = config.time_quantum(Duration::from_millis(quantum));
+        }
+        if let Some(gc_interval) = self.gc_interval {
+            config = config.gc_interval(Duration::from_millis(gc_interval));
+        }
+        if let Some(budget) = self.instr_budget {
+            config = config.instr_budget(budget);
+        }
+        if let Some(budget) = self.thread_instr_budget {
+            config = config.thread_instr_budget(budget);
+        }
+        if let Some(depth) = self.max_call_depth {
+            config = config.max_call_depth(depth);
+        }
+        if let Some(size) = self.max_operand_stack {
+            config = config.max_operand_stack(size);
+        }
+        if let Some(bytes) = self.max_memory {
+            config = config.max_memory(bytes);
+        }
+        if let Some(seed) = self.deterministic {
+            config = config.deterministic(seed);
+        }
+        #[cfg(feature = "jit")]
+        if let Some(threshold) = self.jit {
+            config = config.jit(threshold);
+        }
+
+        config
+    }
+
+    /// Start recording or replaying the context switches of the runtime, as the flags ask.
+    ///
+    /// # Errors
+    ///
+    /// If the log to replay can't be read.
+    pub fn start_switch_log(&self, rt: &mut Runtime) -> Result<(), VmError> {
+        if let Some(path) = &self.replay {
+            let file = std::fs::File::open(path)?;
+            rt.replay_switches(SwitchLog::read(BufReader::new(file))?);
+        }
+        if self.record.is_some() {
+            rt.record_switches();
+        }
+        Ok(())
+    }
+
+    /// Write the context switches recorded so far to the file given by --record, if any,
+    /// which is done whether the run succeeded or not, as failed runs are the ones to reproduce.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be written.
+    pub fn save_switch_log(&self, rt: &Runtime) -> Result<(), VmError> {
+        if let (Some(path), Some(log)) = (&self.record, rt.switch_log()) {
+            let mut file = std::fs::File::create(path)?;
+            log.write(&mut file)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    use super::*;
+
+    #[test]
+    fn test_with_config() {
+        let config = RuntimeConfig::default()
+            .time_quantum(Duration::from_millis(5))
+            .instr_budget(10)
+            .max_call_depth(3)
+            .int_overflow(IntOverflow::Wrap);
+        let rt = Runtime::with_config(vec![], config.clone());
+
+        assert_eq!(rt.time_quantum, Duration::from_millis(5));
+        assert_eq!(rt.instr_budget, Some(10));
+        assert_eq!(rt.thread_instr_budget, None);
+        assert_eq!(rt.max_call_depth, 3);
+        assert_eq!(rt.max_operand_stack, DEFAULT_MAX_OPERAND_STACK);
+        assert_eq!(rt.int_overflow, IntOverflow::Wrap);
+        assert_eq!(rt.config(), config);
+
+        assert_eq!(Runtime::default().config(), RuntimeConfig::default());
+
+        let config = RuntimeConfig::default().deterministic(3);
+        let rt = Runtime::with_config(vec![], config.clone());
+        assert_eq!(rt.instr_quantum, Some(crate::DEFAULT_INSTR_QUANTUM));
+        assert_eq!(rt.config(), config);
+    }
+
+    #[test]
+    fn test_runtime_args() {
+        let args = RuntimeArgs {
+            quantum: Some(5),
+            max_operand_stack: Some(7),
+            deny: vec![Capability::Random],
+            max_memory: Some(1 << 20),
+            wakeup: WakeupPolicy::Lifo,
+            ..Default::default()
+        };
+        let config = args.config();
+
+        assert_eq!(config.time_quantum, Duration::from_millis(5));
+        assert_eq!(config.max_operand_stack, 7);
+        assert_eq!(config.gc_interval, DEFAULT_GC_INTERVAL);
+        assert_eq!(config.int_overflow, IntOverflow::Trap);
+        assert_eq!(config.denied, HashSet::from([Capability::Random]));
+        assert_eq!(config.max_memory, Some(1 << 20));
+        assert_eq!(config.panic_policy, PanicPolicy::Abort);
+        assert_eq!(config.wakeup_policy, WakeupPolicy::Lifo);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::Runtime;
+
+/// Counting how many times each instruction runs, to tell which code a program covered.
+impl Runtime {
+    /// Count the executions of each instruction from now on, see [`Runtime::coverage`].
+    pub fn record_coverage(&mut self) {
+        self.coverage = Some(vec![0; self.instrs.len()]);
+    }
+
+    /// The number of times each instruction has run so far, by address, if coverage is recorded.
+    pub fn coverage(&self) -> Option<&[u64]> {
+        self.coverage.as_deref()
+    }
+
+    /// Count an execution of the instruction at `pc`, if coverage is recorded.
+    #[inline]
+    pub(crate) fn cover(&mut self, pc: usize) {
+        if let Some(counts) = self.coverage.as_mut() {
+            // Code may have been appended since recording started, e.g. by a hot reload
+            if pc >= counts.len() {
+                counts.resize(self.instrs.len(), 0);
+            }
+            counts[pc] += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytecode::ByteCode;
+
+    use crate::run;
+
+    use super::*;
+
+    #[test]
+    fn test_coverage() -> Result<()> {
+        let instrs = vec![
+            ByteCode::ldc(true),
+            ByteCode::JOF(4),
+            ByteCode::ldc(1),
+            ByteCode::GOTO(5),
+            ByteCode::ldc(2),
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs.clone());
+        run(&mut rt)?;
+        assert_eq!(rt.coverage(), None);
+
+        let mut rt = Runtime::new(instrs);
+        rt.record_coverage();
+        run(&mut rt)?;
+        assert_eq!(rt.coverage(), Some([1, 1, 1, 1, 0, 1].as_slice()));
+
+        Ok(())
+    }
+}
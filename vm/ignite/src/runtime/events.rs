@@ -0,0 +1,187 @@
+use std::{fmt::Display, time::Duration};
+
+use bytecode::ThreadID;
+
+use crate::Runtime;
+
+/// What a blocked thread is waiting on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockedOn {
+    /// A semaphore, with or without a timeout.
+    Semaphore,
+    CondVar,
+    Barrier,
+    WaitGroup,
+    /// Sending to a full channel or receiving from an empty one.
+    Channel,
+    /// Pushing to a full bounded queue or popping from an empty one.
+    BoundedQueue,
+    /// An rwlock held by a writer, or by readers when waiting to write.
+    RwLock,
+    /// Any of the semaphores of a select.
+    Select,
+    /// The future of a call to an asynchronous function of the host.
+    Host,
+}
+
+/// A change in how a thread is scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulerEventKind {
+    /// The thread was spawned by the parent thread and added to the ready queue.
+    Spawned { parent: ThreadID },
+    /// The thread was moved from running to the back of the ready queue, because its time quantum expired,
+    /// it yielded or it is waiting to join a thread.
+    Preempted,
+    /// The thread was moved to the blocked queue.
+    Blocked(BlockedOn),
+    /// The thread was moved from the blocked queue to the ready queue.
+    Woken,
+    /// The thread finished, either by executing DONE or by being killed.
+    Finished,
+}
+
+/// A scheduling event of a thread, stamped with when it occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulerEvent {
+    pub thread_id: ThreadID,
+    pub kind: SchedulerEventKind,
+    /// The time since the runtime was created.
+    pub elapsed: Duration,
+    /// The number of instructions executed across all threads.
+    pub instr_count: u64,
+}
+
+impl Display for SchedulerEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            SchedulerEventKind::Spawned { parent } => format!("spawned by thread {}", parent),
+            SchedulerEventKind::Preempted => "preempted".to_string(),
+            SchedulerEventKind::Blocked(on) => format!("blocked on {:?}", on),
+            SchedulerEventKind::Woken => "woken".to_string(),
+            SchedulerEventKind::Finished => "finished".to_string(),
+        };
+
+        write!(
+            f,
+            "time={:?} instrs={} thread={} {}",
+            self.elapsed, self.instr_count, self.thread_id, kind
+        )
+    }
+}
+
+/// A subscriber to the scheduling events of a runtime, e.g. to visualize how threads interleave
+/// or to assert on it in tests.
+pub trait SchedulerSubscriber {
+    fn on_event(&mut self, event: &SchedulerEvent);
+}
+
+impl<F: FnMut(&SchedulerEvent)> SchedulerSubscriber for F {
+    fn on_event(&mut self, event: &SchedulerEvent) {
+        self(event)
+    }
+}
+
+impl Runtime {
+    /// Notify the subscribers of a scheduling event of the thread.
+    #[inline]
+    pub fn emit_event(&mut self, thread_id: ThreadID, kind: SchedulerEventKind) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let event = SchedulerEvent {
+            thread_id,
+            kind,
+            elapsed: self.now(),
+            instr_count: self.instr_count,
+        };
+
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.on_event(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use anyhow::Result;
+    use bytecode::{ByteCode, Semaphore, Value};
+
+    use crate::{run, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_scheduler_events() -> Result<()> {
+        // let s = sem_create(); wait s;
+        // let h = spawn f(); where f waits on s and posts it
+        // yield; post s; join h
+        let sem = Semaphore::new(1);
+        let instrs = vec![
+            ByteCode::enterscope(vec!["s"]),
+            ByteCode::ldc(Value::Semaphore(sem)),
+            ByteCode::assign("s"),
+            ByteCode::ld("s"),
+            ByteCode::WAIT,
+            ByteCode::SPAWN(12, 0),
+            ByteCode::YIELD,
+            ByteCode::ld("s"),
+            ByteCode::POST,
+            ByteCode::JOIN,
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+            ByteCode::POP,
+            ByteCode::ld("s"),
+            ByteCode::WAIT,
+            ByteCode::ld("s"),
+            ByteCode::POST,
+            ByteCode::ldc(Value::Unit),
+            ByteCode::DONE,
+        ];
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut rt = Runtime::new(instrs);
+        rt.set_time_quantum(Duration::from_secs(u64::MAX));
+
+        let log = events.clone();
+        rt.subscribe(move |event: &SchedulerEvent| log.borrow_mut().push(*event));
+        run(&mut rt)?;
+
+        let kinds: Vec<_> = events
+            .borrow()
+            .iter()
+            .map(|event| (event.thread_id, event.kind))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    2,
+                    SchedulerEventKind::Spawned {
+                        parent: MAIN_THREAD_ID
+                    }
+                ),
+                (MAIN_THREAD_ID, SchedulerEventKind::Preempted),
+                (2, SchedulerEventKind::Blocked(BlockedOn::Semaphore)),
+                (2, SchedulerEventKind::Woken),
+                // The main thread joins before the child has run again
+                (MAIN_THREAD_ID, SchedulerEventKind::Preempted),
+                (2, SchedulerEventKind::Finished),
+                (MAIN_THREAD_ID, SchedulerEventKind::Finished),
+            ]
+        );
+
+        // Events are stamped in the order they occur
+        let events = events.borrow();
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].instr_count <= w[1].instr_count && w[0].elapsed <= w[1].elapsed));
+        // SPAWN is the sixth instruction executed, and it is counted before it executes
+        assert_eq!(events[0].instr_count, 6);
+
+        Ok(())
+    }
+}
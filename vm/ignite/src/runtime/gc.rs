@@ -1,8 +1,9 @@
-use std::{cell::RefCell, collections::HashMap, rc::Weak};
+use std::{cell::RefCell, collections::HashMap, mem::size_of, rc::Weak};
 
-use bytecode::{weak_clone, EnvWeak, Environment, StackFrame, Value, W};
+use anyhow::Result;
+use bytecode::{weak_clone, EnvStrong, EnvWeak, Environment, IterState, StackFrame, Value, W};
 
-use crate::{Runtime, Thread};
+use crate::{Runtime, Thread, VmError, WakeSource};
 
 /// Runtime methods at runtime.
 impl Runtime {
@@ -13,17 +14,72 @@ impl Runtime {
     /// - Sweep environment x -> env_registry.remove(x) if env_registry.get(x) = false
     /// - Clean up -> reset env_registry.get(x) = false
     ///
-    /// Traverse through all the threads, for each thread:
+    /// Environments form cycles through the closures stored in them, e.g. a recursive function is stored
+    /// in the environment it captures. Reference counting alone cannot reclaim such cycles, so environments
+    /// are only kept alive by the registry and reclaimed here once they are unreachable from every thread.
+    /// How often this runs is configured with `Runtime::set_gc_interval`.
+    ///
+    /// Traverse through all the threads, including zombie threads waiting to be joined, for each thread:
     ///   - Mark its current environment and the environment of closure values in the current environment,
     ///     and the chain of parent environments.
     ///   - Go through the runtime stack and mark all the environments and environment of closure values in
     ///     their respective environment, and the chain of parent environments
     ///   - Go through the operand stack and mark all the environments of closure values, and the chain of parent environments
     #[inline]
-    pub fn mark_and_weep(self) -> Self {
-        let marked = mark(&self);
+    pub fn mark_and_weep(&mut self) {
+        let marked = mark(self);
         sweep(self, marked)
     }
+
+    /// An estimate of the memory the environments of the program hold, in bytes: the environments themselves
+    /// and the values bound in them, following strings, arrays, structs and variants.
+    /// Values shared between several bindings are counted once per binding, and the operand stacks are not counted.
+    pub fn memory_usage(&self) -> usize {
+        self.env_registry
+            .iter()
+            .map(|env| {
+                let env = env.0.borrow();
+                size_of::<Environment>()
+                    + env.syms.len() * size_of::<bytecode::Symbol>()
+                    + env.slots.iter().map(value_size).sum::<usize>()
+                    + env
+                        .env
+                        .values()
+                        .map(|val| size_of::<bytecode::Symbol>() + value_size(val))
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Check the memory the program holds is within its limit, if it has one.
+    /// Only done after the garbage collector runs, so the limit can be exceeded in between.
+    ///
+    /// # Errors
+    ///
+    /// If the program holds more memory than its limit.
+    pub fn check_memory(&self) -> Result<()> {
+        let Some(limit) = self.max_memory else {
+            return Ok(());
+        };
+
+        let used = self.memory_usage();
+        if used > limit {
+            return Err(VmError::OutOfMemory { used, limit }.into());
+        }
+        Ok(())
+    }
+}
+
+/// The size of a value, including what it owns behind a pointer.
+fn value_size(val: &Value) -> usize {
+    size_of::<Value>()
+        + match val {
+            Value::String(s) => s.capacity(),
+            Value::Array(arr) => arr.iter().map(value_size).sum(),
+            Value::Struct(s) => s.fields.iter().map(value_size).sum(),
+            Value::Variant(v) => v.payload().map_or(0, value_size),
+            _ => 0,
+        }
 }
 
 fn mark(rt: &Runtime) -> HashMap<EnvWeak, bool> {
@@ -41,27 +97,42 @@ fn mark(rt: &Runtime) -> HashMap<EnvWeak, bool> {
         marked = mark_thread(marked, thread);
     }
 
-    // Mark the blocked queue
-    for (thread, _) in rt.blocked_queue.iter() {
+    // Mark the blocked queue, and the values blocked threads are sending, which may be closures
+    for (thread, sources) in rt.blocked_queue.iter() {
         marked = mark_thread(marked, thread);
+        for source in sources {
+            if let WakeSource::ChannelSend { val, .. } = source {
+                marked = mark_value(marked, val);
+            }
+        }
     }
 
-    // Zombie threads will be ignored
+    // Mark the zombie threads, their result may be a closure
+    for thread in rt.zombie_threads.values() {
+        marked = mark_thread(marked, thread);
+    }
 
     marked
 }
 
-fn sweep(mut rt: Runtime, m: HashMap<EnvWeak, bool>) -> Runtime {
+// EnvStrong hashes by pointer, so interior mutability of the environment does not affect the key.
+#[allow(clippy::mutable_key_type)]
+fn sweep(rt: &mut Runtime, m: HashMap<EnvWeak, bool>) {
     if rt.debug {
         println!("Sweep begin")
     }
 
-    let registry = rt
+    // Any environment that is not marked will be removed from the registry and dropped
+    let (registry, garbage): (Vec<EnvStrong>, Vec<EnvStrong>) = rt
         .env_registry
         .drain()
-        .filter(|env| *m.get(&W(weak_clone(env))).unwrap_or(&false))
-        .collect();
-    rt.env_registry = registry;
+        .partition(|env| *m.get(&W(weak_clone(env))).unwrap_or(&false));
+    rt.env_registry = registry.into_iter().collect();
+
+    // Recycle the frames of the removed environments
+    for env in garbage {
+        rt.free_env(env.0);
+    }
 
     if rt.debug {
         println!(
@@ -69,8 +140,6 @@ fn sweep(mut rt: Runtime, m: HashMap<EnvWeak, bool>) -> Runtime {
             m.len() - rt.env_registry.len()
         )
     }
-
-    rt // Any environment that is not marked will be removed from the registry and dropped
 }
 
 fn env_hashmap(rt: &Runtime) -> HashMap<EnvWeak, bool> {
@@ -85,6 +154,9 @@ fn mark_thread(mut m: HashMap<EnvWeak, bool>, t: &Thread) -> HashMap<EnvWeak, bo
     m = mark_env(m, &t.env);
     m = mark_operand_stack(m, &t.operand_stack);
     m = mark_runtime_stack(m, &t.runtime_stack);
+    for val in t.locals.values() {
+        m = mark_value(m, val);
+    }
     m
 }
 
@@ -92,9 +164,10 @@ fn mark_env(
     mut m: HashMap<EnvWeak, bool>,
     env: &Weak<RefCell<Environment>>,
 ) -> HashMap<EnvWeak, bool> {
-    let is_marked = m
-        .get_mut(&W(env.clone()))
-        .expect("Environment must be in the registry");
+    // Builtin closures have no environment
+    let Some(is_marked) = m.get_mut(&W(env.clone())) else {
+        return m;
+    };
 
     match is_marked {
         true => return m, // Already marked
@@ -109,14 +182,86 @@ fn mark_env(
         m = mark_env(m, parent);
     }
 
+    // Closures stored in the environment keep the environment they captured alive
+    for val in env.borrow().env.values().chain(env.borrow().slots.iter()) {
+        m = mark_value(m, val);
+    }
+
+    m
+}
+
+/// Mark the environment of the closure the value is or holds, if any.
+/// Struct types hold the closures of their methods, structs hold their type and field values,
+/// enum variants hold their payload, arrays hold their elements, and generators hold their saved state.
+fn mark_value(mut m: HashMap<EnvWeak, bool>, val: &Value) -> HashMap<EnvWeak, bool> {
+    match val {
+        Value::Closure(closure) => m = mark_env(m, &closure.env),
+        Value::Variant(variant) => {
+            if let Some(val) = variant.payload() {
+                m = mark_value(m, val);
+            }
+        }
+        Value::StructType(ty) => {
+            for method in ty.methods.borrow().values() {
+                m = mark_value(m, method);
+            }
+        }
+        Value::Struct(instance) => {
+            for method in instance.ty.methods.borrow().values() {
+                m = mark_value(m, method);
+            }
+            for field in instance.fields.iter() {
+                m = mark_value(m, field);
+            }
+        }
+        Value::Enum(variant) => {
+            if let Some(val) = &variant.payload {
+                m = mark_value(m, val);
+            }
+        }
+        Value::Array(elems) => {
+            for elem in elems.iter() {
+                m = mark_value(m, elem);
+            }
+        }
+        Value::Iter(iter) => {
+            if let IterState::Array { elems, .. } = &*iter.0.borrow() {
+                for elem in elems.iter() {
+                    m = mark_value(m, elem);
+                }
+            }
+        }
+        // A channel may be sent into itself, so a channel that is already being marked is skipped
+        Value::Channel(ch) => {
+            if let Ok(state) = ch.0.try_borrow_mut() {
+                for val in state.buf.iter() {
+                    m = mark_value(m, val);
+                }
+            }
+        }
+        // Likewise for a bounded queue pushed into itself
+        Value::BoundedQueue(q) => {
+            if let Ok(state) = q.0.try_borrow_mut() {
+                for val in state.buf.iter() {
+                    m = mark_value(m, val);
+                }
+            }
+        }
+        // A suspended generator keeps the environments of its body and frames alive until it is resumed
+        Value::Generator(gen) => {
+            let state = gen.0.borrow();
+            m = mark_env(m, &state.env);
+            m = mark_operand_stack(m, &state.operand_stack);
+            m = mark_runtime_stack(m, &state.runtime_stack);
+        }
+        _ => (),
+    }
     m
 }
 
 fn mark_operand_stack(mut m: HashMap<EnvWeak, bool>, os: &[Value]) -> HashMap<EnvWeak, bool> {
     for val in os.iter() {
-        if let Value::Closure { env, .. } = val {
-            m = mark_env(m, env);
-        }
+        m = mark_value(m, val);
     }
     m
 }
@@ -130,11 +275,10 @@ fn mark_runtime_stack(mut m: HashMap<EnvWeak, bool>, rs: &[StackFrame]) -> HashM
 
 #[cfg(test)]
 mod tests {
-    use crate::run;
+    use crate::{extend_environment, run, MAIN_THREAD_ID};
 
     use super::*;
 
-    use anyhow::Result;
     use bytecode::*;
 
     #[test]
@@ -148,7 +292,7 @@ mod tests {
         let instrs = vec![
             ByteCode::enterscope(empty_vec.clone()), // Program scope
             ByteCode::enterscope(vec!["garbage"]),   // Block scope
-            ByteCode::ldf(0, empty_vec.clone()),
+            ByteCode::ldf(0, "garbage", empty_vec.clone()),
             ByteCode::assign("garbage"),
             ByteCode::EXITSCOPE,
             ByteCode::EXITSCOPE,
@@ -157,15 +301,110 @@ mod tests {
 
         let mut rt = Runtime::new(instrs);
         rt.set_debug_mode();
-        let rt = run(rt)?;
+        run(&mut rt)?;
         assert_eq!(rt.env_registry.len(), 3); // Global env, program env, block env
 
-        let rt = rt.mark_and_weep();
+        rt.mark_and_weep();
         assert_eq!(rt.env_registry.len(), 1); // Only the global environment should be left
 
         Ok(())
     }
 
+    #[test]
+    fn test_gc_closure_in_env() -> Result<()> {
+        // The environment captured by a closure is only reachable through the closure,
+        // which is stored in the current environment.
+        let mut rt = Runtime::default();
+        let global_env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, global_env.clone(), vec!["x"], vec![1])?;
+        let captured_env = rt.current_thread.env.clone();
+
+        let closure = Value::from(Closure {
+            fn_type: FnType::User,
+            sym: "f".into(),
+            prms: vec![],
+            addr: 0,
+            env: W(captured_env),
+        });
+        extend_environment(&mut rt, global_env, vec!["f"], vec![closure])?;
+
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 3); // Global env, captured env, current env
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_zombie_thread() -> Result<()> {
+        // The result of a zombie thread is a closure, which must survive until the thread is joined.
+        let mut rt = Runtime::default();
+        let global_env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, global_env.clone(), vec!["x"], vec![1])?;
+        let captured_env = rt.current_thread.env.clone();
+        rt.current_thread.env = global_env;
+
+        let mut zombie = rt.current_thread.spawn_child(MAIN_THREAD_ID + 1, 0);
+        zombie.operand_stack.push(Value::from(Closure {
+            fn_type: FnType::User,
+            sym: "f".into(),
+            prms: vec![],
+            addr: 0,
+            env: W(captured_env),
+        }));
+        rt.zombie_threads.insert(zombie.thread_id, zombie);
+
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 2); // Global env, captured env
+
+        rt.zombie_threads.clear();
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 1); // Only the global environment should be left
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_builtin_closure() -> Result<()> {
+        // Builtin closures on the operand stack have no environment to mark.
+        let mut rt = Runtime::default();
+        rt.current_thread.operand_stack.push(builtin::println());
+
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_env_pool() -> Result<()> {
+        let mut rt = Runtime::default();
+        let global_env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, global_env.clone(), vec!["a", "b"], vec![1, 2])?;
+        rt.current_thread.env = global_env.clone();
+
+        // The unreachable environment is swept and its frame is pooled
+        rt.mark_and_weep();
+        assert_eq!(rt.env_pool.len(), 1);
+        assert!(rt.env_pool[0].slots.is_empty());
+        assert!(rt.env_pool[0].slots.capacity() >= 2);
+
+        // The pooled frame is reused by the next environment
+        extend_environment(&mut rt, global_env.clone(), vec!["c"], vec![3])?;
+        assert!(rt.env_pool.is_empty());
+        let env = rt.current_thread.env.upgrade().unwrap();
+        assert_eq!(env.borrow().get("c")?, Value::Int(3));
+        assert!(env.borrow().get("a").is_err());
+
+        // Frames are dropped once the pool is full
+        rt.set_env_pool_capacity(0);
+        rt.current_thread.env = global_env;
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 1);
+        assert!(rt.env_pool.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_gc_02() -> Result<()> {
         // fn higher_order(x) {
@@ -182,13 +421,13 @@ mod tests {
             // PC: 0
             ByteCode::enterscope(vec!["higher_order", "add10", "result"]), // Program scope
             // PC: 1
-            ByteCode::ldf(4, vec!["x"]), // higher_order
+            ByteCode::ldf(4, "higher_order", vec!["x"]), // higher_order
             // PC: 2
             ByteCode::assign("higher_order"),
             // PC: 3
             ByteCode::GOTO(11), // Jump past higher_order body
             // PC: 4
-            ByteCode::ldf(6, vec!["y"]), // higher_order annonymous function
+            ByteCode::ldf(6, "lambda", vec!["y"]), // higher_order annonymous function
             // PC: 5
             ByteCode::GOTO(10), // Jump past annonymous function body
             // PC: 6
@@ -231,11 +470,40 @@ mod tests {
 
         let mut rt = Runtime::new(instrs);
         rt.set_debug_mode();
-        let rt = run(rt)?;
+        run(&mut rt)?;
 
-        let rt = rt.mark_and_weep();
+        rt.mark_and_weep();
         assert_eq!(rt.env_registry.len(), 1); // Only the global environment should be left
 
         Ok(())
     }
+
+    #[test]
+    fn test_max_memory() -> Result<()> {
+        // let s = "aaa...";
+        // s
+        let instrs = vec![
+            ByteCode::enterscope(vec!["s"]),
+            ByteCode::ldc("a".repeat(4096)),
+            ByteCode::assign("s"),
+            ByteCode::ld("s"),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
+        let config = crate::RuntimeConfig::default().gc_interval(std::time::Duration::ZERO);
+        let mut rt = Runtime::with_config(instrs.clone(), config.clone());
+        run(&mut rt)?;
+
+        // The builtins bound in the global environment count too
+        let limit = Runtime::default().memory_usage() + 1024;
+        let mut rt = Runtime::with_config(instrs, config.max_memory(limit));
+        let err = run(&mut rt).expect_err("The string should not fit in the memory limit");
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::OutOfMemory { used, .. }) if *used > limit + 3072
+        ));
+
+        Ok(())
+    }
 }
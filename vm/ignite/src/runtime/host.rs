@@ -0,0 +1,437 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use anyhow::Result;
+use bytecode::{ByteCode, Closure, Environment, FnType, Symbol, ThreadID, Value, W};
+
+use crate::{run, BlockedOn, Runtime, SchedulerEventKind, ThreadState, VmError, WakeSource};
+
+/// A function of the host, called with the arguments of the call once the program calls it.
+pub type HostFn = Rc<dyn Fn(Vec<Value>) -> Result<Value>>;
+
+/// The result of a call to an asynchronous function of the host, once it is ready.
+pub type HostFuture = Pin<Box<dyn Future<Output = Result<Value>>>>;
+
+/// An asynchronous function of the host, e.g. for IO, whose calls park the calling thread until the future is ready.
+pub type AsyncHostFn = Rc<dyn Fn(Vec<Value>) -> HostFuture>;
+
+/// How long the runtime sleeps between polls of the futures of the host, when every thread is waiting on them
+/// and none of them has woken the runtime up.
+pub const HOST_IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// The waker of the futures of the host. Futures may be woken up from other threads of the process,
+/// so it only raises a flag, which the runtime checks between instructions.
+#[derive(Debug, Default)]
+pub struct HostWaker {
+    woken: AtomicBool,
+}
+
+impl HostWaker {
+    /// Check if a future was woken up since the last check, lowering the flag.
+    pub fn take_woken(&self) -> bool {
+        self.woken.swap(false, Ordering::AcqRel)
+    }
+}
+
+impl Wake for HostWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+    }
+}
+
+/// Functions provided by the program embedding the runtime.
+impl Runtime {
+    /// Bind a function of the host in the global environment, so the program can call it like a builtin.
+    /// The function always produces a value, which is unit if it has nothing to return.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already bound in the global environment, e.g. to a builtin.
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        arity: usize,
+        f: impl Fn(Vec<Value>) -> Result<Value> + 'static,
+    ) -> Result<()> {
+        self.bind_host_fn(name, arity)?;
+        self.host_fns.insert(name.to_string(), Rc::new(f));
+
+        Ok(())
+    }
+
+    /// Bind an asynchronous function of the host in the global environment, like [`Runtime::register_fn`].
+    /// A thread that calls it is parked until the future it returns is ready, while the other threads keep running.
+    /// If the future fails, so does the run.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already bound in the global environment, e.g. to a builtin.
+    pub fn register_async_fn<F>(
+        &mut self,
+        name: &str,
+        arity: usize,
+        f: impl Fn(Vec<Value>) -> F + 'static,
+    ) -> Result<()>
+    where
+        F: Future<Output = Result<Value>> + 'static,
+    {
+        self.bind_host_fn(name, arity)?;
+        self.async_host_fns
+            .insert(name.to_string(), Rc::new(move |args| Box::pin(f(args))));
+
+        Ok(())
+    }
+
+    /// Get the asynchronous function of the host bound to the name, if there is one.
+    pub fn async_host_fn(&self, name: &str) -> Option<AsyncHostFn> {
+        self.async_host_fns.get(name).cloned()
+    }
+
+    /// Bind a builtin closure for the function of the host in the global environment.
+    fn bind_host_fn(&mut self, name: &str, arity: usize) -> Result<()> {
+        let global_env = self.global_env()?;
+        let sym = Symbol::try_intern(name)?;
+        if global_env.borrow().env.contains_key(&sym) {
+            return Err(VmError::NameAlreadyBound(name.to_string()).into());
+        }
+
+        let closure = Closure {
+            fn_type: FnType::Builtin,
+            sym,
+            prms: (0..arity)
+                .map(|i| Symbol::try_intern(&format!("arg{}", i)))
+                .collect::<Result<_, _>>()?,
+            addr: 0,
+            env: W(Weak::new()),
+        };
+
+        global_env.borrow_mut().set(sym, closure);
+
+        Ok(())
+    }
+
+    /// Wait for the result of a call to an asynchronous function of the host in the current thread.
+    /// If the future is ready when first polled, its value is pushed onto the operand stack.
+    /// Otherwise the current thread is moved to the blocked queue until the future is ready,
+    /// and the next ready thread is popped from the ready queue and set as the current thread.
+    ///
+    /// # Errors
+    ///
+    /// If the future fails, or there are no other threads to run while the current thread waits.
+    pub fn await_host(&mut self, mut fut: HostFuture) -> Result<()> {
+        let waker = Waker::from(Arc::clone(&self.host_waker));
+
+        if let Poll::Ready(res) = fut.as_mut().poll(&mut Context::from_waker(&waker)) {
+            self.current_thread.operand_stack.push(res?);
+            return Ok(());
+        }
+
+        let tid = self.current_thread.thread_id;
+        self.host_futures.push((tid, fut));
+        self.set_thread_state(tid, ThreadState::Blocked);
+        self.emit_event(tid, SchedulerEventKind::Blocked(BlockedOn::Host));
+        let current_thread = std::mem::take(&mut self.current_thread);
+        self.push_blocked(current_thread, vec![WakeSource::Host]);
+
+        self.current_thread = self.pop_ready_thread()?;
+        self.set_thread_state(self.current_thread.thread_id, ThreadState::Running);
+        Ok(())
+    }
+
+    /// Check if any thread is waiting on a future of the host.
+    #[inline]
+    pub fn awaiting_host(&self) -> bool {
+        !self.host_futures.is_empty()
+    }
+
+    /// Poll the futures of the host if one of them was woken up since they were last polled,
+    /// moving the threads whose futures are ready to the ready queue with the value pushed onto their operand stack.
+    ///
+    /// # Errors
+    ///
+    /// If a future failed.
+    pub fn poll_host_futures(&mut self) -> Result<()> {
+        if !self.host_waker.take_woken() {
+            return Ok(());
+        }
+
+        let waker = Waker::from(Arc::clone(&self.host_waker));
+        let mut cx = Context::from_waker(&waker);
+        let mut futures = std::mem::take(&mut self.host_futures).into_iter();
+
+        while let Some((tid, mut fut)) = futures.next() {
+            let val = match fut.as_mut().poll(&mut cx) {
+                Poll::Pending => {
+                    self.host_futures.push((tid, fut));
+                    continue;
+                }
+                Poll::Ready(Ok(val)) => val,
+                Poll::Ready(Err(err)) => {
+                    self.host_futures.extend(futures);
+                    return Err(err);
+                }
+            };
+
+            self.wake_host_waiter(tid, val);
+        }
+
+        Ok(())
+    }
+
+    /// Move the thread waiting on the host to the ready queue, with the value of the call on its operand stack.
+    fn wake_host_waiter(&mut self, tid: ThreadID, val: Value) {
+        let Some(i) = self
+            .blocked_queue
+            .iter()
+            .position(|(thread, _)| thread.thread_id == tid)
+        else {
+            return;
+        };
+
+        let (mut thread, _) = self
+            .blocked_queue
+            .remove(i)
+            .expect("Index should be in bounds since it was just found");
+
+        thread.operand_stack.push(val);
+        self.set_thread_state(tid, ThreadState::Ready);
+        self.emit_event(tid, SchedulerEventKind::Woken);
+        self.push_ready(thread);
+    }
+
+    /// Get the function of the host bound to the name, if there is one.
+    pub fn host_fn(&self, name: &str) -> Option<HostFn> {
+        self.host_fns.get(name).cloned()
+    }
+
+    /// The global environment, the root of the environment of the current thread.
+    fn global_env(&self) -> Result<Rc<RefCell<Environment>>> {
+        let mut env = self
+            .current_thread
+            .env
+            .upgrade()
+            .ok_or(VmError::EnvironmentDroppedError)?;
+
+        loop {
+            let parent = env.borrow().parent.as_ref().and_then(Weak::upgrade);
+            match parent {
+                Some(parent) => env = parent,
+                None => return Ok(env),
+            }
+        }
+    }
+}
+
+/// Functions of the program called by the host.
+impl Runtime {
+    /// Call the function bound to the name once the program is done, running until it returns, and return its value,
+    /// which is unit if it has nothing to return. The name is looked up in the environment the program finished in,
+    /// so only the functions of programs whose top-level scope stays open, e.g. those compiled with
+    /// `Compiler::compile_incremental`, can be called along with the builtins and host functions.
+    /// The result of the previous run is discarded from the operand stack.
+    ///
+    /// # Errors
+    ///
+    /// If the program is not done, the name is not bound to a function taking as many arguments,
+    /// or the call fails, in which case the runtime is recovered so that it can be called again.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value> {
+        if !self.done {
+            return Err(VmError::ProgramNotDone.into());
+        }
+
+        let env = self.current_thread.env.clone();
+        let f = env
+            .upgrade()
+            .ok_or(VmError::EnvironmentDroppedError)?
+            .borrow()
+            .get(name)?;
+
+        // Run the call from the end of the program, then remove the instructions again
+        let start = self.instrs.len();
+        self.instrs
+            .extend([ByteCode::CALL(args.len()), ByteCode::DONE]);
+        self.resume_at(start);
+        self.current_thread.operand_stack.push(f);
+        self.current_thread.operand_stack.extend(args);
+
+        let res = run(self);
+        self.instrs.truncate(start);
+
+        if let Err(err) = res {
+            self.recover(env)?;
+            return Err(err);
+        }
+
+        Ok(self
+            .current_thread
+            .operand_stack
+            .pop()
+            .unwrap_or(Value::Unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use compiler::compiler::{compile_from_string, Compiler};
+
+    use super::*;
+
+    /// A value sent from another thread of the process after a delay, as the result of IO would be.
+    fn delayed(val: i64, delay: Duration) -> impl Future<Output = Result<Value>> {
+        let shared: Arc<Mutex<(Option<i64>, Option<Waker>)>> = Arc::default();
+        let sender = Arc::clone(&shared);
+
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            let mut state = sender.lock().unwrap();
+            state.0 = Some(val);
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        });
+
+        std::future::poll_fn(move |cx| {
+            let mut state = shared.lock().unwrap();
+            match state.0 {
+                Some(val) => Poll::Ready(Ok(Value::Int(val))),
+                None => {
+                    state.1 = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_register_fn() -> Result<()> {
+        let instrs = vec![
+            ByteCode::ld("add"),
+            ByteCode::ldc(1),
+            ByteCode::ldc(2),
+            ByteCode::CALL(2),
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs);
+        rt.register_fn("add", 2, |args| {
+            let (a, b): (i64, i64) = (args[0].clone().try_into()?, args[1].clone().try_into()?);
+            Ok(Value::Int(a + b))
+        })?;
+
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(3)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_call() -> Result<()> {
+        let program = parser::Parser::new_from_string(
+            "let base = 10; fn add(n: int) -> int { base + n } fn fail() { assert(false); }",
+        )
+        .parse()?;
+        let mut instrs = vec![];
+        Compiler::new(program.clone()).compile_incremental(&program, &mut instrs)?;
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_stdout(std::io::sink());
+        assert!(rt.call("add", vec![Value::Int(1)]).is_err());
+
+        run(&mut rt)?;
+        assert_eq!(rt.call("add", vec![Value::Int(1)])?, Value::Int(11));
+        assert_eq!(rt.call("add", vec![Value::Int(2)])?, Value::Int(12));
+        assert_eq!(rt.call("println", vec![Value::Int(2)])?, Value::Unit);
+
+        assert!(rt.call("add", vec![]).is_err());
+        assert!(rt.call("base", vec![]).is_err());
+        assert!(rt.call("fail", vec![]).is_err());
+        assert!(rt.call("missing", vec![]).is_err());
+
+        // The runtime recovers from failed calls
+        assert_eq!(rt.call("add", vec![Value::Int(3)])?, Value::Int(13));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_async_fn_ready() -> Result<()> {
+        let mut rt = Runtime::new(compile_from_string("fetch(4)", false)?);
+        rt.register_async_fn("fetch", 1, |args| async move {
+            let n: i64 = args[0].clone().try_into()?;
+            Ok(Value::Int(n * 10))
+        })?;
+
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(40)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_async_fn_parks_thread() -> Result<()> {
+        let src = r#"
+        let n = 0;
+        fn count() {
+            loop n < 1000 {
+                n = n + 1;
+            }
+        }
+        let t = spawn count();
+        let v = fetch(7);
+        join t;
+        v + n
+        "#;
+
+        let mut rt = Runtime::new(compile_from_string(src, false)?);
+        rt.register_async_fn("fetch", 1, |args| {
+            delayed(
+                args[0].clone().try_into().unwrap(),
+                Duration::from_millis(10),
+            )
+        })?;
+
+        // The spawned thread runs to completion while the main thread waits on the host
+        run(&mut rt)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Int(1007))
+        );
+        assert!(!rt.awaiting_host());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_async_fn_error() -> Result<()> {
+        let mut rt = Runtime::new(compile_from_string("fetch()", false)?);
+        rt.register_async_fn("fetch", 0, |_| async { Err(anyhow::anyhow!("Not found")) })?;
+
+        let err = run(&mut rt).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Not found");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_fn_rejects_bound_name() {
+        let mut rt = Runtime::default();
+        assert!(rt.register_fn("print", 1, |_| Ok(Value::Unit)).is_err());
+        assert!(rt.host_fn("print").is_none());
+    }
+}
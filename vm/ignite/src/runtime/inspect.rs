@@ -0,0 +1,324 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    fmt::Display,
+    rc::Rc,
+};
+
+use bytecode::{Environment, FnType, Symbol, ThreadID, Value};
+
+use crate::{Runtime, Thread};
+
+/// A closure bound in a frame, and the frame it captures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    /// The name the closure is bound to.
+    pub sym: Symbol,
+    /// The name of the function.
+    pub fn_sym: Symbol,
+    pub addr: usize,
+    /// The ID of the captured frame in the report, or `None` if it has been dropped.
+    pub frame: Option<usize>,
+    /// Whether the closure captures the frame it is bound in or one of its ancestors,
+    /// i.e. the closure and the frame form a cycle.
+    pub cycle: bool,
+}
+
+/// A frame of an environment as seen by the inspector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameReport {
+    /// The ID of the parent frame in the report, `None` for the global frame.
+    pub parent: Option<usize>,
+    /// The number of strong references to the frame, excluding the inspector's.
+    pub strong_count: usize,
+    /// The number of weak references to the frame, from threads, call frames, child frames and closures.
+    pub weak_count: usize,
+    /// The bindings of the frame, excluding builtin functions.
+    pub bindings: Vec<(Symbol, Value)>,
+    /// The closures bound in the frame, excluding builtin functions.
+    pub captures: Vec<Capture>,
+}
+
+/// The environment of a thread: the chain of frames from its current environment up to the global frame,
+/// followed by the frames only reachable through the closures bound in them.
+/// Frames are identified by their index in `frames`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvReport {
+    pub thread_id: ThreadID,
+    pub frames: Vec<FrameReport>,
+    /// The number of frames in the environment chain of the thread, which come first in `frames`.
+    pub chain_len: usize,
+}
+
+impl Display for EnvReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = vec![format!("Environment of thread {}:", self.thread_id)];
+
+        for (id, frame) in self.frames.iter().enumerate() {
+            if id == self.chain_len {
+                lines.push("Captured by closures:".to_string());
+            }
+
+            let parent = match frame.parent {
+                Some(parent) => format!("parent=#{}", parent),
+                None => "global".to_string(),
+            };
+
+            lines.push(format!(
+                "  #{} {} strong={} weak={}",
+                id, parent, frame.strong_count, frame.weak_count
+            ));
+
+            // The global frame holds the constants, which would drown out the program's names
+            if frame.parent.is_none() {
+                lines.push(format!("    {} constants", frame.bindings.len()));
+                continue;
+            }
+
+            for (sym, val) in frame.bindings.iter() {
+                match frame.captures.iter().find(|capture| capture.sym == *sym) {
+                    Some(capture) => {
+                        let captured = match capture.frame {
+                            Some(frame) => format!("#{}", frame),
+                            None => "<dropped>".to_string(),
+                        };
+                        let cycle = if capture.cycle { " (cycle)" } else { "" };
+
+                        lines.push(format!(
+                            "    {} = fn {} at pc={} captures {}{}",
+                            sym, capture.fn_sym, capture.addr, captured, cycle
+                        ));
+                    }
+                    None => lines.push(format!("    {} = {:?}", sym, val)),
+                }
+            }
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Runtime {
+    /// Find a thread that has not been joined, whether running, ready, blocked or finished.
+    pub fn find_thread(&self, thread_id: ThreadID) -> Option<&Thread> {
+        if self.current_thread.thread_id == thread_id {
+            return Some(&self.current_thread);
+        }
+
+        self.ready_queue
+            .iter()
+            .chain(self.blocked_queue.iter().map(|(thread, _)| thread))
+            .chain(self.zombie_threads.values())
+            .find(|thread| thread.thread_id == thread_id)
+    }
+
+    /// Inspect the environment of the thread, walking its chain of frames and the frames captured by closures.
+    /// Returns `None` if there is no such thread or its environment has been dropped.
+    pub fn inspect_env(&self, thread_id: ThreadID) -> Option<EnvReport> {
+        let thread = self.find_thread(thread_id)?;
+        let env = thread.env.upgrade()?;
+
+        // Frames are identified by pointer while walking the graph
+        let mut ids: HashMap<*const RefCell<Environment>, usize> = HashMap::new();
+        let mut frames: Vec<Rc<RefCell<Environment>>> = vec![];
+
+        let mut next = Some(env);
+        while let Some(frame) = next {
+            next = frame.borrow().parent.as_ref().and_then(|p| p.upgrade());
+            ids.insert(Rc::as_ptr(&frame), frames.len());
+            frames.push(frame);
+        }
+        let chain_len = frames.len();
+
+        // Discover the frames captured by closures, and their parents, breadth first
+        let mut i = 0;
+        while i < frames.len() {
+            let mut found = vec![];
+            {
+                let frame = frames[i].borrow();
+                let parent = frame.parent.as_ref().and_then(|p| p.upgrade());
+                let captured = closures(&frame).filter_map(|(_, closure)| closure.env.0.upgrade());
+
+                for env in parent.into_iter().chain(captured) {
+                    if let Entry::Vacant(entry) = ids.entry(Rc::as_ptr(&env)) {
+                        entry.insert(frames.len() + found.len());
+                        found.push(env);
+                    }
+                }
+            }
+            frames.extend(found);
+            i += 1;
+        }
+
+        let parents: Vec<Option<usize>> = frames
+            .iter()
+            .map(|frame| {
+                let parent = frame.borrow().parent.as_ref()?.upgrade()?;
+                ids.get(&Rc::as_ptr(&parent)).copied()
+            })
+            .collect();
+
+        let reports = frames
+            .iter()
+            .enumerate()
+            .map(|(id, rc)| {
+                let frame = rc.borrow();
+
+                let captures = closures(&frame)
+                    .map(|(sym, closure)| {
+                        let captured = closure
+                            .env
+                            .0
+                            .upgrade()
+                            .and_then(|env| ids.get(&Rc::as_ptr(&env)).copied());
+
+                        Capture {
+                            sym,
+                            fn_sym: closure.sym,
+                            addr: closure.addr,
+                            frame: captured,
+                            cycle: captured.is_some_and(|c| is_ancestor(&parents, c, id)),
+                        }
+                    })
+                    .collect();
+
+                let bindings = frame
+                    .bindings()
+                    .into_iter()
+                    .filter(|(_, val)| !is_builtin(val))
+                    .collect();
+
+                FrameReport {
+                    parent: parents[id],
+                    strong_count: Rc::strong_count(rc) - 1,
+                    weak_count: Rc::weak_count(rc),
+                    bindings,
+                    captures,
+                }
+            })
+            .collect();
+
+        Some(EnvReport {
+            thread_id,
+            frames: reports,
+            chain_len,
+        })
+    }
+}
+
+/// The closures bound in the frame, excluding builtin functions.
+fn closures(frame: &Environment) -> impl Iterator<Item = (Symbol, bytecode::Closure)> {
+    frame
+        .bindings()
+        .into_iter()
+        .filter_map(|(sym, val)| match val {
+            Value::Closure(closure) if closure.fn_type != FnType::Builtin => {
+                Some((sym, closure.as_ref().clone()))
+            }
+            _ => None,
+        })
+}
+
+fn is_builtin(val: &Value) -> bool {
+    matches!(val, Value::Closure(closure) if closure.fn_type == FnType::Builtin)
+}
+
+/// Whether `ancestor` is `frame` or one of its ancestors.
+fn is_ancestor(parents: &[Option<usize>], ancestor: usize, mut frame: usize) -> bool {
+    loop {
+        if frame == ancestor {
+            return true;
+        }
+
+        match parents[frame] {
+            Some(parent) => frame = parent,
+            None => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytecode::ByteCode;
+
+    use crate::run;
+
+    use super::*;
+
+    #[test]
+    fn test_inspect_env() -> Result<()> {
+        // let x = 1;
+        // fn f() { x }
+        // let g = { let y = 2; fn h() { y } h };
+        let instrs = vec![
+            ByteCode::enterscope(vec!["x", "f", "g"]),
+            ByteCode::ldc(1),
+            ByteCode::assign("x"),
+            ByteCode::ldf(5, "f", Vec::<Symbol>::new()),
+            ByteCode::GOTO(7),
+            ByteCode::ld("x"),
+            ByteCode::RESET(bytecode::FrameType::CallFrame),
+            ByteCode::assign("f"),
+            ByteCode::enterscope(vec!["y", "h"]),
+            ByteCode::ldc(2),
+            ByteCode::assign("y"),
+            ByteCode::ldf(13, "h", Vec::<Symbol>::new()),
+            ByteCode::GOTO(15),
+            ByteCode::ld("y"),
+            ByteCode::RESET(bytecode::FrameType::CallFrame),
+            ByteCode::assign("h"),
+            ByteCode::ld("h"),
+            ByteCode::EXITSCOPE,
+            ByteCode::assign("g"),
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
+
+        assert!(rt.inspect_env(2).is_none());
+        let report = rt
+            .inspect_env(1)
+            .expect("Main thread should have an environment");
+
+        // The block frame of h is only reachable through g
+        assert_eq!(report.chain_len, 2);
+        assert_eq!(report.frames.len(), 3);
+        assert_eq!(report.frames[0].parent, Some(1));
+        assert_eq!(report.frames[1].parent, None);
+        assert_eq!(report.frames[2].parent, Some(0));
+
+        let top = &report.frames[0];
+        assert_eq!(top.bindings[0], ("x".into(), Value::Int(1)));
+        assert_eq!(
+            top.captures,
+            vec![
+                Capture {
+                    sym: "f".into(),
+                    fn_sym: "f".into(),
+                    addr: 5,
+                    frame: Some(0),
+                    cycle: true,
+                },
+                Capture {
+                    sym: "g".into(),
+                    fn_sym: "h".into(),
+                    addr: 13,
+                    frame: Some(2),
+                    cycle: false,
+                },
+            ]
+        );
+        assert!(report.frames[2].captures[0].cycle);
+
+        // Only the registry holds frames strongly
+        assert!(report.frames.iter().all(|frame| frame.strong_count == 1));
+
+        let s = report.to_string();
+        assert!(s.contains("f = fn f at pc=5 captures #0 (cycle)"));
+        assert!(s.contains("Captured by closures:\n  #2 parent=#0"));
+
+        Ok(())
+    }
+}
@@ -1,19 +1,188 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    time::{Duration, Instant},
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    io::{BufRead, Write},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-use bytecode::{weak_clone, ByteCode, EnvStrong, Environment, Semaphore, ThreadID, W};
+use bytecode::{
+    weak_clone, Address, Barrier, BoundedQueue, ByteCode, Channel, CondVar, EnvStrong, Environment,
+    RwLock, Semaphore, ThreadID, Value, WaitGroup, W,
+};
 
-use crate::Thread;
+use crate::{Thread, ThreadState, VmError};
+pub use capability::*;
+pub use clock::*;
+pub use config::*;
+pub use events::*;
+pub use host::*;
+pub use inspect::*;
+pub use overflow::*;
+pub use policy::*;
+pub use replay::*;
+pub use rng::*;
 pub use run::*;
+pub use scheduler::*;
+pub use trace::*;
 
+mod capability;
+mod clock;
+mod config;
+mod coverage;
+mod events;
 mod gc;
+mod host;
+mod inspect;
+mod overflow;
+mod policy;
+mod priority;
+mod reload;
+mod replay;
+mod rng;
 mod run;
+mod scheduler;
+mod snapshot;
+mod trace;
 
 pub const DEFAULT_TIME_QUANTUM: Duration = Duration::from_millis(100);
 pub const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(1);
 pub const MAIN_THREAD_ID: i64 = 1;
+pub const DEFAULT_ENV_POOL_CAPACITY: usize = 1024;
+/// The number of instructions a thread runs before it is preempted in deterministic mode.
+pub const DEFAULT_INSTR_QUANTUM: u64 = 1000;
+/// The number of instructions between garbage collections in deterministic mode.
+pub const GC_INSTR_INTERVAL: u64 = 100_000;
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100_000;
+pub const DEFAULT_MAX_OPERAND_STACK: usize = 1_000_000;
+
+/// Something that a blocked thread is waiting on.
+#[derive(Debug, Clone)]
+pub enum WakeSource {
+    /// The thread is woken up when the semaphore is posted.
+    /// If an address is given, the thread continues execution from that address.
+    Semaphore {
+        sem: Semaphore,
+        addr: Option<Address>,
+    },
+    /// The thread is woken up when the condition variable is notified,
+    /// after which it waits to reacquire the mutex.
+    CondVar { cv: CondVar, mutex: Semaphore },
+    /// The thread is woken up when all parties have arrived at the barrier.
+    Barrier(Barrier),
+    /// The thread is woken up when the count of the wait group reaches 0.
+    WaitGroup(WaitGroup),
+    /// The thread is woken up when a thread receives from the full channel, taking the value it is sending,
+    /// or when the channel is closed.
+    /// If an address is given, the thread sends in a select and continues execution from that address.
+    ChannelSend {
+        ch: Channel,
+        val: Value,
+        addr: Option<Address>,
+    },
+    /// The thread is woken up when a thread sends to the empty channel, handing it the value,
+    /// or when the channel is closed.
+    /// If an address is given, the thread receives in a select and continues execution from that address.
+    ChannelRecv { ch: Channel, addr: Option<Address> },
+    /// The thread is woken up when a thread pops from the full bounded queue, taking the value it is pushing.
+    QueuePush(BoundedQueue),
+    /// The thread is woken up when a thread pushes to the empty bounded queue, handing it the value.
+    QueuePop(BoundedQueue),
+    /// The thread is woken up when the rwlock is released, after which it tries to take it again,
+    /// to write to it or to read from it.
+    RwLock { lock: RwLock, write: bool },
+    /// The thread is woken up once the deadline, a time on the clock of the runtime, has passed,
+    /// if nothing else woke it up first.
+    Timeout(Duration),
+    /// The thread is woken up once the future of the call to an asynchronous function of the host is ready.
+    Host,
+}
+
+impl WakeSource {
+    pub fn new(sem: Semaphore) -> Self {
+        WakeSource::Semaphore { sem, addr: None }
+    }
+
+    pub fn new_with_address(sem: Semaphore, addr: Address) -> Self {
+        WakeSource::Semaphore {
+            sem,
+            addr: Some(addr),
+        }
+    }
+
+    pub fn new_cond_var(cv: CondVar, mutex: Semaphore) -> Self {
+        WakeSource::CondVar { cv, mutex }
+    }
+
+    /// Check if posting the given semaphore wakes up the thread.
+    pub fn is_semaphore(&self, other: &Semaphore) -> bool {
+        matches!(self, WakeSource::Semaphore { sem, .. } if sem == other)
+    }
+
+    /// Check if notifying the given condition variable wakes up the thread.
+    pub fn is_cond_var(&self, other: &CondVar) -> bool {
+        matches!(self, WakeSource::CondVar { cv, .. } if cv == other)
+    }
+
+    /// Check if all parties arriving at the given barrier wakes up the thread.
+    pub fn is_barrier(&self, other: &Barrier) -> bool {
+        matches!(self, WakeSource::Barrier(b) if b == other)
+    }
+
+    /// Check if the count of the given wait group reaching 0 wakes up the thread.
+    pub fn is_wait_group(&self, other: &WaitGroup) -> bool {
+        matches!(self, WakeSource::WaitGroup(wg) if wg == other)
+    }
+
+    /// Check if a thread receiving from the given channel wakes up the thread.
+    pub fn is_channel_send(&self, other: &Channel) -> bool {
+        matches!(self, WakeSource::ChannelSend { ch, .. } if ch == other)
+    }
+
+    /// Check if a thread sending to the given channel wakes up the thread.
+    pub fn is_channel_recv(&self, other: &Channel) -> bool {
+        matches!(self, WakeSource::ChannelRecv { ch, .. } if ch == other)
+    }
+
+    /// Check if a thread popping from the given bounded queue wakes up the thread.
+    pub fn is_queue_push(&self, other: &BoundedQueue) -> bool {
+        matches!(self, WakeSource::QueuePush(q) if q == other)
+    }
+
+    /// Check if a thread pushing to the given bounded queue wakes up the thread.
+    pub fn is_queue_pop(&self, other: &BoundedQueue) -> bool {
+        matches!(self, WakeSource::QueuePop(q) if q == other)
+    }
+
+    /// Check if releasing the given rwlock wakes up the thread.
+    pub fn is_rwlock(&self, other: &RwLock) -> bool {
+        matches!(self, WakeSource::RwLock { lock, .. } if lock == other)
+    }
+
+    /// Check if the thread is waiting to write to the given rwlock.
+    pub fn is_rwlock_writer(&self, other: &RwLock) -> bool {
+        matches!(self, WakeSource::RwLock { lock, write: true } if lock == other)
+    }
+
+    /// Check if the given deadline passing wakes up the thread.
+    pub fn is_timeout(&self, other: &Duration) -> bool {
+        matches!(self, WakeSource::Timeout(deadline) if deadline == other)
+    }
+}
+
+/// A scope block a thread is in, whose spawned threads are joined before the block gives its value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpawnScope {
+    /// The height of the runtime stack of the thread when the scope was opened.
+    pub depth: usize,
+    /// The threads spawned in the scope that may not have finished yet.
+    pub threads: Vec<ThreadID>,
+}
 
 /// The runtime of the virtual machine.
 /// It contains the instructions to execute, the current thread, and the ready and blocked threads.
@@ -21,58 +190,198 @@ pub const MAIN_THREAD_ID: i64 = 1;
 /// The ready queue is a queue of threads that are ready to run.
 /// The blocked queue is a queue of threads that are waiting for some event to occur.
 /// The zombie threads are threads that have finished executing and are waiting to be joined.
+///
+/// A runtime is self-contained: its global environment, builtins and functions of the host, limits, IO, clock
+/// and random number generator are its own, so a process can run many runtimes side by side as isolated instances,
+/// interleaved or on threads of their own. They do share the process-wide table of interned symbols
+/// (see `bytecode::Symbol`), which only grows: the names of every program compiled or loaded in the process count
+/// towards its limit of `bytecode::MAX_INTERNED_BYTES`, past which compiling, loading or restoring a program with
+/// new names fails with an error, as does binding a function of the host, for every runtime of the process.
 pub struct Runtime {
     /// If the program is done.
     pub done: bool,
+    /// The status the program asked to exit with by calling exit, if it did.
+    pub exit_code: Option<i64>,
     /// If the program is in debug mode.
     pub debug: bool,
-    /// The time the program started, used for calculating the time quantum.
-    pub time: Instant,
+    /// The stream print and println write to, stdout of the process by default.
+    pub stdout: Box<dyn Write>,
+    /// The stream read_line reads from, if it is not stdin of the process.
+    pub stdin: Option<Box<dyn BufRead>>,
+    /// The sink executed instructions are logged to, if tracing is on.
+    pub trace_sink: Option<Box<dyn Write>>,
+    /// The source of time of the runtime. Times kept by the runtime are the time since the clock started.
+    pub clock: Box<dyn Clock>,
+    /// The source of randomness of the runtime.
+    pub rng: Box<dyn Rng>,
+    /// The time the current thread was scheduled, used for calculating the time quantum.
+    pub time: Duration,
+    /// The subscribers notified of scheduling events.
+    pub subscribers: Vec<Box<dyn SchedulerSubscriber>>,
     /// The maximum amount of time a thread can run before it is preempted.
     pub time_quantum: Duration,
+    /// The number of instructions a thread runs before it is preempted, instead of the time quantum, if set.
+    pub instr_quantum: Option<u64>,
+    /// The number of instructions executed when the current thread was scheduled, used for the instruction quantum.
+    pub quantum_start: u64,
+    /// The seed of the run, if it is deterministic.
+    pub seed: Option<u64>,
+    /// The context switches so far, if they are recorded.
+    pub recording: Option<Vec<Switch>>,
+    /// The context switches left of the log being replayed, if any.
+    pub replaying: Option<VecDeque<Switch>>,
+    /// The number of times each instruction has run, if coverage is recorded.
+    pub coverage: Option<Vec<u64>>,
     /// The time the garbage collector was last run.
-    pub gc_timer: Instant,
+    pub gc_timer: Duration,
+    /// The number of instructions executed when the garbage collector last ran.
+    pub gc_instr: u64,
     /// The interval at which to run the mark and sweep garbage collector.
     pub gc_interval: Duration,
     /// The instructions to execute.
     pub instrs: Vec<ByteCode>,
     /// The environment registry, holds strong references to environments.
     pub env_registry: HashSet<EnvStrong>,
+    /// The frames of swept environments, cleared but with their capacity retained, to be reused by new environments.
+    pub env_pool: Vec<Environment>,
+    /// The maximum number of frames kept in the environment pool.
+    pub env_pool_capacity: usize,
     /// The number of threads that have been created.
     pub thread_count: i64,
     /// The current thread that is executing.
     pub current_thread: Thread,
-    /// The threads that are ready to run.
+    /// The threads that are ready to run, in the order the scheduler keeps them in.
     pub ready_queue: VecDeque<Thread>,
-    /// The threads that are blocked.
-    pub blocked_queue: VecDeque<(Thread, Semaphore)>,
+    /// The policy deciding which ready thread runs next.
+    pub scheduler: Box<dyn Scheduler>,
+    /// The threads that are blocked, along with what can wake them up.
+    pub blocked_queue: VecDeque<(Thread, Vec<WakeSource>)>,
     /// The threads that have finished executing, waiting to be joined.
     pub zombie_threads: HashMap<ThreadID, Thread>,
+    /// The scope blocks each thread is in, innermost last, with the threads spawned in them.
+    pub spawn_scopes: HashMap<ThreadID, Vec<SpawnScope>>,
+    /// The thread table, holds the state of every thread that has been created.
+    pub thread_states: HashMap<ThreadID, ThreadState>,
+    /// The timer queue, holds the deadlines of blocked threads waiting with a timeout, earliest first.
+    /// Entries of threads that were woken up before their deadline are skipped when they expire.
+    pub timer_queue: BinaryHeap<Reverse<(Duration, ThreadID)>>,
+    /// The maximum number of instructions the program may execute across all threads, if any.
+    pub instr_budget: Option<u64>,
+    /// The maximum number of instructions a single thread may execute, if any.
+    pub thread_instr_budget: Option<u64>,
+    /// The number of instructions executed across all threads.
+    pub instr_count: u64,
+    /// The maximum number of frames on the runtime stack of a thread, i.e. the call and scope nesting depth.
+    pub max_call_depth: usize,
+    /// The maximum number of values on the operand stack of a thread.
+    pub max_operand_stack: usize,
+    /// The maximum memory the environments of the program may hold, in bytes, if any, see [`Runtime::memory_usage`].
+    pub max_memory: Option<usize>,
+    /// What integer arithmetic does on overflow.
+    pub int_overflow: IntOverflow,
+    /// What happens when a thread does not catch a runtime error.
+    pub panic_policy: PanicPolicy,
+    /// Which thread blocked on a semaphore is woken up when it is posted.
+    pub wakeup_policy: WakeupPolicy,
+    /// The capabilities the program is denied the builtins of.
+    pub denied: HashSet<Capability>,
+    /// The functions of the host bound in the global environment, by name.
+    pub host_fns: HashMap<String, HostFn>,
+    /// The asynchronous functions of the host bound in the global environment, by name.
+    pub async_host_fns: HashMap<String, AsyncHostFn>,
+    /// The futures of the calls to asynchronous functions of the host, with the threads waiting on them.
+    pub host_futures: Vec<(ThreadID, HostFuture)>,
+    /// The waker of the futures of the host, which tells the runtime to poll them again.
+    pub host_waker: Arc<HostWaker>,
+    /// Set from outside the runtime, e.g. by the handler of Ctrl-C, to stop the program before its next instruction.
+    pub interrupt: Arc<AtomicBool>,
+    /// The compiler of hot functions to native code, if it is on.
+    #[cfg(feature = "jit")]
+    pub jit: Option<crate::Jit>,
 }
 
 /// Constructors for the runtime.
 impl Runtime {
+    // EnvStrong hashes by pointer, so interior mutability of the environment does not affect the key.
+    /// The runtime for the instructions, with the default configuration.
     pub fn new(instrs: Vec<ByteCode>) -> Self {
+        Runtime::with_config(instrs, RuntimeConfig::default())
+    }
+
+    /// The runtime for the instructions, with the limits and behavior of the configuration.
+    #[allow(clippy::mutable_key_type)]
+    pub fn with_config(instrs: Vec<ByteCode>, config: RuntimeConfig) -> Self {
         let global_env = Environment::new_global_wrapped();
         let global_env_weak = weak_clone(&global_env);
         let mut envs = HashSet::new();
         envs.insert(W(global_env));
 
-        Runtime {
-            debug: false,
+        let mut thread_states = HashMap::new();
+        thread_states.insert(MAIN_THREAD_ID, ThreadState::Running);
+
+        let deterministic = config.deterministic;
+        let mut rt = Runtime {
+            debug: config.debug,
+            stdout: Box::new(std::io::stdout()),
+            stdin: None,
+            trace_sink: None,
             done: false,
-            time: Instant::now(),
-            time_quantum: DEFAULT_TIME_QUANTUM,
-            gc_timer: Instant::now(),
-            gc_interval: DEFAULT_GC_INTERVAL,
+            exit_code: None,
+            clock: default_clock(),
+            rng: default_rng(),
+            time: Duration::ZERO,
+            subscribers: Vec::new(),
+            time_quantum: config.time_quantum,
+            instr_quantum: None,
+            quantum_start: 0,
+            seed: None,
+            recording: None,
+            replaying: None,
+            coverage: None,
+            gc_timer: Duration::ZERO,
+            gc_instr: 0,
+            gc_interval: config.gc_interval,
             instrs,
             env_registry: envs,
+            env_pool: Vec::new(),
+            env_pool_capacity: config.env_pool_capacity,
             thread_count: 1,
             current_thread: Thread::new(MAIN_THREAD_ID, global_env_weak),
             ready_queue: VecDeque::new(),
+            scheduler: Box::new(RoundRobin),
             blocked_queue: VecDeque::new(),
             zombie_threads: HashMap::new(),
+            spawn_scopes: HashMap::new(),
+            thread_states,
+            timer_queue: BinaryHeap::new(),
+            instr_budget: config.instr_budget,
+            thread_instr_budget: config.thread_instr_budget,
+            instr_count: 0,
+            max_call_depth: config.max_call_depth,
+            max_operand_stack: config.max_operand_stack,
+            max_memory: config.max_memory,
+            int_overflow: config.int_overflow,
+            panic_policy: config.panic_policy,
+            wakeup_policy: config.wakeup_policy,
+            denied: config.denied,
+            host_fns: HashMap::new(),
+            async_host_fns: HashMap::new(),
+            host_futures: Vec::new(),
+            host_waker: Arc::default(),
+            interrupt: Arc::default(),
+            #[cfg(feature = "jit")]
+            jit: None,
+        };
+
+        if let Some(seed) = deterministic {
+            rt.set_deterministic(seed);
+        }
+        // Hosts cranelift does not support run the program in the interpreter alone
+        #[cfg(feature = "jit")]
+        if let Some(threshold) = config.jit {
+            rt.set_jit(threshold).ok();
         }
+        rt
     }
 }
 
@@ -84,6 +393,55 @@ impl Default for Runtime {
 
 /// Configuration for the runtime.
 impl Runtime {
+    /// The limits and behavior the runtime currently has.
+    pub fn config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            time_quantum: self.time_quantum,
+            gc_interval: self.gc_interval,
+            env_pool_capacity: self.env_pool_capacity,
+            instr_budget: self.instr_budget,
+            thread_instr_budget: self.thread_instr_budget,
+            max_call_depth: self.max_call_depth,
+            max_operand_stack: self.max_operand_stack,
+            max_memory: self.max_memory,
+            int_overflow: self.int_overflow,
+            panic_policy: self.panic_policy,
+            wakeup_policy: self.wakeup_policy,
+            denied: self.denied.clone(),
+            deterministic: self.seed,
+            debug: self.debug,
+            #[cfg(feature = "jit")]
+            jit: self.jit.as_ref().map(|jit| jit.threshold()),
+        }
+    }
+
+    /// Change the limits and behavior of the runtime to those of the configuration.
+    pub fn set_config(&mut self, config: RuntimeConfig) {
+        self.time_quantum = config.time_quantum;
+        self.gc_interval = config.gc_interval;
+        self.set_env_pool_capacity(config.env_pool_capacity);
+        self.instr_budget = config.instr_budget;
+        self.thread_instr_budget = config.thread_instr_budget;
+        self.max_call_depth = config.max_call_depth;
+        self.max_operand_stack = config.max_operand_stack;
+        self.max_memory = config.max_memory;
+        self.int_overflow = config.int_overflow;
+        self.panic_policy = config.panic_policy;
+        self.wakeup_policy = config.wakeup_policy;
+        self.denied = config.denied;
+        self.debug = config.debug;
+        if let Some(seed) = config.deterministic {
+            self.set_deterministic(seed);
+        }
+        #[cfg(feature = "jit")]
+        match config.jit {
+            Some(threshold) => {
+                self.set_jit(threshold).ok();
+            }
+            None => self.jit = None,
+        }
+    }
+
     pub fn set_time_quantum(&mut self, time_quantum: Duration) {
         self.time_quantum = time_quantum;
     }
@@ -92,7 +450,313 @@ impl Runtime {
         self.gc_interval = gc_interval;
     }
 
+    pub fn set_env_pool_capacity(&mut self, env_pool_capacity: usize) {
+        self.env_pool_capacity = env_pool_capacity;
+        self.env_pool.truncate(env_pool_capacity);
+    }
+
+    /// Limit the number of instructions the program may execute across all threads.
+    pub fn set_instr_budget(&mut self, budget: u64) {
+        self.instr_budget = Some(budget);
+    }
+
+    /// Limit the number of instructions each thread may execute.
+    pub fn set_thread_instr_budget(&mut self, budget: u64) {
+        self.thread_instr_budget = Some(budget);
+    }
+
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    pub fn set_max_operand_stack(&mut self, max_operand_stack: usize) {
+        self.max_operand_stack = max_operand_stack;
+    }
+
+    /// Limit the memory the environments of the program may hold, in bytes, see [`Runtime::memory_usage`].
+    pub fn set_max_memory(&mut self, max_memory: usize) {
+        self.max_memory = Some(max_memory);
+    }
+
+    /// Choose what integer arithmetic does on overflow, trapping by default.
+    pub fn set_int_overflow(&mut self, int_overflow: IntOverflow) {
+        self.int_overflow = int_overflow;
+    }
+
+    /// Choose what happens when a thread does not catch a runtime error, stopping the program by default.
+    pub fn set_panic_policy(&mut self, panic_policy: PanicPolicy) {
+        self.panic_policy = panic_policy;
+    }
+
+    /// Choose which thread blocked on a semaphore is woken up when it is posted, the first to block by default.
+    pub fn set_wakeup_policy(&mut self, wakeup_policy: WakeupPolicy) {
+        self.wakeup_policy = wakeup_policy;
+    }
+
     pub fn set_debug_mode(&mut self) {
         self.debug = true;
     }
+
+    /// Notify the subscriber of every scheduling event.
+    pub fn subscribe(&mut self, subscriber: impl SchedulerSubscriber + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    /// Replace the clock of the runtime, e.g. by a virtual clock for reproducible runs.
+    /// The clock should be set before the program runs, since the times kept so far are on the old clock.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+        self.time = self.now();
+        self.gc_timer = self.time;
+    }
+
+    /// Run the program deterministically: the scheduler and `random_int` draw from random number generators seeded
+    /// with the seed, the clock is virtual and only advances when no thread can run until a timeout expires,
+    /// and threads are preempted every `DEFAULT_INSTR_QUANTUM` instructions instead of by time.
+    /// Runs with the same seed print the same output, so a race found with one seed can be reproduced.
+    pub fn set_deterministic(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.set_clock(VirtualClock::new(Duration::ZERO));
+        self.set_rng(SeededRng::new(seed));
+        self.set_scheduler(SeededScheduler::new(seed));
+        self.set_instr_quantum(DEFAULT_INSTR_QUANTUM);
+    }
+
+    /// Preempt threads after they run the number of instructions, instead of after the time quantum.
+    pub fn set_instr_quantum(&mut self, instr_quantum: u64) {
+        self.instr_quantum = Some(instr_quantum);
+        self.quantum_start = self.instr_count;
+        self.gc_instr = self.instr_count;
+    }
+
+    /// Replace the random number generator of the runtime, e.g. by a seeded one for reproducible runs.
+    pub fn set_rng(&mut self, rng: impl Rng + 'static) {
+        self.rng = Box::new(rng);
+    }
+
+    /// A random int from `lo` up to but excluding `hi`.
+    ///
+    /// # Errors
+    ///
+    /// If the range is empty.
+    pub fn random_int(&self, lo: i64, hi: i64) -> Result<i64, VmError> {
+        if lo >= hi {
+            let msg = format!("random_int expects lo < hi, got {} and {}", lo, hi);
+            return Err(VmError::IllegalArgument(msg));
+        }
+
+        let span = hi.abs_diff(lo);
+        Ok(lo.wrapping_add((self.rng.next_u64() % span) as i64))
+    }
+
+    /// The time on the clock of the runtime.
+    #[inline]
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Stop the program when the flag is set, see [`crate::interrupt_on_ctrl_c`].
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = flag;
+    }
+
+    /// If the program has been asked to stop, whether or not it has yet.
+    #[inline]
+    pub fn interrupted(&self) -> bool {
+        self.interrupt.load(Ordering::Relaxed)
+    }
+
+    /// Write the output of print and println to the stream instead of stdout.
+    pub fn set_stdout(&mut self, stdout: impl Write + 'static) {
+        self.stdout = Box::new(stdout);
+    }
+
+    /// Read the input of read_line from the stream instead of stdin.
+    pub fn set_stdin(&mut self, stdin: impl BufRead + 'static) {
+        self.stdin = Some(Box::new(stdin));
+    }
+
+    /// The runtime with the output of print and println written to the stream, see [`Runtime::set_stdout`].
+    pub fn with_stdout(mut self, stdout: Box<dyn Write>) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    /// The runtime with the input of read_line read from the stream, see [`Runtime::set_stdin`].
+    pub fn with_stdin(mut self, stdin: Box<dyn BufRead>) -> Self {
+        self.stdin = Some(stdin);
+        self
+    }
+
+    /// Log each executed instruction to the sink.
+    pub fn set_trace_sink(&mut self, sink: impl Write + 'static) {
+        self.trace_sink = Some(Box::new(sink));
+    }
+}
+
+/// Environment allocation.
+impl Runtime {
+    /// Allocate a new environment with no parent, reusing a pooled frame if there is one.
+    /// The environment is not added to the registry.
+    pub fn alloc_env(&mut self) -> Rc<RefCell<Environment>> {
+        let env = self.env_pool.pop().unwrap_or_default();
+        Rc::new(RefCell::new(env))
+    }
+
+    /// Return the frame of an environment that is no longer referenced to the pool.
+    /// The frame is cleared, but keeps its capacity.
+    pub fn free_env(&mut self, env: Rc<RefCell<Environment>>) {
+        if self.env_pool.len() >= self.env_pool_capacity {
+            return;
+        }
+
+        // Only reuse the frame if nothing else holds on to the environment.
+        let Some(env) = Rc::into_inner(env) else {
+            return;
+        };
+
+        let mut frame = env.into_inner();
+        frame.clear();
+        self.env_pool.push(frame);
+    }
+}
+
+/// Thread table queries and updates.
+impl Runtime {
+    /// Get the state of the thread with the given ID.
+    /// Returns None if no thread with the given ID has been created.
+    pub fn thread_state(&self, tid: ThreadID) -> Option<ThreadState> {
+        self.thread_states.get(&tid).copied()
+    }
+
+    /// Check if the thread with the given ID has finished executing.
+    pub fn is_finished(&self, tid: ThreadID) -> bool {
+        self.thread_state(tid) == Some(ThreadState::Done)
+    }
+
+    /// Record the state of the thread with the given ID in the thread table.
+    pub fn set_thread_state(&mut self, tid: ThreadID, state: ThreadState) {
+        self.thread_states.insert(tid, state);
+    }
+
+    /// Move every blocked thread that can be woken up by a matching wake source to the ready queue.
+    pub fn wake_blocked<F>(&mut self, matches: F)
+    where
+        F: Fn(&WakeSource) -> bool,
+    {
+        let blocked_queue = std::mem::take(&mut self.blocked_queue);
+
+        for (thread, sources) in blocked_queue {
+            if sources.iter().any(&matches) {
+                self.set_thread_state(thread.thread_id, ThreadState::Ready);
+                self.emit_event(thread.thread_id, SchedulerEventKind::Woken);
+                self.push_ready(thread);
+            } else {
+                self.blocked_queue.push_back((thread, sources));
+            }
+        }
+    }
+
+    /// Remove the blocked thread the scheduler wakes up of those waiting on a source that matches, if any,
+    /// see [`Runtime::take_waiter`]. The thread is not moved to the ready queue, so the caller can hand it a value first.
+    pub fn take_blocked<F>(&mut self, matches: F) -> Option<Thread>
+    where
+        F: Fn(&WakeSource) -> bool,
+    {
+        self.take_waiter(matches).map(|(thread, _)| thread)
+    }
+
+    /// Move a thread taken from the blocked queue to the ready queue.
+    pub fn wake(&mut self, thread: Thread) {
+        self.set_thread_state(thread.thread_id, ThreadState::Ready);
+        self.emit_event(thread.thread_id, SchedulerEventKind::Woken);
+        self.push_ready(thread);
+    }
+}
+
+/// Scheduling of threads waiting with a timeout.
+impl Runtime {
+    /// Check if the earliest deadline in the timer queue has passed.
+    #[inline]
+    pub fn timer_expired(&self) -> bool {
+        self.timer_queue
+            .peek()
+            .is_some_and(|Reverse((deadline, _))| *deadline <= self.now())
+    }
+
+    /// Move every blocked thread whose deadline has passed to the ready queue.
+    /// The thread was not woken up by anything else, so false is pushed onto its operand stack.
+    pub fn wake_expired_timers(&mut self) {
+        let now = self.now();
+
+        while let Some(Reverse((deadline, tid))) = self.timer_queue.peek().copied() {
+            if deadline > now {
+                break;
+            }
+            self.timer_queue.pop();
+
+            let Some(i) = self.blocked_queue.iter().position(|(thread, sources)| {
+                thread.thread_id == tid && sources.iter().any(|s| s.is_timeout(&deadline))
+            }) else {
+                // The thread was woken up before its deadline.
+                continue;
+            };
+
+            let (mut thread, _) = self
+                .blocked_queue
+                .remove(i)
+                .expect("Index should be in bounds since it was just found");
+
+            thread.operand_stack.push(false.into());
+            self.set_thread_state(tid, ThreadState::Ready);
+            self.emit_event(tid, SchedulerEventKind::Woken);
+            self.push_ready(thread);
+        }
+    }
+
+    /// Pop the next ready thread from the ready queue, see `take_next_ready`.
+    /// If the ready queue is empty but some blocked thread is waiting with a timeout or on the host,
+    /// the runtime sleeps until the earliest deadline passes or a future of the host is ready, and wakes the thread up.
+    ///
+    /// # Errors
+    ///
+    /// If the ready queue is empty and no blocked thread is waiting with a timeout or on the host,
+    /// or a future of the host failed.
+    pub fn pop_ready_thread(&mut self) -> anyhow::Result<Thread> {
+        loop {
+            if let Some(thread) = self.take_next_ready()? {
+                return Ok(thread);
+            }
+
+            let deadline = self
+                .timer_queue
+                .peek()
+                .map(|Reverse((deadline, _))| *deadline);
+
+            if self.awaiting_host() {
+                self.poll_host_futures()?;
+                if !self.ready_queue.is_empty() {
+                    continue;
+                }
+
+                // Sleep a little at a time, as the futures may be woken up at any moment
+                let idle = deadline.map_or(HOST_IDLE_SLEEP, |deadline| {
+                    deadline.saturating_sub(self.now()).min(HOST_IDLE_SLEEP)
+                });
+                self.clock.sleep(idle);
+
+                if self.interrupted() {
+                    return Err(VmError::Interrupted.into());
+                }
+            } else {
+                let Some(deadline) = deadline else {
+                    return Err(VmError::NoThreadsInReadyQueue.into());
+                };
+
+                self.clock.sleep(deadline.saturating_sub(self.now()));
+            }
+
+            self.wake_expired_timers();
+        }
+    }
 }
@@ -0,0 +1,117 @@
+use bytecode::BinOp;
+use clap::ValueEnum;
+
+use crate::VmError;
+
+/// What integer arithmetic does when its result does not fit in an i64.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntOverflow {
+    /// Wrap around in two's complement, e.g. `MAX_INT + 1` is `MIN_INT`.
+    Wrap,
+    /// Clamp to the nearest bound, e.g. `MAX_INT + 1` is `MAX_INT`.
+    Saturate,
+    /// Fail with a runtime error giving the operands.
+    #[default]
+    Trap,
+}
+
+impl IntOverflow {
+    /// Apply the arithmetic operation to the ints.
+    ///
+    /// # Errors
+    ///
+    /// If the divisor of a division or modulus is 0, whatever the mode,
+    /// or if the result overflows in trap mode.
+    pub fn apply(self, op: BinOp, lhs: i64, rhs: i64) -> Result<i64, VmError> {
+        let expr = || format!("{} {} {}", lhs, String::from(op.clone()), rhs);
+
+        if matches!(op, BinOp::Div | BinOp::Mod) && rhs == 0 {
+            return Err(VmError::DivisionByZero(expr()));
+        }
+
+        let res = match (self, &op) {
+            (IntOverflow::Wrap, BinOp::Add) => Some(lhs.wrapping_add(rhs)),
+            (IntOverflow::Wrap, BinOp::Sub) => Some(lhs.wrapping_sub(rhs)),
+            (IntOverflow::Wrap, BinOp::Mul) => Some(lhs.wrapping_mul(rhs)),
+            (IntOverflow::Wrap, BinOp::Div) => Some(lhs.wrapping_div(rhs)),
+            (IntOverflow::Saturate, BinOp::Add) => Some(lhs.saturating_add(rhs)),
+            (IntOverflow::Saturate, BinOp::Sub) => Some(lhs.saturating_sub(rhs)),
+            (IntOverflow::Saturate, BinOp::Mul) => Some(lhs.saturating_mul(rhs)),
+            (IntOverflow::Saturate, BinOp::Div) => Some(lhs.saturating_div(rhs)),
+            // MIN_INT % -1 overflows, but its remainder is 0 in every mode but trap
+            (IntOverflow::Wrap | IntOverflow::Saturate, BinOp::Mod) => Some(lhs.wrapping_rem(rhs)),
+            (IntOverflow::Trap, BinOp::Add) => lhs.checked_add(rhs),
+            (IntOverflow::Trap, BinOp::Sub) => lhs.checked_sub(rhs),
+            (IntOverflow::Trap, BinOp::Mul) => lhs.checked_mul(rhs),
+            (IntOverflow::Trap, BinOp::Div) => lhs.checked_div(rhs),
+            (IntOverflow::Trap, BinOp::Mod) => lhs.checked_rem(rhs),
+            (_, op) => unreachable!("{:?} is not an arithmetic operation", op),
+        };
+
+        res.ok_or_else(|| VmError::IntegerOverflow(format!("{} {} {}", lhs, String::from(op), rhs)))
+    }
+
+    /// Negate the int.
+    ///
+    /// # Errors
+    ///
+    /// If the result overflows in trap mode, i.e. the int is `MIN_INT`.
+    pub fn neg(self, i: i64) -> Result<i64, VmError> {
+        match self {
+            IntOverflow::Wrap => Ok(i.wrapping_neg()),
+            IntOverflow::Saturate => Ok(i.saturating_neg()),
+            IntOverflow::Trap => i
+                .checked_neg()
+                .ok_or_else(|| VmError::IntegerOverflow(format!("-({})", i))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_overflow_modes() {
+        assert_eq!(
+            IntOverflow::Wrap.apply(BinOp::Add, i64::MAX, 1).unwrap(),
+            i64::MIN
+        );
+        assert_eq!(
+            IntOverflow::Saturate
+                .apply(BinOp::Add, i64::MAX, 1)
+                .unwrap(),
+            i64::MAX
+        );
+        assert_eq!(
+            IntOverflow::Saturate
+                .apply(BinOp::Mul, i64::MIN, 2)
+                .unwrap(),
+            i64::MIN
+        );
+        assert_eq!(
+            IntOverflow::Wrap.apply(BinOp::Mod, i64::MIN, -1).unwrap(),
+            0
+        );
+        assert_eq!(IntOverflow::Trap.apply(BinOp::Sub, 1, 2).unwrap(), -1);
+
+        let err = IntOverflow::Trap
+            .apply(BinOp::Add, i64::MAX, 1)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Integer overflow: 9223372036854775807 + 1");
+        assert!(IntOverflow::Trap.apply(BinOp::Div, i64::MIN, -1).is_err());
+
+        assert_eq!(IntOverflow::Wrap.neg(i64::MIN).unwrap(), i64::MIN);
+        assert_eq!(IntOverflow::Saturate.neg(i64::MIN).unwrap(), i64::MAX);
+        assert!(IntOverflow::Trap.neg(i64::MIN).is_err());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        for mode in [IntOverflow::Wrap, IntOverflow::Saturate, IntOverflow::Trap] {
+            let err = mode.apply(BinOp::Div, 1, 0).unwrap_err();
+            assert_eq!(err.to_string(), "Division by zero: 1 / 0");
+            assert!(mode.apply(BinOp::Mod, 1, 0).is_err());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{cmp::Reverse, io::Write};
+
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+
+use crate::{micro_code, Runtime, MAIN_THREAD_ID};
+
+/// What happens when a runtime error is not caught by any try block of the thread that raised it.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Stop the program with the error, whichever thread raised it.
+    #[default]
+    Abort,
+    /// Kill the thread that raised the error, reporting it on stderr, and keep running the other threads.
+    /// Joining the killed thread gives unit, as for the kill builtin. An error in the main thread still stops the program.
+    KillThread,
+}
+
+/// Which of the threads blocked on a semaphore is woken up when it is posted, or on anything else that wakes up
+/// one waiter at a time, e.g. a condition variable notified with cv_notify_one or a channel a value is sent on.
+/// Schedulers follow it unless they decide themselves, see [`crate::Scheduler::wake_one`].
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WakeupPolicy {
+    /// The thread that has waited the longest, so every waiter is woken up in turn.
+    #[default]
+    Fifo,
+    /// The thread that blocked last, which favors throughput over fairness as its data is likely still cached.
+    Lifo,
+    /// The thread of the highest effective priority, the one that has waited the longest among equals.
+    Priority,
+}
+
+impl WakeupPolicy {
+    /// The index in the blocked queue of the runtime of the waiter to wake up.
+    /// The waiters are indices in the blocked queue, in its order, and are never empty.
+    pub fn pick(self, rt: &Runtime, waiters: &[usize]) -> usize {
+        match self {
+            WakeupPolicy::Fifo => waiters[0],
+            WakeupPolicy::Lifo => waiters[waiters.len() - 1],
+            WakeupPolicy::Priority => waiters
+                .iter()
+                .copied()
+                .max_by_key(|&i| (rt.effective_priority(&rt.blocked_queue[i].0), Reverse(i)))
+                .expect("There should be a waiter to wake up"),
+        }
+    }
+}
+
+/// Handling of the errors no try block catches.
+impl Runtime {
+    /// Handle an error the current thread did not catch as the panic policy says:
+    /// the error is returned to stop the program, or the thread is killed and the error reported.
+    ///
+    /// # Errors
+    ///
+    /// The error itself if the program is to stop, or if killing the thread fails,
+    /// e.g. as no other thread is left to run.
+    pub fn uncaught(&mut self, err: Error, pc: usize) -> Result<()> {
+        let tid = self.current_thread.thread_id;
+        if self.panic_policy == PanicPolicy::Abort || tid == MAIN_THREAD_ID {
+            return Err(err);
+        }
+
+        self.stdout.flush()?;
+        eprintln!("thread {} panicked at pc {}: {}", tid, pc, err);
+        micro_code::kill(self, tid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{ByteCode, Semaphore, Value};
+
+    use crate::{run, RuntimeConfig, Thread, WakeSource};
+
+    use super::*;
+
+    #[test]
+    fn test_panic_policy() -> Result<()> {
+        // let t = spawn { 1 / 0 };
+        // join t;
+        // 2
+        let instrs = vec![
+            ByteCode::SPAWN(4, 0),
+            ByteCode::JOIN,
+            ByteCode::POP,
+            ByteCode::GOTO(8),
+            ByteCode::POP,
+            ByteCode::ldc(1),
+            ByteCode::ldc(0),
+            ByteCode::BINOP(bytecode::BinOp::Div),
+            ByteCode::ldc(2),
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs.clone());
+        assert!(run(&mut rt).is_err());
+
+        let config = RuntimeConfig::default().panic_policy(PanicPolicy::KillThread);
+        let mut rt = Runtime::with_config(instrs, config);
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(2)]);
+
+        // Errors in the main thread still stop the program
+        let config = RuntimeConfig::default().panic_policy(PanicPolicy::KillThread);
+        let instrs = vec![
+            ByteCode::ldc(1),
+            ByteCode::ldc(0),
+            ByteCode::BINOP(bytecode::BinOp::Div),
+            ByteCode::DONE,
+        ];
+        assert!(run(&mut Runtime::with_config(instrs, config)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wakeup_policy() {
+        let mut rt = Runtime::default();
+        let sem = Semaphore::new(0);
+        for (tid, priority) in [(2, 0), (3, 5), (4, 5), (5, 1)] {
+            let mut thread = Thread::new(tid, rt.current_thread.env.clone());
+            thread.priority = priority;
+            rt.blocked_queue
+                .push_back((thread, vec![WakeSource::new(sem.clone())]));
+        }
+        let waiters = [0, 1, 2, 3];
+
+        assert_eq!(WakeupPolicy::Fifo.pick(&rt, &waiters), 0);
+        assert_eq!(WakeupPolicy::Lifo.pick(&rt, &waiters), 3);
+        assert_eq!(WakeupPolicy::Priority.pick(&rt, &waiters), 1);
+        assert_eq!(WakeupPolicy::Priority.pick(&rt, &[0, 3]), 3);
+    }
+}
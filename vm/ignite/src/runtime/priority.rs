@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use bytecode::ThreadID;
+
+use crate::{Runtime, Thread, WakeSource};
+
+/// Priorities of threads, which the default scheduler runs the ready threads by.
+impl Runtime {
+    /// The priority the thread is scheduled with: the highest of its own priority and the effective priorities
+    /// of the threads blocked on the semaphores it holds, such as a mutex or the mutex of a lock block.
+    /// A thread holding a mutex a higher priority thread waits on runs with that priority until it releases it,
+    /// so threads of a priority in between can't keep it from releasing the mutex (priority inversion).
+    ///
+    /// # Arguments
+    ///
+    /// * `thread` - The thread to get the priority of, which need not be in any queue.
+    pub fn effective_priority(&self, thread: &Thread) -> i64 {
+        self.donated_priority(thread, &mut HashSet::new())
+    }
+
+    // Threads deadlocked on each other's semaphores donate to each other, so each thread is only visited once.
+    fn donated_priority(&self, thread: &Thread, visited: &mut HashSet<ThreadID>) -> i64 {
+        if !visited.insert(thread.thread_id) || thread.held_semaphores.is_empty() {
+            return thread.priority;
+        }
+
+        let mut priority = thread.priority;
+        for (waiter, sources) in self.blocked_queue.iter() {
+            let waits_on_held = sources.iter().any(|source| {
+                matches!(source, WakeSource::Semaphore { sem, .. } if thread.held_semaphores.contains(sem))
+            });
+            if waits_on_held {
+                priority = priority.max(self.donated_priority(waiter, visited));
+            }
+        }
+        priority
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytecode::Semaphore;
+
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_take_next_ready() -> Result<()> {
+        let mut rt = Runtime::default();
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+
+        // Without priorities the ready queue is first in, first out.
+        assert_eq!(
+            rt.ready_queue.front().unwrap().thread_id,
+            MAIN_THREAD_ID + 1
+        );
+        rt.ready_queue.back_mut().unwrap().priority = 1;
+        let next = rt.take_next_ready()?.unwrap();
+        assert_eq!(next.thread_id, MAIN_THREAD_ID + 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_effective_priority() -> Result<()> {
+        let mut rt = Runtime::default();
+        let mutex = Semaphore::new(0);
+        let other = Semaphore::new(0);
+
+        let mut holder = rt.current_thread.spawn_child(MAIN_THREAD_ID + 1, 0);
+        holder.held_semaphores.push(mutex.clone());
+        let mut middle = rt.current_thread.spawn_child(MAIN_THREAD_ID + 2, 0);
+        middle.priority = 5;
+        let mut waiter = rt.current_thread.spawn_child(MAIN_THREAD_ID + 3, 0);
+        waiter.priority = 10;
+
+        // The holder inherits the priority of the thread waiting on its mutex, and runs before the middle thread.
+        assert_eq!(rt.effective_priority(&holder), 0);
+        rt.blocked_queue
+            .push_back((waiter.clone(), vec![WakeSource::new(mutex.clone())]));
+        assert_eq!(rt.effective_priority(&holder), 10);
+        rt.ready_queue.push_back(middle);
+        rt.ready_queue.push_back(holder.clone());
+        let next = rt.take_next_ready()?.unwrap();
+        assert_eq!(next.thread_id, MAIN_THREAD_ID + 1);
+
+        // The donation passes along a chain of threads holding what the next one waits on.
+        rt.blocked_queue.clear();
+        let mut blocked_holder = rt.current_thread.spawn_child(MAIN_THREAD_ID + 4, 0);
+        blocked_holder.held_semaphores.push(other.clone());
+        rt.blocked_queue
+            .push_back((blocked_holder, vec![WakeSource::new(mutex.clone())]));
+        rt.blocked_queue
+            .push_back((waiter, vec![WakeSource::new(other)]));
+        assert_eq!(rt.effective_priority(&holder), 10);
+
+        // Once the mutex is released, the holder is back to its own priority.
+        holder.held_semaphores.clear();
+        assert_eq!(rt.effective_priority(&holder), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::Result;
+use bytecode::{Address, ByteCode, Closure, Environment, FnType, Symbol, Value};
+use types::type_checker::Env;
+
+use crate::{Runtime, VmError, MAIN_THREAD_ID};
+
+/// Hot reloading of the functions of a running program.
+impl Runtime {
+    /// Load a new version of the program, compiled from its changed source, into the runtime while it runs,
+    /// and rebind the functions declared at the top level of the program to their code in the new version.
+    /// The new code is appended to the instructions with its addresses relocated, so the PCs of threads
+    /// running the old code stay valid, and calls made after the reload run the new code.
+    /// Returns the names of the functions that were rebound.
+    /// Only the names of the bindings are checked, see [`Runtime::hot_reload_typed`] to check their types too.
+    ///
+    /// # Errors
+    ///
+    /// If the top-level bindings of the new version are not those of the running program,
+    /// since its code would address the slots of the top-level frame differently. The program has to be restarted then.
+    pub fn hot_reload(&mut self, mut instrs: Vec<ByteCode>) -> Result<Vec<Symbol>> {
+        let syms = match instrs.first() {
+            Some(ByteCode::ENTERSCOPE(syms)) => syms.clone(),
+            _ => vec![],
+        };
+
+        let frame = self.top_level_frame();
+        let running_syms = frame
+            .as_ref()
+            .map(|frame| frame.borrow().syms.clone())
+            .unwrap_or_default();
+
+        if syms != running_syms {
+            let msg = "the top-level bindings of the program changed".to_string();
+            return Err(VmError::HotReload(msg).into());
+        }
+
+        let offset = self.instrs.len();
+        let fns = top_level_fns(&instrs, &syms);
+        instrs.iter_mut().for_each(|instr| instr.relocate(offset));
+        self.instrs.extend(instrs);
+
+        let Some(frame) = frame else {
+            return Ok(vec![]);
+        };
+
+        let mut frame = frame.borrow_mut();
+        let mut reloaded = vec![];
+        for (idx, addr, prms) in fns {
+            let Value::Closure(closure) = &frame.slots[idx] else {
+                continue;
+            };
+
+            if closure.fn_type != FnType::User {
+                continue;
+            }
+
+            let closure = Closure {
+                addr: addr + offset,
+                prms,
+                ..Closure::clone(closure)
+            };
+            frame.slots[idx] = Value::Closure(Rc::new(closure));
+            reloaded.push(syms[idx]);
+        }
+
+        Ok(reloaded)
+    }
+
+    /// Hot reload the new version of the program like [`Runtime::hot_reload`], once the types of its top-level bindings,
+    /// functions included, are checked to be those of the running program. The bytecode has no types, so a function
+    /// whose parameters change type but not number, e.g from `fn f(x: int)` to `fn f(x: str)`, would otherwise be
+    /// rebound and called by the running code with values of the old types.
+    ///
+    /// # Arguments
+    ///
+    /// * `instrs` - The instructions of the new version.
+    ///
+    /// * `running` - The types of the top-level bindings of the running program, as the type checker gives them.
+    ///
+    /// * `types` - The types of the top-level bindings of the new version.
+    ///
+    /// # Errors
+    ///
+    /// If a top-level binding of the new version has another type than in the running program, or as [`Runtime::hot_reload`].
+    pub fn hot_reload_typed(
+        &mut self,
+        instrs: Vec<ByteCode>,
+        running: &Env,
+        types: &Env,
+    ) -> Result<Vec<Symbol>> {
+        let mut names: Vec<&String> = running.keys().chain(types.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let msg = match (running.get(name), types.get(name)) {
+                (Some(old), Some(new)) if old == new => continue,
+                (Some(old), Some(new)) => {
+                    format!("the type of '{}' changed from {} to {}", name, old, new)
+                }
+                _ => "the top-level bindings of the program changed".to_string(),
+            };
+            return Err(VmError::HotReload(msg).into());
+        }
+
+        self.hot_reload(instrs)
+    }
+
+    /// Rebind the function bound to the name in the current frame of the main thread in the frames above it too,
+    /// where the name is bound to a function, e.g. so that the functions of earlier REPL inputs call a redefinition.
+    /// The global frame is left alone, so builtins can't be replaced.
+    /// Returns the number of bindings that were replaced.
+    pub fn rebind_fn(&mut self, sym: Symbol) -> usize {
+        let Some(env) = self
+            .find_thread(MAIN_THREAD_ID)
+            .and_then(|thread| thread.env.upgrade())
+        else {
+            return 0;
+        };
+
+        let val = {
+            let frame = env.borrow();
+            match frame.syms.iter().position(|s| *s == sym) {
+                Some(idx) => frame.slots[idx].clone(),
+                None => return 0,
+            }
+        };
+
+        if !matches!(val, Value::Closure(_)) {
+            return 0;
+        }
+
+        let mut rebound = 0;
+        let mut next = env.borrow().parent.as_ref().and_then(|p| p.upgrade());
+        while let Some(frame) = next {
+            let mut frame_ref = frame.borrow_mut();
+            next = frame_ref.parent.as_ref().and_then(|p| p.upgrade());
+            if next.is_none() {
+                break;
+            }
+
+            let idx = frame_ref.syms.iter().position(|s| *s == sym);
+            if let Some(idx) = idx {
+                if matches!(frame_ref.slots[idx], Value::Closure(_)) {
+                    frame_ref.slots[idx] = val.clone();
+                    rebound += 1;
+                }
+            }
+        }
+
+        rebound
+    }
+
+    /// The frame of the top-level bindings of the program, the one below the global frame in the environment of the main thread.
+    /// Returns `None` if the program has no top-level bindings.
+    fn top_level_frame(&self) -> Option<Rc<RefCell<Environment>>> {
+        let mut frame = self.find_thread(MAIN_THREAD_ID)?.env.upgrade()?;
+
+        loop {
+            let parent = frame.borrow().parent.as_ref()?.upgrade()?;
+            if parent.borrow().parent.is_none() {
+                return Some(frame);
+            }
+            frame = parent;
+        }
+    }
+}
+
+/// The functions declared at the top level of the program, with the slot of the top-level frame each is bound to,
+/// and the address and parameters of its code.
+/// A function declaration is compiled to LDF, a GOTO over its body, and the assignment of the closure to its slot.
+fn top_level_fns(instrs: &[ByteCode], syms: &[Symbol]) -> Vec<(usize, Address, Vec<Symbol>)> {
+    let mut fns = vec![];
+    let mut depth = 0;
+    let mut pc = 0;
+
+    while let Some(instr) = instrs.get(pc) {
+        match instr {
+            ByteCode::ENTERSCOPE(_) | ByteCode::TRY(_) | ByteCode::LOCK | ByteCode::READLOCK => {
+                depth += 1
+            }
+            ByteCode::EXITSCOPE => depth -= 1,
+            ByteCode::LDF(addr, sym, prms) => {
+                if let Some(ByteCode::GOTO(end)) = instrs.get(pc + 1) {
+                    if let Some(ByteCode::ASSIGNSLOT(0, idx)) = instrs.get(*end) {
+                        if depth == 1 && syms.get(*idx) == Some(sym) {
+                            fns.push((*idx, *addr, prms.clone()));
+                        }
+                    }
+
+                    // The body is not top-level code
+                    pc = *end;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+
+        pc += 1;
+    }
+
+    fns
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use compiler::compiler::compile_from_string;
+    use parser::Parser;
+    use types::type_checker::TypeChecker;
+
+    use crate::{run, step};
+
+    use super::*;
+
+    #[test]
+    fn test_relocate() {
+        let mut instrs = vec![
+            ByteCode::GOTO(1),
+            ByteCode::ldf(2, "f", vec!["x"]),
+            ByteCode::SELECT(vec![3, 4]),
+            ByteCode::ASSIGNSLOT(0, 1),
+        ];
+        instrs.iter_mut().for_each(|instr| instr.relocate(10));
+
+        assert_eq!(
+            instrs,
+            vec![
+                ByteCode::GOTO(11),
+                ByteCode::ldf(12, "f", vec!["x"]),
+                ByteCode::SELECT(vec![13, 14]),
+                ByteCode::ASSIGNSLOT(0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hot_reload() -> Result<()> {
+        let v1 = r"
+            fn f(x: int) -> int { x + 1 }
+            fn g() -> int { f(10) }
+            let a = g();
+            yield;
+            let b = g();
+            a * 100 + b
+        ";
+        let v2 = r"
+            fn f(y: int) -> int { y + 2 }
+            fn g() -> int { f(10) }
+            let a = 0;
+            let b = 0;
+            0
+        ";
+
+        let mut rt = Runtime::new(compile_from_string(v1, true)?);
+        // Run until a has been assigned with the old code
+        while rt.instrs[rt.current_thread.pc] != ByteCode::YIELD {
+            step(&mut rt)?;
+        }
+
+        let reloaded = rt.hot_reload(compile_from_string(v2, true)?)?;
+        assert_eq!(reloaded, vec![Symbol::from("f"), Symbol::from("g")]);
+
+        // The top-level code keeps running the old version, and calls made from now on run the new code
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(1112)]);
+
+        // Declaring another top-level binding needs a restart
+        let v3 = "fn f(x: int) -> int { x } let c = 1; c";
+        let err = rt.hot_reload(compile_from_string(v3, true)?).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::HotReload(_))
+        ));
+
+        Ok(())
+    }
+
+    fn top_level_types(src: &str) -> Result<Env> {
+        let program = Parser::new_from_string(src).parse()?;
+        let mut envs = vec![];
+        TypeChecker::new(&program).type_check_in(&mut envs)?;
+        Ok(envs.pop().unwrap_or_default())
+    }
+
+    #[test]
+    fn test_hot_reload_typed() -> Result<()> {
+        let v1 = r"
+            fn f(x: int) -> int { x + 1 }
+            yield;
+            f(1)
+        ";
+        let v2 = r"
+            fn f(x: int) -> int { x + 2 }
+            yield;
+            f(1)
+        ";
+
+        let mut rt = Runtime::new(compile_from_string(v1, true)?);
+        while rt.instrs[rt.current_thread.pc] != ByteCode::YIELD {
+            step(&mut rt)?;
+        }
+
+        // A function whose body changed is rebound
+        let running = top_level_types(v1)?;
+        let reloaded = rt.hot_reload_typed(
+            compile_from_string(v2, true)?,
+            &running,
+            &top_level_types(v2)?,
+        )?;
+        assert_eq!(reloaded, vec![Symbol::from("f")]);
+
+        // One whose parameter changed type needs a restart, though the bytecode binds the same names
+        let v3 = r#"
+            fn f(x: str) -> int { string_len(x) }
+            yield;
+            f("a")
+        "#;
+        let instrs = compile_from_string(v3, true)?;
+        let err = rt
+            .hot_reload_typed(instrs, &top_level_types(v2)?, &top_level_types(v3)?)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't hot reload: the type of 'f' changed from fn(int) -> int to fn(str) -> int"
+        );
+
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(3)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, Write},
+};
+
+use bytecode::ThreadID;
+
+use crate::{Runtime, Thread, VmError};
+
+/// The first line of a switch log file.
+const SWITCH_LOG_HEADER: &str = "rustscript switch log v1";
+
+/// A context switch: the thread taken from the ready queue to run,
+/// after the program had executed the number of instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Switch {
+    pub instr: u64,
+    pub thread_id: ThreadID,
+}
+
+/// The context switches of a run in the order they happened, recorded so that a replay can force the same interleaving.
+/// Written as text, a header line followed by a line `<instr> <thread id>` for each switch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SwitchLog {
+    pub switches: Vec<Switch>,
+}
+
+impl SwitchLog {
+    pub fn write(&self, w: &mut impl Write) -> std::io::Result<()> {
+        writeln!(w, "{}", SWITCH_LOG_HEADER)?;
+        for switch in self.switches.iter() {
+            writeln!(w, "{} {}", switch.instr, switch.thread_id)?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// If the log can't be read or is not a switch log.
+    pub fn read(r: impl BufRead) -> Result<SwitchLog, VmError> {
+        let mut lines = r.lines();
+        let invalid = |msg: String| VmError::InvalidSwitchLog(msg);
+
+        match lines.next().transpose()? {
+            Some(header) if header == SWITCH_LOG_HEADER => (),
+            _ => return Err(invalid("missing header".to_string())),
+        }
+
+        let mut switches = vec![];
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let parsed = line.split_once(' ').and_then(|(instr, thread_id)| {
+                Some(Switch {
+                    instr: instr.parse().ok()?,
+                    thread_id: thread_id.parse().ok()?,
+                })
+            });
+            let switch = parsed.ok_or_else(|| invalid(format!("line {}: {}", i + 2, line)))?;
+            switches.push(switch);
+        }
+
+        Ok(SwitchLog { switches })
+    }
+}
+
+/// Recording and replaying the context switches of a run.
+impl Runtime {
+    /// Record every context switch from now on, see [`Runtime::switch_log`].
+    pub fn record_switches(&mut self) {
+        self.recording = Some(vec![]);
+    }
+
+    /// The context switches recorded so far, if recording.
+    pub fn switch_log(&self) -> Option<SwitchLog> {
+        self.recording.as_ref().map(|switches| SwitchLog {
+            switches: switches.clone(),
+        })
+    }
+
+    /// Force the context switches of the log: threads are preempted after the instructions they were in the recorded run,
+    /// and the thread that ran next then runs next now, whatever the scheduler and time quantum.
+    /// Once the log runs out, the program continues as scheduled.
+    /// Timeouts and futures of the host depend on the time they take, and random numbers on the seed,
+    /// so programs using them replay the same only if they are also run deterministically.
+    pub fn replay_switches(&mut self, log: SwitchLog) {
+        self.replaying = Some(VecDeque::from(log.switches));
+    }
+
+    /// If the log being replayed has a context switch after the instructions executed so far.
+    #[inline]
+    pub fn replay_switch_due(&self) -> Option<bool> {
+        let switch = self.replaying.as_ref()?.front()?;
+        Some(switch.instr == self.instr_count)
+    }
+
+    /// Add the context switch to the log, if recording.
+    pub(crate) fn record_switch(&mut self, thread: &Thread) {
+        if let Some(switches) = self.recording.as_mut() {
+            switches.push(Switch {
+                instr: self.instr_count,
+                thread_id: thread.thread_id,
+            });
+        }
+    }
+
+    /// The index in the ready queue of the thread to run next in the log being replayed, if any switches are left.
+    ///
+    /// # Errors
+    ///
+    /// If the run has diverged from the recorded one: the switch is not due yet, or its thread is not ready.
+    pub(crate) fn replayed_index(&mut self) -> Result<Option<usize>, VmError> {
+        let Some(switch) = self.replaying.as_mut().and_then(|log| log.pop_front()) else {
+            return Ok(None);
+        };
+
+        let i = self
+            .ready_queue
+            .iter()
+            .position(|thread| thread.thread_id == switch.thread_id);
+
+        match i {
+            Some(i) if switch.instr == self.instr_count => Ok(Some(i)),
+            _ => Err(VmError::ReplayDiverged {
+                instr: self.instr_count,
+                thread_id: switch.thread_id,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use anyhow::Result;
+
+    use crate::{run, VirtualClock};
+
+    use super::*;
+
+    #[derive(Default, Clone)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const RACE: &str = r"
+        let n = 0;
+        fn worker(id: int) {
+            let i = 0;
+            loop i < 100 {
+                let m = n;
+                print(id);
+                n = m + 1;
+                i = i + 1;
+            }
+        }
+        let a = spawn worker(1);
+        let b = spawn worker(2);
+        join a;
+        join b;
+        n
+    ";
+
+    #[test]
+    fn test_switch_log_io() -> Result<()> {
+        let log = SwitchLog {
+            switches: vec![
+                Switch {
+                    instr: 3,
+                    thread_id: 1,
+                },
+                Switch {
+                    instr: 10,
+                    thread_id: 2,
+                },
+            ],
+        };
+        let mut buf = vec![];
+        log.write(&mut buf)?;
+        assert_eq!(SwitchLog::read(buf.as_slice())?, log);
+
+        let err = SwitchLog::read("1 2\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, VmError::InvalidSwitchLog(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay() -> Result<()> {
+        let instrs = compiler::compiler::compile_from_string(RACE, true)?;
+
+        // A virtual clock that ticks fast preempts the threads often
+        let recorded_out = SharedBuf::default();
+        let mut rt = Runtime::new(instrs.clone());
+        rt.set_stdout(recorded_out.clone());
+        rt.set_clock(VirtualClock::new(Duration::from_micros(7)));
+        rt.set_time_quantum(Duration::from_micros(500));
+        rt.record_switches();
+        run(&mut rt)?;
+        let recorded = rt.current_thread.operand_stack.clone();
+        let log = rt.switch_log().unwrap();
+        assert!(log.switches.len() > 3);
+
+        // Without the log, the threads would not be preempted before they finish
+        let replayed_out = SharedBuf::default();
+        let mut rt = Runtime::new(instrs.clone());
+        rt.set_stdout(replayed_out.clone());
+        rt.replay_switches(log.clone());
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, recorded);
+        assert_eq!(*replayed_out.0.borrow(), *recorded_out.0.borrow());
+
+        // A log of another run diverges
+        let mut diverged = log;
+        diverged.switches[1].thread_id = 42;
+        let mut rt = Runtime::new(instrs);
+        rt.set_stdout(std::io::sink());
+        rt.replay_switches(diverged);
+        let err = run(&mut rt).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::ReplayDiverged { thread_id: 42, .. })
+        ));
+
+        Ok(())
+    }
+}
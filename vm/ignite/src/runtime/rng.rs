@@ -0,0 +1,64 @@
+use std::cell::Cell;
+
+/// The source of randomness of the runtime, used by `random_int`.
+pub trait Rng {
+    /// The next random number, uniformly distributed over all u64s.
+    fn next_u64(&self) -> u64;
+}
+
+/// A pseudorandom generator (SplitMix64) that gives the same numbers for the same seed.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: Cell<u64>,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            state: Cell::new(seed),
+        }
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// The random number generator of a new runtime: seeded from the system time, except on WebAssembly,
+/// where there is none without the host and the seed is fixed.
+pub fn default_rng() -> Box<dyn Rng> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+
+    #[cfg(target_arch = "wasm32")]
+    let seed = 0;
+
+    Box::new(SeededRng::new(seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng() {
+        let a = SeededRng::new(42);
+        let b = SeededRng::new(42);
+        let xs: Vec<u64> = (0..4).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..4).map(|_| b.next_u64()).collect();
+
+        assert_eq!(xs, ys);
+        assert_ne!(xs[0], xs[1]);
+        assert_ne!(xs[0], SeededRng::new(43).next_u64());
+    }
+}
@@ -1,9 +1,9 @@
-use std::time::Instant;
+use std::{cell::RefCell, io::Write, rc::Weak};
 
 use anyhow::Result;
-use bytecode::ByteCode;
+use bytecode::{ByteCode, Environment, SelectOp};
 
-use crate::{micro_code, Runtime, VmError};
+use crate::{micro_code, Runtime, Thread, ThreadState, VmError, GC_INSTR_INTERVAL, MAIN_THREAD_ID};
 
 /// Runtime methods at runtime.
 impl Runtime {
@@ -27,23 +27,105 @@ impl Runtime {
         self.current_thread.pc += 1;
         Ok(instr)
     }
+
+    /// Charge the instruction about to be executed against the instruction budgets.
+    ///
+    /// # Errors
+    ///
+    /// If the program or the current thread has used up its budget, with the PC of the instruction
+    /// that would have been executed.
+    #[inline]
+    pub fn charge_instr(&mut self) -> Result<()> {
+        self.instr_count += 1;
+        self.current_thread.instr_count += 1;
+
+        let over_budget = self.instr_budget.is_some_and(|b| self.instr_count > b)
+            || self
+                .thread_instr_budget
+                .is_some_and(|b| self.current_thread.instr_count > b);
+
+        if over_budget {
+            return Err(VmError::BudgetExceeded(self.current_thread.pc).into());
+        }
+
+        Ok(())
+    }
+
+    /// Check that another frame can be pushed onto the runtime stack of the current thread.
+    ///
+    /// # Errors
+    ///
+    /// If the runtime stack is already at the maximum call depth, with the PC of the instruction
+    /// that entered the call or scope.
+    #[inline]
+    pub fn check_call_depth(&self) -> Result<()> {
+        let depth = self.current_thread.runtime_stack.len();
+
+        if depth >= self.max_call_depth {
+            return Err(VmError::StackOverflow {
+                pc: self.current_thread.pc.saturating_sub(1),
+                depth,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Check that the operand stack of the current thread is within its maximum size.
+    ///
+    /// # Errors
+    ///
+    /// If the operand stack has grown past the maximum size, with the PC of the instruction that
+    /// was executed last.
+    #[inline]
+    pub fn check_operand_stack(&self, pc: usize) -> Result<()> {
+        let size = self.current_thread.operand_stack.len();
+
+        if size > self.max_operand_stack {
+            return Err(VmError::OperandStackOverflow { pc, size }.into());
+        }
+
+        Ok(())
+    }
+
     /// Check if the time quantum has expired.
-    /// The time quantum is the maximum amount of time a thread can run before it is preempted.
+    /// The time quantum is the maximum amount of time a thread can run before it is preempted,
+    /// or the number of instructions if the runtime has an instruction quantum.
+    /// While a log is replayed, threads are preempted where they were in the recorded run instead.
     #[inline]
     pub fn time_quantum_expired(&self) -> bool {
-        self.time.elapsed() >= self.time_quantum
+        if let Some(due) = self.replay_switch_due() {
+            return due;
+        }
+
+        match self.instr_quantum {
+            Some(quantum) => self.instr_count - self.quantum_start >= quantum,
+            None => self.now().saturating_sub(self.time) >= self.time_quantum,
+        }
     }
 
+    /// Check if the garbage collector is due, every garbage collection interval,
+    /// or every `GC_INSTR_INTERVAL` instructions if the runtime has an instruction quantum.
     #[inline]
     pub fn should_garbage_collect(&self) -> bool {
-        self.gc_timer.elapsed() >= self.gc_interval
+        match self.instr_quantum {
+            Some(_) => self.instr_count - self.gc_instr >= GC_INSTR_INTERVAL,
+            None => self.now().saturating_sub(self.gc_timer) >= self.gc_interval,
+        }
     }
 
+    /// Run the garbage collector, then check the memory the program still holds is within its limit.
+    ///
+    /// # Errors
+    ///
+    /// If the program holds more memory than its limit.
     #[inline]
-    pub fn garbage_collect(mut self) -> Self {
-        self = self.mark_and_weep();
-        self.gc_timer = Instant::now();
-        self
+    pub fn garbage_collect(&mut self) -> Result<()> {
+        self.mark_and_weep();
+        self.gc_timer = self.now();
+        self.gc_instr = self.instr_count;
+        self.check_memory()
     }
 
     /// The program is done if the current thread is the main thread and the current thread is done.
@@ -52,6 +134,87 @@ impl Runtime {
         self.done
     }
 
+    /// Continue the main thread from the instruction at `pc` once the program is done, in the environment
+    /// the program left it in, e.g. to run the instructions a REPL appends for each input.
+    /// The result of the previous run is discarded from the operand stack.
+    pub fn resume_at(&mut self, pc: usize) {
+        self.current_thread.pc = pc;
+        self.current_thread.operand_stack.clear();
+        self.set_thread_state(MAIN_THREAD_ID, ThreadState::Running);
+        self.time = self.now();
+        self.quantum_start = self.instr_count;
+        self.done = false;
+        self.exit_code = None;
+    }
+
+    /// Abandon the run after an error, so that the runtime can be resumed:
+    /// the main thread becomes the current thread again, with its stacks cleared and its environment set to `env`.
+    /// If the error occurred in another thread, that thread is killed.
+    ///
+    /// # Errors
+    ///
+    /// If the semaphores held by the killed thread cannot be released.
+    pub fn recover(&mut self, env: Weak<RefCell<Environment>>) -> Result<()> {
+        let failed = self.current_thread.thread_id;
+
+        if failed != MAIN_THREAD_ID {
+            let ready_idx = self
+                .ready_queue
+                .iter()
+                .position(|t| t.thread_id == MAIN_THREAD_ID);
+            let blocked_idx = self
+                .blocked_queue
+                .iter()
+                .position(|(t, _)| t.thread_id == MAIN_THREAD_ID);
+
+            let main = if let Some(i) = ready_idx {
+                self.ready_queue.remove(i)
+            } else if let Some(i) = blocked_idx {
+                self.blocked_queue.remove(i).map(|(t, _)| t)
+            } else {
+                None
+            };
+
+            let main = main.unwrap_or_else(|| Thread::new(MAIN_THREAD_ID, Weak::clone(&env)));
+            let failed_thread = std::mem::replace(&mut self.current_thread, main);
+
+            // Killing a thread in the ready queue finishes it without scheduling another one
+            self.ready_queue.push_front(failed_thread);
+            micro_code::kill(self, failed)?;
+        }
+
+        self.current_thread.env = env;
+        self.current_thread.operand_stack.clear();
+        self.current_thread.runtime_stack.clear();
+        self.set_thread_state(MAIN_THREAD_ID, ThreadState::Done);
+        self.done = true;
+
+        Ok(())
+    }
+
+    /// Log the instruction about to be executed to the trace sink, if tracing is on.
+    ///
+    /// # Errors
+    ///
+    /// If the sink cannot be written to.
+    #[inline]
+    pub fn trace_instr(&mut self, pc: usize, instr: &ByteCode) -> Result<()> {
+        let Some(sink) = self.trace_sink.as_mut() else {
+            return Ok(());
+        };
+
+        writeln!(
+            sink,
+            "thread={} pc={} op={:?} stack={}",
+            self.current_thread.thread_id,
+            pc,
+            instr,
+            self.current_thread.operand_stack.len()
+        )?;
+
+        Ok(())
+    }
+
     pub fn debug_print(&self) {
         let thread_id = self.current_thread.thread_id;
         let pc = self.current_thread.pc;
@@ -71,41 +234,78 @@ impl Runtime {
 ///
 /// # Arguments
 ///
-/// * `rt` - The runtime to run.
+/// * `rt` - The runtime to run. Once the program is done, the result is left on its operand stack.
+///
+/// # Errors
+///
+/// If an error occurs during execution, with a `RuntimeErrorContext` locating the failing instruction.
+#[inline]
+pub fn run(rt: &mut Runtime) -> Result<()> {
+    while !rt.is_done() {
+        step(rt)?;
+    }
+
+    Ok(())
+}
+
+/// Perform one iteration of the run loop: stop if the program has been interrupted,
+/// run the garbage collector and the scheduler if they are due,
+/// then fetch and execute the next instruction of the current thread.
 ///
-/// # Returns
+/// # Arguments
 ///
-/// The runtime after the program has finished executing.
+/// * `rt` - The runtime to step.
 ///
 /// # Errors
 ///
-/// If an error occurs during execution.
+/// If an error occurs during execution, with a `RuntimeErrorContext` locating the failing instruction.
 #[inline]
-pub fn run(mut rt: Runtime) -> Result<Runtime> {
-    loop {
-        if rt.is_done() {
-            break;
-        }
+pub fn step(rt: &mut Runtime) -> Result<()> {
+    let thread_id = rt.current_thread.thread_id;
+    let pc = rt.current_thread.pc;
 
-        if rt.should_garbage_collect() {
-            rt = rt.garbage_collect();
-        }
+    tick(rt).map_err(|err| err.context(rt.error_context(thread_id, pc)))
+}
 
-        if rt.time_quantum_expired() {
-            rt = micro_code::yield_(rt)?;
-            continue;
-        }
+#[inline]
+fn tick(rt: &mut Runtime) -> Result<()> {
+    // Interruption is not a runtime error of the program, so try blocks don't catch it
+    if rt.interrupted() {
+        rt.stdout.flush()?;
+        return Err(VmError::Interrupted.into());
+    }
 
-        if rt.debug {
-            rt.debug_print();
-        }
+    if rt.should_garbage_collect() {
+        rt.garbage_collect()?;
+    }
+
+    if rt.timer_expired() {
+        rt.wake_expired_timers();
+    }
+
+    if rt.awaiting_host() {
+        rt.poll_host_futures()?;
+    }
 
-        let instr = rt.fetch_instr()?;
+    if rt.time_quantum_expired() {
+        return micro_code::yield_(rt);
+    }
 
-        rt = execute(rt, instr)?;
+    if rt.debug {
+        rt.debug_print();
     }
 
-    Ok(rt)
+    rt.charge_instr()?;
+    let pc = rt.current_thread.pc;
+    let instr = rt.fetch_instr()?;
+
+    rt.cover(pc);
+    rt.trace_instr(pc, &instr)?;
+    // Runtime errors unwind to the innermost try block of the thread, if any
+    if let Err(err) = execute(rt, instr) {
+        return micro_code::catch(rt, err).or_else(|err| rt.uncaught(err, pc));
+    }
+    rt.check_operand_stack(pc)
 }
 
 /// Execute a single instruction, mutating the runtime.
@@ -116,21 +316,19 @@ pub fn run(mut rt: Runtime) -> Result<Runtime> {
 ///
 /// * `instr` - The instruction to execute.
 ///
-/// # Returns
-///
-/// The runtime after the instruction has been executed.
-///
 /// # Errors
 ///
 /// If an error occurs during execution.
 #[inline]
-pub fn execute(rt: Runtime, instr: ByteCode) -> Result<Runtime> {
+pub fn execute(rt: &mut Runtime, instr: ByteCode) -> Result<()> {
     match instr {
         ByteCode::DONE => micro_code::done(rt),
         ByteCode::ASSIGN(sym) => micro_code::assign(rt, sym),
         ByteCode::LD(sym) => micro_code::ld(rt, sym),
         ByteCode::LDC(val) => micro_code::ldc(rt, val),
-        ByteCode::LDF(addr, prms) => micro_code::ldf(rt, addr, prms),
+        ByteCode::LDSLOT(depth, idx) => micro_code::ld_slot(rt, depth, idx),
+        ByteCode::ASSIGNSLOT(depth, idx) => micro_code::assign_slot(rt, depth, idx),
+        ByteCode::LDF(addr, sym, prms) => micro_code::ldf(rt, addr, sym, prms),
         ByteCode::POP => micro_code::pop(rt),
         ByteCode::UNOP(op) => micro_code::unop(rt, op),
         ByteCode::BINOP(op) => micro_code::binop(rt, op),
@@ -140,20 +338,66 @@ pub fn execute(rt: Runtime, instr: ByteCode) -> Result<Runtime> {
         ByteCode::ENTERSCOPE(syms) => micro_code::enter_scope(rt, syms),
         ByteCode::EXITSCOPE => micro_code::exit_scope(rt),
         ByteCode::CALL(arity) => micro_code::call(rt, arity),
-        ByteCode::SPAWN(addr) => micro_code::spawn(rt, addr),
+        ByteCode::SPAWN(addr, n) => micro_code::spawn(rt, addr, n),
         ByteCode::JOIN => micro_code::join(rt),
+        ByteCode::SPAWNSCOPE => micro_code::spawn_scope(rt),
+        ByteCode::JOINSCOPE => micro_code::join_scope(rt),
         ByteCode::YIELD => micro_code::yield_(rt),
         ByteCode::SEMCREATE => micro_code::sem_create(rt),
         ByteCode::WAIT => micro_code::wait(rt),
         ByteCode::POST => micro_code::post(rt),
+        ByteCode::LOCK => micro_code::lock(rt),
+        ByteCode::READLOCK => micro_code::read_lock(rt),
+        ByteCode::SELECT(addrs) => {
+            let arms = addrs.into_iter().map(|addr| (SelectOp::Wait, addr));
+            micro_code::select(rt, arms.collect())
+        }
+        ByteCode::TRY(addr) => micro_code::try_(rt, addr),
+        ByteCode::STRUCT(name, fields) => micro_code::struct_(rt, name, fields),
+        ByteCode::NEWSTRUCT(fields) => micro_code::new_struct(rt, fields),
+        ByteCode::LDFIELD(field) => micro_code::ld_field(rt, field),
+        ByteCode::SETFIELD(field) => micro_code::set_field(rt, field),
+        ByteCode::LDMETHOD(method) => micro_code::ld_method(rt, method),
+        ByteCode::SETMETHOD(method) => micro_code::set_method(rt, method),
+        ByteCode::NEWVARIANT(ty, variant, holds_value) => {
+            micro_code::new_variant(rt, ty, variant, holds_value)
+        }
+        ByteCode::TESTVARIANT(variant) => micro_code::test_variant(rt, variant),
+        ByteCode::LDPAYLOAD => micro_code::ld_payload(rt),
+        ByteCode::NEWARRAY(len) => micro_code::new_array(rt, len),
+        ByteCode::APPEND => micro_code::append(rt),
+        ByteCode::LDELEM => micro_code::ld_elem(rt),
+        ByteCode::SLICE => micro_code::slice(rt),
+        ByteCode::CHECKLEN(n, at_least) => micro_code::check_len(rt, n, at_least),
+        ByteCode::ITER => micro_code::iter(rt),
+        ByteCode::ITERRANGE => micro_code::iter_range(rt),
+        ByteCode::NEXT(pc) => micro_code::next(rt, pc),
+        ByteCode::GENERATOR => micro_code::generator(rt),
+        ByteCode::SUSPEND => micro_code::suspend(rt),
+        ByteCode::LDCBINOP(val, op) => micro_code::ldc_binop(rt, val, op),
+        ByteCode::LDSLOTSBINOP(lhs, rhs, op) => micro_code::ld_slots_binop(rt, lhs, rhs, op),
+        ByteCode::JOFORPOP(pc) => micro_code::jof_or_pop(rt, pc),
+        ByteCode::BINOPR(dst, lhs, rhs, op) => micro_code::binop_r(rt, dst, lhs, rhs, op),
+        ByteCode::MOVR(dst, src) => micro_code::mov_r(rt, dst, src),
+        ByteCode::SELECTARMS(arms) => micro_code::select(rt, arms),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
-
-    use crate::MAIN_THREAD_ID;
+    use std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        Rng, RuntimeConfig, RuntimeErrorContext, SeededRng, TraceFrame, VirtualClock,
+        MAIN_THREAD_ID,
+    };
 
     use super::*;
     use anyhow::{Ok, Result};
@@ -168,35 +412,35 @@ mod tests {
             ByteCode::POP,
             ByteCode::DONE,
         ];
-        let rt = Runtime::new(instrs);
-        let rt = run(rt).unwrap();
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.pc, 5);
 
-        let rt = Runtime::new(vec![
+        let mut rt = Runtime::new(vec![
             ByteCode::ldc(false),
             ByteCode::JOF(3),
             ByteCode::POP, // This will panic since there is no value on the stack
             ByteCode::DONE,
         ]);
-        let rt = run(rt).unwrap();
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.pc, 4);
 
-        let rt = Runtime::new(vec![
+        let mut rt = Runtime::new(vec![
             ByteCode::ldc(true),
             ByteCode::JOF(3), // jump to pop instruction
             ByteCode::DONE,
             ByteCode::POP, // This will panic since there is no value on the stack
             ByteCode::DONE,
         ]);
-        let rt = run(rt).unwrap();
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.pc, 3);
 
-        let rt = Runtime::new(vec![
+        let mut rt = Runtime::new(vec![
             ByteCode::GOTO(2),
             ByteCode::POP, // This will panic since there is no value on the stack
             ByteCode::DONE,
         ]);
-        let rt = run(rt).unwrap();
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.pc, 3);
     }
 
@@ -209,8 +453,8 @@ mod tests {
             ByteCode::BINOP(BinOp::Add),
             ByteCode::DONE,
         ];
-        let rt = Runtime::new(instrs);
-        let rt = run(rt).unwrap();
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(84)]);
 
         // -(42 - 123)
@@ -221,8 +465,8 @@ mod tests {
             ByteCode::UNOP(UnOp::Neg),
             ByteCode::DONE,
         ];
-        let rt = Runtime::new(instrs);
-        let rt = run(rt).unwrap();
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(81)]);
 
         // (2 * 3) > 9
@@ -234,8 +478,8 @@ mod tests {
             ByteCode::BINOP(BinOp::Gt),
             ByteCode::DONE,
         ];
-        let rt = Runtime::new(instrs);
-        let rt = run(rt).unwrap();
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt).unwrap();
         assert_eq!(rt.current_thread.operand_stack, vec![Value::Bool(false)]);
     }
 
@@ -251,7 +495,7 @@ mod tests {
             ByteCode::DONE,
         ];
 
-        let rt = Runtime::new(instrs);
+        let mut rt = Runtime::new(instrs);
         rt.current_thread
             .env
             .upgrade()
@@ -265,23 +509,13 @@ mod tests {
             .borrow_mut()
             .set("y", Value::Unitialized);
 
-        let rt = run(rt).unwrap();
+        run(&mut rt).unwrap();
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"x".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("x")?,
             Value::Int(44)
         );
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"y".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("y")?,
             Value::Int(43)
         );
 
@@ -296,7 +530,7 @@ mod tests {
         // simple(42)
         let instrs = vec![
             ByteCode::enterscope(vec!["simple"]),
-            ByteCode::ldf(3, vec!["n"]),
+            ByteCode::ldf(3, "simple", vec!["n"]),
             ByteCode::GOTO(5), // Jump to the end of the function
             // Body of simple
             ByteCode::ld("n"), // Load the value of n onto the stacks
@@ -309,8 +543,8 @@ mod tests {
             ByteCode::DONE,
         ];
 
-        let rt = Runtime::new(instrs);
-        let mut rt = run(rt)?;
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
 
         let result = rt.current_thread.operand_stack.pop().unwrap();
         assert_eq!(result, Value::Int(42));
@@ -323,8 +557,8 @@ mod tests {
     fn test_global_constants() -> Result<()> {
         let instrs = vec![ByteCode::ld(builtin::PI_SYM), ByteCode::DONE];
 
-        let rt = Runtime::new(instrs);
-        let rt = run(rt)?;
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
         assert_eq!(
             rt.current_thread.operand_stack,
             vec![Value::Float(std::f64::consts::PI)]
@@ -332,13 +566,10 @@ mod tests {
 
         let instrs = vec![ByteCode::ld(builtin::MAX_INT_SYM), ByteCode::DONE];
 
-        let rt = Runtime::new(instrs);
-        let rt = run(rt)?;
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
 
-        assert_eq!(
-            rt.current_thread.operand_stack,
-            vec![Value::Int(std::i64::MAX)]
-        );
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(i64::MAX)]);
 
         Ok(())
     }
@@ -352,8 +583,8 @@ mod tests {
             ByteCode::DONE,
         ];
 
-        let rt = Runtime::new(instrs);
-        let rt = run(rt)?;
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
 
         assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(13)]);
 
@@ -364,8 +595,8 @@ mod tests {
             ByteCode::DONE,
         ];
 
-        let rt = Runtime::new(instrs);
-        let rt = run(rt)?;
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
 
         assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(42)]);
 
@@ -374,11 +605,11 @@ mod tests {
 
     #[test]
     fn test_concurrency_01() -> Result<()> {
-        let instrs = vec![ByteCode::SPAWN(1), ByteCode::DONE];
+        let instrs = vec![ByteCode::SPAWN(1, 0), ByteCode::DONE];
 
         let mut rt = Runtime::new(instrs);
         rt.set_time_quantum(Duration::from_millis(u64::MAX)); // Set the time quantum to infinity
-        let rt = run(rt)?;
+        run(&mut rt)?;
 
         // There is one thread in the ready queue
         assert_eq!(rt.ready_queue.len(), 1);
@@ -403,13 +634,13 @@ mod tests {
         // join 2
         let instrs = vec![
             ByteCode::enterscope(vec!["simple"]),
-            ByteCode::ldf(3, vec!["n"]),
+            ByteCode::ldf(3, "simple", vec!["n"]),
             ByteCode::GOTO(5), // Jump past function body
             ByteCode::ld("n"),
             ByteCode::RESET(FrameType::CallFrame),
             ByteCode::assign("simple"),
-            ByteCode::SPAWN(8), // Parent operand stack will have child tid 2, child operand stack will have
-            ByteCode::GOTO(13), // Parent jump past CALL and DONE
+            ByteCode::SPAWN(8, 0), // Parent operand stack will have child tid 2, child operand stack will have
+            ByteCode::GOTO(13),    // Parent jump past CALL and DONE
             ByteCode::POP,
             ByteCode::ld("simple"),
             ByteCode::ldc(123),
@@ -420,8 +651,8 @@ mod tests {
             ByteCode::DONE,
         ];
 
-        let rt = Runtime::new(instrs);
-        let mut rt = run(rt)?;
+        let mut rt = Runtime::new(instrs);
+        run(&mut rt)?;
 
         println!("{:?}", rt.current_thread.operand_stack);
 
@@ -451,16 +682,16 @@ mod tests {
             ByteCode::enterscope(vec!["count", "infinite_increment"]),
             ByteCode::ldc(0),
             ByteCode::assign("count"), // Set count to 0
-            ByteCode::ldf(6, empty_str_arr),
+            ByteCode::ldf(6, "infinite_increment", empty_str_arr),
             ByteCode::assign("infinite_increment"), // assign function
             ByteCode::GOTO(11),                     // Jump past function body
             ByteCode::ld("count"),                  // Start of function body
             ByteCode::ldc(1),
             ByteCode::BINOP(BinOp::Add),
             ByteCode::assign("count"),
-            ByteCode::GOTO(6),   // End of function body
-            ByteCode::SPAWN(13), // Parent operand stack will have child tid 2, child operand stack will have
-            ByteCode::GOTO(17),  // Parent jump past CALL and DONE
+            ByteCode::GOTO(6),      // End of function body
+            ByteCode::SPAWN(13, 0), // Parent operand stack will have child tid 2, child operand stack will have
+            ByteCode::GOTO(17),     // Parent jump past CALL and DONE
             ByteCode::POP,
             ByteCode::ld("infinite_increment"),
             ByteCode::CALL(0),
@@ -471,7 +702,7 @@ mod tests {
 
         let mut rt = Runtime::new(instrs);
         rt.set_time_quantum(Duration::from_millis(1000)); // Set the time quantum to 1 second
-        let rt = run(rt)?;
+        run(&mut rt)?;
 
         let final_count: i64 = rt
             .current_thread
@@ -479,7 +710,7 @@ mod tests {
             .upgrade()
             .unwrap()
             .borrow()
-            .get(&"count".to_string())
+            .get("count")
             .expect("Count not in environment")
             .try_into()?;
 
@@ -517,7 +748,7 @@ mod tests {
             // pc 2
             ByteCode::assign("count"), // Set count to 0
             // pc 3
-            ByteCode::ldf(6, vec!["times"]),
+            ByteCode::ldf(6, "increment", vec!["times"]),
             // pc 4
             ByteCode::assign("increment"), // assign function
             // pc 5
@@ -561,7 +792,7 @@ mod tests {
             // pc 24
             ByteCode::RESET(FrameType::CallFrame), // End of function
             // pc 25
-            ByteCode::SPAWN(28), // Parent operand stack will have child tid 2, child operand stack will have 0
+            ByteCode::SPAWN(28, 0), // Parent operand stack will have child tid 2, child operand stack will have 0
             // pc 26
             ByteCode::assign("tid_2"), // Parent saves the child tid
             // pc 27
@@ -575,7 +806,7 @@ mod tests {
             // pc 31
             ByteCode::DONE, // Child is done
             // pc 32
-            ByteCode::SPAWN(35), // Parent operand stack will have child tid 3, child operand stack will have 0
+            ByteCode::SPAWN(35, 0), // Parent operand stack will have child tid 3, child operand stack will have 0
             // pc 33
             ByteCode::assign("tid_3"), // Parent saves the child tid
             // pc 34
@@ -589,7 +820,7 @@ mod tests {
             // pc 38
             ByteCode::DONE, // Child is done
             // pc 39
-            ByteCode::SPAWN(42), // Parent operand stack will have child tid 4, child operand stack will have 0
+            ByteCode::SPAWN(42, 0), // Parent operand stack will have child tid 4, child operand stack will have 0
             // pc 40
             ByteCode::assign("tid_4"), // Parent loads the child tid
             // pc 41
@@ -624,7 +855,7 @@ mod tests {
 
         // Set the time quantum to a short time, so that race conditions are more likely to occur
         rt.set_time_quantum(Duration::from_micros(1));
-        let rt = run(rt)?;
+        run(&mut rt)?;
 
         let final_count: i64 = rt
             .current_thread
@@ -632,7 +863,7 @@ mod tests {
             .upgrade()
             .unwrap()
             .borrow()
-            .get(&"count".to_string())
+            .get("count")
             .expect("Count not in environment")
             .try_into()?;
 
@@ -681,7 +912,7 @@ mod tests {
             // pc 5
             ByteCode::assign("sem"), // Set sem to the semaphore
             // pc 6
-            ByteCode::ldf(9, vec!["times"]),
+            ByteCode::ldf(9, "increment", vec!["times"]),
             // pc 7
             ByteCode::assign("increment"), // assign function
             // pc 8
@@ -733,7 +964,7 @@ mod tests {
             // pc 31
             ByteCode::RESET(FrameType::CallFrame), // End of function
             // pc 32
-            ByteCode::SPAWN(35), // Parent operand stack will have child tid 2, child operand stack will have 0
+            ByteCode::SPAWN(35, 0), // Parent operand stack will have child tid 2, child operand stack will have 0
             // pc 33
             ByteCode::assign("tid_2"), // Parent saves the child tid
             // pc 34
@@ -747,7 +978,7 @@ mod tests {
             // pc 38
             ByteCode::DONE, // Child is done
             // pc 39
-            ByteCode::SPAWN(42), // Parent operand stack will have child tid 3, child operand stack will have 0
+            ByteCode::SPAWN(42, 0), // Parent operand stack will have child tid 3, child operand stack will have 0
             // pc 40
             ByteCode::assign("tid_3"), // Parent saves the child tid
             // pc 41
@@ -761,7 +992,7 @@ mod tests {
             // pc 45
             ByteCode::DONE, // Child is done
             // pc 46
-            ByteCode::SPAWN(49), // Parent operand stack will have child tid 4, child operand stack will have 0
+            ByteCode::SPAWN(49, 0), // Parent operand stack will have child tid 4, child operand stack will have 0
             // pc 47
             ByteCode::assign("tid_4"), // Parent loads the child tid
             // pc 48
@@ -795,7 +1026,7 @@ mod tests {
         let mut rt = Runtime::new(instrs.clone());
         // Set the time quantum to a short time, so that race conditions are more likely to occur
         rt.set_time_quantum(Duration::from_micros(10));
-        let rt = run(rt)?;
+        run(&mut rt)?;
 
         let final_count: i64 = rt
             .current_thread
@@ -803,7 +1034,7 @@ mod tests {
             .upgrade()
             .unwrap()
             .borrow()
-            .get(&"count".to_string())
+            .get("count")
             .expect("Count not in environment")
             .try_into()?;
 
@@ -815,7 +1046,7 @@ mod tests {
 
         let mut rt = Runtime::new(instrs.clone());
         rt.set_time_quantum(Duration::from_micros(1));
-        let rt = run(rt)?;
+        run(&mut rt)?;
 
         let final_count: i64 = rt
             .current_thread
@@ -823,7 +1054,7 @@ mod tests {
             .upgrade()
             .unwrap()
             .borrow()
-            .get(&"count".to_string())
+            .get("count")
             .expect("Count not in environment")
             .try_into()?;
 
@@ -835,4 +1066,506 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_instr_budget() -> Result<()> {
+        // loop {}
+        let instrs = vec![ByteCode::GOTO(0), ByteCode::DONE];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_instr_budget(100);
+        let err = run(&mut rt).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::BudgetExceeded(0))
+        ));
+        assert_eq!(rt.instr_count, 101);
+
+        // A budget that is large enough does not stop the program
+        let mut rt = Runtime::new(vec![ByteCode::ldc(42), ByteCode::DONE]);
+        rt.set_instr_budget(2);
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(42)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interrupt() -> Result<()> {
+        // loop {}
+        let instrs = vec![ByteCode::GOTO(0), ByteCode::DONE];
+
+        let mut rt = Runtime::new(instrs);
+        let flag = Arc::new(AtomicBool::new(false));
+        rt.set_interrupt_flag(Arc::clone(&flag));
+        rt.set_instr_budget(100);
+
+        step(&mut rt)?;
+        flag.store(true, Ordering::Relaxed);
+        let err = run(&mut rt).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::Interrupted)
+        ));
+
+        let ctx = err.downcast_ref::<RuntimeErrorContext>().unwrap();
+        assert_eq!(ctx.thread_id, MAIN_THREAD_ID);
+        assert_eq!(rt.instr_count, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_instr_budget() -> Result<()> {
+        // The child spins forever while the parent yields to it
+        let instrs = vec![
+            ByteCode::SPAWN(3, 0),
+            ByteCode::YIELD,
+            ByteCode::DONE,
+            ByteCode::POP,
+            ByteCode::GOTO(4),
+        ];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_time_quantum(Duration::from_millis(u64::MAX));
+        rt.set_thread_instr_budget(10);
+        let err = run(&mut rt).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::BudgetExceeded(4))
+        ));
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        assert_eq!(rt.current_thread.instr_count, 11);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow() -> Result<()> {
+        // fn f(n) {
+        //     return f(n);
+        // }
+        // f(0)
+        let instrs = vec![
+            ByteCode::enterscope(vec!["f"]),
+            ByteCode::ldf(3, "f", vec!["n"]),
+            ByteCode::GOTO(7),
+            ByteCode::ld("f"),
+            ByteCode::ld("n"),
+            ByteCode::CALL(1),
+            ByteCode::RESET(FrameType::CallFrame),
+            ByteCode::assign("f"),
+            ByteCode::ld("f"),
+            ByteCode::ldc(0),
+            ByteCode::CALL(1),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_max_call_depth(100);
+        let err = run(&mut rt).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::StackOverflow { pc: 5, depth: 100 })
+        ));
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Stack overflow at pc=5, call depth=100"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_operand_stack_overflow() -> Result<()> {
+        let instrs = vec![ByteCode::ldc(1), ByteCode::GOTO(0)];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_max_operand_stack(10);
+        let err = run(&mut rt).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::OperandStackOverflow { pc: 0, size: 11 })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_context() -> Result<()> {
+        // fn f(n) {
+        //     return f(n);
+        // }
+        // f(0)
+        let instrs = vec![
+            ByteCode::enterscope(vec!["f"]),
+            ByteCode::ldf(3, "f", vec!["n"]),
+            ByteCode::GOTO(7),
+            ByteCode::ld("f"),
+            ByteCode::ld("n"),
+            ByteCode::CALL(1),
+            ByteCode::RESET(FrameType::CallFrame),
+            ByteCode::assign("f"),
+            ByteCode::ld("f"),
+            ByteCode::ldc(0),
+            ByteCode::CALL(1),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_max_call_depth(3);
+        let err = run(&mut rt).unwrap_err();
+
+        let ctx = err
+            .downcast_ref::<RuntimeErrorContext>()
+            .expect("Error has a context");
+        let f = Some(Symbol::from("f"));
+        assert_eq!(ctx.thread_id, MAIN_THREAD_ID);
+        assert_eq!(ctx.pc, 5);
+        assert_eq!(ctx.instr.as_deref(), Some("CALL(1)"));
+        assert_eq!(
+            ctx.trace.frames,
+            vec![
+                TraceFrame { sym: f, pc: 5 },
+                TraceFrame { sym: f, pc: 5 },
+                TraceFrame { sym: None, pc: 10 },
+            ]
+        );
+        assert_eq!(
+            ctx.to_string(),
+            "Runtime error in thread 1 at pc=5: CALL(1)\n    at f (pc=5)\n    at f (pc=5)\n    at <top level> (pc=10)"
+        );
+
+        // A failing instruction at the top level has a single frame
+        let mut rt = Runtime::new(vec![ByteCode::ldc(1), ByteCode::POP, ByteCode::POP]);
+        let err = run(&mut rt).unwrap_err();
+
+        let ctx = err
+            .downcast_ref::<RuntimeErrorContext>()
+            .expect("Error has a context");
+        assert_eq!(ctx.instr.as_deref(), Some("POP"));
+        assert_eq!(ctx.trace.frames, vec![TraceFrame { sym: None, pc: 2 }]);
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::OperandStackUnderflow)
+        ));
+
+        // Errors outside of instructions are located at the PC the thread was at
+        let mut rt = Runtime::new(vec![ByteCode::GOTO(5)]);
+        let err = run(&mut rt).unwrap_err();
+
+        let ctx = err
+            .downcast_ref::<RuntimeErrorContext>()
+            .expect("Error has a context");
+        assert_eq!(ctx.pc, 5);
+        assert_eq!(ctx.instr, None);
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::PcOutOfBounds(5))
+        ));
+
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            std::io::Result::Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace() -> Result<()> {
+        let instrs = vec![
+            ByteCode::ldc(1),
+            ByteCode::ldc(2),
+            ByteCode::BINOP(BinOp::Add),
+            ByteCode::DONE,
+        ];
+
+        let buf = SharedBuf::default();
+        let mut rt = Runtime::new(instrs);
+        rt.set_trace_sink(buf.clone());
+        run(&mut rt)?;
+
+        let trace = String::from_utf8(buf.0.borrow().clone())?;
+        assert_eq!(
+            trace,
+            "thread=1 pc=0 op=LDC(1) stack=0\n\
+             thread=1 pc=1 op=LDC(2) stack=1\n\
+             thread=1 pc=2 op=BINOP(Add) stack=2\n\
+             thread=1 pc=3 op=DONE stack=1\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdout() -> Result<()> {
+        let instrs = vec![
+            ByteCode::ld("print"),
+            ByteCode::ldc("a"),
+            ByteCode::CALL(1),
+            ByteCode::ld("println"),
+            ByteCode::ldc(1),
+            ByteCode::CALL(1),
+            ByteCode::DONE,
+        ];
+
+        let buf = SharedBuf::default();
+        let mut rt = Runtime::new(instrs);
+        rt.set_stdout(buf.clone());
+        run(&mut rt)?;
+
+        assert_eq!(String::from_utf8(buf.0.borrow().clone())?, "a1\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_isolates() -> Result<()> {
+        let src = r"
+            let n = 0;
+            fn count(k: int) { n = n + k; }
+            count(1);
+            count(2);
+            println(n);
+            random_int(0, 1000000)
+        ";
+        let instrs = compiler::compiler::compile_from_string(src, true)?;
+
+        // Interleaved in one thread, runtimes share no globals, output, limits or randomness
+        let (a_out, b_out) = (SharedBuf::default(), SharedBuf::default());
+        let mut a = Runtime::with_config(instrs.clone(), RuntimeConfig::default());
+        let mut b = Runtime::with_config(instrs.clone(), RuntimeConfig::default().instr_budget(5));
+        a.set_stdout(a_out.clone());
+        b.set_stdout(b_out.clone());
+        a.set_rng(SeededRng::new(1));
+
+        let mut b_res = Ok(());
+        while !a.is_done() {
+            step(&mut a)?;
+            if b_res.is_ok() && !b.is_done() {
+                b_res = step(&mut b);
+            }
+        }
+
+        assert!(b_res.is_err());
+        assert_eq!(String::from_utf8(a_out.0.borrow().clone())?, "3\n");
+        assert!(b_out.0.borrow().is_empty());
+
+        let expected = SeededRng::new(1).next_u64() % 1000000;
+        assert_eq!(
+            a.current_thread.operand_stack,
+            vec![Value::Int(expected as i64)]
+        );
+
+        // Each thread of the process can run its own runtimes, though values can't be sent between them
+        let handles: Vec<_> = (0..4)
+            .map(|seed| {
+                std::thread::spawn(move || {
+                    let instrs = compiler::compiler::compile_from_string(src, true).unwrap();
+                    let mut rt = Runtime::new(instrs);
+                    rt.set_stdout(std::io::sink());
+                    rt.set_rng(SeededRng::new(seed));
+                    run(&mut rt).unwrap();
+                    i64::try_from(rt.current_thread.operand_stack.pop().unwrap()).unwrap()
+                })
+            })
+            .collect();
+
+        for (seed, handle) in handles.into_iter().enumerate() {
+            let expected = SeededRng::new(seed as u64).next_u64() % 1000000;
+            assert_eq!(handle.join().unwrap(), expected as i64);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deterministic() -> Result<()> {
+        let src = r"
+            fn worker(id: int) {
+                let i = 0;
+                loop i < 200 {
+                    print(id);
+                    i = i + 1;
+                }
+            }
+            let a = spawn worker(1);
+            let b = spawn worker(2);
+            worker(3);
+            join a;
+            join b;
+            random_int(0, 1000000)
+        ";
+        let instrs = compiler::compiler::compile_from_string(src, true)?;
+
+        let run_with = |seed| -> Result<(String, Vec<Value>)> {
+            let out = SharedBuf::default();
+            let config = RuntimeConfig::default().deterministic(seed);
+            let mut rt = Runtime::with_config(instrs.clone(), config);
+            rt.set_stdout(out.clone());
+            run(&mut rt)?;
+            let out = String::from_utf8(out.0.borrow().clone())?;
+            Ok((out, rt.current_thread.operand_stack.clone()))
+        };
+
+        // The threads are interleaved, the same way for the same seed
+        let (out, result) = run_with(42)?;
+        assert_eq!(out.len(), 600);
+        assert!(!out.starts_with(&"3".repeat(200)));
+        assert_eq!(run_with(42)?, (out.clone(), result));
+
+        let outs: HashSet<String> = (0..4)
+            .map(|seed| run_with(seed).map(|(out, _)| out))
+            .collect::<Result<_>>()?;
+        assert!(outs.len() > 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdin() -> Result<()> {
+        let instrs = vec![
+            ByteCode::ld("print"),
+            ByteCode::ld("read_line"),
+            ByteCode::CALL(0),
+            ByteCode::CALL(1),
+            ByteCode::ld("read_line"),
+            ByteCode::CALL(0),
+            ByteCode::DONE,
+        ];
+
+        let buf = SharedBuf::default();
+        let mut rt = Runtime::new(instrs)
+            .with_stdout(Box::new(buf.clone()))
+            .with_stdin(Box::new("first\nsecond\n".as_bytes()));
+        run(&mut rt)?;
+
+        assert_eq!(String::from_utf8(buf.0.borrow().clone())?, "first\n");
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::from("second\n"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_virtual_clock_preempts() -> Result<()> {
+        // The main thread spins until the spawned thread sets x, which needs the main thread to be preempted
+        let instrs = vec![
+            ByteCode::enterscope(vec!["x"]),
+            ByteCode::ldc(false),
+            ByteCode::assign("x"),
+            ByteCode::SPAWN(9, 0),
+            ByteCode::POP,
+            // Main thread
+            ByteCode::ld("x"),
+            ByteCode::JOF(5),
+            ByteCode::ld("x"),
+            ByteCode::DONE,
+            // Spawned thread
+            ByteCode::POP,
+            ByteCode::ldc(true),
+            ByteCode::assign("x"),
+            ByteCode::DONE,
+        ];
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_clock(VirtualClock::new(Duration::from_millis(1)));
+        run(&mut rt)?;
+
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Bool(true)]);
+        // The quantum of 100ms is 50 ticks of 1ms, at two readings of the clock per instruction
+        assert!(rt.instr_count < 200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_and_recover() -> Result<()> {
+        // let x = 1;
+        let mut rt = Runtime::new(vec![
+            ByteCode::enterscope(vec!["x"]),
+            ByteCode::ldc(1),
+            ByteCode::assign("x"),
+            ByteCode::DONE,
+        ]);
+        run(&mut rt)?;
+
+        // x + 1, in the environment left by the previous run
+        let start = rt.instrs.len();
+        rt.instrs.extend([
+            ByteCode::ld("x"),
+            ByteCode::ldc(1),
+            ByteCode::BINOP(BinOp::Add),
+            ByteCode::DONE,
+        ]);
+        rt.resume_at(start);
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(2)]);
+
+        // An error in a spawned thread
+        let env = rt.current_thread.env.clone();
+        let start = rt.instrs.len();
+        rt.instrs.extend([
+            ByteCode::SPAWN(start + 4, 0),
+            ByteCode::YIELD,
+            ByteCode::ld("x"),
+            ByteCode::DONE,
+            ByteCode::POP,
+            ByteCode::ld("y"),
+            ByteCode::DONE,
+        ]);
+        rt.resume_at(start);
+        assert!(run(&mut rt).is_err());
+        assert_eq!(rt.current_thread.thread_id, 2);
+
+        rt.recover(env)?;
+        assert!(rt.is_done());
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert!(rt.current_thread.operand_stack.is_empty());
+        assert_eq!(rt.thread_state(2), Some(ThreadState::Done));
+        assert!(rt.ready_queue.is_empty());
+
+        // x is still bound after recovering
+        rt.resume_at(start + 2);
+        run(&mut rt)?;
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit() -> Result<()> {
+        // exit(3); 1
+        let mut rt = Runtime::new(vec![
+            ByteCode::ld(builtin::EXIT_SYM),
+            ByteCode::ldc(3),
+            ByteCode::CALL(1),
+            ByteCode::ldc(1),
+            ByteCode::DONE,
+        ]);
+        run(&mut rt)?;
+
+        assert_eq!(rt.exit_code, Some(3));
+        assert!(rt.current_thread.operand_stack.is_empty());
+
+        // Resuming clears the status
+        rt.resume_at(3);
+        run(&mut rt)?;
+        assert_eq!(rt.exit_code, None);
+
+        Ok(())
+    }
 }
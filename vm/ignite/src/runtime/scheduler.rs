@@ -0,0 +1,303 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    time::Duration,
+};
+
+use bytecode::ThreadID;
+
+use crate::{Rng, Runtime, SeededRng, Thread, VmError, WakeSource};
+
+/// The policy deciding the order threads run and are woken up in.
+/// The runtime keeps the threads in its ready, blocked and timer queues, and asks the scheduler where a thread that
+/// becomes ready, blocks or waits with a timeout goes, which ready thread runs next and which blocked thread is woken up
+/// when only one can be, so a policy only orders the queues and the micro code is the same for all of them.
+pub trait Scheduler {
+    /// Add a thread that has become ready to the ready queue, whether it was spawned, woken up or preempted.
+    fn enqueue(&self, ready_queue: &mut VecDeque<Thread>, thread: Thread) {
+        ready_queue.push_back(thread);
+    }
+
+    /// The index in the ready queue of the thread to run next, which the runtime removes from the queue.
+    /// The runtime is given for the rest of its state, e.g. the threads blocked on the semaphores a ready thread holds.
+    /// None if the ready queue is empty.
+    fn next(&self, rt: &Runtime) -> Option<usize>;
+
+    /// Add a thread that has blocked to the blocked queue, with what can wake it up.
+    fn block(
+        &self,
+        blocked_queue: &mut VecDeque<(Thread, Vec<WakeSource>)>,
+        thread: Thread,
+        sources: Vec<WakeSource>,
+    ) {
+        blocked_queue.push_back((thread, sources));
+    }
+
+    /// The index in the blocked queue of the thread to wake up when only one of the threads waiting on something can be,
+    /// e.g. a semaphore is posted or a value is sent on a channel. The waiters are the indices of those threads,
+    /// in the order of the blocked queue, and are never empty. By default, the one the wakeup policy of the runtime picks.
+    fn wake_one(&self, rt: &Runtime, waiters: &[usize]) -> usize {
+        rt.wakeup_policy.pick(rt, waiters)
+    }
+
+    /// Add the deadline of a blocked thread waiting with a timeout to the timer queue,
+    /// which wakes up the threads whose deadlines have passed, earliest first.
+    fn add_timer(
+        &self,
+        timer_queue: &mut BinaryHeap<Reverse<(Duration, ThreadID)>>,
+        deadline: Duration,
+        tid: ThreadID,
+    ) {
+        timer_queue.push(Reverse((deadline, tid)));
+    }
+}
+
+/// The default scheduler, which runs the ready threads in turn in the order they became ready.
+/// Threads that have been given priorities run before the threads of a lower effective priority,
+/// see `Runtime::effective_priority`, and in turn among themselves.
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobin;
+
+impl Scheduler for RoundRobin {
+    fn next(&self, rt: &Runtime) -> Option<usize> {
+        let prioritized = rt
+            .ready_queue
+            .iter()
+            .chain(rt.blocked_queue.iter().map(|(thread, _)| thread))
+            .any(|thread| thread.priority != 0);
+
+        if !prioritized {
+            return (!rt.ready_queue.is_empty()).then_some(0);
+        }
+
+        let priorities: Vec<i64> = rt
+            .ready_queue
+            .iter()
+            .map(|thread| rt.effective_priority(thread))
+            .collect();
+        let max = priorities.iter().max()?;
+        priorities.iter().position(|p| p == max)
+    }
+}
+
+/// The scheduler of deterministic mode, which runs a ready thread of the highest effective priority
+/// chosen by a seeded random number generator, so the same seed gives the same interleaving
+/// and different seeds explore different ones.
+#[derive(Debug, Clone)]
+pub struct SeededScheduler {
+    rng: SeededRng,
+}
+
+impl SeededScheduler {
+    pub fn new(seed: u64) -> Self {
+        SeededScheduler {
+            rng: SeededRng::new(seed),
+        }
+    }
+}
+
+impl Scheduler for SeededScheduler {
+    fn next(&self, rt: &Runtime) -> Option<usize> {
+        let priorities: Vec<i64> = rt
+            .ready_queue
+            .iter()
+            .map(|thread| rt.effective_priority(thread))
+            .collect();
+        let max = priorities.iter().max()?;
+        let candidates: Vec<usize> = (0..priorities.len())
+            .filter(|&i| priorities[i] == *max)
+            .collect();
+
+        let i = self.rng.next_u64() % candidates.len() as u64;
+        Some(candidates[i as usize])
+    }
+}
+
+/// Moving threads in and out of the ready, blocked and timer queues, as the scheduler decides.
+impl Runtime {
+    /// Replace the scheduler of the runtime, which is round robin by default.
+    pub fn set_scheduler(&mut self, scheduler: impl Scheduler + 'static) {
+        self.scheduler = Box::new(scheduler);
+    }
+
+    /// Add a thread that has become ready to the ready queue.
+    pub fn push_ready(&mut self, thread: Thread) {
+        self.scheduler.enqueue(&mut self.ready_queue, thread);
+    }
+
+    /// Add a thread that has blocked to the blocked queue, with what can wake it up.
+    pub fn push_blocked(&mut self, thread: Thread, sources: Vec<WakeSource>) {
+        self.scheduler
+            .block(&mut self.blocked_queue, thread, sources);
+    }
+
+    /// Add the deadline of a blocked thread to the timer queue.
+    pub fn add_timer(&mut self, deadline: Duration, tid: ThreadID) {
+        self.scheduler
+            .add_timer(&mut self.timer_queue, deadline, tid);
+    }
+
+    /// Remove the blocked thread the scheduler wakes up of those waiting on a source that matches, if any,
+    /// with what it was waiting on. The thread is not moved to the ready queue, so the caller can hand it a value first.
+    pub fn take_waiter<F>(&mut self, matches: F) -> Option<(Thread, Vec<WakeSource>)>
+    where
+        F: Fn(&WakeSource) -> bool,
+    {
+        let waiters: Vec<usize> = self
+            .blocked_queue
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, sources))| sources.iter().any(&matches))
+            .map(|(i, _)| i)
+            .collect();
+        if waiters.is_empty() {
+            return None;
+        }
+
+        let i = self.scheduler.wake_one(self, &waiters);
+        self.blocked_queue.remove(i)
+    }
+
+    /// Remove the thread the scheduler runs next from the ready queue, if any.
+    /// While a log is replayed, the thread is the one that ran next in the recorded run.
+    ///
+    /// # Errors
+    ///
+    /// If the run has diverged from the replayed one.
+    pub fn take_next_ready(&mut self) -> Result<Option<Thread>, VmError> {
+        if self.ready_queue.is_empty() {
+            return Ok(None);
+        }
+
+        let i = match self.replayed_index()? {
+            Some(i) => Some(i),
+            None => self.scheduler.next(self),
+        };
+        let Some(thread) = i.and_then(|i| self.ready_queue.remove(i)) else {
+            return Ok(None);
+        };
+
+        self.record_switch(&thread);
+        Ok(Some(thread))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use bytecode::Semaphore;
+
+    use crate::{micro_code::post, micro_code::spawn, micro_code::yield_, MAIN_THREAD_ID};
+
+    use super::*;
+
+    /// Runs the thread that became ready last, and wakes up the thread that blocked last.
+    struct Lifo;
+
+    impl Scheduler for Lifo {
+        fn next(&self, rt: &Runtime) -> Option<usize> {
+            rt.ready_queue.len().checked_sub(1)
+        }
+
+        fn wake_one(&self, _rt: &Runtime, waiters: &[usize]) -> usize {
+            waiters[waiters.len() - 1]
+        }
+    }
+
+    /// Wakes up threads waiting with a timeout on the next multiple of 10ms after their deadline.
+    struct Slack;
+
+    impl Scheduler for Slack {
+        fn next(&self, rt: &Runtime) -> Option<usize> {
+            (!rt.ready_queue.is_empty()).then_some(0)
+        }
+
+        fn add_timer(
+            &self,
+            timer_queue: &mut BinaryHeap<Reverse<(Duration, ThreadID)>>,
+            deadline: Duration,
+            tid: ThreadID,
+        ) {
+            let deadline = Duration::from_millis(deadline.as_millis().div_ceil(10) as u64 * 10);
+            timer_queue.push(Reverse((deadline, tid)));
+        }
+    }
+
+    #[test]
+    fn test_seeded_scheduler() -> Result<()> {
+        let picks = |seed| -> Result<Vec<i64>> {
+            let mut rt = Runtime::default();
+            rt.set_scheduler(SeededScheduler::new(seed));
+            for _ in 0..4 {
+                spawn(&mut rt, 0, 0)?;
+            }
+            rt.ready_queue.back_mut().unwrap().priority = -1;
+
+            let mut picks = vec![];
+            while let Some(thread) = rt.take_next_ready()? {
+                picks.push(thread.thread_id);
+            }
+            Ok(picks)
+        };
+
+        // The same seed picks the same threads, and a lower priority thread runs last.
+        let first = picks(7)?;
+        assert_eq!(first, picks(7)?);
+        assert_eq!(first.len(), 4);
+        assert_eq!(first.last(), Some(&(MAIN_THREAD_ID + 4)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_scheduler() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.set_scheduler(Lifo);
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+
+        // The main thread is added to the back, so it runs again.
+        yield_(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert_eq!(rt.ready_queue.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocked_and_timer_queues() -> Result<()> {
+        let sem = Semaphore::new(0);
+        let blocked = |rt: &mut Runtime| {
+            for tid in [MAIN_THREAD_ID + 1, MAIN_THREAD_ID + 2] {
+                let thread = rt.current_thread.spawn_child(tid, 0);
+                rt.push_blocked(thread, vec![WakeSource::new(sem.clone())]);
+            }
+        };
+
+        // By default the thread that blocked first is woken up
+        let mut rt = Runtime::default();
+        blocked(&mut rt);
+        rt.current_thread.operand_stack.push(sem.clone().into());
+        post(&mut rt)?;
+        assert_eq!(rt.ready_queue[0].thread_id, MAIN_THREAD_ID + 1);
+
+        let mut rt = Runtime::default();
+        rt.set_scheduler(Lifo);
+        blocked(&mut rt);
+        rt.current_thread.operand_stack.push(sem.clone().into());
+        post(&mut rt)?;
+        assert_eq!(rt.ready_queue[0].thread_id, MAIN_THREAD_ID + 2);
+        assert_eq!(rt.blocked_queue[0].0.thread_id, MAIN_THREAD_ID + 1);
+
+        let mut rt = Runtime::default();
+        rt.set_scheduler(Slack);
+        rt.add_timer(Duration::from_millis(3), MAIN_THREAD_ID + 1);
+        assert_eq!(
+            rt.timer_queue.peek(),
+            Some(&Reverse((Duration::from_millis(10), MAIN_THREAD_ID + 1)))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1437 @@
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::HashMap,
+    io::{Read, Write},
+    rc::{Rc, Weak},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::Result;
+use bytecode::{
+    read_bytecode, weak_clone, write_bytecode, Address, AtomicInt, Barrier, BarrierState,
+    BoundedQueue, BoundedQueueState, Channel, ChannelState, Closure, CondVar, Enum, Environment,
+    FnType, FrameType, Generator, GeneratorState, GeneratorStatus, Iter, IterState, RwLock,
+    RwLockState, Semaphore, StackFrame, Struct, StructType, Symbol, ThreadID, Value, Variant,
+    WaitGroup, WaitGroupState, W,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Runtime, SpawnScope, Thread, ThreadState, VmError, WakeSource};
+
+/// The state of a paused runtime, with the object graph flattened into tables.
+///
+/// Environments, struct types, iterators, generators, channels, bounded queues and synchronization primitives are shared between threads and values, so they are
+/// stored once each and referred to by their index in the table. Symbols are stored as strings since
+/// the ids of interned symbols differ between processes, and deadlines are stored as the time remaining.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    /// The instructions, in the .o2 format.
+    instrs: Vec<u8>,
+    done: bool,
+    thread_count: i64,
+    instr_count: u64,
+    envs: Vec<EnvSnapshot>,
+    semaphores: Vec<u64>,
+    cond_vars: usize,
+    barriers: Vec<(u64, u64)>,
+    wait_groups: Vec<u64>,
+    rwlocks: Vec<(u64, bool)>,
+    atomic_ints: Vec<i64>,
+    channels: Vec<ChannelSnapshot>,
+    bounded_queues: Vec<BoundedQueueSnapshot>,
+    struct_types: Vec<StructTypeSnapshot>,
+    iters: Vec<IterSnapshot>,
+    generators: Vec<GeneratorSnapshot>,
+    current_thread: ThreadSnapshot,
+    ready_queue: Vec<ThreadSnapshot>,
+    blocked_queue: Vec<(ThreadSnapshot, Vec<WakeSourceSnapshot>)>,
+    zombie_threads: Vec<ThreadSnapshot>,
+    spawn_scopes: Vec<(ThreadID, Vec<SpawnScopeSnapshot>)>,
+    thread_states: Vec<(ThreadID, ThreadState)>,
+    timer_queue: Vec<(Duration, ThreadID)>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum ValueSnapshot {
+    Unitialized,
+    Unit,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Semaphore(usize),
+    CondVar(usize),
+    Barrier(usize),
+    WaitGroup(usize),
+    RwLock(usize),
+    AtomicInt(usize),
+    Channel(usize),
+    BoundedQueue(usize),
+    Closure {
+        builtin: bool,
+        sym: String,
+        prms: Vec<String>,
+        addr: Address,
+        env: Option<usize>,
+    },
+    Some(Box<ValueSnapshot>),
+    None,
+    Ok(Box<ValueSnapshot>),
+    Err(Box<ValueSnapshot>),
+    StructType(usize),
+    Struct {
+        ty: usize,
+        fields: Vec<ValueSnapshot>,
+    },
+    Enum {
+        ty: String,
+        variant: String,
+        payload: Option<Box<ValueSnapshot>>,
+    },
+    Array(Vec<ValueSnapshot>),
+    Iter(usize),
+    Generator(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+enum IterSnapshot {
+    Range {
+        next: i64,
+        end: i64,
+    },
+    Array {
+        elems: Vec<ValueSnapshot>,
+        idx: usize,
+    },
+    Chars {
+        s: String,
+        idx: usize,
+    },
+}
+
+/// A generator and the state it is resumed with, saved like that of a thread.
+#[derive(Serialize, Deserialize)]
+struct GeneratorSnapshot {
+    status: GeneratorStatus,
+    pc: usize,
+    env: Option<usize>,
+    operand_stack: Vec<ValueSnapshot>,
+    runtime_stack: Vec<FrameSnapshot>,
+    done_addr: usize,
+}
+
+/// A channel with the values buffered in it. The values blocked senders are sending are in their wake sources.
+#[derive(Serialize, Deserialize)]
+struct ChannelSnapshot {
+    cap: usize,
+    buf: Vec<ValueSnapshot>,
+    closed: bool,
+}
+
+/// A bounded queue, its capacity and the values in it. The values blocked pushers are pushing are on their operand stacks.
+#[derive(Serialize, Deserialize)]
+struct BoundedQueueSnapshot {
+    cap: usize,
+    buf: Vec<ValueSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructTypeSnapshot {
+    name: String,
+    fields: Vec<String>,
+    methods: Vec<(String, ValueSnapshot)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EnvSnapshot {
+    parent: Option<usize>,
+    env: Vec<(String, ValueSnapshot)>,
+    syms: Vec<String>,
+    slots: Vec<ValueSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpawnScopeSnapshot {
+    depth: usize,
+    threads: Vec<ThreadID>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameSnapshot {
+    frame_type: FrameType,
+    address: Option<Address>,
+    env: Option<usize>,
+    sym: Option<String>,
+    operand_len: Option<usize>,
+    lock: Option<ValueSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ThreadSnapshot {
+    thread_id: ThreadID,
+    env: Option<usize>,
+    operand_stack: Vec<ValueSnapshot>,
+    runtime_stack: Vec<FrameSnapshot>,
+    pc: usize,
+    held_semaphores: Vec<usize>,
+    instr_count: u64,
+    locals: Vec<(String, ValueSnapshot)>,
+    priority: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum WakeSourceSnapshot {
+    Semaphore {
+        sem: usize,
+        addr: Option<Address>,
+    },
+    CondVar {
+        cv: usize,
+        mutex: usize,
+    },
+    Barrier(usize),
+    WaitGroup(usize),
+    RwLock {
+        lock: usize,
+        write: bool,
+    },
+    ChannelSend {
+        ch: usize,
+        val: ValueSnapshot,
+        addr: Option<Address>,
+    },
+    ChannelRecv {
+        ch: usize,
+        addr: Option<Address>,
+    },
+    QueuePush(usize),
+    QueuePop(usize),
+    Timeout(Duration),
+}
+
+/// Snapshots and resumption.
+impl Runtime {
+    /// Write the state of the runtime to the writer, so that it can be resumed with `load_snapshot`.
+    /// The runtime must be paused between instructions, e.g. by the debugger.
+    ///
+    /// The configuration of the runtime (time quantum, limits, debug and trace modes) is not saved,
+    /// the runtime that resumes is configured anew.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot cannot be serialized or written.
+    pub fn save_snapshot<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let snapshot = Flattener::default().flatten(self)?;
+        bincode::serialize_into(writer, &snapshot)?;
+        Ok(())
+    }
+
+    /// Read a runtime written by `save_snapshot`, ready to continue where it was paused.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot cannot be read or refers to objects it does not contain.
+    pub fn load_snapshot<R: Read>(reader: &mut R) -> Result<Runtime> {
+        let snapshot: Snapshot = bincode::deserialize_from(reader)?;
+        Restorer::default().restore(snapshot)
+    }
+}
+
+/// Assigns each shared object an index the first time it is seen, by pointer.
+#[derive(Default)]
+struct Flattener {
+    envs: HashMap<*const RefCell<Environment>, usize>,
+    semaphores: HashMap<*const Mutex<u64>, usize>,
+    semaphore_values: Vec<u64>,
+    cond_vars: HashMap<*const (), usize>,
+    barriers: HashMap<*const Mutex<BarrierState>, usize>,
+    barrier_values: Vec<(u64, u64)>,
+    wait_groups: HashMap<*const Mutex<WaitGroupState>, usize>,
+    wait_group_values: Vec<u64>,
+    rwlocks: HashMap<*const Mutex<RwLockState>, usize>,
+    rwlock_values: Vec<(u64, bool)>,
+    atomic_ints: HashMap<*const Mutex<i64>, usize>,
+    atomic_int_values: Vec<i64>,
+    channels: HashMap<*const RefCell<ChannelState>, usize>,
+    channel_values: Vec<ChannelSnapshot>,
+    bounded_queues: HashMap<*const RefCell<BoundedQueueState>, usize>,
+    bounded_queue_values: Vec<BoundedQueueSnapshot>,
+    struct_types: HashMap<*const StructType, usize>,
+    struct_type_values: Vec<StructTypeSnapshot>,
+    iters: HashMap<*const RefCell<IterState>, usize>,
+    iter_values: Vec<IterSnapshot>,
+    generators: HashMap<*const RefCell<GeneratorState>, usize>,
+    generator_values: Vec<GeneratorSnapshot>,
+}
+
+impl Flattener {
+    fn flatten(mut self, rt: &Runtime) -> Result<Snapshot> {
+        let mut instrs = vec![];
+        write_bytecode(&rt.instrs, &mut instrs)?;
+
+        // Only environments in the registry are alive, references to any other environment dangle
+        let registry: Vec<_> = rt
+            .env_registry
+            .iter()
+            .map(|env| Rc::clone(&env.0))
+            .collect();
+        for (idx, env) in registry.iter().enumerate() {
+            self.envs.insert(Rc::as_ptr(env), idx);
+        }
+
+        let envs = registry
+            .iter()
+            .map(|env| self.env(&env.borrow()))
+            .collect::<Result<_>>()?;
+
+        let current_thread = self.thread(&rt.current_thread)?;
+        let ready_queue = rt
+            .ready_queue
+            .iter()
+            .map(|t| self.thread(t))
+            .collect::<Result<_>>()?;
+
+        let now = rt.now();
+        let mut blocked_queue = vec![];
+        for (thread, sources) in rt.blocked_queue.iter() {
+            let sources = sources
+                .iter()
+                .map(|source| self.wake_source(source, now))
+                .collect::<Result<_>>()?;
+            blocked_queue.push((self.thread(thread)?, sources));
+        }
+
+        let zombie_threads = rt
+            .zombie_threads
+            .values()
+            .map(|t| self.thread(t))
+            .collect::<Result<_>>()?;
+
+        let spawn_scopes = rt
+            .spawn_scopes
+            .iter()
+            .map(|(tid, scopes)| {
+                let scopes = scopes
+                    .iter()
+                    .map(|scope| SpawnScopeSnapshot {
+                        depth: scope.depth,
+                        threads: scope.threads.clone(),
+                    })
+                    .collect();
+                (*tid, scopes)
+            })
+            .collect();
+
+        let timer_queue = rt
+            .timer_queue
+            .iter()
+            .map(|Reverse((deadline, tid))| (deadline.saturating_sub(now), *tid))
+            .collect();
+
+        Ok(Snapshot {
+            instrs,
+            done: rt.done,
+            thread_count: rt.thread_count,
+            instr_count: rt.instr_count,
+            envs,
+            semaphores: self.semaphore_values,
+            cond_vars: self.cond_vars.len(),
+            barriers: self.barrier_values,
+            wait_groups: self.wait_group_values,
+            rwlocks: self.rwlock_values,
+            atomic_ints: self.atomic_int_values,
+            channels: self.channel_values,
+            bounded_queues: self.bounded_queue_values,
+            struct_types: self.struct_type_values,
+            iters: self.iter_values,
+            generators: self.generator_values,
+            current_thread,
+            ready_queue,
+            blocked_queue,
+            zombie_threads,
+            spawn_scopes,
+            thread_states: rt.thread_states.iter().map(|(t, s)| (*t, *s)).collect(),
+            timer_queue,
+        })
+    }
+
+    fn env_ref(&self, env: &Weak<RefCell<Environment>>) -> Option<usize> {
+        self.envs.get(&Weak::as_ptr(env)).copied()
+    }
+
+    fn env(&mut self, env: &Environment) -> Result<EnvSnapshot> {
+        let mut named = vec![];
+        for (sym, val) in env.env.iter() {
+            named.push((sym.to_string(), self.value(val)?));
+        }
+
+        Ok(EnvSnapshot {
+            parent: env.parent.as_ref().and_then(|p| self.env_ref(p)),
+            env: named,
+            syms: env.syms.iter().map(|s| s.to_string()).collect(),
+            slots: env
+                .slots
+                .iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn frame(&mut self, frame: &StackFrame) -> Result<FrameSnapshot> {
+        Ok(FrameSnapshot {
+            frame_type: frame.frame_type.clone(),
+            address: frame.address,
+            env: self.env_ref(&frame.env.0),
+            sym: frame.sym.map(|s| s.to_string()),
+            operand_len: frame.operand_len,
+            lock: frame.lock.as_ref().map(|v| self.value(v)).transpose()?,
+        })
+    }
+
+    fn thread(&mut self, thread: &Thread) -> Result<ThreadSnapshot> {
+        Ok(ThreadSnapshot {
+            thread_id: thread.thread_id,
+            env: self.env_ref(&thread.env),
+            operand_stack: thread
+                .operand_stack
+                .iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?,
+            runtime_stack: thread
+                .runtime_stack
+                .iter()
+                .map(|frame| self.frame(frame))
+                .collect::<Result<_>>()?,
+            pc: thread.pc,
+            held_semaphores: thread
+                .held_semaphores
+                .iter()
+                .map(|s| self.semaphore(s))
+                .collect::<Result<_>>()?,
+            instr_count: thread.instr_count,
+            locals: thread
+                .locals
+                .iter()
+                .map(|(key, v)| Ok((key.clone(), self.value(v)?)))
+                .collect::<Result<_>>()?,
+            priority: thread.priority,
+        })
+    }
+
+    fn wake_source(&mut self, source: &WakeSource, now: Duration) -> Result<WakeSourceSnapshot> {
+        let source = match source {
+            WakeSource::Semaphore { sem, addr } => WakeSourceSnapshot::Semaphore {
+                sem: self.semaphore(sem)?,
+                addr: *addr,
+            },
+            WakeSource::CondVar { cv, mutex } => WakeSourceSnapshot::CondVar {
+                cv: self.cond_var(cv),
+                mutex: self.semaphore(mutex)?,
+            },
+            WakeSource::Barrier(barrier) => WakeSourceSnapshot::Barrier(self.barrier(barrier)?),
+            WakeSource::WaitGroup(wg) => WakeSourceSnapshot::WaitGroup(self.wait_group(wg)?),
+            WakeSource::RwLock { lock, write } => WakeSourceSnapshot::RwLock {
+                lock: self.rwlock(lock)?,
+                write: *write,
+            },
+            WakeSource::Timeout(deadline) => {
+                WakeSourceSnapshot::Timeout(deadline.saturating_sub(now))
+            }
+            WakeSource::ChannelSend { ch, val, addr } => WakeSourceSnapshot::ChannelSend {
+                ch: self.channel(ch)?,
+                val: self.value(val)?,
+                addr: *addr,
+            },
+            WakeSource::ChannelRecv { ch, addr } => WakeSourceSnapshot::ChannelRecv {
+                ch: self.channel(ch)?,
+                addr: *addr,
+            },
+            WakeSource::QueuePush(q) => WakeSourceSnapshot::QueuePush(self.bounded_queue(q)?),
+            WakeSource::QueuePop(q) => WakeSourceSnapshot::QueuePop(self.bounded_queue(q)?),
+            // Futures of the host live in the host, and cannot be saved
+            WakeSource::Host => {
+                return Err(
+                    VmError::InvalidSnapshot("a thread is waiting on the host".to_string()).into(),
+                )
+            }
+        };
+
+        Ok(source)
+    }
+
+    fn value(&mut self, val: &Value) -> Result<ValueSnapshot> {
+        let val = match val {
+            Value::Unitialized => ValueSnapshot::Unitialized,
+            Value::Unit => ValueSnapshot::Unit,
+            Value::Int(i) => ValueSnapshot::Int(*i),
+            Value::Float(f) => ValueSnapshot::Float(*f),
+            Value::Bool(b) => ValueSnapshot::Bool(*b),
+            Value::String(s) => ValueSnapshot::String(s.to_string()),
+            Value::Semaphore(sem) => ValueSnapshot::Semaphore(self.semaphore(sem)?),
+            Value::CondVar(cv) => ValueSnapshot::CondVar(self.cond_var(cv)),
+            Value::Barrier(barrier) => ValueSnapshot::Barrier(self.barrier(barrier)?),
+            Value::WaitGroup(wg) => ValueSnapshot::WaitGroup(self.wait_group(wg)?),
+            Value::RwLock(rw) => ValueSnapshot::RwLock(self.rwlock(rw)?),
+            Value::AtomicInt(a) => ValueSnapshot::AtomicInt(self.atomic_int(a)?),
+            Value::Closure(closure) => ValueSnapshot::Closure {
+                builtin: closure.fn_type == FnType::Builtin,
+                sym: closure.sym.to_string(),
+                prms: closure.prms.iter().map(|s| s.to_string()).collect(),
+                addr: closure.addr,
+                env: self.env_ref(&closure.env.0),
+            },
+            Value::Variant(variant) => match &**variant {
+                Variant::Some(val) => ValueSnapshot::Some(Box::new(self.value(val)?)),
+                Variant::None => ValueSnapshot::None,
+                Variant::Ok(val) => ValueSnapshot::Ok(Box::new(self.value(val)?)),
+                Variant::Err(val) => ValueSnapshot::Err(Box::new(self.value(val)?)),
+            },
+            Value::StructType(ty) => ValueSnapshot::StructType(self.struct_type(ty)?),
+            Value::Struct(instance) => ValueSnapshot::Struct {
+                ty: self.struct_type(&instance.ty)?,
+                fields: instance
+                    .fields
+                    .iter()
+                    .map(|v| self.value(v))
+                    .collect::<Result<_>>()?,
+            },
+            Value::Enum(variant) => ValueSnapshot::Enum {
+                ty: variant.ty.to_string(),
+                variant: variant.variant.to_string(),
+                payload: match &variant.payload {
+                    Some(val) => Some(Box::new(self.value(val)?)),
+                    None => None,
+                },
+            },
+            Value::Array(elems) => {
+                ValueSnapshot::Array(elems.iter().map(|v| self.value(v)).collect::<Result<_>>()?)
+            }
+            Value::Iter(iter) => ValueSnapshot::Iter(self.iter(iter)?),
+            Value::Channel(ch) => ValueSnapshot::Channel(self.channel(ch)?),
+            Value::BoundedQueue(q) => ValueSnapshot::BoundedQueue(self.bounded_queue(q)?),
+            Value::Generator(gen) => ValueSnapshot::Generator(self.generator(gen)?),
+        };
+
+        Ok(val)
+    }
+
+    fn iter(&mut self, iter: &Iter) -> Result<usize> {
+        let ptr = Rc::as_ptr(&iter.0);
+        if let Some(idx) = self.iters.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        let state = match &*iter.0.borrow() {
+            IterState::Range { next, end } => IterSnapshot::Range {
+                next: *next,
+                end: *end,
+            },
+            IterState::Array { elems, idx } => IterSnapshot::Array {
+                elems: elems.iter().map(|v| self.value(v)).collect::<Result<_>>()?,
+                idx: *idx,
+            },
+            IterState::Chars { s, idx } => IterSnapshot::Chars {
+                s: s.to_string(),
+                idx: *idx,
+            },
+        };
+
+        let idx = self.iter_values.len();
+        self.iter_values.push(state);
+        self.iters.insert(ptr, idx);
+        Ok(idx)
+    }
+
+    fn generator(&mut self, gen: &Generator) -> Result<usize> {
+        let ptr = Rc::as_ptr(&gen.0);
+        if let Some(idx) = self.generators.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        // The index is taken before the operands are flattened, in case a generator yields itself
+        let idx = self.generator_values.len();
+        self.generators.insert(ptr, idx);
+        let state = gen.0.borrow();
+        self.generator_values.push(GeneratorSnapshot {
+            status: state.status,
+            pc: state.pc,
+            env: self.env_ref(&state.env.0),
+            operand_stack: vec![],
+            runtime_stack: vec![],
+            done_addr: state.done_addr,
+        });
+
+        let operand_stack = state
+            .operand_stack
+            .iter()
+            .map(|v| self.value(v))
+            .collect::<Result<_>>()?;
+        let runtime_stack = state
+            .runtime_stack
+            .iter()
+            .map(|frame| self.frame(frame))
+            .collect::<Result<_>>()?;
+        self.generator_values[idx].operand_stack = operand_stack;
+        self.generator_values[idx].runtime_stack = runtime_stack;
+        Ok(idx)
+    }
+
+    fn semaphore(&mut self, sem: &Semaphore) -> Result<usize> {
+        let ptr = std::sync::Arc::as_ptr(&sem.0);
+        if let Some(idx) = self.semaphores.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        let idx = self.semaphore_values.len();
+        self.semaphore_values.push(*poisoned(sem.lock())?);
+        self.semaphores.insert(ptr, idx);
+        Ok(idx)
+    }
+
+    fn cond_var(&mut self, cv: &CondVar) -> usize {
+        let next = self.cond_vars.len();
+        *self
+            .cond_vars
+            .entry(std::sync::Arc::as_ptr(&cv.0))
+            .or_insert(next)
+    }
+
+    fn barrier(&mut self, barrier: &Barrier) -> Result<usize> {
+        let ptr = std::sync::Arc::as_ptr(&barrier.0);
+        if let Some(idx) = self.barriers.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        let state = poisoned(barrier.lock())?;
+        let idx = self.barrier_values.len();
+        self.barrier_values.push((state.parties, state.arrived));
+        self.barriers.insert(ptr, idx);
+        Ok(idx)
+    }
+
+    fn struct_type(&mut self, ty: &Rc<StructType>) -> Result<usize> {
+        let ptr = Rc::as_ptr(ty);
+        if let Some(idx) = self.struct_types.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        // The index is taken before the methods are flattened, in case they refer back to the type
+        let idx = self.struct_type_values.len();
+        self.struct_types.insert(ptr, idx);
+        self.struct_type_values.push(StructTypeSnapshot {
+            name: ty.name.to_string(),
+            fields: ty.fields.iter().map(|f| f.to_string()).collect(),
+            methods: vec![],
+        });
+
+        let mut methods = vec![];
+        for (sym, method) in ty.methods.borrow().iter() {
+            methods.push((sym.to_string(), self.value(method)?));
+        }
+        self.struct_type_values[idx].methods = methods;
+        Ok(idx)
+    }
+
+    fn channel(&mut self, ch: &Channel) -> Result<usize> {
+        let ptr = Rc::as_ptr(&ch.0);
+        if let Some(idx) = self.channels.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        // The index is taken before the values are flattened, in case a channel is sent on itself
+        let idx = self.channel_values.len();
+        self.channels.insert(ptr, idx);
+        let state = ch.0.borrow();
+        self.channel_values.push(ChannelSnapshot {
+            cap: state.cap,
+            buf: vec![],
+            closed: state.closed,
+        });
+
+        let buf = state
+            .buf
+            .iter()
+            .map(|v| self.value(v))
+            .collect::<Result<_>>()?;
+        self.channel_values[idx].buf = buf;
+        Ok(idx)
+    }
+
+    fn bounded_queue(&mut self, q: &BoundedQueue) -> Result<usize> {
+        let ptr = Rc::as_ptr(&q.0);
+        if let Some(idx) = self.bounded_queues.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        // The index is taken before the values are flattened, in case a queue is pushed to itself
+        let idx = self.bounded_queue_values.len();
+        self.bounded_queues.insert(ptr, idx);
+        let state = q.0.borrow();
+        self.bounded_queue_values.push(BoundedQueueSnapshot {
+            cap: state.cap,
+            buf: vec![],
+        });
+
+        let buf = state
+            .buf
+            .iter()
+            .map(|v| self.value(v))
+            .collect::<Result<_>>()?;
+        self.bounded_queue_values[idx].buf = buf;
+        Ok(idx)
+    }
+
+    fn wait_group(&mut self, wg: &WaitGroup) -> Result<usize> {
+        let ptr = std::sync::Arc::as_ptr(&wg.0);
+        if let Some(idx) = self.wait_groups.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        let idx = self.wait_group_values.len();
+        self.wait_group_values.push(poisoned(wg.lock())?.count);
+        self.wait_groups.insert(ptr, idx);
+        Ok(idx)
+    }
+
+    fn rwlock(&mut self, rw: &RwLock) -> Result<usize> {
+        let ptr = std::sync::Arc::as_ptr(&rw.0);
+        if let Some(idx) = self.rwlocks.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        let state = poisoned(rw.lock())?;
+        let idx = self.rwlock_values.len();
+        self.rwlock_values.push((state.readers, state.writer));
+        self.rwlocks.insert(ptr, idx);
+        Ok(idx)
+    }
+
+    fn atomic_int(&mut self, a: &AtomicInt) -> Result<usize> {
+        let ptr = std::sync::Arc::as_ptr(&a.0);
+        if let Some(idx) = self.atomic_ints.get(&ptr) {
+            return Ok(*idx);
+        }
+
+        let idx = self.atomic_int_values.len();
+        self.atomic_int_values.push(*poisoned(a.lock())?);
+        self.atomic_ints.insert(ptr, idx);
+        Ok(idx)
+    }
+}
+
+fn poisoned<T>(result: std::sync::LockResult<T>) -> Result<T> {
+    result.map_err(|_| VmError::InvalidSnapshot("lock poisoned".to_string()).into())
+}
+
+/// Recreates the shared objects of a snapshot, then resolves references to them by index.
+#[derive(Default)]
+struct Restorer {
+    envs: Vec<Rc<RefCell<Environment>>>,
+    semaphores: Vec<Semaphore>,
+    cond_vars: Vec<CondVar>,
+    barriers: Vec<Barrier>,
+    wait_groups: Vec<WaitGroup>,
+    rwlocks: Vec<RwLock>,
+    atomic_ints: Vec<AtomicInt>,
+    channels: Vec<Channel>,
+    bounded_queues: Vec<BoundedQueue>,
+    struct_types: Vec<Rc<StructType>>,
+    iters: Vec<Iter>,
+    generators: Vec<Generator>,
+}
+
+impl Restorer {
+    fn restore(mut self, snapshot: Snapshot) -> Result<Runtime> {
+        let instrs = read_bytecode(&mut snapshot.instrs.as_slice())?;
+
+        self.semaphores = snapshot
+            .semaphores
+            .into_iter()
+            .map(Semaphore::new)
+            .collect();
+        self.cond_vars = (0..snapshot.cond_vars).map(|_| CondVar::new()).collect();
+        self.barriers = snapshot
+            .barriers
+            .into_iter()
+            .map(|(parties, arrived)| {
+                let barrier = Barrier::new(parties);
+                barrier.lock().expect("New barrier is not poisoned").arrived = arrived;
+                barrier
+            })
+            .collect();
+        self.wait_groups = snapshot
+            .wait_groups
+            .into_iter()
+            .map(|count| {
+                let wg = WaitGroup::new();
+                wg.lock().expect("New wait group is not poisoned").count = count;
+                wg
+            })
+            .collect();
+        self.rwlocks = snapshot
+            .rwlocks
+            .into_iter()
+            .map(|(readers, writer)| {
+                let rw = RwLock::new();
+                *rw.lock().expect("New rwlock is not poisoned") = RwLockState { readers, writer };
+                rw
+            })
+            .collect();
+        self.atomic_ints = snapshot
+            .atomic_ints
+            .into_iter()
+            .map(AtomicInt::new)
+            .collect();
+
+        // Channels and queues may hold any value, so they are filled in once everything else is allocated
+        self.channels = snapshot
+            .channels
+            .iter()
+            .map(|ch| {
+                let channel = Channel::new(ch.cap);
+                channel.0.borrow_mut().closed = ch.closed;
+                channel
+            })
+            .collect();
+        self.bounded_queues = snapshot
+            .bounded_queues
+            .iter()
+            .map(|q| BoundedQueue::new(q.cap))
+            .collect();
+
+        // Generators may hold any value, including themselves, so they are filled in like channels
+        self.generators = snapshot
+            .generators
+            .iter()
+            .map(|_| Generator::new(0, W(Weak::new())))
+            .collect();
+
+        // Environments refer to each other, so all of them are allocated before any is filled in
+        self.envs = snapshot
+            .envs
+            .iter()
+            .map(|_| Environment::new_wrapped())
+            .collect();
+
+        // Methods are closures over environments and may refer to struct types, so the same applies
+        self.struct_types = snapshot
+            .struct_types
+            .iter()
+            .map(|ty| {
+                let fields = ty
+                    .fields
+                    .iter()
+                    .map(|f| Symbol::try_intern(f))
+                    .collect::<Result<_, _>>()?;
+                Ok(Rc::new(StructType::new(
+                    Symbol::try_intern(&ty.name)?,
+                    fields,
+                )))
+            })
+            .collect::<Result<_>>()?;
+
+        for (idx, ty) in snapshot.struct_types.into_iter().enumerate() {
+            for (sym, method) in ty.methods {
+                let method = self.value(method)?;
+                self.struct_types[idx]
+                    .methods
+                    .borrow_mut()
+                    .insert(Symbol::try_intern(&sym)?, method);
+            }
+        }
+
+        // Iterators only hold values that can be restored now, and are held by the slots of environments
+        for iter in snapshot.iters {
+            let state = match iter {
+                IterSnapshot::Range { next, end } => IterState::Range { next, end },
+                IterSnapshot::Array { elems, idx } => IterState::Array {
+                    elems: Rc::new(
+                        elems
+                            .into_iter()
+                            .map(|v| self.value(v))
+                            .collect::<Result<Vec<_>>>()?
+                            .into(),
+                    ),
+                    idx,
+                },
+                IterSnapshot::Chars { s, idx } => IterState::Chars { s: Rc::new(s), idx },
+            };
+            self.iters.push(state.into());
+        }
+
+        for (idx, ch) in snapshot.channels.into_iter().enumerate() {
+            let buf = ch
+                .buf
+                .into_iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?;
+            self.channels[idx].0.borrow_mut().buf = buf;
+        }
+
+        for (idx, q) in snapshot.bounded_queues.into_iter().enumerate() {
+            let buf = q
+                .buf
+                .into_iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?;
+            self.bounded_queues[idx].0.borrow_mut().buf = buf;
+        }
+
+        for (idx, gen) in snapshot.generators.into_iter().enumerate() {
+            let operand_stack = gen
+                .operand_stack
+                .into_iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?;
+            let runtime_stack = gen
+                .runtime_stack
+                .into_iter()
+                .map(|frame| self.frame(frame))
+                .collect::<Result<_>>()?;
+
+            *self.generators[idx].0.borrow_mut() = GeneratorState {
+                status: gen.status,
+                pc: gen.pc,
+                env: W(self.env_ref(gen.env)?),
+                operand_stack,
+                runtime_stack,
+                done_addr: gen.done_addr,
+            };
+        }
+
+        for (idx, env) in snapshot.envs.into_iter().enumerate() {
+            let parent = env.parent.map(|p| self.env_ref(Some(p))).transpose()?;
+
+            let mut named = HashMap::new();
+            for (sym, val) in env.env {
+                named.insert(Symbol::try_intern(&sym)?, self.value(val)?);
+            }
+
+            let slots = env
+                .slots
+                .into_iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?;
+
+            *self.envs[idx].borrow_mut() = Environment {
+                parent,
+                env: named,
+                syms: env.syms.into_iter().map(Into::into).collect(),
+                slots,
+            };
+        }
+
+        let mut rt = Runtime::new(instrs);
+        rt.env_registry = self.envs.iter().map(|env| W(Rc::clone(env))).collect();
+        rt.done = snapshot.done;
+        rt.thread_count = snapshot.thread_count;
+        rt.instr_count = snapshot.instr_count;
+        rt.current_thread = self.thread(snapshot.current_thread)?;
+
+        for thread in snapshot.ready_queue {
+            let thread = self.thread(thread)?;
+            rt.ready_queue.push_back(thread);
+        }
+
+        let now = rt.now();
+        for (thread, sources) in snapshot.blocked_queue {
+            let thread = self.thread(thread)?;
+            let sources = sources
+                .into_iter()
+                .map(|source| self.wake_source(source, now))
+                .collect::<Result<_>>()?;
+            rt.blocked_queue.push_back((thread, sources));
+        }
+
+        for thread in snapshot.zombie_threads {
+            let thread = self.thread(thread)?;
+            rt.zombie_threads.insert(thread.thread_id, thread);
+        }
+
+        for (tid, scopes) in snapshot.spawn_scopes {
+            let scopes = scopes
+                .into_iter()
+                .map(|scope| SpawnScope {
+                    depth: scope.depth,
+                    threads: scope.threads,
+                })
+                .collect();
+            rt.spawn_scopes.insert(tid, scopes);
+        }
+
+        rt.thread_states = snapshot.thread_states.into_iter().collect();
+        rt.timer_queue = snapshot
+            .timer_queue
+            .into_iter()
+            .map(|(remaining, tid)| Reverse((now + remaining, tid)))
+            .collect();
+
+        Ok(rt)
+    }
+
+    /// Get a weak reference to the environment at the index, or a dangling one if there is none.
+    fn env_ref(&self, idx: Option<usize>) -> Result<Weak<RefCell<Environment>>> {
+        let Some(idx) = idx else {
+            return Ok(Weak::new());
+        };
+
+        let env = self
+            .envs
+            .get(idx)
+            .ok_or_else(|| VmError::InvalidSnapshot(format!("no environment {}", idx)))?;
+
+        Ok(weak_clone(env))
+    }
+
+    fn frame(&self, frame: FrameSnapshot) -> Result<StackFrame> {
+        Ok(StackFrame {
+            frame_type: frame.frame_type,
+            address: frame.address,
+            env: W(self.env_ref(frame.env)?),
+            sym: frame.sym.map(Into::into),
+            operand_len: frame.operand_len,
+            lock: frame.lock.map(|v| self.value(v)).transpose()?,
+        })
+    }
+
+    fn thread(&self, thread: ThreadSnapshot) -> Result<Thread> {
+        Ok(Thread {
+            thread_id: thread.thread_id,
+            env: self.env_ref(thread.env)?,
+            operand_stack: thread
+                .operand_stack
+                .into_iter()
+                .map(|v| self.value(v))
+                .collect::<Result<_>>()?,
+            runtime_stack: thread
+                .runtime_stack
+                .into_iter()
+                .map(|frame| self.frame(frame))
+                .collect::<Result<_>>()?,
+            pc: thread.pc,
+            held_semaphores: thread
+                .held_semaphores
+                .into_iter()
+                .map(|idx| self.semaphore(idx))
+                .collect::<Result<_>>()?,
+            instr_count: thread.instr_count,
+            locals: thread
+                .locals
+                .into_iter()
+                .map(|(key, v)| Ok((key, self.value(v)?)))
+                .collect::<Result<_>>()?,
+            priority: thread.priority,
+        })
+    }
+
+    fn wake_source(&self, source: WakeSourceSnapshot, now: Duration) -> Result<WakeSource> {
+        let source = match source {
+            WakeSourceSnapshot::Semaphore { sem, addr } => WakeSource::Semaphore {
+                sem: self.semaphore(sem)?,
+                addr,
+            },
+            WakeSourceSnapshot::CondVar { cv, mutex } => WakeSource::CondVar {
+                cv: get(&self.cond_vars, cv, "condition variable")?,
+                mutex: self.semaphore(mutex)?,
+            },
+            WakeSourceSnapshot::Barrier(idx) => {
+                WakeSource::Barrier(get(&self.barriers, idx, "barrier")?)
+            }
+            WakeSourceSnapshot::WaitGroup(idx) => {
+                WakeSource::WaitGroup(get(&self.wait_groups, idx, "wait group")?)
+            }
+            WakeSourceSnapshot::RwLock { lock, write } => WakeSource::RwLock {
+                lock: get(&self.rwlocks, lock, "rwlock")?,
+                write,
+            },
+            WakeSourceSnapshot::ChannelSend { ch, val, addr } => WakeSource::ChannelSend {
+                ch: get(&self.channels, ch, "channel")?,
+                val: self.value(val)?,
+                addr,
+            },
+            WakeSourceSnapshot::ChannelRecv { ch, addr } => WakeSource::ChannelRecv {
+                ch: get(&self.channels, ch, "channel")?,
+                addr,
+            },
+            WakeSourceSnapshot::QueuePush(idx) => {
+                WakeSource::QueuePush(get(&self.bounded_queues, idx, "bounded queue")?)
+            }
+            WakeSourceSnapshot::QueuePop(idx) => {
+                WakeSource::QueuePop(get(&self.bounded_queues, idx, "bounded queue")?)
+            }
+            WakeSourceSnapshot::Timeout(remaining) => WakeSource::Timeout(now + remaining),
+        };
+
+        Ok(source)
+    }
+
+    fn value(&self, val: ValueSnapshot) -> Result<Value> {
+        let val = match val {
+            ValueSnapshot::Unitialized => Value::Unitialized,
+            ValueSnapshot::Unit => Value::Unit,
+            ValueSnapshot::Int(i) => Value::Int(i),
+            ValueSnapshot::Float(f) => Value::Float(f),
+            ValueSnapshot::Bool(b) => Value::Bool(b),
+            ValueSnapshot::String(s) => Value::from(s),
+            ValueSnapshot::Semaphore(idx) => Value::Semaphore(self.semaphore(idx)?),
+            ValueSnapshot::CondVar(idx) => {
+                Value::CondVar(get(&self.cond_vars, idx, "condition variable")?)
+            }
+            ValueSnapshot::Barrier(idx) => Value::Barrier(get(&self.barriers, idx, "barrier")?),
+            ValueSnapshot::WaitGroup(idx) => {
+                Value::WaitGroup(get(&self.wait_groups, idx, "wait group")?)
+            }
+            ValueSnapshot::RwLock(idx) => Value::RwLock(get(&self.rwlocks, idx, "rwlock")?),
+            ValueSnapshot::AtomicInt(idx) => {
+                Value::AtomicInt(get(&self.atomic_ints, idx, "atomic int")?)
+            }
+            ValueSnapshot::Channel(idx) => Value::Channel(get(&self.channels, idx, "channel")?),
+            ValueSnapshot::BoundedQueue(idx) => {
+                Value::BoundedQueue(get(&self.bounded_queues, idx, "bounded queue")?)
+            }
+            ValueSnapshot::Closure {
+                builtin,
+                sym,
+                prms,
+                addr,
+                env,
+            } => Closure {
+                fn_type: if builtin {
+                    FnType::Builtin
+                } else {
+                    FnType::User
+                },
+                sym: Symbol::try_intern(&sym)?,
+                prms: prms
+                    .iter()
+                    .map(|prm| Symbol::try_intern(prm))
+                    .collect::<Result<_, _>>()?,
+                addr,
+                env: W(self.env_ref(env)?),
+            }
+            .into(),
+            ValueSnapshot::Some(val) => Variant::Some(self.value(*val)?).into(),
+            ValueSnapshot::None => Variant::None.into(),
+            ValueSnapshot::Ok(val) => Variant::Ok(self.value(*val)?).into(),
+            ValueSnapshot::Err(val) => Variant::Err(self.value(*val)?).into(),
+            ValueSnapshot::StructType(idx) => {
+                Value::StructType(get(&self.struct_types, idx, "struct type")?)
+            }
+            ValueSnapshot::Struct { ty, fields } => Struct {
+                ty: get(&self.struct_types, ty, "struct type")?,
+                fields: fields
+                    .into_iter()
+                    .map(|v| self.value(v))
+                    .collect::<Result<_>>()?,
+            }
+            .into(),
+            ValueSnapshot::Enum {
+                ty,
+                variant,
+                payload,
+            } => Enum {
+                ty: Symbol::try_intern(&ty)?,
+                variant: Symbol::try_intern(&variant)?,
+                payload: match payload {
+                    Some(val) => Some(self.value(*val)?),
+                    None => None,
+                },
+            }
+            .into(),
+            ValueSnapshot::Array(elems) => elems
+                .into_iter()
+                .map(|v| self.value(v))
+                .collect::<Result<Vec<_>>>()?
+                .into(),
+            ValueSnapshot::Iter(idx) => Value::Iter(get(&self.iters, idx, "iterator")?),
+            ValueSnapshot::Generator(idx) => {
+                Value::Generator(get(&self.generators, idx, "generator")?)
+            }
+        };
+
+        Ok(val)
+    }
+
+    fn semaphore(&self, idx: usize) -> Result<Semaphore> {
+        get(&self.semaphores, idx, "semaphore")
+    }
+}
+
+fn get<T: Clone>(objs: &[T], idx: usize, kind: &str) -> Result<T> {
+    objs.get(idx)
+        .cloned()
+        .ok_or_else(|| VmError::InvalidSnapshot(format!("no {} {}", kind, idx)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::ByteCode;
+    use compiler::compiler::compile_from_string;
+
+    use crate::{run, step, MAIN_THREAD_ID};
+
+    use super::*;
+
+    fn program() -> Result<Vec<ByteCode>> {
+        let t = r"
+        let s = sem_create();
+        let total = 0;
+
+        fn add(n: int) {
+            wait s;
+            total = total + n;
+            post s;
+        }
+
+        wait s;
+        let h1 = spawn add(1);
+        let h2 = spawn add(2);
+        yield;
+        post s;
+        join h1;
+        join h2;
+        total
+        ";
+
+        compile_from_string(t, true)
+    }
+
+    #[test]
+    fn test_snapshot_resume() -> Result<()> {
+        let instrs = program()?;
+
+        let mut rt = Runtime::new(instrs.clone());
+        run(&mut rt)?;
+        let steps = rt.instr_count;
+
+        // Pausing anywhere and resuming from the snapshot gives the same result
+        for n in 0..steps {
+            let mut rt = Runtime::new(instrs.clone());
+            while rt.instr_count < n {
+                step(&mut rt)?;
+            }
+
+            let mut bytes = vec![];
+            rt.save_snapshot(&mut bytes)?;
+            drop(rt);
+
+            let mut rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+            assert_eq!(rt.instr_count, n);
+            run(&mut rt)?;
+            assert_eq!(rt.current_thread.operand_stack.last(), Some(&Value::Int(3)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_generators() -> Result<()> {
+        let t = r"
+        fn evens(n: int) {
+            let i = 0;
+            loop i < n {
+                yield i * 2;
+                i = i + 1;
+            }
+        }
+
+        let total = 0;
+        for x in evens(4) {
+            total = total + x;
+        }
+        total
+        ";
+        let instrs = compile_from_string(t, true)?;
+
+        let mut rt = Runtime::new(instrs.clone());
+        run(&mut rt)?;
+        let steps = rt.instr_count;
+
+        // Pausing while the generator is suspended or running resumes it where it was
+        for n in 0..steps {
+            let mut rt = Runtime::new(instrs.clone());
+            while rt.instr_count < n {
+                step(&mut rt)?;
+            }
+
+            let mut bytes = vec![];
+            rt.save_snapshot(&mut bytes)?;
+            drop(rt);
+
+            let mut rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+            run(&mut rt)?;
+            assert_eq!(
+                rt.current_thread.operand_stack.last(),
+                Some(&Value::Int(12))
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_shared_objects() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let sem = Semaphore::new(5);
+        let atomic = AtomicInt::new(-3);
+        rt.current_thread.operand_stack = vec![
+            Value::Semaphore(sem.clone()),
+            Value::Semaphore(sem),
+            Value::from("hello"),
+            Value::AtomicInt(atomic.clone()),
+            Value::AtomicInt(atomic),
+        ];
+
+        let mut bytes = vec![];
+        rt.save_snapshot(&mut bytes)?;
+        let rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+
+        let [Value::Semaphore(a), Value::Semaphore(b), s, Value::AtomicInt(x), Value::AtomicInt(y)] =
+            &rt.current_thread.operand_stack[..]
+        else {
+            panic!("Expected two semaphores, a string and two atomic ints");
+        };
+        assert_eq!(a, b);
+        assert_eq!(*a.lock().unwrap(), 5);
+        assert_eq!(s, &Value::from("hello"));
+        assert_eq!(x, y);
+        assert_eq!(*x.lock().unwrap(), -3);
+
+        // The environment of the thread is restored with its builtins
+        let env = rt
+            .current_thread
+            .env
+            .upgrade()
+            .expect("Environment is alive");
+        assert!(env.borrow().get(bytecode::builtin::ABS_SYM).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_spawn_scopes() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let scope = SpawnScope {
+            depth: 1,
+            threads: vec![2, 3],
+        };
+        rt.spawn_scopes.insert(MAIN_THREAD_ID, vec![scope.clone()]);
+
+        let mut bytes = vec![];
+        rt.save_snapshot(&mut bytes)?;
+        let rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+
+        assert_eq!(rt.spawn_scopes[&MAIN_THREAD_ID], vec![scope]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_structs() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let ty = Rc::new(StructType::new("P".into(), vec!["x".into()]));
+        ty.methods.borrow_mut().insert("f".into(), Value::Int(1));
+        let p = Struct {
+            ty: ty.clone(),
+            fields: vec![2.into()],
+        };
+        rt.current_thread.operand_stack = vec![Value::StructType(ty), p.into()];
+
+        let mut bytes = vec![];
+        rt.save_snapshot(&mut bytes)?;
+        let rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+
+        let [Value::StructType(ty), Value::Struct(p)] = &rt.current_thread.operand_stack[..] else {
+            panic!("Expected a struct type and a struct");
+        };
+        // The struct still has the type it was created from, with its methods
+        assert!(Rc::ptr_eq(ty, &p.ty));
+        assert_eq!(ty.name, "P".into());
+        assert_eq!(ty.methods.borrow().get(&"f".into()), Some(&Value::Int(1)));
+        assert_eq!(p.field("x".into()), Some(&Value::Int(2)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_channels() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let ch = Channel::new(3);
+        let closed = Channel::new(1);
+        closed.0.borrow_mut().closed = true;
+        // A channel can carry channels, including itself
+        ch.0.borrow_mut()
+            .buf
+            .extend([Value::Int(1), Value::from("a"), Value::Channel(ch.clone())]);
+        rt.current_thread.operand_stack = vec![Value::Channel(ch.clone()), Value::Channel(closed)];
+
+        let receiver = Thread::new(2, rt.current_thread.env.clone());
+        rt.blocked_queue
+            .push_back((receiver, vec![WakeSource::ChannelRecv { ch, addr: None }]));
+
+        let mut bytes = vec![];
+        rt.save_snapshot(&mut bytes)?;
+        let rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+
+        let [Value::Channel(ch), Value::Channel(closed)] = &rt.current_thread.operand_stack[..]
+        else {
+            panic!("Expected two channels");
+        };
+        assert!(closed.0.borrow().closed);
+
+        let state = ch.0.borrow();
+        assert_eq!(state.cap, 3);
+        assert!(!state.closed);
+        assert_eq!(
+            state.buf,
+            [Value::Int(1), Value::from("a"), Value::Channel(ch.clone())]
+        );
+
+        // The blocked receiver waits on the same channel
+        let (_, sources) = &rt.blocked_queue[0];
+        assert!(sources[0].is_channel_recv(ch));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_bounded_queues() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let q = BoundedQueue::new(2);
+        q.0.borrow_mut()
+            .buf
+            .extend([Value::Int(1), Value::from(vec![Value::Bool(true)])]);
+        rt.current_thread.operand_stack = vec![
+            Value::BoundedQueue(q.clone()),
+            Value::BoundedQueue(q.clone()),
+        ];
+
+        // The queue is full, so a pusher is blocked with the value it pushes on its operand stack
+        let mut pusher = Thread::new(2, rt.current_thread.env.clone());
+        pusher.operand_stack.push(Value::Int(3));
+        rt.blocked_queue
+            .push_back((pusher, vec![WakeSource::QueuePush(q)]));
+
+        let mut bytes = vec![];
+        rt.save_snapshot(&mut bytes)?;
+        let rt = Runtime::load_snapshot(&mut bytes.as_slice())?;
+
+        let [Value::BoundedQueue(a), Value::BoundedQueue(b)] = &rt.current_thread.operand_stack[..]
+        else {
+            panic!("Expected two bounded queues");
+        };
+        assert_eq!(a, b);
+        assert_eq!(a.0.borrow().cap, 2);
+        assert_eq!(
+            a.0.borrow().buf,
+            [Value::Int(1), Value::from(vec![Value::Bool(true)])]
+        );
+
+        let (pusher, sources) = &rt.blocked_queue[0];
+        assert_eq!(pusher.operand_stack, vec![Value::Int(3)]);
+        assert!(sources[0].is_queue_push(a));
+
+        Ok(())
+    }
+}
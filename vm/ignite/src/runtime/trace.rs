@@ -0,0 +1,84 @@
+use std::fmt::Display;
+
+use bytecode::{FrameType, Symbol, ThreadID};
+
+use crate::{Runtime, RuntimeErrorContext};
+
+/// The number of frames shown at each end of a stack trace that is too long to print in full.
+const TRACE_ELISION_THRESHOLD: usize = 10;
+
+/// A frame of a stack trace, the function that was executing and the PC it was at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFrame {
+    /// The name of the function, or `None` for the top level of the thread.
+    pub sym: Option<Symbol>,
+    pub pc: usize,
+}
+
+/// The call frames of a thread at the point a runtime error occurred, innermost first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackTrace {
+    pub frames: Vec<TraceFrame>,
+}
+
+impl Display for StackTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elided = self
+            .frames
+            .len()
+            .saturating_sub(2 * TRACE_ELISION_THRESHOLD);
+
+        let mut lines = vec![];
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            if elided > 0 && i == TRACE_ELISION_THRESHOLD {
+                lines.push(format!("    ... {} frames omitted", elided));
+            }
+
+            if (TRACE_ELISION_THRESHOLD..TRACE_ELISION_THRESHOLD + elided).contains(&i) {
+                continue;
+            }
+
+            match frame.sym {
+                Some(sym) => lines.push(format!("    at {} (pc={})", sym, frame.pc)),
+                None => lines.push(format!("    at <top level> (pc={})", frame.pc)),
+            }
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Runtime {
+    /// Build a stack trace of the current thread, whose innermost frame is at the given PC.
+    /// The PC of the other frames is the CALL instruction that created the frame above it.
+    pub fn stack_trace(&self, mut pc: usize) -> StackTrace {
+        let mut frames = vec![];
+
+        for frame in self.current_thread.runtime_stack.iter().rev() {
+            if frame.frame_type != FrameType::CallFrame {
+                continue;
+            }
+
+            frames.push(TraceFrame { sym: frame.sym, pc });
+
+            if let Some(address) = frame.address {
+                pc = address.saturating_sub(1);
+            }
+        }
+
+        frames.push(TraceFrame { sym: None, pc });
+
+        StackTrace { frames }
+    }
+
+    /// Build the context of an error that occurred while the given thread was at the given PC.
+    pub fn error_context(&self, thread_id: ThreadID, pc: usize) -> RuntimeErrorContext {
+        RuntimeErrorContext {
+            thread_id,
+            pc,
+            instr: self.instrs.get(pc).map(|instr| format!("{:?}", instr)),
+            trace: self.stack_trace(pc),
+        }
+    }
+}
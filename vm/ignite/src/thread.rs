@@ -1,10 +1,41 @@
-use std::{cell::RefCell, rc::Weak};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Weak};
 
 use anyhow::Result;
-use bytecode::{weak_clone, Environment, StackFrame, Symbol, ThreadID, Value, W};
+use bytecode::{weak_clone, Environment, Semaphore, StackFrame, Symbol, ThreadID, Value, W};
+use serde::{Deserialize, Serialize};
 
 use crate::{Runtime, VmError};
 
+/// The scheduling state of a thread, as tracked by the thread table of the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThreadState {
+    /// The thread is in the ready queue, waiting to be scheduled.
+    Ready,
+    /// The thread is the current thread of the runtime.
+    Running,
+    /// The thread is in the blocked queue, waiting on a semaphore.
+    Blocked,
+    /// The thread is in the blocked queue with a deadline in the timer queue, waiting on a semaphore
+    /// until it is posted or the deadline passes.
+    Sleeping,
+    /// The thread has executed DONE.
+    Done,
+}
+
+impl Display for ThreadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ThreadState::Ready => "ready",
+            ThreadState::Running => "running",
+            ThreadState::Blocked => "blocked",
+            ThreadState::Sleeping => "sleeping",
+            ThreadState::Done => "done",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
 /// A thread of execution.
 /// Each thread has its own environment, operand stack, runtime stack, and program counter.
 #[derive(Debug, Default, Clone)]
@@ -14,6 +45,16 @@ pub struct Thread {
     pub operand_stack: Vec<Value>,
     pub runtime_stack: Vec<StackFrame>,
     pub pc: usize,
+    /// The mutexes the thread holds in lock blocks, released if the thread is killed.
+    /// Semaphores it waits on outside of a lock block are not recorded, as the thread consumes them.
+    pub held_semaphores: Vec<Semaphore>,
+    /// The number of instructions the thread has executed, checked against the per-thread budget.
+    pub instr_count: u64,
+    /// The thread-local values of the thread by key, which no other thread sees.
+    pub locals: HashMap<String, Value>,
+    /// The priority the thread was given, higher runs first. It may run with a higher priority
+    /// donated by the threads waiting on the semaphores it holds, see `Runtime::effective_priority`.
+    pub priority: i64,
 }
 
 impl Thread {
@@ -28,7 +69,7 @@ impl Thread {
     }
 
     /// Create a new thread with the same environment as the current thread.
-    /// But operand stack and runtime stack are empty.
+    /// But operand stack and runtime stack are empty, it has no thread-local values yet and it has the default priority.
     pub fn spawn_child(&self, thread_id: i64, pc: usize) -> Self {
         Thread {
             thread_id,
@@ -36,17 +77,21 @@ impl Thread {
             operand_stack: Vec::new(),
             runtime_stack: Vec::new(),
             pc,
+            held_semaphores: Vec::new(),
+            instr_count: 0,
+            locals: HashMap::new(),
+            priority: 0,
         }
     }
 }
 
 #[inline]
 pub fn extend_environment<S, V>(
-    mut rt: Runtime,
+    rt: &mut Runtime,
     env: Weak<RefCell<Environment>>,
     syms: Vec<S>,
     vals: Vec<V>,
-) -> Result<Runtime>
+) -> Result<()>
 where
     S: Into<Symbol>,
     V: Into<Value>,
@@ -58,17 +103,17 @@ where
         .into());
     }
 
-    let new_env = Environment::new_wrapped();
+    let new_env = rt.alloc_env();
     new_env.borrow_mut().set_parent(env);
 
-    for (sym, val) in syms.into_iter().zip(vals.into_iter()) {
-        new_env.borrow_mut().set(sym, val);
+    for (sym, val) in syms.into_iter().zip(vals) {
+        new_env.borrow_mut().declare(sym, val);
     }
 
     rt.current_thread.env = weak_clone(&new_env);
     rt.env_registry.insert(W(new_env));
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -97,7 +142,7 @@ mod tests {
 
         let empty: Vec<String> = Vec::new();
         let current_env = rt.current_thread.env.clone();
-        let result = extend_environment(rt, current_env, vec!["c", "d"], empty);
+        let result = extend_environment(&mut rt, current_env, vec!["c", "d"], empty);
         assert!(result.is_err());
 
         Ok(())
@@ -123,50 +168,30 @@ mod tests {
             .set("b", 123);
 
         let current_env = rt.current_thread.env.clone();
-        let rt = extend_environment(
-            rt,
+        extend_environment(
+            &mut rt,
             current_env,
             vec!["c", "d"],
             vec![Value::Float(12.3), Value::Bool(true)],
         )?;
 
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"a".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("a")?,
             Value::Int(42)
         );
 
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"b".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("b")?,
             Value::Int(123)
         );
 
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"c".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("c")?,
             Value::Float(12.3)
         );
 
         assert_eq!(
-            rt.current_thread
-                .env
-                .upgrade()
-                .unwrap()
-                .borrow()
-                .get(&"d".to_string())?,
+            rt.current_thread.env.upgrade().unwrap().borrow().get("d")?,
             Value::Bool(true)
         );
 
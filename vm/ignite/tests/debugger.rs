@@ -0,0 +1,45 @@
+use anyhow::Result;
+use bytecode::{ByteCode, Value};
+use ignite::{Debugger, Runtime, StopReason, WatchEvent};
+
+// The debugger as an embedder drives it, through the exports of the crate
+#[test]
+fn breakpoints_and_watchpoints() -> Result<()> {
+    // let x = 1;
+    // x = 2;
+    // x
+    let instrs = vec![
+        ByteCode::enterscope(vec!["x"]),
+        ByteCode::ldc(1),
+        ByteCode::assign("x"),
+        ByteCode::ldc(2),
+        ByteCode::assign("x"),
+        ByteCode::ld("x"),
+        ByteCode::EXITSCOPE,
+        ByteCode::DONE,
+    ];
+
+    let mut dbg = Debugger::new(Runtime::new(instrs));
+    assert!(dbg.add_breakpoint(5));
+    assert!(dbg.add_watchpoint("x"));
+    assert_eq!(dbg.breakpoints().collect::<Vec<_>>(), vec![5]);
+    assert_eq!(dbg.watchpoints().collect::<Vec<_>>(), vec!["x".into()]);
+
+    assert_eq!(
+        dbg.cont()?,
+        StopReason::Watchpoint(WatchEvent {
+            sym: "x".into(),
+            thread_id: 1,
+            pc: 2,
+            old: Value::Unitialized,
+            new: Value::Int(1),
+        })
+    );
+
+    assert!(dbg.remove_watchpoint("x"));
+    assert_eq!(dbg.cont()?, StopReason::Breakpoint(5));
+    assert_eq!(dbg.cont()?, StopReason::Done);
+    assert_eq!(dbg.operand_stack(), &[Value::Int(2)]);
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use assert_cmd::prelude::*;
-use compiler::compiler::compile_from_string;
+use compiler::{compiler::compile_from_string, optimize::PassManager, register};
 use predicates::prelude::*;
 use std::process::Command;
 
@@ -9,17 +9,30 @@ const OXIDATE_BINARY: &str = "oxidate";
 
 // Have to use random file name because tests run in parallel
 // With fixed filename we get errors due to race conditions
+// The program is run again once optimized, and once lowered to the register backend, which must not change its output
 fn test_pass(inp: &str, exp: &str) -> Result<()> {
+    let comp = compile_from_string(inp, true)?;
+    run_pass(&comp, exp)?;
+
+    let mut optimized = comp;
+    PassManager::all().run(&mut optimized);
+    run_pass(&optimized, exp)?;
+
+    let mut lowered = optimized;
+    register::lower(&mut lowered);
+    run_pass(&lowered, exp)
+}
+
+fn run_pass(comp: &[bytecode::ByteCode], exp: &str) -> Result<()> {
     let file_num = rand::random::<u128>().to_string();
-    let file_name = format!("./{file_num}.o2");
+    let file_name = std::env::temp_dir().join(format!("{file_num}.o2"));
 
     let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
-    let comp = compile_from_string(inp, true)?;
 
-    let mut file = std::fs::File::create(file_name.clone())?;
-    bytecode::write_bytecode(&comp, &mut file)?;
+    let mut file = std::fs::File::create(&file_name)?;
+    bytecode::write_bytecode(comp, &mut file)?;
 
-    cmd.arg(file_name.clone());
+    cmd.arg(&file_name);
     let exp = if exp.is_empty() {
         String::from("")
     } else {
@@ -32,12 +45,44 @@ fn test_pass(inp: &str, exp: &str) -> Result<()> {
     Ok(())
 }
 
+// Expects the program to fail at runtime with stderr containing each line of exp.
+// Lines are matched separately as anyhow indents the lines of the error it prints
+fn test_fail(inp: &str, exp: &str) -> Result<()> {
+    let file_num = rand::random::<u128>().to_string();
+    let file_name = std::env::temp_dir().join(format!("{file_num}.o2"));
+
+    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    let comp = compile_from_string(inp, true)?;
+
+    let mut file = std::fs::File::create(&file_name)?;
+    bytecode::write_bytecode(&comp, &mut file)?;
+
+    cmd.arg(&file_name);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::function(|err: &str| {
+            exp.lines().all(|line| err.contains(line))
+        }));
+
+    std::fs::remove_file(file_name)?;
+
+    Ok(())
+}
+
 // Test files in example/
 // file_name is expected to be prefix before .rst
+// Each file is run as compiled, optimized, and optimized for the register backend
 fn test_file(file_name: &str, exp: &str) -> Result<()> {
+    run_file(file_name, exp, &[])?;
+    run_file(file_name, exp, &["-O"])?;
+    run_file(file_name, exp, &["-O", "--backend", "register"])
+}
+
+fn run_file(file_name: &str, exp: &str, args: &[&str]) -> Result<()> {
     let file_name_rst = format!("../../example/{file_name}.rst");
 
     let mut cmd = Command::cargo_bin(OXIDATE_BINARY)?;
+    cmd.args(args);
     cmd.arg(file_name_rst.clone()).assert().success();
 
     dbg!(format!("{file_name}.o2"));
@@ -70,6 +115,7 @@ fn test_e2e_example_folder() -> Result<()> {
     test_file("loop-03", "55")?;
     test_file("loop-04", "20")?;
     test_file("type-01", "33")?;
+    test_file("concurrency-06", "55")?;
     Ok(())
 }
 
@@ -525,6 +571,45 @@ fn test_e2e_loops() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_e2e_break_exits_scopes() -> Result<()> {
+    // break from inside nested scopes, outer variables are still resolved correctly after the loop
+    let t = r"
+    let x = 1;
+    let y = 2;
+    loop {
+        let a = 10;
+        {
+            let b = 20;
+            if a + b == 30 {
+                let c = 5;
+                x = x + c;
+                break;
+            }
+        }
+    }
+    let z = 3;
+    x + y + z
+    ";
+    test_pass(t, "11")?;
+
+    // closures capture the frame they were declared in
+    let t = r"
+    let x = 1;
+    fn f(a: int) -> int {
+        let y = 2;
+        fn g(b: int) -> int {
+            a + b + x + y
+        }
+        g(3)
+    }
+    f(4)
+    ";
+    test_pass(t, "10")?;
+
+    Ok(())
+}
+
 #[test]
 fn test_e2e_fib() -> Result<()> {
     // loop-fib-01.rst
@@ -613,3 +698,1945 @@ fn test_e2e_fn_decl() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_e2e_thread_handles() -> Result<()> {
+    let t = r"
+    fn f() {}
+
+    let h = spawn f();
+    h.id()
+    ";
+    test_pass(t, "2")?;
+
+    let t = r"
+    fn f() {}
+
+    let h = spawn f();
+    let before = h.is_finished();
+    join h;
+    !before && h.is_finished()
+    ";
+    test_pass(t, "true")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_spawn_join_values() -> Result<()> {
+    let t = r"
+    fn heavy_calc(n: int) -> int {
+        let acc = 0;
+        for i in 0..n {
+            acc = acc + i;
+        }
+        acc
+    }
+
+    let h = spawn heavy_calc(10);
+    let r = join(h);
+    r
+    ";
+    test_pass(t, "45")?;
+
+    // blocks see the variables around them
+    let t = r"
+    let x = 20;
+    let a = spawn { x + 1 };
+    let b = spawn { let y = x * 2; y > 30 };
+    let ra = join a;
+    let rb = join(b);
+    println(ra);
+    rb
+    ";
+    test_pass(t, "21\ntrue")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_scope() -> Result<()> {
+    // the block gives its value once the threads spawned in it have finished
+    let t = r"
+    let total = 0;
+    let s = sem_create();
+
+    fn add(n: int) {
+        for i in 0..50 {
+            yield;
+        }
+        wait s;
+        total = total + n;
+        post s;
+    }
+
+    let res = scope {
+        spawn add(1);
+        spawn { add(2) };
+        total
+    };
+    println(res);
+    total
+    ";
+    test_pass(t, "0\n3")?;
+
+    // threads spawned by functions called in the block are joined too, and handles leaving it can still be joined
+    let t = r"
+    let done = 0;
+    fn work(n: int) -> int {
+        yield;
+        done = done + 1;
+        n
+    }
+    fn start(n: int) -> tid<int> {
+        spawn work(n)
+    }
+
+    let h = scope {
+        start(1);
+        scope {
+            spawn work(2);
+        };
+        start(3)
+    };
+    println(done);
+    join h
+    ";
+    test_pass(t, "3\n3")?;
+
+    // an error leaving the block doesn't wait for its threads
+    let t = r#"
+    let x = 0;
+    fn slow() {
+        for i in 0..50 {
+            yield;
+        }
+        x = 1;
+    }
+    try {
+        scope {
+            spawn slow();
+            1 / 0
+        }
+    } catch e {
+        0
+    };
+    x
+    "#;
+    test_pass(t, "0")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_select() -> Result<()> {
+    // ready semaphore is picked without blocking
+    let t = r"
+    let a = sem_create();
+    let b = sem_create();
+    wait a;
+    select {
+        a => { 1 }
+        b => { 2 }
+    }
+    ";
+    test_pass(t, "2")?;
+
+    // main thread blocks until the child posts
+    let t = r"
+    let a = sem_create();
+    let b = sem_create();
+    wait a;
+    wait b;
+
+    fn f() {
+        post a;
+    }
+
+    spawn f();
+    let x = select {
+        a => { 10 }
+        b => { 20 }
+    };
+    x
+    ";
+    test_pass(t, "10")?;
+
+    // values are received from whichever channel has one, the channels' types are inferred from the arms
+    let t = r"
+    let a = channel(1);
+    let b = channel(1);
+
+    fn produce(ch: Channel<int>, n: int) {
+        for i in 0..n {
+            send(ch, i);
+        }
+    }
+
+    spawn produce(a, 3);
+    spawn produce(b, 4);
+
+    let sum = 0;
+    for i in 0..7 {
+        let v = select {
+            recv(a) -> x => { x }
+            recv(b) -> x => { x }
+        };
+        match v {
+            Some(n) => { sum = sum + n; }
+            None => { }
+        }
+    }
+    sum
+    ";
+    test_pass(t, "9")?;
+
+    // a select blocked sending to a full channel sends once a thread receives from it
+    let t = r"
+    let out = channel(1);
+    send(out, 1);
+    let never = sem_create();
+    wait never;
+
+    fn drain() {
+        recv(out);
+    }
+
+    spawn drain();
+    let sent = select {
+        send(out, 2) -> sent => { sent }
+        never => { false }
+    };
+    let x = recv(out);
+    match x {
+        Some(n) => { sent && n == 2 }
+        None => { false }
+    }
+    ";
+    test_pass(t, "true")?;
+
+    // a closed channel gives None to a recv arm and fails a send arm
+    let t = r"
+    let ch : Channel<int> = channel(1);
+    close(ch);
+    let got = select {
+        recv(ch) -> x => {
+            match x {
+                Some(_) => { 1 }
+                None => { 0 }
+            }
+        }
+    };
+    let sent = select {
+        send(ch, 1) -> sent => { sent }
+    };
+    got == 0 && !sent
+    ";
+    test_pass(t, "true")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_kill() -> Result<()> {
+    // killed thread never runs and joining it produces unit
+    let t = r"
+    fn f() {
+        println(1);
+    }
+
+    let h = spawn f();
+    kill(h);
+    join h;
+    h.is_finished()
+    ";
+    test_pass(t, "true")?;
+
+    // mutex held in a lock block by a killed thread is released
+    let t = r"
+    let m = mutex();
+    let started = sem_create();
+    wait started;
+
+    fn f() {
+        lock(m) {
+            post started;
+            loop {
+                yield;
+            }
+        }
+    }
+
+    let h = spawn f();
+    wait started;
+    kill(h);
+    lock(m) {
+        2
+    }
+    ";
+    test_pass(t, "2")?;
+
+    // items a killed consumer took are not posted back
+    let t = r"
+    let items = sem_create();
+    wait items;
+
+    fn consumer() {
+        let i = 0;
+        loop i < 5 {
+            wait items;
+            i = i + 1;
+        }
+        loop {
+            yield;
+        }
+    }
+
+    let c = spawn consumer();
+    let i = 0;
+    loop i < 5 {
+        post items;
+        yield;
+        i = i + 1;
+    }
+    kill(c);
+    wait_timeout(items, 1)
+    ";
+    test_pass(t, "false")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_condvar() -> Result<()> {
+    // consumer waits until the producer sets the flag
+    let t = r"
+    let m = sem_create();
+    let cv = cv_create();
+    let ready = false;
+    let val = 0;
+
+    fn producer() {
+        wait m;
+        val = 42;
+        ready = true;
+        cv_notify_one(cv);
+        post m;
+    }
+
+    wait m;
+    spawn producer();
+    loop !ready {
+        cv_wait(cv, m);
+    }
+    post m;
+    val
+    ";
+    test_pass(t, "42")?;
+
+    // notify_all wakes up every waiter
+    let t = r"
+    let m = sem_create();
+    let cv = cv_create();
+    let go = false;
+    let count = 0;
+
+    fn waiter() {
+        wait m;
+        loop !go {
+            cv_wait(cv, m);
+        }
+        count = count + 1;
+        post m;
+    }
+
+    let h1 = spawn waiter();
+    let h2 = spawn waiter();
+    yield;
+
+    wait m;
+    go = true;
+    cv_notify_all(cv);
+    post m;
+
+    join h1;
+    join h2;
+    count
+    ";
+    test_pass(t, "2")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_barrier_wait_group() -> Result<()> {
+    // no thread passes the barrier before every thread arrives
+    let t = r"
+    let b = barrier_create(3);
+    let arrived = 0;
+    let ok = true;
+
+    fn worker() {
+        arrived = arrived + 1;
+        barrier_wait(b);
+        if !(arrived == 3) {
+            ok = false;
+        }
+    }
+
+    let h1 = spawn worker();
+    let h2 = spawn worker();
+    worker();
+    join h1;
+    join h2;
+    ok
+    ";
+    test_pass(t, "true")?;
+
+    // main thread waits for every worker to be done
+    let t = r"
+    let wg = wg_create();
+    let sum = 0;
+    let m = sem_create();
+
+    fn worker(x: int) {
+        wait m;
+        sum = sum + x;
+        post m;
+        wg_done(wg);
+    }
+
+    wg_add(wg, 3);
+    spawn worker(1);
+    spawn worker(2);
+    spawn worker(3);
+    wg_wait(wg);
+    sum
+    ";
+    test_pass(t, "6")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_channel() -> Result<()> {
+    // the producer blocks while the channel is full, and the consumer stops once it is closed and empty
+    let t = r"
+    let ch : Channel<int> = channel(2);
+
+    fn produce(n: int) {
+        for i in 1..n + 1 {
+            send(ch, i);
+        }
+        close(ch);
+    }
+
+    let h = spawn produce(5);
+    let sum = 0;
+    loop {
+        match recv(ch) {
+            Some(v) => { sum = sum + v; }
+            None => { break; }
+        }
+    }
+    join h;
+    sum
+    ";
+    test_pass(t, "15")?;
+
+    // receiving blocks until a value is sent, sending to a closed channel fails, and closing twice does nothing
+    let t = r#"
+    let ch : Channel<str> = channel(1);
+    spawn {
+        yield;
+        send(ch, "hi");
+    };
+    println(unwrap(recv(ch)));
+    close(ch);
+    close(ch);
+    println(send(ch, "bye"));
+    is_none(recv(ch))
+    "#;
+    test_pass(t, "hi\nfalse\ntrue")?;
+
+    // senders blocked on a full channel are woken by close
+    let t = r"
+    let ch : Channel<int> = channel(1);
+    send(ch, 1);
+    let h = spawn send(ch, 2);
+    yield;
+    close(ch);
+    let sent = join h;
+    println(sent);
+    println(unwrap(recv(ch)));
+    recv(ch)
+    ";
+    test_pass(t, "false\n1\nNone")?;
+
+    test_fail("channel(0)", "channel needs a positive capacity, got 0")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_lock() -> Result<()> {
+    // the threads yield while holding the mutex, so without it increments would be lost
+    let t = r"
+    let m = mutex();
+    let count = 0;
+
+    fn incr(n: int) {
+        for i in 0..n {
+            lock(m) {
+                let c = count;
+                yield;
+                count = c + 1;
+            }
+        }
+    }
+
+    let a = spawn incr(5);
+    let b = spawn incr(5);
+    join a;
+    join b;
+    count
+    ";
+    test_pass(t, "10")?;
+
+    // the mutex is released however the block is left
+    let t = r#"
+    let m = mutex();
+
+    fn first(xs: [int]) -> int {
+        lock(m) { return xs[0]; }
+    }
+
+    fn get(x: Option<int>) -> Option<int> {
+        let y = lock(m) { x? };
+        Some(y)
+    }
+
+    println(first([4, 5]));
+    println(get(None));
+    loop {
+        lock(m) { break; }
+    }
+    try {
+        lock(m) { panic("boom"); }
+    } catch e {
+        println(e);
+    }
+    lock(m) { 2 }
+    "#;
+    test_pass(t, "4\nNone\nboom\n2")?;
+
+    // readers share the rwlock, and a writer waits for them
+    let t = r"
+    let rw = rwlock();
+    let readers = 0;
+    let most = 0;
+    let x = 0;
+
+    fn read() {
+        read_lock(rw) {
+            readers = readers + 1;
+            most = max(most, readers);
+            yield;
+            readers = readers - 1;
+        }
+    }
+
+    fn write() {
+        lock(rw) {
+            x = readers;
+        }
+    }
+
+    let a = spawn read();
+    let b = spawn read();
+    yield;
+    let w = spawn write();
+    join a;
+    join b;
+    join w;
+    println(most);
+    x
+    ";
+    test_pass(t, "2\n0")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_atomic_int() -> Result<()> {
+    // each fetch_add reads and updates the counter in one step, so no increment is lost when threads yield
+    let t = r"
+    let count = atomic_int(0);
+
+    fn incr(n: int) {
+        for i in 0..n {
+            fetch_add(count, 1);
+            yield;
+        }
+    }
+
+    let a = spawn incr(50);
+    let b = spawn incr(50);
+    join a;
+    join b;
+    load(count)
+    ";
+    test_pass(t, "100")?;
+
+    // compare_and_swap gives back the value held, and only swaps if it was the current value
+    let t = r"
+    let a = atomic_int(1);
+    println(compare_and_swap(a, 2, 10));
+    println(compare_and_swap(a, 1, 10));
+    store(a, fetch_add(a, 5) * 2);
+    load(a)
+    ";
+    test_pass(t, "1\n1\n20")?;
+
+    test_fail(
+        "let a = atomic_int(9223372036854775807); fetch_add(a, 1)",
+        "Integer overflow: 9223372036854775807 + 1",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_bounded_queue() -> Result<()> {
+    // values come out in the order they went in: the producer blocks pushing 1 until 0 is popped,
+    // and the consumer blocks popping until 2 is pushed
+    let t = r"
+    let q = bounded_queue(1);
+
+    fn produce() {
+        for i in 0..3 {
+            push(q, i);
+            println(i);
+        }
+    }
+
+    let t = spawn produce();
+    yield;
+    for i in 0..3 {
+        let v: int = pop(q);
+        println(v * 10);
+    }
+    join t;
+    ";
+    test_pass(t, "0\n0\n10\n1\n2\n20")?;
+
+    test_fail(
+        "let q: BoundedQueue<int> = bounded_queue(1); pop(q)",
+        "No threads in ready queue",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_priority() -> Result<()> {
+    // high waits on the mutex held by low, so low runs with the priority of high until it releases it,
+    // instead of being kept waiting by medium
+    let t = r#"
+    let m = mutex();
+
+    fn low() {
+        lock(m) {
+            for i in 0..3 {
+                yield;
+            }
+            println("low releases");
+        }
+    }
+
+    fn high() {
+        set_priority(10);
+        lock(m) {
+            println("high");
+        }
+    }
+
+    fn medium() {
+        set_priority(5);
+        for i in 0..3 {
+            println("medium");
+            yield;
+        }
+    }
+
+    let l = spawn low();
+    yield;
+    let h = spawn high();
+    let md = spawn medium();
+    join h;
+    join md;
+    join l;
+    priority()
+    "#;
+    test_pass(t, "low releases\nhigh\nmedium\nmedium\nmedium\n0")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_spawn_args() -> Result<()> {
+    // the parent evaluates the args when it spawns the thread, so later changes to x don't reach the child
+    let t = r"
+    fn worker(id: int) -> int {
+        id * 10
+    }
+
+    let x = 1;
+    let a = spawn(worker, x);
+    x = 2;
+    let b = spawn worker(x);
+    x = 3;
+    let ra = join a;
+    let rb = join b;
+    println(ra);
+    rb
+    ";
+    test_pass(t, "10\n20")?;
+
+    // each worker gets its own id from the loop
+    let t = r"
+    let total = 0;
+    let m = mutex();
+
+    fn add(id: int) {
+        lock(m) {
+            total = total + id;
+        }
+    }
+
+    scope {
+        for i in 1..5 {
+            spawn(add, i);
+        }
+    }
+    total
+    ";
+    test_pass(t, "10")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_thread_local() -> Result<()> {
+    // each thread counts its own calls, even though the threads interleave
+    let t = r#"
+    fn count() -> int {
+        let n = thread_local("calls", 0) + 1;
+        set_thread_local("calls", n);
+        n
+    }
+
+    fn work(times: int) -> int {
+        for i in 0..times {
+            count();
+            yield;
+        }
+        thread_local("calls", 0)
+    }
+
+    let a = spawn work(3);
+    let b = spawn work(5);
+    count();
+    let x = join a;
+    let y = join b;
+    println(x);
+    println(y);
+    thread_local("calls", 100)
+    "#;
+    test_pass(t, "3\n5\n1")?;
+
+    // thread locals hold values of any type, such as a scratch array
+    let t = r#"
+    set_thread_local("buf", [1, 2]);
+    let h = spawn { len(thread_local("buf", [0])) };
+    let n = join h;
+    println(n);
+    len(thread_local("buf", [0]))
+    "#;
+    test_pass(t, "1\n2")?;
+
+    // a value set with another type than init's is an error rather than read as init's type
+    let t = r#"
+    set_thread_local("k", 1);
+    let s: str = thread_local("k", "x");
+    string_len(s)
+    "#;
+    test_fail(t, "expected str for thread local 'k', found int")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_wait_timeout() -> Result<()> {
+    // nobody posts, so the wait times out
+    let t = r"
+    let s = sem_create();
+    wait s;
+    wait_timeout(s, 10)
+    ";
+    test_pass(t, "false")?;
+
+    // the child posts before the deadline
+    let t = r"
+    let s = sem_create();
+    wait s;
+
+    fn f() {
+        post s;
+    }
+
+    spawn f();
+    wait_timeout(s, 5000)
+    ";
+    test_pass(t, "true")?;
+
+    // a thread blocked forever does not stop the timer from firing
+    let t = r"
+    let s = sem_create();
+    let t = sem_create();
+    wait s;
+    wait t;
+
+    fn f() {
+        wait t;
+    }
+
+    spawn f();
+    wait_timeout(s, 10)
+    ";
+    test_pass(t, "false")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_assert() -> Result<()> {
+    let t = r"
+    let x = 2;
+    assert(x == 2);
+    assert(x < 3);
+    assert_eq(x + 1, 3);
+    x
+    ";
+    test_pass(t, "2")?;
+
+    let t = r"
+    let x = 2;
+    assert(x > 3);
+    ";
+    test_fail(t, "assertion failed: x > 3\n  left: 2\n right: 3")?;
+
+    let t = r"
+    let x = false;
+    assert(x);
+    ";
+    test_fail(t, "assertion failed: x")?;
+
+    let t = r#"
+    let s = "a";
+    assert_eq(s, "a ");
+    "#;
+    test_fail(
+        t,
+        "assertion `left == right` failed: s == a \n  left: \"a\"\n right: \"a \"",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_typeof() -> Result<()> {
+    let t = r#"
+    fn f() {}
+    println(typeof(1));
+    println(typeof(2.5));
+    println(typeof("s"));
+    println(typeof(sem_create()));
+    println(typeof(f));
+    typeof(f())
+    "#;
+    test_pass(t, "int\nfloat\nstr\nsem\nfn\n()")?;
+
+    let t = r#"
+    let x = 1;
+    if is_int(x) && !is_string(x) {
+        println("int");
+    }
+    is_float(x) || is_bool(true) && is_unit(println(""))
+    "#;
+    test_pass(t, "int\n\ntrue")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_division_by_zero() -> Result<()> {
+    // Floats follow IEEE 754
+    let t = r"
+    let zero = 0.0;
+    println(1.0 / zero);
+    println(-1.0 / zero);
+    println(zero / zero);
+    is_nan(zero / zero) && is_inf(1.0 / zero) && !is_nan(1.0)
+    ";
+    test_pass(t, "inf\n-inf\nNaN\ntrue")?;
+
+    let t = r"
+    let zero = 0;
+    10 / zero
+    ";
+    test_fail(t, "Division by zero: 10 / 0\nBINOP(Div)")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_option_result() -> Result<()> {
+    let t = r#"
+    fn find(n : int) -> Option<int> {
+        if n > 0 {
+            Some(n * 10)
+        } else {
+            None
+        }
+    }
+
+    println(find(2));
+    println(find(0));
+    println(Err("oops"));
+
+    let total = 0;
+    let i = -1;
+    loop i < 3 {
+        total = total + match find(i) {
+            Some(v) => { v }
+            None => { 100 }
+        };
+        i = i + 1;
+    }
+    total
+    "#;
+    test_pass(t, "Some(20)\nNone\nErr(oops)\n230")?;
+
+    // match in a function, with returns in its arms
+    let t = r#"
+    fn describe(r : Result<int, str>) -> str {
+        match r {
+            Ok(v) => {
+                if v > 9 {
+                    return "big";
+                }
+                itoa(v)
+            }
+            Err(e) => { return e; }
+        }
+    }
+    println(describe(Ok(3)));
+    println(describe(Ok(30)));
+    describe(Err("bad"))
+    "#;
+    test_pass(t, "3\nbig\nbad")?;
+
+    let t = r"
+    let x : Option<int> = None;
+    unwrap(x)
+    ";
+    test_fail(t, "Called unwrap on None")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_try() -> Result<()> {
+    let t = r#"
+    fn parse(s : str) -> Result<int, str> {
+        if string_len(s) > 0 {
+            Ok(atoi(s))
+        } else {
+            Err("empty")
+        }
+    }
+
+    fn sum(a : str, b : str) -> Result<int, str> {
+        let x = parse(a)?;
+        println("parsed a");
+        Ok(x + parse(b)?)
+    }
+
+    println(sum("1", "2"));
+    println(sum("", "2"));
+    sum("1", "")
+    "#;
+    test_pass(t, "parsed a\nOk(3)\nErr(empty)\nparsed a\nErr(empty)")?;
+
+    // ? in nested blocks and loops returns from the function
+    let t = r"
+    fn first_none(a : Option<int>, b : Option<int>) -> Option<int> {
+        let total = 0;
+        loop {
+            total = total + { a? + b? };
+            break;
+        }
+        Some(total)
+    }
+    println(first_none(Some(1), Some(2)));
+    first_none(Some(1), None)
+    ";
+    test_pass(t, "Some(3)\nNone")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_try_catch() -> Result<()> {
+    let t = r#"
+    fn div(a : int, b : int) -> int {
+        if b == 0 {
+            panic("division by zero");
+        }
+        a / b
+    }
+
+    let total = 0;
+    let i = 0;
+    loop i < 3 {
+        total = total + try { div(6, i) } catch e { println(e); 100 };
+        i = i + 1;
+    }
+    total
+    "#;
+    test_pass(t, "division by zero\n109")?;
+
+    // errors unwind to the innermost handler, and runtime errors are caught like panics
+    let t = r#"
+    let x = try {
+        try { panic("inner"); } catch e { println(e); }
+        let y = 9223372036854775807 + 1;
+        y
+    } catch e {
+        println(e);
+        5
+    };
+    x
+    "#;
+    test_pass(t, "inner\nInteger overflow: 9223372036854775807 + 1\n5")?;
+
+    // return and break leave the try block without keeping its handler
+    let t = r#"
+    fn first() -> int {
+        try { return 1; } catch e { return 2; }
+    }
+
+    let i = 0;
+    loop {
+        try {
+            i = i + first();
+            if i > 2 {
+                break;
+            }
+        } catch e { }
+    }
+    panic("after the loop");
+    "#;
+    test_fail(t, "after the loop")?;
+
+    // each thread has its own handlers
+    let t = r#"
+    fn worker() {
+        try { panic("in worker"); } catch e { println(e); }
+    }
+
+    let t = spawn worker();
+    join t;
+    try { panic("in main"); 1 } catch e { println(e); 2 }
+    "#;
+    test_pass(t, "in worker\nin main\n2")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_structural_eq() -> Result<()> {
+    let t = r#"
+    let a = "ab";
+    println(a == "ab");
+    println(Some(Ok(2)) == Some(Ok(2)));
+    println(Some(Ok(2)) == Some(Err("2")));
+    println(Some(0.0 / 0.0) == Some(0.0 / 0.0));
+
+    let s = sem_create();
+    let t = s;
+    println(s == t);
+    s == sem_create()
+    "#;
+    test_pass(t, "true\ntrue\nfalse\nfalse\ntrue\nfalse")?;
+
+    let t = r#"
+    assert_eq(Ok("x"), Ok("x"));
+    assert_eq(Some(1), None);
+    "#;
+    test_fail(
+        t,
+        "assertion `left == right` failed: Some(1) == None\n  left: Some(1)\n right: None",
+    )?;
+
+    let t = r#"
+    let x : Result<int, str> = Ok(2);
+    println(x == Ok(2));
+    x == Err("2")
+    "#;
+    test_pass(t, "true\nfalse")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_ordering() -> Result<()> {
+    let t = r#"
+    println("apple" < "banana");
+    println("ab" < "abc");
+    println("Zebra" < "apple");
+    println("b" >= "abc");
+    println(2 <= 2);
+    println(2.5 >= 3.0);
+
+    fn later(a: str, b: str) -> str {
+        if a >= b {
+            return a;
+        }
+        b
+    }
+    later("pear", "peach")
+    "#;
+    test_pass(t, "true\ntrue\ntrue\ntrue\ntrue\nfalse\npear")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_mutual_recursion() -> Result<()> {
+    let t = r#"
+    println(is_even(10));
+
+    fn is_even(n: int) -> bool {
+        if n == 0 {
+            return true;
+        }
+        is_odd(n - 1)
+    }
+
+    fn is_odd(n: int) -> bool {
+        if n == 0 {
+            return false;
+        }
+        is_even(n - 1)
+    }
+
+    is_odd(7)
+    "#;
+    test_pass(t, "true\ntrue")?;
+
+    // Only the first decl of a name is hoisted, later ones shadow it where they are declared
+    let t = r#"
+    println(f());
+    fn f() -> int { 1 }
+    println(f());
+    fn f() -> int { 2 }
+    f()
+    "#;
+    test_pass(t, "1\n1\n2")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_named_args() -> Result<()> {
+    let t = r#"
+    fn draw(x: int, y: int, label: str) {
+        print(label);
+        print(" ");
+        print(x);
+        print(",");
+        println(y);
+    }
+
+    draw(y: 2, label: "a", x: 1);
+    draw(3, label: "b", y: 4);
+    "#;
+    test_pass(t, "a 1,2\nb 3,4")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_structs() -> Result<()> {
+    let t = r#"
+    struct Point { x: int, y: int }
+
+    let p = Point { y: 2, x: 1 };
+    println(p);
+    p.x = 3;
+    println(p.x + p.y);
+
+    // update syntax copies the other fields, leaving p as it was
+    let q = Point { x: 5, ..p };
+    println(q);
+    println(p);
+    p == Point { x: 3, y: 2 }
+    "#;
+    test_pass(
+        t,
+        "Point { x: 1, y: 2 }\n5\nPoint { x: 5, y: 2 }\nPoint { x: 3, y: 2 }\ntrue",
+    )?;
+
+    // assigning a field of a copy leaves the original as it was
+    let t = r#"
+    struct Point { x: int, y: int }
+    struct Line { from: Point, to: Point }
+
+    let p = Point { x: 0, y: 0 };
+    let l = Line { from: p, to: Point { x: 1, y: 1 } };
+    l.to.x = 4;
+    p.x = 2;
+    println(l.to.x);
+    l.from.x
+    "#;
+    test_pass(t, "4\n0")?;
+
+    let t = r#"
+    struct Counter { count: int }
+
+    impl Counter {
+        fn next(self) -> Counter {
+            Counter { count: self.count + 1 }
+        }
+
+        fn add(self, n: int) -> int {
+            self.count + n
+        }
+    }
+
+    let c = Counter { count: 0 };
+    c.next().next().add(10)
+    "#;
+    test_pass(t, "12")?;
+
+    // methods can be called before the impl block
+    let t = r#"
+    struct Point { x: int, y: int }
+
+    fn norm(p: Point) -> int {
+        p.len()
+    }
+
+    println(norm(Point { x: 3, y: 4 }));
+
+    impl Point {
+        fn len(self) -> int {
+            self.x * self.x + self.y * self.y
+        }
+    }
+    "#;
+    test_pass(t, "25")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_enums() -> Result<()> {
+    let t = r#"
+    enum Shape { Circle(int), Square(int), Empty }
+
+    fn area(s: Shape) -> int {
+        match s {
+            Circle(r) => { 3 * r * r }
+            Square(side) => { side * side }
+            Empty => { 0 }
+        }
+    }
+
+    println(Circle(2));
+    println(Empty);
+    println(typeof(Square(1)));
+    println(area(Circle(2)) + area(Square(3)) + area(Empty));
+    println(Square(2) == Square(2));
+    Square(2) == Circle(2)
+    "#;
+    test_pass(t, "Circle(2)\nEmpty\nShape\n21\ntrue\nfalse")?;
+
+    // variants can be used before the enum is declared, and options still match
+    let t = r#"
+    fn first(s: Option<Dir>) -> str {
+        match s {
+            Some(d) => {
+                match d {
+                    Up => { "up" }
+                    Down => { "down" }
+                }
+            }
+            None => { "none" }
+        }
+    }
+
+    enum Dir { Up, Down }
+
+    println(first(Some(Down)));
+    first(None)
+    "#;
+    test_pass(t, "down\nnone")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_match_guards() -> Result<()> {
+    let t = r#"
+    enum Shape { Circle(int), Square(int), Empty }
+
+    fn describe(s: Shape, flat: bool) -> str {
+        match s {
+            Circle(r) if r > 10 => { "big circle" }
+            Circle(r) => { "circle" }
+            Square(side) if side == 0 || flat => { "flat square" }
+            Square(side) => { "square" }
+            Empty if flat => { "flat" }
+            Empty => { "empty" }
+        }
+    }
+
+    println(describe(Circle(11), false));
+    println(describe(Circle(2), false));
+    println(describe(Square(0), false));
+    println(describe(Square(3), true));
+    println(describe(Square(3), false));
+    println(describe(Empty, true));
+    describe(Empty, false)
+    "#;
+    test_pass(
+        t,
+        "big circle\ncircle\nflat square\nflat square\nsquare\nflat\nempty",
+    )?;
+
+    // the parameter is inferred from the guard, and falling through leaves no scope behind
+    let t = r#"
+    fn classify(o) {
+        match o {
+            Some(n) if n % 15 == 0 => { 15 }
+            Some(n) if n % 5 == 0 => { 5 }
+            Some(n) if n % 3 == 0 => { 3 }
+            Some(n) => { 0 }
+            None => { -1 }
+        }
+    }
+
+    let total = 0;
+    for i in 0..100 {
+        total = total + classify(Some(i));
+    }
+    println(total);
+    classify(None)
+    "#;
+    test_pass(t, "251\n-1")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_nested_patterns() -> Result<()> {
+    let t = r#"
+    enum Shape { Circle(int), Square(int), Empty }
+
+    fn size(s: Option<Shape>) -> int {
+        match s {
+            Some(Circle(0) | Square(0)) | Some(Empty) => { 0 }
+            Some(Circle(r) | Square(r)) if r > 100 => { 100 }
+            Some(Circle(r) | Square(r)) => { r }
+            None => { -1 }
+        }
+    }
+
+    fn name(n: int) -> str {
+        match n {
+            1 | 2 | 3 => { "small" }
+            -1 => { "minus one" }
+            0 => { "zero" }
+            _ => { "big" }
+        }
+    }
+
+    fn greet(r: Result<str, int>) -> str {
+        match r {
+            Ok("hi" | "hello") => { "greeting" }
+            Ok(s) => { s }
+            Err(404) => { "not found" }
+            Err(code) => { itoa(code) }
+        }
+    }
+
+    println(size(Some(Circle(0))) + size(Some(Empty)));
+    println(size(Some(Square(7))));
+    println(size(Some(Circle(700))));
+    println(size(None));
+    println([name(2), name(-1), name(0), name(8)]);
+    println([greet(Ok("hello")), greet(Ok("yo")), greet(Err(404)), greet(Err(500))]);
+    match Some(false) {
+        Some(true) => { "yes" }
+        Some(false) | None => { "no" }
+    }
+    "#;
+    test_pass(
+        t,
+        "0\n7\n100\n-1\n[small, minus one, zero, big]\n[greeting, yo, not found, 500]\nno",
+    )?;
+
+    // alternatives can bind a name at different depths
+    let t = r#"
+    enum Nest { Wrap(Nest), Leaf(int) }
+
+    fn peel(n: Nest) -> int {
+        match n {
+            Wrap(Wrap(Leaf(x))) | Wrap(Leaf(x)) | Leaf(x) => { x }
+            Wrap(Wrap(Wrap(inner))) => { 100 + peel(inner) }
+        }
+    }
+
+    println(peel(Leaf(1)));
+    println(peel(Wrap(Leaf(2))));
+    println(peel(Wrap(Wrap(Leaf(3)))));
+    peel(Wrap(Wrap(Wrap(Leaf(4)))))
+    "#;
+    test_pass(t, "1\n2\n3\n104")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_range_patterns() -> Result<()> {
+    let t = r#"
+    fn grade(score: int) -> str {
+        match score {
+            90..=100 => { "A" }
+            80..=89 => { "B" }
+            s @ 50..=79 if s % 2 == 0 => { "C even" }
+            50..=79 => { "C" }
+            -100..=-1 | 101..=1000 => { "invalid" }
+            _ => { "F" }
+        }
+    }
+
+    fn size(o: Option<int>) -> int {
+        match o {
+            Some(n @ 0..=9) => { n }
+            Some(n @ (10..=99)) => { n * 10 }
+            Some(n) => { -n }
+            None => { 0 }
+        }
+    }
+
+    println([grade(100), grade(85), grade(64), grade(63), grade(-5), grade(20)]);
+    println([size(Some(7)), size(Some(12)), size(Some(100)), size(None)]);
+    "#;
+    test_pass(t, "[A, B, C even, C, invalid, F]\n[7, 120, -100, 0]")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_labeled_loops() -> Result<()> {
+    let t = r#"
+    fn find(grid: [[int]], target: int) -> Option<[int]> {
+        let r = 0;
+        'rows: loop {
+            if r == len(grid) {
+                break 'rows None;
+            }
+            let c = 0;
+            for x in grid[r] {
+                if x == target {
+                    break 'rows Some([r, c]);
+                }
+                c = c + 1;
+            }
+            r = r + 1;
+        }
+    }
+
+    let grid = [[1, 2, 3], [4, 5, 6]];
+    println(find(grid, 5));
+    println(find(grid, 9));
+
+    // break leaves the labelled loop from inside nested loops and scopes
+    let count = 0;
+    let total = 0;
+    'outer: for i in 0..5 {
+        for j in 0..5 {
+            let sum = i + j;
+            if sum > 4 {
+                break 'outer;
+            }
+            if j > i {
+                break;
+            }
+            count = count + 1;
+            total = total + sum;
+        }
+    }
+    println([count, total]);
+
+    let n = 27;
+    let steps = loop {
+        let steps = 0;
+        loop n > 1 {
+            if n % 2 == 0 { n = n / 2; } else { n = 3 * n + 1; }
+            steps = steps + 1;
+        }
+        break steps;
+    };
+    println(steps);
+    "#;
+    test_pass(t, "Some([1, 1])\nNone\n[6, 12]\n111")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_generics() -> Result<()> {
+    let t = r#"
+    fn id(x) { x }
+    fn apply(f, x) { f(x) }
+    fn or_else(o, d) {
+        match o {
+            Some(v) => { v }
+            None => { d }
+        }
+    }
+
+    println(id(1) + 1);
+    println(id("one"));
+    println(apply(id, true));
+    println(or_else(Some(2.5), 0.0));
+    or_else(None, "default")
+    "#;
+    test_pass(t, "2\none\ntrue\n2.5\ndefault")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_operator_overloading() -> Result<()> {
+    let t = r#"
+    struct Vec2 { x: int, y: int }
+    struct Id { id: int, name: str }
+
+    impl Vec2 {
+        fn add(self, other: Vec2) -> Vec2 {
+            Vec2 { x: self.x + other.x, y: self.y + other.y }
+        }
+    }
+
+    // ids are equal whatever their names
+    impl Id {
+        fn eq(self, other: Id) -> bool {
+            self.id == other.id
+        }
+    }
+
+    let a = Vec2 { x: 1, y: 2 };
+    let sum = a + Vec2 { x: 10, y: 20 } + a;
+    println(sum);
+    println(sum == Vec2 { x: 12, y: 24 });
+    println(Id { id: 1, name: "a" } == Id { id: 1, name: "b" });
+    Id { id: 1, name: "a" } == Id { id: 2, name: "a" }
+    "#;
+    test_pass(t, "Vec2 { x: 12, y: 24 }\ntrue\ntrue\nfalse")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_comprehensions() -> Result<()> {
+    let t = r#"
+    let evens = [x * 2 for x in 0..10 if x % 2 == 0];
+    println(evens);
+    println(len(evens));
+    println([x > 4 for x in evens]);
+    println([[y for y in 0..x] for x in 1..4]);
+    [x for x in 0..0] == []
+    "#;
+    test_pass(
+        t,
+        "[0, 4, 8, 12, 16]\n5\n[false, false, true, true, true]\n[[0], [0, 1], [0, 1, 2]]\ntrue",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_slices() -> Result<()> {
+    let t = r#"
+    let a = [1, 2, 3, 4, 5];
+    let mid = a[1..4];
+    println(mid);
+    println(mid[1..]);
+    println(a[..2]);
+    println(a[0] + mid[0]);
+
+    let s = "hello world";
+    let n = 5;
+    println(s[..n]);
+    println(s[n + 1..]);
+
+    // out of bounds slices and indexes are runtime errors, which can be caught
+    println(try { a[3..9] } catch e { println(e); [] });
+    try { a[5] } catch e { println(e); 0 }
+    "#;
+    test_pass(
+        t,
+        "[2, 3, 4]\n[3, 4]\n[1, 2]\n3\nhello\nworld\nSlice 3..9 out of bounds for Array of length 5\n[]\nIndex 5 out of bounds for Array of length 5\n0",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_strings_by_char() -> Result<()> {
+    // strings are indexed, sliced, counted and iterated by chars, and bytes gives the UTF-8 bytes
+    let t = r#"
+    let s = "héllo wörld";
+    println(len(s));
+    println(string_len(s));
+    println(s[1]);
+    println(s[6..]);
+    println([c for c in s if !(c == "l")]);
+    println(bytes("é"));
+    println(len(bytes(s)));
+    try { s[11] } catch e { e }
+    "#;
+    test_pass(
+        t,
+        "11\n13\né\nwörld\n[h, é, o,  , w, ö, r, d]\n[195, 169]\n13\nIndex 11 out of bounds for String of length 11",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_for_loops() -> Result<()> {
+    // for loops go over ranges, arrays and strings, and break leaves the loop and its scope
+    let t = r#"
+    let total = 0;
+    for x in 0..5 {
+        total = total + x;
+    }
+    println(total);
+
+    for name in ["a", "b"] {
+        print(name);
+    }
+    println("");
+
+    let count = 0;
+    for c in "héllo" {
+        if c == "l" {
+            break;
+        }
+        count = count + 1;
+    }
+    println(count);
+
+    // the iterable is evaluated once, and nested loops have their own iterators
+    let pairs = 0;
+    let n = 2;
+    for i in 0..n {
+        n = 10;
+        for j in [i, i] {
+            pairs = pairs + 1;
+        }
+    }
+    println(pairs);
+
+    for x in 3..1 {
+        println(x);
+    }
+
+    fn first_even(xs: [int]) -> int {
+        for x in xs {
+            if x % 2 == 0 {
+                return x;
+            }
+        }
+        -1
+    }
+    first_even([1, 3, 4, 6])
+    "#;
+    test_pass(t, "10\nab\n2\n4\n4")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_let_destructure() -> Result<()> {
+    let t = r#"
+    let [a, b, ..rest] = [1, 2, 3, 4];
+    println(a + b);
+    println(rest);
+
+    let [first, ..others] = "héllo";
+    println(first);
+    println(others);
+
+    struct Point { x: int, y: int, label: str }
+    let Point { x, y: height, .. } = Point { x: 3, y: 4, label: "p" };
+    println(x * height);
+
+    // the names can be reassigned like those of other lets
+    fn swap(pair: [int]) -> [int] {
+        let [a, b] = pair;
+        a = a * 10;
+        [b, a]
+    }
+    swap([1, 2])
+    "#;
+    test_pass(t, "3\n[3, 4]\nh\néllo\n12\n[2, 10]")?;
+
+    // the length of an array is checked at runtime
+    let t = r#"
+    let n = try {
+        let [a, b] = [1, 2, 3];
+        a + b
+    } catch e {
+        println(e);
+        0
+    };
+    let [x, ..rest] = [n];
+    println(rest);
+    x
+    "#;
+    test_pass(
+        t,
+        "Can't destructure Array of length 3 into 2 elements\n[]\n0",
+    )?;
+
+    let t = r#"
+    let [y, z, ..more] = [1];
+    "#;
+    test_fail(
+        t,
+        "Can't destructure Array of length 1 into at least 2 elements",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_generators() -> Result<()> {
+    // each yield gives the next value of the loop going over the generator
+    let t = r#"
+    fn gen() {
+        yield 1;
+        yield 2;
+    }
+    for x in gen() {
+        print(x);
+    }
+    println("");
+
+    fn evens(n) {
+        for i in 0..n {
+            if i % 2 == 0 {
+                yield i;
+            }
+        }
+    }
+    println([x * 10 for x in evens(7)]);
+
+    // generators can go over other generators
+    fn chain() -> Generator<int> {
+        for x in gen() {
+            yield x;
+        }
+        yield 9;
+    }
+    println([x for x in chain()]);
+    "#;
+    test_pass(t, "12\n[0, 20, 40, 60]\n[1, 2, 9]")?;
+
+    // a generator left by break carries on from where it stopped, and return ends it
+    let t = r#"
+    fn count_up(from: int) -> Generator<int> {
+        let i = from;
+        loop {
+            yield i;
+            i = i + 1;
+        }
+    }
+    let g = count_up(5);
+    for x in g {
+        if x == 6 {
+            break;
+        }
+    }
+    for x in g {
+        println(x);
+        break;
+    }
+
+    fn upto(n: int) -> Generator<int> {
+        let i = 0;
+        loop {
+            if i == n {
+                return;
+            }
+            yield i;
+            i = i + 1;
+        }
+    }
+    let u = upto(2);
+    println([x for x in u]);
+    println([x for x in u]);
+    "#;
+    test_pass(t, "7\n[0, 1]\n[]")?;
+
+    // an error in a generator ends it, and try blocks in it are kept across yields
+    let t = r#"
+    fn risky() -> Generator<int> {
+        let x = try {
+            yield 1;
+            panic("inner");
+            0
+        } catch e {
+            println(e);
+            2
+        };
+        yield x;
+        panic("boom");
+        yield 3;
+    }
+    let r = risky();
+    let total = try {
+        let total = 0;
+        for x in r {
+            total = total + x;
+        }
+        total
+    } catch e {
+        println(e);
+        -1
+    };
+    println(total);
+    println([x for x in r]);
+    "#;
+    test_pass(t, "inner\nboom\n-1\n[]")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_random_int() -> Result<()> {
+    let t = r"
+    let in_range = true;
+    for i in 0..100 {
+        let n = random_int(-3, 4);
+        in_range = in_range && n >= -3 && n < 4;
+    }
+    println(in_range);
+    random_int(5, 6)
+    ";
+    test_pass(t, "true\n5")?;
+
+    test_fail(
+        "random_int(2, 2)",
+        "random_int expects lo < hi, got 2 and 2",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_files() -> Result<()> {
+    // File builtins give results, whose errors can be matched, passed up with ? or caught once unwrapped
+    let dir = std::env::temp_dir().join(format!("rustscript_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir)?;
+    let dir = dir.to_str().unwrap();
+
+    let t = format!(
+        r#"
+    let path = "{dir}/data.txt";
+    println(file_exists("{dir}/missing.txt"));
+    unwrap(write_file(path, "3
+4
+"));
+    unwrap(append_file(path, "5
+"));
+    println(file_exists(path));
+
+    fn total(path: str) -> Result<int, str> {{
+        let sum = 0;
+        for line in read_lines(path)? {{
+            sum = sum + atoi(line);
+        }}
+        Ok(sum)
+    }}
+    println(total(path));
+    println(len(unwrap(read_file(path))));
+
+    match read_file("{dir}/missing.txt") {{
+        Ok(s) => {{ println(s); }}
+        Err(e) => {{ println("missing"); }}
+    }}
+    println(is_err(total("{dir}/missing.txt")));
+    println(is_err(write_file("{dir}", "x")));
+    try {{ unwrap(read_file("{dir}/missing.txt")) }} catch e {{ "caught" }}
+    "#
+    );
+    test_pass(&t, "false\ntrue\nOk(12)\n6\nmissing\ntrue\ntrue\ncaught")?;
+
+    std::fs::remove_dir_all(dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_json() -> Result<()> {
+    // JSON is parsed into values of the shape of an example, structs from objects, and printed back compact or indented
+    let t = r#"
+    struct Point { x: int, y: float }
+
+    let xs = unwrap(json_parse(" [1, 2, 3] ", [0]));
+    println(xs[0] + xs[2]);
+    let nested = unwrap(json_parse("[[], [4, 5]]", [[0]]));
+    println(len(nested[1]));
+    println(unwrap(json_stringify(xs, false)));
+    println(unwrap(json_stringify([[1.5], []], true)));
+
+    let s = unwrap(json_stringify([Point { x: 1, y: 2.5 }], false));
+    println(s);
+    let ps = unwrap(json_parse(s, [Point { x: 0, y: 0.0 }]));
+    println(ps[0].x + 1);
+
+    match json_parse("[1,", [0]) {
+        Ok(x) => { println("parsed"); }
+        Err(e) => { println(e); }
+    }
+    println(unwrap_err(json_parse("[1, true]", [0])));
+    println(is_err(json_parse("{}", [0])));
+    println(is_err(json_stringify(0.0 / 0.0, false)));
+    try { unwrap(json_parse("nope", "")) } catch e { "caught" }
+    "#;
+    test_pass(
+        t,
+        "4\n2\n[1,2,3]\n[\n  [\n    1.5\n  ],\n  []\n]\n[{\"x\":1,\"y\":2.5}]\n2\nEOF while parsing a value at line 1 column 3\nBad type, expected integer, found bool\ntrue\ntrue\ncaught",
+    )?;
+
+    Ok(())
+}
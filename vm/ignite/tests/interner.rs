@@ -0,0 +1,41 @@
+use anyhow::Result;
+use bytecode::{read_bytecode, write_bytecode, Symbol, MAX_INTERNED_BYTES};
+use compiler::compiler::compile_from_string;
+
+// The interner is process wide, so filling it has a test binary of its own.
+// Compiling or loading a program with new names once it is full fails with an error instead of panicking
+#[test]
+fn full_interner() -> Result<()> {
+    let loaded = format!("let {} = 1;", "l".repeat(1024));
+    let mut o2 = vec![];
+    write_bytecode(&compile_from_string(&loaded, true)?, &mut o2)?;
+
+    // Fill the interner with new names, halving their length each time the next one doesn't fit
+    let name = |i: usize, len: usize| format!("{}-{}", i, "0".repeat(len));
+    let mut i = 0;
+    let mut len = 1 << 20;
+    while len > 0 {
+        if Symbol::try_intern(&name(i, len)).is_err() {
+            len /= 2;
+        }
+        i += 1;
+        assert!(i <= MAX_INTERNED_BYTES);
+    }
+
+    let compiled = "let fresh_name = 1;";
+    let err = compile_from_string(compiled, true).unwrap_err();
+    assert!(err.to_string().contains("Too many names"));
+
+    // The names of a .o2 file are interned again when it is read, so a name of the same length
+    // the process has never seen is swapped in for the one compiled before filling the interner
+    let (old, new) = ("l".repeat(1024).into_bytes(), "m".repeat(1024).into_bytes());
+    let at = o2.windows(old.len()).position(|w| w == old).unwrap();
+    o2[at..at + old.len()].copy_from_slice(&new);
+    let err = read_bytecode(&mut o2.as_slice()).unwrap_err();
+    assert!(err.to_string().contains("Too many names"));
+
+    // Names interned before are still found
+    assert!(Symbol::try_intern(&name(0, 1 << 20)).is_ok());
+
+    Ok(())
+}
@@ -52,3 +52,118 @@ fn run_simple_program() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn warn_incompatible_compiler() -> Result<()> {
+    let bytecode = vec![ByteCode::ldc(1), ByteCode::POP, ByteCode::DONE];
+    let metadata = |version: &str| bytecode::Metadata {
+        compiler_version: version.to_string(),
+        source_hash: 0,
+        timestamp: 0,
+        opt_level: 0,
+    };
+
+    let mut file = std::fs::File::create("./old_compiler.o2")?;
+    let old = metadata("0.0.1");
+    bytecode::write_bytecode_metadata(&bytecode, Default::default(), &old, &mut file)?;
+    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    cmd.arg("./old_compiler.o2");
+    cmd.assert().success().stderr(predicate::str::contains(
+        "warning: ./old_compiler.o2 was compiled by compiler 0.0.1",
+    ));
+
+    let mut file = std::fs::File::create("./old_compiler.o2")?;
+    let current = metadata(env!("CARGO_PKG_VERSION"));
+    bytecode::write_bytecode_metadata(&bytecode, Default::default(), &current, &mut file)?;
+    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    cmd.arg("./old_compiler.o2");
+    cmd.assert().success().stderr(predicate::str::is_empty());
+
+    std::fs::remove_file("./old_compiler.o2")?;
+
+    Ok(())
+}
+
+#[test]
+fn deny_capability() -> Result<()> {
+    let bytecode = vec![
+        ByteCode::ld("exit"),
+        ByteCode::ldc(3),
+        ByteCode::CALL(1),
+        ByteCode::DONE,
+    ];
+
+    let mut file = std::fs::File::create("./deny.o2")?;
+    bytecode::write_bytecode(&bytecode, &mut file)?;
+
+    Command::cargo_bin(IGNITE_BINARY)?
+        .arg("./deny.o2")
+        .assert()
+        .code(3);
+
+    Command::cargo_bin(IGNITE_BINARY)?
+        .args(["./deny.o2", "--deny", "process"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "exit needs the process capability, which the runtime is denied",
+        ));
+
+    std::fs::remove_file("./deny.o2")?;
+
+    Ok(())
+}
+
+#[test]
+fn record_and_replay() -> Result<()> {
+    let bytecode = vec![ByteCode::ldc(42), ByteCode::DONE];
+
+    let mut file = std::fs::File::create("./replay.o2")?;
+    bytecode::write_bytecode(&bytecode, &mut file)?;
+
+    Command::cargo_bin(IGNITE_BINARY)?
+        .args(["./replay.o2", "--record", "./replay.log"])
+        .assert()
+        .success();
+    let log = std::fs::read_to_string("./replay.log")?;
+    assert!(log.starts_with("rustscript switch log v1\n"));
+
+    Command::cargo_bin(IGNITE_BINARY)?
+        .args(["./replay.o2", "--replay", "./replay.log"])
+        .assert()
+        .success()
+        .stdout("42\n");
+
+    // A log that does not match the program
+    std::fs::write("./replay.log", "rustscript switch log v1\n0 5\n")?;
+    Command::cargo_bin(IGNITE_BINARY)?
+        .args(["./replay.o2", "--replay", "./replay.log"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("diverged"));
+
+    std::fs::remove_file("./replay.o2")?;
+    std::fs::remove_file("./replay.log")?;
+
+    Ok(())
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn runs_hot_functions_natively() -> Result<()> {
+    let src = "fn sq(n: int) -> int { n * n } let s = 0; let i = 0; loop i < 50 { s = s + sq(i); i = i + 1; } s";
+    let bytecode = compiler::compiler::compile_from_string(src, true)?;
+
+    let mut file = std::fs::File::create("./jit.o2")?;
+    bytecode::write_bytecode(&bytecode, &mut file)?;
+
+    Command::cargo_bin(IGNITE_BINARY)?
+        .args(["./jit.o2", "--jit=10"])
+        .assert()
+        .success()
+        .stdout("40425\n");
+
+    std::fs::remove_file("./jit.o2")?;
+
+    Ok(())
+}
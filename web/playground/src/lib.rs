@@ -0,0 +1,127 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use compiler::compiler::Compiler;
+use ignite::{run, Runtime, RuntimeErrorContext};
+use types::type_checker::TypeChecker;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// The number of instructions a program may run, so that a program that does not terminate
+/// fails instead of freezing the page.
+pub const INSTR_BUDGET: u64 = 10_000_000;
+
+/// The output of the program, shared between the runtime writing it and the caller reading it.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+/// Compile the source, formatting the errors of the phase that failed as the rustscript CLI does.
+fn compile(src: &str) -> Result<Vec<bytecode::ByteCode>, String> {
+    // The parser expects the lexer to succeed
+    let mut lexer = lexer::lex(src);
+    while let Some(tok) = lexer.next() {
+        if tok.is_err() {
+            return Err(format!(
+                "error[lex]: Unrecognized token '{}'",
+                lexer.slice()
+            ));
+        }
+    }
+
+    let program = parser::Parser::new_from_string(src)
+        .parse()
+        .map_err(|err| format!("error[parse]: {}", err.msg()))?;
+
+    TypeChecker::new(&program).type_check().map_err(|errs| {
+        errs.errs()
+            .iter()
+            .map(|err| format!("error[type]: {}", err))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
+
+    Compiler::new(program)
+        .compile()
+        .map_err(|err| format!("error[compile]: {}", err.msg()))
+}
+
+/// Compile and run the source, returning what it printed followed by its final value if it has one,
+/// or by the error it failed with.
+///
+/// The runtime keeps time on a virtual clock, which advances with the instructions executed,
+/// as there is no system clock in the browser without calling out to JavaScript.
+#[wasm_bindgen]
+pub fn run_source(src: &str) -> String {
+    let instrs = match compile(src) {
+        Ok(instrs) => instrs,
+        Err(err) => return err,
+    };
+
+    let output = Output::default();
+    let mut rt = Runtime::new(instrs);
+    rt.set_clock(ignite::VirtualClock::default());
+    rt.set_instr_budget(INSTR_BUDGET);
+    rt.set_stdout(output.clone());
+
+    let res = run(&mut rt);
+    let mut text = output.text();
+
+    match res {
+        Ok(()) if rt.exit_code.is_none() => {
+            if let Some(val) = rt.current_thread.operand_stack.last() {
+                text.push_str(&format!("{}\n", val));
+            }
+        }
+        Ok(()) => (),
+        Err(err) => {
+            text.push_str(&format!("error[runtime]: {}", err.root_cause()));
+            if let Some(ctx) = err.downcast_ref::<RuntimeErrorContext>() {
+                for line in ctx.to_string().lines() {
+                    text.push_str(&format!("\n  {}", line));
+                }
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_source() {
+        assert_eq!(run_source("println(\"hi\"); 1 + 2"), "hi\n3\n");
+        assert!(run_source("let x: int = true;").starts_with("error[type]:"));
+        assert!(run_source("let x = 1 ` 2;").starts_with("error[lex]: Unrecognized token '`'"));
+        assert!(run_source("let = 1;").starts_with("error[parse]:"));
+        assert!(run_source("print(1); loop {}").starts_with("1error[runtime]: Instruction budget"));
+    }
+
+    #[test]
+    fn test_threads() {
+        let src = r#"
+        let x = 0;
+        fn inc() { x = x + 1; }
+        let h = spawn inc();
+        join h;
+        x
+        "#;
+        assert_eq!(run_source(src), "1\n");
+    }
+}